@@ -3,6 +3,7 @@ package main
 import (
 	"context"
 	"flag"
+	"fmt"
 	"net/http"
 	"os"
 	"os/signal"
@@ -13,20 +14,14 @@ import (
 	"go.uber.org/zap/zapcore"
 	"gopkg.in/yaml.v3"
 
+	"github.com/bifrost/go-gateway/internal/forward"
 	"github.com/bifrost/go-gateway/internal/gateway"
+	"github.com/bifrost/go-gateway/internal/logging"
 )
 
 // Config represents the gateway configuration
 type Config struct {
-	Gateway struct {
-		Port           int           `yaml:"port"`
-		GRPCPort       int           `yaml:"grpc_port"`
-		MaxConnections int           `yaml:"max_connections"`
-		DataBufferSize int           `yaml:"data_buffer_size"`
-		UpdateInterval time.Duration `yaml:"update_interval"`
-		EnableMetrics  bool          `yaml:"enable_metrics"`
-		LogLevel       string        `yaml:"log_level"`
-	} `yaml:"gateway"`
+	Gateway gateway.Config `yaml:"gateway"`
 
 	Protocols struct {
 		Modbus struct {
@@ -39,6 +34,8 @@ type Config struct {
 			EnableKeepAlive   bool          `yaml:"enable_keep_alive"`
 		} `yaml:"modbus"`
 	} `yaml:"protocols"`
+
+	Logging logging.Config `yaml:"logging"`
 }
 
 func main() {
@@ -49,6 +46,7 @@ func main() {
 		port        = flag.Int("port", 8080, "HTTP server port")
 		grpcPort    = flag.Int("grpc-port", 9090, "gRPC server port")
 		healthCheck = flag.Bool("health-check", false, "Perform health check and exit")
+		unlockQueue = flag.String("unlock-queue", "", "Remove the lock a stopped gateway left on a store-and-forward queue directory and exit")
 	)
 	flag.Parse()
 
@@ -57,6 +55,15 @@ func main() {
 		os.Exit(performHealthCheck())
 	}
 
+	// Recover a queue whose lock outlived its gateway
+	if *unlockQueue != "" {
+		if err := forward.Unlock(*unlockQueue); err != nil {
+			fmt.Fprintln(os.Stderr, "Failed to unlock queue:", err)
+			os.Exit(1)
+		}
+		os.Exit(0)
+	}
+
 	// Load configuration
 	config, err := loadConfig(*configFile)
 	if err != nil {
@@ -64,18 +71,21 @@ func main() {
 	}
 
 	// Override config with command-line flags
-	if *port != 8080 {
-		config.Gateway.Port = *port
-	}
-	if *grpcPort != 9090 {
-		config.Gateway.GRPCPort = *grpcPort
-	}
-	if *logLevel != "info" {
-		config.Gateway.LogLevel = *logLevel
+	applyFlags := func(config *Config) {
+		if *port != 8080 {
+			config.Gateway.Port = *port
+		}
+		if *grpcPort != 9090 {
+			config.Gateway.GRPCPort = *grpcPort
+		}
+		if *logLevel != "info" {
+			config.Gateway.LogLevel = *logLevel
+		}
 	}
+	applyFlags(config)
 
 	// Set up logging
-	logger := setupLogger(config.Gateway.LogLevel)
+	logger := setupLogger(config.Gateway.LogLevel, config.Logging)
 	defer logger.Sync()
 
 	logger.Info("Starting Bifrost Industrial Gateway",
@@ -85,19 +95,8 @@ func main() {
 		zap.String("log_level", config.Gateway.LogLevel),
 	)
 
-	// Create gateway configuration
-	gatewayConfig := &gateway.Config{
-		Port:           config.Gateway.Port,
-		GRPCPort:       config.Gateway.GRPCPort,
-		MaxConnections: config.Gateway.MaxConnections,
-		DataBufferSize: config.Gateway.DataBufferSize,
-		UpdateInterval: config.Gateway.UpdateInterval,
-		EnableMetrics:  config.Gateway.EnableMetrics,
-		LogLevel:       config.Gateway.LogLevel,
-	}
-
 	// Create and start the gateway
-	gw := gateway.NewIndustrialGateway(gatewayConfig, logger)
+	gw := gateway.NewIndustrialGateway(newGatewayConfig(config), logger)
 
 	// Set up graceful shutdown
 	ctx, cancel := context.WithCancel(context.Background())
@@ -113,6 +112,24 @@ func main() {
 		cancel()
 	}()
 
+	// Reload the configuration file on SIGHUP without dropping data
+	reloadChan := make(chan os.Signal, 1)
+	signal.Notify(reloadChan, syscall.SIGHUP)
+
+	go func() {
+		for range reloadChan {
+			logger.Info("Received reload signal, reloading configuration", zap.String("file", *configFile))
+			reloaded, err := loadConfig(*configFile)
+			if err == nil {
+				applyFlags(reloaded)
+				err = gw.Reload(newGatewayConfig(reloaded))
+			}
+			if err != nil {
+				logger.Error("Configuration reload failed, keeping the running configuration", zap.Error(err))
+			}
+		}
+	}()
+
 	// Start the gateway
 	if err := gw.Start(ctx); err != nil {
 		logger.Error("Gateway startup failed", zap.Error(err))
@@ -122,6 +139,12 @@ func main() {
 	logger.Info("Gateway shutdown complete")
 }
 
+// newGatewayConfig creates the gateway configuration
+func newGatewayConfig(config *Config) *gateway.Config {
+	gatewayConfig := config.Gateway
+	return &gatewayConfig
+}
+
 func loadConfig(filename string) (*Config, error) {
 	// Set default configuration
 	config := &Config{}
@@ -153,7 +176,7 @@ func loadConfig(filename string) (*Config, error) {
 	return config, nil
 }
 
-func setupLogger(level string) *zap.Logger {
+func setupLogger(level string, sink logging.Config) *zap.Logger {
 	var zapLevel zapcore.Level
 	switch level {
 	case "debug":
@@ -194,7 +217,22 @@ func setupLogger(level string) *zap.Logger {
 		panic("Failed to initialize logger: " + err.Error())
 	}
 
-	return logger
+	// Route logs to journald or syslog, keeping standard output if the
+	// backend is unavailable
+	if sink.Backend == "" || sink.Backend == logging.BackendConsole {
+		return logger
+	}
+	core, err := logging.NewCore(sink, config.Level)
+	if err != nil {
+		logger.Error("Failed to initialize logging backend, logging to standard output", zap.Error(err))
+		return logger
+	}
+	return logger.WithOptions(zap.WrapCore(func(console zapcore.Core) zapcore.Core {
+		if sink.Console {
+			return zapcore.NewTee(console, core)
+		}
+		return core
+	}))
 }
 
 func performHealthCheck() int {
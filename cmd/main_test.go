@@ -0,0 +1,119 @@
+package main
+
+import (
+	"os"
+	"path/filepath"
+	"testing"
+	"time"
+
+	"github.com/stretchr/testify/assert"
+
+	"github.com/bifrost/go-gateway/internal/auth"
+)
+
+const testConfig = `
+gateway:
+  port: 8443
+  rules:
+    - id: high-level
+      condition: Level > 90
+      actions:
+        - type: event
+          event: HighLevel
+  scripts:
+    - name: scale
+      source: "def transform(samples): return samples"
+  ingest_scripts: [scale]
+  tls:
+    enabled: true
+    cert_file: /etc/bifrost/gateway.pem
+    key_file: /etc/bifrost/gateway.key
+  auth:
+    enabled: true
+    keys:
+      - name: hmi
+        key_hash: 0000000000000000000000000000000000000000000000000000000000000000
+        permissions: [read]
+        roles: [hmi]
+    roles:
+      - name: hmi
+        read:
+          - devices: "plant1/**"
+  ingest_rate_limit:
+    default:
+      points_per_second: 1000
+      bytes_per_second: 65536
+  api_rate_limit:
+    default:
+      points_per_second: 50
+  backpressure:
+    high_watermark: 0.9
+  health:
+    tcp_address: ":8081"
+  memory:
+    limit: 268435456
+  cloud_sinks:
+    - name: hub
+      provider: azure
+      azure:
+        host_name: my-hub.azure-devices.net
+      queue:
+        dir: /var/lib/bifrost/hub
+  time_sync:
+    source: chrony
+    max_offset: 50ms
+`
+
+func TestLoadConfig(t *testing.T) {
+	path := filepath.Join(t.TempDir(), "gateway.yaml")
+	assert.NoError(t, os.WriteFile(path, []byte(testConfig), 0o644))
+
+	config, err := loadConfig(path)
+	assert.NoError(t, err)
+	gatewayConfig := newGatewayConfig(config)
+
+	// Defaults of unset settings
+	assert.Equal(t, 8443, gatewayConfig.Port)
+	assert.Equal(t, 9090, gatewayConfig.GRPCPort)
+	assert.Equal(t, time.Second, gatewayConfig.UpdateInterval)
+	assert.True(t, gatewayConfig.EnableMetrics)
+
+	if assert.Len(t, gatewayConfig.Rules, 1) {
+		assert.Equal(t, "HighLevel", gatewayConfig.Rules[0].Actions[0].Event)
+	}
+	if assert.Len(t, gatewayConfig.Scripts, 1) {
+		assert.Equal(t, "scale", gatewayConfig.Scripts[0].Name)
+	}
+	assert.Equal(t, []string{"scale"}, gatewayConfig.IngestScripts)
+	assert.True(t, gatewayConfig.TLS.Enabled)
+	assert.True(t, gatewayConfig.Auth.Enabled)
+	if assert.Len(t, gatewayConfig.Auth.Keys, 1) {
+		assert.Equal(t, []auth.Permission{auth.PermissionRead}, gatewayConfig.Auth.Keys[0].Permissions)
+		assert.Equal(t, []string{"hmi"}, gatewayConfig.Auth.Keys[0].Roles)
+	}
+	if assert.Len(t, gatewayConfig.Auth.Roles, 1) {
+		assert.Equal(t, []auth.TagPattern{{Devices: "plant1/**"}}, gatewayConfig.Auth.Roles[0].Read)
+	}
+	assert.Equal(t, 65536.0, gatewayConfig.IngestRateLimit.Default.BytesPerSecond)
+	assert.Equal(t, 50.0, gatewayConfig.APIRateLimit.Default.PointsPerSecond)
+	assert.Equal(t, 0.9, gatewayConfig.Backpressure.HighWatermark)
+	assert.Equal(t, ":8081", gatewayConfig.Health.TCPAddress)
+	assert.Equal(t, int64(256<<20), gatewayConfig.Memory.Limit)
+	if assert.Len(t, gatewayConfig.CloudSinks, 1) {
+		assert.Equal(t, "my-hub.azure-devices.net", gatewayConfig.CloudSinks[0].Azure.HostName)
+		assert.Equal(t, "/var/lib/bifrost/hub", gatewayConfig.CloudSinks[0].Queue.Dir)
+	}
+	assert.Equal(t, "chrony", gatewayConfig.TimeSync.Source)
+	assert.Equal(t, 50*time.Millisecond, gatewayConfig.TimeSync.MaxOffset)
+
+	// Each call returns a copy the gateway may keep
+	gatewayConfig.Port = 1
+	assert.Equal(t, 8443, newGatewayConfig(config).Port)
+}
+
+func TestLoadConfig_Invalid(t *testing.T) {
+	path := filepath.Join(t.TempDir(), "gateway.yaml")
+	assert.NoError(t, os.WriteFile(path, []byte("gateway:\n  rules: {}\n"), 0o644))
+	_, err := loadConfig(path)
+	assert.Error(t, err)
+}
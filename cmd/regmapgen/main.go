@@ -0,0 +1,49 @@
+// Command regmapgen generates a Go tag map from a register map exported as
+// CSV or JSON, for use with go:generate:
+//
+//	//go:generate regmapgen -in boiler.csv -out boiler_tags.go -package boiler
+package main
+
+import (
+	"flag"
+	"fmt"
+	"os"
+
+	"github.com/bifrost/go-gateway/internal/protocols"
+)
+
+func main() {
+	var (
+		input       = flag.String("in", "", "Register map file (.csv or .json)")
+		output      = flag.String("out", "", "Generated Go file (default stdout)")
+		packageName = flag.String("package", "main", "Package of the generated file")
+		funcName    = flag.String("func", "NewTagMap", "Name of the generated constructor")
+	)
+	flag.Parse()
+
+	if *input == "" {
+		fmt.Fprintln(os.Stderr, "regmapgen: -in is required")
+		os.Exit(2)
+	}
+
+	tags, err := protocols.LoadRegisterMap(*input)
+	if err != nil {
+		fmt.Fprintf(os.Stderr, "regmapgen: %v\n", err)
+		os.Exit(1)
+	}
+
+	source, err := protocols.GenerateTagMapSource(tags, *packageName, *funcName, *input)
+	if err != nil {
+		fmt.Fprintf(os.Stderr, "regmapgen: %v\n", err)
+		os.Exit(1)
+	}
+
+	if *output == "" {
+		os.Stdout.Write(source)
+		return
+	}
+	if err := os.WriteFile(*output, source, 0644); err != nil {
+		fmt.Fprintf(os.Stderr, "regmapgen: %v\n", err)
+		os.Exit(1)
+	}
+}
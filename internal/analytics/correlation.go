@@ -0,0 +1,168 @@
+package analytics
+
+import (
+	"fmt"
+	"math"
+	"time"
+)
+
+// Cross-Correlation
+//
+// CrossCorrelation finds delays between cause and effect, e.g. a pressure
+// that rises 30s after a valve command: both series are resampled onto a
+// common grid over the time they overlap and correlated at every lag up to
+// a maximum. A positive lag means the second series follows the first.
+
+// Point is a timestamped value of a series
+type Point struct {
+	Time  time.Time `json:"time"`
+	Value float64   `json:"value"`
+}
+
+// Correlation is the lagged cross-correlation of two series
+type Correlation struct {
+	Interval     time.Duration `json:"interval"`
+	MaxLag       time.Duration `json:"max_lag"`
+	Coefficients []float64     `json:"coefficients"` // Lag -MaxLag to MaxLag in steps of Interval
+	Lag          time.Duration `json:"lag"`          // Lag of the strongest correlation
+	Coefficient  float64       `json:"coefficient"`
+}
+
+// At returns the coefficient at a lag, rounded to the interval
+func (c *Correlation) At(lag time.Duration) float64 {
+	index := int(math.Round(float64(lag+c.MaxLag) / float64(c.Interval)))
+	if index < 0 || index >= len(c.Coefficients) {
+		return 0
+	}
+	return c.Coefficients[index]
+}
+
+// CrossCorrelation correlates two series sorted by time at lags from
+// -maxLag to maxLag. The series are resampled at interval over the time
+// both cover. Lags without at least two pairs of values, or where either
+// series is constant, have coefficient 0.
+func CrossCorrelation(a, b []Point, interval, maxLag time.Duration) (*Correlation, error) {
+	if len(a) == 0 || len(b) == 0 {
+		return nil, fmt.Errorf("both series need values")
+	}
+	if interval <= 0 {
+		return nil, fmt.Errorf("interval must be positive")
+	}
+	if maxLag < 0 {
+		return nil, fmt.Errorf("maximum lag must not be negative")
+	}
+
+	start, end := a[0].Time, a[len(a)-1].Time
+	if b[0].Time.After(start) {
+		start = b[0].Time
+	}
+	if b[len(b)-1].Time.Before(end) {
+		end = b[len(b)-1].Time
+	}
+	if !end.After(start) {
+		return nil, fmt.Errorf("series do not overlap")
+	}
+
+	count := int(end.Sub(start)/interval) + 1
+	lags := int(maxLag / interval)
+	coefficients := CrossCorrelate(Resample(a, start, interval, count), Resample(b, start, interval, count), lags)
+
+	result := &Correlation{
+		Interval:     interval,
+		MaxLag:       time.Duration(lags) * interval,
+		Coefficients: coefficients,
+	}
+	for i, coefficient := range coefficients {
+		if math.IsNaN(coefficient) {
+			coefficients[i] = 0
+			continue
+		}
+		if math.Abs(coefficient) > math.Abs(result.Coefficient) {
+			result.Coefficient = coefficient
+			result.Lag = time.Duration(i-lags) * interval
+		}
+	}
+	return result, nil
+}
+
+// CrossCorrelate returns the Pearson correlation of x[i] and y[i+lag] for
+// lags from -maxLag to maxLag, at index lag+maxLag. NaN values are skipped;
+// lags without two pairs of values or with a constant series are NaN.
+func CrossCorrelate(x, y []float64, maxLag int) []float64 {
+	coefficients := make([]float64, 2*maxLag+1)
+	for lag := -maxLag; lag <= maxLag; lag++ {
+		coefficients[lag+maxLag] = laggedPearson(x, y, lag)
+	}
+	return coefficients
+}
+
+func laggedPearson(x, y []float64, lag int) float64 {
+	start, end := 0, len(x)
+	if lag < 0 {
+		start = -lag
+	}
+	if len(y)-lag < end {
+		end = len(y) - lag
+	}
+
+	var count int
+	var meanX, meanY float64
+	for i := start; i < end; i++ {
+		if math.IsNaN(x[i]) || math.IsNaN(y[i+lag]) {
+			continue
+		}
+		count++
+		meanX += x[i]
+		meanY += y[i+lag]
+	}
+	if count < 2 {
+		return math.NaN()
+	}
+	meanX /= float64(count)
+	meanY /= float64(count)
+
+	var covariance, varianceX, varianceY float64
+	for i := start; i < end; i++ {
+		if math.IsNaN(x[i]) || math.IsNaN(y[i+lag]) {
+			continue
+		}
+		dx, dy := x[i]-meanX, y[i+lag]-meanY
+		covariance += dx * dy
+		varianceX += dx * dx
+		varianceY += dy * dy
+	}
+	if varianceX == 0 || varianceY == 0 {
+		return math.NaN()
+	}
+	return covariance / math.Sqrt(varianceX*varianceY)
+}
+
+// Resample returns count values of a series sorted by time at start,
+// start+interval and so on, interpolated linearly between the points
+// around them. Values outside the series are NaN.
+func Resample(series []Point, start time.Time, interval time.Duration, count int) []float64 {
+	values := make([]float64, count)
+	j := 0
+	for i := range values {
+		at := start.Add(time.Duration(i) * interval)
+		for j+1 < len(series) && !series[j+1].Time.After(at) {
+			j++
+		}
+
+		switch {
+		case len(series) == 0 || at.Before(series[0].Time):
+			values[i] = math.NaN()
+		case j+1 == len(series):
+			if at.Equal(series[j].Time) {
+				values[i] = series[j].Value
+			} else {
+				values[i] = math.NaN()
+			}
+		default:
+			before, after := series[j], series[j+1]
+			fraction := float64(at.Sub(before.Time)) / float64(after.Time.Sub(before.Time))
+			values[i] = before.Value + fraction*(after.Value-before.Value)
+		}
+	}
+	return values
+}
@@ -0,0 +1,84 @@
+package analytics
+
+import (
+	"math"
+	"math/rand"
+	"testing"
+	"time"
+
+	"github.com/stretchr/testify/assert"
+)
+
+func TestCrossCorrelation_Delay(t *testing.T) {
+	random := rand.New(rand.NewSource(1))
+	start := time.Date(2024, 1, 1, 0, 0, 0, 0, time.UTC)
+
+	// The valve opens and closes at random; pressure follows 30s later.
+	// Pressure is sampled every 2s, the valve state every 5s.
+	states := make([]float64, 200)
+	for i := range states {
+		states[i] = float64(random.Intn(2))
+	}
+	valveAt := func(seconds int) float64 {
+		return states[seconds/20]
+	}
+	var valve, pressure []Point
+	for seconds := 0; seconds <= 3600; seconds += 5 {
+		valve = append(valve, Point{Time: start.Add(time.Duration(seconds) * time.Second), Value: valveAt(seconds)})
+	}
+	for seconds := 10; seconds <= 3610; seconds += 2 {
+		value := 2.0
+		if seconds >= 30 {
+			value += 3 * valveAt(seconds-30)
+		}
+		pressure = append(pressure, Point{Time: start.Add(time.Duration(seconds) * time.Second), Value: value + random.NormFloat64()*0.2})
+	}
+
+	correlation, err := CrossCorrelation(valve, pressure, 5*time.Second, 2*time.Minute)
+	assert.NoError(t, err)
+	assert.Equal(t, 30*time.Second, correlation.Lag)
+	assert.Greater(t, correlation.Coefficient, 0.9)
+	assert.Len(t, correlation.Coefficients, 49)
+	assert.Equal(t, correlation.Coefficient, correlation.At(30*time.Second))
+	assert.Less(t, math.Abs(correlation.At(-30*time.Second)), 0.5)
+	assert.Equal(t, 0.0, correlation.At(time.Hour))
+}
+
+func TestCrossCorrelation_Errors(t *testing.T) {
+	start := time.Date(2024, 1, 1, 0, 0, 0, 0, time.UTC)
+	early := []Point{{Time: start, Value: 1}, {Time: start.Add(time.Minute), Value: 2}}
+	late := []Point{{Time: start.Add(time.Hour), Value: 1}, {Time: start.Add(2 * time.Hour), Value: 2}}
+
+	_, err := CrossCorrelation(early, late, time.Second, time.Minute)
+	assert.Error(t, err)
+	_, err = CrossCorrelation(early, nil, time.Second, time.Minute)
+	assert.Error(t, err)
+	_, err = CrossCorrelation(early, early, 0, time.Minute)
+	assert.Error(t, err)
+}
+
+func TestCrossCorrelate(t *testing.T) {
+	x := []float64{1, 2, 3, 4, 5, 4, 3, 2}
+	y := []float64{0, 1, 2, 3, 4, 5, 4, 3}
+
+	coefficients := CrossCorrelate(x, y, 2)
+	assert.Len(t, coefficients, 5)
+	assert.InDelta(t, 1.0, coefficients[3], 1e-12) // y lags x by one sample
+
+	constant := CrossCorrelate(x, []float64{1, 1, 1, 1, 1, 1, 1, 1}, 0)
+	assert.True(t, math.IsNaN(constant[0]))
+}
+
+func TestResample(t *testing.T) {
+	start := time.Date(2024, 1, 1, 0, 0, 0, 0, time.UTC)
+	series := []Point{
+		{Time: start, Value: 0},
+		{Time: start.Add(10 * time.Second), Value: 10},
+		{Time: start.Add(20 * time.Second), Value: 0},
+	}
+
+	values := Resample(series, start.Add(-5*time.Second), 5*time.Second, 7)
+	assert.True(t, math.IsNaN(values[0]))
+	assert.Equal(t, []float64{0, 5, 10, 5, 0}, values[1:6])
+	assert.True(t, math.IsNaN(values[6]))
+}
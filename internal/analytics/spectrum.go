@@ -0,0 +1,220 @@
+package analytics
+
+import (
+	"fmt"
+	"math"
+	"math/cmplx"
+	"time"
+)
+
+// Spectral Analysis
+//
+// Spectrum and PSD give the frequency content of uniformly sampled values,
+// e.g. vibration measurements: Spectrum the single-sided amplitude
+// spectrum of one windowed FFT, PSD the power spectral density averaged
+// over half-overlapping segments (Welch's method). Series are resampled
+// onto a uniform grid first.
+
+// Window is a window function applied before the FFT
+type Window string
+
+const (
+	WindowRectangular Window = "rectangular"
+	WindowHann        Window = "hann"
+	WindowHamming     Window = "hamming"
+)
+
+// Coefficients returns the periodic window of length n
+func (w Window) Coefficients(n int) ([]float64, error) {
+	coefficients := make([]float64, n)
+	for i := range coefficients {
+		phase := 2 * math.Pi * float64(i) / float64(n)
+		switch w {
+		case WindowRectangular, "":
+			coefficients[i] = 1
+		case WindowHann:
+			coefficients[i] = 0.5 - 0.5*math.Cos(phase)
+		case WindowHamming:
+			coefficients[i] = 0.54 - 0.46*math.Cos(phase)
+		default:
+			return nil, fmt.Errorf("unknown window: %s", w)
+		}
+	}
+	return coefficients, nil
+}
+
+// SpectrumBin is the magnitude of one frequency
+type SpectrumBin struct {
+	Frequency float64 `json:"frequency"` // Hz
+	Magnitude float64 `json:"magnitude"`
+}
+
+// FFT returns the discrete Fourier transform of values. The length of
+// values must be a power of two.
+func FFT(values []complex128) ([]complex128, error) {
+	n := len(values)
+	if n == 0 || n&(n-1) != 0 {
+		return nil, fmt.Errorf("FFT length %d is not a power of two", n)
+	}
+
+	result := make([]complex128, n)
+	copy(result, values)
+
+	// Reorder by bit-reversed index, then combine butterflies of doubling size
+	for i, j := 1, 0; i < n; i++ {
+		bit := n >> 1
+		for ; j&bit != 0; bit >>= 1 {
+			j ^= bit
+		}
+		j ^= bit
+		if i < j {
+			result[i], result[j] = result[j], result[i]
+		}
+	}
+	for size := 2; size <= n; size <<= 1 {
+		half := size / 2
+		for k := 0; k < half; k++ {
+			twiddle := cmplx.Rect(1, -2*math.Pi*float64(k)/float64(size))
+			for start := 0; start < n; start += size {
+				even, odd := result[start+k], result[start+k+half]*twiddle
+				result[start+k] = even + odd
+				result[start+k+half] = even - odd
+			}
+		}
+	}
+	return result, nil
+}
+
+// Spectrum returns the single-sided amplitude spectrum of values sampled
+// at sampleRate Hz: a sine of amplitude A shows as a bin of magnitude A.
+// Values are zero-padded to a power of two.
+func Spectrum(values []float64, sampleRate float64, window Window) ([]SpectrumBin, error) {
+	if len(values) < 2 {
+		return nil, fmt.Errorf("spectrum needs at least two values")
+	}
+	if sampleRate <= 0 {
+		return nil, fmt.Errorf("sample rate must be positive")
+	}
+	coefficients, err := window.Coefficients(len(values))
+	if err != nil {
+		return nil, err
+	}
+
+	size := 1
+	for size < len(values) {
+		size <<= 1
+	}
+	input := make([]complex128, size)
+	var gain float64
+	for i, value := range values {
+		input[i] = complex(value*coefficients[i], 0)
+		gain += coefficients[i]
+	}
+	output, err := FFT(input)
+	if err != nil {
+		return nil, err
+	}
+
+	bins := make([]SpectrumBin, size/2+1)
+	for k := range bins {
+		magnitude := cmplx.Abs(output[k]) / gain
+		if k != 0 && k != size/2 {
+			magnitude *= 2
+		}
+		bins[k] = SpectrumBin{Frequency: float64(k) * sampleRate / float64(size), Magnitude: magnitude}
+	}
+	return bins, nil
+}
+
+// PSD returns the single-sided power spectral density of values sampled at
+// sampleRate Hz in units²/Hz, averaged over segments of segmentSize values
+// overlapping by half. segmentSize must be a power of two; 0 uses the
+// largest power of two that fits the values.
+func PSD(values []float64, sampleRate float64, window Window, segmentSize int) ([]SpectrumBin, error) {
+	if sampleRate <= 0 {
+		return nil, fmt.Errorf("sample rate must be positive")
+	}
+	if segmentSize == 0 {
+		segmentSize = 1
+		for segmentSize*2 <= len(values) {
+			segmentSize <<= 1
+		}
+	}
+	if segmentSize < 2 || segmentSize&(segmentSize-1) != 0 {
+		return nil, fmt.Errorf("segment size %d is not a power of two", segmentSize)
+	}
+	if segmentSize > len(values) {
+		return nil, fmt.Errorf("segment size %d exceeds %d values", segmentSize, len(values))
+	}
+	coefficients, err := window.Coefficients(segmentSize)
+	if err != nil {
+		return nil, err
+	}
+
+	var power float64
+	for _, coefficient := range coefficients {
+		power += coefficient * coefficient
+	}
+
+	density := make([]float64, segmentSize/2+1)
+	segments := 0
+	input := make([]complex128, segmentSize)
+	for start := 0; start+segmentSize <= len(values); start += segmentSize / 2 {
+		for i := range input {
+			input[i] = complex(values[start+i]*coefficients[i], 0)
+		}
+		output, err := FFT(input)
+		if err != nil {
+			return nil, err
+		}
+		for k := range density {
+			magnitude := cmplx.Abs(output[k])
+			density[k] += magnitude * magnitude
+		}
+		segments++
+	}
+
+	scale := 1 / (sampleRate * power * float64(segments))
+	bins := make([]SpectrumBin, len(density))
+	for k, value := range density {
+		value *= scale
+		if k != 0 && k != segmentSize/2 {
+			value *= 2
+		}
+		bins[k] = SpectrumBin{Frequency: float64(k) * sampleRate / float64(segmentSize), Magnitude: value}
+	}
+	return bins, nil
+}
+
+// SeriesSpectrum resamples a series sorted by time at interval and returns
+// its amplitude spectrum
+func SeriesSpectrum(series []Point, interval time.Duration, window Window) ([]SpectrumBin, error) {
+	values, err := resampleSeries(series, interval)
+	if err != nil {
+		return nil, err
+	}
+	return Spectrum(values, 1/interval.Seconds(), window)
+}
+
+// SeriesPSD resamples a series sorted by time at interval and returns its
+// power spectral density
+func SeriesPSD(series []Point, interval time.Duration, window Window, segmentSize int) ([]SpectrumBin, error) {
+	values, err := resampleSeries(series, interval)
+	if err != nil {
+		return nil, err
+	}
+	return PSD(values, 1/interval.Seconds(), window, segmentSize)
+}
+
+// resampleSeries resamples a series over the time it covers
+func resampleSeries(series []Point, interval time.Duration) ([]float64, error) {
+	if len(series) < 2 {
+		return nil, fmt.Errorf("series needs at least two values")
+	}
+	if interval <= 0 {
+		return nil, fmt.Errorf("interval must be positive")
+	}
+	start := series[0].Time
+	count := int(series[len(series)-1].Time.Sub(start)/interval) + 1
+	return Resample(series, start, interval, count), nil
+}
@@ -0,0 +1,105 @@
+package analytics
+
+import (
+	"math"
+	"math/cmplx"
+	"testing"
+	"time"
+
+	"github.com/stretchr/testify/assert"
+)
+
+// sine returns count samples at sampleRate of offset + amplitude*sin(2πft)
+func sine(count int, sampleRate, frequency, amplitude, offset float64) []float64 {
+	values := make([]float64, count)
+	for i := range values {
+		values[i] = offset + amplitude*math.Sin(2*math.Pi*frequency*float64(i)/sampleRate)
+	}
+	return values
+}
+
+func TestFFT(t *testing.T) {
+	output, err := FFT([]complex128{1, 2, 3, 4})
+	assert.NoError(t, err)
+	expected := []complex128{10, complex(-2, 2), -2, complex(-2, -2)}
+	for i := range expected {
+		assert.InDelta(t, 0, cmplx.Abs(output[i]-expected[i]), 1e-12)
+	}
+
+	_, err = FFT(make([]complex128, 6))
+	assert.Error(t, err)
+}
+
+func TestSpectrum(t *testing.T) {
+	values := sine(1024, 1024, 50, 2, 1)
+
+	for _, window := range []Window{WindowRectangular, WindowHann, WindowHamming} {
+		t.Run(string(window), func(t *testing.T) {
+			bins, err := Spectrum(values, 1024, window)
+			assert.NoError(t, err)
+			assert.Len(t, bins, 513)
+			assert.Equal(t, 50.0, bins[50].Frequency)
+			assert.InDelta(t, 2.0, bins[50].Magnitude, 1e-9)
+			assert.InDelta(t, 1.0, bins[0].Magnitude, 1e-9)
+			assert.InDelta(t, 0, bins[200].Magnitude, 1e-9)
+		})
+	}
+
+	_, err := Spectrum(values, 1024, Window("blackman"))
+	assert.Error(t, err)
+	_, err = Spectrum(values[:1], 1024, WindowHann)
+	assert.Error(t, err)
+}
+
+func TestPSD(t *testing.T) {
+	values := sine(4096, 1024, 50, 2, 0)
+
+	bins, err := PSD(values, 1024, WindowHann, 256)
+	assert.NoError(t, err)
+	assert.Len(t, bins, 129)
+
+	// The density integrates to the power of the sine, A²/2
+	var total float64
+	peak := bins[0]
+	for _, bin := range bins {
+		total += bin.Magnitude * 4 // 4 Hz bins
+		if bin.Magnitude > peak.Magnitude {
+			peak = bin
+		}
+	}
+	assert.InDelta(t, 2.0, total, 1e-9)
+	assert.InDelta(t, 50, peak.Frequency, 4)
+
+	bins, err = PSD(values[:3000], 1024, WindowHann, 0)
+	assert.NoError(t, err)
+	assert.Len(t, bins, 1025)
+
+	_, err = PSD(values, 1024, WindowHann, 100)
+	assert.Error(t, err)
+	_, err = PSD(values[:100], 1024, WindowHann, 128)
+	assert.Error(t, err)
+}
+
+func TestSeriesSpectrum(t *testing.T) {
+	// 1 kHz vibration sampled at 10 kHz, between two FFT bins
+	start := time.Date(2024, 1, 1, 0, 0, 0, 0, time.UTC)
+	values := sine(2048, 10000, 1000, 0.5, 0)
+	series := make([]Point, len(values))
+	for i, value := range values {
+		series[i] = Point{Time: start.Add(time.Duration(i) * 100 * time.Microsecond), Value: value}
+	}
+
+	bins, err := SeriesSpectrum(series, 100*time.Microsecond, WindowHann)
+	assert.NoError(t, err)
+	peak := bins[0]
+	for _, bin := range bins {
+		if bin.Magnitude > peak.Magnitude {
+			peak = bin
+		}
+	}
+	assert.InDelta(t, 1000, peak.Frequency, 10000.0/2048)
+	assert.InDelta(t, 0.5, peak.Magnitude, 0.1)
+
+	_, err = SeriesPSD(series[:1], 100*time.Microsecond, WindowHann, 0)
+	assert.Error(t, err)
+}
@@ -0,0 +1,254 @@
+// Package analytics processes sensor value streams at the edge
+package analytics
+
+import (
+	"fmt"
+	"math"
+	"sort"
+)
+
+// Streaming Statistics
+//
+// Stats summarises an unbounded stream in constant memory: count, mean and
+// variance with Welford's algorithm, exact minimum and maximum, and
+// quantiles estimated with the P² algorithm of Jain and Chlamtac, which
+// keeps five markers per quantile instead of the samples.
+
+// Stats accumulates statistics of a stream of values. It is not safe for
+// concurrent use.
+type Stats struct {
+	count uint64
+	mean  float64
+	m2    float64 // Sum of squared differences from the mean
+	min   float64
+	max   float64
+
+	quantiles []*P2Quantile
+}
+
+// Summary is a snapshot of Stats
+type Summary struct {
+	Count     uint64             `json:"count"`
+	Mean      float64            `json:"mean"`
+	Variance  float64            `json:"variance"`
+	StdDev    float64            `json:"std_dev"`
+	Min       float64            `json:"min"`
+	Max       float64            `json:"max"`
+	Quantiles map[string]float64 `json:"quantiles,omitempty"`
+}
+
+// NewStats creates an accumulator estimating the given quantiles, e.g. 0.5
+// for the median. It panics if a quantile is not between 0 and 1.
+func NewStats(quantiles ...float64) *Stats {
+	s := &Stats{}
+	for _, p := range quantiles {
+		s.quantiles = append(s.quantiles, NewP2Quantile(p))
+	}
+	return s
+}
+
+// Add adds a value. NaN values are ignored.
+func (s *Stats) Add(value float64) {
+	if math.IsNaN(value) {
+		return
+	}
+
+	s.count++
+	if s.count == 1 {
+		s.min, s.max = value, value
+	} else if value < s.min {
+		s.min = value
+	} else if value > s.max {
+		s.max = value
+	}
+
+	delta := value - s.mean
+	s.mean += delta / float64(s.count)
+	s.m2 += delta * (value - s.mean)
+
+	for _, quantile := range s.quantiles {
+		quantile.Add(value)
+	}
+}
+
+// Count returns the number of values added
+func (s *Stats) Count() uint64 {
+	return s.count
+}
+
+// Mean returns the mean, 0 without values
+func (s *Stats) Mean() float64 {
+	return s.mean
+}
+
+// Variance returns the sample variance, 0 for fewer than two values
+func (s *Stats) Variance() float64 {
+	if s.count < 2 {
+		return 0
+	}
+	return s.m2 / float64(s.count-1)
+}
+
+// PopulationVariance returns the population variance
+func (s *Stats) PopulationVariance() float64 {
+	if s.count == 0 {
+		return 0
+	}
+	return s.m2 / float64(s.count)
+}
+
+// StdDev returns the sample standard deviation
+func (s *Stats) StdDev() float64 {
+	return math.Sqrt(s.Variance())
+}
+
+// Min returns the smallest value, 0 without values
+func (s *Stats) Min() float64 {
+	return s.min
+}
+
+// Max returns the largest value, 0 without values
+func (s *Stats) Max() float64 {
+	return s.max
+}
+
+// Quantile returns the estimate of a quantile passed to NewStats
+func (s *Stats) Quantile(p float64) (float64, bool) {
+	for _, quantile := range s.quantiles {
+		if quantile.p == p {
+			return quantile.Value(), true
+		}
+	}
+	return 0, false
+}
+
+// Reset removes all values
+func (s *Stats) Reset() {
+	quantiles := s.quantiles
+	*s = Stats{quantiles: quantiles}
+	for i, quantile := range quantiles {
+		quantiles[i] = NewP2Quantile(quantile.p)
+	}
+}
+
+// Summary returns the statistics; quantiles are keyed "p50", "p99.9" etc.
+func (s *Stats) Summary() Summary {
+	summary := Summary{
+		Count:    s.count,
+		Mean:     s.mean,
+		Variance: s.Variance(),
+		StdDev:   s.StdDev(),
+		Min:      s.min,
+		Max:      s.max,
+	}
+	if len(s.quantiles) > 0 {
+		summary.Quantiles = make(map[string]float64, len(s.quantiles))
+		for _, quantile := range s.quantiles {
+			summary.Quantiles[fmt.Sprintf("p%g", quantile.p*100)] = quantile.Value()
+		}
+	}
+	return summary
+}
+
+// P2Quantile estimates one quantile of a stream with the P² algorithm. The
+// estimate is exact for up to five values.
+type P2Quantile struct {
+	p     float64
+	count int
+
+	heights   [5]float64 // Marker heights, the first five values until count is 5
+	positions [5]float64 // Actual marker positions
+	desired   [5]float64 // Desired marker positions
+	increment [5]float64 // Desired position increment per value
+}
+
+// NewP2Quantile creates an estimator of quantile p. It panics if p is not
+// between 0 and 1.
+func NewP2Quantile(p float64) *P2Quantile {
+	if p < 0 || p > 1 || math.IsNaN(p) {
+		panic(fmt.Sprintf("analytics: quantile %g out of range [0, 1]", p))
+	}
+	return &P2Quantile{
+		p:         p,
+		positions: [5]float64{0, 1, 2, 3, 4},
+		desired:   [5]float64{0, 2 * p, 4 * p, 2 + 2*p, 4},
+		increment: [5]float64{0, p / 2, p, (1 + p) / 2, 1},
+	}
+}
+
+// Add adds a value
+func (q *P2Quantile) Add(value float64) {
+	if q.count < 5 {
+		q.heights[q.count] = value
+		q.count++
+		if q.count == 5 {
+			sort.Float64s(q.heights[:])
+		}
+		return
+	}
+	q.count++
+
+	// Find the cell of the value, extending the extreme markers
+	var cell int
+	switch {
+	case value < q.heights[0]:
+		q.heights[0] = value
+		cell = 0
+	case value >= q.heights[4]:
+		q.heights[4] = value
+		cell = 3
+	default:
+		for value >= q.heights[cell+1] {
+			cell++
+		}
+	}
+
+	for i := cell + 1; i < 5; i++ {
+		q.positions[i]++
+	}
+	for i := range q.desired {
+		q.desired[i] += q.increment[i]
+	}
+
+	// Move the middle markers that are off their desired position
+	for i := 1; i < 4; i++ {
+		offset := q.desired[i] - q.positions[i]
+		if (offset >= 1 && q.positions[i+1]-q.positions[i] > 1) || (offset <= -1 && q.positions[i-1]-q.positions[i] < -1) {
+			step := math.Copysign(1, offset)
+			height := q.parabolic(i, step)
+			if height <= q.heights[i-1] || height >= q.heights[i+1] {
+				height = q.linear(i, step)
+			}
+			q.heights[i] = height
+			q.positions[i] += step
+		}
+	}
+}
+
+// Value returns the estimate, 0 without values
+func (q *P2Quantile) Value() float64 {
+	if q.count == 0 {
+		return 0
+	}
+	if q.count < 5 {
+		values := make([]float64, q.count)
+		copy(values, q.heights[:q.count])
+		sort.Float64s(values)
+		return values[int(math.Round(q.p*float64(q.count-1)))]
+	}
+	return q.heights[2]
+}
+
+// parabolic is the piecewise-parabolic prediction of marker i moved by step
+func (q *P2Quantile) parabolic(i int, step float64) float64 {
+	n, h := q.positions, q.heights
+	above := (n[i] - n[i-1] + step) * (h[i+1] - h[i]) / (n[i+1] - n[i])
+	below := (n[i+1] - n[i] - step) * (h[i] - h[i-1]) / (n[i] - n[i-1])
+	return h[i] + step/(n[i+1]-n[i-1])*(above+below)
+}
+
+// linear is the linear prediction of marker i moved by step
+func (q *P2Quantile) linear(i int, step float64) float64 {
+	j := i + int(step)
+	return q.heights[i] + step*(q.heights[j]-q.heights[i])/(q.positions[j]-q.positions[i])
+}
@@ -0,0 +1,76 @@
+package analytics
+
+import (
+	"math"
+	"math/rand"
+	"sort"
+	"testing"
+
+	"github.com/stretchr/testify/assert"
+)
+
+func TestStats_Moments(t *testing.T) {
+	stats := NewStats()
+	for _, value := range []float64{2, 4, 4, 4, 5, 5, 7, 9, math.NaN()} {
+		stats.Add(value)
+	}
+
+	assert.Equal(t, uint64(8), stats.Count())
+	assert.InDelta(t, 5.0, stats.Mean(), 1e-12)
+	assert.InDelta(t, 4.0, stats.PopulationVariance(), 1e-12)
+	assert.InDelta(t, 32.0/7, stats.Variance(), 1e-12)
+	assert.Equal(t, 2.0, stats.Min())
+	assert.Equal(t, 9.0, stats.Max())
+
+	_, ok := stats.Quantile(0.5)
+	assert.False(t, ok)
+
+	stats.Reset()
+	assert.Equal(t, uint64(0), stats.Count())
+	assert.Equal(t, 0.0, stats.Variance())
+}
+
+func TestStats_LargeOffset(t *testing.T) {
+	// Welford's update keeps precision where sum of squares would not
+	stats := NewStats()
+	for _, value := range []float64{1e9 + 4, 1e9 + 7, 1e9 + 13, 1e9 + 16} {
+		stats.Add(value)
+	}
+	assert.InDelta(t, 30.0, stats.Variance(), 1e-6)
+}
+
+func TestStats_Quantiles(t *testing.T) {
+	random := rand.New(rand.NewSource(1))
+	stats := NewStats(0.5, 0.9, 0.99)
+	values := make([]float64, 0, 100000)
+	for i := 0; i < cap(values); i++ {
+		value := random.NormFloat64()*10 + 50
+		values = append(values, value)
+		stats.Add(value)
+	}
+	sort.Float64s(values)
+
+	for _, p := range []float64{0.5, 0.9, 0.99} {
+		estimate, ok := stats.Quantile(p)
+		assert.True(t, ok)
+		assert.InDelta(t, values[int(p*float64(len(values)))], estimate, 0.5, "p%g", p*100)
+	}
+
+	summary := stats.Summary()
+	assert.Equal(t, uint64(len(values)), summary.Count)
+	assert.InDelta(t, 10.0, summary.StdDev, 0.1)
+	assert.Contains(t, summary.Quantiles, "p50")
+	assert.Contains(t, summary.Quantiles, "p99")
+}
+
+func TestP2Quantile_FewValues(t *testing.T) {
+	median := NewP2Quantile(0.5)
+	assert.Equal(t, 0.0, median.Value())
+
+	for _, value := range []float64{9, 1, 5} {
+		median.Add(value)
+	}
+	assert.Equal(t, 5.0, median.Value())
+
+	assert.Panics(t, func() { NewP2Quantile(1.5) })
+}
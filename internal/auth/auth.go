@@ -0,0 +1,363 @@
+// Package auth authenticates clients of the gateway's network services
+package auth
+
+import (
+	"context"
+	"crypto/hmac"
+	"crypto/sha256"
+	"encoding/base64"
+	"encoding/hex"
+	"encoding/json"
+	"errors"
+	"fmt"
+	"net/http"
+	"strings"
+	"time"
+)
+
+// Authentication
+//
+// Clients of the REST, WebSocket and gRPC APIs authenticate with one of
+//
+//   - a static API key, sent as "Authorization: Bearer <key>" or in the
+//     X-API-Key header. Keys are configured in plain text or, preferably,
+//     as the hex SHA-256 hash of the key.
+//   - a token issued by the gateway, sent like a key. A token is an
+//     HMAC-SHA256 signed claim of a subject, its permissions and an
+//     expiry, so it can be handed to a browser or script without sharing
+//     a key.
+//   - a client certificate verified by the TLS server, mapped to
+//     permissions by its common name.
+//
+// Keys, tokens and certificates may further be limited to the tags of
+// their roles.
+//
+// Browsers cannot set headers on WebSocket requests, so a credential may
+// also be passed as the access_token query parameter.
+
+// Permission is an operation class a client may perform
+type Permission string
+
+const (
+	PermissionRead  Permission = "read"  // Read devices, tags, rules and live data
+	PermissionWrite Permission = "write" // Write tags, change rules, run discovery
+)
+
+// Authentication methods of an identity
+const (
+	MethodKey         = "key"
+	MethodToken       = "token"
+	MethodCertificate = "certificate"
+)
+
+// ErrUnauthenticated is returned when a request carries no valid credential
+var ErrUnauthenticated = errors.New("unauthenticated")
+
+// KeyConfig configures a static API key
+type KeyConfig struct {
+	Name        string       `yaml:"name"`
+	Key         string       `yaml:"key"`      // The key itself, or
+	KeyHash     string       `yaml:"key_hash"` // its hex SHA-256 hash
+	Permissions []Permission `yaml:"permissions"`
+	Roles       []string     `yaml:"roles"`
+}
+
+// CertificateConfig maps a client certificate to permissions
+type CertificateConfig struct {
+	CommonName  string       `yaml:"common_name"`
+	Permissions []Permission `yaml:"permissions"`
+	Roles       []string     `yaml:"roles"`
+}
+
+// Config configures authentication
+type Config struct {
+	Enabled      bool                `yaml:"enabled"`
+	Keys         []KeyConfig         `yaml:"keys"`
+	TokenSecret  string              `yaml:"token_secret"` // Signs tokens; tokens are disabled if empty
+	Certificates []CertificateConfig `yaml:"certificates"` // Requires TLS client certificate verification
+	Roles        []RoleConfig        `yaml:"roles"`
+}
+
+// Identity is an authenticated client
+type Identity struct {
+	Name        string       `json:"name"`
+	Method      string       `json:"method"`
+	Permissions []Permission `json:"permissions"`
+	Roles       []string     `json:"roles,omitempty"`
+	Expires     time.Time    `json:"expires,omitempty"` // Tokens only
+}
+
+// Can reports whether the identity has a permission
+func (i Identity) Can(permission Permission) bool {
+	for _, p := range i.Permissions {
+		if p == permission {
+			return true
+		}
+	}
+	return false
+}
+
+// tokenClaims is the signed content of a token
+type tokenClaims struct {
+	Subject     string       `json:"sub"`
+	Permissions []Permission `json:"perm"`
+	Roles       []string     `json:"roles,omitempty"`
+	Expires     int64        `json:"exp"`
+}
+
+// Authenticator checks request credentials
+type Authenticator struct {
+	enabled      bool
+	keys         map[[sha256.Size]byte]Identity
+	secret       []byte
+	certificates map[string]Identity
+	roles        map[string]role
+}
+
+// NewAuthenticator validates the configuration
+func NewAuthenticator(config Config) (*Authenticator, error) {
+	a := &Authenticator{
+		enabled:      config.Enabled,
+		keys:         make(map[[sha256.Size]byte]Identity),
+		secret:       []byte(config.TokenSecret),
+		certificates: make(map[string]Identity),
+		roles:        make(map[string]role),
+	}
+
+	for _, roleConfig := range config.Roles {
+		if _, exists := a.roles[roleConfig.Name]; exists {
+			return nil, fmt.Errorf("duplicate role %s", roleConfig.Name)
+		}
+		parsed, err := parseRole(roleConfig)
+		if err != nil {
+			return nil, err
+		}
+		a.roles[roleConfig.Name] = parsed
+	}
+
+	for _, key := range config.Keys {
+		if err := validatePermissions(key.Permissions); err != nil {
+			return nil, fmt.Errorf("api key %s: %w", key.Name, err)
+		}
+		if err := a.validateRoles(key.Roles); err != nil {
+			return nil, fmt.Errorf("api key %s: %w", key.Name, err)
+		}
+		var hash [sha256.Size]byte
+		switch {
+		case key.Key != "" && key.KeyHash != "":
+			return nil, fmt.Errorf("api key %s: set key or key_hash, not both", key.Name)
+		case key.Key != "":
+			hash = sha256.Sum256([]byte(key.Key))
+		case key.KeyHash != "":
+			decoded, err := hex.DecodeString(key.KeyHash)
+			if err != nil || len(decoded) != sha256.Size {
+				return nil, fmt.Errorf("api key %s: key_hash must be a hex SHA-256 hash", key.Name)
+			}
+			copy(hash[:], decoded)
+		default:
+			return nil, fmt.Errorf("api key %s: no key", key.Name)
+		}
+		if _, exists := a.keys[hash]; exists {
+			return nil, fmt.Errorf("api key %s: duplicate key", key.Name)
+		}
+		a.keys[hash] = Identity{Name: key.Name, Method: MethodKey, Permissions: key.Permissions, Roles: key.Roles}
+	}
+
+	for _, certificate := range config.Certificates {
+		if certificate.CommonName == "" {
+			return nil, fmt.Errorf("client certificate without common_name")
+		}
+		if err := validatePermissions(certificate.Permissions); err != nil {
+			return nil, fmt.Errorf("client certificate %s: %w", certificate.CommonName, err)
+		}
+		if err := a.validateRoles(certificate.Roles); err != nil {
+			return nil, fmt.Errorf("client certificate %s: %w", certificate.CommonName, err)
+		}
+		a.certificates[certificate.CommonName] = Identity{
+			Name:        certificate.CommonName,
+			Method:      MethodCertificate,
+			Permissions: certificate.Permissions,
+			Roles:       certificate.Roles,
+		}
+	}
+
+	if config.Enabled && len(a.keys) == 0 && len(a.secret) == 0 && len(a.certificates) == 0 {
+		return nil, fmt.Errorf("authentication is enabled without keys, token secret or certificates")
+	}
+	return a, nil
+}
+
+// DenyAll returns an authenticator rejecting every request, used in place
+// of an invalid configuration
+func DenyAll() *Authenticator {
+	return &Authenticator{enabled: true}
+}
+
+// Enabled reports whether requests are authenticated
+func (a *Authenticator) Enabled() bool {
+	return a.enabled
+}
+
+// Authenticate returns the identity of a request. A verified client
+// certificate is preferred over a key or token.
+func (a *Authenticator) Authenticate(r *http.Request) (Identity, error) {
+	if r.TLS != nil && len(r.TLS.VerifiedChains) > 0 && len(r.TLS.VerifiedChains[0]) > 0 {
+		if identity, ok := a.certificates[r.TLS.VerifiedChains[0][0].Subject.CommonName]; ok {
+			return identity, nil
+		}
+	}
+	return a.AuthenticateCredential(Credential(r))
+}
+
+// AuthenticateCredential returns the identity of an API key or token,
+// e.g. taken from gRPC metadata
+func (a *Authenticator) AuthenticateCredential(credential string) (Identity, error) {
+	if credential == "" {
+		return Identity{}, ErrUnauthenticated
+	}
+	if identity, ok := a.keys[sha256.Sum256([]byte(credential))]; ok {
+		return identity, nil
+	}
+	return a.verifyToken(credential)
+}
+
+// Credential returns the API key or token of a request
+func Credential(r *http.Request) string {
+	if header := r.Header.Get("Authorization"); header != "" {
+		if scheme, credential, ok := strings.Cut(header, " "); ok && strings.EqualFold(scheme, "Bearer") {
+			return strings.TrimSpace(credential)
+		}
+		return ""
+	}
+	if key := r.Header.Get("X-API-Key"); key != "" {
+		return key
+	}
+	return r.URL.Query().Get("access_token")
+}
+
+// IssueToken returns a token for subject valid for ttl, restricted to the
+// tags of roles if any are given
+func (a *Authenticator) IssueToken(subject string, permissions []Permission, roles []string, ttl time.Duration) (string, error) {
+	if len(a.secret) == 0 {
+		return "", fmt.Errorf("tokens are disabled without a token secret")
+	}
+	if ttl <= 0 {
+		return "", fmt.Errorf("token lifetime must be positive")
+	}
+	if err := validatePermissions(permissions); err != nil {
+		return "", err
+	}
+	if err := a.validateRoles(roles); err != nil {
+		return "", err
+	}
+
+	payload, err := json.Marshal(tokenClaims{
+		Subject:     subject,
+		Permissions: permissions,
+		Roles:       roles,
+		Expires:     time.Now().Add(ttl).Unix(),
+	})
+	if err != nil {
+		return "", err
+	}
+	encoded := base64.RawURLEncoding.EncodeToString(payload)
+	return encoded + "." + base64.RawURLEncoding.EncodeToString(a.sign(encoded)), nil
+}
+
+func (a *Authenticator) verifyToken(token string) (Identity, error) {
+	encoded, signature, ok := strings.Cut(token, ".")
+	if !ok || len(a.secret) == 0 {
+		return Identity{}, ErrUnauthenticated
+	}
+	decoded, err := base64.RawURLEncoding.DecodeString(signature)
+	if err != nil || !hmac.Equal(decoded, a.sign(encoded)) {
+		return Identity{}, ErrUnauthenticated
+	}
+
+	payload, err := base64.RawURLEncoding.DecodeString(encoded)
+	if err != nil {
+		return Identity{}, ErrUnauthenticated
+	}
+	var claims tokenClaims
+	if err := json.Unmarshal(payload, &claims); err != nil {
+		return Identity{}, ErrUnauthenticated
+	}
+	expires := time.Unix(claims.Expires, 0)
+	if !time.Now().Before(expires) {
+		return Identity{}, fmt.Errorf("%w: token expired", ErrUnauthenticated)
+	}
+	return Identity{
+		Name:        claims.Subject,
+		Method:      MethodToken,
+		Permissions: claims.Permissions,
+		Roles:       claims.Roles,
+		Expires:     expires,
+	}, nil
+}
+
+func (a *Authenticator) sign(payload string) []byte {
+	mac := hmac.New(sha256.New, a.secret)
+	mac.Write([]byte(payload))
+	return mac.Sum(nil)
+}
+
+func validatePermissions(permissions []Permission) error {
+	for _, permission := range permissions {
+		if permission != PermissionRead && permission != PermissionWrite {
+			return fmt.Errorf("unknown permission %q", permission)
+		}
+	}
+	return nil
+}
+
+// identityKey is the context key of the request identity
+type identityKey struct{}
+
+// FromContext returns the identity of an authenticated request
+func FromContext(ctx context.Context) (Identity, bool) {
+	identity, ok := ctx.Value(identityKey{}).(Identity)
+	return identity, ok
+}
+
+// Require returns a handler that calls next for requests whose identity
+// has permission and otherwise responds 401 or 403. The identity is added
+// to the request context.
+func (a *Authenticator) Require(permission Permission, next http.HandlerFunc) http.HandlerFunc {
+	return func(w http.ResponseWriter, r *http.Request) {
+		if !a.enabled {
+			next(w, r)
+			return
+		}
+
+		identity, err := a.Authenticate(r)
+		if err != nil {
+			w.Header().Set("WWW-Authenticate", `Bearer realm="bifrost"`)
+			writeError(w, http.StatusUnauthorized, err.Error())
+			return
+		}
+		if !identity.Can(permission) {
+			writeError(w, http.StatusForbidden, fmt.Sprintf("%s permission required", permission))
+			return
+		}
+		next(w, r.WithContext(context.WithValue(r.Context(), identityKey{}, identity)))
+	}
+}
+
+// RequireByMethod requires read permission for GET and HEAD requests and
+// write permission for all others
+func (a *Authenticator) RequireByMethod(next http.HandlerFunc) http.HandlerFunc {
+	read, write := a.Require(PermissionRead, next), a.Require(PermissionWrite, next)
+	return func(w http.ResponseWriter, r *http.Request) {
+		if r.Method == http.MethodGet || r.Method == http.MethodHead {
+			read(w, r)
+		} else {
+			write(w, r)
+		}
+	}
+}
+
+func writeError(w http.ResponseWriter, status int, message string) {
+	w.Header().Set("Content-Type", "application/json")
+	w.WriteHeader(status)
+	json.NewEncoder(w).Encode(map[string]string{"error": message})
+}
@@ -0,0 +1,169 @@
+package auth
+
+import (
+	"bytes"
+	"crypto/sha256"
+	"crypto/tls"
+	"crypto/x509"
+	"crypto/x509/pkix"
+	"encoding/base64"
+	"encoding/hex"
+	"net/http"
+	"net/http/httptest"
+	"strings"
+	"testing"
+	"time"
+
+	"github.com/stretchr/testify/assert"
+)
+
+func testAuthenticator(t *testing.T) *Authenticator {
+	hash := sha256.Sum256([]byte("operator-key"))
+	a, err := NewAuthenticator(Config{
+		Enabled: true,
+		Keys: []KeyConfig{
+			{Name: "dashboard", Key: "dashboard-key", Permissions: []Permission{PermissionRead}},
+			{Name: "operator", KeyHash: hex.EncodeToString(hash[:]), Permissions: []Permission{PermissionRead, PermissionWrite}},
+		},
+		TokenSecret:  "secret",
+		Certificates: []CertificateConfig{{CommonName: "scada-01", Permissions: []Permission{PermissionRead}}},
+	})
+	assert.NoError(t, err)
+	return a
+}
+
+func request(target, header, value string) *http.Request {
+	r := httptest.NewRequest(http.MethodGet, target, nil)
+	if header != "" {
+		r.Header.Set(header, value)
+	}
+	return r
+}
+
+func TestAuthenticator_Authenticate(t *testing.T) {
+	a := testAuthenticator(t)
+
+	identity, err := a.Authenticate(request("/api/tags", "Authorization", "Bearer dashboard-key"))
+	assert.NoError(t, err)
+	assert.Equal(t, Identity{Name: "dashboard", Method: MethodKey, Permissions: []Permission{PermissionRead}}, identity)
+
+	identity, err = a.Authenticate(request("/api/tags", "X-API-Key", "operator-key"))
+	assert.NoError(t, err)
+	assert.Equal(t, "operator", identity.Name)
+	assert.True(t, identity.Can(PermissionWrite))
+
+	identity, err = a.Authenticate(request("/ws?access_token=dashboard-key", "", ""))
+	assert.NoError(t, err)
+	assert.Equal(t, "dashboard", identity.Name)
+
+	// A verified client certificate
+	r := request("/api/tags", "", "")
+	r.TLS = &tls.ConnectionState{VerifiedChains: [][]*x509.Certificate{{{Subject: pkix.Name{CommonName: "scada-01"}}}}}
+	identity, err = a.Authenticate(r)
+	assert.NoError(t, err)
+	assert.Equal(t, MethodCertificate, identity.Method)
+
+	for _, r := range []*http.Request{
+		request("/api/tags", "", ""),
+		request("/api/tags", "Authorization", "Bearer unknown"),
+		request("/api/tags", "Authorization", "Basic dashboard-key"),
+		request("/api/tags", "X-API-Key", "operator-key.forged"),
+	} {
+		_, err := a.Authenticate(r)
+		assert.ErrorIs(t, err, ErrUnauthenticated)
+	}
+}
+
+func TestAuthenticator_Tokens(t *testing.T) {
+	a := testAuthenticator(t)
+
+	token, err := a.IssueToken("operator", []Permission{PermissionRead}, nil, time.Minute)
+	assert.NoError(t, err)
+	identity, err := a.AuthenticateCredential(token)
+	assert.NoError(t, err)
+	assert.Equal(t, "operator", identity.Name)
+	assert.Equal(t, MethodToken, identity.Method)
+	assert.Equal(t, []Permission{PermissionRead}, identity.Permissions)
+	assert.WithinDuration(t, time.Now().Add(time.Minute), identity.Expires, 2*time.Second)
+
+	// Tampered claims fail the signature check
+	payload, signature, _ := strings.Cut(token, ".")
+	claims, err := base64.RawURLEncoding.DecodeString(payload)
+	assert.NoError(t, err)
+	claims = bytes.Replace(claims, []byte(`"read"`), []byte(`"write"`), 1)
+	forged := base64.RawURLEncoding.EncodeToString(claims) + "." + signature
+	_, err = a.AuthenticateCredential(forged)
+	assert.ErrorIs(t, err, ErrUnauthenticated)
+
+	// Tokens of another gateway are rejected
+	other, err := NewAuthenticator(Config{Enabled: true, TokenSecret: "other"})
+	assert.NoError(t, err)
+	_, err = other.AuthenticateCredential(token)
+	assert.ErrorIs(t, err, ErrUnauthenticated)
+
+	// Expired tokens are rejected
+	token, err = a.IssueToken("operator", []Permission{PermissionRead}, nil, time.Nanosecond)
+	assert.NoError(t, err)
+	_, err = a.AuthenticateCredential(token)
+	assert.ErrorIs(t, err, ErrUnauthenticated)
+
+	_, err = a.IssueToken("operator", []Permission{"admin"}, nil, time.Minute)
+	assert.Error(t, err)
+	_, err = a.IssueToken("operator", nil, nil, 0)
+	assert.Error(t, err)
+}
+
+func TestAuthenticator_Require(t *testing.T) {
+	a := testAuthenticator(t)
+	var name string
+	handler := a.RequireByMethod(func(w http.ResponseWriter, r *http.Request) {
+		identity, _ := FromContext(r.Context())
+		name = identity.Name
+	})
+
+	for _, test := range []struct {
+		method string
+		key    string
+		status int
+	}{
+		{http.MethodGet, "", http.StatusUnauthorized},
+		{http.MethodGet, "dashboard-key", http.StatusOK},
+		{http.MethodPut, "dashboard-key", http.StatusForbidden},
+		{http.MethodPut, "operator-key", http.StatusOK},
+	} {
+		r := httptest.NewRequest(test.method, "/api/rules", nil)
+		if test.key != "" {
+			r.Header.Set("X-API-Key", test.key)
+		}
+		w := httptest.NewRecorder()
+		handler(w, r)
+		assert.Equal(t, test.status, w.Code, "%s %s", test.method, test.key)
+	}
+	assert.Equal(t, "operator", name)
+
+	// Without authentication every request passes
+	open, err := NewAuthenticator(Config{})
+	assert.NoError(t, err)
+	w := httptest.NewRecorder()
+	open.Require(PermissionWrite, func(w http.ResponseWriter, r *http.Request) {})(w, httptest.NewRequest(http.MethodPost, "/api/tags/write", nil))
+	assert.Equal(t, http.StatusOK, w.Code)
+
+	w = httptest.NewRecorder()
+	DenyAll().Require(PermissionRead, func(w http.ResponseWriter, r *http.Request) {})(w, request("/api/tags", "X-API-Key", "dashboard-key"))
+	assert.Equal(t, http.StatusUnauthorized, w.Code)
+}
+
+func TestNewAuthenticator_Invalid(t *testing.T) {
+	for _, config := range []Config{
+		{Enabled: true},
+		{Keys: []KeyConfig{{Name: "a"}}},
+		{Keys: []KeyConfig{{Name: "a", Key: "k", KeyHash: "00"}}},
+		{Keys: []KeyConfig{{Name: "a", KeyHash: "not-hex"}}},
+		{Keys: []KeyConfig{{Name: "a", Key: "k", Permissions: []Permission{"admin"}}}},
+		{Keys: []KeyConfig{{Name: "a", Key: "k"}, {Name: "b", Key: "k"}}},
+		{Certificates: []CertificateConfig{{Permissions: []Permission{PermissionRead}}}},
+	} {
+		_, err := NewAuthenticator(config)
+		assert.Error(t, err, config)
+	}
+}
@@ -0,0 +1,107 @@
+package auth
+
+import (
+	"fmt"
+	"path"
+
+	"github.com/bifrost/go-gateway/internal/protocols"
+)
+
+// Roles
+//
+// Permissions decide which operations a client may perform at all. Roles
+// narrow them down to tags: a role lists the tags its members may read and
+// the tags they may write, so an HMI account can read every tag while only
+// the control service writes setpoints. An identity with roles may access
+// the tags granted by any of them; an identity without roles may access
+// every tag its permissions allow.
+
+// TagPattern selects tags by device and tag ID
+type TagPattern struct {
+	Devices string `yaml:"devices"` // Device ID pattern, e.g. "plant1/**"
+	Tags    string `yaml:"tags"`    // Glob of tag IDs, e.g. "Setpoint*"; default every tag
+}
+
+// RoleConfig configures the tags a role may read and write
+type RoleConfig struct {
+	Name  string       `yaml:"name"`
+	Read  []TagPattern `yaml:"read"`
+	Write []TagPattern `yaml:"write"`
+}
+
+// tagGrant is a parsed TagPattern
+type tagGrant struct {
+	devices *protocols.DeviceIDPattern
+	tags    string
+}
+
+func (g tagGrant) match(deviceID, tagID string) bool {
+	if !g.devices.MatchString(deviceID) {
+		return false
+	}
+	matched, _ := path.Match(g.tags, tagID)
+	return matched
+}
+
+// role holds the grants of a role by permission
+type role map[Permission][]tagGrant
+
+func parseRole(config RoleConfig) (role, error) {
+	if config.Name == "" {
+		return nil, fmt.Errorf("role without name")
+	}
+
+	r := make(role)
+	for permission, patterns := range map[Permission][]TagPattern{
+		PermissionRead:  config.Read,
+		PermissionWrite: config.Write,
+	} {
+		for _, pattern := range patterns {
+			devices, err := protocols.ParseDeviceIDPattern(pattern.Devices)
+			if err != nil {
+				return nil, fmt.Errorf("role %s: %w", config.Name, err)
+			}
+			tags := pattern.Tags
+			if tags == "" {
+				tags = "*"
+			}
+			if _, err := path.Match(tags, ""); err != nil {
+				return nil, fmt.Errorf("role %s: tag pattern %q: %w", config.Name, tags, err)
+			}
+			r[permission] = append(r[permission], tagGrant{devices: devices, tags: tags})
+		}
+	}
+	return r, nil
+}
+
+// validateRoles checks that roles are configured
+func (a *Authenticator) validateRoles(roles []string) error {
+	for _, name := range roles {
+		if _, exists := a.roles[name]; !exists {
+			return fmt.Errorf("unknown role %q", name)
+		}
+	}
+	return nil
+}
+
+// Allows reports whether an identity may read or write a tag of a device.
+// Every access is allowed without authentication.
+func (a *Authenticator) Allows(identity Identity, permission Permission, deviceID, tagID string) bool {
+	if !a.enabled {
+		return true
+	}
+	if !identity.Can(permission) {
+		return false
+	}
+	if len(identity.Roles) == 0 {
+		return true
+	}
+	for _, name := range identity.Roles {
+		for _, grant := range a.roles[name][permission] {
+			if grant.match(deviceID, tagID) {
+				return true
+			}
+		}
+	}
+	return false
+}
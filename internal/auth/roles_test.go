@@ -0,0 +1,85 @@
+package auth
+
+import (
+	"testing"
+	"time"
+
+	"github.com/stretchr/testify/assert"
+)
+
+func testRoleAuthenticator(t *testing.T) *Authenticator {
+	a, err := NewAuthenticator(Config{
+		Enabled: true,
+		Keys: []KeyConfig{
+			{Name: "hmi", Key: "hmi-key", Permissions: []Permission{PermissionRead, PermissionWrite}, Roles: []string{"hmi"}},
+			{Name: "control", Key: "control-key", Permissions: []Permission{PermissionRead, PermissionWrite}, Roles: []string{"hmi", "control"}},
+			{Name: "engineer", Key: "engineer-key", Permissions: []Permission{PermissionRead, PermissionWrite}},
+		},
+		TokenSecret: "secret",
+		Roles: []RoleConfig{
+			{Name: "hmi", Read: []TagPattern{{Devices: "**"}}},
+			{Name: "control", Write: []TagPattern{{Devices: "plant1/**", Tags: "Setpoint*"}}},
+		},
+	})
+	assert.NoError(t, err)
+	return a
+}
+
+func TestAuthenticator_Allows(t *testing.T) {
+	a := testRoleAuthenticator(t)
+	identity := func(key string) Identity {
+		identity, err := a.AuthenticateCredential(key)
+		assert.NoError(t, err)
+		return identity
+	}
+	hmi, control, engineer := identity("hmi-key"), identity("control-key"), identity("engineer-key")
+	assert.Equal(t, []string{"hmi"}, hmi.Roles)
+
+	for _, test := range []struct {
+		identity   Identity
+		permission Permission
+		device     string
+		tag        string
+		allowed    bool
+	}{
+		{hmi, PermissionRead, "plant1/line1/tank", "Level", true},
+		{hmi, PermissionWrite, "plant1/line1/tank", "SetpointLevel", false},
+		{control, PermissionWrite, "plant1/line1/tank", "SetpointLevel", true},
+		{control, PermissionWrite, "plant1/line1/tank", "InletValve", false},
+		{control, PermissionWrite, "plant2/line1/tank", "SetpointLevel", false},
+		{control, PermissionRead, "plant2/line1/tank", "Level", true},
+		{engineer, PermissionWrite, "plant2/line1/tank", "InletValve", true},
+		{Identity{Name: "dashboard", Permissions: []Permission{PermissionRead}}, PermissionWrite, "plant1/line1/tank", "SetpointLevel", false},
+	} {
+		assert.Equal(t, test.allowed, a.Allows(test.identity, test.permission, test.device, test.tag),
+			"%s %s %s/%s", test.identity.Name, test.permission, test.device, test.tag)
+	}
+
+	// Tokens carry their roles
+	token, err := a.IssueToken("control", []Permission{PermissionRead, PermissionWrite}, []string{"hmi"}, time.Minute)
+	assert.NoError(t, err)
+	restricted := identity(token)
+	assert.Equal(t, []string{"hmi"}, restricted.Roles)
+	assert.False(t, a.Allows(restricted, PermissionWrite, "plant1/line1/tank", "SetpointLevel"))
+	_, err = a.IssueToken("control", []Permission{PermissionRead}, []string{"admin"}, time.Minute)
+	assert.Error(t, err)
+
+	// Without authentication every access is allowed
+	open, err := NewAuthenticator(Config{})
+	assert.NoError(t, err)
+	assert.True(t, open.Allows(Identity{}, PermissionWrite, "plant1/line1/tank", "SetpointLevel"))
+}
+
+func TestNewAuthenticator_InvalidRoles(t *testing.T) {
+	for _, config := range []Config{
+		{Roles: []RoleConfig{{Read: []TagPattern{{Devices: "**"}}}}},
+		{Roles: []RoleConfig{{Name: "a"}, {Name: "a"}}},
+		{Roles: []RoleConfig{{Name: "a", Read: []TagPattern{{}}}}},
+		{Roles: []RoleConfig{{Name: "a", Write: []TagPattern{{Devices: "plant1/**", Tags: "["}}}}},
+		{Keys: []KeyConfig{{Name: "a", Key: "k", Roles: []string{"missing"}}}},
+		{Certificates: []CertificateConfig{{CommonName: "scada-01", Roles: []string{"missing"}}}},
+	} {
+		_, err := NewAuthenticator(config)
+		assert.Error(t, err, config)
+	}
+}
@@ -0,0 +1,160 @@
+// Package backpressure tells producers when buffers downstream are filling
+// up so they can slow down
+package backpressure
+
+import (
+	"context"
+	"sort"
+	"sync"
+	"time"
+)
+
+// Backpressure
+//
+// Buffers such as the store-and-forward queue and the event bus report
+// their Pressure: how many items they hold, how full they are and how long
+// their consumer has been behind. A Monitor combines the buffers into one
+// signal with hysteresis: it turns on when a buffer reaches the high
+// watermark or its lag exceeds MaxLag, and off when all buffers are back
+// at the low watermark. Pollers skip cycles and network endpoints reject
+// or delay work while the signal is on, instead of buffers evicting data.
+
+const (
+	defaultHighWatermark = 0.8
+	defaultLowWatermark  = 0.5
+	waitInterval         = 50 * time.Millisecond
+)
+
+// Pressure is the load of a buffer
+type Pressure struct {
+	Depth     int           `json:"depth"`      // Items held
+	FillRatio float64       `json:"fill_ratio"` // 0 empty to 1 full
+	Lag       time.Duration `json:"lag"`        // How long the consumer has been behind
+}
+
+// Source is a buffer reporting its pressure
+type Source interface {
+	Pressure() Pressure
+}
+
+// Config configures when the signal turns on and off
+type Config struct {
+	HighWatermark float64       `yaml:"high_watermark"` // Fill ratio turning the signal on, default 0.8
+	LowWatermark  float64       `yaml:"low_watermark"`  // Fill ratio turning it off, default 0.5
+	MaxLag        time.Duration `yaml:"max_lag"`        // Lag turning it on, 0 ignores lag
+}
+
+// Signal is the combined state of the buffers
+type Signal struct {
+	Overloaded bool                `json:"overloaded"`
+	Source     string              `json:"source,omitempty"` // Fullest buffer
+	Pressure   Pressure            `json:"pressure"`         // Of the fullest buffer
+	Sources    map[string]Pressure `json:"sources"`
+}
+
+// Monitor combines the pressure of several buffers
+type Monitor struct {
+	config Config
+
+	mutex      sync.Mutex
+	sources    map[string]Source
+	overloaded bool
+}
+
+// NewMonitor creates a monitor without sources
+func NewMonitor(config Config) *Monitor {
+	return &Monitor{config: withDefaults(config), sources: make(map[string]Source)}
+}
+
+func withDefaults(config Config) Config {
+	if config.HighWatermark <= 0 || config.HighWatermark > 1 {
+		config.HighWatermark = defaultHighWatermark
+	}
+	if config.LowWatermark <= 0 || config.LowWatermark > config.HighWatermark {
+		config.LowWatermark = config.HighWatermark * defaultLowWatermark / defaultHighWatermark
+	}
+	return config
+}
+
+// SetConfig changes the watermarks, taking effect with the next Signal
+func (m *Monitor) SetConfig(config Config) {
+	m.mutex.Lock()
+	defer m.mutex.Unlock()
+	m.config = withDefaults(config)
+}
+
+// Register adds or replaces a buffer
+func (m *Monitor) Register(name string, source Source) {
+	m.mutex.Lock()
+	defer m.mutex.Unlock()
+	m.sources[name] = source
+}
+
+// Unregister removes a buffer
+func (m *Monitor) Unregister(name string) {
+	m.mutex.Lock()
+	defer m.mutex.Unlock()
+	delete(m.sources, name)
+}
+
+// Signal samples the buffers and returns the combined state
+func (m *Monitor) Signal() Signal {
+	m.mutex.Lock()
+	defer m.mutex.Unlock()
+
+	signal := Signal{Sources: make(map[string]Pressure, len(m.sources))}
+	names := make([]string, 0, len(m.sources))
+	for name := range m.sources {
+		names = append(names, name)
+	}
+	sort.Strings(names)
+
+	high, lagging := false, false
+	for _, name := range names {
+		pressure := m.sources[name].Pressure()
+		signal.Sources[name] = pressure
+		if signal.Source == "" || pressure.FillRatio > signal.Pressure.FillRatio {
+			signal.Source, signal.Pressure = name, pressure
+		}
+		if pressure.FillRatio >= m.config.HighWatermark {
+			high = true
+		}
+		if m.config.MaxLag > 0 && pressure.Lag >= m.config.MaxLag {
+			lagging = true
+		}
+	}
+
+	switch {
+	case high || lagging:
+		m.overloaded = true
+	case signal.Pressure.FillRatio <= m.config.LowWatermark:
+		m.overloaded = false
+	}
+	signal.Overloaded = m.overloaded
+	return signal
+}
+
+// Overloaded reports whether producers should slow down
+func (m *Monitor) Overloaded() bool {
+	return m.Signal().Overloaded
+}
+
+// Wait blocks until the signal is off or ctx is done
+func (m *Monitor) Wait(ctx context.Context) error {
+	if !m.Overloaded() {
+		return nil
+	}
+
+	ticker := time.NewTicker(waitInterval)
+	defer ticker.Stop()
+	for {
+		select {
+		case <-ctx.Done():
+			return ctx.Err()
+		case <-ticker.C:
+			if !m.Overloaded() {
+				return nil
+			}
+		}
+	}
+}
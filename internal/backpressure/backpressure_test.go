@@ -0,0 +1,87 @@
+package backpressure
+
+import (
+	"context"
+	"sync"
+	"testing"
+	"time"
+
+	"github.com/stretchr/testify/assert"
+)
+
+// buffer is a source with a settable pressure
+type buffer struct {
+	mutex    sync.Mutex
+	pressure Pressure
+}
+
+func (b *buffer) Pressure() Pressure {
+	b.mutex.Lock()
+	defer b.mutex.Unlock()
+	return b.pressure
+}
+
+func (b *buffer) set(pressure Pressure) {
+	b.mutex.Lock()
+	defer b.mutex.Unlock()
+	b.pressure = pressure
+}
+
+func TestMonitor_Hysteresis(t *testing.T) {
+	monitor := NewMonitor(Config{})
+	queue, events := &buffer{}, &buffer{}
+	monitor.Register("queue", queue)
+	monitor.Register("events", events)
+	assert.False(t, monitor.Overloaded())
+
+	queue.set(Pressure{Depth: 90, FillRatio: 0.9})
+	events.set(Pressure{Depth: 10, FillRatio: 0.1})
+	signal := monitor.Signal()
+	assert.True(t, signal.Overloaded)
+	assert.Equal(t, "queue", signal.Source)
+	assert.Equal(t, 90, signal.Pressure.Depth)
+	assert.Len(t, signal.Sources, 2)
+
+	// The signal stays on between the watermarks
+	queue.set(Pressure{Depth: 60, FillRatio: 0.6})
+	assert.True(t, monitor.Overloaded())
+	queue.set(Pressure{Depth: 50, FillRatio: 0.5})
+	assert.False(t, monitor.Overloaded())
+	queue.set(Pressure{Depth: 60, FillRatio: 0.6})
+	assert.False(t, monitor.Overloaded())
+
+	monitor.Unregister("queue")
+	assert.Equal(t, "events", monitor.Signal().Source)
+}
+
+func TestMonitor_Lag(t *testing.T) {
+	monitor := NewMonitor(Config{MaxLag: time.Minute})
+	queue := &buffer{}
+	monitor.Register("queue", queue)
+
+	queue.set(Pressure{Depth: 1, FillRatio: 0.01, Lag: 2 * time.Minute})
+	assert.True(t, monitor.Overloaded())
+
+	// Lag is ignored without MaxLag
+	monitor.SetConfig(Config{})
+	queue.set(Pressure{Depth: 1, FillRatio: 0.01})
+	assert.False(t, monitor.Overloaded())
+	queue.set(Pressure{Depth: 1, FillRatio: 0.01, Lag: 2 * time.Minute})
+	assert.False(t, monitor.Overloaded())
+}
+
+func TestMonitor_Wait(t *testing.T) {
+	monitor := NewMonitor(Config{HighWatermark: 0.9, LowWatermark: 0.2})
+	queue := &buffer{pressure: Pressure{FillRatio: 0.95}}
+	monitor.Register("queue", queue)
+
+	ctx, cancel := context.WithTimeout(context.Background(), 100*time.Millisecond)
+	defer cancel()
+	assert.ErrorIs(t, monitor.Wait(ctx), context.DeadlineExceeded)
+
+	go func() {
+		time.Sleep(20 * time.Millisecond)
+		queue.set(Pressure{FillRatio: 0.1})
+	}()
+	assert.NoError(t, monitor.Wait(context.Background()))
+}
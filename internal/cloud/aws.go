@@ -0,0 +1,65 @@
+package cloud
+
+import (
+	"fmt"
+	"strings"
+
+	mqtt "github.com/eclipse/paho.mqtt.golang"
+	"go.uber.org/zap"
+
+	"github.com/bifrost/go-gateway/internal/tlsconfig"
+)
+
+const (
+	awsMaxMessageSize = 128 * 1024
+	awsDefaultTopic   = "bifrost/{client_id}/telemetry"
+	awsALPNProtocol   = "x-amzn-mqtt-ca" // MQTT with certificates on port 443
+)
+
+// AWSConfig configures an AWS IoT Core thing
+type AWSConfig struct {
+	Endpoint string           `yaml:"endpoint"`  // Device data endpoint, e.g. xxx-ats.iot.eu-west-1.amazonaws.com
+	ClientID string           `yaml:"client_id"` // Usually the thing name
+	Topic    string           `yaml:"topic"`     // Default bifrost/{client_id}/telemetry
+	Port     int              `yaml:"port"`      // 8883 (default) or 443
+	TLS      tlsconfig.Config `yaml:"tls"`       // Certificate and key of the thing
+}
+
+// awsConnection prepares an IoT Core connection authenticated with the
+// thing's certificate
+func awsConnection(config AWSConfig, logger *zap.Logger) (connection, error) {
+	if config.Endpoint == "" || config.ClientID == "" {
+		return connection{}, fmt.Errorf("aws requires an endpoint and client id")
+	}
+	if config.TLS.CertFile == "" || config.TLS.KeyFile == "" {
+		return connection{}, fmt.Errorf("aws requires a certificate and key")
+	}
+	if config.Topic == "" {
+		config.Topic = awsDefaultTopic
+	}
+	if config.Port == 0 {
+		config.Port = 8883
+	}
+
+	config.TLS.Enabled = true
+	loader, err := tlsconfig.NewLoader(config.TLS, logger)
+	if err != nil {
+		return connection{}, err
+	}
+	tlsConfig := loader.ClientConfig()
+	if config.Port == 443 {
+		tlsConfig.NextProtos = []string{awsALPNProtocol}
+	}
+
+	options := mqtt.NewClientOptions().
+		AddBroker(fmt.Sprintf("ssl://%s:%d", config.Endpoint, config.Port)).
+		SetClientID(config.ClientID).
+		SetProtocolVersion(4).
+		SetTLSConfig(tlsConfig)
+
+	return connection{
+		options:        options,
+		topic:          strings.ReplaceAll(config.Topic, "{client_id}", config.ClientID),
+		maxMessageSize: awsMaxMessageSize,
+	}, nil
+}
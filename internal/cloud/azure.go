@@ -0,0 +1,121 @@
+package cloud
+
+import (
+	"crypto/hmac"
+	"crypto/sha256"
+	"encoding/base64"
+	"fmt"
+	"net/url"
+	"strconv"
+	"strings"
+	"time"
+
+	mqtt "github.com/eclipse/paho.mqtt.golang"
+)
+
+// Azure IoT Hub transports
+const (
+	TransportWebSocket = "websocket" // MQTT over WebSocket on port 443
+	TransportMQTT      = "mqtt"      // MQTT on port 8883
+)
+
+const (
+	azureAPIVersion     = "2021-04-12"
+	azureMaxMessageSize = 256 * 1024
+	defaultTokenTTL     = time.Hour
+)
+
+// AzureConfig configures an Azure IoT Hub device identity. Either
+// ConnectionString or HostName, DeviceID and SharedAccessKey are set.
+type AzureConfig struct {
+	ConnectionString string        `yaml:"connection_string"` // HostName=...;DeviceId=...;SharedAccessKey=...
+	HostName         string        `yaml:"host_name"`         // e.g. my-hub.azure-devices.net
+	DeviceID         string        `yaml:"device_id"`
+	SharedAccessKey  string        `yaml:"shared_access_key"` // Base64 device key
+	TokenTTL         time.Duration `yaml:"token_ttl"`         // Validity of SAS tokens, default 1h
+	Transport        string        `yaml:"transport"`         // websocket (default) or mqtt
+}
+
+// parseConnectionString fills in the fields of a device connection string
+func (c *AzureConfig) parseConnectionString() error {
+	for _, part := range strings.Split(c.ConnectionString, ";") {
+		if part == "" {
+			continue
+		}
+		key, value, ok := strings.Cut(part, "=")
+		if !ok {
+			return fmt.Errorf("invalid connection string part %q", part)
+		}
+		switch key {
+		case "HostName":
+			c.HostName = value
+		case "DeviceId":
+			c.DeviceID = value
+		case "SharedAccessKey":
+			c.SharedAccessKey = value
+		}
+	}
+	return nil
+}
+
+// azureConnection prepares an IoT Hub device connection
+func azureConnection(config AzureConfig) (connection, error) {
+	if config.ConnectionString != "" {
+		if err := config.parseConnectionString(); err != nil {
+			return connection{}, err
+		}
+	}
+	if config.HostName == "" || config.DeviceID == "" || config.SharedAccessKey == "" {
+		return connection{}, fmt.Errorf("azure requires a host name, device id and shared access key")
+	}
+	key, err := base64.StdEncoding.DecodeString(config.SharedAccessKey)
+	if err != nil {
+		return connection{}, fmt.Errorf("invalid shared access key: %w", err)
+	}
+	if config.TokenTTL <= 0 {
+		config.TokenTTL = defaultTokenTTL
+	}
+
+	var broker string
+	switch config.Transport {
+	case "", TransportWebSocket:
+		broker = "wss://" + config.HostName + ":443/$iothub/websocket"
+	case TransportMQTT:
+		broker = "ssl://" + config.HostName + ":8883"
+	default:
+		return connection{}, fmt.Errorf("unknown azure transport %q", config.Transport)
+	}
+
+	resource := config.HostName + "/devices/" + config.DeviceID
+	username := config.HostName + "/" + config.DeviceID + "/?api-version=" + azureAPIVersion
+	options := mqtt.NewClientOptions().
+		AddBroker(broker).
+		SetClientID(config.DeviceID).
+		SetProtocolVersion(4).
+		// A fresh token on every connect, so reconnects after the previous
+		// one expired succeed
+		SetCredentialsProvider(func() (string, string) {
+			return username, sasToken(resource, key, time.Now().Add(config.TokenTTL))
+		})
+
+	return connection{
+		options:        options,
+		topic:          "devices/" + config.DeviceID + "/messages/events/$.ct=application%2Fjson&$.ce=utf-8",
+		maxMessageSize: azureMaxMessageSize,
+	}, nil
+}
+
+// sasToken returns a shared access signature for resource valid until
+// expiry, signed with key
+func sasToken(resource string, key []byte, expiry time.Time) string {
+	encoded := url.QueryEscape(resource)
+	se := strconv.FormatInt(expiry.Unix(), 10)
+
+	mac := hmac.New(sha256.New, key)
+	mac.Write([]byte(encoded + "\n" + se))
+	signature := base64.StdEncoding.EncodeToString(mac.Sum(nil))
+
+	return "SharedAccessSignature sr=" + encoded +
+		"&sig=" + url.QueryEscape(signature) +
+		"&se=" + se
+}
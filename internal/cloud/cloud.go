@@ -0,0 +1,289 @@
+// Package cloud forwards collected samples to Azure IoT Hub or AWS IoT Core
+package cloud
+
+import (
+	"bytes"
+	"context"
+	"encoding/json"
+	"fmt"
+	"time"
+
+	mqtt "github.com/eclipse/paho.mqtt.golang"
+	"go.uber.org/zap"
+
+	"github.com/bifrost/go-gateway/internal/forward"
+	"github.com/bifrost/go-gateway/internal/protocols"
+)
+
+// Cloud Sinks
+//
+// A Sink is a protocols.SampleSink that queues samples in a
+// store-and-forward queue and publishes them to a cloud IoT service over
+// MQTT, so samples collected during an outage arrive once the uplink is
+// back. Queued records are merged into JSON arrays of samples up to the
+// service's message size limit and published with QoS 1; a batch is
+// acknowledged in the queue once every message of it was acknowledged by
+// the service. A transform set with SetTransform is applied to queued
+// samples as they are sent, e.g. to correct their timestamps.
+//
+//   - Azure IoT Hub: device-to-cloud messages of a device identity,
+//     authenticated with a SAS token signed by the device key. The token
+//     is renewed on every (re)connect; IoT Hub disconnects a client whose
+//     token expired and the client reconnects with a new one. MQTT runs
+//     over WebSocket on port 443 by default, which passes most firewalls.
+//   - AWS IoT Core: messages to a topic of a thing, authenticated with
+//     its X.509 certificate. Port 443 uses ALPN as AWS requires.
+
+// Providers
+const (
+	ProviderAzure = "azure"
+	ProviderAWS   = "aws"
+)
+
+const defaultConnectTimeout = 30 * time.Second
+
+// Config configures a cloud sink
+type Config struct {
+	Name           string                  `yaml:"name"`
+	Provider       string                  `yaml:"provider"` // azure or aws
+	Azure          AzureConfig             `yaml:"azure"`
+	AWS            AWSConfig               `yaml:"aws"`
+	Queue          forward.QueueConfig     `yaml:"queue"`
+	Forwarder      forward.ForwarderConfig `yaml:"forwarder"`
+	MaxMessageSize int                     `yaml:"max_message_size"` // Bytes, default the provider's limit
+	ConnectTimeout time.Duration           `yaml:"connect_timeout"`  // Default 30s
+}
+
+// Transform changes samples before they are sent
+type Transform func(samples []protocols.TagSample) []protocols.TagSample
+
+// connection is what a provider needs to connect and publish
+type connection struct {
+	options        *mqtt.ClientOptions
+	topic          string
+	maxMessageSize int
+}
+
+// Sink queues samples and forwards them to a cloud service
+type Sink struct {
+	*forward.SampleQueue
+
+	config    Config
+	logger    *zap.Logger
+	queue     *forward.Queue
+	forwarder *forward.Forwarder
+	client    mqtt.Client
+	topic     string
+	maxSize   int
+	transform Transform
+}
+
+// NewSink validates the configuration and opens the queue. It does not
+// connect until Run.
+func NewSink(config Config, logger *zap.Logger) (*Sink, error) {
+	if config.ConnectTimeout <= 0 {
+		config.ConnectTimeout = defaultConnectTimeout
+	}
+
+	conn, err := newConnection(config, logger)
+	if err != nil {
+		return nil, err
+	}
+	if config.MaxMessageSize > 0 && config.MaxMessageSize < conn.maxMessageSize {
+		conn.maxMessageSize = config.MaxMessageSize
+	}
+
+	conn.options.
+		SetConnectTimeout(config.ConnectTimeout).
+		SetAutoReconnect(true).
+		SetConnectRetry(true).
+		SetCleanSession(false).
+		SetConnectionLostHandler(func(_ mqtt.Client, err error) {
+			logger.Warn("Cloud connection lost", zap.String("sink", config.Name), zap.Error(err))
+		}).
+		SetOnConnectHandler(func(mqtt.Client) {
+			logger.Info("Cloud connection established", zap.String("sink", config.Name))
+		})
+
+	queue, err := forward.OpenQueue(config.Queue, logger)
+	if err != nil {
+		return nil, fmt.Errorf("cloud sink %s: %w", config.Name, err)
+	}
+
+	s := &Sink{
+		SampleQueue: forward.NewSampleQueue(queue),
+		config:      config,
+		logger:      logger,
+		queue:       queue,
+		client:      mqtt.NewClient(conn.options),
+		topic:       conn.topic,
+		maxSize:     conn.maxMessageSize,
+	}
+	send := sender(s.client, s.topic, s.maxSize, s.applyTransform, config.Name, logger)
+	s.forwarder = forward.NewForwarder(queue, send, config.Forwarder, logger)
+	return s, nil
+}
+
+// Validate returns the configuration errors NewSink would return, without
+// opening the queue
+func Validate(config Config) error {
+	if config.Queue.Dir == "" {
+		return fmt.Errorf("cloud sink %s: queue directory is required", config.Name)
+	}
+	_, err := newConnection(config, zap.NewNop())
+	return err
+}
+
+// newConnection returns the connection to the configured provider
+func newConnection(config Config, logger *zap.Logger) (connection, error) {
+	var conn connection
+	var err error
+	switch config.Provider {
+	case ProviderAzure:
+		conn, err = azureConnection(config.Azure)
+	case ProviderAWS:
+		conn, err = awsConnection(config.AWS, logger)
+	default:
+		err = fmt.Errorf("unknown provider %q", config.Provider)
+	}
+	if err != nil {
+		return connection{}, fmt.Errorf("cloud sink %s: %w", config.Name, err)
+	}
+	return conn, nil
+}
+
+// SetTransform sets a function applied to samples before they are sent.
+// It must be called before Run.
+func (s *Sink) SetTransform(transform Transform) {
+	s.transform = transform
+}
+
+func (s *Sink) applyTransform(samples []protocols.TagSample) []protocols.TagSample {
+	if s.transform == nil {
+		return samples
+	}
+	return s.transform(samples)
+}
+
+// Run connects and forwards queued samples until ctx is done, then
+// disconnects and closes the queue
+func (s *Sink) Run(ctx context.Context) error {
+	defer s.queue.Close()
+
+	// With connect retry the token completes once connected; publishing
+	// fails until then and the forwarder retries
+	s.client.Connect()
+	defer s.client.Disconnect(250)
+
+	s.logger.Info("Cloud sink started",
+		zap.String("sink", s.config.Name),
+		zap.String("provider", s.config.Provider),
+		zap.String("topic", s.topic),
+	)
+	err := s.forwarder.Run(ctx)
+	if ctx.Err() != nil {
+		return nil
+	}
+	return err
+}
+
+// Close closes the queue of a sink that is not running. Run closes it
+// when it returns.
+func (s *Sink) Close() error {
+	return s.queue.Close()
+}
+
+// Check fails while the service is unreachable or rejects messages
+func (s *Sink) Check(ctx context.Context) error {
+	if !s.client.IsConnectionOpen() {
+		return fmt.Errorf("not connected to %s", s.config.Provider)
+	}
+	return s.forwarder.Check(ctx)
+}
+
+// Name returns the configured name of the sink
+func (s *Sink) Name() string {
+	return s.config.Name
+}
+
+// Stats returns the state of the queue
+func (s *Sink) Stats() forward.QueueStats {
+	return s.queue.Stats()
+}
+
+// publisher is the part of an MQTT client the sender needs
+type publisher interface {
+	Publish(topic string, qos byte, retained bool, payload interface{}) mqtt.Token
+}
+
+// sender returns a forward.Sender that publishes batches of queued records
+// to topic
+func sender(client publisher, topic string, maxSize int, transform Transform, name string, logger *zap.Logger) forward.Sender {
+	return func(ctx context.Context, records [][]byte) error {
+		messages, dropped := pack(records, maxSize, transform)
+		if dropped > 0 {
+			logger.Warn("Dropping samples larger than the message size limit",
+				zap.String("sink", name),
+				zap.Int("dropped", dropped),
+				zap.Int("max_message_size", maxSize),
+			)
+		}
+
+		for _, message := range messages {
+			token := client.Publish(topic, 1, false, message)
+			select {
+			case <-ctx.Done():
+				return ctx.Err()
+			case <-token.Done():
+			}
+			if err := token.Error(); err != nil {
+				return err
+			}
+		}
+		return nil
+	}
+}
+
+// pack merges queued records, each a JSON array of samples, into JSON
+// arrays of at most maxSize bytes after applying transform. Records that
+// do not decode and samples larger than maxSize by themselves are dropped
+// and counted.
+func pack(records [][]byte, maxSize int, transform Transform) ([][]byte, int) {
+	var messages [][]byte
+	var message bytes.Buffer
+	dropped := 0
+	flush := func() {
+		if message.Len() > 0 {
+			message.WriteByte(']')
+			messages = append(messages, append([]byte(nil), message.Bytes()...))
+			message.Reset()
+		}
+	}
+
+	for _, record := range records {
+		var samples []protocols.TagSample
+		if err := json.Unmarshal(record, &samples); err != nil {
+			dropped++
+			continue
+		}
+		for _, sample := range transform(samples) {
+			data, err := json.Marshal(sample)
+			if err != nil || len(data)+2 > maxSize {
+				dropped++
+				continue
+			}
+			// One byte for the separator, one for the closing bracket
+			if message.Len() > 0 && message.Len()+len(data)+2 > maxSize {
+				flush()
+			}
+			if message.Len() == 0 {
+				message.WriteByte('[')
+			} else {
+				message.WriteByte(',')
+			}
+			message.Write(data)
+		}
+	}
+	flush()
+	return messages, dropped
+}
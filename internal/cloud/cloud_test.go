@@ -0,0 +1,176 @@
+package cloud
+
+import (
+	"context"
+	"crypto/hmac"
+	"crypto/sha256"
+	"encoding/base64"
+	"encoding/json"
+	"errors"
+	"net/url"
+	"strings"
+	"testing"
+	"time"
+
+	mqtt "github.com/eclipse/paho.mqtt.golang"
+	"github.com/stretchr/testify/assert"
+	"go.uber.org/zap"
+
+	"github.com/bifrost/go-gateway/internal/forward"
+	"github.com/bifrost/go-gateway/internal/protocols"
+)
+
+// token is a completed publish
+type token struct {
+	err error
+}
+
+func (t token) Wait() bool                     { return true }
+func (t token) WaitTimeout(time.Duration) bool { return true }
+func (t token) Error() error                   { return t.err }
+
+func (t token) Done() <-chan struct{} {
+	done := make(chan struct{})
+	close(done)
+	return done
+}
+
+// broker records published messages
+type broker struct {
+	topics   []string
+	messages []string
+	err      error
+}
+
+func (b *broker) Publish(topic string, qos byte, retained bool, payload interface{}) mqtt.Token {
+	if b.err == nil {
+		b.topics = append(b.topics, topic)
+		b.messages = append(b.messages, string(payload.([]byte)))
+	}
+	return token{err: b.err}
+}
+
+func TestSASToken(t *testing.T) {
+	key := []byte("device key")
+	expiry := time.Unix(1700000000, 0)
+	sas := sasToken("hub.azure-devices.net/devices/press-1", key, expiry)
+
+	assert.True(t, strings.HasPrefix(sas, "SharedAccessSignature "))
+	values, err := url.ParseQuery(strings.TrimPrefix(sas, "SharedAccessSignature "))
+	assert.NoError(t, err)
+	assert.Equal(t, "hub.azure-devices.net/devices/press-1", values.Get("sr"))
+	assert.Equal(t, "1700000000", values.Get("se"))
+
+	mac := hmac.New(sha256.New, key)
+	mac.Write([]byte("hub.azure-devices.net%2Fdevices%2Fpress-1\n1700000000"))
+	assert.Equal(t, base64.StdEncoding.EncodeToString(mac.Sum(nil)), values.Get("sig"))
+}
+
+func TestAzureConnection(t *testing.T) {
+	key := base64.StdEncoding.EncodeToString([]byte("device key"))
+	conn, err := azureConnection(AzureConfig{
+		ConnectionString: "HostName=hub.azure-devices.net;DeviceId=press-1;SharedAccessKey=" + key,
+	})
+	assert.NoError(t, err)
+	assert.Equal(t, "devices/press-1/messages/events/$.ct=application%2Fjson&$.ce=utf-8", conn.topic)
+	assert.Equal(t, azureMaxMessageSize, conn.maxMessageSize)
+	assert.Equal(t, "wss://hub.azure-devices.net:443/$iothub/websocket", conn.options.Servers[0].String())
+	assert.Equal(t, "press-1", conn.options.ClientID)
+
+	username, password := conn.options.CredentialsProvider()
+	assert.Equal(t, "hub.azure-devices.net/press-1/?api-version="+azureAPIVersion, username)
+	assert.Contains(t, password, "sr=hub.azure-devices.net%2Fdevices%2Fpress-1&")
+
+	_, err = azureConnection(AzureConfig{ConnectionString: "HostName=hub.azure-devices.net;DeviceId=press-1"})
+	assert.Error(t, err)
+	_, err = azureConnection(AzureConfig{HostName: "hub", DeviceID: "press-1", SharedAccessKey: "not base64!"})
+	assert.Error(t, err)
+	_, err = azureConnection(AzureConfig{HostName: "hub", DeviceID: "press-1", SharedAccessKey: key, Transport: "amqp"})
+	assert.Error(t, err)
+}
+
+func TestNewSink_Invalid(t *testing.T) {
+	_, err := NewSink(Config{Name: "uplink", Provider: "gcp"}, zap.NewNop())
+	assert.Error(t, err)
+	_, err = NewSink(Config{Name: "uplink", Provider: ProviderAWS, AWS: AWSConfig{Endpoint: "iot.example.com"}}, zap.NewNop())
+	assert.Error(t, err)
+}
+
+func TestValidate(t *testing.T) {
+	key := base64.StdEncoding.EncodeToString([]byte("device key"))
+	config := Config{
+		Name:     "uplink",
+		Provider: ProviderAzure,
+		Azure:    AzureConfig{ConnectionString: "HostName=hub.azure-devices.net;DeviceId=press-1;SharedAccessKey=" + key},
+		Queue:    forward.QueueConfig{Dir: t.TempDir()},
+	}
+	assert.NoError(t, Validate(config))
+
+	config.Provider = "gcp"
+	assert.Error(t, Validate(config))
+	config.Provider, config.Queue.Dir = ProviderAzure, ""
+	assert.Error(t, Validate(config))
+}
+
+func TestPack(t *testing.T) {
+	a, b, c := encode(t, sample("a")), encode(t, sample("b")), encode(t, sample("c"))
+	records := [][]byte{
+		encode(t, []protocols.TagSample{sample("a"), sample("b")}),
+		[]byte(`not json`),
+		encode(t, []protocols.TagSample{sample("c"), sample(strings.Repeat("x", 64))}),
+	}
+
+	// Two samples fit into a message, the long one does not fit at all
+	messages, dropped := pack(records, 2*len(a)+3, noTransform)
+	assert.Equal(t, 2, dropped)
+	assert.Equal(t, []string{"[" + string(a) + "," + string(b) + "]", "[" + string(c) + "]"}, toStrings(messages))
+
+	// The transform sees the samples of each record
+	rename := func(samples []protocols.TagSample) []protocols.TagSample {
+		for i := range samples {
+			samples[i].Name = "renamed"
+		}
+		return samples
+	}
+	messages, dropped = pack(records[:1], 1024, rename)
+	assert.Zero(t, dropped)
+	renamed := string(encode(t, sample("renamed")))
+	assert.Equal(t, []string{"[" + renamed + "," + renamed + "]"}, toStrings(messages))
+}
+
+func TestSender(t *testing.T) {
+	one := encode(t, sample("v"))
+	b := &broker{}
+	send := sender(b, "things/press-1", 2*len(one)+3, noTransform, "uplink", zap.NewNop())
+
+	record := encode(t, []protocols.TagSample{sample("v"), sample("v"), sample("v")})
+	assert.NoError(t, send(context.Background(), [][]byte{record}))
+	assert.Equal(t, []string{"[" + string(one) + "," + string(one) + "]", "[" + string(one) + "]"}, b.messages)
+	assert.Equal(t, []string{"things/press-1", "things/press-1"}, b.topics)
+
+	// A rejected message fails the batch so the forwarder retries it
+	b.err = errors.New("not authorized")
+	assert.Error(t, send(context.Background(), [][]byte{record}))
+}
+
+func noTransform(samples []protocols.TagSample) []protocols.TagSample {
+	return samples
+}
+
+func sample(name string) protocols.TagSample {
+	return protocols.TagSample{Name: name, Value: 1.5, Quality: protocols.QualityGood, Timestamp: time.Unix(1700000000, 0).UTC()}
+}
+
+func encode(t *testing.T, v interface{}) []byte {
+	data, err := json.Marshal(v)
+	assert.NoError(t, err)
+	return data
+}
+
+func toStrings(messages [][]byte) []string {
+	var strs []string
+	for _, message := range messages {
+		strs = append(strs, string(message))
+	}
+	return strs
+}
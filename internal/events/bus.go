@@ -0,0 +1,378 @@
+// Package events connects gateway components through a publish/subscribe bus
+package events
+
+import (
+	"sync"
+	"sync/atomic"
+	"time"
+
+	"go.uber.org/zap"
+
+	"github.com/bifrost/go-gateway/internal/backpressure"
+	"github.com/bifrost/go-gateway/internal/protocols"
+)
+
+// Event Bus
+//
+// Bus is an in-process publish/subscribe bus between the components of
+// the gateway: drivers publish data and connection state, the rule engine
+// publishes alarms, and storage, sinks and the rule engine subscribe to
+// what they need instead of being called directly. Each subscriber
+// receives its events in publish order on its own goroutine, so a slow
+// subscriber does not hold up the others. When a subscriber's buffer is
+// full, events for it are dropped and counted, unless it subscribed as
+// blocking, in which case Publish waits.
+
+// eventSize is the estimated memory of a queued event with its samples
+const eventSize = 512
+
+// Kind identifies the type of an event
+type Kind string
+
+const (
+	KindData       Kind = "data"
+	KindAlarm      Kind = "alarm"
+	KindConnection Kind = "connection"
+)
+
+// Event is published on the bus
+type Event interface {
+	Kind() Kind
+}
+
+// DataEvent carries samples collected from a device. Sample names are
+// SampleName(device, tag).
+type DataEvent struct {
+	DeviceID string                `json:"device_id"`
+	Samples  []protocols.TagSample `json:"samples"`
+}
+
+// Kind implements Event
+func (DataEvent) Kind() Kind { return KindData }
+
+// AlarmEvent is an alarm or notification, e.g. raised by a rule
+type AlarmEvent struct {
+	Name     string                 `json:"name"`
+	Severity string                 `json:"severity,omitempty"`
+	Message  string                 `json:"message,omitempty"`
+	Source   string                 `json:"source"` // E.g. the ID of the rule
+	Time     time.Time              `json:"time"`
+	Values   map[string]interface{} `json:"values,omitempty"`
+}
+
+// Kind implements Event
+func (AlarmEvent) Kind() Kind { return KindAlarm }
+
+// ConnectionEvent reports that a device connected or disconnected
+type ConnectionEvent struct {
+	DeviceID  string    `json:"device_id"`
+	Protocol  string    `json:"protocol"`
+	Connected bool      `json:"connected"`
+	Error     string    `json:"error,omitempty"`
+	Time      time.Time `json:"time"`
+}
+
+// Kind implements Event
+func (ConnectionEvent) Kind() Kind { return KindConnection }
+
+// SampleName is the name of a device tag in data events, e.g.
+// "site/line/pump/Flow"
+func SampleName(deviceID, tagID string) string {
+	return deviceID + "/" + tagID
+}
+
+// Handler receives events
+type Handler func(event Event)
+
+// OnData returns a handler calling handler with data events
+func OnData(handler func(DataEvent)) Handler {
+	return func(event Event) {
+		if data, ok := event.(DataEvent); ok {
+			handler(data)
+		}
+	}
+}
+
+// OnAlarm returns a handler calling handler with alarm events
+func OnAlarm(handler func(AlarmEvent)) Handler {
+	return func(event Event) {
+		if alarm, ok := event.(AlarmEvent); ok {
+			handler(alarm)
+		}
+	}
+}
+
+// OnConnection returns a handler calling handler with connection events
+func OnConnection(handler func(ConnectionEvent)) Handler {
+	return func(event Event) {
+		if connection, ok := event.(ConnectionEvent); ok {
+			handler(connection)
+		}
+	}
+}
+
+// SinkHandler returns a handler writing the samples of data events to
+// sink, e.g. a store-and-forward queue or the rule engine
+func SinkHandler(sink protocols.SampleSink, logger *zap.Logger) Handler {
+	return OnData(func(data DataEvent) {
+		if err := sink.WriteSamples(data.Samples); err != nil {
+			logger.Warn("Failed to write samples",
+				zap.String("device", data.DeviceID),
+				zap.Int("samples", len(data.Samples)),
+				zap.Error(err),
+			)
+		}
+	})
+}
+
+// SubscribeOptions configures a subscription
+type SubscribeOptions struct {
+	Kinds    []Kind // Empty for all kinds
+	Buffer   int    // Queued events, default 1024
+	Blocking bool   // Publish waits for buffer space instead of dropping
+}
+
+// SubscriptionStats are the delivery counters of a subscription
+type SubscriptionStats struct {
+	Name      string `json:"name"`
+	Kinds     []Kind `json:"kinds,omitempty"`
+	Delivered uint64 `json:"delivered"`
+	Dropped   uint64 `json:"dropped"`
+	Pending   int    `json:"pending"`
+}
+
+// Subscription delivers events to a handler
+type Subscription struct {
+	delivered uint64 // First for 64-bit alignment of atomic access
+	dropped   uint64
+
+	bus     *Bus
+	name    string
+	options SubscribeOptions
+	kinds   map[Kind]bool
+	handler Handler
+	done    chan struct{}
+
+	mutex  sync.RWMutex
+	events chan Event
+	closed bool
+}
+
+// Unsubscribe stops the subscription after the queued events are handled
+func (s *Subscription) Unsubscribe() {
+	s.bus.remove(s)
+
+	s.mutex.Lock()
+	if !s.closed {
+		s.closed = true
+		close(s.events)
+	}
+	s.mutex.Unlock()
+	<-s.done
+}
+
+// Stats returns the delivery counters
+func (s *Subscription) Stats() SubscriptionStats {
+	return SubscriptionStats{
+		Name:      s.name,
+		Kinds:     s.options.Kinds,
+		Delivered: atomic.LoadUint64(&s.delivered),
+		Dropped:   atomic.LoadUint64(&s.dropped),
+		Pending:   len(s.events),
+	}
+}
+
+// Pressure reports how full the subscription's buffer is
+func (s *Subscription) Pressure() backpressure.Pressure {
+	pending := len(s.events)
+	return backpressure.Pressure{Depth: pending, FillRatio: float64(pending) / float64(cap(s.events))}
+}
+
+func (s *Subscription) accepts(kind Kind) bool {
+	return len(s.kinds) == 0 || s.kinds[kind]
+}
+
+// deliver queues an event. If the event is dropped it returns the number
+// of events dropped so far, otherwise 0.
+func (s *Subscription) deliver(event Event) uint64 {
+	s.mutex.RLock()
+	defer s.mutex.RUnlock()
+	if s.closed {
+		return 0
+	}
+
+	if s.options.Blocking {
+		s.events <- event
+		return 0
+	}
+	select {
+	case s.events <- event:
+		return 0
+	default:
+		return atomic.AddUint64(&s.dropped, 1)
+	}
+}
+
+func (s *Subscription) run(logger *zap.Logger) {
+	defer close(s.done)
+	for event := range s.events {
+		s.handle(event, logger)
+		atomic.AddUint64(&s.delivered, 1)
+	}
+}
+
+// handle calls the handler, recovering from a panic so that one faulty
+// subscriber does not stop the gateway
+func (s *Subscription) handle(event Event, logger *zap.Logger) {
+	defer func() {
+		if r := recover(); r != nil {
+			logger.Error("Event handler panicked",
+				zap.String("subscriber", s.name),
+				zap.String("kind", string(event.Kind())),
+				zap.Any("panic", r),
+			)
+		}
+	}()
+	s.handler(event)
+}
+
+// Bus is an in-process event bus
+type Bus struct {
+	logger *zap.Logger
+
+	mutex         sync.RWMutex
+	subscriptions []*Subscription
+}
+
+// NewBus creates a bus without subscribers
+func NewBus(logger *zap.Logger) *Bus {
+	return &Bus{logger: logger}
+}
+
+// Subscribe delivers events of the given kinds to handler until the
+// subscription is cancelled. The name identifies the subscriber in logs
+// and statistics.
+func (b *Bus) Subscribe(name string, options SubscribeOptions, handler Handler) *Subscription {
+	if options.Buffer <= 0 {
+		options.Buffer = 1024
+	}
+	s := &Subscription{
+		bus:     b,
+		name:    name,
+		options: options,
+		kinds:   make(map[Kind]bool, len(options.Kinds)),
+		handler: handler,
+		events:  make(chan Event, options.Buffer),
+		done:    make(chan struct{}),
+	}
+	for _, kind := range options.Kinds {
+		s.kinds[kind] = true
+	}
+	go s.run(b.logger)
+
+	b.mutex.Lock()
+	defer b.mutex.Unlock()
+	b.subscriptions = append(b.subscriptions[:len(b.subscriptions):len(b.subscriptions)], s)
+	return s
+}
+
+// Publish delivers an event to the subscribers of its kind. Handlers may
+// publish events themselves.
+func (b *Bus) Publish(event Event) {
+	kind := event.Kind()
+
+	// The slice is replaced, never modified, when subscribers change
+	b.mutex.RLock()
+	subscriptions := b.subscriptions
+	b.mutex.RUnlock()
+
+	for _, s := range subscriptions {
+		if !s.accepts(kind) {
+			continue
+		}
+		if dropped := s.deliver(event); dropped == 1 || dropped > 0 && dropped%1000 == 0 {
+			b.logger.Warn("Event subscriber is too slow, dropping events",
+				zap.String("subscriber", s.name),
+				zap.String("kind", string(kind)),
+				zap.Uint64("dropped", dropped),
+			)
+		}
+	}
+}
+
+// Stats returns the counters of all subscriptions
+func (b *Bus) Stats() []SubscriptionStats {
+	b.mutex.RLock()
+	defer b.mutex.RUnlock()
+
+	stats := make([]SubscriptionStats, len(b.subscriptions))
+	for i, s := range b.subscriptions {
+		stats[i] = s.Stats()
+	}
+	return stats
+}
+
+// Pressure reports the queued events of all subscriptions and the fill
+// ratio of the fullest buffer
+func (b *Bus) Pressure() backpressure.Pressure {
+	b.mutex.RLock()
+	defer b.mutex.RUnlock()
+
+	var pressure backpressure.Pressure
+	for _, s := range b.subscriptions {
+		p := s.Pressure()
+		pressure.Depth += p.Depth
+		if p.FillRatio > pressure.FillRatio {
+			pressure.FillRatio = p.FillRatio
+		}
+	}
+	return pressure
+}
+
+// MemoryUsage estimates the bytes held by queued events
+func (b *Bus) MemoryUsage() int64 {
+	return int64(b.Pressure().Depth) * eventSize
+}
+
+// Close unsubscribes all subscribers after their queued events are handled
+func (b *Bus) Close() {
+	b.mutex.RLock()
+	subscriptions := b.subscriptions
+	b.mutex.RUnlock()
+
+	for _, s := range subscriptions {
+		s.Unsubscribe()
+	}
+}
+
+// remove removes a subscription
+func (b *Bus) remove(s *Subscription) {
+	b.mutex.Lock()
+	defer b.mutex.Unlock()
+
+	subscriptions := make([]*Subscription, 0, len(b.subscriptions))
+	for _, subscribed := range b.subscriptions {
+		if subscribed != s {
+			subscriptions = append(subscriptions, subscribed)
+		}
+	}
+	b.subscriptions = subscriptions
+}
+
+// PublishTags publishes changed tags as a data event. It is a
+// protocols.TagChangeFunc, so driver subscriptions can publish directly.
+func (b *Bus) PublishTags(device *protocols.Device, tags []*protocols.Tag) {
+	samples := make([]protocols.TagSample, len(tags))
+	for i, tag := range tags {
+		samples[i] = protocols.TagSample{
+			Name:          SampleName(device.ID, tag.ID),
+			Value:         tag.Value,
+			Quality:       tag.Quality,
+			Substatus:     tag.Substatus,
+			QualitySource: tag.QualitySource,
+			Timestamp:     tag.Timestamp,
+			Unit:          tag.Unit,
+		}
+	}
+	b.Publish(DataEvent{DeviceID: device.ID, Samples: samples})
+}
@@ -0,0 +1,142 @@
+package events
+
+import (
+	"sync"
+	"testing"
+	"time"
+
+	"github.com/stretchr/testify/assert"
+	"go.uber.org/zap"
+
+	"github.com/bifrost/go-gateway/internal/protocols"
+)
+
+// eventLog records handled events
+type eventLog struct {
+	mutex  sync.Mutex
+	events []Event
+}
+
+func (l *eventLog) handle(event Event) {
+	l.mutex.Lock()
+	defer l.mutex.Unlock()
+	l.events = append(l.events, event)
+}
+
+func (l *eventLog) kinds() []Kind {
+	l.mutex.Lock()
+	defer l.mutex.Unlock()
+
+	var kinds []Kind
+	for _, event := range l.events {
+		kinds = append(kinds, event.Kind())
+	}
+	return kinds
+}
+
+func TestBus_Subscribe(t *testing.T) {
+	bus := NewBus(zap.NewNop())
+	all, alarms := &eventLog{}, &eventLog{}
+	bus.Subscribe("all", SubscribeOptions{}, all.handle)
+	bus.Subscribe("alarms", SubscribeOptions{Kinds: []Kind{KindAlarm}}, alarms.handle)
+
+	var connections []ConnectionEvent
+	bus.Subscribe("connections", SubscribeOptions{}, OnConnection(func(event ConnectionEvent) {
+		connections = append(connections, event)
+	}))
+
+	bus.Publish(ConnectionEvent{DeviceID: "site/pump", Connected: true})
+	bus.Publish(DataEvent{DeviceID: "site/pump"})
+	bus.Publish(AlarmEvent{Name: "HighLevel", Source: "high-level"})
+	bus.Close()
+
+	assert.Equal(t, []Kind{KindConnection, KindData, KindAlarm}, all.kinds())
+	assert.Equal(t, []Kind{KindAlarm}, alarms.kinds())
+	assert.Equal(t, []ConnectionEvent{{DeviceID: "site/pump", Connected: true}}, connections)
+
+	// Nothing is delivered after Close
+	bus.Publish(AlarmEvent{Name: "HighLevel"})
+	assert.Len(t, alarms.kinds(), 1)
+	assert.Empty(t, bus.Stats())
+}
+
+func TestBus_SlowSubscriber(t *testing.T) {
+	bus := NewBus(zap.NewNop())
+	release := make(chan struct{})
+	slow := bus.Subscribe("slow", SubscribeOptions{Buffer: 1}, func(event Event) { <-release })
+	fast := &eventLog{}
+	bus.Subscribe("fast", SubscribeOptions{}, fast.handle)
+
+	// The first event is being handled, the second queued, the rest dropped
+	bus.Publish(AlarmEvent{Name: "1"})
+	assert.Eventually(t, func() bool { return slow.Stats().Pending == 0 }, time.Second, time.Millisecond)
+	for i := 0; i < 4; i++ {
+		bus.Publish(AlarmEvent{Name: "n"})
+	}
+	assert.Equal(t, uint64(3), slow.Stats().Dropped)
+	assert.Equal(t, 1.0, slow.Pressure().FillRatio)
+	assert.Equal(t, 1.0, bus.Pressure().FillRatio)
+
+	close(release)
+	bus.Close()
+	assert.Equal(t, uint64(2), slow.Stats().Delivered)
+	assert.Len(t, fast.kinds(), 5)
+}
+
+func TestBus_BlockingSubscriber(t *testing.T) {
+	bus := NewBus(zap.NewNop())
+	log := &eventLog{}
+	subscription := bus.Subscribe("storage", SubscribeOptions{Buffer: 1, Blocking: true}, func(event Event) {
+		time.Sleep(time.Millisecond)
+		log.handle(event)
+	})
+
+	for i := 0; i < 10; i++ {
+		bus.Publish(DataEvent{DeviceID: "site/pump"})
+	}
+	subscription.Unsubscribe()
+	assert.Len(t, log.kinds(), 10)
+	assert.Equal(t, uint64(0), subscription.Stats().Dropped)
+}
+
+func TestBus_HandlerPanic(t *testing.T) {
+	bus := NewBus(zap.NewNop())
+	var handled int
+	bus.Subscribe("faulty", SubscribeOptions{}, OnAlarm(func(event AlarmEvent) {
+		handled++
+		if event.Name == "panic" {
+			panic("faulty handler")
+		}
+	}))
+
+	bus.Publish(AlarmEvent{Name: "panic"})
+	bus.Publish(AlarmEvent{Name: "ok"})
+	bus.Close()
+	assert.Equal(t, 2, handled)
+}
+
+type busTestSink struct {
+	samples []protocols.TagSample
+}
+
+func (s *busTestSink) WriteSamples(samples []protocols.TagSample) error {
+	s.samples = append(s.samples, samples...)
+	return nil
+}
+
+func TestBus_PublishTags(t *testing.T) {
+	bus := NewBus(zap.NewNop())
+	sink := &busTestSink{}
+	bus.Subscribe("sink", SubscribeOptions{Kinds: []Kind{KindData}}, SinkHandler(sink, zap.NewNop()))
+
+	now := time.Now()
+	device := &protocols.Device{ID: "site/line/pump"}
+	bus.PublishTags(device, []*protocols.Tag{
+		{ID: "Flow", Value: 12.5, Quality: protocols.QualityGood, Timestamp: now, Unit: "m3/h"},
+	})
+	bus.Close()
+
+	assert.Equal(t, []protocols.TagSample{
+		{Name: "site/line/pump/Flow", Value: 12.5, Quality: protocols.QualityGood, Timestamp: now, Unit: "m3/h"},
+	}, sink.samples)
+}
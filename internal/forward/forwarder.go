@@ -0,0 +1,189 @@
+package forward
+
+import (
+	"context"
+	"encoding/json"
+	"fmt"
+	"sync"
+	"time"
+
+	"go.uber.org/zap"
+
+	"github.com/bifrost/go-gateway/internal/backpressure"
+	"github.com/bifrost/go-gateway/internal/protocols"
+)
+
+// Forwarder
+//
+// A Forwarder delivers the records of a Queue to an uplink in batches,
+// e.g. an MQTT broker, InfluxDB or a cloud service. A batch that fails is
+// retried with exponential backoff until it is delivered, so records
+// buffered during an outage arrive in order once the uplink is back.
+
+// Sender delivers a batch of records. It returns an error if any record
+// was not delivered; the whole batch is sent again.
+type Sender func(ctx context.Context, records [][]byte) error
+
+// ForwarderConfig configures batching and retries
+type ForwarderConfig struct {
+	BatchSize     int           `yaml:"batch_size"`      // Default 100
+	RetryDelay    time.Duration `yaml:"retry_delay"`     // Default 1s
+	MaxRetryDelay time.Duration `yaml:"max_retry_delay"` // Default 1m
+}
+
+// Forwarder moves records from a queue to a Sender
+type Forwarder struct {
+	queue  *Queue
+	send   Sender
+	config ForwarderConfig
+	logger *zap.Logger
+
+	// The failure of the batch being retried, for health checks
+	mutex        sync.Mutex
+	failingSince time.Time
+	failure      error
+}
+
+// NewForwarder creates a forwarder of queue to send
+func NewForwarder(queue *Queue, send Sender, config ForwarderConfig, logger *zap.Logger) *Forwarder {
+	if config.BatchSize <= 0 {
+		config.BatchSize = 100
+	}
+	if config.RetryDelay <= 0 {
+		config.RetryDelay = time.Second
+	}
+	if config.MaxRetryDelay < config.RetryDelay {
+		config.MaxRetryDelay = time.Minute
+	}
+	return &Forwarder{
+		queue:  queue,
+		send:   send,
+		config: config,
+		logger: logger,
+	}
+}
+
+// Run forwards records until ctx is done or the queue is closed
+func (f *Forwarder) Run(ctx context.Context) error {
+	delay := f.config.RetryDelay
+	for {
+		records, err := f.queue.Peek(ctx, f.config.BatchSize)
+		if err != nil {
+			return err
+		}
+
+		if err := f.send(ctx, records); err != nil {
+			f.setFailure(err)
+			f.logger.Warn("Failed to forward records, retrying",
+				zap.Int("records", len(records)),
+				zap.Duration("delay", delay),
+				zap.Error(err),
+			)
+			select {
+			case <-ctx.Done():
+				return ctx.Err()
+			case <-time.After(delay):
+			}
+			delay *= 2
+			if delay > f.config.MaxRetryDelay {
+				delay = f.config.MaxRetryDelay
+			}
+			continue
+		}
+
+		f.setFailure(nil)
+		delay = f.config.RetryDelay
+		if err := f.queue.Ack(len(records)); err != nil {
+			return err
+		}
+	}
+}
+
+// Check fails while a batch is being retried, e.g. as a health check of
+// the uplink
+func (f *Forwarder) Check(ctx context.Context) error {
+	f.mutex.Lock()
+	defer f.mutex.Unlock()
+	if f.failure == nil {
+		return nil
+	}
+	return fmt.Errorf("forwarding failing since %s: %w", f.failingSince.Format(time.RFC3339), f.failure)
+}
+
+func (f *Forwarder) setFailure(err error) {
+	f.mutex.Lock()
+	defer f.mutex.Unlock()
+	if err != nil && f.failure == nil {
+		f.failingSince = time.Now()
+	}
+	f.failure = err
+}
+
+// SampleQueue is a protocols.SampleSink that queues samples for forwarding
+type SampleQueue struct {
+	queue *Queue
+}
+
+// NewSampleQueue creates a sample sink that appends to queue
+func NewSampleQueue(queue *Queue) *SampleQueue {
+	return &SampleQueue{queue: queue}
+}
+
+// WriteSamples queues the samples as one record
+func (s *SampleQueue) WriteSamples(samples []protocols.TagSample) error {
+	if len(samples) == 0 {
+		return nil
+	}
+	data, err := json.Marshal(samples)
+	if err != nil {
+		return err
+	}
+	return s.queue.Append(data)
+}
+
+// WriteSamplesWait queues the samples as one record, waiting while the
+// queue is full
+func (s *SampleQueue) WriteSamplesWait(ctx context.Context, samples []protocols.TagSample) error {
+	if len(samples) == 0 {
+		return nil
+	}
+	data, err := json.Marshal(samples)
+	if err != nil {
+		return err
+	}
+	return s.queue.AppendWait(ctx, data)
+}
+
+// Pressure returns the pressure of the queue
+func (s *SampleQueue) Pressure() backpressure.Pressure {
+	return s.queue.Pressure()
+}
+
+// MemoryUsage returns the bytes queued but not yet written to disk
+func (s *SampleQueue) MemoryUsage() int64 {
+	return s.queue.MemoryUsage()
+}
+
+// Flush writes the queued samples to disk
+func (s *SampleQueue) Flush() error {
+	return s.queue.Flush()
+}
+
+// SampleSender returns a Sender that writes records queued by a
+// SampleQueue to sink. Records that do not decode are skipped.
+func SampleSender(sink protocols.SampleSink) Sender {
+	return func(ctx context.Context, records [][]byte) error {
+		var samples []protocols.TagSample
+		for _, record := range records {
+			var batch []protocols.TagSample
+			if err := json.Unmarshal(record, &batch); err != nil {
+				continue
+			}
+			samples = append(samples, batch...)
+		}
+		if len(samples) == 0 {
+			return nil
+		}
+		return sink.WriteSamples(samples)
+	}
+}
@@ -0,0 +1,109 @@
+package forward
+
+import (
+	"context"
+	"errors"
+	"sync"
+	"testing"
+	"time"
+
+	"github.com/stretchr/testify/assert"
+	"go.uber.org/zap"
+
+	"github.com/bifrost/go-gateway/internal/protocols"
+)
+
+type forwardTestSink struct {
+	mutex   sync.Mutex
+	samples []protocols.TagSample
+}
+
+func (s *forwardTestSink) WriteSamples(samples []protocols.TagSample) error {
+	s.mutex.Lock()
+	defer s.mutex.Unlock()
+
+	s.samples = append(s.samples, samples...)
+	return nil
+}
+
+func (s *forwardTestSink) names() []string {
+	s.mutex.Lock()
+	defer s.mutex.Unlock()
+
+	var names []string
+	for _, sample := range s.samples {
+		names = append(names, sample.Name)
+	}
+	return names
+}
+
+func TestForwarder_RetriesInOrder(t *testing.T) {
+	queue := openTestQueue(t, QueueConfig{})
+	for i := 0; i < 5; i++ {
+		assert.NoError(t, queue.Append(record(i)))
+	}
+
+	// The uplink is down for the first two attempts
+	var mutex sync.Mutex
+	var attempts int
+	var delivered [][]byte
+	var forwarder *Forwarder
+	var failing error
+	ctx, cancel := context.WithCancel(context.Background())
+	send := func(ctx context.Context, records [][]byte) error {
+		mutex.Lock()
+		defer mutex.Unlock()
+		attempts++
+		if attempts <= 2 {
+			return errors.New("connection refused")
+		}
+		if attempts == 3 {
+			failing = forwarder.Check(ctx)
+		}
+		delivered = append(delivered, records...)
+		return nil
+	}
+
+	forwarder = NewForwarder(queue, send, ForwarderConfig{BatchSize: 2, RetryDelay: time.Millisecond}, zap.NewNop())
+	done := make(chan error)
+	go func() { done <- forwarder.Run(ctx) }()
+
+	assert.Eventually(t, func() bool { return queue.Len() == 0 }, time.Second, time.Millisecond)
+	cancel()
+	assert.ErrorIs(t, <-done, context.Canceled)
+
+	mutex.Lock()
+	defer mutex.Unlock()
+	assert.Equal(t, [][]byte{record(0), record(1), record(2), record(3), record(4)}, delivered)
+	assert.Equal(t, 5, attempts)
+
+	// The uplink was reported failing until a batch was delivered
+	assert.ErrorContains(t, failing, "connection refused")
+	assert.NoError(t, forwarder.Check(ctx))
+}
+
+func TestSampleQueue(t *testing.T) {
+	queue := openTestQueue(t, QueueConfig{})
+	samples := NewSampleQueue(queue)
+	now := time.Now()
+
+	assert.NoError(t, samples.WriteSamples([]protocols.TagSample{
+		{Name: "Flow", Value: 12.5, Quality: protocols.QualityGood, Timestamp: now},
+		{Name: "Pressure", Value: 3.2, Quality: protocols.QualityGood, Timestamp: now},
+	}))
+	assert.NoError(t, queue.Append([]byte("not samples")))
+	assert.NoError(t, samples.WriteSamples([]protocols.TagSample{
+		{Name: "Level", Value: 80.0, Quality: protocols.QualityUncertain, Timestamp: now},
+	}))
+	assert.NoError(t, samples.WriteSamples(nil))
+	assert.Equal(t, 3, queue.Len())
+
+	sink := &forwardTestSink{}
+	forwarder := NewForwarder(queue, SampleSender(sink), ForwarderConfig{}, zap.NewNop())
+	ctx, cancel := context.WithCancel(context.Background())
+	defer cancel()
+	go forwarder.Run(ctx)
+
+	assert.Eventually(t, func() bool { return queue.Len() == 0 }, time.Second, time.Millisecond)
+	assert.Equal(t, []string{"Flow", "Pressure", "Level"}, sink.names())
+}
@@ -0,0 +1,106 @@
+package forward
+
+import (
+	"errors"
+	"fmt"
+	"os"
+	"path/filepath"
+	"strconv"
+	"strings"
+)
+
+// Directory Lock
+//
+// Two processes appending to the same queue directory would corrupt each
+// other's segments, so a queue holds an advisory lock on a lock file in
+// its directory while it is open. The file holds the PID of the owner for
+// the error of the process that is refused. The operating system releases
+// the lock when its process exits, so a crashed gateway does not block its
+// restart. A lock that outlives its owner, e.g. inherited by a child
+// process, is removed with Unlock, which refuses while the owner runs.
+// Queues cannot be opened on platforms without file locks.
+
+// lockFileName is the lock file in a queue directory
+const lockFileName = "lock"
+
+// errLocked is returned by lockFile when another process holds the lock
+var errLocked = errors.New("locked")
+
+// dirLock is a held queue directory lock
+type dirLock struct {
+	file *os.File
+}
+
+// lockDir locks dir or returns ErrQueueLocked with the PID of the owner
+func lockDir(dir string) (*dirLock, error) {
+	path := filepath.Join(dir, lockFileName)
+	file, err := os.OpenFile(path, os.O_CREATE|os.O_RDWR, 0o644)
+	if err != nil {
+		return nil, err
+	}
+	if err := lockFile(file); err != nil {
+		file.Close()
+		if errors.Is(err, errLocked) {
+			owner, _ := os.ReadFile(path)
+			return nil, fmt.Errorf("%w: %s held by PID %s", ErrQueueLocked, dir, strings.TrimSpace(string(owner)))
+		}
+		return nil, err
+	}
+
+	if err := file.Truncate(0); err != nil {
+		file.Close()
+		return nil, err
+	}
+	if _, err := file.WriteAt([]byte(strconv.Itoa(os.Getpid())+"\n"), 0); err != nil {
+		file.Close()
+		return nil, err
+	}
+	return &dirLock{file: file}, nil
+}
+
+// unlock releases the lock. The file is left in place: removing it could
+// remove the file another process has just locked.
+func (l *dirLock) unlock() error {
+	return l.file.Close()
+}
+
+// Unlock removes the lock of the queue in dir for recovery, e.g. from the
+// command line. It fails if the process recorded as the owner is still
+// running. A queue that is not locked is left as it is.
+func Unlock(dir string) error {
+	path := filepath.Join(dir, lockFileName)
+	owner, err := os.ReadFile(path)
+	if os.IsNotExist(err) {
+		return nil
+	}
+	if err != nil {
+		return err
+	}
+
+	// The lock is free unless a process holds it
+	file, err := os.OpenFile(path, os.O_RDWR, 0o644)
+	if err != nil {
+		return err
+	}
+	err = lockFile(file)
+	file.Close()
+	if err == nil {
+		return nil
+	}
+	if !errors.Is(err, errLocked) {
+		return err
+	}
+
+	pid, err := strconv.Atoi(strings.TrimSpace(string(owner)))
+	if err != nil {
+		return fmt.Errorf("%s: invalid owner %q", path, strings.TrimSpace(string(owner)))
+	}
+	running, err := processRunning(pid)
+	if err != nil {
+		return err
+	}
+	if running {
+		return fmt.Errorf("%w: %s held by running PID %d", ErrQueueLocked, dir, pid)
+	}
+	return os.Remove(path)
+}
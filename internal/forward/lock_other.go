@@ -0,0 +1,19 @@
+//go:build !unix
+
+package forward
+
+import (
+	"fmt"
+	"os"
+	"runtime"
+)
+
+// lockFile fails: without file locks two processes could write a queue
+func lockFile(file *os.File) error {
+	return fmt.Errorf("store-and-forward queues need file locks, which are not supported on %s", runtime.GOOS)
+}
+
+// processRunning fails like lockFile
+func processRunning(pid int) (bool, error) {
+	return false, fmt.Errorf("store-and-forward queues need file locks, which are not supported on %s", runtime.GOOS)
+}
@@ -0,0 +1,695 @@
+// Package forward buffers outbound data on disk while uplinks are down
+package forward
+
+import (
+	"context"
+	"encoding/binary"
+	"errors"
+	"fmt"
+	"hash/crc32"
+	"os"
+	"path/filepath"
+	"sort"
+	"strconv"
+	"strings"
+	"sync"
+	"time"
+
+	"go.uber.org/zap"
+
+	"github.com/bifrost/go-gateway/internal/backpressure"
+	"github.com/bifrost/go-gateway/internal/protocols"
+)
+
+// Store and Forward Queue
+//
+// Queue is a durable FIFO of records. Records are appended to segment
+// files and read from the oldest unacknowledged record: a consumer peeks a
+// batch, delivers it and acknowledges it, so every record is delivered at
+// least once and in order, across restarts. The acknowledged position is
+// kept in an ack file. The queue is bounded by MaxSize: when full it drops
+// its oldest segment, or rejects appends with ErrQueueFull if configured.
+// Producers that must not lose records use AppendWait, which waits for
+// the consumer instead, or slow down on the queue's Pressure.
+//
+// Each record is framed as a 4 byte length and a 4 byte CRC-32 of the
+// data, both big-endian. A torn write at the end of a segment is truncated
+// when the queue is opened.
+
+var (
+	// ErrQueueFull is returned by Append when the queue is full and
+	// configured to reject records
+	ErrQueueFull = errors.New("queue full")
+	// ErrQueueClosed is returned by operations on a closed queue
+	ErrQueueClosed = errors.New("queue closed")
+	// ErrQueueLocked is returned by OpenQueue when another process has
+	// the queue open
+	ErrQueueLocked = errors.New("queue in use by another process")
+)
+
+const (
+	segmentSuffix      = ".seg"
+	ackFileName        = "ack"
+	recordHeaderSize   = 8
+	defaultSegmentSize = 4 << 20
+	defaultMaxSize     = 256 << 20
+)
+
+// QueueConfig configures a Queue. MaxSize must be at least twice the
+// segment size.
+type QueueConfig struct {
+	Dir            string `yaml:"dir"`
+	MaxSize        int64  `yaml:"max_size"`     // Bytes, default 256 MiB
+	SegmentSize    int64  `yaml:"segment_size"` // Bytes, default 4 MiB
+	Sync           bool   `yaml:"sync"`         // fsync every append and ack
+	RejectWhenFull bool   `yaml:"reject_when_full"`
+}
+
+// QueueStats is the state of a queue
+type QueueStats struct {
+	Records  int    `json:"records"` // Unacknowledged
+	Bytes    int64  `json:"bytes"`
+	Segments int    `json:"segments"`
+	Dropped  uint64 `json:"dropped"` // Dropped unacknowledged because the queue was full
+}
+
+// position is the offset of a record in a segment
+type position struct {
+	segment uint64
+	offset  int64
+}
+
+type segment struct {
+	id      uint64
+	size    int64
+	records int
+}
+
+// Queue is a disk-backed FIFO for a single consumer
+type Queue struct {
+	config QueueConfig
+	logger *zap.Logger
+	clock  protocols.Clock
+	lock   *dirLock
+
+	mutex       sync.Mutex
+	segments    []*segment
+	writer      *os.File // Last segment
+	reader      *os.File
+	readerID    uint64
+	head        position   // Oldest unacknowledged record
+	headRecords int        // Acknowledged records of the first segment
+	pending     []position // End of each record returned by the last Peek
+	records     int
+	size        int64
+	unsynced    int64 // Appended since the last fsync
+	dropped     uint64
+	closed      bool
+	signal      chan struct{}
+	freed       chan struct{} // Closed when a segment is removed
+	progress    time.Time     // Last ack, or append to an empty queue
+}
+
+// OpenQueue opens the queue in config.Dir, creating it if needed
+func OpenQueue(config QueueConfig, logger *zap.Logger) (*Queue, error) {
+	if config.Dir == "" {
+		return nil, fmt.Errorf("queue directory is required")
+	}
+	if config.SegmentSize <= 0 {
+		config.SegmentSize = defaultSegmentSize
+	}
+	if config.MaxSize <= 0 {
+		config.MaxSize = defaultMaxSize
+	}
+	if config.MaxSize < 2*config.SegmentSize {
+		return nil, fmt.Errorf("queue max size %d is less than two segments of %d bytes", config.MaxSize, config.SegmentSize)
+	}
+	if err := os.MkdirAll(config.Dir, 0o755); err != nil {
+		return nil, err
+	}
+	lock, err := lockDir(config.Dir)
+	if err != nil {
+		return nil, err
+	}
+
+	q := &Queue{
+		config: config,
+		logger: logger,
+		clock:  protocols.DefaultClock,
+		lock:   lock,
+		signal: make(chan struct{}, 1),
+		freed:  make(chan struct{}),
+	}
+	if err := q.load(); err != nil {
+		q.closeFiles()
+		lock.unlock()
+		return nil, err
+	}
+	return q, nil
+}
+
+// SetClock sets the clock measuring how long the consumer has not made
+// progress
+func (q *Queue) SetClock(clock protocols.Clock) {
+	q.mutex.Lock()
+	defer q.mutex.Unlock()
+	q.clock = clock
+	q.progress = clock.Now()
+}
+
+// load scans the segments and opens the last one for writing
+func (q *Queue) load() error {
+	ids, err := q.segmentIDs()
+	if err != nil {
+		return err
+	}
+	head, err := q.readAck()
+	if err != nil {
+		return err
+	}
+
+	for _, id := range ids {
+		if id < head.segment {
+			// Acknowledged before the segment could be removed
+			if err := os.Remove(q.segmentPath(id)); err != nil {
+				return err
+			}
+			continue
+		}
+		if len(q.segments) == 0 && id > head.segment {
+			// The acknowledged segment was dropped
+			head = position{segment: id}
+		}
+
+		from := int64(0)
+		if id == head.segment {
+			from = head.offset
+		}
+		info, err := os.Stat(q.segmentPath(id))
+		if err != nil {
+			return err
+		}
+		size, records, unread, err := scanSegment(q.segmentPath(id), from)
+		if err != nil {
+			return err
+		}
+		if size < info.Size() {
+			q.logger.Warn("Truncated corrupt store-and-forward segment",
+				zap.Uint64("segment", id),
+				zap.Int64("bytes", info.Size()-size),
+			)
+		}
+		if len(q.segments) == 0 {
+			q.headRecords = records - unread
+		}
+		q.segments = append(q.segments, &segment{id: id, size: size, records: records})
+		q.records += unread
+		q.size += size
+	}
+	q.head = head
+
+	if len(q.segments) == 0 {
+		q.segments = []*segment{{id: head.segment + 1}}
+		q.head = position{segment: head.segment + 1}
+		q.headRecords = 0
+	}
+	q.progress = q.clock.Now()
+	return q.openWriter()
+}
+
+// Append adds a record to the end of the queue
+func (q *Queue) Append(record []byte) error {
+	size, err := q.recordSize(record)
+	if err != nil {
+		return err
+	}
+
+	q.mutex.Lock()
+	defer q.mutex.Unlock()
+	if q.closed {
+		return ErrQueueClosed
+	}
+	return q.append(record, size)
+}
+
+// AppendWait adds a record like Append, but while the queue is full it
+// waits for the consumer to free space instead of dropping the oldest
+// records or returning ErrQueueFull
+func (q *Queue) AppendWait(ctx context.Context, record []byte) error {
+	size, err := q.recordSize(record)
+	if err != nil {
+		return err
+	}
+
+	for {
+		q.mutex.Lock()
+		if q.closed {
+			q.mutex.Unlock()
+			return ErrQueueClosed
+		}
+		if q.size+size <= q.config.MaxSize {
+			err := q.append(record, size)
+			q.mutex.Unlock()
+			return err
+		}
+		freed := q.freed
+		q.mutex.Unlock()
+
+		select {
+		case <-ctx.Done():
+			return ctx.Err()
+		case <-freed:
+		}
+	}
+}
+
+func (q *Queue) recordSize(record []byte) (int64, error) {
+	size := int64(recordHeaderSize + len(record))
+	if size > q.config.SegmentSize {
+		return 0, fmt.Errorf("record of %d bytes exceeds the segment size", len(record))
+	}
+	return size, nil
+}
+
+func (q *Queue) append(record []byte, size int64) error {
+	last := q.segments[len(q.segments)-1]
+	if last.size > 0 && last.size+size > q.config.SegmentSize {
+		if err := q.roll(); err != nil {
+			return err
+		}
+		last = q.segments[len(q.segments)-1]
+	}
+	for q.size+size > q.config.MaxSize && len(q.segments) > 1 {
+		if q.config.RejectWhenFull {
+			return ErrQueueFull
+		}
+		if err := q.dropOldest(); err != nil {
+			return err
+		}
+	}
+
+	frame := make([]byte, size)
+	binary.BigEndian.PutUint32(frame[0:], uint32(len(record)))
+	binary.BigEndian.PutUint32(frame[4:], crc32.ChecksumIEEE(record))
+	copy(frame[recordHeaderSize:], record)
+	if _, err := q.writer.Write(frame); err != nil {
+		return err
+	}
+	if q.config.Sync {
+		if err := q.writer.Sync(); err != nil {
+			return err
+		}
+	} else {
+		q.unsynced += size
+	}
+
+	if q.records == 0 {
+		q.progress = q.clock.Now()
+	}
+	last.size += size
+	last.records++
+	q.size += size
+	q.records++
+
+	select {
+	case q.signal <- struct{}{}:
+	default:
+	}
+	return nil
+}
+
+// Peek returns up to max of the oldest unacknowledged records, waiting
+// until there is one. Records stay queued until acknowledged with Ack;
+// the next Peek returns the same records again.
+func (q *Queue) Peek(ctx context.Context, max int) ([][]byte, error) {
+	if max <= 0 {
+		max = 1
+	}
+	for {
+		q.mutex.Lock()
+		if q.closed {
+			q.mutex.Unlock()
+			return nil, ErrQueueClosed
+		}
+		if q.records > 0 {
+			records, err := q.read(max)
+			q.mutex.Unlock()
+			return records, err
+		}
+		q.mutex.Unlock()
+
+		select {
+		case <-ctx.Done():
+			return nil, ctx.Err()
+		case <-q.signal:
+		}
+	}
+}
+
+// Ack removes the first n records returned by the last Peek. Records that
+// were dropped in the meantime are ignored.
+func (q *Queue) Ack(n int) error {
+	q.mutex.Lock()
+	defer q.mutex.Unlock()
+	if q.closed {
+		return ErrQueueClosed
+	}
+	if n > len(q.pending) {
+		n = len(q.pending)
+	}
+	if n <= 0 {
+		return nil
+	}
+
+	for _, end := range q.pending[:n] {
+		for q.segments[0].id != end.segment {
+			if err := q.removeFirst(); err != nil {
+				return err
+			}
+		}
+		q.head = end
+		q.headRecords++
+	}
+	q.pending = q.pending[n:]
+	q.records -= n
+	q.progress = q.clock.Now()
+
+	if q.head.offset >= q.segments[0].size && len(q.segments) > 1 {
+		if err := q.removeFirst(); err != nil {
+			return err
+		}
+	}
+	return q.writeAck()
+}
+
+// Len returns the number of unacknowledged records
+func (q *Queue) Len() int {
+	q.mutex.Lock()
+	defer q.mutex.Unlock()
+	return q.records
+}
+
+// Pressure returns the number of unacknowledged records, how full the
+// queue is and how long the consumer has not acknowledged any of them
+func (q *Queue) Pressure() backpressure.Pressure {
+	q.mutex.Lock()
+	defer q.mutex.Unlock()
+
+	pressure := backpressure.Pressure{
+		Depth:     q.records,
+		FillRatio: float64(q.size) / float64(q.config.MaxSize),
+	}
+	if q.records > 0 {
+		pressure.Lag = q.clock.Now().Sub(q.progress)
+	}
+	return pressure
+}
+
+// Stats returns the size of the queue and the number of dropped records
+func (q *Queue) Stats() QueueStats {
+	q.mutex.Lock()
+	defer q.mutex.Unlock()
+	return QueueStats{
+		Records:  q.records,
+		Bytes:    q.size,
+		Segments: len(q.segments),
+		Dropped:  q.dropped,
+	}
+}
+
+// MemoryUsage returns the bytes appended since they were last written to
+// disk. Until then they are dirty pages charged to the memory of the
+// process's cgroup.
+func (q *Queue) MemoryUsage() int64 {
+	q.mutex.Lock()
+	defer q.mutex.Unlock()
+	return q.unsynced
+}
+
+// Flush writes the appended records to disk
+func (q *Queue) Flush() error {
+	q.mutex.Lock()
+	defer q.mutex.Unlock()
+	if q.closed {
+		return ErrQueueClosed
+	}
+	return q.sync()
+}
+
+func (q *Queue) sync() error {
+	if q.unsynced == 0 {
+		return nil
+	}
+	if err := q.writer.Sync(); err != nil {
+		return err
+	}
+	q.unsynced = 0
+	return nil
+}
+
+// Close closes the segment files. Waiting Peek and AppendWait calls return
+// ErrQueueClosed.
+func (q *Queue) Close() error {
+	q.mutex.Lock()
+	defer q.mutex.Unlock()
+	if q.closed {
+		return nil
+	}
+	q.closed = true
+	close(q.signal)
+	close(q.freed)
+	err := q.closeFiles()
+	q.lock.unlock()
+	return err
+}
+
+// read reads up to max records from the head
+func (q *Queue) read(max int) ([][]byte, error) {
+	q.pending = q.pending[:0]
+	records := make([][]byte, 0, max)
+
+	at := q.head
+	index := 0
+	for len(records) < max && len(records) < q.records {
+		seg := q.segments[index]
+		if at.offset >= seg.size {
+			index++
+			at = position{segment: q.segments[index].id}
+			continue
+		}
+
+		record, err := q.readRecord(at)
+		if err != nil {
+			return nil, err
+		}
+		at.offset += int64(recordHeaderSize + len(record))
+		records = append(records, record)
+		q.pending = append(q.pending, at)
+	}
+	return records, nil
+}
+
+func (q *Queue) readRecord(at position) ([]byte, error) {
+	if q.reader == nil || q.readerID != at.segment {
+		if q.reader != nil {
+			q.reader.Close()
+		}
+		reader, err := os.Open(q.segmentPath(at.segment))
+		if err != nil {
+			q.reader = nil
+			return nil, err
+		}
+		q.reader, q.readerID = reader, at.segment
+	}
+
+	header := make([]byte, recordHeaderSize)
+	if _, err := q.reader.ReadAt(header, at.offset); err != nil {
+		return nil, err
+	}
+	record := make([]byte, binary.BigEndian.Uint32(header[0:]))
+	if _, err := q.reader.ReadAt(record, at.offset+recordHeaderSize); err != nil {
+		return nil, err
+	}
+	if crc32.ChecksumIEEE(record) != binary.BigEndian.Uint32(header[4:]) {
+		return nil, fmt.Errorf("corrupt record in segment %d at offset %d", at.segment, at.offset)
+	}
+	return record, nil
+}
+
+// roll starts a new segment
+func (q *Queue) roll() error {
+	if err := q.sync(); err != nil {
+		return err
+	}
+	if err := q.writer.Close(); err != nil {
+		return err
+	}
+	last := q.segments[len(q.segments)-1]
+	q.segments = append(q.segments, &segment{id: last.id + 1})
+	return q.openWriter()
+}
+
+// dropOldest removes the first segment with its unacknowledged records
+func (q *Queue) dropOldest() error {
+	first := q.segments[0]
+	unread := first.records - q.headRecords
+	q.logger.Warn("Store-and-forward queue full, dropping oldest records",
+		zap.String("dir", q.config.Dir),
+		zap.Int("records", unread),
+	)
+
+	q.dropped += uint64(unread)
+	q.records -= unread
+	q.pending = nil
+	if err := q.removeFirst(); err != nil {
+		return err
+	}
+	return q.writeAck()
+}
+
+// removeFirst deletes the first segment and moves the head to the next
+func (q *Queue) removeFirst() error {
+	first := q.segments[0]
+	if q.reader != nil && q.readerID == first.id {
+		q.reader.Close()
+		q.reader = nil
+	}
+	if err := os.Remove(q.segmentPath(first.id)); err != nil && !os.IsNotExist(err) {
+		return err
+	}
+
+	q.size -= first.size
+	q.segments = q.segments[1:]
+	q.head = position{segment: q.segments[0].id}
+	q.headRecords = 0
+
+	// Wake AppendWait callers
+	close(q.freed)
+	q.freed = make(chan struct{})
+	return nil
+}
+
+func (q *Queue) openWriter() error {
+	last := q.segments[len(q.segments)-1]
+	writer, err := os.OpenFile(q.segmentPath(last.id), os.O_CREATE|os.O_WRONLY|os.O_APPEND, 0o644)
+	if err != nil {
+		return err
+	}
+	q.writer = writer
+	return nil
+}
+
+func (q *Queue) closeFiles() error {
+	var err error
+	if q.reader != nil {
+		q.reader.Close()
+		q.reader = nil
+	}
+	if q.writer != nil {
+		err = q.writer.Close()
+		q.writer = nil
+	}
+	return err
+}
+
+func (q *Queue) segmentPath(id uint64) string {
+	return filepath.Join(q.config.Dir, fmt.Sprintf("%020d%s", id, segmentSuffix))
+}
+
+// segmentIDs returns the IDs of the segment files in order
+func (q *Queue) segmentIDs() ([]uint64, error) {
+	entries, err := os.ReadDir(q.config.Dir)
+	if err != nil {
+		return nil, err
+	}
+
+	var ids []uint64
+	for _, entry := range entries {
+		name := entry.Name()
+		if entry.IsDir() || !strings.HasSuffix(name, segmentSuffix) {
+			continue
+		}
+		id, err := strconv.ParseUint(strings.TrimSuffix(name, segmentSuffix), 10, 64)
+		if err != nil {
+			continue
+		}
+		ids = append(ids, id)
+	}
+	sort.Slice(ids, func(i, j int) bool { return ids[i] < ids[j] })
+	return ids, nil
+}
+
+// readAck reads the acknowledged position, zero for a new queue
+func (q *Queue) readAck() (position, error) {
+	data, err := os.ReadFile(filepath.Join(q.config.Dir, ackFileName))
+	if os.IsNotExist(err) {
+		return position{}, nil
+	}
+	if err != nil {
+		return position{}, err
+	}
+	if len(data) != 16 {
+		return position{}, fmt.Errorf("invalid queue ack file of %d bytes", len(data))
+	}
+	return position{
+		segment: binary.BigEndian.Uint64(data[0:]),
+		offset:  int64(binary.BigEndian.Uint64(data[8:])),
+	}, nil
+}
+
+// writeAck replaces the ack file with the head position
+func (q *Queue) writeAck() error {
+	data := make([]byte, 16)
+	binary.BigEndian.PutUint64(data[0:], q.head.segment)
+	binary.BigEndian.PutUint64(data[8:], uint64(q.head.offset))
+
+	path := filepath.Join(q.config.Dir, ackFileName)
+	file, err := os.Create(path + ".tmp")
+	if err != nil {
+		return err
+	}
+	if _, err := file.Write(data); err != nil {
+		file.Close()
+		return err
+	}
+	if q.config.Sync {
+		if err := file.Sync(); err != nil {
+			file.Close()
+			return err
+		}
+	}
+	if err := file.Close(); err != nil {
+		return err
+	}
+	return os.Rename(path+".tmp", path)
+}
+
+// scanSegment validates the records of a segment and truncates the file
+// after the last valid one. It returns the valid size, the number of
+// records and the number of records at or after offset from.
+func scanSegment(path string, from int64) (size int64, records, unread int, err error) {
+	data, err := os.ReadFile(path)
+	if err != nil {
+		return 0, 0, 0, err
+	}
+
+	for size+recordHeaderSize <= int64(len(data)) {
+		length := int64(binary.BigEndian.Uint32(data[size:]))
+		end := size + recordHeaderSize + length
+		if end > int64(len(data)) || crc32.ChecksumIEEE(data[size+recordHeaderSize:end]) != binary.BigEndian.Uint32(data[size+4:]) {
+			break
+		}
+		if size >= from {
+			unread++
+		}
+		records++
+		size = end
+	}
+
+	if size < int64(len(data)) {
+		if err := os.Truncate(path, size); err != nil {
+			return 0, 0, 0, err
+		}
+	}
+	return size, records, unread, nil
+}
@@ -0,0 +1,271 @@
+package forward
+
+import (
+	"context"
+	"fmt"
+	"os"
+	"os/exec"
+	"path/filepath"
+	"testing"
+	"time"
+
+	"github.com/stretchr/testify/assert"
+	"go.uber.org/zap"
+
+	"github.com/bifrost/go-gateway/internal/protocols"
+)
+
+func openTestQueue(t *testing.T, config QueueConfig) *Queue {
+	t.Helper()
+	if config.Dir == "" {
+		config.Dir = t.TempDir()
+	}
+	queue, err := OpenQueue(config, zap.NewNop())
+	if !assert.NoError(t, err) {
+		t.FailNow()
+	}
+	t.Cleanup(func() { queue.Close() })
+	return queue
+}
+
+// record returns a 16 byte record
+func record(i int) []byte {
+	return []byte(fmt.Sprintf("record-%09d", i))
+}
+
+func TestQueue_PeekAck(t *testing.T) {
+	queue := openTestQueue(t, QueueConfig{})
+	ctx := context.Background()
+	for i := 0; i < 3; i++ {
+		assert.NoError(t, queue.Append(record(i)))
+	}
+
+	records, err := queue.Peek(ctx, 2)
+	assert.NoError(t, err)
+	assert.Equal(t, [][]byte{record(0), record(1)}, records)
+
+	// Unacknowledged records are returned again
+	records, err = queue.Peek(ctx, 2)
+	assert.NoError(t, err)
+	assert.Equal(t, [][]byte{record(0), record(1)}, records)
+
+	assert.NoError(t, queue.Ack(1))
+	records, err = queue.Peek(ctx, 10)
+	assert.NoError(t, err)
+	assert.Equal(t, [][]byte{record(1), record(2)}, records)
+
+	assert.NoError(t, queue.Ack(2))
+	assert.Equal(t, 0, queue.Len())
+}
+
+func TestQueue_Reopen(t *testing.T) {
+	config := QueueConfig{Dir: t.TempDir(), SegmentSize: 64, MaxSize: 1024}
+	ctx := context.Background()
+
+	queue, err := OpenQueue(config, zap.NewNop())
+	assert.NoError(t, err)
+	for i := 0; i < 5; i++ {
+		assert.NoError(t, queue.Append(record(i)))
+	}
+	_, err = queue.Peek(ctx, 3)
+	assert.NoError(t, err)
+	assert.NoError(t, queue.Ack(3))
+	assert.Equal(t, 2, queue.Stats().Segments) // The first segment was consumed
+	assert.NoError(t, queue.Close())
+
+	queue = openTestQueue(t, config)
+	assert.Equal(t, 2, queue.Len())
+	assert.NoError(t, queue.Append(record(5)))
+
+	records, err := queue.Peek(ctx, 10)
+	assert.NoError(t, err)
+	assert.Equal(t, [][]byte{record(3), record(4), record(5)}, records)
+}
+
+func TestQueue_Lock(t *testing.T) {
+	config := QueueConfig{Dir: t.TempDir()}
+	queue, err := OpenQueue(config, zap.NewNop())
+	assert.NoError(t, err)
+
+	// A second writer is refused with the PID of the first
+	_, err = OpenQueue(config, zap.NewNop())
+	assert.ErrorIs(t, err, ErrQueueLocked)
+	assert.Contains(t, err.Error(), fmt.Sprintf("PID %d", os.Getpid()))
+
+	// The lock of a running owner cannot be removed
+	assert.ErrorIs(t, Unlock(config.Dir), ErrQueueLocked)
+
+	// Closing releases the lock
+	assert.NoError(t, queue.Close())
+	assert.NoError(t, Unlock(config.Dir))
+	queue = openTestQueue(t, config)
+	assert.NoError(t, queue.Append(record(0)))
+}
+
+func TestUnlock_OwnerExited(t *testing.T) {
+	dir := t.TempDir()
+	exited := exec.Command("true")
+	assert.NoError(t, exited.Run())
+
+	// A lock held on behalf of an owner that has exited
+	held, err := lockDir(dir)
+	assert.NoError(t, err)
+	defer held.unlock()
+	path := filepath.Join(dir, lockFileName)
+	assert.NoError(t, os.WriteFile(path, []byte(fmt.Sprintf("%d\n", exited.ProcessState.Pid())), 0o644))
+	_, err = OpenQueue(QueueConfig{Dir: dir}, zap.NewNop())
+	assert.ErrorIs(t, err, ErrQueueLocked)
+
+	assert.NoError(t, Unlock(dir))
+	assert.NoFileExists(t, path)
+	openTestQueue(t, QueueConfig{Dir: dir})
+}
+
+func TestQueue_TornWrite(t *testing.T) {
+	config := QueueConfig{Dir: t.TempDir()}
+	queue, err := OpenQueue(config, zap.NewNop())
+	assert.NoError(t, err)
+	assert.NoError(t, queue.Append(record(0)))
+	assert.NoError(t, queue.Append(record(1)))
+	assert.NoError(t, queue.Close())
+
+	// A record cut short by a power failure
+	path := filepath.Join(config.Dir, fmt.Sprintf("%020d.seg", 1))
+	file, err := os.OpenFile(path, os.O_WRONLY|os.O_APPEND, 0o644)
+	assert.NoError(t, err)
+	_, err = file.Write([]byte{0, 0, 0, 16, 1, 2, 3, 4, 'r', 'e'})
+	assert.NoError(t, err)
+	assert.NoError(t, file.Close())
+
+	queue = openTestQueue(t, config)
+	assert.Equal(t, 2, queue.Len())
+	assert.NoError(t, queue.Append(record(2)))
+
+	records, err := queue.Peek(context.Background(), 10)
+	assert.NoError(t, err)
+	assert.Equal(t, [][]byte{record(0), record(1), record(2)}, records)
+}
+
+func TestQueue_DropOldest(t *testing.T) {
+	// Two 24 byte records per segment, at most five records
+	queue := openTestQueue(t, QueueConfig{SegmentSize: 64, MaxSize: 128})
+	for i := 0; i < 10; i++ {
+		assert.NoError(t, queue.Append(record(i)))
+	}
+
+	stats := queue.Stats()
+	assert.Equal(t, 4, stats.Records)
+	assert.Equal(t, uint64(6), stats.Dropped)
+	assert.LessOrEqual(t, stats.Bytes, int64(128))
+
+	records, err := queue.Peek(context.Background(), 10)
+	assert.NoError(t, err)
+	assert.Equal(t, [][]byte{record(6), record(7), record(8), record(9)}, records)
+}
+
+func TestQueue_RejectWhenFull(t *testing.T) {
+	queue := openTestQueue(t, QueueConfig{SegmentSize: 64, MaxSize: 128, RejectWhenFull: true})
+	for i := 0; i < 5; i++ {
+		assert.NoError(t, queue.Append(record(i)))
+	}
+	assert.ErrorIs(t, queue.Append(record(5)), ErrQueueFull)
+	assert.Equal(t, 5, queue.Len())
+	assert.Equal(t, uint64(0), queue.Stats().Dropped)
+}
+
+func TestQueue_AppendWait(t *testing.T) {
+	queue := openTestQueue(t, QueueConfig{SegmentSize: 64, MaxSize: 128})
+	for i := 0; i < 5; i++ {
+		assert.NoError(t, queue.AppendWait(context.Background(), record(i)))
+	}
+	pressure := queue.Pressure()
+	assert.Equal(t, 5, pressure.Depth)
+	assert.InDelta(t, 120.0/128, pressure.FillRatio, 1e-9)
+
+	// A full queue makes the producer wait instead of dropping records
+	ctx, cancel := context.WithTimeout(context.Background(), 10*time.Millisecond)
+	defer cancel()
+	assert.ErrorIs(t, queue.AppendWait(ctx, record(5)), context.DeadlineExceeded)
+	assert.Greater(t, queue.Pressure().Lag, time.Duration(0))
+
+	go func() {
+		time.Sleep(10 * time.Millisecond)
+		queue.Peek(context.Background(), 2)
+		queue.Ack(2)
+	}()
+	assert.NoError(t, queue.AppendWait(context.Background(), record(5)))
+	assert.Equal(t, 4, queue.Len())
+	assert.Equal(t, uint64(0), queue.Stats().Dropped)
+}
+
+func TestQueue_Lag(t *testing.T) {
+	clock := protocols.NewManualClock(time.Date(2024, 1, 1, 0, 0, 0, 0, time.UTC))
+	queue := openTestQueue(t, QueueConfig{})
+	queue.SetClock(clock)
+
+	// An empty queue does not lag however long nothing is appended
+	clock.Advance(time.Hour)
+	assert.Equal(t, time.Duration(0), queue.Pressure().Lag)
+
+	assert.NoError(t, queue.Append(record(0)))
+	assert.NoError(t, queue.Append(record(1)))
+	clock.Advance(time.Minute)
+	assert.Equal(t, time.Minute, queue.Pressure().Lag)
+
+	// Acknowledging records resets the lag
+	_, err := queue.Peek(context.Background(), 1)
+	assert.NoError(t, err)
+	assert.NoError(t, queue.Ack(1))
+	clock.Advance(time.Second)
+	assert.Equal(t, time.Second, queue.Pressure().Lag)
+}
+
+func TestQueue_PeekWaits(t *testing.T) {
+	queue := openTestQueue(t, QueueConfig{})
+
+	ctx, cancel := context.WithTimeout(context.Background(), 10*time.Millisecond)
+	defer cancel()
+	_, err := queue.Peek(ctx, 1)
+	assert.ErrorIs(t, err, context.DeadlineExceeded)
+
+	go func() {
+		time.Sleep(10 * time.Millisecond)
+		queue.Append(record(0))
+	}()
+	records, err := queue.Peek(context.Background(), 1)
+	assert.NoError(t, err)
+	assert.Equal(t, [][]byte{record(0)}, records)
+
+	assert.NoError(t, queue.Close())
+	_, err = queue.Peek(context.Background(), 1)
+	assert.ErrorIs(t, err, ErrQueueClosed)
+}
+
+func TestQueue_Flush(t *testing.T) {
+	queue := openTestQueue(t, QueueConfig{SegmentSize: 48, MaxSize: 1024})
+	assert.NoError(t, queue.Append(record(0)))
+	assert.NoError(t, queue.Append(record(1)))
+	assert.Equal(t, int64(2*(recordHeaderSize+16)), queue.MemoryUsage())
+	assert.NoError(t, queue.Flush())
+	assert.Equal(t, int64(0), queue.MemoryUsage())
+
+	// Segments are written to disk when rolled
+	assert.NoError(t, queue.Append(record(2)))
+	assert.NoError(t, queue.Append(record(3)))
+	assert.NoError(t, queue.Append(record(4)))
+	assert.Equal(t, int64(recordHeaderSize+16), queue.MemoryUsage())
+
+	synced := openTestQueue(t, QueueConfig{Sync: true})
+	assert.NoError(t, synced.Append(record(0)))
+	assert.Equal(t, int64(0), synced.MemoryUsage())
+}
+
+func TestOpenQueue_Config(t *testing.T) {
+	_, err := OpenQueue(QueueConfig{}, zap.NewNop())
+	assert.Error(t, err)
+	_, err = OpenQueue(QueueConfig{Dir: t.TempDir(), SegmentSize: 1024, MaxSize: 1500}, zap.NewNop())
+	assert.Error(t, err)
+
+	queue := openTestQueue(t, QueueConfig{SegmentSize: 64})
+	assert.Error(t, queue.Append(make([]byte, 64)))
+}
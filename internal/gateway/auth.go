@@ -0,0 +1,95 @@
+package gateway
+
+import (
+	"encoding/json"
+	"net/http"
+	"time"
+
+	"github.com/bifrost/go-gateway/internal/auth"
+)
+
+const maxTokenLifetime = 24 * time.Hour
+
+// handleTokens issues a short-lived token to an authenticated client, e.g.
+// for a browser opening the WebSocket. The token has at most the
+// permissions and the tags of the caller.
+func (g *IndustrialGateway) handleTokens(w http.ResponseWriter, r *http.Request) {
+	if r.Method != http.MethodPost {
+		writeJSONError(w, http.StatusMethodNotAllowed, "method not allowed")
+		return
+	}
+	identity, ok := auth.FromContext(r.Context())
+	if !ok {
+		writeJSONError(w, http.StatusNotFound, "authentication is disabled")
+		return
+	}
+	if identity.Method == auth.MethodToken {
+		// A token must not extend its own lifetime
+		writeJSONError(w, http.StatusForbidden, "tokens cannot issue tokens")
+		return
+	}
+
+	var req struct {
+		Permissions []auth.Permission `json:"permissions"`
+		Roles       []string          `json:"roles"`
+		TTL         string            `json:"ttl"` // E.g. "15m", default 1h
+	}
+	if err := json.NewDecoder(r.Body).Decode(&req); err != nil {
+		writeJSONError(w, http.StatusBadRequest, "invalid JSON body: "+err.Error())
+		return
+	}
+
+	ttl := time.Hour
+	if req.TTL != "" {
+		var err error
+		if ttl, err = time.ParseDuration(req.TTL); err != nil {
+			writeJSONError(w, http.StatusBadRequest, "invalid ttl: "+err.Error())
+			return
+		}
+	}
+	if ttl > maxTokenLifetime {
+		writeJSONError(w, http.StatusBadRequest, "ttl exceeds 24h")
+		return
+	}
+	if len(req.Permissions) == 0 {
+		req.Permissions = identity.Permissions
+	}
+	for _, permission := range req.Permissions {
+		if !identity.Can(permission) {
+			writeJSONError(w, http.StatusForbidden, "cannot grant "+string(permission)+" permission")
+			return
+		}
+	}
+	if len(req.Roles) == 0 {
+		req.Roles = identity.Roles
+	}
+	if len(identity.Roles) > 0 {
+		// A client limited to roles cannot widen them
+		held := make(map[string]bool, len(identity.Roles))
+		for _, role := range identity.Roles {
+			held[role] = true
+		}
+		for _, role := range req.Roles {
+			if !held[role] {
+				writeJSONError(w, http.StatusForbidden, "cannot grant "+role+" role")
+				return
+			}
+		}
+	}
+
+	token, err := g.auth.IssueToken(identity.Name, req.Permissions, req.Roles, ttl)
+	if err != nil {
+		writeJSONError(w, http.StatusBadRequest, err.Error())
+		return
+	}
+	writeJSON(w, http.StatusOK, map[string]interface{}{
+		"token":   token,
+		"expires": time.Now().Add(ttl),
+	})
+}
+
+// allows reports whether the client of a request may read or write a tag
+func (g *IndustrialGateway) allows(r *http.Request, permission auth.Permission, deviceID, tagID string) bool {
+	identity, _ := auth.FromContext(r.Context())
+	return g.auth.Allows(identity, permission, deviceID, tagID)
+}
@@ -0,0 +1,35 @@
+package gateway
+
+import (
+	"net/http"
+
+	"go.uber.org/zap"
+)
+
+// handleBackpressure returns the combined backpressure signal and the
+// pressure of every buffer
+func (g *IndustrialGateway) handleBackpressure(w http.ResponseWriter, r *http.Request) {
+	if r.Method != http.MethodGet {
+		writeJSONError(w, http.StatusMethodNotAllowed, "method not allowed")
+		return
+	}
+	writeJSON(w, http.StatusOK, g.pressure.Signal())
+}
+
+// throttleCollection reports whether a collection cycle should be skipped
+// because buffers downstream are full, logging when that changes
+func (g *IndustrialGateway) throttleCollection(throttled bool) bool {
+	signal := g.pressure.Signal()
+	switch {
+	case signal.Overloaded && !throttled:
+		g.logger.Warn("Buffers are filling up, pausing data collection",
+			zap.String("buffer", signal.Source),
+			zap.Int("depth", signal.Pressure.Depth),
+			zap.Float64("fill_ratio", signal.Pressure.FillRatio),
+			zap.Duration("lag", signal.Pressure.Lag),
+		)
+	case !signal.Overloaded && throttled:
+		g.logger.Info("Buffers drained, resuming data collection")
+	}
+	return signal.Overloaded
+}
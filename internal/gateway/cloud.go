@@ -0,0 +1,207 @@
+package gateway
+
+import (
+	"context"
+	"fmt"
+	"reflect"
+
+	"go.uber.org/zap"
+
+	"github.com/bifrost/go-gateway/internal/cloud"
+	"github.com/bifrost/go-gateway/internal/events"
+	"github.com/bifrost/go-gateway/internal/forward"
+	"github.com/bifrost/go-gateway/internal/health"
+)
+
+// Cloud sinks are opened at startup and reconciled with the configuration
+// on reload: removed sinks are closed, added ones opened, and changed ones
+// closed and reopened on their queue directory, so the data buffered for
+// them is sent once they run again.
+
+// cloudSink is an open cloud sink fed collected data
+type cloudSink struct {
+	*cloud.Sink
+	config       cloud.Config
+	subscription *events.Subscription
+	cancel       context.CancelFunc // Stops Run, nil until started
+	done         chan struct{}      // Closed when Run returned
+}
+
+// cloudSinkConfigs returns configs with unnamed sinks named after their
+// provider and position
+func cloudSinkConfigs(configs []cloud.Config) []cloud.Config {
+	named := make([]cloud.Config, len(configs))
+	for i, config := range configs {
+		if config.Name == "" {
+			config.Name = fmt.Sprintf("%s-%d", config.Provider, i)
+		}
+		named[i] = config
+	}
+	return named
+}
+
+// validateCloudSinks checks the configured cloud sinks without opening
+// their queues
+func validateCloudSinks(configs []cloud.Config) error {
+	names := make(map[string]bool, len(configs))
+	for _, config := range cloudSinkConfigs(configs) {
+		if names[config.Name] {
+			return fmt.Errorf("duplicate cloud sink: %s", config.Name)
+		}
+		names[config.Name] = true
+		if err := cloud.Validate(config); err != nil {
+			return err
+		}
+	}
+	return nil
+}
+
+// openCloudSinks opens the queues of the configured cloud sinks and feeds
+// them collected data. A sink that fails to open is logged and skipped.
+func (g *IndustrialGateway) openCloudSinks(configs []cloud.Config) {
+	g.cloudMutex.Lock()
+	defer g.cloudMutex.Unlock()
+
+	for _, config := range cloudSinkConfigs(configs) {
+		g.openCloudSink(config)
+	}
+}
+
+// openCloudSink opens a sink and starts it if the sinks run. The caller
+// holds cloudMutex.
+func (g *IndustrialGateway) openCloudSink(config cloud.Config) {
+	sink, err := cloud.NewSink(config, g.logger)
+	if err != nil {
+		g.logger.Error("Invalid cloud sink configuration", zap.String("sink", config.Name), zap.Error(err))
+		return
+	}
+	// Correct timestamps of queued data taken while the clock was wrong
+	sink.SetTransform(g.timeSync.Apply)
+
+	name := "cloud/" + config.Name
+	s := &cloudSink{Sink: sink, config: config}
+	s.subscription = g.bus.Subscribe(name,
+		events.SubscribeOptions{Kinds: []events.Kind{events.KindData}, Blocking: true},
+		events.SinkHandler(sink, g.logger),
+	)
+	g.pressure.Register(name, sink)
+	g.memory.Register(name, sink)
+	g.health.Register(name, health.CheckOptions{}, sink.Check)
+	g.cloudSinks = append(g.cloudSinks, s)
+
+	if g.cloudContext != nil {
+		g.startCloudSink(s)
+	}
+}
+
+// runCloudSinks runs the cloud sinks, including those opened by Reload,
+// until ctx is done
+func (g *IndustrialGateway) runCloudSinks(ctx context.Context) {
+	g.cloudMutex.Lock()
+	g.cloudContext = ctx
+	for _, sink := range g.cloudSinks {
+		g.startCloudSink(sink)
+	}
+	g.cloudMutex.Unlock()
+
+	<-ctx.Done()
+	g.cloudMutex.Lock()
+	g.cloudContext = nil
+	g.cloudMutex.Unlock()
+	g.cloudRunning.Wait()
+}
+
+// startCloudSink runs a sink until it is closed or the sinks stop. The
+// caller holds cloudMutex.
+func (g *IndustrialGateway) startCloudSink(sink *cloudSink) {
+	ctx, cancel := context.WithCancel(g.cloudContext)
+	sink.cancel, sink.done = cancel, make(chan struct{})
+
+	g.cloudRunning.Add(1)
+	go func() {
+		defer g.cloudRunning.Done()
+		defer close(sink.done)
+		if err := sink.Run(ctx); err != nil {
+			g.logger.Error("Cloud sink error", zap.String("sink", sink.Name()), zap.Error(err))
+		}
+	}()
+}
+
+// closeCloudSink stops feeding a sink, waits for it to stop and closes its
+// queue. The caller holds cloudMutex.
+func (g *IndustrialGateway) closeCloudSink(sink *cloudSink) {
+	name := "cloud/" + sink.Name()
+
+	// Data already delivered to the subscription is queued first
+	sink.subscription.Unsubscribe()
+	g.pressure.Unregister(name)
+	g.memory.Unregister(name)
+	g.health.Unregister(name)
+
+	if sink.cancel != nil {
+		sink.cancel()
+		<-sink.done
+	}
+	if err := sink.Close(); err != nil {
+		g.logger.Error("Failed to close cloud sink queue", zap.String("sink", sink.Name()), zap.Error(err))
+	}
+}
+
+// reloadCloudSinks reconciles the open cloud sinks with configs and returns
+// the configs in effect. A changed sink keeps its queue directory, as the
+// data buffered for it is there.
+func (g *IndustrialGateway) reloadCloudSinks(configs []cloud.Config) []cloud.Config {
+	g.cloudMutex.Lock()
+	defer g.cloudMutex.Unlock()
+
+	configs = cloudSinkConfigs(configs)
+	wanted := make(map[string]int, len(configs))
+	for i, config := range configs {
+		wanted[config.Name] = i
+	}
+
+	kept := make(map[string]bool, len(g.cloudSinks))
+	sinks := make([]*cloudSink, 0, len(configs))
+	for _, sink := range g.cloudSinks {
+		i, exists := wanted[sink.Name()]
+		if !exists {
+			g.closeCloudSink(sink)
+			g.logger.Info("Cloud sink removed", zap.String("sink", sink.Name()))
+			continue
+		}
+
+		if configs[i].Queue.Dir != sink.config.Queue.Dir {
+			g.logger.Warn("Cloud sink queue directory changes take effect after a restart",
+				zap.String("sink", sink.Name()),
+				zap.String("dir", sink.config.Queue.Dir),
+			)
+			configs[i].Queue.Dir = sink.config.Queue.Dir
+		}
+		if reflect.DeepEqual(configs[i], sink.config) {
+			kept[sink.Name()] = true
+			sinks = append(sinks, sink)
+			continue
+		}
+		g.closeCloudSink(sink)
+	}
+	g.cloudSinks = sinks
+
+	for _, config := range configs {
+		if !kept[config.Name] {
+			g.openCloudSink(config)
+		}
+	}
+	return configs
+}
+
+// cloudSinkStats returns the queue state of each cloud sink
+func (g *IndustrialGateway) cloudSinkStats() map[string]forward.QueueStats {
+	g.cloudMutex.Lock()
+	defer g.cloudMutex.Unlock()
+
+	stats := make(map[string]forward.QueueStats, len(g.cloudSinks))
+	for _, sink := range g.cloudSinks {
+		stats[sink.Name()] = sink.Stats()
+	}
+	return stats
+}
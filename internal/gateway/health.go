@@ -0,0 +1,76 @@
+package gateway
+
+import (
+	"context"
+	"fmt"
+	"sort"
+	"strings"
+	"time"
+
+	"go.uber.org/zap"
+
+	"github.com/bifrost/go-gateway/internal/health"
+	"github.com/bifrost/go-gateway/internal/systemd"
+)
+
+// minCollectionTimeout is the shortest time without a finished collection
+// cycle before the gateway is reported stuck
+const minCollectionTimeout = 30 * time.Second
+
+// registerHealthChecks registers the checks of the gateway's own
+// components. Sinks and forwarders register theirs when they start.
+func (g *IndustrialGateway) registerHealthChecks() {
+	g.collectionHeartbeat = health.NewHeartbeat(collectionTimeout(g.currentConfig().UpdateInterval))
+	g.health.Register("collection", health.CheckOptions{Liveness: true, Critical: true}, g.collectionHeartbeat.Check)
+
+	g.health.Register("devices", health.CheckOptions{}, g.checkDevices)
+	g.health.Register("backpressure", health.CheckOptions{}, g.checkBackpressure)
+	g.health.Register("time_sync", health.CheckOptions{}, g.timeSync.Check)
+}
+
+// collectionTimeout returns how long a collection cycle may take before
+// the gateway is reported stuck. A cycle may take several intervals with
+// slow devices.
+func collectionTimeout(interval time.Duration) time.Duration {
+	if timeout := 10 * interval; timeout > minCollectionTimeout {
+		return timeout
+	}
+	return minCollectionTimeout
+}
+
+// checkDevices fails while devices are disconnected
+func (g *IndustrialGateway) checkDevices(ctx context.Context) error {
+	var disconnected []string
+	g.devices.Range(func(key, value interface{}) bool {
+		if device := value.(*Device); !device.Connected {
+			disconnected = append(disconnected, device.ID)
+		}
+		return true
+	})
+	if len(disconnected) == 0 {
+		return nil
+	}
+	sort.Strings(disconnected)
+	return fmt.Errorf("disconnected: %s", strings.Join(disconnected, ", "))
+}
+
+// checkBackpressure fails while data collection is paused for full buffers
+func (g *IndustrialGateway) checkBackpressure(ctx context.Context) error {
+	signal := g.pressure.Signal()
+	if !signal.Overloaded {
+		return nil
+	}
+	return fmt.Errorf("%s overloaded at %.0f%%", signal.Source, signal.Pressure.FillRatio*100)
+}
+
+// alive reports whether the liveness checks pass
+func (g *IndustrialGateway) alive(ctx context.Context) bool {
+	return g.health.Liveness(ctx).Status != health.StatusDown
+}
+
+// notifySystemd sends a state to systemd when running under it
+func (g *IndustrialGateway) notifySystemd(state string) {
+	if _, err := systemd.Notify(state); err != nil {
+		g.logger.Warn("Failed to notify systemd", zap.String("state", state), zap.Error(err))
+	}
+}
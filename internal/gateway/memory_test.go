@@ -0,0 +1,54 @@
+package gateway
+
+import (
+	"testing"
+
+	"github.com/stretchr/testify/assert"
+	"go.uber.org/zap"
+
+	"github.com/bifrost/go-gateway/internal/cloud"
+	"github.com/bifrost/go-gateway/internal/memory"
+	"github.com/bifrost/go-gateway/internal/protocols"
+)
+
+// cachingHandler is a driver handler with a cache
+type cachingHandler struct {
+	protocols.ProtocolHandler
+	bytes int64
+}
+
+func (h *cachingHandler) MemoryUsage() int64 { return h.bytes }
+
+func (h *cachingHandler) Shrink(bytes int64) int64 {
+	freed := h.bytes
+	h.bytes = 0
+	return freed
+}
+
+func TestMemoryBudget(t *testing.T) {
+	// Every process exceeds a budget of one byte
+	g := newTestGateway(t, &Config{
+		Memory:     memory.Config{Limit: 1},
+		CloudSinks: []cloud.Config{testCloudSink(t, "uplink", "press-1")},
+	})
+	cache := &cachingHandler{bytes: 1 << 20}
+	g.drivers.MustRegister("caching", func(*zap.Logger) protocols.ProtocolHandler { return cache })
+	_, exists := g.drivers.Driver("caching")
+	assert.True(t, exists)
+
+	if !assert.Len(t, g.cloudSinks, 1) {
+		t.FailNow()
+	}
+	assert.NoError(t, g.cloudSinks[0].WriteSamples([]protocols.TagSample{{Name: "temperature", Value: 21.5}}))
+
+	stats := g.memory.Stats()
+	assert.Equal(t, int64(1<<20), stats.Consumers["driver/caching"])
+	assert.Greater(t, stats.Consumers["cloud/uplink"], int64(0))
+
+	g.memory.Check()
+	stats = g.memory.Stats()
+	assert.Equal(t, int64(0), stats.Consumers["driver/caching"])
+	assert.Equal(t, int64(0), stats.Consumers["cloud/uplink"])
+	assert.NotZero(t, stats.Shrinks)
+	assert.NotZero(t, stats.Flushes)
+}
@@ -0,0 +1,77 @@
+package gateway
+
+import (
+	"errors"
+	"math"
+	"net"
+	"net/http"
+	"strconv"
+	"sync"
+	"time"
+
+	"go.uber.org/zap"
+
+	"github.com/bifrost/go-gateway/internal/auth"
+	"github.com/bifrost/go-gateway/internal/ratelimit"
+)
+
+// throttleWarnInterval is the minimum time between warnings about the
+// samples a device lost to the ingest rate limit
+const throttleWarnInterval = time.Minute
+
+// throttledDevice counts the samples a device lost to the ingest rate
+// limit since the last warning
+type throttledDevice struct {
+	mutex   sync.Mutex
+	dropped int
+	warned  time.Time
+}
+
+// dropThrottled records samples not collected because a device exceeded
+// its ingest rate limit, warning at most once a minute per device
+func (g *IndustrialGateway) dropThrottled(device *Device, samples int, err error) {
+	g.metrics.samplesThrottled.Add(float64(samples))
+
+	value, _ := g.throttled.LoadOrStore(device.ID, &throttledDevice{})
+	throttled := value.(*throttledDevice)
+	throttled.mutex.Lock()
+	defer throttled.mutex.Unlock()
+	throttled.dropped += samples
+	if time.Since(throttled.warned) < throttleWarnInterval {
+		return
+	}
+	g.logger.Warn("Dropping collected data over the ingest rate limit",
+		zap.String("device", device.ID),
+		zap.Int("samples", throttled.dropped),
+		zap.Error(err),
+	)
+	throttled.dropped = 0
+	throttled.warned = time.Now()
+}
+
+// limitAPI returns a handler that rate limits next per client: the
+// authenticated identity, or the remote address without authentication.
+// Every request counts as one point plus its body size in bytes.
+func (g *IndustrialGateway) limitAPI(next http.HandlerFunc) http.HandlerFunc {
+	return func(w http.ResponseWriter, r *http.Request) {
+		client, _, _ := net.SplitHostPort(r.RemoteAddr)
+		if identity, ok := auth.FromContext(r.Context()); ok {
+			client = identity.Name
+		}
+
+		bytes := 0
+		if r.ContentLength > 0 {
+			bytes = int(r.ContentLength)
+		}
+		_, apiLimiter := g.limiters()
+		if err := apiLimiter.Allow(client, 1, bytes); err != nil {
+			var throttled *ratelimit.ThrottledError
+			if errors.As(err, &throttled) {
+				w.Header().Set("Retry-After", strconv.Itoa(int(math.Ceil(throttled.RetryAfter.Seconds()))))
+			}
+			writeJSONError(w, http.StatusTooManyRequests, err.Error())
+			return
+		}
+		next(w, r)
+	}
+}
@@ -0,0 +1,144 @@
+package gateway
+
+import (
+	"fmt"
+	"reflect"
+	"sort"
+	"strings"
+
+	"go.uber.org/zap"
+
+	"github.com/bifrost/go-gateway/internal/ratelimit"
+	"github.com/bifrost/go-gateway/internal/systemd"
+)
+
+// Hot Reload
+//
+// Reload applies a changed configuration to the running gateway, keeping
+// connected devices, buffered data and the state of unchanged rules. The
+// whole configuration is validated first; nothing changes if any part is
+// invalid. The poll interval, rules, scripts, ingest scripts, rate limits,
+// backpressure watermarks and cloud sinks take effect immediately; a changed
+// cloud sink is reopened on its queue directory and keeps its buffered data.
+// Ports, TLS, authentication, metrics, the health endpoints, the memory
+// budget and time sync are set up at startup, so changes to them keep their
+// running values and are logged as needing a restart.
+
+// currentConfig returns the configuration in effect
+func (g *IndustrialGateway) currentConfig() *Config {
+	g.configMutex.RLock()
+	defer g.configMutex.RUnlock()
+	return g.config
+}
+
+// limiters returns the rate limiters of collected data and API clients
+func (g *IndustrialGateway) limiters() (ingest, api *ratelimit.Limiter) {
+	g.configMutex.RLock()
+	defer g.configMutex.RUnlock()
+	return g.ingestLimiter, g.apiLimiter
+}
+
+// Reload validates config and applies it to the running gateway
+func (g *IndustrialGateway) Reload(config *Config) error {
+	g.reloadMutex.Lock()
+	defer g.reloadMutex.Unlock()
+	current := g.currentConfig()
+
+	g.notifySystemd(systemd.Reloading)
+	defer g.notifySystemd(systemd.Ready)
+
+	if config.UpdateInterval <= 0 {
+		return fmt.Errorf("update_interval must be positive")
+	}
+	scriptSet, err := g.scripts.Compile(config.Scripts)
+	if err != nil {
+		return fmt.Errorf("scripts: %w", err)
+	}
+	scripts := make(map[string]bool, len(config.Scripts))
+	for _, script := range config.Scripts {
+		scripts[script.Name] = true
+	}
+	for _, name := range config.IngestScripts {
+		if !scripts[name] {
+			return fmt.Errorf("ingest script %s is not configured", name)
+		}
+	}
+	ruleSet, err := g.rules.Compile(config.Rules)
+	if err != nil {
+		return fmt.Errorf("rules: %w", err)
+	}
+	if err := validateCloudSinks(config.CloudSinks); err != nil {
+		return fmt.Errorf("cloud sinks: %w", err)
+	}
+
+	// Unchanged limiters keep their buckets
+	ingestLimiter, apiLimiter := g.limiters()
+	if !reflect.DeepEqual(config.IngestRateLimit, current.IngestRateLimit) {
+		if ingestLimiter, err = ratelimit.NewLimiter(config.IngestRateLimit); err != nil {
+			return fmt.Errorf("ingest rate limit: %w", err)
+		}
+	}
+	if !reflect.DeepEqual(config.APIRateLimit, current.APIRateLimit) {
+		if apiLimiter, err = ratelimit.NewLimiter(config.APIRateLimit); err != nil {
+			return fmt.Errorf("API rate limit: %w", err)
+		}
+	}
+
+	g.pressure.SetConfig(config.Backpressure)
+	g.collectionHeartbeat.SetTimeout(collectionTimeout(config.UpdateInterval))
+
+	updated := *config
+	if restart := keepStartupSettings(&updated, current); len(restart) > 0 {
+		g.logger.Warn("Configuration changes take effect after a restart",
+			zap.String("settings", strings.Join(restart, ", ")),
+		)
+	}
+	updated.CloudSinks = g.reloadCloudSinks(updated.CloudSinks)
+
+	// Rules, the scripts they run and the ingest scripts configured change
+	// together
+	g.configMutex.Lock()
+	g.scripts.Replace(scriptSet)
+	g.rules.Replace(ruleSet)
+	g.config, g.ingestLimiter, g.apiLimiter = &updated, ingestLimiter, apiLimiter
+	g.configMutex.Unlock()
+
+	g.logger.Info("Configuration reloaded",
+		zap.Duration("update_interval", updated.UpdateInterval),
+		zap.Int("rules", len(updated.Rules)),
+		zap.Int("scripts", len(updated.Scripts)),
+	)
+	return nil
+}
+
+// keepStartupSettings sets the settings read only at startup back to their
+// running values and returns the names of those that were changed
+func keepStartupSettings(config, running *Config) []string {
+	var changed []string
+	for name, different := range map[string]bool{
+		"port":             config.Port != running.Port,
+		"grpc_port":        config.GRPCPort != running.GRPCPort,
+		"max_connections":  config.MaxConnections != running.MaxConnections,
+		"data_buffer_size": config.DataBufferSize != running.DataBufferSize,
+		"enable_metrics":   config.EnableMetrics != running.EnableMetrics,
+		"log_level":        config.LogLevel != running.LogLevel,
+		"tls":              !reflect.DeepEqual(config.TLS, running.TLS),
+		"auth":             !reflect.DeepEqual(config.Auth, running.Auth),
+		"health":           config.Health != running.Health,
+		"memory":           config.Memory != running.Memory,
+		"time_sync":        config.TimeSync != running.TimeSync,
+	} {
+		if different {
+			changed = append(changed, name)
+		}
+	}
+	sort.Strings(changed)
+
+	config.Port, config.GRPCPort = running.Port, running.GRPCPort
+	config.MaxConnections, config.DataBufferSize = running.MaxConnections, running.DataBufferSize
+	config.EnableMetrics, config.LogLevel = running.EnableMetrics, running.LogLevel
+	config.TLS, config.Auth, config.Health = running.TLS, running.Auth, running.Health
+	config.Memory = running.Memory
+	config.TimeSync = running.TimeSync
+	return changed
+}
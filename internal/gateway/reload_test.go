@@ -0,0 +1,73 @@
+package gateway
+
+import (
+	"testing"
+	"time"
+
+	"github.com/stretchr/testify/assert"
+	"go.uber.org/zap"
+
+	"github.com/bifrost/go-gateway/internal/cloud"
+	"github.com/bifrost/go-gateway/internal/forward"
+	"github.com/bifrost/go-gateway/internal/protocols"
+	"github.com/bifrost/go-gateway/internal/rules"
+	"github.com/bifrost/go-gateway/internal/scripting"
+)
+
+func TestReload_ScriptsAndRules(t *testing.T) {
+	g := newTestGateway(t, &Config{UpdateInterval: time.Second})
+	script := scripting.Config{Name: "scale", Source: "def transform(sample):\n    return sample"}
+	rule := rules.Rule{ID: "high", Condition: "Level > 90", Actions: []rules.Action{{Type: rules.ActionScript, Script: "scale"}}}
+
+	assert.NoError(t, g.Reload(&Config{
+		UpdateInterval: time.Second,
+		Scripts:        []scripting.Config{script},
+		IngestScripts:  []string{"scale"},
+		Rules:          []rules.Rule{rule},
+	}))
+	assert.Equal(t, []string{"scale"}, g.scripts.Names())
+	assert.Len(t, g.rules.Rules(), 1)
+
+	// An invalid rule leaves the scripts, the rules and the configuration
+	// unchanged
+	assert.Error(t, g.Reload(&Config{
+		UpdateInterval: time.Second,
+		Scripts:        []scripting.Config{{Name: "offset", Source: script.Source}},
+		Rules:          []rules.Rule{{ID: "broken", Condition: "Level >"}},
+	}))
+	assert.Equal(t, []string{"scale"}, g.scripts.Names())
+	assert.Equal(t, "high", g.rules.Rules()[0].ID)
+	assert.Equal(t, []string{"scale"}, g.currentConfig().IngestScripts)
+}
+
+func TestReload_CloudSinks(t *testing.T) {
+	uplink, backup := testCloudSink(t, "uplink", "press-1"), testCloudSink(t, "backup", "press-1")
+	g := newTestGateway(t, &Config{UpdateInterval: time.Second, CloudSinks: []cloud.Config{uplink, backup}})
+	if !assert.Len(t, g.cloudSinks, 2) {
+		t.FailNow()
+	}
+	assert.NoError(t, g.cloudSinks[0].WriteSamples([]protocols.TagSample{{Name: "temperature", Value: 21.5}}))
+
+	// The retargeted uplink is reopened on its queue, the backup closed and
+	// the archive opened
+	retargeted := testCloudSink(t, "uplink", "press-2")
+	retargeted.Queue = uplink.Queue
+	archive := testCloudSink(t, "archive", "press-1")
+	assert.NoError(t, g.Reload(&Config{UpdateInterval: time.Second, CloudSinks: []cloud.Config{retargeted, archive}}))
+
+	stats := g.cloudSinkStats()
+	assert.Len(t, stats, 2)
+	assert.Equal(t, 1, stats["uplink"].Records)
+	assert.Contains(t, stats, "archive")
+	assert.Equal(t, retargeted.Azure, g.currentConfig().CloudSinks[0].Azure)
+
+	queue, err := forward.OpenQueue(backup.Queue, zap.NewNop())
+	if assert.NoError(t, err) {
+		assert.NoError(t, queue.Close())
+	}
+	assert.NotContains(t, g.memory.Stats().Consumers, "cloud/backup")
+
+	// An invalid sink leaves the sinks unchanged
+	assert.Error(t, g.Reload(&Config{UpdateInterval: time.Second, CloudSinks: []cloud.Config{{Name: "pubsub", Provider: "gcp"}}}))
+	assert.Len(t, g.cloudSinkStats(), 2)
+}
@@ -0,0 +1,111 @@
+package gateway
+
+import (
+	"context"
+	"encoding/json"
+	"fmt"
+	"net/http"
+	"time"
+
+	"go.uber.org/zap"
+
+	"github.com/bifrost/go-gateway/internal/auth"
+	"github.com/bifrost/go-gateway/internal/events"
+	"github.com/bifrost/go-gateway/internal/rules"
+)
+
+// WriteTag writes a value to a tag of a connected device for a rule action
+func (g *IndustrialGateway) WriteTag(ctx context.Context, deviceID, tagID string, value interface{}) error {
+	device, err := g.connectedDevice(deviceID)
+	if err != nil {
+		return err
+	}
+
+	tag, exists := device.Tags[tagID]
+	if !exists {
+		return fmt.Errorf("tag not found: %s", tagID)
+	}
+
+	value, err = coerceJSONValue(value, tag.DataType)
+	if err != nil {
+		return err
+	}
+
+	start := time.Now()
+	err = device.Handler.WriteTag(device.toProtocolDevice(), tag.toProtocolTag(), value)
+	g.metrics.responseTime.Observe(time.Since(start).Seconds())
+	if err != nil {
+		g.metrics.errorRate.Inc()
+	}
+	return err
+}
+
+// RaiseEvent logs an event raised by a rule and publishes it as an alarm
+func (g *IndustrialGateway) RaiseEvent(event rules.Event) error {
+	g.logger.Info("Rule event",
+		zap.String("event", event.Name),
+		zap.String("rule", event.Rule),
+		zap.String("severity", event.Severity),
+		zap.String("message", event.Message),
+	)
+
+	g.bus.Publish(events.AlarmEvent{
+		Name:     event.Name,
+		Severity: event.Severity,
+		Message:  event.Message,
+		Source:   event.Rule,
+		Time:     event.Time,
+		Values:   event.Values,
+	})
+	return nil
+}
+
+// handleRules lists rules on GET, adds or replaces a rule on PUT and
+// deletes the rule given by the id query parameter on DELETE. A rule may
+// only write tags the client may write, and only clients without roles
+// may add script actions.
+func (g *IndustrialGateway) handleRules(w http.ResponseWriter, r *http.Request) {
+	switch r.Method {
+	case http.MethodGet:
+		writeJSON(w, http.StatusOK, map[string]interface{}{"rules": g.rules.Rules()})
+
+	case http.MethodPut:
+		var rule rules.Rule
+		if err := json.NewDecoder(r.Body).Decode(&rule); err != nil {
+			writeJSONError(w, http.StatusBadRequest, "invalid request body: "+err.Error())
+			return
+		}
+		// A rule writes with the permissions of the client that put it.
+		// Scripts may write any tag, so clients limited to the tags of
+		// roles cannot use them.
+		identity, _ := auth.FromContext(r.Context())
+		for _, action := range rule.Actions {
+			if action.Type == rules.ActionWrite && !g.allows(r, auth.PermissionWrite, action.Device, action.Tag) {
+				writeJSONError(w, http.StatusForbidden, "write permission required for tag "+action.Tag)
+				return
+			}
+			if action.Type == rules.ActionScript && len(identity.Roles) > 0 {
+				writeJSONError(w, http.StatusForbidden, "script actions require access to every tag")
+				return
+			}
+		}
+		if err := g.rules.Put(rule); err != nil {
+			writeJSONError(w, http.StatusBadRequest, err.Error())
+			return
+		}
+		g.logger.Info("Rule updated", zap.String("rule", rule.ID))
+		writeJSON(w, http.StatusOK, rule)
+
+	case http.MethodDelete:
+		id := r.URL.Query().Get("id")
+		if !g.rules.Delete(id) {
+			writeJSONError(w, http.StatusNotFound, "rule not found: "+id)
+			return
+		}
+		g.logger.Info("Rule deleted", zap.String("rule", id))
+		writeJSON(w, http.StatusOK, map[string]interface{}{"deleted": id})
+
+	default:
+		writeJSONError(w, http.StatusMethodNotAllowed, "method not allowed")
+	}
+}
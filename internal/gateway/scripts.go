@@ -0,0 +1,30 @@
+package gateway
+
+import (
+	"go.uber.org/zap"
+
+	"github.com/bifrost/go-gateway/internal/protocols"
+)
+
+// transformIngest passes collected samples through the ingest scripts. If
+// a script fails the samples are kept untransformed with bad quality, so
+// raw values are not mistaken for transformed ones.
+func (g *IndustrialGateway) transformIngest(samples []protocols.TagSample) []protocols.TagSample {
+	names := g.currentConfig().IngestScripts
+	if len(names) == 0 || len(samples) == 0 {
+		return samples
+	}
+
+	transformed, err := g.scripts.Transform(names, samples)
+	if err == nil {
+		return transformed
+	}
+
+	g.logger.Error("Ingest script failed", zap.Error(err))
+	for i := range samples {
+		samples[i].Quality = protocols.QualityBad
+		samples[i].Substatus = protocols.QualitySubstatusConfigError
+		samples[i].QualitySource = protocols.QualitySourceGateway
+	}
+	return samples
+}
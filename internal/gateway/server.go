@@ -2,7 +2,9 @@ package gateway
 
 import (
 	"context"
+	"encoding/json"
 	"fmt"
+	"math"
 	"net/http"
 	"sync"
 	"time"
@@ -12,14 +14,50 @@ import (
 	"github.com/prometheus/client_golang/prometheus/promhttp"
 	"go.uber.org/zap"
 
+	"github.com/bifrost/go-gateway/internal/auth"
+	"github.com/bifrost/go-gateway/internal/backpressure"
+	"github.com/bifrost/go-gateway/internal/cloud"
+	"github.com/bifrost/go-gateway/internal/events"
+	"github.com/bifrost/go-gateway/internal/health"
+	"github.com/bifrost/go-gateway/internal/memory"
 	"github.com/bifrost/go-gateway/internal/protocols"
+	"github.com/bifrost/go-gateway/internal/ratelimit"
+	"github.com/bifrost/go-gateway/internal/rules"
+	"github.com/bifrost/go-gateway/internal/scripting"
+	"github.com/bifrost/go-gateway/internal/systemd"
+	"github.com/bifrost/go-gateway/internal/timesync"
+	"github.com/bifrost/go-gateway/internal/tlsconfig"
 )
 
 // IndustrialGateway is the main server handling multiple industrial protocols
 type IndustrialGateway struct {
-	logger    *zap.Logger
-	devices   sync.Map // map[string]*Device
-	protocols map[string]protocols.ProtocolHandler
+	logger  *zap.Logger
+	devices sync.Map // map[string]*Device
+	drivers *protocols.DriverRegistry
+	bus     *events.Bus
+	rules   *rules.Engine
+	scripts *scripting.Registry
+	auth    *auth.Authenticator
+
+	// Backpressure of the buffers fed by data collection
+	pressure *backpressure.Monitor
+
+	// Memory budget of buffers and caches
+	memory *memory.Accountant
+
+	// Store-and-forward uplinks to cloud IoT services, reconciled by
+	// Reload, and the context they run in once started
+	cloudMutex   sync.Mutex
+	cloudSinks   []*cloudSink
+	cloudContext context.Context
+	cloudRunning sync.WaitGroup
+
+	// Synchronization of the clock stamping collected data
+	timeSync *timesync.Monitor
+
+	// Liveness and readiness of the gateway's components
+	health              *health.Health
+	collectionHeartbeat *health.Heartbeat
 
 	// Performance metrics
 	metrics struct {
@@ -27,28 +65,66 @@ type IndustrialGateway struct {
 		dataPointsProcessed prometheus.Counter
 		errorRate           prometheus.Counter
 		responseTime        prometheus.Histogram
+		samplesThrottled    prometheus.Counter
 	}
 
+	// Samples dropped by the ingest rate limit since the last warning, by
+	// device ID
+	throttled sync.Map // map[string]*throttledDevice
+
 	// WebSocket connections for real-time data
 	wsUpgrader websocket.Upgrader
-	wsClients  sync.Map // map[*websocket.Conn]bool
+	wsClients  sync.Map // map[*websocket.Conn]auth.Identity
+
+	// Configuration and the rate limits of collected data per device and
+	// of API clients, replaced by Reload
+	configMutex   sync.RWMutex
+	config        *Config
+	ingestLimiter *ratelimit.Limiter
+	apiLimiter    *ratelimit.Limiter
 
-	// Configuration
-	config *Config
+	// Serializes Reload
+	reloadMutex sync.Mutex
 }
 
 type Config struct {
-	Port           int           `yaml:"port"`
-	GRPCPort       int           `yaml:"grpc_port"`
-	MaxConnections int           `yaml:"max_connections"`
-	DataBufferSize int           `yaml:"data_buffer_size"`
-	UpdateInterval time.Duration `yaml:"update_interval"`
-	EnableMetrics  bool          `yaml:"enable_metrics"`
-	LogLevel       string        `yaml:"log_level"`
+	Port           int                `yaml:"port"`
+	GRPCPort       int                `yaml:"grpc_port"`
+	MaxConnections int                `yaml:"max_connections"`
+	DataBufferSize int                `yaml:"data_buffer_size"`
+	UpdateInterval time.Duration      `yaml:"update_interval"`
+	EnableMetrics  bool               `yaml:"enable_metrics"`
+	LogLevel       string             `yaml:"log_level"`
+	Rules          []rules.Rule       `yaml:"rules"`
+	Scripts        []scripting.Config `yaml:"scripts"`
+	IngestScripts  []string           `yaml:"ingest_scripts"` // Transform scripts applied to collected values
+	TLS            tlsconfig.Config   `yaml:"tls"`            // Serves the HTTP and gRPC APIs over TLS when enabled
+	Auth           auth.Config        `yaml:"auth"`           // Authenticates API and WebSocket clients when enabled
+
+	// Rate limits by device ID for collected data and by client for tag
+	// reads and writes through the API
+	IngestRateLimit ratelimit.Config `yaml:"ingest_rate_limit"`
+	APIRateLimit    ratelimit.Config `yaml:"api_rate_limit"`
+
+	// Watermarks at which data collection pauses for full buffers
+	Backpressure backpressure.Config `yaml:"backpressure"`
+
+	// Health endpoints for orchestrators and watchdogs
+	Health health.Config `yaml:"health"`
+
+	// Resident memory budget enforced by shrinking caches and flushing
+	// buffers
+	Memory memory.Config `yaml:"memory"`
+
+	// Uplinks forwarding collected data to Azure IoT Hub or AWS IoT Core
+	CloudSinks []cloud.Config `yaml:"cloud_sinks"`
+
+	// NTP or PTP daemon queried for the synchronization of the clock
+	TimeSync timesync.Config `yaml:"time_sync"`
 }
 
 type Device struct {
-	ID       string                 `json:"id"`
+	ID       string                 `json:"id"` // A protocols.DeviceID such as "site/line/device"
 	Name     string                 `json:"name"`
 	Protocol string                 `json:"protocol"`
 	Address  string                 `json:"address"`
@@ -56,10 +132,14 @@ type Device struct {
 	Config   map[string]interface{} `json:"config"`
 
 	// Runtime state
-	Connected bool                      `json:"connected"`
-	LastSeen  time.Time                 `json:"last_seen"`
-	Tags      map[string]*Tag           `json:"tags"`
-	Handler   protocols.ProtocolHandler `json:"-"`
+	Connected bool                     `json:"connected"`
+	LastSeen  time.Time                `json:"last_seen"`
+	Tags      map[string]*Tag          `json:"tags"`
+	Handler   protocols.ProtocolDriver `json:"-"`
+
+	// protocolDevice carries handler connection state (e.g. ConnectionID)
+	// between calls; it is created on connect and reused for every operation.
+	protocolDevice *protocols.Device
 
 	// Performance tracking
 	Stats struct {
@@ -72,24 +152,27 @@ type Device struct {
 }
 
 type Tag struct {
-	ID          string      `json:"id"`
-	Name        string      `json:"name"`
-	Address     string      `json:"address"`
-	DataType    string      `json:"data_type"`
-	Value       interface{} `json:"value"`
-	Quality     string      `json:"quality"`
-	Timestamp   time.Time   `json:"timestamp"`
-	Writable    bool        `json:"writable"`
-	Unit        string      `json:"unit"`
-	Description string      `json:"description"`
+	ID            string      `json:"id"`
+	Name          string      `json:"name"`
+	Address       string      `json:"address"`
+	DataType      string      `json:"data_type"`
+	Value         interface{} `json:"value"`
+	Quality       string      `json:"quality"`
+	Substatus     string      `json:"substatus,omitempty"`
+	QualitySource string      `json:"quality_source,omitempty"`
+	Timestamp     time.Time   `json:"timestamp"`
+	Writable      bool        `json:"writable"`
+	Unit          string      `json:"unit"`
+	Description   string      `json:"description"`
 }
 
 // NewIndustrialGateway creates a new gateway instance
 func NewIndustrialGateway(config *Config, logger *zap.Logger) *IndustrialGateway {
 	gateway := &IndustrialGateway{
-		logger:    logger,
-		protocols: make(map[string]protocols.ProtocolHandler),
-		config:    config,
+		logger:  logger,
+		drivers: protocols.NewDriverRegistry(logger),
+		bus:     events.NewBus(logger),
+		config:  config,
 		wsUpgrader: websocket.Upgrader{
 			CheckOrigin: func(r *http.Request) bool {
 				return true // Allow all origins for development
@@ -103,9 +186,95 @@ func NewIndustrialGateway(config *Config, logger *zap.Logger) *IndustrialGateway
 	// Register protocol handlers
 	gateway.registerProtocols()
 
+	// Load scripts transforming collected values and run by rules
+	gateway.scripts = scripting.NewRegistry(gateway, logger)
+	if err := gateway.scripts.Load(config.Scripts); err != nil {
+		logger.Error("Invalid scripts configuration", zap.Error(err))
+	}
+
+	// Authenticate clients of the network-facing APIs. An invalid
+	// configuration must not leave the APIs open.
+	authenticator, err := auth.NewAuthenticator(config.Auth)
+	if err != nil {
+		logger.Error("Invalid authentication configuration, denying all API requests", zap.Error(err))
+		authenticator = auth.DenyAll()
+	}
+	gateway.auth = authenticator
+
+	// Keep misbehaving devices and API clients from starving the others
+	if gateway.ingestLimiter, err = ratelimit.NewLimiter(config.IngestRateLimit); err != nil {
+		logger.Error("Invalid ingest rate limit configuration", zap.Error(err))
+	}
+	if gateway.apiLimiter, err = ratelimit.NewLimiter(config.APIRateLimit); err != nil {
+		logger.Error("Invalid API rate limit configuration", zap.Error(err))
+	}
+
+	// Evaluate edge rules against collected tag values
+	gateway.rules = rules.NewEngine(rules.Actuators{Writer: gateway, Events: gateway, Scripts: gateway.scripts}, 0, logger)
+	if err := gateway.rules.Load(config.Rules); err != nil {
+		logger.Error("Invalid rules configuration", zap.Error(err))
+	}
+
+	// Connect components through the event bus
+	gateway.bus.Subscribe("rules",
+		events.SubscribeOptions{Kinds: []events.Kind{events.KindData}, Blocking: true},
+		events.SinkHandler(gateway.rules, logger),
+	)
+	gateway.bus.Subscribe("websocket",
+		events.SubscribeOptions{Kinds: []events.Kind{events.KindAlarm, events.KindConnection}},
+		gateway.broadcastEvent,
+	)
+
+	// Pause data collection instead of dropping events when subscribers
+	// fall behind
+	gateway.pressure = backpressure.NewMonitor(config.Backpressure)
+	gateway.pressure.Register("events", gateway.bus)
+
+	// Account the memory of buffers, caches and queues and pause
+	// collection as the budget fills
+	gateway.memory = memory.NewAccountant(config.Memory, logger)
+	gateway.memory.Register("events", gateway.bus)
+	gateway.drivers.OnCreate(gateway.registerDriverMemory)
+	gateway.pressure.Register("memory", gateway.memory)
+
+	// Track the synchronization of the clock stamping collected data
+	if gateway.timeSync, err = timesync.NewMonitor(config.TimeSync, logger); err != nil {
+		logger.Error("Invalid time sync configuration", zap.Error(err))
+		gateway.timeSync, _ = timesync.NewMonitor(timesync.Config{}, logger)
+	}
+
+	// Report liveness and readiness
+	gateway.health = health.New(config.Health, logger)
+	gateway.registerHealthChecks()
+
+	// Forward collected data to cloud IoT services
+	gateway.openCloudSinks(config.CloudSinks)
+
+	// Log wall clock steps and drift affecting timestamps and record steps
+	// as periods of wrong timestamps
+	if clock, ok := protocols.DefaultClock.(*protocols.HybridClock); ok {
+		clock.OnEvent(gateway.logClockEvent)
+	}
+
 	return gateway
 }
 
+// registerDriverMemory accounts the caches of a driver once it is created
+func (g *IndustrialGateway) registerDriverMemory(protocol string, handler protocols.ProtocolHandler) {
+	if consumer, ok := handler.(memory.Consumer); ok {
+		g.memory.Register("driver/"+protocol, consumer)
+	}
+}
+
+func (g *IndustrialGateway) logClockEvent(event protocols.ClockEvent) {
+	g.logger.Warn("System clock changed",
+		zap.String("event", string(event.Type)),
+		zap.Duration("offset", event.Offset),
+		zap.Float64("drift_ppm", event.Drift),
+	)
+	g.timeSync.OnClockEvent(event)
+}
+
 func (g *IndustrialGateway) initMetrics() {
 	g.metrics.connectionsTotal = prometheus.NewCounter(prometheus.CounterOpts{
 		Name: "bifrost_connections_total",
@@ -128,33 +297,65 @@ func (g *IndustrialGateway) initMetrics() {
 		Buckets: prometheus.DefBuckets,
 	})
 
+	g.metrics.samplesThrottled = prometheus.NewCounter(prometheus.CounterOpts{
+		Name: "bifrost_samples_throttled_total",
+		Help: "Total number of samples not collected because of the ingest rate limit",
+	})
+
 	// Register metrics
 	prometheus.MustRegister(
 		g.metrics.connectionsTotal,
 		g.metrics.dataPointsProcessed,
 		g.metrics.errorRate,
 		g.metrics.responseTime,
+		g.metrics.samplesThrottled,
 	)
 }
 
 func (g *IndustrialGateway) registerProtocols() {
 	// Register Modbus TCP/RTU handler
-	modbusHandler := protocols.NewModbusHandler(g.logger)
-	g.protocols["modbus-tcp"] = modbusHandler
-	g.protocols["modbus-rtu"] = modbusHandler
+	g.drivers.MustRegister("modbus-tcp", protocols.NewModbusHandler, "modbus-rtu")
 
 	// Register OPC UA handler
-	opcuaHandler := protocols.NewOPCUAHandler(g.logger)
-	g.protocols["opcua"] = opcuaHandler
+	g.drivers.MustRegister("opcua", protocols.NewOPCUAHandler)
+
+	// Register MQTT Sparkplug B handler
+	g.drivers.MustRegister("sparkplug-b", protocols.NewSparkplugHandler)
+
+	// Register DNP3 handler
+	g.drivers.MustRegister("dnp3", protocols.NewDNP3Handler)
+
+	// Register BACnet/IP handler
+	g.drivers.MustRegister("bacnet-ip", protocols.NewBACnetHandler)
+
+	// Register EtherNet/IP handler
+	g.drivers.MustRegister("ethernet-ip", protocols.NewEtherNetIPHandler)
+
+	// Register Siemens S7 handler
+	g.drivers.MustRegister("s7", protocols.NewS7Handler)
 
-	// TODO: Add Ethernet/IP, S7, etc.
+	// Register CAN bus (J1939/CANopen) handler
+	g.drivers.MustRegister("can", protocols.NewCANHandler)
+
+	// Register SNMP handler
+	g.drivers.MustRegister("snmp", protocols.NewSNMPHandler)
+
+	// Register M-Bus handler
+	g.drivers.MustRegister("mbus", protocols.NewMBusHandler)
+
+	// Register KNXnet/IP handler
+	g.drivers.MustRegister("knx", protocols.NewKNXHandler)
+
+	// Register HART-IP handler
+	g.drivers.MustRegister("hart-ip", protocols.NewHARTIPHandler)
 }
 
 // Start begins the gateway services
 func (g *IndustrialGateway) Start(ctx context.Context) error {
+	config := g.currentConfig()
 	g.logger.Info("Starting Bifrost Industrial Gateway",
-		zap.Int("port", g.config.Port),
-		zap.Int("grpc_port", g.config.GRPCPort),
+		zap.Int("port", config.Port),
+		zap.Int("grpc_port", config.GRPCPort),
 	)
 
 	var wg sync.WaitGroup
@@ -173,6 +374,15 @@ func (g *IndustrialGateway) Start(ctx context.Context) error {
 		g.startGRPCServer(ctx)
 	}()
 
+	// Start plain TCP health endpoint if configured
+	wg.Add(1)
+	go func() {
+		defer wg.Done()
+		if err := g.health.ServeTCP(ctx); err != nil {
+			g.logger.Error("Health TCP endpoint error", zap.Error(err))
+		}
+	}()
+
 	// Start data collection loop
 	wg.Add(1)
 	go func() {
@@ -180,7 +390,46 @@ func (g *IndustrialGateway) Start(ctx context.Context) error {
 		g.startDataCollection(ctx)
 	}()
 
+	// Forward queued data to cloud IoT services
+	wg.Add(1)
+	go func() {
+		defer wg.Done()
+		g.runCloudSinks(ctx)
+	}()
+
+	// Query the clock synchronization
+	wg.Add(1)
+	go func() {
+		defer wg.Done()
+		g.timeSync.Run(ctx)
+	}()
+
+	// Enforce the memory budget
+	wg.Add(1)
+	go func() {
+		defer wg.Done()
+		g.memory.Run(ctx)
+	}()
+
+	// Feed the systemd watchdog while the liveness checks pass
+	wg.Add(1)
+	go func() {
+		defer wg.Done()
+		if err := systemd.RunWatchdog(ctx, g.alive, g.logger); err != nil {
+			g.logger.Error("systemd watchdog error", zap.Error(err))
+		}
+	}()
+
+	g.notifySystemd(systemd.Ready)
+	wg.Add(1)
+	go func() {
+		defer wg.Done()
+		<-ctx.Done()
+		g.notifySystemd(systemd.Stopping)
+	}()
+
 	wg.Wait()
+	g.bus.Close()
 	return nil
 }
 
@@ -188,55 +437,97 @@ func (g *IndustrialGateway) startHTTPServer(ctx context.Context) {
 	mux := http.NewServeMux()
 
 	// WebSocket endpoint for real-time data
-	mux.HandleFunc("/ws", g.handleWebSocket)
+	mux.HandleFunc("/ws", g.auth.Require(auth.PermissionRead, g.handleWebSocket))
 
 	// REST API endpoints
-	mux.HandleFunc("/api/devices", g.handleDevices)
-	mux.HandleFunc("/api/devices/discover", g.handleDiscovery)
-	mux.HandleFunc("/api/tags/read", g.handleTagRead)
-	mux.HandleFunc("/api/tags/write", g.handleTagWrite)
+	mux.HandleFunc("/api/devices", g.auth.Require(auth.PermissionRead, g.handleDevices))
+	mux.HandleFunc("/api/devices/discover", g.auth.Require(auth.PermissionWrite, g.handleDiscovery))
+	mux.HandleFunc("/api/tags", g.auth.Require(auth.PermissionRead, g.handleTags))
+	mux.HandleFunc("/api/tags/read", g.auth.Require(auth.PermissionRead, g.limitAPI(g.handleTagRead)))
+	mux.HandleFunc("/api/tags/write", g.auth.Require(auth.PermissionWrite, g.limitAPI(g.handleTagWrite)))
+	mux.HandleFunc("/api/rules", g.auth.RequireByMethod(g.handleRules))
+	mux.HandleFunc("/api/tokens", g.auth.Require(auth.PermissionRead, g.handleTokens))
+	mux.HandleFunc("/api/backpressure", g.auth.Require(auth.PermissionRead, g.handleBackpressure))
+	mux.HandleFunc("/api/timesync", g.auth.Require(auth.PermissionRead, g.handleTimeSync))
+	mux.HandleFunc("/api/timesync/corrections", g.auth.RequireByMethod(g.handleTimeSyncCorrections))
+
+	// Health endpoints for orchestrators, always unauthenticated. /health
+	// is the liveness report, used by --health-check.
+	mux.HandleFunc("/health", g.health.LivenessHandler())
+	mux.HandleFunc("/health/live", g.health.LivenessHandler())
+	mux.HandleFunc("/health/ready", g.health.ReadinessHandler())
 
 	// Metrics endpoint
-	if g.config.EnableMetrics {
-		mux.Handle("/metrics", promhttp.Handler())
+	config := g.currentConfig()
+	if config.EnableMetrics {
+		mux.Handle("/metrics", g.auth.Require(auth.PermissionRead, promhttp.Handler().ServeHTTP))
 	}
 
 	server := &http.Server{
-		Addr:    fmt.Sprintf(":%d", g.config.Port),
+		Addr:    fmt.Sprintf(":%d", config.Port),
 		Handler: mux,
 	}
+	if config.TLS.Enabled {
+		loader, err := tlsconfig.NewLoader(config.TLS, g.logger)
+		if err == nil {
+			server.TLSConfig, err = loader.ServerConfig()
+		}
+		if err != nil {
+			g.logger.Error("Invalid TLS configuration", zap.Error(err))
+			return
+		}
+	}
 
-	g.logger.Info("HTTP server started", zap.Int("port", g.config.Port))
+	g.logger.Info("HTTP server started", zap.Int("port", config.Port), zap.Bool("tls", config.TLS.Enabled))
 
 	go func() {
 		<-ctx.Done()
 		server.Shutdown(context.Background())
 	}()
 
-	if err := server.ListenAndServe(); err != nil && err != http.ErrServerClosed {
+	var err error
+	if server.TLSConfig != nil {
+		// The certificate comes from the TLS configuration
+		err = server.ListenAndServeTLS("", "")
+	} else {
+		err = server.ListenAndServe()
+	}
+	if err != nil && err != http.ErrServerClosed {
 		g.logger.Error("HTTP server error", zap.Error(err))
 	}
 }
 
 func (g *IndustrialGateway) startGRPCServer(ctx context.Context) {
-	// TODO: Implement gRPC server for backend API
-	g.logger.Info("gRPC server started", zap.Int("port", g.config.GRPCPort))
+	// TODO: Implement gRPC server for backend API, serving TLS from
+	// the TLS configuration like the HTTP server
+	g.logger.Info("gRPC server started", zap.Int("port", g.currentConfig().GRPCPort))
 }
 
 // startDataCollection runs the main data collection loop
 func (g *IndustrialGateway) startDataCollection(ctx context.Context) {
-	ticker := time.NewTicker(g.config.UpdateInterval)
+	interval := g.currentConfig().UpdateInterval
+	ticker := time.NewTicker(interval)
 	defer ticker.Stop()
 
-	g.logger.Info("Data collection started", zap.Duration("interval", g.config.UpdateInterval))
+	g.logger.Info("Data collection started", zap.Duration("interval", interval))
 
+	throttled := false
 	for {
 		select {
 		case <-ctx.Done():
 			g.logger.Info("Data collection stopped")
 			return
 		case <-ticker.C:
-			g.collectAllData(ctx)
+			// Reload may have changed the interval
+			if current := g.currentConfig().UpdateInterval; current != interval {
+				interval = current
+				ticker.Reset(interval)
+				g.logger.Info("Data collection interval changed", zap.Duration("interval", interval))
+			}
+			if throttled = g.throttleCollection(throttled); !throttled {
+				g.collectAllData(ctx)
+			}
+			g.collectionHeartbeat.Beat()
 		}
 	}
 }
@@ -267,41 +558,35 @@ func (g *IndustrialGateway) collectDeviceData(ctx context.Context, device *Devic
 		g.metrics.responseTime.Observe(duration.Seconds())
 	}()
 
-	handler, exists := g.protocols[device.Protocol]
+	handler, exists := g.drivers.Driver(device.Protocol)
 	if !exists {
 		g.logger.Error("Unknown protocol", zap.String("protocol", device.Protocol))
 		return
 	}
 
-	// Create protocols.Device from gateway.Device
-	protocolDevice := &protocols.Device{
-		ID:       device.ID,
-		Name:     device.Name,
-		Protocol: device.Protocol,
-		Address:  device.Address,
-		Port:     device.Port,
-		Config:   device.Config,
+	// A device over its rate limit is not polled until it is back within
+	// it. Each poll counts one point per tag; the size of the samples is
+	// charged once they are known.
+	ingestLimiter, _ := g.limiters()
+	if err := ingestLimiter.Allow(device.ID, len(device.Tags), 0); err != nil {
+		g.dropThrottled(device, len(device.Tags), err)
+		return
 	}
 
+	protocolDevice := device.toProtocolDevice()
+	samples := make([]protocols.TagSample, 0, len(device.Tags))
+	tags := make(map[string]*Tag, len(device.Tags))
+
 	// Read all tags for this device
 	for _, tag := range device.Tags {
 		select {
 		case <-ctx.Done():
 			return
 		default:
-			// Create protocols.Tag from gateway.Tag
-			protocolTag := &protocols.Tag{
-				ID:          tag.ID,
-				Name:        tag.Name,
-				Address:     tag.Address,
-				DataType:    tag.DataType,
-				Writable:    tag.Writable,
-				Unit:        tag.Unit,
-				Description: tag.Description,
-			}
-
+			protocolTag := tag.toProtocolTag()
 			value, err := handler.ReadTag(protocolDevice, protocolTag)
 			if err != nil {
+				tag.setStatus(protocols.NewQualityStatus(protocols.QualityBad, protocols.QualitySubstatusCommFailure, protocols.QualitySourceDriver))
 				g.metrics.errorRate.Inc()
 				device.Stats.RequestsFailed++
 				g.logger.Error("Failed to read tag",
@@ -312,52 +597,90 @@ func (g *IndustrialGateway) collectDeviceData(ctx context.Context, device *Devic
 				continue
 			}
 
-			// Update tag value
-			tag.Value = value
-			tag.Timestamp = time.Now()
-			tag.Quality = "GOOD"
+			status := protocols.GoodQuality
+			if protocolTag.Quality != "" {
+				status = protocolTag.Status()
+			}
+			name := events.SampleName(device.ID, tag.ID)
+			tags[name] = tag
+			samples = append(samples, protocols.TagSample{
+				Name:          name,
+				Value:         value,
+				Quality:       status.Quality,
+				Substatus:     status.Substatus,
+				QualitySource: status.Source,
+				Timestamp:     protocols.DefaultClock.Now(),
+				ClockSync:     g.timeSync.Sync(),
+				Unit:          tag.Unit,
+			})
 
 			device.Stats.RequestsSuccessful++
 			g.metrics.dataPointsProcessed.Inc()
+		}
+	}
 
-			// Broadcast to WebSocket clients
-			g.broadcastTagUpdate(device, tag)
+	// Update tag values after ingest scripts; samples computed by scripts
+	// are only published
+	samples = g.transformIngest(samples)
+	if payload, err := json.Marshal(samples); err == nil {
+		ingestLimiter.Charge(device.ID, 0, len(payload))
+	}
+	for _, sample := range samples {
+		tag, exists := tags[sample.Name]
+		if !exists {
+			continue
 		}
+		tag.Value = sample.Value
+		tag.Timestamp = sample.Timestamp
+		tag.setStatus(protocols.QualityStatus{Quality: sample.Quality, Substatus: sample.Substatus, Source: sample.QualitySource})
+
+		// Broadcast to WebSocket clients
+		g.broadcastTagUpdate(device, tag)
 	}
 
 	device.LastSeen = time.Now()
 	device.Stats.LastUpdate = time.Now()
+
+	g.bus.Publish(events.DataEvent{DeviceID: device.ID, Samples: samples})
 }
 
 // ConnectDevice establishes connection to an industrial device
 func (g *IndustrialGateway) ConnectDevice(ctx context.Context, device *Device) error {
-	handler, exists := g.protocols[device.Protocol]
-	if !exists {
-		return fmt.Errorf("unsupported protocol: %s", device.Protocol)
+	if _, err := protocols.ParseDeviceID(device.ID); err != nil {
+		return err
 	}
 
-	// Create protocols.Device from gateway.Device
-	protocolDevice := &protocols.Device{
-		ID:       device.ID,
-		Name:     device.Name,
-		Protocol: device.Protocol,
-		Address:  device.Address,
-		Port:     device.Port,
-		Config:   device.Config,
+	handler, exists := g.drivers.Driver(device.Protocol)
+	if !exists {
+		return fmt.Errorf("unsupported protocol: %s", device.Protocol)
 	}
 
 	// Attempt connection
+	protocolDevice := device.toProtocolDevice()
 	if err := handler.Connect(protocolDevice); err != nil {
 		g.metrics.errorRate.Inc()
+		g.bus.Publish(events.ConnectionEvent{
+			DeviceID: device.ID,
+			Protocol: device.Protocol,
+			Error:    err.Error(),
+			Time:     time.Now(),
+		})
 		return fmt.Errorf("failed to connect to device %s: %w", device.ID, err)
 	}
 
+	device.Handler = handler
 	device.Connected = true
 	device.LastSeen = time.Now()
 	g.metrics.connectionsTotal.Inc()
 
 	// Store device
 	g.devices.Store(device.ID, device)
+	g.bus.Publish(events.ConnectionEvent{
+		DeviceID:  device.ID,
+		Protocol:  device.Protocol,
+		Connected: true,
+		Time:      device.LastSeen,
+	})
 
 	g.logger.Info("Device connected",
 		zap.String("id", device.ID),
@@ -378,38 +701,87 @@ func (g *IndustrialGateway) DisconnectDevice(deviceID string) error {
 	device := deviceInterface.(*Device)
 
 	if device.Handler != nil {
-		// Create protocols.Device from gateway.Device
-		protocolDevice := &protocols.Device{
-			ID:       device.ID,
-			Name:     device.Name,
-			Protocol: device.Protocol,
-			Address:  device.Address,
-			Port:     device.Port,
-			Config:   device.Config,
-		}
-
-		if err := device.Handler.Disconnect(protocolDevice); err != nil {
+		if err := device.Handler.Disconnect(device.toProtocolDevice()); err != nil {
 			g.logger.Error("Error disconnecting device", zap.Error(err))
 		}
 	}
 
 	device.Connected = false
 	g.devices.Store(deviceID, device)
+	g.bus.Publish(events.ConnectionEvent{
+		DeviceID: device.ID,
+		Protocol: device.Protocol,
+		Time:     time.Now(),
+	})
 
 	g.logger.Info("Device disconnected", zap.String("id", deviceID))
 	return nil
 }
 
+// toProtocolDevice returns the protocols.Device used for handler calls,
+// creating it on first use.
+func (d *Device) toProtocolDevice() *protocols.Device {
+	if d.protocolDevice == nil {
+		d.protocolDevice = &protocols.Device{
+			ID:       d.ID,
+			Name:     d.Name,
+			Protocol: d.Protocol,
+			Address:  d.Address,
+			Port:     d.Port,
+			Config:   d.Config,
+		}
+	}
+	return d.protocolDevice
+}
+
+// toProtocolTag creates a protocols.Tag from a gateway.Tag
+func (t *Tag) toProtocolTag() *protocols.Tag {
+	return &protocols.Tag{
+		ID:          t.ID,
+		Name:        t.Name,
+		Address:     t.Address,
+		DataType:    t.DataType,
+		Writable:    t.Writable,
+		Unit:        t.Unit,
+		Description: t.Description,
+	}
+}
+
+// setStatus sets the quality, substatus and quality source of the tag
+func (t *Tag) setStatus(status protocols.QualityStatus) {
+	t.Quality = string(status.Quality)
+	t.Substatus = string(status.Substatus)
+	t.QualitySource = string(status.Source)
+}
+
+// broadcastTagUpdate sends a tag value to the WebSocket clients allowed to
+// read the tag
 func (g *IndustrialGateway) broadcastTagUpdate(device *Device, tag *Tag) {
-	message := map[string]interface{}{
+	g.broadcast(map[string]interface{}{
 		"type":      "tag_update",
 		"device_id": device.ID,
 		"tag":       tag,
-	}
+	}, func(identity auth.Identity) bool {
+		return g.auth.Allows(identity, auth.PermissionRead, device.ID, tag.ID)
+	})
+}
+
+// broadcastEvent sends alarm and connection events to WebSocket clients
+func (g *IndustrialGateway) broadcastEvent(event events.Event) {
+	g.broadcast(map[string]interface{}{
+		"type":  string(event.Kind()) + "_event",
+		"event": event,
+	}, nil)
+}
 
-	// Broadcast to all WebSocket clients
+// broadcast sends a message to the WebSocket clients for which allow
+// returns true, or to all clients if allow is nil
+func (g *IndustrialGateway) broadcast(message interface{}, allow func(identity auth.Identity) bool) {
 	g.wsClients.Range(func(key, value interface{}) bool {
 		conn := key.(*websocket.Conn)
+		if allow != nil && !allow(value.(auth.Identity)) {
+			return true
+		}
 		if err := conn.WriteJSON(message); err != nil {
 			// Remove disconnected client
 			g.wsClients.Delete(conn)
@@ -432,10 +804,20 @@ func (g *IndustrialGateway) GetStats() map[string]interface{} {
 		return true
 	})
 
+	ingestLimiter, apiLimiter := g.limiters()
 	return map[string]interface{}{
 		"devices_total":     deviceCount,
 		"devices_connected": connectedCount,
+		"event_subscribers": g.bus.Stats(),
+		"backpressure":      g.pressure.Signal(),
+		"memory":            g.memory.Stats(),
+		"cloud_sinks":       g.cloudSinkStats(),
+		"time_sync":         g.timeSync.Status(),
 		"uptime":            time.Since(time.Now()), // TODO: Track actual uptime
+		"rate_limits": map[string]interface{}{
+			"ingest": ingestLimiter.Stats(),
+			"api":    apiLimiter.Stats(),
+		},
 	}
 }
 
@@ -448,8 +830,10 @@ func (g *IndustrialGateway) handleWebSocket(w http.ResponseWriter, r *http.Reque
 		return
 	}
 
-	// Register client
-	g.wsClients.Store(conn, true)
+	// Register client with its identity, which is empty without
+	// authentication
+	identity, _ := auth.FromContext(r.Context())
+	g.wsClients.Store(conn, identity)
 
 	g.logger.Info("WebSocket client connected")
 
@@ -470,35 +854,374 @@ func (g *IndustrialGateway) handleWebSocket(w http.ResponseWriter, r *http.Reque
 }
 
 func (g *IndustrialGateway) handleDevices(w http.ResponseWriter, r *http.Request) {
-	w.Header().Set("Content-Type", "application/json")
+	if r.Method != http.MethodGet {
+		writeJSONError(w, http.StatusMethodNotAllowed, "method not allowed")
+		return
+	}
+
+	filter, err := deviceFilter(r)
+	if err != nil {
+		writeJSONError(w, http.StatusBadRequest, err.Error())
+		return
+	}
 
 	devices := make([]*Device, 0)
 	g.devices.Range(func(key, value interface{}) bool {
-		devices = append(devices, value.(*Device))
+		device := value.(*Device)
+		if filter == nil || filter.MatchString(device.ID) {
+			devices = append(devices, g.readableTags(r, device))
+		}
 		return true
 	})
 
-	// Simple JSON response (in production, use proper JSON library)
-	w.Write([]byte(fmt.Sprintf(`{"devices": %v}`, devices)))
+	writeJSON(w, http.StatusOK, map[string]interface{}{"devices": devices})
+}
+
+// readableTags returns the device with only the tags the client of a
+// request may read, or the device itself if it may read all of them
+func (g *IndustrialGateway) readableTags(r *http.Request, device *Device) *Device {
+	tags := make(map[string]*Tag, len(device.Tags))
+	for id, tag := range device.Tags {
+		if g.allows(r, auth.PermissionRead, device.ID, id) {
+			tags[id] = tag
+		}
+	}
+	if len(tags) == len(device.Tags) {
+		return device
+	}
+	restricted := *device
+	restricted.Tags = tags
+	return &restricted
+}
+
+// discoveryRequest is the body accepted by /api/devices/discover
+type discoveryRequest struct {
+	Protocol     string `json:"protocol"`
+	NetworkRange string `json:"network_range"`
+	TimeoutMs    int    `json:"timeout_ms,omitempty"`
 }
 
 func (g *IndustrialGateway) handleDiscovery(w http.ResponseWriter, r *http.Request) {
-	w.Header().Set("Content-Type", "application/json")
+	if r.Method != http.MethodPost {
+		writeJSONError(w, http.StatusMethodNotAllowed, "method not allowed")
+		return
+	}
+
+	var req discoveryRequest
+	if err := json.NewDecoder(r.Body).Decode(&req); err != nil {
+		writeJSONError(w, http.StatusBadRequest, "invalid request body: "+err.Error())
+		return
+	}
+
+	handler, exists := g.drivers.Driver(req.Protocol)
+	if !exists {
+		writeJSONError(w, http.StatusBadRequest, "unsupported protocol: "+req.Protocol)
+		return
+	}
+
+	timeout := 30 * time.Second
+	if req.TimeoutMs > 0 {
+		timeout = time.Duration(req.TimeoutMs) * time.Millisecond
+	}
+
+	ctx, cancel := context.WithTimeout(r.Context(), timeout)
+	defer cancel()
+
+	devices, err := handler.DiscoverDevices(ctx, req.NetworkRange)
+	if err != nil && err != context.DeadlineExceeded {
+		writeJSONError(w, http.StatusBadRequest, err.Error())
+		return
+	}
 
-	// TODO: Implement device discovery
-	w.Write([]byte(`{"message": "Device discovery not implemented yet"}`))
+	writeJSON(w, http.StatusOK, map[string]interface{}{"devices": devices})
+}
+
+// deviceFilter parses the device_id query parameter of a listing, a device
+// ID or a pattern such as "site1/line2/**". It returns nil without one.
+func deviceFilter(r *http.Request) (*protocols.DeviceIDPattern, error) {
+	pattern := r.URL.Query().Get("device_id")
+	if pattern == "" {
+		return nil, nil
+	}
+	return protocols.ParseDeviceIDPattern(pattern)
+}
+
+// tagSeries describes one tag in the /api/tags listing
+type tagSeries struct {
+	DeviceID protocols.DeviceID `json:"device_id"`
+	*Tag
+}
+
+func (g *IndustrialGateway) handleTags(w http.ResponseWriter, r *http.Request) {
+	if r.Method != http.MethodGet {
+		writeJSONError(w, http.StatusMethodNotAllowed, "method not allowed")
+		return
+	}
+
+	filter, err := deviceFilter(r)
+	if err != nil {
+		writeJSONError(w, http.StatusBadRequest, err.Error())
+		return
+	}
+
+	series := make([]tagSeries, 0)
+	g.devices.Range(func(key, value interface{}) bool {
+		device := value.(*Device)
+		if filter != nil && !filter.MatchString(device.ID) {
+			return true
+		}
+		for _, tag := range device.Tags {
+			if g.allows(r, auth.PermissionRead, device.ID, tag.ID) {
+				series = append(series, tagSeries{DeviceID: protocols.DeviceID(device.ID), Tag: tag})
+			}
+		}
+		return true
+	})
+
+	writeJSON(w, http.StatusOK, map[string]interface{}{"tags": series})
+}
+
+// tagReadRequest is the body accepted by /api/tags/read. An empty TagIDs
+// list reads every tag configured on the device that the client may read.
+type tagReadRequest struct {
+	DeviceID string   `json:"device_id"`
+	TagIDs   []string `json:"tag_ids"`
 }
 
 func (g *IndustrialGateway) handleTagRead(w http.ResponseWriter, r *http.Request) {
-	w.Header().Set("Content-Type", "application/json")
+	if r.Method != http.MethodPost {
+		writeJSONError(w, http.StatusMethodNotAllowed, "method not allowed")
+		return
+	}
+
+	var req tagReadRequest
+	if err := json.NewDecoder(r.Body).Decode(&req); err != nil {
+		writeJSONError(w, http.StatusBadRequest, "invalid request body: "+err.Error())
+		return
+	}
+
+	device, err := g.connectedDevice(req.DeviceID)
+	if err != nil {
+		writeJSONError(w, http.StatusNotFound, err.Error())
+		return
+	}
+
+	tagIDs := req.TagIDs
+	if len(tagIDs) == 0 {
+		for id := range device.Tags {
+			if g.allows(r, auth.PermissionRead, device.ID, id) {
+				tagIDs = append(tagIDs, id)
+			}
+		}
+	}
+
+	tags := make([]*protocols.Tag, 0, len(tagIDs))
+	errors := make(map[string]string)
+	for _, id := range tagIDs {
+		tag, exists := device.Tags[id]
+		if !exists {
+			errors[id] = "tag not found"
+			continue
+		}
+		if !g.allows(r, auth.PermissionRead, device.ID, id) {
+			errors[id] = "permission denied"
+			continue
+		}
+		tags = append(tags, tag.toProtocolTag())
+	}
+
+	start := time.Now()
+	values, err := device.Handler.ReadMultipleTags(device.toProtocolDevice(), tags)
+	g.metrics.responseTime.Observe(time.Since(start).Seconds())
+	if err != nil {
+		g.metrics.errorRate.Inc()
+		writeJSONError(w, http.StatusBadGateway, err.Error())
+		return
+	}
+
+	for _, tag := range tags {
+		if _, ok := values[tag.ID]; !ok {
+			errors[tag.ID] = "read failed"
+		}
+	}
+
+	writeJSON(w, http.StatusOK, map[string]interface{}{
+		"device_id": device.ID,
+		"values":    values,
+		"errors":    errors,
+		"timestamp": time.Now(),
+	})
+}
 
-	// TODO: Implement tag reading
-	w.Write([]byte(`{"message": "Tag reading not implemented yet"}`))
+// tagWriteRequest is the body accepted by /api/tags/write
+type tagWriteRequest struct {
+	DeviceID string      `json:"device_id"`
+	TagID    string      `json:"tag_id"`
+	Value    interface{} `json:"value"`
 }
 
 func (g *IndustrialGateway) handleTagWrite(w http.ResponseWriter, r *http.Request) {
+	if r.Method != http.MethodPost {
+		writeJSONError(w, http.StatusMethodNotAllowed, "method not allowed")
+		return
+	}
+
+	var req tagWriteRequest
+	if err := json.NewDecoder(r.Body).Decode(&req); err != nil {
+		writeJSONError(w, http.StatusBadRequest, "invalid request body: "+err.Error())
+		return
+	}
+
+	device, err := g.connectedDevice(req.DeviceID)
+	if err != nil {
+		writeJSONError(w, http.StatusNotFound, err.Error())
+		return
+	}
+
+	tag, exists := device.Tags[req.TagID]
+	if !exists {
+		writeJSONError(w, http.StatusNotFound, "tag not found: "+req.TagID)
+		return
+	}
+	if !g.allows(r, auth.PermissionWrite, device.ID, tag.ID) {
+		writeJSONError(w, http.StatusForbidden, "write permission required for tag "+tag.ID)
+		return
+	}
+
+	value, err := coerceJSONValue(req.Value, tag.DataType)
+	if err != nil {
+		writeJSONError(w, http.StatusBadRequest, err.Error())
+		return
+	}
+
+	start := time.Now()
+	err = device.Handler.WriteTag(device.toProtocolDevice(), tag.toProtocolTag(), value)
+	g.metrics.responseTime.Observe(time.Since(start).Seconds())
+	if err != nil {
+		g.metrics.errorRate.Inc()
+		writeJSONError(w, http.StatusBadGateway, err.Error())
+		return
+	}
+
+	writeJSON(w, http.StatusOK, map[string]interface{}{
+		"device_id": device.ID,
+		"tag_id":    tag.ID,
+		"value":     value,
+		"timestamp": time.Now(),
+	})
+}
+
+// connectedDevice looks up a device that has an active protocol handler
+func (g *IndustrialGateway) connectedDevice(deviceID string) (*Device, error) {
+	deviceInterface, exists := g.devices.Load(deviceID)
+	if !exists {
+		return nil, fmt.Errorf("device not found: %s", deviceID)
+	}
+
+	device := deviceInterface.(*Device)
+	if !device.Connected || device.Handler == nil {
+		return nil, fmt.Errorf("device not connected: %s", deviceID)
+	}
+
+	return device, nil
+}
+
+// coerceJSONValue converts a decoded JSON value (numbers arrive as float64)
+// into the Go type expected by the protocol handlers for dataType.
+func coerceJSONValue(value interface{}, dataType string) (interface{}, error) {
+	number, isNumber := value.(float64)
+
+	switch protocols.DataType(dataType) {
+	case protocols.DataTypeBool:
+		if b, ok := value.(bool); ok {
+			return b, nil
+		}
+		if isNumber {
+			return number != 0, nil
+		}
+	case protocols.DataTypeInt16:
+		if isNumber {
+			if err := checkInteger(number, math.MinInt16, math.MaxInt16+1, dataType); err != nil {
+				return nil, err
+			}
+			return int16(number), nil
+		}
+	case protocols.DataTypeUInt16:
+		if isNumber {
+			if err := checkInteger(number, 0, math.MaxUint16+1, dataType); err != nil {
+				return nil, err
+			}
+			return uint16(number), nil
+		}
+	case protocols.DataTypeInt32:
+		if isNumber {
+			if err := checkInteger(number, math.MinInt32, math.MaxInt32+1, dataType); err != nil {
+				return nil, err
+			}
+			return int32(number), nil
+		}
+	case protocols.DataTypeUInt32:
+		if isNumber {
+			if err := checkInteger(number, 0, math.MaxUint32+1, dataType); err != nil {
+				return nil, err
+			}
+			return uint32(number), nil
+		}
+	case protocols.DataTypeInt64:
+		if isNumber {
+			if err := checkInteger(number, math.MinInt64, math.MaxInt64+1, dataType); err != nil {
+				return nil, err
+			}
+			return int64(number), nil
+		}
+	case protocols.DataTypeUInt64:
+		if isNumber {
+			if err := checkInteger(number, 0, math.MaxUint64+1, dataType); err != nil {
+				return nil, err
+			}
+			return uint64(number), nil
+		}
+	case protocols.DataTypeFloat32:
+		if isNumber {
+			if math.Abs(number) > math.MaxFloat32 {
+				return nil, fmt.Errorf("value %v is out of range for data type %s", value, dataType)
+			}
+			return float32(number), nil
+		}
+	case protocols.DataTypeFloat64:
+		if isNumber {
+			return number, nil
+		}
+	case protocols.DataTypeString:
+		if str, ok := value.(string); ok {
+			return str, nil
+		}
+	default:
+		return value, nil
+	}
+
+	return nil, fmt.Errorf("value %v is not valid for data type %s", value, dataType)
+}
+
+// checkInteger returns an error unless number is a whole number from min
+// up to but excluding limit, so a value is never wrapped or truncated
+// before it is written to a device
+func checkInteger(number, min, limit float64, dataType string) error {
+	if number != math.Trunc(number) {
+		return fmt.Errorf("value %v is not a whole number as required by data type %s", number, dataType)
+	}
+	if number < min || number >= limit {
+		return fmt.Errorf("value %v is out of range for data type %s", number, dataType)
+	}
+	return nil
+}
+
+func writeJSON(w http.ResponseWriter, status int, body interface{}) {
 	w.Header().Set("Content-Type", "application/json")
+	w.WriteHeader(status)
+	json.NewEncoder(w).Encode(body)
+}
 
-	// TODO: Implement tag writing
-	w.Write([]byte(`{"message": "Tag writing not implemented yet"}`))
+func writeJSONError(w http.ResponseWriter, status int, message string) {
+	writeJSON(w, status, map[string]string{"error": message})
 }
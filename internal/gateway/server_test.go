@@ -0,0 +1,112 @@
+package gateway
+
+import (
+	"encoding/base64"
+	"math"
+	"testing"
+
+	"github.com/stretchr/testify/assert"
+	"go.uber.org/zap"
+
+	"github.com/bifrost/go-gateway/internal/backpressure"
+	"github.com/bifrost/go-gateway/internal/cloud"
+	"github.com/bifrost/go-gateway/internal/events"
+	"github.com/bifrost/go-gateway/internal/forward"
+	"github.com/bifrost/go-gateway/internal/health"
+	"github.com/bifrost/go-gateway/internal/memory"
+	"github.com/bifrost/go-gateway/internal/protocols"
+	"github.com/bifrost/go-gateway/internal/rules"
+	"github.com/bifrost/go-gateway/internal/scripting"
+	"github.com/bifrost/go-gateway/internal/timesync"
+)
+
+// newTestGateway wires a gateway like NewIndustrialGateway without
+// registering its metrics, which may happen once per process
+func newTestGateway(t *testing.T, config *Config) *IndustrialGateway {
+	t.Helper()
+	logger := zap.NewNop()
+	g := &IndustrialGateway{
+		logger:   logger,
+		drivers:  protocols.NewDriverRegistry(logger),
+		bus:      events.NewBus(logger),
+		config:   config,
+		pressure: backpressure.NewMonitor(config.Backpressure),
+		memory:   memory.NewAccountant(config.Memory, logger),
+		health:   health.New(config.Health, logger),
+	}
+	g.timeSync, _ = timesync.NewMonitor(config.TimeSync, logger)
+	g.scripts = scripting.NewRegistry(g, logger)
+	g.rules = rules.NewEngine(rules.Actuators{Writer: g, Events: g, Scripts: g.scripts}, 0, logger)
+	assert.NoError(t, g.scripts.Load(config.Scripts))
+	assert.NoError(t, g.rules.Load(config.Rules))
+	g.registerHealthChecks()
+	g.drivers.OnCreate(g.registerDriverMemory)
+	g.openCloudSinks(config.CloudSinks)
+	t.Cleanup(g.bus.Close)
+	t.Cleanup(func() { g.reloadCloudSinks(nil) })
+	return g
+}
+
+// testCloudSink configures an Azure sink queueing in a temporary directory
+func testCloudSink(t *testing.T, name, deviceID string) cloud.Config {
+	key := base64.StdEncoding.EncodeToString([]byte("device key"))
+	return cloud.Config{
+		Name:     name,
+		Provider: cloud.ProviderAzure,
+		Azure: cloud.AzureConfig{
+			ConnectionString: "HostName=hub.azure-devices.net;DeviceId=" + deviceID + ";SharedAccessKey=" + key,
+		},
+		Queue: forward.QueueConfig{Dir: t.TempDir()},
+	}
+}
+
+func TestCoerceJSONValue(t *testing.T) {
+	for _, test := range []struct {
+		value    interface{}
+		dataType protocols.DataType
+		expected interface{}
+	}{
+		{true, protocols.DataTypeBool, true},
+		{1.0, protocols.DataTypeBool, true},
+		{-32768.0, protocols.DataTypeInt16, int16(-32768)},
+		{32767.0, protocols.DataTypeInt16, int16(32767)},
+		{65535.0, protocols.DataTypeUInt16, uint16(65535)},
+		{-2147483648.0, protocols.DataTypeInt32, int32(math.MinInt32)},
+		{4294967295.0, protocols.DataTypeUInt32, uint32(math.MaxUint32)},
+		{-9223372036854775808.0, protocols.DataTypeInt64, int64(math.MinInt64)},
+		{0.0, protocols.DataTypeUInt64, uint64(0)},
+		{1.5, protocols.DataTypeFloat32, float32(1.5)},
+		{1.5, protocols.DataTypeFloat64, 1.5},
+		{"on", protocols.DataTypeString, "on"},
+	} {
+		value, err := coerceJSONValue(test.value, string(test.dataType))
+		assert.NoError(t, err, "%v as %s", test.value, test.dataType)
+		assert.Equal(t, test.expected, value, "%v as %s", test.value, test.dataType)
+	}
+}
+
+func TestCoerceJSONValue_Invalid(t *testing.T) {
+	for _, test := range []struct {
+		value    interface{}
+		dataType protocols.DataType
+	}{
+		{70000.0, protocols.DataTypeInt16},
+		{32768.0, protocols.DataTypeInt16},
+		{-32769.0, protocols.DataTypeInt16},
+		{12.5, protocols.DataTypeInt16},
+		{-1.0, protocols.DataTypeUInt16},
+		{65536.0, protocols.DataTypeUInt16},
+		{2147483648.0, protocols.DataTypeInt32},
+		{4294967296.0, protocols.DataTypeUInt32},
+		{0.1, protocols.DataTypeUInt32},
+		{9223372036854775808.0, protocols.DataTypeInt64},
+		{18446744073709551616.0, protocols.DataTypeUInt64},
+		{-1.0, protocols.DataTypeUInt64},
+		{1e39, protocols.DataTypeFloat32},
+		{"12", protocols.DataTypeInt16},
+		{12.0, protocols.DataTypeString},
+	} {
+		_, err := coerceJSONValue(test.value, string(test.dataType))
+		assert.Error(t, err, "%v as %s", test.value, test.dataType)
+	}
+}
@@ -0,0 +1,46 @@
+package gateway
+
+import (
+	"encoding/json"
+	"net/http"
+
+	"github.com/bifrost/go-gateway/internal/timesync"
+)
+
+// handleTimeSync returns the synchronization of the clock, the periods in
+// which it was wrong and the timestamp corrections
+func (g *IndustrialGateway) handleTimeSync(w http.ResponseWriter, r *http.Request) {
+	if r.Method != http.MethodGet {
+		writeJSONError(w, http.StatusMethodNotAllowed, "method not allowed")
+		return
+	}
+	writeJSON(w, http.StatusOK, map[string]interface{}{
+		"status":      g.timeSync.Status(),
+		"windows":     g.timeSync.Windows(),
+		"corrections": g.timeSync.Corrections(),
+	})
+}
+
+// handleTimeSyncCorrections lists the corrections on GET and adds one on
+// POST, re-stamping or flagging data of a period that is still queued
+func (g *IndustrialGateway) handleTimeSyncCorrections(w http.ResponseWriter, r *http.Request) {
+	switch r.Method {
+	case http.MethodGet:
+		writeJSON(w, http.StatusOK, map[string]interface{}{"corrections": g.timeSync.Corrections()})
+
+	case http.MethodPost:
+		var correction timesync.Correction
+		if err := json.NewDecoder(r.Body).Decode(&correction); err != nil {
+			writeJSONError(w, http.StatusBadRequest, "invalid request body: "+err.Error())
+			return
+		}
+		if err := g.timeSync.Correct(correction); err != nil {
+			writeJSONError(w, http.StatusBadRequest, err.Error())
+			return
+		}
+		writeJSON(w, http.StatusCreated, correction)
+
+	default:
+		writeJSONError(w, http.StatusMethodNotAllowed, "method not allowed")
+	}
+}
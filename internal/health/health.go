@@ -0,0 +1,293 @@
+// Package health reports whether the gateway is alive and ready to serve
+package health
+
+import (
+	"context"
+	"encoding/json"
+	"fmt"
+	"net"
+	"net/http"
+	"sort"
+	"sync"
+	"sync/atomic"
+	"time"
+
+	"go.uber.org/zap"
+)
+
+// Health Checks
+//
+// Components register checks: the collection loop and flush threads a
+// Heartbeat, sinks whether their uplink is failing, the gateway whether
+// devices are connected and buffers are overloaded. Two reports are built
+// from them for orchestrators and watchdogs:
+//
+//   - liveness, from the checks registered for it, tells whether the
+//     process is stuck and must be restarted;
+//   - readiness, from all checks, tells whether it does its job. A failed
+//     critical check makes it down, any other failed check degraded.
+//
+// Both are served over HTTP, 503 when down, and the readiness status over
+// a plain TCP endpoint that writes one line and closes the connection, for
+// load balancers and watchdogs without an HTTP client.
+
+// Status is the health of a check or report
+type Status string
+
+const (
+	StatusUp       Status = "up"
+	StatusDegraded Status = "degraded" // Working with reduced function
+	StatusDown     Status = "down"
+)
+
+const defaultTimeout = 5 * time.Second
+
+// Check returns nil if a component is healthy, or why it is not
+type Check func(ctx context.Context) error
+
+// CheckOptions configures how a check affects the reports
+type CheckOptions struct {
+	Liveness bool // Part of the liveness report too
+	Critical bool // Failing makes readiness down instead of degraded
+}
+
+// Result is the outcome of one check
+type Result struct {
+	Status   Status        `json:"status"`
+	Error    string        `json:"error,omitempty"`
+	Duration time.Duration `json:"duration"`
+}
+
+// Report is the combined outcome of the checks
+type Report struct {
+	Status Status            `json:"status"`
+	Time   time.Time         `json:"time"`
+	Checks map[string]Result `json:"checks"`
+}
+
+// Config configures the health endpoints
+type Config struct {
+	TCPAddress string        `yaml:"tcp_address"` // E.g. ":8081", disabled if empty
+	Timeout    time.Duration `yaml:"timeout"`     // Per check, default 5s
+}
+
+type check struct {
+	options CheckOptions
+	check   Check
+}
+
+// Health runs the registered checks
+type Health struct {
+	config Config
+	logger *zap.Logger
+
+	mutex  sync.RWMutex
+	checks map[string]check
+}
+
+// New creates a Health without checks
+func New(config Config, logger *zap.Logger) *Health {
+	if config.Timeout <= 0 {
+		config.Timeout = defaultTimeout
+	}
+	return &Health{config: config, logger: logger, checks: make(map[string]check)}
+}
+
+// Register adds or replaces a check
+func (h *Health) Register(name string, options CheckOptions, c Check) {
+	h.mutex.Lock()
+	defer h.mutex.Unlock()
+	h.checks[name] = check{options: options, check: c}
+}
+
+// Unregister removes a check
+func (h *Health) Unregister(name string) {
+	h.mutex.Lock()
+	defer h.mutex.Unlock()
+	delete(h.checks, name)
+}
+
+// Liveness runs the liveness checks. Any failure makes it down.
+func (h *Health) Liveness(ctx context.Context) Report {
+	return h.report(ctx, true)
+}
+
+// Readiness runs all checks
+func (h *Health) Readiness(ctx context.Context) Report {
+	return h.report(ctx, false)
+}
+
+func (h *Health) report(ctx context.Context, liveness bool) Report {
+	h.mutex.RLock()
+	checks := make(map[string]check, len(h.checks))
+	for name, c := range h.checks {
+		if !liveness || c.options.Liveness {
+			checks[name] = c
+		}
+	}
+	h.mutex.RUnlock()
+
+	report := Report{Status: StatusUp, Time: time.Now(), Checks: make(map[string]Result, len(checks))}
+	var mutex sync.Mutex
+	var wg sync.WaitGroup
+	for name, c := range checks {
+		wg.Add(1)
+		go func(name string, c check) {
+			defer wg.Done()
+			result := h.run(ctx, c)
+
+			mutex.Lock()
+			defer mutex.Unlock()
+			report.Checks[name] = result
+			if result.Status == StatusUp {
+				return
+			}
+			if liveness || c.options.Critical {
+				report.Status = StatusDown
+			} else if report.Status == StatusUp {
+				report.Status = StatusDegraded
+			}
+		}(name, c)
+	}
+	wg.Wait()
+	return report
+}
+
+// run calls a check with the timeout, recovering from a panic so that a
+// faulty check reports down instead of stopping the gateway
+func (h *Health) run(ctx context.Context, c check) Result {
+	ctx, cancel := context.WithTimeout(ctx, h.config.Timeout)
+	defer cancel()
+
+	start := time.Now()
+	done := make(chan error, 1)
+	go func() {
+		defer func() {
+			if r := recover(); r != nil {
+				done <- fmt.Errorf("check panicked: %v", r)
+			}
+		}()
+		done <- c.check(ctx)
+	}()
+
+	var err error
+	select {
+	case err = <-done:
+	case <-ctx.Done():
+		err = fmt.Errorf("check timed out")
+	}
+	if err != nil {
+		return Result{Status: StatusDown, Error: err.Error(), Duration: time.Since(start)}
+	}
+	return Result{Status: StatusUp, Duration: time.Since(start)}
+}
+
+// LivenessHandler serves the liveness report, 503 when down
+func (h *Health) LivenessHandler() http.HandlerFunc {
+	return h.handler(h.Liveness)
+}
+
+// ReadinessHandler serves the readiness report, 503 when down
+func (h *Health) ReadinessHandler() http.HandlerFunc {
+	return h.handler(h.Readiness)
+}
+
+func (h *Health) handler(report func(context.Context) Report) http.HandlerFunc {
+	return func(w http.ResponseWriter, r *http.Request) {
+		result := report(r.Context())
+		status := http.StatusOK
+		if result.Status == StatusDown {
+			status = http.StatusServiceUnavailable
+		}
+		w.Header().Set("Content-Type", "application/json")
+		w.Header().Set("Cache-Control", "no-store")
+		w.WriteHeader(status)
+		json.NewEncoder(w).Encode(result)
+	}
+}
+
+// ServeTCP writes the readiness status and the failed checks as one line,
+// e.g. "degraded devices", to every connection on config.TCPAddress until
+// ctx is done. It returns immediately if no address is configured.
+func (h *Health) ServeTCP(ctx context.Context) error {
+	if h.config.TCPAddress == "" {
+		return nil
+	}
+	listener, err := net.Listen("tcp", h.config.TCPAddress)
+	if err != nil {
+		return err
+	}
+	h.logger.Info("Health TCP endpoint started", zap.String("address", listener.Addr().String()))
+	return h.serveTCP(ctx, listener)
+}
+
+func (h *Health) serveTCP(ctx context.Context, listener net.Listener) error {
+	go func() {
+		<-ctx.Done()
+		listener.Close()
+	}()
+
+	for {
+		conn, err := listener.Accept()
+		if err != nil {
+			if ctx.Err() != nil {
+				return nil
+			}
+			return err
+		}
+		go func() {
+			defer conn.Close()
+			conn.SetWriteDeadline(time.Now().Add(h.config.Timeout))
+			fmt.Fprintln(conn, StatusLine(h.Readiness(ctx)))
+		}()
+	}
+}
+
+// StatusLine formats a report as its status followed by the names of the
+// failed checks
+func StatusLine(report Report) string {
+	var failed []string
+	for name, result := range report.Checks {
+		if result.Status != StatusUp {
+			failed = append(failed, name)
+		}
+	}
+	sort.Strings(failed)
+
+	line := string(report.Status)
+	for _, name := range failed {
+		line += " " + name
+	}
+	return line
+}
+
+// Heartbeat is a liveness check for a loop: it fails when Beat was not
+// called within the timeout
+type Heartbeat struct {
+	// Accessed atomically, first for 64-bit alignment
+	last    int64 // Unix nanoseconds
+	timeout int64 // Nanoseconds
+}
+
+// NewHeartbeat creates a heartbeat that beat now
+func NewHeartbeat(timeout time.Duration) *Heartbeat {
+	return &Heartbeat{last: time.Now().UnixNano(), timeout: int64(timeout)}
+}
+
+// SetTimeout changes the timeout, e.g. when the loop interval changes
+func (h *Heartbeat) SetTimeout(timeout time.Duration) {
+	atomic.StoreInt64(&h.timeout, int64(timeout))
+}
+
+// Beat records that the loop is alive
+func (h *Heartbeat) Beat() {
+	atomic.StoreInt64(&h.last, time.Now().UnixNano())
+}
+
+// Check fails when the last beat is older than the timeout
+func (h *Heartbeat) Check(ctx context.Context) error {
+	if since := time.Since(time.Unix(0, atomic.LoadInt64(&h.last))); since > time.Duration(atomic.LoadInt64(&h.timeout)) {
+		return fmt.Errorf("no heartbeat for %v", since.Round(time.Second))
+	}
+	return nil
+}
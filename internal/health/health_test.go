@@ -0,0 +1,107 @@
+package health
+
+import (
+	"bufio"
+	"context"
+	"encoding/json"
+	"errors"
+	"net"
+	"net/http"
+	"net/http/httptest"
+	"testing"
+	"time"
+
+	"github.com/stretchr/testify/assert"
+	"go.uber.org/zap"
+)
+
+func ok(ctx context.Context) error { return nil }
+
+func failing(ctx context.Context) error { return errors.New("connection refused") }
+
+func TestHealth_Reports(t *testing.T) {
+	h := New(Config{}, zap.NewNop())
+	h.Register("collection", CheckOptions{Liveness: true, Critical: true}, ok)
+	h.Register("devices", CheckOptions{}, failing)
+
+	live := h.Liveness(context.Background())
+	assert.Equal(t, StatusUp, live.Status)
+	assert.Len(t, live.Checks, 1)
+
+	ready := h.Readiness(context.Background())
+	assert.Equal(t, StatusDegraded, ready.Status)
+	assert.Equal(t, StatusDown, ready.Checks["devices"].Status)
+	assert.Equal(t, "connection refused", ready.Checks["devices"].Error)
+	assert.Equal(t, "degraded devices", StatusLine(ready))
+
+	// A failed critical check makes readiness down
+	h.Register("uplink", CheckOptions{Critical: true}, failing)
+	assert.Equal(t, "down devices uplink", StatusLine(h.Readiness(context.Background())))
+
+	h.Unregister("uplink")
+	h.Unregister("devices")
+	assert.Equal(t, StatusUp, h.Readiness(context.Background()).Status)
+}
+
+func TestHealth_FaultyChecks(t *testing.T) {
+	h := New(Config{Timeout: 10 * time.Millisecond}, zap.NewNop())
+	h.Register("stuck", CheckOptions{Liveness: true}, func(ctx context.Context) error {
+		time.Sleep(time.Second)
+		return nil
+	})
+	h.Register("panics", CheckOptions{Liveness: true}, func(ctx context.Context) error {
+		panic("nil map")
+	})
+
+	report := h.Liveness(context.Background())
+	assert.Equal(t, StatusDown, report.Status)
+	assert.Equal(t, "check timed out", report.Checks["stuck"].Error)
+	assert.Equal(t, "check panicked: nil map", report.Checks["panics"].Error)
+}
+
+func TestHealth_Endpoints(t *testing.T) {
+	h := New(Config{}, zap.NewNop())
+	h.Register("uplink", CheckOptions{Critical: true}, failing)
+
+	w := httptest.NewRecorder()
+	h.LivenessHandler()(w, httptest.NewRequest(http.MethodGet, "/health/live", nil))
+	assert.Equal(t, http.StatusOK, w.Code)
+
+	w = httptest.NewRecorder()
+	h.ReadinessHandler()(w, httptest.NewRequest(http.MethodGet, "/health/ready", nil))
+	assert.Equal(t, http.StatusServiceUnavailable, w.Code)
+	var report Report
+	assert.NoError(t, json.NewDecoder(w.Body).Decode(&report))
+	assert.Equal(t, StatusDown, report.Status)
+
+	listener, err := net.Listen("tcp", "127.0.0.1:0")
+	assert.NoError(t, err)
+	ctx, cancel := context.WithCancel(context.Background())
+	done := make(chan error)
+	go func() { done <- h.serveTCP(ctx, listener) }()
+
+	conn, err := net.Dial("tcp", listener.Addr().String())
+	assert.NoError(t, err)
+	line, err := bufio.NewReader(conn).ReadString('\n')
+	assert.NoError(t, err)
+	assert.Equal(t, "down uplink\n", line)
+	conn.Close()
+
+	cancel()
+	assert.NoError(t, <-done)
+}
+
+func TestHeartbeat(t *testing.T) {
+	heartbeat := NewHeartbeat(20 * time.Millisecond)
+	assert.NoError(t, heartbeat.Check(context.Background()))
+
+	time.Sleep(30 * time.Millisecond)
+	assert.ErrorContains(t, heartbeat.Check(context.Background()), "no heartbeat")
+
+	heartbeat.SetTimeout(time.Minute)
+	assert.NoError(t, heartbeat.Check(context.Background()))
+	heartbeat.SetTimeout(time.Millisecond)
+	time.Sleep(2 * time.Millisecond)
+	heartbeat.Beat()
+	assert.NoError(t, heartbeat.Check(context.Background()))
+}
@@ -0,0 +1,105 @@
+package logging
+
+import (
+	"encoding/binary"
+	"errors"
+	"net"
+	"os"
+	"path/filepath"
+	"strconv"
+	"strings"
+	"testing"
+	"time"
+
+	"github.com/stretchr/testify/assert"
+	"go.uber.org/zap"
+	"go.uber.org/zap/zapcore"
+)
+
+// listen returns a datagram listener and a function reading one message
+func listen(t *testing.T, network, address string) (net.PacketConn, func() string) {
+	conn, err := net.ListenPacket(network, address)
+	assert.NoError(t, err)
+	t.Cleanup(func() { conn.Close() })
+
+	return conn, func() string {
+		buf := make([]byte, 65536)
+		conn.SetReadDeadline(time.Now().Add(time.Second))
+		n, _, err := conn.ReadFrom(buf)
+		assert.NoError(t, err)
+		return string(buf[:n])
+	}
+}
+
+func TestCore_Journald(t *testing.T) {
+	socket := filepath.Join(t.TempDir(), "journal.sock")
+	_, read := listen(t, "unixgram", socket)
+
+	core, err := NewCore(Config{Backend: BackendJournald, Address: socket, Identifier: "bifrost"}, zapcore.InfoLevel)
+	assert.NoError(t, err)
+	defer core.Close()
+	logger := zap.New(core).Named("gateway").With(zap.String("device", "site/pump"))
+
+	logger.Debug("Not enabled")
+	logger.Warn("Device disconnected", zap.Duration("offline", 2*time.Second), zap.Error(errors.New("timeout")))
+	assert.Equal(t, strings.Join([]string{
+		"MESSAGE=Device disconnected",
+		"PRIORITY=4",
+		"SYSLOG_IDENTIFIER=bifrost",
+		"LOGGER=gateway",
+		"DEVICE=site/pump",
+		"ERROR=timeout",
+		"OFFLINE=2s",
+		"",
+	}, "\n"), read())
+
+	// Multi-line values are length-prefixed
+	logger.Error("Script failed", zap.String("trace", "line 1\nline 2"))
+	var length [8]byte
+	binary.LittleEndian.PutUint64(length[:], 13)
+	assert.True(t, strings.HasSuffix(read(), "TRACE\n"+string(length[:])+"line 1\nline 2\n"))
+}
+
+func TestCore_Syslog(t *testing.T) {
+	conn, read := listen(t, "udp", "127.0.0.1:0")
+
+	core, err := NewCore(Config{
+		Backend:    BackendSyslog,
+		Network:    "udp",
+		Address:    conn.LocalAddr().String(),
+		Facility:   16,
+		Identifier: "bifrost",
+	}, zapcore.DebugLevel)
+	assert.NoError(t, err)
+	defer core.Close()
+	core.sink.hostname = "edge-01"
+
+	logger := zap.New(core)
+	logger.Info("Tag written", zap.String("tag", `level "raw"]`), zap.Int("unit id", 3))
+	message := read()
+	assert.True(t, strings.HasPrefix(message, "<134>1 "), message)
+	assert.True(t, strings.HasSuffix(message,
+		` edge-01 bifrost `+strconv.Itoa(os.Getpid())+` - [fields@32473 tag="level \"raw\"\]" unit_id="3"] Tag written`), message)
+
+	logger.Error("Plain")
+	assert.True(t, strings.HasSuffix(read(), " - - Plain"))
+
+	_, err = NewCore(Config{Backend: BackendSyslog, Facility: 24}, zapcore.InfoLevel)
+	assert.Error(t, err)
+	_, err = NewCore(Config{Backend: "file"}, zapcore.InfoLevel)
+	assert.Error(t, err)
+}
+
+func TestJournalFieldName(t *testing.T) {
+	for key, name := range map[string]string{
+		"device":      "DEVICE",
+		"fill_ratio":  "FILL_RATIO",
+		"rate-limit":  "RATE_LIMIT",
+		"_cursor":     "F_CURSOR",
+		"2fa":         "F2FA",
+		"":            "F",
+		"device.name": "DEVICE_NAME",
+	} {
+		assert.Equal(t, name, journalFieldName(key), key)
+	}
+}
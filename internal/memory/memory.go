@@ -0,0 +1,240 @@
+// Package memory keeps the gateway within a memory budget
+package memory
+
+import (
+	"context"
+	"os"
+	"runtime"
+	"runtime/debug"
+	"sort"
+	"strconv"
+	"strings"
+	"sync"
+	"time"
+
+	"go.uber.org/zap"
+
+	"github.com/bifrost/go-gateway/internal/backpressure"
+)
+
+// Memory Budget
+//
+// Buffers, caches and queues register with an Accountant and report how
+// many bytes they hold. The Accountant compares the resident set size of
+// the process with the budget and, when it exceeds the high watermark,
+// frees memory down to the low watermark before the OOM killer steps in:
+//
+//  1. caches (Shrinkers) evict entries, the largest cache first;
+//  2. buffers (Flushers) write what they hold to disk or their uplink;
+//  3. freed memory is returned to the operating system.
+//
+// The Accountant is also a backpressure.Source whose fill ratio is the
+// share of the budget in use, so producers slow down as the budget fills.
+
+const (
+	defaultHighWatermark = 0.9
+	defaultLowWatermark  = 0.75
+	defaultInterval      = 5 * time.Second
+)
+
+// Consumer is a component holding memory
+type Consumer interface {
+	MemoryUsage() int64 // Estimated bytes held
+}
+
+// Shrinker is a consumer that can drop data it can recreate, e.g. a cache
+type Shrinker interface {
+	Consumer
+	Shrink(bytes int64) int64 // Frees about bytes, returns the bytes freed
+}
+
+// Flusher is a consumer that can move data out of memory, e.g. a write
+// buffer
+type Flusher interface {
+	Consumer
+	Flush() error
+}
+
+// Config configures the budget
+type Config struct {
+	Limit         int64         `yaml:"limit"`          // Bytes of resident memory, 0 disables enforcement
+	HighWatermark float64       `yaml:"high_watermark"` // Share of the limit that starts freeing, default 0.9
+	LowWatermark  float64       `yaml:"low_watermark"`  // Share of the limit freeing aims for, default 0.75
+	Interval      time.Duration `yaml:"interval"`       // Between checks, default 5s
+}
+
+// Stats are the memory use and the actions taken
+type Stats struct {
+	Limit     int64            `json:"limit"`
+	RSS       int64            `json:"rss"`
+	Consumers map[string]int64 `json:"consumers"` // Bytes by consumer
+	Shrinks   uint64           `json:"shrinks"`
+	Flushes   uint64           `json:"flushes"`
+}
+
+// Accountant enforces the memory budget
+type Accountant struct {
+	config Config
+	logger *zap.Logger
+	rss    func() int64
+
+	mutex     sync.Mutex
+	consumers map[string]Consumer
+	shrinks   uint64
+	flushes   uint64
+}
+
+// NewAccountant creates an accountant without consumers
+func NewAccountant(config Config, logger *zap.Logger) *Accountant {
+	if config.HighWatermark <= 0 || config.HighWatermark > 1 {
+		config.HighWatermark = defaultHighWatermark
+	}
+	if config.LowWatermark <= 0 || config.LowWatermark > config.HighWatermark {
+		config.LowWatermark = config.HighWatermark * defaultLowWatermark / defaultHighWatermark
+	}
+	if config.Interval <= 0 {
+		config.Interval = defaultInterval
+	}
+	return &Accountant{
+		config:    config,
+		logger:    logger,
+		rss:       residentSetSize,
+		consumers: make(map[string]Consumer),
+	}
+}
+
+// Register adds or replaces a consumer
+func (a *Accountant) Register(name string, consumer Consumer) {
+	a.mutex.Lock()
+	defer a.mutex.Unlock()
+	a.consumers[name] = consumer
+}
+
+// Unregister removes a consumer
+func (a *Accountant) Unregister(name string) {
+	a.mutex.Lock()
+	defer a.mutex.Unlock()
+	delete(a.consumers, name)
+}
+
+// Run checks the budget every interval until ctx is done. It returns
+// immediately without a limit.
+func (a *Accountant) Run(ctx context.Context) {
+	if a.config.Limit <= 0 {
+		return
+	}
+
+	ticker := time.NewTicker(a.config.Interval)
+	defer ticker.Stop()
+	for {
+		select {
+		case <-ctx.Done():
+			return
+		case <-ticker.C:
+			a.Check()
+		}
+	}
+}
+
+// Check frees memory if the process exceeds the high watermark and
+// returns the bytes the consumers reported freeing
+func (a *Accountant) Check() int64 {
+	if a.config.Limit <= 0 {
+		return 0
+	}
+	rss := a.rss()
+	if float64(rss) <= a.config.HighWatermark*float64(a.config.Limit) {
+		return 0
+	}
+
+	a.mutex.Lock()
+	defer a.mutex.Unlock()
+
+	need := rss - int64(a.config.LowWatermark*float64(a.config.Limit))
+	a.logger.Warn("Memory budget exceeded, freeing memory",
+		zap.Int64("rss", rss),
+		zap.Int64("limit", a.config.Limit),
+		zap.Int64("target", need),
+	)
+
+	var freed int64
+	for _, name := range a.byUsage() {
+		if shrinker, ok := a.consumers[name].(Shrinker); ok && freed < need {
+			freed += shrinker.Shrink(need - freed)
+			a.shrinks++
+		}
+	}
+	for _, name := range a.byUsage() {
+		if flusher, ok := a.consumers[name].(Flusher); ok && freed < need {
+			before := flusher.MemoryUsage()
+			if err := flusher.Flush(); err != nil {
+				a.logger.Error("Failed to flush to free memory", zap.String("consumer", name), zap.Error(err))
+				continue
+			}
+			freed += before - flusher.MemoryUsage()
+			a.flushes++
+		}
+	}
+
+	debug.FreeOSMemory()
+	return freed
+}
+
+// byUsage returns the consumer names, largest first
+func (a *Accountant) byUsage() []string {
+	usage := make(map[string]int64, len(a.consumers))
+	names := make([]string, 0, len(a.consumers))
+	for name, consumer := range a.consumers {
+		usage[name] = consumer.MemoryUsage()
+		names = append(names, name)
+	}
+	sort.Slice(names, func(i, j int) bool {
+		if usage[names[i]] != usage[names[j]] {
+			return usage[names[i]] > usage[names[j]]
+		}
+		return names[i] < names[j]
+	})
+	return names
+}
+
+// Pressure reports the share of the budget in use
+func (a *Accountant) Pressure() backpressure.Pressure {
+	if a.config.Limit <= 0 {
+		return backpressure.Pressure{}
+	}
+	return backpressure.Pressure{FillRatio: float64(a.rss()) / float64(a.config.Limit)}
+}
+
+// Stats returns the memory use by consumer
+func (a *Accountant) Stats() Stats {
+	a.mutex.Lock()
+	defer a.mutex.Unlock()
+
+	stats := Stats{
+		Limit:     a.config.Limit,
+		RSS:       a.rss(),
+		Consumers: make(map[string]int64, len(a.consumers)),
+		Shrinks:   a.shrinks,
+		Flushes:   a.flushes,
+	}
+	for name, consumer := range a.consumers {
+		stats.Consumers[name] = consumer.MemoryUsage()
+	}
+	return stats
+}
+
+// residentSetSize returns the resident memory of the process from
+// /proc/self/statm, or the memory obtained by the Go runtime where that
+// is unavailable
+func residentSetSize() int64 {
+	if data, err := os.ReadFile("/proc/self/statm"); err == nil {
+		if fields := strings.Fields(string(data)); len(fields) > 1 {
+			if pages, err := strconv.ParseInt(fields[1], 10, 64); err == nil {
+				return pages * int64(os.Getpagesize())
+			}
+		}
+	}
+	var stats runtime.MemStats
+	runtime.ReadMemStats(&stats)
+	return int64(stats.Sys)
+}
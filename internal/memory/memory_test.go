@@ -0,0 +1,108 @@
+package memory
+
+import (
+	"errors"
+	"testing"
+
+	"github.com/stretchr/testify/assert"
+	"go.uber.org/zap"
+)
+
+// cache is a Shrinker whose usage feeds the fake resident set size
+type cache struct {
+	usage *int64
+	bytes int64
+}
+
+func (c *cache) MemoryUsage() int64 { return c.bytes }
+
+func (c *cache) Shrink(bytes int64) int64 {
+	if bytes > c.bytes {
+		bytes = c.bytes
+	}
+	c.bytes -= bytes
+	*c.usage -= bytes
+	return bytes
+}
+
+// buffer is a Flusher
+type buffer struct {
+	usage *int64
+	bytes int64
+	err   error
+}
+
+func (b *buffer) MemoryUsage() int64 { return b.bytes }
+
+func (b *buffer) Flush() error {
+	if b.err != nil {
+		return b.err
+	}
+	*b.usage -= b.bytes
+	b.bytes = 0
+	return nil
+}
+
+// queue only reports its usage
+type queue int64
+
+func (q queue) MemoryUsage() int64 { return int64(q) }
+
+func testAccountant(limit int64, rss *int64) *Accountant {
+	accountant := NewAccountant(Config{Limit: limit}, zap.NewNop())
+	accountant.rss = func() int64 { return *rss }
+	return accountant
+}
+
+func TestAccountant_Check(t *testing.T) {
+	rss := int64(850)
+	accountant := testAccountant(1000, &rss)
+	small, large := &cache{usage: &rss, bytes: 50}, &cache{usage: &rss, bytes: 300}
+	writes := &buffer{usage: &rss, bytes: 200}
+	accountant.Register("tags", small)
+	accountant.Register("values", large)
+	accountant.Register("writes", writes)
+	accountant.Register("events", queue(100))
+
+	// Below the high watermark nothing happens
+	assert.Zero(t, accountant.Check())
+	assert.InDelta(t, 0.85, accountant.Pressure().FillRatio, 1e-9)
+
+	// Above it caches shrink to the low watermark, largest first
+	rss = 950
+	assert.Equal(t, int64(200), accountant.Check())
+	assert.Equal(t, int64(750), rss)
+	assert.Equal(t, int64(50), small.bytes)
+	assert.Equal(t, int64(100), large.bytes)
+	assert.Equal(t, int64(200), writes.bytes)
+
+	// Buffers flush when the caches do not free enough
+	rss = 1000
+	assert.Equal(t, int64(350), accountant.Check())
+	assert.Zero(t, small.bytes+large.bytes+writes.bytes)
+
+	stats := accountant.Stats()
+	assert.Equal(t, int64(1000), stats.Limit)
+	assert.Equal(t, map[string]int64{"tags": 0, "values": 0, "writes": 0, "events": 100}, stats.Consumers)
+	assert.Equal(t, uint64(1), stats.Flushes)
+}
+
+func TestAccountant_FlushError(t *testing.T) {
+	rss := int64(1200)
+	accountant := testAccountant(1000, &rss)
+	accountant.Register("writes", &buffer{usage: &rss, bytes: 500, err: errors.New("disk full")})
+
+	assert.Zero(t, accountant.Check())
+	assert.Zero(t, accountant.Stats().Flushes)
+
+	accountant.Unregister("writes")
+	assert.Empty(t, accountant.Stats().Consumers)
+}
+
+func TestAccountant_Disabled(t *testing.T) {
+	accountant := NewAccountant(Config{}, zap.NewNop())
+	accountant.Register("events", queue(1 << 40))
+	assert.Zero(t, accountant.Check())
+	assert.Zero(t, accountant.Pressure().FillRatio)
+	assert.Greater(t, residentSetSize(), int64(0))
+}
@@ -4,12 +4,13 @@ import (
 	"context"
 	"fmt"
 	"runtime"
-	"sort"
 	"sync"
 	"sync/atomic"
 	"time"
 
 	"go.uber.org/zap"
+
+	"github.com/bifrost/go-gateway/internal/analytics"
 )
 
 // BenchmarkSuite provides comprehensive performance benchmarking
@@ -763,33 +764,28 @@ func (bs *BenchmarkSuite) analyzeLatencyDistribution(samples []time.Duration) *L
 		return &LatencyResults{}
 	}
 
-	// Sort samples for percentile calculation
-	sort.Slice(samples, func(i, j int) bool {
-		return samples[i] < samples[j]
-	})
-
-	results := &LatencyResults{
-		Samples:      samples,
-		MinLatency:   samples[0],
-		MaxLatency:   samples[len(samples)-1],
-		Distribution: make(map[string]int),
-	}
-
-	// Calculate percentiles
-	results.MedianLatency = samples[len(samples)/2]
-	results.P90Latency = samples[int(float64(len(samples))*0.90)]
-	results.P95Latency = samples[int(float64(len(samples))*0.95)]
-	results.P99Latency = samples[int(float64(len(samples))*0.99)]
-	results.P999Latency = samples[int(float64(len(samples))*0.999)]
-
-	// Calculate mean
-	var total time.Duration
+	// Estimate percentiles in one pass instead of sorting the samples
+	stats := analytics.NewStats(0.5, 0.9, 0.95, 0.99, 0.999)
 	for _, sample := range samples {
-		total += sample
+		stats.Add(float64(sample))
+	}
+	quantile := func(p float64) time.Duration {
+		value, _ := stats.Quantile(p)
+		return time.Duration(value)
 	}
-	results.MeanLatency = total / time.Duration(len(samples))
 
-	return results
+	return &LatencyResults{
+		Samples:       samples,
+		MinLatency:    time.Duration(stats.Min()),
+		MaxLatency:    time.Duration(stats.Max()),
+		MeanLatency:   time.Duration(stats.Mean()),
+		MedianLatency: quantile(0.5),
+		P90Latency:    quantile(0.9),
+		P95Latency:    quantile(0.95),
+		P99Latency:    quantile(0.99),
+		P999Latency:   quantile(0.999),
+		Distribution:  make(map[string]int),
+	}
 }
 
 func (bs *BenchmarkSuite) calculateAverageThroughput(data []DataPoint) float64 {
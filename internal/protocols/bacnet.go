@@ -0,0 +1,643 @@
+package protocols
+
+import (
+	"context"
+	"encoding/hex"
+	"errors"
+	"fmt"
+	"math"
+	"net"
+	"strconv"
+	"sync"
+	"sync/atomic"
+	"time"
+
+	"go.uber.org/zap"
+)
+
+// bacnetWildcardInstance addresses whichever device receives a request
+// to its device object
+const bacnetWildcardInstance = bacnetMaxObjectInstance
+
+// BACnetHandler implements ProtocolHandler for BACnet/IP devices. Tag
+// addresses are parsed by ParseBACnetAddress, e.g. "AI:3" for the present
+// value of analog input 3. The config key "device_instance" sets the
+// device object instance; "network" and "mac" (hex) address a device
+// behind a BACnet router.
+type BACnetHandler struct {
+	logger      *zap.Logger
+	config      *BACnetConfig
+	connections sync.Map // map[string]*BACnetConnection
+	processID   uint32   // Last COV subscriber process ID
+
+	mutex  sync.Mutex
+	client *BACnetClient
+}
+
+// BACnetConnection is the state kept for one device. BACnet/IP is
+// connectionless; all devices share the handler's socket.
+type BACnetConnection struct {
+	target    *BACnetTarget
+	device    BACnetObjectID
+	createdAt time.Time
+	done      chan struct{}
+
+	mutex         sync.RWMutex
+	lastUsed      time.Time
+	requests      uint64
+	errors        uint64
+	subscriptions map[uint32]BACnetObjectID
+}
+
+// BACnetConfig holds BACnet-specific configuration
+type BACnetConfig struct {
+	LocalAddress      string        `yaml:"local_address"`
+	DefaultTimeout    time.Duration `yaml:"default_timeout"`
+	Retries           int           `yaml:"retries"`
+	WritePriority     uint8         `yaml:"write_priority"`
+	COVLifetime       time.Duration `yaml:"cov_lifetime"`
+	DiscoveryTimeout  time.Duration `yaml:"discovery_timeout"`
+	MaxReadProperties int           `yaml:"max_read_properties"` // Per ReadPropertyMultiple
+}
+
+// NewBACnetHandler creates a new BACnet/IP protocol handler
+func NewBACnetHandler(logger *zap.Logger) ProtocolHandler {
+	return &BACnetHandler{
+		logger: logger,
+		config: &BACnetConfig{
+			LocalAddress:      fmt.Sprintf(":%d", BACnetDefaultPort),
+			DefaultTimeout:    3 * time.Second,
+			Retries:           2,
+			WritePriority:     16,
+			COVLifetime:       5 * time.Minute,
+			DiscoveryTimeout:  3 * time.Second,
+			MaxReadProperties: 32,
+		},
+	}
+}
+
+// Connect checks that a device answers and registers it
+func (b *BACnetHandler) Connect(device *Device) error {
+	client, err := b.getClient()
+	if err != nil {
+		return err
+	}
+
+	port := device.Port
+	if port == 0 {
+		port = BACnetDefaultPort
+	}
+	address, err := net.ResolveUDPAddr("udp4", net.JoinHostPort(device.Address, strconv.Itoa(port)))
+	if err != nil {
+		return fmt.Errorf("invalid BACnet device address: %w", err)
+	}
+
+	target := &BACnetTarget{Address: address}
+	if network, ok := device.Config["network"].(int); ok && network > 0 && network < math.MaxUint16 {
+		target.Network = uint16(network)
+		mac, _ := device.Config["mac"].(string)
+		if target.MAC, err = hex.DecodeString(mac); err != nil || len(target.MAC) == 0 {
+			return fmt.Errorf("routed BACnet device requires a hex \"mac\" address")
+		}
+	}
+
+	instance := uint32(bacnetWildcardInstance)
+	if n, ok := device.Config["device_instance"].(int); ok && n >= 0 && n < bacnetWildcardInstance {
+		instance = uint32(n)
+	}
+	deviceObject := BACnetObjectID{Type: BACnetDevice, Instance: instance}
+
+	connectionKey := fmt.Sprintf("%s/%d/%x/%d", address, target.Network, target.MAC, instance)
+	if _, exists := b.connections.Load(connectionKey); exists {
+		device.ConnectionID = connectionKey
+		return nil
+	}
+
+	// Reading the device object's identifier also resolves the wildcard
+	// instance to the device's own
+	value, err := client.ReadProperty(target, &BACnetAddress{Object: deviceObject, Property: BACnetPropertyObjectIdentifier})
+	if err != nil {
+		return fmt.Errorf("failed to connect to BACnet device: %w", err)
+	}
+	if id, ok := value.(BACnetObjectID); ok && id.Type == BACnetDevice {
+		deviceObject = id
+	}
+
+	b.connections.Store(connectionKey, &BACnetConnection{
+		target:        target,
+		device:        deviceObject,
+		createdAt:     time.Now(),
+		done:          make(chan struct{}),
+		lastUsed:      time.Now(),
+		subscriptions: make(map[uint32]BACnetObjectID),
+	})
+	device.ConnectionID = connectionKey
+
+	b.logger.Info("BACnet device connected",
+		zap.String("device_id", device.ID),
+		zap.String("address", address.String()),
+		zap.Uint32("device_instance", deviceObject.Instance),
+	)
+
+	return nil
+}
+
+// Disconnect cancels the device's COV subscriptions and forgets it
+func (b *BACnetHandler) Disconnect(device *Device) error {
+	if device.ConnectionID == "" {
+		return nil
+	}
+
+	connInterface, exists := b.connections.LoadAndDelete(device.ConnectionID)
+	if !exists {
+		return nil
+	}
+	device.ConnectionID = ""
+
+	conn := connInterface.(*BACnetConnection)
+	close(conn.done)
+
+	conn.mutex.Lock()
+	subscriptions := conn.subscriptions
+	conn.subscriptions = nil
+	conn.mutex.Unlock()
+
+	var err error
+	for processID, object := range subscriptions {
+		if cancelErr := b.client.UnsubscribeCOV(conn.target, object, processID); cancelErr != nil {
+			err = cancelErr
+		}
+	}
+	return err
+}
+
+// IsConnected checks if the device is registered
+func (b *BACnetHandler) IsConnected(device *Device) bool {
+	_, err := b.getConnection(device)
+	return err == nil
+}
+
+// ReadTag reads one property. For present values the object's status
+// flags are read with it and set the tag's quality.
+func (b *BACnetHandler) ReadTag(device *Device, tag *Tag) (interface{}, error) {
+	values, err := b.ReadMultipleTags(device, []*Tag{tag})
+	if err != nil {
+		return nil, err
+	}
+
+	value, exists := values[tag.ID]
+	if !exists {
+		return nil, fmt.Errorf("no value for tag %s", tag.ID)
+	}
+	return value, nil
+}
+
+// WriteTag writes a property at the configured priority. Present values
+// are converted to the object's datatype; writing nil relinquishes the
+// priority.
+func (b *BACnetHandler) WriteTag(device *Device, tag *Tag, value interface{}) error {
+	if !tag.Writable {
+		return fmt.Errorf("tag %s is not writable", tag.ID)
+	}
+
+	conn, err := b.getConnection(device)
+	if err != nil {
+		return err
+	}
+
+	address, err := ParseBACnetAddress(tag.Address)
+	if err != nil {
+		return err
+	}
+
+	if address.Property == BACnetPropertyPresentValue {
+		if value, err = bacnetPresentValue(address.Object.Type, value); err != nil {
+			return fmt.Errorf("invalid value for %s: %w", tag.Address, err)
+		}
+	}
+
+	err = b.client.WriteProperty(conn.target, address, value, b.config.WritePriority)
+	conn.record(err)
+	return err
+}
+
+// ReadMultipleTags reads tags with as few ReadPropertyMultiple requests
+// as possible, falling back to ReadProperty for devices without it. Tags
+// whose property could not be read are left out of the results.
+func (b *BACnetHandler) ReadMultipleTags(device *Device, tags []*Tag) (map[string]interface{}, error) {
+	conn, err := b.getConnection(device)
+	if err != nil {
+		return nil, err
+	}
+
+	// Each present value is read with the object's status flags
+	type request struct {
+		tag         *Tag
+		address     *BACnetAddress
+		statusFlags bool
+	}
+	var requests []request
+	var addresses []*BACnetAddress
+	for _, tag := range tags {
+		address, err := ParseBACnetAddress(tag.Address)
+		if err != nil {
+			continue
+		}
+		requests = append(requests, request{tag: tag, address: address})
+		addresses = append(addresses, address)
+		if address.Property == BACnetPropertyPresentValue {
+			requests = append(requests, request{tag: tag, statusFlags: true})
+			addresses = append(addresses, &BACnetAddress{Object: address.Object, Property: BACnetPropertyStatusFlags})
+		}
+	}
+
+	values, err := b.readProperties(conn, addresses)
+	if err != nil {
+		return nil, err
+	}
+
+	results := make(map[string]interface{})
+	for i, request := range requests {
+		value := values[i]
+		if request.statusFlags {
+			// Follows the tag's value, whose failure takes precedence
+			if flags, ok := value.Value.(BACnetBitString); ok && value.Err == nil && values[i-1].Err == nil {
+				request.tag.SetStatus(bacnetQuality(flags))
+			}
+			continue
+		}
+		if value.Err != nil {
+			request.tag.SetStatus(NewQualityStatus(QualityBad, "", QualitySourceDevice))
+			continue
+		}
+		request.tag.SetStatus(GoodQuality)
+		results[request.tag.ID] = bacnetTagValue(request.address, value.Value)
+	}
+	return results, nil
+}
+
+// readProperties reads properties in batches of MaxReadProperties
+func (b *BACnetHandler) readProperties(conn *BACnetConnection, addresses []*BACnetAddress) ([]BACnetPropertyValue, error) {
+	values := make([]BACnetPropertyValue, 0, len(addresses))
+	for start := 0; start < len(addresses); start += b.config.MaxReadProperties {
+		end := start + b.config.MaxReadProperties
+		if end > len(addresses) {
+			end = len(addresses)
+		}
+
+		batch, err := b.client.ReadPropertyMultiple(conn.target, addresses[start:end])
+		var bacnetErr *BACnetError
+		if errors.As(err, &bacnetErr) && bacnetErr.Kind != "error" {
+			// Rejected or aborted: the device does not support the service
+			batch, err = b.readEach(conn, addresses[start:end])
+		}
+		conn.record(err)
+		if err != nil {
+			return nil, err
+		}
+		values = append(values, batch...)
+	}
+	return values, nil
+}
+
+// readEach reads properties one ReadProperty request at a time
+func (b *BACnetHandler) readEach(conn *BACnetConnection, addresses []*BACnetAddress) ([]BACnetPropertyValue, error) {
+	values := make([]BACnetPropertyValue, len(addresses))
+	for i, address := range addresses {
+		values[i] = BACnetPropertyValue{Object: address.Object, Property: address.Property, ArrayIndex: address.ArrayIndex}
+		value, err := b.client.ReadProperty(conn.target, address)
+
+		var bacnetErr *BACnetError
+		switch {
+		case errors.As(err, &bacnetErr):
+			values[i].Err = err
+		case err != nil:
+			return nil, err
+		default:
+			values[i].Value = value
+		}
+	}
+	return values, nil
+}
+
+// SubscribeCOV subscribes to changes of a tag's object and passes each
+// change to callback as a copy of the tag holding the new value, quality
+// and timestamp. The subscription is renewed until the device is
+// disconnected.
+func (b *BACnetHandler) SubscribeCOV(device *Device, tag *Tag, callback func(*Tag)) error {
+	conn, err := b.getConnection(device)
+	if err != nil {
+		return err
+	}
+
+	address, err := ParseBACnetAddress(tag.Address)
+	if err != nil {
+		return err
+	}
+
+	processID := atomic.AddUint32(&b.processID, 1)
+	handler := func(notification *BACnetCOVNotification) {
+		update := *tag
+		update.SetStatus(GoodQuality)
+		update.Timestamp = time.Now()
+		found := false
+		for _, value := range notification.Values {
+			switch value.Property {
+			case address.Property:
+				update.Value = bacnetTagValue(address, value.Value)
+				found = true
+			case BACnetPropertyStatusFlags:
+				if flags, ok := value.Value.(BACnetBitString); ok {
+					update.SetStatus(bacnetQuality(flags))
+				}
+			}
+		}
+		if found {
+			callback(&update)
+		}
+	}
+
+	subscribe := func() error {
+		err := b.client.SubscribeCOV(conn.target, address.Object, processID, false, b.config.COVLifetime, handler)
+		conn.record(err)
+		return err
+	}
+	if err := subscribe(); err != nil {
+		return err
+	}
+
+	conn.mutex.Lock()
+	if conn.subscriptions == nil {
+		conn.mutex.Unlock()
+		return fmt.Errorf("device disconnected")
+	}
+	conn.subscriptions[processID] = address.Object
+	conn.mutex.Unlock()
+
+	go func() {
+		ticker := time.NewTicker(b.config.COVLifetime / 2)
+		defer ticker.Stop()
+		for {
+			select {
+			case <-conn.done:
+				return
+			case <-ticker.C:
+				if err := subscribe(); err != nil {
+					b.logger.Warn("BACnet COV subscription renewal failed",
+						zap.String("device_id", device.ID),
+						zap.String("address", tag.Address),
+						zap.Error(err),
+					)
+				}
+			}
+		}
+	}()
+	return nil
+}
+
+// DiscoverDevices broadcasts Who-Is to a network, given as a CIDR range
+// or broadcast address, and returns the devices that answer
+func (b *BACnetHandler) DiscoverDevices(ctx context.Context, networkRange string) ([]*Device, error) {
+	client, err := b.getClient()
+	if err != nil {
+		return nil, err
+	}
+
+	broadcast := net.IPv4bcast
+	if networkRange != "" {
+		if ip, network, err := net.ParseCIDR(networkRange); err == nil && ip.To4() != nil {
+			broadcast = make(net.IP, net.IPv4len)
+			for i, octet := range network.IP.To4() {
+				broadcast[i] = octet | ^network.Mask[len(network.Mask)-net.IPv4len+i]
+			}
+		} else if ip := net.ParseIP(networkRange); ip != nil && ip.To4() != nil {
+			broadcast = ip
+		} else {
+			return nil, fmt.Errorf("invalid network range %q", networkRange)
+		}
+	}
+
+	if _, hasDeadline := ctx.Deadline(); !hasDeadline {
+		var cancel context.CancelFunc
+		ctx, cancel = context.WithTimeout(ctx, b.config.DiscoveryTimeout)
+		defer cancel()
+	}
+
+	replies, err := client.WhoIs(ctx, &net.UDPAddr{IP: broadcast, Port: BACnetDefaultPort})
+	devices := make([]*Device, 0, len(replies))
+	for _, reply := range replies {
+		if reply.Address == nil {
+			continue
+		}
+		devices = append(devices, &Device{
+			ID:       fmt.Sprintf("bacnet-%d", reply.Device.Instance),
+			Name:     fmt.Sprintf("BACnet Device %d", reply.Device.Instance),
+			Protocol: "bacnet-ip",
+			Address:  reply.Address.IP.String(),
+			Port:     reply.Address.Port,
+			Config: map[string]interface{}{
+				"device_instance": int(reply.Device.Instance),
+				"vendor_id":       int(reply.VendorID),
+				"max_apdu":        int(reply.MaxAPDU),
+			},
+			LastSeen: time.Now(),
+		})
+	}
+	return devices, err
+}
+
+// GetDeviceInfo returns information about a BACnet device, read from its
+// device object when connected
+func (b *BACnetHandler) GetDeviceInfo(device *Device) (*DeviceInfo, error) {
+	info := &DeviceInfo{
+		Vendor:         "Unknown",
+		Model:          "BACnet Device",
+		Capabilities:   []string{"bacnet-ip", "read-property-multiple", "cov"},
+		MaxConnections: 0, // Connectionless
+		CustomInfo:     make(map[string]string),
+	}
+
+	conn, err := b.getConnection(device)
+	if err != nil {
+		return info, nil
+	}
+	info.SerialNumber = strconv.FormatUint(uint64(conn.device.Instance), 10)
+	info.CustomInfo["device_instance"] = info.SerialNumber
+
+	properties := []BACnetPropertyID{BACnetPropertyVendorName, BACnetPropertyModelName, BACnetPropertyFirmwareRevision}
+	addresses := make([]*BACnetAddress, len(properties))
+	for i, property := range properties {
+		addresses[i] = &BACnetAddress{Object: conn.device, Property: property}
+	}
+
+	values, err := b.readProperties(conn, addresses)
+	if err != nil {
+		b.logger.Debug("BACnet device properties not available",
+			zap.String("device_id", device.ID),
+			zap.Error(err),
+		)
+		return info, nil
+	}
+	for i, field := range []*string{&info.Vendor, &info.Model, &info.FirmwareVersion} {
+		if s, ok := values[i].Value.(string); ok && values[i].Err == nil {
+			*field = s
+		}
+	}
+	return info, nil
+}
+
+// GetSupportedDataTypes returns the data types of BACnet property values
+func (b *BACnetHandler) GetSupportedDataTypes() []string {
+	return []string{
+		string(DataTypeBool),
+		string(DataTypeInt32),
+		string(DataTypeUInt32),
+		string(DataTypeFloat32),
+		string(DataTypeFloat64),
+		string(DataTypeString),
+		string(DataTypeBytes),
+	}
+}
+
+// ValidateTagAddress validates a BACnet object and property address
+func (b *BACnetHandler) ValidateTagAddress(address string) error {
+	_, err := ParseBACnetAddress(address)
+	return err
+}
+
+// Ping reads the device's object name
+func (b *BACnetHandler) Ping(device *Device) error {
+	conn, err := b.getConnection(device)
+	if err != nil {
+		return err
+	}
+
+	_, err = b.client.ReadProperty(conn.target, &BACnetAddress{Object: conn.device, Property: BACnetPropertyObjectName})
+	conn.record(err)
+	return err
+}
+
+// GetDiagnostics returns diagnostic information for a BACnet device
+func (b *BACnetHandler) GetDiagnostics(device *Device) (*Diagnostics, error) {
+	conn, err := b.getConnection(device)
+	if err != nil {
+		return nil, err
+	}
+
+	conn.mutex.RLock()
+	defer conn.mutex.RUnlock()
+
+	diagnostics := &Diagnostics{
+		IsHealthy:         conn.requests == 0 || conn.errors < conn.requests,
+		LastCommunication: conn.lastUsed,
+		ErrorCount:        conn.errors,
+		ConnectionUptime:  time.Since(conn.createdAt),
+		ProtocolDiagnostics: map[string]interface{}{
+			"device_instance":   conn.device.Instance,
+			"cov_subscriptions": len(conn.subscriptions),
+		},
+	}
+	if conn.requests > 0 {
+		diagnostics.SuccessRate = float64(conn.requests-conn.errors) / float64(conn.requests)
+	}
+	return diagnostics, nil
+}
+
+// getClient opens the shared socket on first use
+func (b *BACnetHandler) getClient() (*BACnetClient, error) {
+	b.mutex.Lock()
+	defer b.mutex.Unlock()
+
+	if b.client == nil {
+		client, err := ListenBACnet(b.config.LocalAddress, b.logger)
+		if err != nil {
+			return nil, err
+		}
+		client.Timeout = b.config.DefaultTimeout
+		client.Retries = b.config.Retries
+		b.client = client
+	}
+	return b.client, nil
+}
+
+func (b *BACnetHandler) getConnection(device *Device) (*BACnetConnection, error) {
+	if device.ConnectionID == "" {
+		return nil, fmt.Errorf("device not connected")
+	}
+
+	connInterface, exists := b.connections.Load(device.ConnectionID)
+	if !exists {
+		return nil, fmt.Errorf("connection not found")
+	}
+	return connInterface.(*BACnetConnection), nil
+}
+
+func (c *BACnetConnection) record(err error) {
+	c.mutex.Lock()
+	defer c.mutex.Unlock()
+
+	c.requests++
+	c.lastUsed = time.Now()
+	if err != nil {
+		c.errors++
+	}
+}
+
+// bacnetQuality maps status flags (in-alarm, fault, overridden,
+// out-of-service) to a tag quality status
+func bacnetQuality(flags BACnetBitString) QualityStatus {
+	switch {
+	case len(flags) > 1 && flags[1]:
+		return NewQualityStatus(QualityBad, QualitySubstatusDeviceFailure, QualitySourceDevice)
+	case len(flags) > 3 && flags[3]:
+		return NewQualityStatus(QualityUncertain, QualitySubstatusOutOfService, QualitySourceDevice)
+	case len(flags) > 2 && flags[2]:
+		return NewQualityStatus(QualityUncertain, QualitySubstatusOverridden, QualitySourceDevice)
+	}
+	return GoodQuality
+}
+
+// bacnetTagValue converts a property value to the value a tag carries:
+// binary present values become bools and other enumerations uint32
+func bacnetTagValue(address *BACnetAddress, value interface{}) interface{} {
+	enumerated, ok := value.(BACnetEnumerated)
+	if !ok {
+		return value
+	}
+	if address.Property == BACnetPropertyPresentValue && bacnetIsBinary(address.Object.Type) {
+		return enumerated != 0
+	}
+	return uint32(enumerated)
+}
+
+// bacnetPresentValue converts a value written to a present value to the
+// object type's datatype: Real for analog, Enumerated for binary and
+// Unsigned for multi-state objects. nil (relinquish) is passed through.
+func bacnetPresentValue(objectType BACnetObjectType, value interface{}) (interface{}, error) {
+	if value == nil {
+		return nil, nil
+	}
+
+	switch {
+	case objectType == BACnetAnalogInput || objectType == BACnetAnalogOutput || objectType == BACnetAnalogValue:
+		v, err := floatValue(value)
+		return float32(v), err
+	case bacnetIsBinary(objectType):
+		if on, ok := value.(bool); ok {
+			if on {
+				return BACnetEnumerated(1), nil
+			}
+			return BACnetEnumerated(0), nil
+		}
+		v, err := integerValue(value, 0, 1)
+		return BACnetEnumerated(v), err
+	case objectType == BACnetMultiStateInput || objectType == BACnetMultiStateOutput || objectType == BACnetMultiStateValue:
+		v, err := integerValue(value, 1, math.MaxUint32)
+		return uint32(v), err
+	}
+	return value, nil
+}
+
+func bacnetIsBinary(objectType BACnetObjectType) bool {
+	return objectType == BACnetBinaryInput || objectType == BACnetBinaryOutput || objectType == BACnetBinaryValue
+}
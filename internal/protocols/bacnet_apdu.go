@@ -0,0 +1,514 @@
+package protocols
+
+import (
+	"encoding/binary"
+	"fmt"
+	"net"
+)
+
+// BACnet/IP Framing and Services
+//
+// A BACnet/IP datagram is a BVLC header (type, function, length), an NPDU
+// (version, control and optional routing fields) and an APDU. Only
+// unsegmented messages are produced or accepted; requests advertise a
+// 1476 byte maximum APDU so compliant devices reply unsegmented or reject.
+
+const (
+	bacnetBVLCType              = 0x81
+	bacnetBVLCForwardedNPDU     = 0x04
+	bacnetBVLCOriginalUnicast   = 0x0A
+	bacnetBVLCOriginalBroadcast = 0x0B
+	bacnetBVLCHeaderSize        = 4
+
+	bacnetNPDUVersion        = 0x01
+	bacnetNPDUNetworkMessage = 0x80
+	bacnetNPDUDestination    = 0x20
+	bacnetNPDUSource         = 0x08
+	bacnetNPDUExpectReply    = 0x04
+
+	// BACnetDefaultPort is the standard BACnet/IP UDP port (0xBAC0)
+	BACnetDefaultPort = 47808
+
+	// bacnetMaxAPDUAccepted is the max-APDU field for 1476 octets
+	bacnetMaxAPDUAccepted = 0x05
+)
+
+// APDU types
+const (
+	bacnetPDUConfirmedRequest   = 0x0
+	bacnetPDUUnconfirmedRequest = 0x1
+	bacnetPDUSimpleAck          = 0x2
+	bacnetPDUComplexAck         = 0x3
+	bacnetPDUError              = 0x5
+	bacnetPDUReject             = 0x6
+	bacnetPDUAbort              = 0x7
+
+	bacnetPDUSegmented = 0x08 // Segmented message flag in the first octet
+)
+
+// Service choices
+const (
+	bacnetServiceConfirmedCOVNotification = 1
+	bacnetServiceSubscribeCOV             = 5
+	bacnetServiceReadProperty             = 12
+	bacnetServiceReadPropertyMultiple     = 14
+	bacnetServiceWriteProperty            = 15
+
+	bacnetServiceIAm                        = 0
+	bacnetServiceUnconfirmedCOVNotification = 2
+	bacnetServiceWhoIs                      = 8
+)
+
+// BACnetError is an Error, Reject or Abort returned by a device
+type BACnetError struct {
+	Kind   string // "error", "reject" or "abort"
+	Class  uint32 // Error class; errors only
+	Code   uint32 // Error code, or the reject or abort reason
+	Server bool   // Abort sent by the server
+}
+
+func (e *BACnetError) Error() string {
+	if e.Kind == "error" {
+		return fmt.Sprintf("BACnet error class %d code %d", e.Class, e.Code)
+	}
+	return fmt.Sprintf("BACnet %s, reason %d", e.Kind, e.Code)
+}
+
+// BACnetPropertyValue is one property value read from or reported by a
+// device. Err is set instead of Value when the device could not return it.
+type BACnetPropertyValue struct {
+	Object     BACnetObjectID
+	Property   BACnetPropertyID
+	ArrayIndex *uint32
+	Value      interface{}
+	Err        error
+}
+
+// BACnetCOVNotification is a change of value notification
+type BACnetCOVNotification struct {
+	ProcessID     uint32
+	Device        BACnetObjectID
+	Object        BACnetObjectID
+	TimeRemaining uint32 // Seconds left in the subscription
+	Values        []BACnetPropertyValue
+}
+
+// BACnetIAm is a device's answer to Who-Is
+type BACnetIAm struct {
+	Device       BACnetObjectID
+	MaxAPDU      uint32
+	Segmentation BACnetEnumerated
+	VendorID     uint32
+	Address      *net.UDPAddr
+}
+
+// bacnetMessage is a decoded datagram
+type bacnetMessage struct {
+	Source         *net.UDPAddr // The originating device, for forwarded NPDUs
+	NetworkMessage bool
+	APDU           []byte
+}
+
+// bacnetAPDU is a decoded APDU header
+type bacnetAPDU struct {
+	Type     byte
+	InvokeID byte
+	Service  byte
+	Data     []byte
+}
+
+// appendBACnetFrame encodes a datagram carrying an APDU. A non-zero
+// network routes the request through BACnet routers to the device with
+// the given MAC address on that network.
+func appendBACnetFrame(dst []byte, broadcast, expectReply bool, network uint16, mac []byte, apdu []byte) []byte {
+	start := len(dst)
+	function := byte(bacnetBVLCOriginalUnicast)
+	if broadcast {
+		function = bacnetBVLCOriginalBroadcast
+	}
+	dst = append(dst, bacnetBVLCType, function, 0, 0)
+
+	control := byte(0)
+	if expectReply {
+		control |= bacnetNPDUExpectReply
+	}
+	if network != 0 {
+		control |= bacnetNPDUDestination
+	}
+	dst = append(dst, bacnetNPDUVersion, control)
+	if network != 0 {
+		dst = binary.BigEndian.AppendUint16(dst, network)
+		dst = append(dst, byte(len(mac)))
+		dst = append(dst, mac...)
+		dst = append(dst, 255) // Hop count
+	}
+
+	dst = append(dst, apdu...)
+	binary.BigEndian.PutUint16(dst[start+2:], uint16(len(dst)-start))
+	return dst
+}
+
+// parseBACnetFrame decodes the BVLC and NPDU headers of a datagram
+func parseBACnetFrame(data []byte) (*bacnetMessage, error) {
+	if len(data) < bacnetBVLCHeaderSize || data[0] != bacnetBVLCType {
+		return nil, fmt.Errorf("not a BACnet/IP datagram")
+	}
+	if length := int(binary.BigEndian.Uint16(data[2:4])); length != len(data) {
+		return nil, fmt.Errorf("BVLC length %d does not match datagram of %d bytes", length, len(data))
+	}
+
+	message := &bacnetMessage{}
+	npdu := data[bacnetBVLCHeaderSize:]
+	switch data[1] {
+	case bacnetBVLCOriginalUnicast, bacnetBVLCOriginalBroadcast:
+	case bacnetBVLCForwardedNPDU:
+		if len(npdu) < 6 {
+			return nil, fmt.Errorf("truncated forwarded NPDU")
+		}
+		message.Source = &net.UDPAddr{IP: net.IP(append([]byte(nil), npdu[0:4]...)), Port: int(binary.BigEndian.Uint16(npdu[4:6]))}
+		npdu = npdu[6:]
+	default:
+		return nil, fmt.Errorf("unsupported BVLC function 0x%02X", data[1])
+	}
+
+	if len(npdu) < 2 || npdu[0] != bacnetNPDUVersion {
+		return nil, fmt.Errorf("invalid NPDU")
+	}
+	control := npdu[1]
+	rest := npdu[2:]
+
+	skipAddress := func() error {
+		if len(rest) < 3 || len(rest) < 3+int(rest[2]) {
+			return fmt.Errorf("truncated NPDU address")
+		}
+		rest = rest[3+int(rest[2]):]
+		return nil
+	}
+	if control&bacnetNPDUDestination != 0 {
+		if err := skipAddress(); err != nil {
+			return nil, err
+		}
+	}
+	if control&bacnetNPDUSource != 0 {
+		if err := skipAddress(); err != nil {
+			return nil, err
+		}
+	}
+	if control&bacnetNPDUDestination != 0 {
+		if len(rest) < 1 {
+			return nil, fmt.Errorf("truncated NPDU hop count")
+		}
+		rest = rest[1:]
+	}
+
+	message.NetworkMessage = control&bacnetNPDUNetworkMessage != 0
+	message.APDU = rest
+	return message, nil
+}
+
+// appendBACnetConfirmedRequest encodes an unsegmented confirmed request
+func appendBACnetConfirmedRequest(dst []byte, invokeID, service byte, data []byte) []byte {
+	dst = append(dst, bacnetPDUConfirmedRequest<<4, bacnetMaxAPDUAccepted, invokeID, service)
+	return append(dst, data...)
+}
+
+// appendBACnetUnconfirmedRequest encodes an unconfirmed request
+func appendBACnetUnconfirmedRequest(dst []byte, service byte, data []byte) []byte {
+	dst = append(dst, bacnetPDUUnconfirmedRequest<<4, service)
+	return append(dst, data...)
+}
+
+// parseBACnetAPDU decodes an APDU header. Segmented messages are rejected.
+func parseBACnetAPDU(data []byte) (*bacnetAPDU, error) {
+	if len(data) < 2 {
+		return nil, fmt.Errorf("truncated APDU")
+	}
+
+	apdu := &bacnetAPDU{Type: data[0] >> 4}
+	switch apdu.Type {
+	case bacnetPDUConfirmedRequest:
+		if data[0]&bacnetPDUSegmented != 0 {
+			return nil, fmt.Errorf("segmented requests are not supported")
+		}
+		if len(data) < 4 {
+			return nil, fmt.Errorf("truncated confirmed request")
+		}
+		apdu.InvokeID, apdu.Service, apdu.Data = data[2], data[3], data[4:]
+
+	case bacnetPDUUnconfirmedRequest:
+		apdu.Service, apdu.Data = data[1], data[2:]
+
+	case bacnetPDUComplexAck:
+		if data[0]&bacnetPDUSegmented != 0 {
+			return nil, fmt.Errorf("segmented responses are not supported")
+		}
+		if len(data) < 3 {
+			return nil, fmt.Errorf("truncated complex ACK")
+		}
+		apdu.InvokeID, apdu.Service, apdu.Data = data[1], data[2], data[3:]
+
+	case bacnetPDUSimpleAck, bacnetPDUError, bacnetPDUReject, bacnetPDUAbort:
+		if len(data) < 3 {
+			return nil, fmt.Errorf("truncated APDU")
+		}
+		// Reject and abort carry the reason where the others have the service
+		apdu.InvokeID, apdu.Service, apdu.Data = data[1], data[2], data[3:]
+
+	default:
+		return nil, fmt.Errorf("unsupported APDU type %d", apdu.Type)
+	}
+	return apdu, nil
+}
+
+// err returns the error an Error, Reject or Abort APDU carries
+func (a *bacnetAPDU) err(abortedByServer bool) error {
+	switch a.Type {
+	case bacnetPDUError:
+		// Error class and code, both enumerated; malformed fields read as 0
+		e := &BACnetError{Kind: "error"}
+		data := a.Data
+		for _, field := range []*uint32{&e.Class, &e.Code} {
+			value, n, err := DecodeBACnetValue(data)
+			if err != nil {
+				break
+			}
+			if v, ok := value.(BACnetEnumerated); ok {
+				*field = uint32(v)
+			}
+			data = data[n:]
+		}
+		return e
+	case bacnetPDUReject:
+		return &BACnetError{Kind: "reject", Code: uint32(a.Service)}
+	case bacnetPDUAbort:
+		return &BACnetError{Kind: "abort", Code: uint32(a.Service), Server: abortedByServer}
+	}
+	return nil
+}
+
+// encodeBACnetReadProperty encodes ReadProperty request data
+func encodeBACnetReadProperty(address *BACnetAddress) []byte {
+	data := appendBACnetObjectID(nil, 0, true, address.Object)
+	data = appendBACnetUnsigned(data, 1, true, uint32(address.Property))
+	if address.ArrayIndex != nil {
+		data = appendBACnetUnsigned(data, 2, true, *address.ArrayIndex)
+	}
+	return data
+}
+
+// decodeBACnetReadPropertyAck decodes a ReadProperty ComplexACK
+func decodeBACnetReadPropertyAck(data []byte) (*BACnetPropertyValue, error) {
+	r := &bacnetReader{data: data}
+	value := &BACnetPropertyValue{}
+
+	var err error
+	if value.Object, err = r.contextObjectID(0); err != nil {
+		return nil, err
+	}
+	property, err := r.contextUnsigned(1)
+	if err != nil {
+		return nil, err
+	}
+	value.Property = BACnetPropertyID(property)
+	if r.isContext(2) {
+		index, err := r.contextUnsigned(2)
+		if err != nil {
+			return nil, err
+		}
+		value.ArrayIndex = &index
+	}
+
+	if err := r.bracket(3, true); err != nil {
+		return nil, err
+	}
+	if value.Value, err = r.values(3); err != nil {
+		return nil, err
+	}
+	return value, nil
+}
+
+// encodeBACnetReadPropertyMultiple encodes ReadPropertyMultiple request
+// data, grouping consecutive properties of the same object
+func encodeBACnetReadPropertyMultiple(addresses []*BACnetAddress) []byte {
+	var data []byte
+	for i, address := range addresses {
+		if i == 0 || addresses[i-1].Object != address.Object {
+			if i > 0 {
+				data = appendBACnetClosingTag(data, 1)
+			}
+			data = appendBACnetObjectID(data, 0, true, address.Object)
+			data = appendBACnetOpeningTag(data, 1)
+		}
+		data = appendBACnetUnsigned(data, 0, true, uint32(address.Property))
+		if address.ArrayIndex != nil {
+			data = appendBACnetUnsigned(data, 1, true, *address.ArrayIndex)
+		}
+	}
+	if len(addresses) > 0 {
+		data = appendBACnetClosingTag(data, 1)
+	}
+	return data
+}
+
+// decodeBACnetReadPropertyMultipleAck decodes a ReadPropertyMultiple
+// ComplexACK
+func decodeBACnetReadPropertyMultipleAck(data []byte) ([]BACnetPropertyValue, error) {
+	r := &bacnetReader{data: data}
+	var values []BACnetPropertyValue
+
+	for len(r.data) > 0 {
+		object, err := r.contextObjectID(0)
+		if err != nil {
+			return nil, err
+		}
+		if err := r.bracket(1, true); err != nil {
+			return nil, err
+		}
+
+		for !r.isClosing(1) {
+			value := BACnetPropertyValue{Object: object}
+			property, err := r.contextUnsigned(2)
+			if err != nil {
+				return nil, err
+			}
+			value.Property = BACnetPropertyID(property)
+			if r.isContext(3) {
+				index, err := r.contextUnsigned(3)
+				if err != nil {
+					return nil, err
+				}
+				value.ArrayIndex = &index
+			}
+
+			switch {
+			case r.isOpening(4):
+				_ = r.bracket(4, true)
+				if value.Value, err = r.values(4); err != nil {
+					return nil, err
+				}
+			case r.isOpening(5):
+				_ = r.bracket(5, true)
+				errorAPDU := &bacnetAPDU{Type: bacnetPDUError, Data: r.data}
+				value.Err = errorAPDU.err(false)
+				if _, err := r.values(5); err != nil {
+					return nil, err
+				}
+			default:
+				return nil, fmt.Errorf("property %d has neither a value nor an error", property)
+			}
+			values = append(values, value)
+		}
+		if err := r.bracket(1, false); err != nil {
+			return nil, err
+		}
+	}
+	return values, nil
+}
+
+// encodeBACnetWriteProperty encodes WriteProperty request data. Priority
+// 0 leaves it out.
+func encodeBACnetWriteProperty(address *BACnetAddress, value interface{}, priority uint8) ([]byte, error) {
+	data := encodeBACnetReadProperty(address)
+	data = appendBACnetOpeningTag(data, 3)
+	data, err := AppendBACnetValue(data, value)
+	if err != nil {
+		return nil, err
+	}
+	data = appendBACnetClosingTag(data, 3)
+	if priority != 0 {
+		data = appendBACnetUnsigned(data, 4, true, uint32(priority))
+	}
+	return data, nil
+}
+
+// encodeBACnetSubscribeCOV encodes SubscribeCOV request data. A zero
+// lifetime with cancel set removes the subscription.
+func encodeBACnetSubscribeCOV(processID uint32, object BACnetObjectID, confirmed bool, lifetime uint32, cancel bool) []byte {
+	data := appendBACnetUnsigned(nil, 0, true, processID)
+	data = appendBACnetObjectID(data, 1, true, object)
+	if cancel {
+		return data
+	}
+
+	confirmedValue := byte(0)
+	if confirmed {
+		confirmedValue = 1
+	}
+	data = appendBACnetTag(data, 2, true, 1)
+	data = append(data, confirmedValue)
+	return appendBACnetUnsigned(data, 3, true, lifetime)
+}
+
+// decodeBACnetCOVNotification decodes confirmed and unconfirmed COV
+// notification data
+func decodeBACnetCOVNotification(data []byte) (*BACnetCOVNotification, error) {
+	r := &bacnetReader{data: data}
+	notification := &BACnetCOVNotification{}
+
+	var err error
+	if notification.ProcessID, err = r.contextUnsigned(0); err != nil {
+		return nil, err
+	}
+	if notification.Device, err = r.contextObjectID(1); err != nil {
+		return nil, err
+	}
+	if notification.Object, err = r.contextObjectID(2); err != nil {
+		return nil, err
+	}
+	if notification.TimeRemaining, err = r.contextUnsigned(3); err != nil {
+		return nil, err
+	}
+	if err := r.bracket(4, true); err != nil {
+		return nil, err
+	}
+
+	for !r.isClosing(4) {
+		value := BACnetPropertyValue{Object: notification.Object}
+		property, err := r.contextUnsigned(0)
+		if err != nil {
+			return nil, err
+		}
+		value.Property = BACnetPropertyID(property)
+		if r.isContext(1) {
+			index, err := r.contextUnsigned(1)
+			if err != nil {
+				return nil, err
+			}
+			value.ArrayIndex = &index
+		}
+		if err := r.bracket(2, true); err != nil {
+			return nil, err
+		}
+		if value.Value, err = r.values(2); err != nil {
+			return nil, err
+		}
+		if r.isContext(3) {
+			if _, err := r.context(3); err != nil { // Priority
+				return nil, err
+			}
+		}
+		notification.Values = append(notification.Values, value)
+	}
+	return notification, r.bracket(4, false)
+}
+
+// decodeBACnetIAm decodes I-Am request data
+func decodeBACnetIAm(data []byte) (*BACnetIAm, error) {
+	var fields [4]interface{}
+	for i := range fields {
+		value, n, err := DecodeBACnetValue(data)
+		if err != nil {
+			return nil, fmt.Errorf("I-Am field %d: %w", i, err)
+		}
+		fields[i], data = value, data[n:]
+	}
+
+	device, ok1 := fields[0].(BACnetObjectID)
+	maxAPDU, ok2 := fields[1].(uint32)
+	segmentation, ok3 := fields[2].(BACnetEnumerated)
+	vendorID, ok4 := fields[3].(uint32)
+	if !ok1 || !ok2 || !ok3 || !ok4 {
+		return nil, fmt.Errorf("malformed I-Am")
+	}
+	return &BACnetIAm{Device: device, MaxAPDU: maxAPDU, Segmentation: segmentation, VendorID: vendorID}, nil
+}
@@ -0,0 +1,362 @@
+package protocols
+
+import (
+	"context"
+	"errors"
+	"fmt"
+	"net"
+	"sync"
+	"time"
+
+	"go.uber.org/zap"
+)
+
+// BACnet/IP Client
+//
+// BACnetClient shares one UDP socket between all devices. Confirmed
+// requests are matched to their acknowledgements by invoke ID and resent
+// on timeout; COV notifications and I-Am replies are dispatched from the
+// receive loop as they arrive.
+
+// BACnetCOVFunc receives COV notifications for a subscription
+type BACnetCOVFunc func(notification *BACnetCOVNotification)
+
+// BACnetClient is a BACnet/IP client
+type BACnetClient struct {
+	Timeout time.Duration
+	Retries int
+
+	conn   net.PacketConn
+	logger *zap.Logger
+
+	mutex         sync.Mutex
+	nextInvokeID  byte
+	pending       map[byte]*bacnetPending
+	subscriptions map[uint32]BACnetCOVFunc // By process ID
+	iAmWaiters    map[chan *BACnetIAm]struct{}
+	closed        chan struct{}
+}
+
+// bacnetPending is an outstanding confirmed request
+type bacnetPending struct {
+	address  string
+	response chan *bacnetAPDU
+}
+
+// BACnetTarget addresses a device directly or, with a non-zero network,
+// behind a BACnet router
+type BACnetTarget struct {
+	Address *net.UDPAddr
+	Network uint16
+	MAC     []byte
+}
+
+// ListenBACnet opens a UDP socket on address (e.g. ":47808") and starts a
+// client on it
+func ListenBACnet(address string, logger *zap.Logger) (*BACnetClient, error) {
+	conn, err := net.ListenPacket("udp4", address)
+	if err != nil {
+		return nil, fmt.Errorf("failed to open BACnet/IP socket: %w", err)
+	}
+	return NewBACnetClient(conn, logger), nil
+}
+
+// NewBACnetClient starts a client on an open socket
+func NewBACnetClient(conn net.PacketConn, logger *zap.Logger) *BACnetClient {
+	c := &BACnetClient{
+		Timeout:       3 * time.Second,
+		Retries:       2,
+		conn:          conn,
+		logger:        logger,
+		pending:       make(map[byte]*bacnetPending),
+		subscriptions: make(map[uint32]BACnetCOVFunc),
+		iAmWaiters:    make(map[chan *BACnetIAm]struct{}),
+		closed:        make(chan struct{}),
+	}
+	go c.receive()
+	return c
+}
+
+// Close closes the socket; outstanding requests fail
+func (c *BACnetClient) Close() error {
+	return c.conn.Close()
+}
+
+// LocalAddr returns the address of the client's socket
+func (c *BACnetClient) LocalAddr() net.Addr {
+	return c.conn.LocalAddr()
+}
+
+// ReadProperty reads one property
+func (c *BACnetClient) ReadProperty(target *BACnetTarget, address *BACnetAddress) (interface{}, error) {
+	ack, err := c.request(target, bacnetServiceReadProperty, encodeBACnetReadProperty(address))
+	if err != nil {
+		return nil, err
+	}
+
+	value, err := decodeBACnetReadPropertyAck(ack.Data)
+	if err != nil {
+		return nil, fmt.Errorf("invalid ReadProperty response: %w", err)
+	}
+	return value.Value, nil
+}
+
+// ReadPropertyMultiple reads several properties in one request. Values
+// are returned in request order; properties the device could not read
+// carry an error instead of a value.
+func (c *BACnetClient) ReadPropertyMultiple(target *BACnetTarget, addresses []*BACnetAddress) ([]BACnetPropertyValue, error) {
+	ack, err := c.request(target, bacnetServiceReadPropertyMultiple, encodeBACnetReadPropertyMultiple(addresses))
+	if err != nil {
+		return nil, err
+	}
+
+	values, err := decodeBACnetReadPropertyMultipleAck(ack.Data)
+	if err != nil {
+		return nil, fmt.Errorf("invalid ReadPropertyMultiple response: %w", err)
+	}
+	if len(values) != len(addresses) {
+		return nil, fmt.Errorf("ReadPropertyMultiple returned %d values for %d properties", len(values), len(addresses))
+	}
+	return values, nil
+}
+
+// WriteProperty writes one property at a priority from 1 to 16; 0 writes
+// without a priority
+func (c *BACnetClient) WriteProperty(target *BACnetTarget, address *BACnetAddress, value interface{}, priority uint8) error {
+	if priority > 16 {
+		return fmt.Errorf("invalid BACnet write priority %d", priority)
+	}
+
+	data, err := encodeBACnetWriteProperty(address, value, priority)
+	if err != nil {
+		return err
+	}
+	_, err = c.request(target, bacnetServiceWriteProperty, data)
+	return err
+}
+
+// SubscribeCOV subscribes to change of value notifications for an object.
+// Subscriptions expire after lifetime and must be renewed by subscribing
+// again with the same process ID.
+func (c *BACnetClient) SubscribeCOV(target *BACnetTarget, object BACnetObjectID, processID uint32, confirmed bool, lifetime time.Duration, handler BACnetCOVFunc) error {
+	c.mutex.Lock()
+	c.subscriptions[processID] = handler
+	c.mutex.Unlock()
+
+	data := encodeBACnetSubscribeCOV(processID, object, confirmed, uint32(lifetime/time.Second), false)
+	if _, err := c.request(target, bacnetServiceSubscribeCOV, data); err != nil {
+		c.mutex.Lock()
+		delete(c.subscriptions, processID)
+		c.mutex.Unlock()
+		return err
+	}
+	return nil
+}
+
+// UnsubscribeCOV cancels a subscription
+func (c *BACnetClient) UnsubscribeCOV(target *BACnetTarget, object BACnetObjectID, processID uint32) error {
+	c.mutex.Lock()
+	delete(c.subscriptions, processID)
+	c.mutex.Unlock()
+
+	_, err := c.request(target, bacnetServiceSubscribeCOV, encodeBACnetSubscribeCOV(processID, object, false, 0, true))
+	return err
+}
+
+// WhoIs sends a Who-Is to address, usually a broadcast address, and
+// collects I-Am replies until ctx is done
+func (c *BACnetClient) WhoIs(ctx context.Context, address *net.UDPAddr) ([]*BACnetIAm, error) {
+	replies := make(chan *BACnetIAm, 64)
+	c.mutex.Lock()
+	c.iAmWaiters[replies] = struct{}{}
+	c.mutex.Unlock()
+	defer func() {
+		c.mutex.Lock()
+		delete(c.iAmWaiters, replies)
+		c.mutex.Unlock()
+	}()
+
+	frame := appendBACnetFrame(nil, true, false, 0, nil, appendBACnetUnconfirmedRequest(nil, bacnetServiceWhoIs, nil))
+	if _, err := c.conn.WriteTo(frame, address); err != nil {
+		return nil, fmt.Errorf("failed to send Who-Is: %w", err)
+	}
+
+	var devices []*BACnetIAm
+	seen := make(map[uint32]bool)
+	for {
+		select {
+		case <-ctx.Done():
+			return devices, nil
+		case <-c.closed:
+			return devices, errors.New("BACnet client closed")
+		case iAm := <-replies:
+			if !seen[iAm.Device.Instance] {
+				seen[iAm.Device.Instance] = true
+				devices = append(devices, iAm)
+			}
+		}
+	}
+}
+
+// request sends a confirmed request and waits for its acknowledgement,
+// resending it on timeout
+func (c *BACnetClient) request(target *BACnetTarget, service byte, data []byte) (*bacnetAPDU, error) {
+	pending := &bacnetPending{address: target.Address.String(), response: make(chan *bacnetAPDU, 1)}
+	invokeID, err := c.allocate(pending)
+	if err != nil {
+		return nil, err
+	}
+	defer func() {
+		c.mutex.Lock()
+		delete(c.pending, invokeID)
+		c.mutex.Unlock()
+	}()
+
+	apdu := appendBACnetConfirmedRequest(nil, invokeID, service, data)
+	frame := appendBACnetFrame(nil, false, true, target.Network, target.MAC, apdu)
+
+	for attempt := 0; attempt <= c.Retries; attempt++ {
+		if _, err := c.conn.WriteTo(frame, target.Address); err != nil {
+			return nil, fmt.Errorf("failed to send BACnet request: %w", err)
+		}
+
+		timer := time.NewTimer(c.Timeout)
+		select {
+		case response := <-pending.response:
+			timer.Stop()
+			if response.Type == bacnetPDUSimpleAck || response.Type == bacnetPDUComplexAck {
+				if response.Service != service {
+					return nil, fmt.Errorf("response for service %d to request for service %d", response.Service, service)
+				}
+				return response, nil
+			}
+			return nil, response.err(true)
+		case <-c.closed:
+			timer.Stop()
+			return nil, errors.New("BACnet client closed")
+		case <-timer.C:
+		}
+	}
+	return nil, fmt.Errorf("no response from BACnet device %s after %d attempts", target.Address, c.Retries+1)
+}
+
+// allocate assigns an unused invoke ID to a request
+func (c *BACnetClient) allocate(pending *bacnetPending) (byte, error) {
+	c.mutex.Lock()
+	defer c.mutex.Unlock()
+
+	for i := 0; i < 256; i++ {
+		invokeID := c.nextInvokeID
+		c.nextInvokeID++
+		if _, busy := c.pending[invokeID]; !busy {
+			c.pending[invokeID] = pending
+			return invokeID, nil
+		}
+	}
+	return 0, errors.New("too many outstanding BACnet requests")
+}
+
+// receive dispatches incoming datagrams until the socket is closed
+func (c *BACnetClient) receive() {
+	defer close(c.closed)
+
+	buffer := make([]byte, 1500)
+	for {
+		n, from, err := c.conn.ReadFrom(buffer)
+		if err != nil {
+			if !errors.Is(err, net.ErrClosed) {
+				c.logger.Warn("BACnet/IP receive failed", zap.Error(err))
+			}
+			return
+		}
+
+		udpFrom, _ := from.(*net.UDPAddr)
+		if err := c.dispatch(append([]byte(nil), buffer[:n]...), udpFrom); err != nil {
+			c.logger.Debug("Ignoring BACnet/IP datagram", zap.Stringer("from", from), zap.Error(err))
+		}
+	}
+}
+
+func (c *BACnetClient) dispatch(data []byte, from *net.UDPAddr) error {
+	message, err := parseBACnetFrame(data)
+	if err != nil || message.NetworkMessage {
+		return err
+	}
+	if message.Source != nil {
+		from = message.Source
+	}
+
+	apdu, err := parseBACnetAPDU(message.APDU)
+	if err != nil {
+		return err
+	}
+
+	switch apdu.Type {
+	case bacnetPDUSimpleAck, bacnetPDUComplexAck, bacnetPDUError, bacnetPDUReject, bacnetPDUAbort:
+		c.mutex.Lock()
+		pending, exists := c.pending[apdu.InvokeID]
+		c.mutex.Unlock()
+		// Replies routed through a BBMD arrive from its address, so only
+		// direct replies are checked against the target
+		if !exists || (message.Source == nil && from != nil && pending.address != from.String()) {
+			return fmt.Errorf("unexpected response for invoke ID %d", apdu.InvokeID)
+		}
+		select {
+		case pending.response <- apdu:
+		default:
+		}
+
+	case bacnetPDUUnconfirmedRequest:
+		switch apdu.Service {
+		case bacnetServiceIAm:
+			iAm, err := decodeBACnetIAm(apdu.Data)
+			if err != nil {
+				return err
+			}
+			iAm.Address = from
+			c.mutex.Lock()
+			for waiter := range c.iAmWaiters {
+				select {
+				case waiter <- iAm:
+				default:
+				}
+			}
+			c.mutex.Unlock()
+		case bacnetServiceUnconfirmedCOVNotification:
+			return c.notify(apdu.Data)
+		}
+
+	case bacnetPDUConfirmedRequest:
+		if apdu.Service != bacnetServiceConfirmedCOVNotification {
+			// Only notifications are served; reject anything else
+			// (reason 9, unrecognized service)
+			reject := []byte{bacnetPDUReject << 4, apdu.InvokeID, 9}
+			_, err := c.conn.WriteTo(appendBACnetFrame(nil, false, false, 0, nil, reject), from)
+			return err
+		}
+		if err := c.notify(apdu.Data); err != nil {
+			return err
+		}
+		ack := []byte{bacnetPDUSimpleAck << 4, apdu.InvokeID, apdu.Service}
+		_, err := c.conn.WriteTo(appendBACnetFrame(nil, false, false, 0, nil, ack), from)
+		return err
+	}
+	return nil
+}
+
+// notify passes a COV notification to its subscription's handler
+func (c *BACnetClient) notify(data []byte) error {
+	notification, err := decodeBACnetCOVNotification(data)
+	if err != nil {
+		return err
+	}
+
+	c.mutex.Lock()
+	handler := c.subscriptions[notification.ProcessID]
+	c.mutex.Unlock()
+	if handler == nil {
+		return fmt.Errorf("COV notification for unknown process %d", notification.ProcessID)
+	}
+	handler(notification)
+	return nil
+}
@@ -0,0 +1,675 @@
+package protocols
+
+import (
+	"encoding/binary"
+	"fmt"
+	"math"
+	"strconv"
+	"strings"
+)
+
+// BACnet Tag and Value Codec
+//
+// BACnet encodes every value behind a tag: one initial octet holding the
+// tag number, the class (application or context) and a length/value/type
+// field, followed by extended tag number and length octets when needed.
+// Application tags identify the datatype; context tags are numbered fields
+// of a service whose datatype is implied. Opening and closing context tags
+// bracket constructed values.
+
+// Application tag numbers
+const (
+	bacnetTagNull            = 0
+	bacnetTagBoolean         = 1
+	bacnetTagUnsigned        = 2
+	bacnetTagSigned          = 3
+	bacnetTagReal            = 4
+	bacnetTagDouble          = 5
+	bacnetTagOctetString     = 6
+	bacnetTagCharacterString = 7
+	bacnetTagBitString       = 8
+	bacnetTagEnumerated      = 9
+	bacnetTagDate            = 10
+	bacnetTagTime            = 11
+	bacnetTagObjectID        = 12
+)
+
+// BACnetObjectType is a BACnet object type
+type BACnetObjectType uint16
+
+const (
+	BACnetAnalogInput      BACnetObjectType = 0
+	BACnetAnalogOutput     BACnetObjectType = 1
+	BACnetAnalogValue      BACnetObjectType = 2
+	BACnetBinaryInput      BACnetObjectType = 3
+	BACnetBinaryOutput     BACnetObjectType = 4
+	BACnetBinaryValue      BACnetObjectType = 5
+	BACnetDevice           BACnetObjectType = 8
+	BACnetMultiStateInput  BACnetObjectType = 13
+	BACnetMultiStateOutput BACnetObjectType = 14
+	BACnetMultiStateValue  BACnetObjectType = 19
+)
+
+// Object identifier field limits
+const (
+	bacnetMaxObjectType     BACnetObjectType = 1023
+	bacnetMaxObjectInstance                  = 1<<22 - 1
+)
+
+// Object type abbreviations accepted in tag addresses
+var bacnetObjectTypeNames = map[string]BACnetObjectType{
+	"AI":                 BACnetAnalogInput,
+	"AO":                 BACnetAnalogOutput,
+	"AV":                 BACnetAnalogValue,
+	"BI":                 BACnetBinaryInput,
+	"BO":                 BACnetBinaryOutput,
+	"BV":                 BACnetBinaryValue,
+	"DEV":                BACnetDevice,
+	"MI":                 BACnetMultiStateInput,
+	"MO":                 BACnetMultiStateOutput,
+	"MV":                 BACnetMultiStateValue,
+	"ANALOG-INPUT":       BACnetAnalogInput,
+	"ANALOG-OUTPUT":      BACnetAnalogOutput,
+	"ANALOG-VALUE":       BACnetAnalogValue,
+	"BINARY-INPUT":       BACnetBinaryInput,
+	"BINARY-OUTPUT":      BACnetBinaryOutput,
+	"BINARY-VALUE":       BACnetBinaryValue,
+	"DEVICE":             BACnetDevice,
+	"MULTI-STATE-INPUT":  BACnetMultiStateInput,
+	"MULTI-STATE-OUTPUT": BACnetMultiStateOutput,
+	"MULTI-STATE-VALUE":  BACnetMultiStateValue,
+}
+
+// BACnetPropertyID is a BACnet property identifier
+type BACnetPropertyID uint32
+
+const (
+	BACnetPropertyDescription      BACnetPropertyID = 28
+	BACnetPropertyFirmwareRevision BACnetPropertyID = 44
+	BACnetPropertyModelName        BACnetPropertyID = 70
+	BACnetPropertyObjectIdentifier BACnetPropertyID = 75
+	BACnetPropertyObjectList       BACnetPropertyID = 76
+	BACnetPropertyObjectName       BACnetPropertyID = 77
+	BACnetPropertyPresentValue     BACnetPropertyID = 85
+	BACnetPropertyStatusFlags      BACnetPropertyID = 111
+	BACnetPropertyUnits            BACnetPropertyID = 117
+	BACnetPropertyVendorName       BACnetPropertyID = 121
+)
+
+// Property names accepted in tag addresses
+var bacnetPropertyNames = map[string]BACnetPropertyID{
+	"description":       BACnetPropertyDescription,
+	"firmware-revision": BACnetPropertyFirmwareRevision,
+	"model-name":        BACnetPropertyModelName,
+	"object-identifier": BACnetPropertyObjectIdentifier,
+	"object-list":       BACnetPropertyObjectList,
+	"object-name":       BACnetPropertyObjectName,
+	"present-value":     BACnetPropertyPresentValue,
+	"status-flags":      BACnetPropertyStatusFlags,
+	"units":             BACnetPropertyUnits,
+	"vendor-name":       BACnetPropertyVendorName,
+}
+
+// BACnetObjectID identifies an object within a device
+type BACnetObjectID struct {
+	Type     BACnetObjectType `json:"type"`
+	Instance uint32           `json:"instance"`
+}
+
+func (o BACnetObjectID) String() string {
+	return fmt.Sprintf("%d:%d", o.Type, o.Instance)
+}
+
+func (o BACnetObjectID) encode() uint32 {
+	return uint32(o.Type)<<22 | o.Instance&bacnetMaxObjectInstance
+}
+
+func decodeBACnetObjectID(v uint32) BACnetObjectID {
+	return BACnetObjectID{Type: BACnetObjectType(v >> 22), Instance: v & bacnetMaxObjectInstance}
+}
+
+// BACnetEnumerated is an enumerated value, such as the present value of a
+// binary object (0 inactive, 1 active)
+type BACnetEnumerated uint32
+
+// BACnetBitString is a bit string, such as status flags
+type BACnetBitString []bool
+
+// BACnetDate is a date; 255 in any field means unspecified
+type BACnetDate struct {
+	Year, Month, Day, Weekday uint8 // Year is offset from 1900
+}
+
+// BACnetTime is a time of day; 255 in any field means unspecified
+type BACnetTime struct {
+	Hour, Minute, Second, Hundredths uint8
+}
+
+// BACnetAddress is a parsed tag address: an object and one of its
+// properties
+type BACnetAddress struct {
+	Object     BACnetObjectID
+	Property   BACnetPropertyID
+	ArrayIndex *uint32
+}
+
+// ParseBACnetAddress parses "<type>:<instance>[.<property>[[<index>]]]",
+// e.g. "AI:3", "analog-value:12.object-name" or "DEV:1001.object-list[2]".
+// Types and properties may also be given by number; the property defaults
+// to present-value.
+func ParseBACnetAddress(address string) (*BACnetAddress, error) {
+	object, property, hasProperty := strings.Cut(strings.TrimSpace(address), ".")
+
+	typeName, instance, found := strings.Cut(object, ":")
+	if !found {
+		return nil, fmt.Errorf("invalid BACnet address %q, expected <type>:<instance>", address)
+	}
+
+	parsed := &BACnetAddress{Property: BACnetPropertyPresentValue}
+	if objectType, exists := bacnetObjectTypeNames[strings.ToUpper(typeName)]; exists {
+		parsed.Object.Type = objectType
+	} else if n, err := strconv.ParseUint(typeName, 10, 16); err == nil && BACnetObjectType(n) <= bacnetMaxObjectType {
+		parsed.Object.Type = BACnetObjectType(n)
+	} else {
+		return nil, fmt.Errorf("unknown BACnet object type %q", typeName)
+	}
+
+	n, err := strconv.ParseUint(instance, 10, 32)
+	if err != nil || n > bacnetMaxObjectInstance {
+		return nil, fmt.Errorf("invalid BACnet object instance %q", instance)
+	}
+	parsed.Object.Instance = uint32(n)
+
+	if !hasProperty {
+		return parsed, nil
+	}
+
+	if name, index, indexed := strings.Cut(property, "["); indexed {
+		if !strings.HasSuffix(index, "]") {
+			return nil, fmt.Errorf("invalid BACnet array index in %q", address)
+		}
+		n, err := strconv.ParseUint(strings.TrimSuffix(index, "]"), 10, 32)
+		if err != nil {
+			return nil, fmt.Errorf("invalid BACnet array index in %q", address)
+		}
+		arrayIndex := uint32(n)
+		parsed.ArrayIndex = &arrayIndex
+		property = name
+	}
+
+	if id, exists := bacnetPropertyNames[strings.ToLower(property)]; exists {
+		parsed.Property = id
+	} else if n, err := strconv.ParseUint(property, 10, 22); err == nil {
+		parsed.Property = BACnetPropertyID(n)
+	} else {
+		return nil, fmt.Errorf("unknown BACnet property %q", property)
+	}
+	return parsed, nil
+}
+
+// bacnetTag is a decoded tag header
+type bacnetTag struct {
+	Number  byte
+	Context bool
+	Length  int // Content length; the value itself for application booleans
+	Opening bool
+	Closing bool
+}
+
+// appendBACnetTag encodes a tag header
+func appendBACnetTag(dst []byte, number byte, context bool, length int) []byte {
+	initial := byte(0)
+	if context {
+		initial |= 0x08
+	}
+	if number <= 14 {
+		initial |= number << 4
+	} else {
+		initial |= 0xF0
+	}
+
+	switch {
+	case length <= 4:
+		dst = append(dst, initial|byte(length))
+	default:
+		dst = append(dst, initial|5)
+	}
+	if number > 14 {
+		dst = append(dst, number)
+	}
+
+	switch {
+	case length <= 4:
+	case length < 254:
+		dst = append(dst, byte(length))
+	case length <= math.MaxUint16:
+		dst = append(dst, 254)
+		dst = binary.BigEndian.AppendUint16(dst, uint16(length))
+	default:
+		dst = append(dst, 255)
+		dst = binary.BigEndian.AppendUint32(dst, uint32(length))
+	}
+	return dst
+}
+
+func appendBACnetOpeningTag(dst []byte, number byte) []byte {
+	return appendBACnetBracket(dst, number, 6)
+}
+
+func appendBACnetClosingTag(dst []byte, number byte) []byte {
+	return appendBACnetBracket(dst, number, 7)
+}
+
+func appendBACnetBracket(dst []byte, number byte, lvt byte) []byte {
+	if number <= 14 {
+		return append(dst, number<<4|0x08|lvt)
+	}
+	return append(dst, 0xF8|lvt, number)
+}
+
+// decodeBACnetTag decodes the tag header at the start of data and returns
+// it with the header length
+func decodeBACnetTag(data []byte) (bacnetTag, int, error) {
+	if len(data) == 0 {
+		return bacnetTag{}, 0, fmt.Errorf("missing tag")
+	}
+
+	initial := data[0]
+	tag := bacnetTag{Number: initial >> 4, Context: initial&0x08 != 0}
+	n := 1
+	if tag.Number == 15 {
+		if len(data) < 2 {
+			return tag, 0, fmt.Errorf("truncated extended tag number")
+		}
+		tag.Number = data[1]
+		n++
+	}
+
+	lvt := initial & 0x07
+	switch {
+	case tag.Context && lvt == 6:
+		tag.Opening = true
+		return tag, n, nil
+	case tag.Context && lvt == 7:
+		tag.Closing = true
+		return tag, n, nil
+	case lvt < 5:
+		tag.Length = int(lvt)
+		return tag, n, nil
+	}
+
+	if len(data) < n+1 {
+		return tag, 0, fmt.Errorf("truncated tag length")
+	}
+	switch length := data[n]; {
+	case length < 254:
+		tag.Length = int(length)
+		n++
+	case length == 254:
+		if len(data) < n+3 {
+			return tag, 0, fmt.Errorf("truncated tag length")
+		}
+		tag.Length = int(binary.BigEndian.Uint16(data[n+1:]))
+		n += 3
+	default:
+		if len(data) < n+5 {
+			return tag, 0, fmt.Errorf("truncated tag length")
+		}
+		tag.Length = int(binary.BigEndian.Uint32(data[n+1:]))
+		n += 5
+	}
+	return tag, n, nil
+}
+
+// appendBACnetUnsigned encodes an unsigned value in as few octets as
+// possible, with an application or context tag
+func appendBACnetUnsigned(dst []byte, number byte, context bool, v uint32) []byte {
+	content := bacnetUnsignedBytes(v)
+	dst = appendBACnetTag(dst, number, context, len(content))
+	return append(dst, content...)
+}
+
+func bacnetUnsignedBytes(v uint32) []byte {
+	switch {
+	case v <= math.MaxUint8:
+		return []byte{byte(v)}
+	case v <= math.MaxUint16:
+		return []byte{byte(v >> 8), byte(v)}
+	case v <= 1<<24-1:
+		return []byte{byte(v >> 16), byte(v >> 8), byte(v)}
+	}
+	return binary.BigEndian.AppendUint32(nil, v)
+}
+
+func appendBACnetObjectID(dst []byte, number byte, context bool, id BACnetObjectID) []byte {
+	dst = appendBACnetTag(dst, number, context, 4)
+	return binary.BigEndian.AppendUint32(dst, id.encode())
+}
+
+// AppendBACnetValue encodes a value with its application tag. Go integers
+// are encoded as Unsigned or Signed, float32 as Real and float64 as
+// Double.
+func AppendBACnetValue(dst []byte, value interface{}) ([]byte, error) {
+	switch v := value.(type) {
+	case nil:
+		return appendBACnetTag(dst, bacnetTagNull, false, 0), nil
+	case bool:
+		length := 0
+		if v {
+			length = 1
+		}
+		return appendBACnetTag(dst, bacnetTagBoolean, false, length), nil
+	case uint8, uint16, uint32, uint, uint64:
+		u, err := integerValue(v, 0, math.MaxUint32)
+		if err != nil {
+			return nil, err
+		}
+		return appendBACnetUnsigned(dst, bacnetTagUnsigned, false, uint32(u)), nil
+	case int8, int16, int32, int, int64:
+		s, err := integerValue(v, math.MinInt32, math.MaxInt32)
+		if err != nil {
+			return nil, err
+		}
+		content := binary.BigEndian.AppendUint32(nil, uint32(s))
+		for len(content) > 1 && (content[0] == 0x00 && content[1]&0x80 == 0 || content[0] == 0xFF && content[1]&0x80 != 0) {
+			content = content[1:]
+		}
+		dst = appendBACnetTag(dst, bacnetTagSigned, false, len(content))
+		return append(dst, content...), nil
+	case float32:
+		dst = appendBACnetTag(dst, bacnetTagReal, false, 4)
+		return binary.BigEndian.AppendUint32(dst, math.Float32bits(v)), nil
+	case float64:
+		dst = appendBACnetTag(dst, bacnetTagDouble, false, 8)
+		return binary.BigEndian.AppendUint64(dst, math.Float64bits(v)), nil
+	case []byte:
+		dst = appendBACnetTag(dst, bacnetTagOctetString, false, len(v))
+		return append(dst, v...), nil
+	case string:
+		dst = appendBACnetTag(dst, bacnetTagCharacterString, false, len(v)+1)
+		dst = append(dst, 0) // UTF-8
+		return append(dst, v...), nil
+	case BACnetBitString:
+		content := make([]byte, 1+(len(v)+7)/8)
+		content[0] = byte((8 - len(v)%8) % 8)
+		for i, bit := range v {
+			if bit {
+				content[1+i/8] |= 0x80 >> (i % 8)
+			}
+		}
+		dst = appendBACnetTag(dst, bacnetTagBitString, false, len(content))
+		return append(dst, content...), nil
+	case BACnetEnumerated:
+		return appendBACnetUnsigned(dst, bacnetTagEnumerated, false, uint32(v)), nil
+	case BACnetDate:
+		dst = appendBACnetTag(dst, bacnetTagDate, false, 4)
+		return append(dst, v.Year, v.Month, v.Day, v.Weekday), nil
+	case BACnetTime:
+		dst = appendBACnetTag(dst, bacnetTagTime, false, 4)
+		return append(dst, v.Hour, v.Minute, v.Second, v.Hundredths), nil
+	case BACnetObjectID:
+		return appendBACnetObjectID(dst, bacnetTagObjectID, false, v), nil
+	}
+	return nil, fmt.Errorf("unsupported BACnet value type %T", value)
+}
+
+// DecodeBACnetValue decodes one application-tagged value and returns it
+// with the number of bytes consumed
+func DecodeBACnetValue(data []byte) (interface{}, int, error) {
+	tag, n, err := decodeBACnetTag(data)
+	if err != nil {
+		return nil, 0, err
+	}
+	if tag.Context || tag.Opening || tag.Closing {
+		return nil, 0, fmt.Errorf("expected application tag, got context tag %d", tag.Number)
+	}
+	if tag.Number == bacnetTagBoolean {
+		return tag.Length != 0, n, nil
+	}
+	if len(data) < n+tag.Length {
+		return nil, 0, fmt.Errorf("truncated value of %d bytes", tag.Length)
+	}
+
+	content := data[n : n+tag.Length]
+	value, err := decodeBACnetContent(tag.Number, content)
+	return value, n + tag.Length, err
+}
+
+// decodeBACnetContent decodes the content octets of an application tag
+func decodeBACnetContent(number byte, content []byte) (interface{}, error) {
+	fixed := func(size int) error {
+		if len(content) != size {
+			return fmt.Errorf("application tag %d with %d bytes, expected %d", number, len(content), size)
+		}
+		return nil
+	}
+
+	switch number {
+	case bacnetTagNull:
+		return nil, nil
+	case bacnetTagUnsigned:
+		v, err := bacnetUnsignedValue(content)
+		return v, err
+	case bacnetTagSigned:
+		if len(content) == 0 || len(content) > 4 {
+			return nil, fmt.Errorf("signed value of %d bytes", len(content))
+		}
+		v := int32(int8(content[0]))
+		for _, b := range content[1:] {
+			v = v<<8 | int32(b)
+		}
+		return v, nil
+	case bacnetTagReal:
+		if err := fixed(4); err != nil {
+			return nil, err
+		}
+		return math.Float32frombits(binary.BigEndian.Uint32(content)), nil
+	case bacnetTagDouble:
+		if err := fixed(8); err != nil {
+			return nil, err
+		}
+		return math.Float64frombits(binary.BigEndian.Uint64(content)), nil
+	case bacnetTagOctetString:
+		return append([]byte(nil), content...), nil
+	case bacnetTagCharacterString:
+		if len(content) == 0 {
+			return nil, fmt.Errorf("character string without a character set")
+		}
+		if content[0] != 0 {
+			return nil, fmt.Errorf("unsupported character set %d", content[0])
+		}
+		return string(content[1:]), nil
+	case bacnetTagBitString:
+		if len(content) == 0 || content[0] > 7 {
+			return nil, fmt.Errorf("invalid bit string")
+		}
+		bits := make(BACnetBitString, 0, 8*(len(content)-1))
+		for _, b := range content[1:] {
+			for i := 0; i < 8; i++ {
+				bits = append(bits, b&(0x80>>i) != 0)
+			}
+		}
+		if unused := int(content[0]); unused <= len(bits) {
+			bits = bits[:len(bits)-unused]
+		}
+		return bits, nil
+	case bacnetTagEnumerated:
+		v, err := bacnetUnsignedValue(content)
+		return BACnetEnumerated(v), err
+	case bacnetTagDate:
+		if err := fixed(4); err != nil {
+			return nil, err
+		}
+		return BACnetDate{Year: content[0], Month: content[1], Day: content[2], Weekday: content[3]}, nil
+	case bacnetTagTime:
+		if err := fixed(4); err != nil {
+			return nil, err
+		}
+		return BACnetTime{Hour: content[0], Minute: content[1], Second: content[2], Hundredths: content[3]}, nil
+	case bacnetTagObjectID:
+		if err := fixed(4); err != nil {
+			return nil, err
+		}
+		return decodeBACnetObjectID(binary.BigEndian.Uint32(content)), nil
+	}
+	return nil, fmt.Errorf("unsupported application tag %d", number)
+}
+
+func bacnetUnsignedValue(content []byte) (uint32, error) {
+	if len(content) == 0 || len(content) > 4 {
+		return 0, fmt.Errorf("unsigned value of %d bytes", len(content))
+	}
+	var v uint32
+	for _, b := range content {
+		v = v<<8 | uint32(b)
+	}
+	return v, nil
+}
+
+// bacnetReader walks the tagged fields of a service
+type bacnetReader struct {
+	data []byte
+}
+
+// peek returns the next tag without consuming it
+func (r *bacnetReader) peek() (bacnetTag, bool) {
+	tag, _, err := decodeBACnetTag(r.data)
+	return tag, err == nil
+}
+
+// isContext reports whether the next tag is the given context tag
+func (r *bacnetReader) isContext(number byte) bool {
+	tag, ok := r.peek()
+	return ok && tag.Context && !tag.Opening && !tag.Closing && tag.Number == number
+}
+
+// isOpening reports whether the next tag is the given opening tag
+func (r *bacnetReader) isOpening(number byte) bool {
+	tag, ok := r.peek()
+	return ok && tag.Opening && tag.Number == number
+}
+
+// isClosing reports whether the next tag is the given closing tag
+func (r *bacnetReader) isClosing(number byte) bool {
+	tag, ok := r.peek()
+	return ok && tag.Closing && tag.Number == number
+}
+
+// context consumes a primitive context tag and returns its content
+func (r *bacnetReader) context(number byte) ([]byte, error) {
+	tag, n, err := decodeBACnetTag(r.data)
+	if err != nil {
+		return nil, err
+	}
+	if !tag.Context || tag.Opening || tag.Closing || tag.Number != number {
+		return nil, fmt.Errorf("expected context tag %d", number)
+	}
+	if len(r.data) < n+tag.Length {
+		return nil, fmt.Errorf("truncated context tag %d", number)
+	}
+	content := r.data[n : n+tag.Length]
+	r.data = r.data[n+tag.Length:]
+	return content, nil
+}
+
+func (r *bacnetReader) contextUnsigned(number byte) (uint32, error) {
+	content, err := r.context(number)
+	if err != nil {
+		return 0, err
+	}
+	return bacnetUnsignedValue(content)
+}
+
+func (r *bacnetReader) contextObjectID(number byte) (BACnetObjectID, error) {
+	content, err := r.context(number)
+	if err != nil {
+		return BACnetObjectID{}, err
+	}
+	if len(content) != 4 {
+		return BACnetObjectID{}, fmt.Errorf("object identifier of %d bytes", len(content))
+	}
+	return decodeBACnetObjectID(binary.BigEndian.Uint32(content)), nil
+}
+
+// bracket consumes an opening or closing tag
+func (r *bacnetReader) bracket(number byte, opening bool) error {
+	tag, n, err := decodeBACnetTag(r.data)
+	if err != nil {
+		return err
+	}
+	if tag.Number != number || (opening && !tag.Opening) || (!opening && !tag.Closing) {
+		if opening {
+			return fmt.Errorf("expected opening tag %d", number)
+		}
+		return fmt.Errorf("expected closing tag %d", number)
+	}
+	r.data = r.data[n:]
+	return nil
+}
+
+// values consumes the application-tagged values up to the closing tag
+// number. A single value is returned as is, several as a slice; values
+// with nested context tags are skipped.
+func (r *bacnetReader) values(number byte) (interface{}, error) {
+	var values []interface{}
+	for !r.isClosing(number) {
+		tag, ok := r.peek()
+		if !ok {
+			return nil, fmt.Errorf("unterminated value list %d", number)
+		}
+		if tag.Context {
+			if err := r.skip(); err != nil {
+				return nil, err
+			}
+			continue
+		}
+
+		value, n, err := DecodeBACnetValue(r.data)
+		if err != nil {
+			return nil, err
+		}
+		values = append(values, value)
+		r.data = r.data[n:]
+	}
+	if err := r.bracket(number, false); err != nil {
+		return nil, err
+	}
+
+	if len(values) == 1 {
+		return values[0], nil
+	}
+	return values, nil
+}
+
+// skip consumes one element, including everything within an opening tag
+func (r *bacnetReader) skip() error {
+	tag, n, err := decodeBACnetTag(r.data)
+	if err != nil {
+		return err
+	}
+
+	switch {
+	case tag.Opening:
+		r.data = r.data[n:]
+		for !r.isClosing(tag.Number) {
+			if len(r.data) == 0 {
+				return fmt.Errorf("unterminated opening tag %d", tag.Number)
+			}
+			if err := r.skip(); err != nil {
+				return err
+			}
+		}
+		return r.bracket(tag.Number, false)
+	case tag.Closing:
+		return fmt.Errorf("unexpected closing tag %d", tag.Number)
+	case !tag.Context && tag.Number == bacnetTagBoolean:
+		r.data = r.data[n:]
+		return nil
+	}
+
+	if len(r.data) < n+tag.Length {
+		return fmt.Errorf("truncated tag")
+	}
+	r.data = r.data[n+tag.Length:]
+	return nil
+}
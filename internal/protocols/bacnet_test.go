@@ -0,0 +1,409 @@
+package protocols
+
+import (
+	"context"
+	"math"
+	"net"
+	"sync"
+	"testing"
+	"time"
+
+	"github.com/stretchr/testify/assert"
+	"go.uber.org/zap"
+)
+
+func TestBACnetValues_RoundTrip(t *testing.T) {
+	values := []interface{}{
+		nil, true, false,
+		uint32(0), uint32(70000), int32(-1), int32(-129), int32(127), int32(math.MaxInt32),
+		float32(21.5), float64(-0.125),
+		"Zone Temp", []byte{0x01, 0x02},
+		BACnetBitString{false, true, false, false},
+		BACnetEnumerated(1),
+		BACnetDate{Year: 124, Month: 3, Day: 15, Weekday: 5},
+		BACnetTime{Hour: 12, Minute: 30, Second: 0, Hundredths: 255},
+		BACnetObjectID{Type: BACnetAnalogValue, Instance: 12},
+	}
+
+	for _, value := range values {
+		encoded, err := AppendBACnetValue(nil, value)
+		assert.NoError(t, err)
+
+		decoded, n, err := DecodeBACnetValue(encoded)
+		assert.NoError(t, err)
+		assert.Equal(t, len(encoded), n)
+		assert.Equal(t, value, decoded)
+	}
+
+	// Integers use the fewest octets
+	encoded, _ := AppendBACnetValue(nil, int32(-1))
+	assert.Equal(t, []byte{0x31, 0xFF}, encoded)
+	encoded, _ = AppendBACnetValue(nil, uint16(256))
+	assert.Equal(t, []byte{0x22, 0x01, 0x00}, encoded)
+
+	_, err := AppendBACnetValue(nil, struct{}{})
+	assert.Error(t, err)
+	_, _, err = DecodeBACnetValue([]byte{0x44, 0x00})
+	assert.Error(t, err)
+}
+
+func TestBACnetTag_Extended(t *testing.T) {
+	for _, length := range []int{0, 4, 5, 253, 254, 70000} {
+		encoded := appendBACnetTag(nil, 20, true, length)
+		tag, n, err := decodeBACnetTag(encoded)
+		assert.NoError(t, err)
+		assert.Equal(t, len(encoded), n)
+		assert.Equal(t, bacnetTag{Number: 20, Context: true, Length: length}, tag)
+	}
+
+	tag, _, err := decodeBACnetTag(appendBACnetClosingTag(nil, 3))
+	assert.NoError(t, err)
+	assert.True(t, tag.Closing)
+}
+
+func TestParseBACnetAddress(t *testing.T) {
+	address, err := ParseBACnetAddress("ai:3")
+	assert.NoError(t, err)
+	assert.Equal(t, &BACnetAddress{Object: BACnetObjectID{Type: BACnetAnalogInput, Instance: 3}, Property: BACnetPropertyPresentValue}, address)
+
+	address, err = ParseBACnetAddress("DEV:1001.object-list[2]")
+	assert.NoError(t, err)
+	assert.Equal(t, BACnetPropertyObjectList, address.Property)
+	if assert.NotNil(t, address.ArrayIndex) {
+		assert.Equal(t, uint32(2), *address.ArrayIndex)
+	}
+
+	address, err = ParseBACnetAddress("130:7.4000")
+	assert.NoError(t, err)
+	assert.Equal(t, BACnetObjectID{Type: 130, Instance: 7}, address.Object)
+	assert.Equal(t, BACnetPropertyID(4000), address.Property)
+
+	for _, invalid := range []string{"AI", "XX:1", "AI:4194304", "AI:1.bogus", "AI:1.present-value[x]", "2000:1"} {
+		_, err := ParseBACnetAddress(invalid)
+		assert.Error(t, err, invalid)
+	}
+}
+
+func TestBACnetFrame_RoundTrip(t *testing.T) {
+	apdu := appendBACnetConfirmedRequest(nil, 7, bacnetServiceReadProperty, []byte{0x0C})
+
+	message, err := parseBACnetFrame(appendBACnetFrame(nil, false, true, 5, []byte{0x21}, apdu))
+	assert.NoError(t, err)
+	assert.Equal(t, apdu, message.APDU)
+
+	parsed, err := parseBACnetAPDU(message.APDU)
+	assert.NoError(t, err)
+	assert.Equal(t, &bacnetAPDU{Type: bacnetPDUConfirmedRequest, InvokeID: 7, Service: bacnetServiceReadProperty, Data: []byte{0x0C}}, parsed)
+
+	// Forwarded NPDUs carry the originating device's address
+	forwarded := []byte{bacnetBVLCType, bacnetBVLCForwardedNPDU, 0, 0, 192, 168, 1, 20, 0xBA, 0xC0, bacnetNPDUVersion, 0}
+	forwarded = append(forwarded, appendBACnetUnconfirmedRequest(nil, bacnetServiceWhoIs, nil)...)
+	forwarded[3] = byte(len(forwarded))
+	message, err = parseBACnetFrame(forwarded)
+	assert.NoError(t, err)
+	assert.Equal(t, "192.168.1.20:47808", message.Source.String())
+
+	_, err = parseBACnetFrame(forwarded[:len(forwarded)-1])
+	assert.Error(t, err)
+}
+
+func TestBACnetReadPropertyMultipleAck(t *testing.T) {
+	object := BACnetObjectID{Type: BACnetAnalogInput, Instance: 1}
+	data := appendBACnetObjectID(nil, 0, true, object)
+	data = appendBACnetOpeningTag(data, 1)
+	data = appendBACnetUnsigned(data, 2, true, uint32(BACnetPropertyPresentValue))
+	data = appendBACnetOpeningTag(data, 4)
+	data, _ = AppendBACnetValue(data, float32(20))
+	data = appendBACnetClosingTag(data, 4)
+	data = appendBACnetUnsigned(data, 2, true, uint32(BACnetPropertyUnits))
+	data = appendBACnetOpeningTag(data, 5)
+	data, _ = AppendBACnetValue(data, BACnetEnumerated(2))
+	data, _ = AppendBACnetValue(data, BACnetEnumerated(32))
+	data = appendBACnetClosingTag(data, 5)
+	data = appendBACnetClosingTag(data, 1)
+
+	values, err := decodeBACnetReadPropertyMultipleAck(data)
+	assert.NoError(t, err)
+	if assert.Len(t, values, 2) {
+		assert.Equal(t, float32(20), values[0].Value)
+		assert.Equal(t, &BACnetError{Kind: "error", Class: 2, Code: 32}, values[1].Err)
+	}
+
+	_, err = decodeBACnetReadPropertyMultipleAck(data[:len(data)-1])
+	assert.Error(t, err)
+}
+
+// bacnetTestDevice is a BACnet/IP device serving a fixed set of
+// properties on the loopback interface
+type bacnetTestDevice struct {
+	conn net.PacketConn
+	id   BACnetObjectID
+
+	mutex      sync.Mutex
+	properties map[BACnetObjectID]map[BACnetPropertyID]interface{}
+	writes     []interface{}
+}
+
+func newBACnetTestDevice(t *testing.T) *bacnetTestDevice {
+	conn, err := net.ListenPacket("udp4", "127.0.0.1:0")
+	assert.NoError(t, err)
+	t.Cleanup(func() { conn.Close() })
+
+	d := &bacnetTestDevice{
+		conn: conn,
+		id:   BACnetObjectID{Type: BACnetDevice, Instance: 1001},
+	}
+	d.properties = map[BACnetObjectID]map[BACnetPropertyID]interface{}{
+		d.id: {
+			BACnetPropertyObjectIdentifier: d.id,
+			BACnetPropertyObjectName:       "AHU-1",
+			BACnetPropertyVendorName:       "Acme",
+		},
+		{Type: BACnetAnalogInput, Instance: 1}: {
+			BACnetPropertyPresentValue: float32(21.5),
+			BACnetPropertyStatusFlags:  BACnetBitString{false, false, false, true},
+		},
+		{Type: BACnetBinaryOutput, Instance: 2}: {
+			BACnetPropertyPresentValue: BACnetEnumerated(0),
+			BACnetPropertyStatusFlags:  BACnetBitString{false, false, false, false},
+		},
+	}
+	go d.serve()
+	return d
+}
+
+func (d *bacnetTestDevice) address() *net.UDPAddr {
+	return d.conn.LocalAddr().(*net.UDPAddr)
+}
+
+func (d *bacnetTestDevice) serve() {
+	buffer := make([]byte, 1500)
+	for {
+		n, from, err := d.conn.ReadFrom(buffer)
+		if err != nil {
+			return
+		}
+		message, err := parseBACnetFrame(buffer[:n])
+		if err != nil {
+			continue
+		}
+		apdu, err := parseBACnetAPDU(message.APDU)
+		if err != nil {
+			continue
+		}
+
+		var responses [][]byte
+		switch {
+		case apdu.Type == bacnetPDUUnconfirmedRequest && apdu.Service == bacnetServiceWhoIs:
+			data := appendBACnetObjectID(nil, bacnetTagObjectID, false, d.id)
+			data = appendBACnetUnsigned(data, bacnetTagUnsigned, false, 1476)
+			data = appendBACnetUnsigned(data, bacnetTagEnumerated, false, 3)
+			data = appendBACnetUnsigned(data, bacnetTagUnsigned, false, 260)
+			responses = append(responses, appendBACnetUnconfirmedRequest(nil, bacnetServiceIAm, data))
+		case apdu.Type == bacnetPDUConfirmedRequest:
+			responses = d.handle(apdu)
+		}
+
+		for _, response := range responses {
+			_, _ = d.conn.WriteTo(appendBACnetFrame(nil, false, false, 0, nil, response), from)
+		}
+	}
+}
+
+func (d *bacnetTestDevice) handle(request *bacnetAPDU) [][]byte {
+	d.mutex.Lock()
+	defer d.mutex.Unlock()
+
+	r := &bacnetReader{data: request.Data}
+	ack := []byte{bacnetPDUComplexAck << 4, request.InvokeID, request.Service}
+	simpleAck := []byte{bacnetPDUSimpleAck << 4, request.InvokeID, request.Service}
+
+	switch request.Service {
+	case bacnetServiceReadProperty:
+		object, _ := r.contextObjectID(0)
+		property, _ := r.contextUnsigned(1)
+		value, exists := d.lookup(&object, BACnetPropertyID(property))
+		if !exists {
+			response := []byte{bacnetPDUError << 4, request.InvokeID, request.Service}
+			response, _ = AppendBACnetValue(response, BACnetEnumerated(2))
+			response, _ = AppendBACnetValue(response, BACnetEnumerated(32))
+			return [][]byte{response}
+		}
+		ack = appendBACnetObjectID(ack, 0, true, object)
+		ack = appendBACnetUnsigned(ack, 1, true, property)
+		ack = appendBACnetOpeningTag(ack, 3)
+		ack, _ = AppendBACnetValue(ack, value)
+		return [][]byte{appendBACnetClosingTag(ack, 3)}
+
+	case bacnetServiceReadPropertyMultiple:
+		for len(r.data) > 0 {
+			object, _ := r.contextObjectID(0)
+			_ = r.bracket(1, true)
+			ack = appendBACnetObjectID(ack, 0, true, object)
+			ack = appendBACnetOpeningTag(ack, 1)
+			for !r.isClosing(1) {
+				property, _ := r.contextUnsigned(0)
+				ack = appendBACnetUnsigned(ack, 2, true, property)
+				if value, exists := d.lookup(&object, BACnetPropertyID(property)); exists {
+					ack = appendBACnetOpeningTag(ack, 4)
+					ack, _ = AppendBACnetValue(ack, value)
+					ack = appendBACnetClosingTag(ack, 4)
+				} else {
+					ack = appendBACnetOpeningTag(ack, 5)
+					ack, _ = AppendBACnetValue(ack, BACnetEnumerated(2))
+					ack, _ = AppendBACnetValue(ack, BACnetEnumerated(32))
+					ack = appendBACnetClosingTag(ack, 5)
+				}
+			}
+			_ = r.bracket(1, false)
+			ack = appendBACnetClosingTag(ack, 1)
+		}
+		return [][]byte{ack}
+
+	case bacnetServiceWriteProperty:
+		object, _ := r.contextObjectID(0)
+		property, _ := r.contextUnsigned(1)
+		_ = r.bracket(3, true)
+		value, _ := r.values(3)
+		d.properties[object][BACnetPropertyID(property)] = value
+		d.writes = append(d.writes, value)
+		return [][]byte{simpleAck}
+
+	case bacnetServiceSubscribeCOV:
+		processID, _ := r.contextUnsigned(0)
+		object, _ := r.contextObjectID(1)
+		if len(r.data) == 0 { // Cancellation
+			return [][]byte{simpleAck}
+		}
+
+		data := appendBACnetUnsigned(nil, 0, true, processID)
+		data = appendBACnetObjectID(data, 1, true, d.id)
+		data = appendBACnetObjectID(data, 2, true, object)
+		data = appendBACnetUnsigned(data, 3, true, 300)
+		data = appendBACnetOpeningTag(data, 4)
+		for _, property := range []BACnetPropertyID{BACnetPropertyPresentValue, BACnetPropertyStatusFlags} {
+			data = appendBACnetUnsigned(data, 0, true, uint32(property))
+			data = appendBACnetOpeningTag(data, 2)
+			data, _ = AppendBACnetValue(data, d.properties[object][property])
+			data = appendBACnetClosingTag(data, 2)
+		}
+		data = appendBACnetClosingTag(data, 4)
+		return [][]byte{simpleAck, appendBACnetUnconfirmedRequest(nil, bacnetServiceUnconfirmedCOVNotification, data)}
+	}
+	return [][]byte{{bacnetPDUReject << 4, request.InvokeID, 9}}
+}
+
+// lookup resolves the wildcard device instance and returns a property
+func (d *bacnetTestDevice) lookup(object *BACnetObjectID, property BACnetPropertyID) (interface{}, bool) {
+	if object.Type == BACnetDevice && object.Instance == bacnetWildcardInstance {
+		*object = d.id
+	}
+	value, exists := d.properties[*object][property]
+	return value, exists
+}
+
+func newBACnetTestClient(t *testing.T) *BACnetClient {
+	client, err := ListenBACnet("127.0.0.1:0", zap.NewNop())
+	assert.NoError(t, err)
+	t.Cleanup(func() { client.Close() })
+	client.Timeout = time.Second
+	return client
+}
+
+func TestBACnetClient_Properties(t *testing.T) {
+	device := newBACnetTestDevice(t)
+	client := newBACnetTestClient(t)
+	target := &BACnetTarget{Address: device.address()}
+
+	value, err := client.ReadProperty(target, &BACnetAddress{Object: BACnetObjectID{Type: BACnetAnalogInput, Instance: 1}, Property: BACnetPropertyPresentValue})
+	assert.NoError(t, err)
+	assert.Equal(t, float32(21.5), value)
+
+	_, err = client.ReadProperty(target, &BACnetAddress{Object: BACnetObjectID{Type: BACnetAnalogInput, Instance: 1}, Property: BACnetPropertyUnits})
+	assert.Equal(t, &BACnetError{Kind: "error", Class: 2, Code: 32}, err)
+
+	values, err := client.ReadPropertyMultiple(target, []*BACnetAddress{
+		{Object: BACnetObjectID{Type: BACnetAnalogInput, Instance: 1}, Property: BACnetPropertyPresentValue},
+		{Object: BACnetObjectID{Type: BACnetAnalogInput, Instance: 1}, Property: BACnetPropertyUnits},
+		{Object: device.id, Property: BACnetPropertyObjectName},
+	})
+	assert.NoError(t, err)
+	if assert.Len(t, values, 3) {
+		assert.Equal(t, float32(21.5), values[0].Value)
+		assert.Error(t, values[1].Err)
+		assert.Equal(t, "AHU-1", values[2].Value)
+	}
+
+	address := &BACnetAddress{Object: BACnetObjectID{Type: BACnetBinaryOutput, Instance: 2}, Property: BACnetPropertyPresentValue}
+	assert.NoError(t, client.WriteProperty(target, address, BACnetEnumerated(1), 8))
+	value, err = client.ReadProperty(target, address)
+	assert.NoError(t, err)
+	assert.Equal(t, BACnetEnumerated(1), value)
+	assert.Error(t, client.WriteProperty(target, address, BACnetEnumerated(1), 17))
+
+	ctx, cancel := context.WithTimeout(context.Background(), 200*time.Millisecond)
+	defer cancel()
+	devices, err := client.WhoIs(ctx, device.address())
+	assert.NoError(t, err)
+	if assert.Len(t, devices, 1) {
+		assert.Equal(t, device.id, devices[0].Device)
+		assert.Equal(t, uint32(260), devices[0].VendorID)
+		assert.Equal(t, device.address().String(), devices[0].Address.String())
+	}
+}
+
+func TestBACnetClient_Timeout(t *testing.T) {
+	silent, err := net.ListenPacket("udp4", "127.0.0.1:0")
+	assert.NoError(t, err)
+	defer silent.Close()
+
+	client := newBACnetTestClient(t)
+	client.Timeout, client.Retries = 20*time.Millisecond, 1
+	_, err = client.ReadProperty(&BACnetTarget{Address: silent.LocalAddr().(*net.UDPAddr)}, &BACnetAddress{Object: BACnetObjectID{Type: BACnetDevice, Instance: 1}})
+	assert.Error(t, err)
+}
+
+func TestBACnetHandler_Tags(t *testing.T) {
+	device := newBACnetTestDevice(t)
+	handler := NewBACnetHandler(zap.NewNop()).(*BACnetHandler)
+	handler.config.LocalAddress = "127.0.0.1:0"
+
+	gatewayDevice := &Device{ID: "ahu", Address: "127.0.0.1", Port: device.address().Port}
+	assert.NoError(t, handler.Connect(gatewayDevice))
+	defer handler.Disconnect(gatewayDevice)
+
+	temperature := &Tag{ID: "temp", Address: "AI:1"}
+	fan := &Tag{ID: "fan", Address: "BO:2", Writable: true}
+	missing := &Tag{ID: "units", Address: "AI:1.units"}
+
+	values, err := handler.ReadMultipleTags(gatewayDevice, []*Tag{temperature, fan, missing})
+	assert.NoError(t, err)
+	assert.Equal(t, map[string]interface{}{"temp": float32(21.5), "fan": false}, values)
+	assert.Equal(t, QualityUncertain, temperature.Quality) // Out of service
+	assert.Equal(t, QualityGood, fan.Quality)
+	assert.Equal(t, QualityBad, missing.Quality)
+
+	assert.NoError(t, handler.WriteTag(gatewayDevice, fan, true))
+	value, err := handler.ReadTag(gatewayDevice, fan)
+	assert.NoError(t, err)
+	assert.Equal(t, true, value)
+	device.mutex.Lock()
+	assert.Equal(t, []interface{}{BACnetEnumerated(1)}, device.writes)
+	device.mutex.Unlock()
+
+	updates := make(chan *Tag, 1)
+	assert.NoError(t, handler.SubscribeCOV(gatewayDevice, temperature, func(tag *Tag) { updates <- tag }))
+	select {
+	case update := <-updates:
+		assert.Equal(t, float32(21.5), update.Value)
+		assert.Equal(t, QualityUncertain, update.Quality)
+	case <-time.After(time.Second):
+		t.Fatal("no COV notification")
+	}
+
+	info, err := handler.GetDeviceInfo(gatewayDevice)
+	assert.NoError(t, err)
+	assert.Equal(t, "Acme", info.Vendor)
+	assert.Equal(t, "1001", info.SerialNumber)
+	assert.NoError(t, handler.Ping(gatewayDevice))
+}
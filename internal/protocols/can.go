@@ -0,0 +1,483 @@
+package protocols
+
+import (
+	"context"
+	"fmt"
+	"net"
+	"os"
+	"strconv"
+	"strings"
+	"sync"
+	"time"
+
+	"go.uber.org/zap"
+)
+
+// CANHandler implements ProtocolHandler for CAN buses carrying J1939 or
+// CANopen traffic. The device address names a SocketCAN interface, e.g.
+// "can0"; the handler listens to the bus and keeps the latest value of
+// every decoded signal. Tag addresses are "J1939:<spn>" for a parameter
+// from any ECU or "J1939:<spn>@<source address>" from one, and
+// "CANOPEN:<node>:<index>.<subindex>" (in hex) for a CANopen object, or
+// "CANOPEN:<node>:NMT" and "CANOPEN:<node>:EMCY" for the state and last
+// emergency of a node. The config key "pdos" maps PDO COB-IDs to their
+// objects, e.g. {"0x185": ["6041.00:UNSIGNED16", "6064.00:INTEGER32"]},
+// and "spns" adds J1939 parameters to the defaults.
+type CANHandler struct {
+	logger      *zap.Logger
+	config      *CANConfig
+	connections sync.Map // map[string]*CANConnection
+	openBus     func(name string) (CANBus, error)
+}
+
+// CANBus is a source of frames
+type CANBus interface {
+	ReadFrame() (*CANFrame, error)
+	Close() error
+}
+
+// CANConnection listens to one bus
+type CANConnection struct {
+	bus       CANBus
+	decoder   *CANDecoder
+	iface     string
+	createdAt time.Time
+
+	mutex     sync.RWMutex
+	signals   map[string]*CANSignal
+	lastFrame time.Time
+	frames    uint64
+	errors    uint64
+	err       error
+}
+
+// CANConfig holds CAN-specific configuration
+type CANConfig struct {
+	// StaleTimeout is the age after which a signal's value is stale and
+	// a silent bus unhealthy
+	StaleTimeout time.Duration `yaml:"stale_timeout"`
+}
+
+// NewCANHandler creates a new CAN protocol handler
+func NewCANHandler(logger *zap.Logger) ProtocolHandler {
+	return &CANHandler{
+		logger: logger,
+		config: &CANConfig{
+			StaleTimeout: 10 * time.Second,
+		},
+		openBus: func(name string) (CANBus, error) {
+			bus, err := OpenSocketCAN(name)
+			if err != nil {
+				return nil, err
+			}
+			return bus, nil
+		},
+	}
+}
+
+// Connect starts listening to a bus
+func (c *CANHandler) Connect(device *Device) error {
+	connectionKey := fmt.Sprintf("%s/%s", device.Address, device.ID)
+	if _, exists := c.connections.Load(connectionKey); exists {
+		device.ConnectionID = connectionKey
+		return nil
+	}
+
+	decoder := NewCANDecoder()
+	if err := c.configure(decoder, device.Config); err != nil {
+		return err
+	}
+
+	bus, err := c.openBus(device.Address)
+	if err != nil {
+		return fmt.Errorf("failed to open CAN bus: %w", err)
+	}
+
+	connection := &CANConnection{
+		bus:       bus,
+		decoder:   decoder,
+		iface:     device.Address,
+		createdAt: time.Now(),
+		signals:   make(map[string]*CANSignal),
+	}
+	c.connections.Store(connectionKey, connection)
+	device.ConnectionID = connectionKey
+	go connection.listen()
+
+	c.logger.Info("CAN bus opened",
+		zap.String("device_id", device.ID),
+		zap.String("interface", device.Address),
+	)
+
+	return nil
+}
+
+// Disconnect stops listening to a bus
+func (c *CANHandler) Disconnect(device *Device) error {
+	if device.ConnectionID == "" {
+		return nil
+	}
+
+	connInterface, exists := c.connections.LoadAndDelete(device.ConnectionID)
+	if !exists {
+		return nil
+	}
+
+	device.ConnectionID = ""
+	return connInterface.(*CANConnection).bus.Close()
+}
+
+// IsConnected checks if the handler listens to the device's bus
+func (c *CANHandler) IsConnected(device *Device) bool {
+	_, err := c.getConnection(device)
+	return err == nil
+}
+
+// ReadTag returns the latest value of a signal
+func (c *CANHandler) ReadTag(device *Device, tag *Tag) (interface{}, error) {
+	conn, err := c.getConnection(device)
+	if err != nil {
+		return nil, err
+	}
+
+	address, err := parseCANAddress(tag.Address)
+	if err != nil {
+		return nil, err
+	}
+
+	conn.mutex.RLock()
+	signal, exists := conn.signals[address]
+	conn.mutex.RUnlock()
+	if !exists {
+		return nil, fmt.Errorf("no value received for %s", address)
+	}
+
+	tag.SetStatus(c.quality(signal))
+	tag.Timestamp = signal.Timestamp
+	return signal.Value, nil
+}
+
+// WriteTag is not supported; the handler only listens to the bus
+func (c *CANHandler) WriteTag(device *Device, tag *Tag, value interface{}) error {
+	return fmt.Errorf("CAN signals are read-only")
+}
+
+// ReadMultipleTags returns the latest values of signals. Signals not yet
+// received are left out of the results.
+func (c *CANHandler) ReadMultipleTags(device *Device, tags []*Tag) (map[string]interface{}, error) {
+	conn, err := c.getConnection(device)
+	if err != nil {
+		return nil, err
+	}
+
+	conn.mutex.RLock()
+	defer conn.mutex.RUnlock()
+
+	results := make(map[string]interface{})
+	for _, tag := range tags {
+		address, err := parseCANAddress(tag.Address)
+		if err != nil {
+			continue
+		}
+		if signal, exists := conn.signals[address]; exists {
+			tag.SetStatus(c.quality(signal))
+			tag.Timestamp = signal.Timestamp
+			results[tag.ID] = signal.Value
+		}
+	}
+	return results, nil
+}
+
+// ReadSignals returns the latest value of every signal received on a bus
+// as tags, with units, quality and the time the signal was received
+func (c *CANHandler) ReadSignals(device *Device) ([]*Tag, error) {
+	conn, err := c.getConnection(device)
+	if err != nil {
+		return nil, err
+	}
+
+	conn.mutex.RLock()
+	defer conn.mutex.RUnlock()
+
+	tags := make([]*Tag, 0, len(conn.signals))
+	for address, signal := range conn.signals {
+		// J1939 parameters are also kept under their source-less address
+		if address != signal.Address {
+			continue
+		}
+		tag := signal.Tag()
+		tag.SetStatus(c.quality(signal))
+		tags = append(tags, tag)
+	}
+	return tags, nil
+}
+
+// DiscoverDevices lists the CAN interfaces of the host; the network range
+// is not used
+func (c *CANHandler) DiscoverDevices(ctx context.Context, networkRange string) ([]*Device, error) {
+	interfaces, err := net.Interfaces()
+	if err != nil {
+		return nil, err
+	}
+
+	devices := make([]*Device, 0)
+	for _, iface := range interfaces {
+		// ARPHRD_CAN
+		linkType, err := os.ReadFile(fmt.Sprintf("/sys/class/net/%s/type", iface.Name))
+		if err != nil || strings.TrimSpace(string(linkType)) != "280" {
+			continue
+		}
+		devices = append(devices, &Device{
+			ID:       fmt.Sprintf("can-%s", iface.Name),
+			Name:     fmt.Sprintf("CAN bus %s", iface.Name),
+			Protocol: "can",
+			Address:  iface.Name,
+			Config:   make(map[string]interface{}),
+			LastSeen: time.Now(),
+		})
+	}
+	return devices, ctx.Err()
+}
+
+// GetDeviceInfo returns information about a CAN bus
+func (c *CANHandler) GetDeviceInfo(device *Device) (*DeviceInfo, error) {
+	info := &DeviceInfo{
+		Vendor:         "Unknown",
+		Model:          "CAN Bus",
+		Capabilities:   []string{"socketcan", "j1939", "j1939-transport", "canopen-pdo", "canopen-sdo"},
+		MaxConnections: 1,
+		CustomInfo:     make(map[string]string),
+	}
+
+	if conn, err := c.getConnection(device); err == nil {
+		info.CustomInfo["interface"] = conn.iface
+	}
+	return info, nil
+}
+
+// GetSupportedDataTypes returns the data types of decoded signals
+func (c *CANHandler) GetSupportedDataTypes() []string {
+	return []string{
+		string(DataTypeBool),
+		string(DataTypeUInt16),
+		string(DataTypeInt64),
+		string(DataTypeUInt64),
+		string(DataTypeFloat32),
+		string(DataTypeFloat64),
+		string(DataTypeString),
+		string(DataTypeBytes),
+	}
+}
+
+// ValidateTagAddress validates a J1939 or CANopen signal address
+func (c *CANHandler) ValidateTagAddress(address string) error {
+	_, err := parseCANAddress(address)
+	return err
+}
+
+// Ping checks that frames are being received
+func (c *CANHandler) Ping(device *Device) error {
+	conn, err := c.getConnection(device)
+	if err != nil {
+		return err
+	}
+
+	conn.mutex.RLock()
+	defer conn.mutex.RUnlock()
+
+	if conn.err != nil {
+		return conn.err
+	}
+	if time.Since(conn.lastFrame) > c.config.StaleTimeout {
+		return fmt.Errorf("no CAN frames received on %s for %s", conn.iface, c.config.StaleTimeout)
+	}
+	return nil
+}
+
+// GetDiagnostics returns diagnostic information for a bus
+func (c *CANHandler) GetDiagnostics(device *Device) (*Diagnostics, error) {
+	conn, err := c.getConnection(device)
+	if err != nil {
+		return nil, err
+	}
+
+	conn.mutex.RLock()
+	defer conn.mutex.RUnlock()
+
+	protocolDiagnostics := map[string]interface{}{
+		"interface": conn.iface,
+		"frames":    conn.frames,
+		"signals":   len(conn.signals),
+	}
+	if conn.err != nil {
+		protocolDiagnostics["last_error"] = conn.err.Error()
+	}
+	diagnostics := &Diagnostics{
+		IsHealthy:           conn.err == nil && time.Since(conn.lastFrame) <= c.config.StaleTimeout,
+		LastCommunication:   conn.lastFrame,
+		ErrorCount:          conn.errors,
+		ConnectionUptime:    time.Since(conn.createdAt),
+		ProtocolDiagnostics: protocolDiagnostics,
+	}
+	if total := conn.frames + conn.errors; total > 0 {
+		diagnostics.SuccessRate = float64(conn.frames) / float64(total)
+	}
+	return diagnostics, nil
+}
+
+func (c *CANHandler) getConnection(device *Device) (*CANConnection, error) {
+	if device.ConnectionID == "" {
+		return nil, fmt.Errorf("device not connected")
+	}
+
+	connInterface, exists := c.connections.Load(device.ConnectionID)
+	if !exists {
+		return nil, fmt.Errorf("connection not found")
+	}
+	return connInterface.(*CANConnection), nil
+}
+
+// quality returns a signal's quality status, stale once it is older than
+// the stale timeout
+func (c *CANHandler) quality(signal *CANSignal) QualityStatus {
+	switch {
+	case signal.Quality != QualityGood:
+		return NewQualityStatus(signal.Quality, "", QualitySourceDevice)
+	case time.Since(signal.Timestamp) > c.config.StaleTimeout:
+		return NewQualityStatus(QualityStale, QualitySubstatusLastKnownValue, QualitySourceGateway)
+	}
+	return GoodQuality
+}
+
+// configure adds the PDO mappings and J1939 parameters of a device's
+// config to a decoder
+func (c *CANHandler) configure(decoder *CANDecoder, config map[string]interface{}) error {
+	if pdos, ok := config["pdos"].(map[string]interface{}); ok {
+		for cobIDText, objects := range pdos {
+			cobID, err := strconv.ParseUint(cobIDText, 0, 11)
+			if err != nil {
+				return fmt.Errorf("invalid PDO COB-ID %q", cobIDText)
+			}
+			entries, ok := objects.([]interface{})
+			if !ok {
+				return fmt.Errorf("PDO 0x%03X: expected a list of objects", cobID)
+			}
+
+			mappings := make([]CANopenMapping, 0, len(entries))
+			for _, entry := range entries {
+				mapping, err := ParseCANopenMapping(fmt.Sprint(entry))
+				if err != nil {
+					return fmt.Errorf("PDO 0x%03X: %w", cobID, err)
+				}
+				mappings = append(mappings, mapping)
+			}
+			if err := decoder.MapPDO(uint32(cobID), mappings); err != nil {
+				return fmt.Errorf("PDO 0x%03X: %w", cobID, err)
+			}
+		}
+	}
+
+	if spns, ok := config["spns"].([]interface{}); ok {
+		for _, entry := range spns {
+			fields, ok := entry.(map[string]interface{})
+			if !ok {
+				return fmt.Errorf("invalid J1939 parameter %v", entry)
+			}
+			spn := J1939SPN{
+				SPN:      uint32(canConfigNumber(fields["spn"])),
+				PGN:      uint32(canConfigNumber(fields["pgn"])),
+				StartBit: int(canConfigNumber(fields["start_bit"])),
+				Length:   int(canConfigNumber(fields["length"])),
+				Scale:    canConfigNumber(fields["scale"]),
+				Offset:   canConfigNumber(fields["offset"]),
+			}
+			spn.Name, _ = fields["name"].(string)
+			spn.Unit, _ = fields["unit"].(string)
+			if spn.SPN == 0 || spn.Length <= 0 || spn.Length > 32 || spn.StartBit < 0 {
+				return fmt.Errorf("invalid J1939 parameter %v", entry)
+			}
+			decoder.AddSPN(spn)
+		}
+	}
+	return nil
+}
+
+// canConfigNumber returns a number of a config value, 0 if it is not one
+func canConfigNumber(value interface{}) float64 {
+	switch v := value.(type) {
+	case int:
+		return float64(v)
+	case int64:
+		return float64(v)
+	case uint64:
+		return float64(v)
+	case float64:
+		return v
+	}
+	return 0
+}
+
+// listen decodes frames until the bus is closed or fails
+func (c *CANConnection) listen() {
+	for {
+		frame, err := c.bus.ReadFrame()
+		if err != nil {
+			c.mutex.Lock()
+			c.errors++
+			c.err = fmt.Errorf("CAN bus %s: %w", c.iface, err)
+			c.mutex.Unlock()
+			return
+		}
+
+		signals := c.decoder.Decode(frame)
+
+		c.mutex.Lock()
+		c.frames++
+		c.lastFrame = time.Now()
+		for _, signal := range signals {
+			c.signals[signal.Address] = signal
+			if spn, _, found := strings.Cut(signal.Address, "@"); found {
+				c.signals[spn] = signal
+			}
+		}
+		c.mutex.Unlock()
+	}
+}
+
+// parseCANAddress parses a signal address and returns it in the form
+// signals are stored under
+func parseCANAddress(address string) (string, error) {
+	protocol, rest, _ := strings.Cut(strings.TrimSpace(address), ":")
+	switch strings.ToUpper(protocol) {
+	case "J1939":
+		spnText, sourceText, hasSource := strings.Cut(rest, "@")
+		spn, err := strconv.ParseUint(spnText, 10, 19)
+		if err != nil {
+			return "", fmt.Errorf("invalid J1939 SPN %q", spnText)
+		}
+		if !hasSource {
+			return fmt.Sprintf("J1939:%d", spn), nil
+		}
+		source, err := strconv.ParseUint(sourceText, 0, 8)
+		if err != nil {
+			return "", fmt.Errorf("invalid J1939 source address %q", sourceText)
+		}
+		return fmt.Sprintf("J1939:%d@%d", spn, source), nil
+	case "CANOPEN":
+		nodeText, object, _ := strings.Cut(rest, ":")
+		node, err := strconv.ParseUint(nodeText, 0, 7)
+		if err != nil || node == 0 {
+			return "", fmt.Errorf("invalid CANopen node ID %q", nodeText)
+		}
+		switch strings.ToUpper(object) {
+		case "NMT", "EMCY":
+			return fmt.Sprintf("CANOPEN:%d:%s", node, strings.ToUpper(object)), nil
+		}
+		index, subIndex, err := parseCANopenObject(object)
+		if err != nil {
+			return "", err
+		}
+		return canopenObject{uint8(node), index, subIndex}.address(), nil
+	}
+	return "", fmt.Errorf("invalid CAN address %q, expected J1939:<spn> or CANOPEN:<node>:<object>", address)
+}
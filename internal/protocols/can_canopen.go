@@ -0,0 +1,481 @@
+package protocols
+
+import (
+	"encoding/binary"
+	"fmt"
+	"math"
+	"strconv"
+	"strings"
+	"time"
+)
+
+// CANopen
+//
+// CANopen splits 11-bit identifiers into a function code and a node ID
+// (1-127). Process data objects (PDOs) carry object dictionary entries
+// packed as little-endian bit fields; which entries is configured per
+// device, so a PDO is only decoded once its mapping is known. Service data
+// objects (SDOs) read and write single entries: expedited transfers carry
+// up to 4 bytes in the initiating frame, segmented transfers follow with
+// up to 7 bytes per frame. Heartbeats report the NMT state of a node and
+// emergency objects its error code and error register.
+
+// CANopen function codes, the identifier without the node ID
+const (
+	CANopenNMT       = 0x000
+	CANopenEmergency = 0x080 // SYNC when sent without a node ID
+	CANopenTime      = 0x100
+	CANopenTPDO1     = 0x180
+	CANopenRPDO1     = 0x200
+	CANopenTPDO2     = 0x280
+	CANopenRPDO2     = 0x300
+	CANopenTPDO3     = 0x380
+	CANopenRPDO3     = 0x400
+	CANopenTPDO4     = 0x480
+	CANopenRPDO4     = 0x500
+	CANopenSDOTx     = 0x580 // server to client
+	CANopenSDORx     = 0x600 // client to server
+	CANopenHeartbeat = 0x700
+)
+
+// SDO client command specifiers, the top 3 bits of the command byte
+const (
+	canopenSDODownloadSegmentRequest  = 0
+	canopenSDOInitiateDownloadRequest = 1
+	canopenSDOInitiateUploadRequest   = 2
+	canopenSDOAbort                   = 4
+)
+
+// SDO server command specifiers
+const (
+	canopenSDOUploadSegmentResponse    = 0
+	canopenSDODownloadSegmentResponse  = 1
+	canopenSDOInitiateUploadResponse   = 2
+	canopenSDOInitiateDownloadResponse = 3
+)
+
+// CANopenType is a CANopen data type, numbered as in EDS files
+type CANopenType uint16
+
+const (
+	CANopenBoolean       CANopenType = 0x0001
+	CANopenInteger8      CANopenType = 0x0002
+	CANopenInteger16     CANopenType = 0x0003
+	CANopenInteger32     CANopenType = 0x0004
+	CANopenUnsigned8     CANopenType = 0x0005
+	CANopenUnsigned16    CANopenType = 0x0006
+	CANopenUnsigned32    CANopenType = 0x0007
+	CANopenReal32        CANopenType = 0x0008
+	CANopenVisibleString CANopenType = 0x0009
+	CANopenOctetString   CANopenType = 0x000A
+	CANopenReal64        CANopenType = 0x0011
+	CANopenInteger64     CANopenType = 0x0015
+	CANopenUnsigned64    CANopenType = 0x001B
+)
+
+var canopenTypeNames = map[CANopenType]string{
+	CANopenBoolean:       "BOOLEAN",
+	CANopenInteger8:      "INTEGER8",
+	CANopenInteger16:     "INTEGER16",
+	CANopenInteger32:     "INTEGER32",
+	CANopenInteger64:     "INTEGER64",
+	CANopenUnsigned8:     "UNSIGNED8",
+	CANopenUnsigned16:    "UNSIGNED16",
+	CANopenUnsigned32:    "UNSIGNED32",
+	CANopenUnsigned64:    "UNSIGNED64",
+	CANopenReal32:        "REAL32",
+	CANopenReal64:        "REAL64",
+	CANopenVisibleString: "VISIBLE_STRING",
+	CANopenOctetString:   "OCTET_STRING",
+}
+
+// CANopen NMT states reported by heartbeats
+var canopenNMTStates = map[byte]string{
+	0x00: "BOOT_UP",
+	0x04: "STOPPED",
+	0x05: "OPERATIONAL",
+	0x7F: "PRE_OPERATIONAL",
+}
+
+func (t CANopenType) String() string {
+	if name, exists := canopenTypeNames[t]; exists {
+		return name
+	}
+	return fmt.Sprintf("0x%04X", uint16(t))
+}
+
+// Bits returns the size of a value of the type, 0 for strings
+func (t CANopenType) Bits() int {
+	switch t {
+	case CANopenBoolean:
+		return 1
+	case CANopenInteger8, CANopenUnsigned8:
+		return 8
+	case CANopenInteger16, CANopenUnsigned16:
+		return 16
+	case CANopenInteger32, CANopenUnsigned32, CANopenReal32:
+		return 32
+	case CANopenInteger64, CANopenUnsigned64, CANopenReal64:
+		return 64
+	}
+	return 0
+}
+
+// DataType returns the tag data type values of the type decode to
+func (t CANopenType) DataType() DataType {
+	switch t {
+	case CANopenBoolean:
+		return DataTypeBool
+	case CANopenInteger8, CANopenInteger16, CANopenInteger32, CANopenInteger64:
+		return DataTypeInt64
+	case CANopenReal32:
+		return DataTypeFloat32
+	case CANopenReal64:
+		return DataTypeFloat64
+	case CANopenVisibleString:
+		return DataTypeString
+	case CANopenOctetString:
+		return DataTypeBytes
+	}
+	return DataTypeUInt64
+}
+
+// CANopenMapping is an object dictionary entry mapped into a PDO
+type CANopenMapping struct {
+	Index    uint16
+	SubIndex uint8
+	Type     CANopenType
+}
+
+// ParseCANopenMapping parses "<index>.<subindex>:<type>" with the index
+// and subindex in hex, e.g. "6064.00:INTEGER32"
+func ParseCANopenMapping(text string) (CANopenMapping, error) {
+	object, typeName, found := strings.Cut(strings.TrimSpace(text), ":")
+	if !found {
+		return CANopenMapping{}, fmt.Errorf("invalid CANopen mapping %q, expected <index>.<subindex>:<type>", text)
+	}
+
+	index, subIndex, err := parseCANopenObject(object)
+	if err != nil {
+		return CANopenMapping{}, err
+	}
+	for dataType, name := range canopenTypeNames {
+		if strings.EqualFold(typeName, name) {
+			return CANopenMapping{Index: index, SubIndex: subIndex, Type: dataType}, nil
+		}
+	}
+	return CANopenMapping{}, fmt.Errorf("invalid CANopen data type %q", typeName)
+}
+
+// String returns the mapping as "<index>.<subindex>:<type>"
+func (m CANopenMapping) String() string {
+	return fmt.Sprintf("%04X.%02X:%s", m.Index, m.SubIndex, m.Type)
+}
+
+// parseCANopenObject parses "<index>.<subindex>" in hex
+func parseCANopenObject(text string) (uint16, uint8, error) {
+	indexText, subIndexText, found := strings.Cut(text, ".")
+	if !found {
+		return 0, 0, fmt.Errorf("invalid CANopen object %q, expected <index>.<subindex>", text)
+	}
+	index, err := strconv.ParseUint(indexText, 16, 16)
+	if err != nil {
+		return 0, 0, fmt.Errorf("invalid CANopen index %q", indexText)
+	}
+	subIndex, err := strconv.ParseUint(subIndexText, 16, 8)
+	if err != nil {
+		return 0, 0, fmt.Errorf("invalid CANopen subindex %q", subIndexText)
+	}
+	return uint16(index), uint8(subIndex), nil
+}
+
+// canopenObject is an object dictionary entry of a node
+type canopenObject struct {
+	node     uint8
+	index    uint16
+	subIndex uint8
+}
+
+func (o canopenObject) address() string {
+	return fmt.Sprintf("CANOPEN:%d:%04X.%02X", o.node, o.index, o.subIndex)
+}
+
+// canopenSDO is an SDO transfer in progress on a node
+type canopenSDO struct {
+	object   canopenObject
+	download bool
+	complete bool
+	data     []byte
+}
+
+// MapPDO sets the entries mapped into the PDO with a COB-ID, in order.
+// The entries belong to the node in the low 7 bits of the COB-ID.
+func (d *CANDecoder) MapPDO(cobID uint32, objects []CANopenMapping) error {
+	bits := 0
+	for _, object := range objects {
+		if object.Type.Bits() == 0 {
+			return fmt.Errorf("CANopen type %s cannot be mapped into a PDO", object.Type)
+		}
+		bits += object.Type.Bits()
+	}
+	if bits > 64 {
+		return fmt.Errorf("PDO mapping of %d bits exceeds 8 bytes", bits)
+	}
+
+	d.mutex.Lock()
+	defer d.mutex.Unlock()
+
+	d.pdos[cobID] = objects
+	for _, object := range objects {
+		d.objectTypes[canopenObject{uint8(cobID & 0x7F), object.Index, object.SubIndex}] = object.Type
+	}
+	return nil
+}
+
+// SetCANopenType sets the type SDO values of a node's entry decode as.
+// Entries of unknown type decode as unsigned integers, or as bytes when
+// longer than 8 bytes.
+func (d *CANDecoder) SetCANopenType(node uint8, index uint16, subIndex uint8, dataType CANopenType) {
+	d.mutex.Lock()
+	defer d.mutex.Unlock()
+
+	d.objectTypes[canopenObject{node, index, subIndex}] = dataType
+}
+
+// decodeCANopen decodes a CANopen frame
+func (d *CANDecoder) decodeCANopen(frame *CANFrame, timestamp time.Time) []*CANSignal {
+	function, node := frame.ID&^0x7F, uint8(frame.ID&0x7F)
+	data := frame.Data
+
+	if objects, exists := d.pdos[frame.ID]; exists && !frame.Remote {
+		return d.decodePDO(node, objects, data, timestamp)
+	}
+	if frame.Remote || node == 0 {
+		return nil
+	}
+
+	switch function {
+	case CANopenEmergency:
+		if len(data) < 3 {
+			return nil
+		}
+		return []*CANSignal{
+			{
+				Address:   fmt.Sprintf("CANOPEN:%d:EMCY", node),
+				Name:      "Emergency Error Code",
+				Value:     binary.LittleEndian.Uint16(data[0:2]),
+				DataType:  DataTypeUInt16,
+				Quality:   QualityGood,
+				Timestamp: timestamp,
+			},
+			// The error register is object 1001h
+			d.canopenSignal(canopenObject{node, 0x1001, 0}, data[2:3], timestamp),
+		}
+	case CANopenSDOTx:
+		if signal := d.decodeSDOResponse(node, data, timestamp); signal != nil {
+			return []*CANSignal{signal}
+		}
+	case CANopenSDORx:
+		d.decodeSDORequest(node, data)
+	case CANopenHeartbeat:
+		if len(data) < 1 {
+			return nil
+		}
+		// Node guarding responses carry a toggle bit
+		state, known := canopenNMTStates[data[0]&0x7F]
+		quality := QualityGood
+		if !known {
+			state, quality = fmt.Sprintf("0x%02X", data[0]&0x7F), QualityUncertain
+		}
+		return []*CANSignal{{
+			Address:   fmt.Sprintf("CANOPEN:%d:NMT", node),
+			Name:      "NMT State",
+			Value:     state,
+			DataType:  DataTypeString,
+			Quality:   quality,
+			Timestamp: timestamp,
+		}}
+	}
+	return nil
+}
+
+// decodePDO decodes the mapped entries of a PDO. Entries beyond the data
+// of a short PDO are left out.
+func (d *CANDecoder) decodePDO(node uint8, objects []CANopenMapping, data []byte, timestamp time.Time) []*CANSignal {
+	signals := make([]*CANSignal, 0, len(objects))
+	offset := 0
+	for _, object := range objects {
+		bits := object.Type.Bits()
+		raw, ok := canBits(data, offset, bits)
+		offset += bits
+		if !ok {
+			break
+		}
+
+		entry := canopenObject{node, object.Index, object.SubIndex}
+		signals = append(signals, &CANSignal{
+			Address:   entry.address(),
+			Name:      fmt.Sprintf("%04X.%02X", object.Index, object.SubIndex),
+			Value:     canopenNumber(object.Type, raw),
+			DataType:  object.Type.DataType(),
+			Quality:   QualityGood,
+			Timestamp: timestamp,
+		})
+	}
+	return signals
+}
+
+// decodeSDORequest follows the client's side of an SDO transfer. Values
+// written are reported once the server confirms them.
+func (d *CANDecoder) decodeSDORequest(node uint8, data []byte) {
+	if len(data) < 8 {
+		return
+	}
+
+	command := data[0]
+	switch command >> 5 {
+	case canopenSDOInitiateDownloadRequest:
+		transfer := &canopenSDO{object: d.sdoObject(node, data), download: true}
+		if command&0x02 != 0 {
+			transfer.data = append(transfer.data, data[4:4+sdoExpeditedSize(command)]...)
+			transfer.complete = true
+		}
+		d.sdos[node] = transfer
+	case canopenSDODownloadSegmentRequest:
+		transfer, exists := d.sdos[node]
+		if !exists || !transfer.download || transfer.complete {
+			return
+		}
+		unused := int(command>>1) & 0x07
+		transfer.data = append(transfer.data, data[1:8-unused]...)
+		transfer.complete = command&0x01 != 0
+	case canopenSDOInitiateUploadRequest, canopenSDOAbort:
+		delete(d.sdos, node)
+	}
+}
+
+// decodeSDOResponse follows the server's side of an SDO transfer and
+// returns the entry's value when a transfer completes. An abort reports
+// the entry with bad quality.
+func (d *CANDecoder) decodeSDOResponse(node uint8, data []byte, timestamp time.Time) *CANSignal {
+	if len(data) < 8 {
+		return nil
+	}
+
+	command := data[0]
+	switch command >> 5 {
+	case canopenSDOInitiateUploadResponse:
+		object := d.sdoObject(node, data)
+		if command&0x02 != 0 {
+			delete(d.sdos, node)
+			return d.canopenSignal(object, data[4:4+sdoExpeditedSize(command)], timestamp)
+		}
+		d.sdos[node] = &canopenSDO{object: object}
+	case canopenSDOUploadSegmentResponse:
+		transfer, exists := d.sdos[node]
+		if !exists || transfer.download {
+			return nil
+		}
+		unused := int(command>>1) & 0x07
+		transfer.data = append(transfer.data, data[1:8-unused]...)
+		if command&0x01 == 0 {
+			return nil
+		}
+		delete(d.sdos, node)
+		return d.canopenSignal(transfer.object, transfer.data, timestamp)
+	case canopenSDOInitiateDownloadResponse, canopenSDODownloadSegmentResponse:
+		// Confirms an expedited download or a segment; the value is
+		// written once the last segment is confirmed
+		transfer, exists := d.sdos[node]
+		if !exists || !transfer.download || !transfer.complete {
+			return nil
+		}
+		delete(d.sdos, node)
+		return d.canopenSignal(transfer.object, transfer.data, timestamp)
+	case canopenSDOAbort:
+		delete(d.sdos, node)
+		object := d.sdoObject(node, data)
+		return &CANSignal{
+			Address:   object.address(),
+			Name:      fmt.Sprintf("%04X.%02X", object.index, object.subIndex),
+			DataType:  d.canopenType(object).DataType(),
+			Quality:   QualityBad,
+			Timestamp: timestamp,
+		}
+	}
+	return nil
+}
+
+// sdoObject returns the entry addressed by an initiating or abort frame
+func (d *CANDecoder) sdoObject(node uint8, data []byte) canopenObject {
+	return canopenObject{node, binary.LittleEndian.Uint16(data[1:3]), data[3]}
+}
+
+// sdoExpeditedSize returns the number of data bytes of an expedited
+// transfer, all 4 unless the size is indicated
+func sdoExpeditedSize(command byte) int {
+	if command&0x01 == 0 {
+		return 4
+	}
+	return 4 - int(command>>2)&0x03
+}
+
+// canopenType returns the configured type of an entry
+func (d *CANDecoder) canopenType(object canopenObject) CANopenType {
+	if dataType, exists := d.objectTypes[object]; exists {
+		return dataType
+	}
+	if object.index == 0x1001 {
+		return CANopenUnsigned8
+	}
+	return 0
+}
+
+// canopenSignal decodes an entry's value from its bytes
+func (d *CANDecoder) canopenSignal(object canopenObject, data []byte, timestamp time.Time) *CANSignal {
+	signal := &CANSignal{
+		Address:   object.address(),
+		Name:      fmt.Sprintf("%04X.%02X", object.index, object.subIndex),
+		Quality:   QualityGood,
+		Timestamp: timestamp,
+	}
+
+	dataType := d.canopenType(object)
+	switch {
+	case dataType == CANopenVisibleString:
+		signal.Value = strings.TrimRight(string(data), "\x00")
+	case dataType.Bits() > 0 && len(data)*8 >= dataType.Bits():
+		var raw [8]byte
+		copy(raw[:], data)
+		signal.Value = canopenNumber(dataType, binary.LittleEndian.Uint64(raw[:]))
+	case dataType == 0 && len(data) <= 8:
+		var raw [8]byte
+		copy(raw[:], data)
+		signal.Value = binary.LittleEndian.Uint64(raw[:])
+		dataType = CANopenUnsigned64
+	default:
+		signal.Value = append([]byte(nil), data...)
+		dataType = CANopenOctetString
+	}
+	signal.DataType = dataType.DataType()
+	return signal
+}
+
+// canopenNumber converts the raw bits of a numeric value
+func canopenNumber(dataType CANopenType, raw uint64) interface{} {
+	bits := dataType.Bits()
+	switch dataType {
+	case CANopenBoolean:
+		return raw&0x01 != 0
+	case CANopenInteger8, CANopenInteger16, CANopenInteger32, CANopenInteger64:
+		return int64(raw<<(64-bits)) >> (64 - bits)
+	case CANopenReal32:
+		return math.Float32frombits(uint32(raw))
+	case CANopenReal64:
+		return math.Float64frombits(raw)
+	}
+	if bits < 64 {
+		raw &= uint64(1)<<bits - 1
+	}
+	return raw
+}
@@ -0,0 +1,101 @@
+package protocols
+
+import (
+	"sync"
+	"time"
+)
+
+// CAN Signal Decoding
+//
+// CANDecoder turns frames into signals: frames with extended identifiers
+// are decoded as J1939, frames with standard identifiers as CANopen. The
+// decoder keeps the state of transport protocol and SDO transfers, so one
+// decoder must see all frames of a bus in order.
+
+// CANSignal is a value decoded from one or more frames
+type CANSignal struct {
+	Address   string
+	Name      string
+	Value     interface{}
+	DataType  DataType
+	Unit      string
+	Quality   Quality
+	Timestamp time.Time
+}
+
+// Tag returns the signal as a tag with the signal's address
+func (s *CANSignal) Tag() *Tag {
+	return &Tag{
+		ID:        s.Address,
+		Name:      s.Name,
+		Address:   s.Address,
+		DataType:  string(s.DataType),
+		Value:     s.Value,
+		Quality:   s.Quality,
+		Timestamp: s.Timestamp,
+		Unit:      s.Unit,
+	}
+}
+
+// CANDecoder decodes J1939 parameters and CANopen objects from frames
+type CANDecoder struct {
+	mutex       sync.Mutex
+	spns        map[uint32][]J1939SPN // by PGN
+	transports  map[uint16]*j1939Transport
+	pdos        map[uint32][]CANopenMapping // by COB-ID
+	objectTypes map[canopenObject]CANopenType
+	sdos        map[uint8]*canopenSDO // by node
+}
+
+// NewCANDecoder creates a decoder for the J1939 default parameters and
+// no PDOs
+func NewCANDecoder() *CANDecoder {
+	d := &CANDecoder{
+		spns:        make(map[uint32][]J1939SPN),
+		transports:  make(map[uint16]*j1939Transport),
+		pdos:        make(map[uint32][]CANopenMapping),
+		objectTypes: make(map[canopenObject]CANopenType),
+		sdos:        make(map[uint8]*canopenSDO),
+	}
+	for _, spn := range J1939DefaultSPNs {
+		d.AddSPN(spn)
+	}
+	return d
+}
+
+// AddSPN adds a J1939 parameter to decode, replacing a parameter with the
+// same number
+func (d *CANDecoder) AddSPN(spn J1939SPN) {
+	d.mutex.Lock()
+	defer d.mutex.Unlock()
+
+	for pgn, spns := range d.spns {
+		for i := range spns {
+			if spns[i].SPN == spn.SPN {
+				d.spns[pgn] = append(spns[:i:i], spns[i+1:]...)
+				break
+			}
+		}
+	}
+	d.spns[spn.PGN] = append(d.spns[spn.PGN], spn)
+}
+
+// Decode returns the signals a frame completes. Frames without a
+// timestamp are stamped with the current time.
+func (d *CANDecoder) Decode(frame *CANFrame) []*CANSignal {
+	timestamp := frame.Timestamp
+	if timestamp.IsZero() {
+		timestamp = time.Now()
+	}
+
+	d.mutex.Lock()
+	defer d.mutex.Unlock()
+
+	if frame.Extended {
+		if frame.Remote {
+			return nil
+		}
+		return d.decodeJ1939(frame, timestamp)
+	}
+	return d.decodeCANopen(frame, timestamp)
+}
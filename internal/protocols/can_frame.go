@@ -0,0 +1,145 @@
+package protocols
+
+import (
+	"encoding/binary"
+	"encoding/hex"
+	"fmt"
+	"strconv"
+	"strings"
+	"time"
+)
+
+// CAN Frames
+//
+// CANFrame is one classic CAN frame of up to 8 data bytes. Frames are read
+// from a SocketCAN raw socket, from the 16-byte struct can_frame layout
+// SocketCAN uses, or from candump log lines such as
+// "(1600000000.000000) can0 18FEF100#FFFF0AFFFFFFFFFF".
+
+// CANFrameSize is the size of a SocketCAN struct can_frame
+const CANFrameSize = 16
+
+// SocketCAN identifier flags and masks
+const (
+	canEFFFlag = 0x80000000 // 29-bit extended identifier
+	canRTRFlag = 0x40000000 // remote transmission request
+	canERRFlag = 0x20000000 // error frame
+	canSFFMask = 0x000007FF
+	canEFFMask = 0x1FFFFFFF
+)
+
+// CANFrame is a classic CAN frame
+type CANFrame struct {
+	ID        uint32
+	Extended  bool
+	Remote    bool
+	Data      []byte
+	Timestamp time.Time
+}
+
+// ParseCANFrame parses a SocketCAN struct can_frame. Error frames report
+// bus errors rather than carry data and are returned as an error.
+func ParseCANFrame(raw []byte) (*CANFrame, error) {
+	if len(raw) < CANFrameSize {
+		return nil, fmt.Errorf("CAN frame of %d bytes, expected %d", len(raw), CANFrameSize)
+	}
+
+	id := binary.LittleEndian.Uint32(raw[0:4])
+	if id&canERRFlag != 0 {
+		return nil, fmt.Errorf("CAN error frame 0x%08X", id&canEFFMask)
+	}
+	length := int(raw[4])
+	if length > 8 {
+		return nil, fmt.Errorf("CAN frame with %d data bytes", length)
+	}
+
+	frame := &CANFrame{
+		Extended: id&canEFFFlag != 0,
+		Remote:   id&canRTRFlag != 0,
+	}
+	if frame.Extended {
+		frame.ID = id & canEFFMask
+	} else {
+		frame.ID = id & canSFFMask
+	}
+	if !frame.Remote {
+		frame.Data = append([]byte(nil), raw[8:8+length]...)
+	}
+	return frame, nil
+}
+
+// AppendCANFrame appends frame in the struct can_frame layout
+func AppendCANFrame(dst []byte, frame *CANFrame) []byte {
+	id := frame.ID & canSFFMask
+	if frame.Extended {
+		id = frame.ID&canEFFMask | canEFFFlag
+	}
+	if frame.Remote {
+		id |= canRTRFlag
+	}
+
+	var data [8]byte
+	length := copy(data[:], frame.Data)
+	dst = binary.LittleEndian.AppendUint32(dst, id)
+	dst = append(dst, byte(length), 0, 0, 0)
+	return append(dst, data[:]...)
+}
+
+// ParseCandumpLine parses a line of a candump log ("candump -L"), or a
+// bare "<id>#<data>" frame as given to cansend. Identifiers of more than
+// three hex digits are extended.
+func ParseCandumpLine(line string) (*CANFrame, error) {
+	fields := strings.Fields(line)
+	if len(fields) == 0 {
+		return nil, fmt.Errorf("empty candump line")
+	}
+
+	var timestamp time.Time
+	if strings.HasPrefix(fields[0], "(") && strings.HasSuffix(fields[0], ")") {
+		seconds, err := strconv.ParseFloat(strings.Trim(fields[0], "()"), 64)
+		if err != nil {
+			return nil, fmt.Errorf("invalid candump timestamp %q", fields[0])
+		}
+		whole := int64(seconds)
+		timestamp = time.Unix(whole, int64((seconds-float64(whole))*1e9)).UTC()
+		fields = fields[1:]
+	}
+	// The interface name precedes the frame
+	frameText := fields[len(fields)-1]
+
+	idText, dataText, found := strings.Cut(frameText, "#")
+	if !found {
+		return nil, fmt.Errorf("invalid candump frame %q, expected <id>#<data>", frameText)
+	}
+	id, err := strconv.ParseUint(idText, 16, 32)
+	if err != nil || id > canEFFMask {
+		return nil, fmt.Errorf("invalid CAN identifier %q", idText)
+	}
+
+	frame := &CANFrame{ID: uint32(id), Extended: len(idText) > 3, Timestamp: timestamp}
+	if !frame.Extended && id > canSFFMask {
+		return nil, fmt.Errorf("invalid CAN identifier %q", idText)
+	}
+	if strings.HasPrefix(dataText, "R") {
+		frame.Remote = true
+		return frame, nil
+	}
+
+	frame.Data, err = hex.DecodeString(strings.ReplaceAll(dataText, ".", ""))
+	if err != nil || len(frame.Data) > 8 {
+		return nil, fmt.Errorf("invalid CAN data %q", dataText)
+	}
+	return frame, nil
+}
+
+// String returns the frame as "<id>#<data>"
+func (f *CANFrame) String() string {
+	id := fmt.Sprintf("%03X", f.ID)
+	if f.Extended {
+		id = fmt.Sprintf("%08X", f.ID)
+	}
+	if f.Remote {
+		return id + "#R"
+	}
+	return id + "#" + strings.ToUpper(hex.EncodeToString(f.Data))
+}
@@ -0,0 +1,295 @@
+package protocols
+
+import (
+	"fmt"
+	"time"
+)
+
+// SAE J1939
+//
+// J1939 runs on 29-bit identifiers carrying a priority, a parameter group
+// number (PGN) and the source address of the sender. A parameter group
+// packs suspect parameters (SPNs), each a little-endian bit field scaled
+// by a resolution and an offset. Groups longer than 8 bytes are sent with
+// the transport protocol: a connection management frame announces the
+// group and data transfer frames carry it 7 bytes at a time.
+
+// J1939 parameter group numbers
+const (
+	J1939PGNRequest        = 0xEA00
+	J1939PGNTPData         = 0xEB00
+	J1939PGNTPConnection   = 0xEC00
+	J1939PGNAddressClaimed = 0xEE00
+)
+
+// J1939GlobalAddress is the destination of broadcast groups
+const J1939GlobalAddress = 0xFF
+
+// Transport protocol connection management control bytes
+const (
+	j1939TPRequestToSend   = 16
+	j1939TPClearToSend     = 17
+	j1939TPEndOfMessageAck = 19
+	j1939TPBroadcast       = 32
+	j1939TPAbort           = 255
+)
+
+// j1939TPMaxSize is the longest group the transport protocol carries
+const j1939TPMaxSize = 1785
+
+// J1939ID is a decoded 29-bit J1939 identifier
+type J1939ID struct {
+	Priority    uint8
+	PGN         uint32
+	Source      uint8
+	Destination uint8
+}
+
+// ParseJ1939ID decodes a 29-bit identifier. Groups with a PDU format
+// below 240 are sent to the destination address in the PDU specific
+// byte, which is not part of their PGN.
+func ParseJ1939ID(id uint32) J1939ID {
+	result := J1939ID{
+		Priority:    uint8(id>>26) & 0x07,
+		PGN:         (id >> 8) & 0x3FFFF,
+		Source:      uint8(id),
+		Destination: J1939GlobalAddress,
+	}
+	if uint8(id>>16) < 240 {
+		result.Destination = uint8(id >> 8)
+		result.PGN &^= 0xFF
+	}
+	return result
+}
+
+// CANID returns the 29-bit identifier
+func (id J1939ID) CANID() uint32 {
+	canID := uint32(id.Priority&0x07)<<26 | (id.PGN&0x3FFFF)<<8 | uint32(id.Source)
+	if uint8(id.PGN>>8) < 240 {
+		canID |= uint32(id.Destination) << 8
+	}
+	return canID
+}
+
+// J1939SPN defines a suspect parameter: where it sits in its group and
+// how its raw value scales to engineering units
+type J1939SPN struct {
+	SPN      uint32
+	Name     string
+	PGN      uint32
+	StartBit int // bit offset in the group, byte 1 bit 1 being 0
+	Length   int // length in bits
+	Scale    float64
+	Offset   float64
+	Unit     string
+}
+
+// J1939DefaultSPNs are the engine and vehicle parameters decoded without
+// configuration
+var J1939DefaultSPNs = []J1939SPN{
+	{SPN: 512, Name: "Driver's Demand Engine - Percent Torque", PGN: 61444, StartBit: 8, Length: 8, Scale: 1, Offset: -125, Unit: "%"},
+	{SPN: 513, Name: "Actual Engine - Percent Torque", PGN: 61444, StartBit: 16, Length: 8, Scale: 1, Offset: -125, Unit: "%"},
+	{SPN: 190, Name: "Engine Speed", PGN: 61444, StartBit: 24, Length: 16, Scale: 0.125, Unit: "rpm"},
+	{SPN: 91, Name: "Accelerator Pedal Position 1", PGN: 61443, StartBit: 8, Length: 8, Scale: 0.4, Unit: "%"},
+	{SPN: 92, Name: "Engine Percent Load At Current Speed", PGN: 61443, StartBit: 16, Length: 8, Scale: 1, Unit: "%"},
+	{SPN: 247, Name: "Engine Total Hours of Operation", PGN: 65253, StartBit: 0, Length: 32, Scale: 0.05, Unit: "h"},
+	{SPN: 250, Name: "Engine Total Fuel Used", PGN: 65257, StartBit: 32, Length: 32, Scale: 0.5, Unit: "L"},
+	{SPN: 245, Name: "Total Vehicle Distance", PGN: 65248, StartBit: 32, Length: 32, Scale: 0.125, Unit: "km"},
+	{SPN: 110, Name: "Engine Coolant Temperature", PGN: 65262, StartBit: 0, Length: 8, Scale: 1, Offset: -40, Unit: "°C"},
+	{SPN: 174, Name: "Engine Fuel Temperature 1", PGN: 65262, StartBit: 8, Length: 8, Scale: 1, Offset: -40, Unit: "°C"},
+	{SPN: 175, Name: "Engine Oil Temperature 1", PGN: 65262, StartBit: 16, Length: 16, Scale: 0.03125, Offset: -273, Unit: "°C"},
+	{SPN: 94, Name: "Engine Fuel Delivery Pressure", PGN: 65263, StartBit: 0, Length: 8, Scale: 4, Unit: "kPa"},
+	{SPN: 98, Name: "Engine Oil Level", PGN: 65263, StartBit: 16, Length: 8, Scale: 0.4, Unit: "%"},
+	{SPN: 100, Name: "Engine Oil Pressure", PGN: 65263, StartBit: 24, Length: 8, Scale: 4, Unit: "kPa"},
+	{SPN: 111, Name: "Engine Coolant Level", PGN: 65263, StartBit: 56, Length: 8, Scale: 0.4, Unit: "%"},
+	{SPN: 84, Name: "Wheel-Based Vehicle Speed", PGN: 65265, StartBit: 8, Length: 16, Scale: 1.0 / 256, Unit: "km/h"},
+	{SPN: 183, Name: "Engine Fuel Rate", PGN: 65266, StartBit: 0, Length: 16, Scale: 0.05, Unit: "L/h"},
+	{SPN: 184, Name: "Engine Instantaneous Fuel Economy", PGN: 65266, StartBit: 16, Length: 16, Scale: 1.0 / 512, Unit: "km/L"},
+	{SPN: 108, Name: "Barometric Pressure", PGN: 65269, StartBit: 0, Length: 8, Scale: 0.5, Unit: "kPa"},
+	{SPN: 171, Name: "Ambient Air Temperature", PGN: 65269, StartBit: 24, Length: 16, Scale: 0.03125, Offset: -273, Unit: "°C"},
+	{SPN: 102, Name: "Engine Intake Manifold 1 Pressure", PGN: 65270, StartBit: 8, Length: 8, Scale: 2, Unit: "kPa"},
+	{SPN: 105, Name: "Engine Intake Manifold 1 Temperature", PGN: 65270, StartBit: 16, Length: 8, Scale: 1, Offset: -40, Unit: "°C"},
+	{SPN: 168, Name: "Battery Potential / Power Input 1", PGN: 65271, StartBit: 32, Length: 16, Scale: 0.05, Unit: "V"},
+	{SPN: 96, Name: "Fuel Level 1", PGN: 65276, StartBit: 8, Length: 8, Scale: 0.4, Unit: "%"},
+}
+
+// Decode returns the parameter's value in a group. Parameters the sender
+// marks as not available are not decoded; an error indicator decodes to
+// a nil value of bad quality and reserved values to uncertain quality.
+func (s *J1939SPN) Decode(data []byte) (value interface{}, quality Quality, ok bool) {
+	raw, ok := canBits(data, s.StartBit, s.Length)
+	if !ok {
+		return nil, QualityBad, false
+	}
+
+	quality, ok = j1939Status(raw, s.Length)
+	if !ok {
+		return nil, quality, false
+	}
+	if quality == QualityBad {
+		return nil, quality, true
+	}
+
+	scale := s.Scale
+	if scale == 0 {
+		scale = 1
+	}
+	return float64(raw)*scale + s.Offset, quality, true
+}
+
+// j1939Status classifies a raw parameter value. For parameters of whole
+// bytes the most significant byte 0xFF marks the parameter not available,
+// 0xFE an error and 0xFB to 0xFD are reserved; for bit fields all ones
+// is not available and the value below it an error.
+func j1939Status(raw uint64, length int) (Quality, bool) {
+	if length%8 == 0 {
+		switch top := raw >> (length - 8); {
+		case top == 0xFF:
+			return QualityBad, false
+		case top == 0xFE:
+			return QualityBad, true
+		case top > 0xFA:
+			return QualityUncertain, true
+		}
+		return QualityGood, true
+	}
+
+	all := uint64(1)<<length - 1
+	switch {
+	case raw == all:
+		return QualityBad, false
+	case length > 1 && raw == all-1:
+		return QualityBad, true
+	}
+	return QualityGood, true
+}
+
+// canBits extracts a little-endian bit field of up to 64 bits
+func canBits(data []byte, start, length int) (uint64, bool) {
+	if start < 0 || length <= 0 || length > 64 || start+length > len(data)*8 {
+		return 0, false
+	}
+
+	var value uint64
+	for i := 0; i < length; i++ {
+		bit := start + i
+		value |= uint64(data[bit/8]>>(bit%8)&1) << i
+	}
+	return value, true
+}
+
+// j1939Transport is a group being reassembled from data transfer frames
+type j1939Transport struct {
+	pgn     uint32
+	size    int
+	packets int
+	next    int
+	data    []byte
+}
+
+// j1939TransportKey identifies a transport session by its sender and
+// destination
+func j1939TransportKey(source, destination uint8) uint16 {
+	return uint16(source)<<8 | uint16(destination)
+}
+
+// decodeJ1939 decodes the parameters of a J1939 frame, reassembling
+// groups sent with the transport protocol
+func (d *CANDecoder) decodeJ1939(frame *CANFrame, timestamp time.Time) []*CANSignal {
+	id := ParseJ1939ID(frame.ID)
+	data := frame.Data
+
+	switch id.PGN {
+	case J1939PGNTPConnection:
+		d.j1939Connection(id, data)
+		return nil
+	case J1939PGNTPData:
+		pgn, group, complete := d.j1939Data(id, data)
+		if !complete {
+			return nil
+		}
+		id.PGN, data = pgn, group
+	}
+
+	var signals []*CANSignal
+	for i := range d.spns[id.PGN] {
+		spn := &d.spns[id.PGN][i]
+		value, quality, ok := spn.Decode(data)
+		if !ok {
+			continue
+		}
+		signals = append(signals, &CANSignal{
+			Address:   fmt.Sprintf("J1939:%d@%d", spn.SPN, id.Source),
+			Name:      spn.Name,
+			Value:     value,
+			DataType:  DataTypeFloat64,
+			Unit:      spn.Unit,
+			Quality:   quality,
+			Timestamp: timestamp,
+		})
+	}
+	return signals
+}
+
+// j1939Connection opens or closes a transport session. Sessions with a
+// destination follow the receiver's clear to send frames.
+func (d *CANDecoder) j1939Connection(id J1939ID, data []byte) {
+	if len(data) < 8 {
+		return
+	}
+
+	key := j1939TransportKey(id.Source, id.Destination)
+	switch data[0] {
+	case j1939TPRequestToSend, j1939TPBroadcast:
+		size := int(data[1]) | int(data[2])<<8
+		packets := int(data[3])
+		if size <= 8 || size > j1939TPMaxSize || packets != (size+6)/7 {
+			delete(d.transports, key)
+			return
+		}
+		d.transports[key] = &j1939Transport{
+			pgn:     uint32(data[5]) | uint32(data[6])<<8 | uint32(data[7])<<16,
+			size:    size,
+			packets: packets,
+			next:    1,
+			data:    make([]byte, 0, packets*7),
+		}
+	case j1939TPClearToSend:
+		// The receiver may ask for packets to be sent again
+		transport, exists := d.transports[j1939TransportKey(id.Destination, id.Source)]
+		next := int(data[2])
+		if exists && data[1] > 0 && next >= 1 && next <= transport.next {
+			transport.next = next
+			transport.data = transport.data[:(next-1)*7]
+		}
+	case j1939TPAbort:
+		// Either side may abort; the receiver's abort comes from the
+		// session's destination
+		delete(d.transports, key)
+		delete(d.transports, j1939TransportKey(id.Destination, id.Source))
+	}
+}
+
+// j1939Data adds a data transfer frame to its session and returns the
+// group once all packets arrived. A packet out of sequence drops the
+// session.
+func (d *CANDecoder) j1939Data(id J1939ID, data []byte) (uint32, []byte, bool) {
+	key := j1939TransportKey(id.Source, id.Destination)
+	transport, exists := d.transports[key]
+	if !exists || len(data) < 8 {
+		return 0, nil, false
+	}
+	if int(data[0]) != transport.next {
+		delete(d.transports, key)
+		return 0, nil, false
+	}
+
+	transport.data = append(transport.data, data[1:8]...)
+	transport.next++
+	if transport.next <= transport.packets {
+		return 0, nil, false
+	}
+
+	delete(d.transports, key)
+	return transport.pgn, transport.data[:transport.size], true
+}
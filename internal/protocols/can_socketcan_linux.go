@@ -0,0 +1,78 @@
+//go:build linux && (amd64 || arm || arm64 || riscv64)
+
+package protocols
+
+import (
+	"fmt"
+	"net"
+	"os"
+	"syscall"
+	"time"
+	"unsafe"
+)
+
+// canRaw is the CAN_RAW protocol of AF_CAN sockets
+const canRaw = 1
+
+// sockaddrCAN is struct sockaddr_can
+type sockaddrCAN struct {
+	family  uint16
+	_       uint16
+	ifindex int32
+	_       [16]byte
+}
+
+// SocketCAN is a raw CAN socket bound to a network interface
+type SocketCAN struct {
+	file *os.File
+}
+
+// OpenSocketCAN opens a raw socket receiving all frames of a CAN
+// interface, e.g. "can0"
+func OpenSocketCAN(name string) (*SocketCAN, error) {
+	iface, err := net.InterfaceByName(name)
+	if err != nil {
+		return nil, fmt.Errorf("CAN interface %s: %w", name, err)
+	}
+
+	fd, err := syscall.Socket(syscall.AF_CAN, syscall.SOCK_RAW, canRaw)
+	if err != nil {
+		return nil, fmt.Errorf("failed to open CAN socket: %w", err)
+	}
+
+	address := sockaddrCAN{family: syscall.AF_CAN, ifindex: int32(iface.Index)}
+	_, _, errno := syscall.Syscall(syscall.SYS_BIND, uintptr(fd), uintptr(unsafe.Pointer(&address)), unsafe.Sizeof(address))
+	if errno != 0 {
+		syscall.Close(fd)
+		return nil, fmt.Errorf("failed to bind CAN socket to %s: %w", name, errno)
+	}
+
+	// A non-blocking descriptor is served by the runtime poller, so Close
+	// interrupts a pending read
+	if err := syscall.SetNonblock(fd, true); err != nil {
+		syscall.Close(fd)
+		return nil, err
+	}
+	return &SocketCAN{file: os.NewFile(uintptr(fd), name)}, nil
+}
+
+// ReadFrame reads the next frame. Each read of a raw socket returns one
+// frame; error frames are not delivered unless enabled with a filter.
+func (s *SocketCAN) ReadFrame() (*CANFrame, error) {
+	buffer := make([]byte, CANFrameSize)
+	n, err := s.file.Read(buffer)
+	if err != nil {
+		return nil, err
+	}
+	frame, err := ParseCANFrame(buffer[:n])
+	if err != nil {
+		return nil, err
+	}
+	frame.Timestamp = time.Now()
+	return frame, nil
+}
+
+// Close closes the socket
+func (s *SocketCAN) Close() error {
+	return s.file.Close()
+}
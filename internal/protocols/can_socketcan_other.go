@@ -0,0 +1,26 @@
+//go:build !linux || !(amd64 || arm || arm64 || riscv64)
+
+package protocols
+
+import (
+	"fmt"
+	"runtime"
+)
+
+// SocketCAN is a raw CAN socket bound to a network interface
+type SocketCAN struct{}
+
+// OpenSocketCAN fails; SocketCAN is only available on Linux
+func OpenSocketCAN(name string) (*SocketCAN, error) {
+	return nil, fmt.Errorf("SocketCAN is not supported on %s/%s", runtime.GOOS, runtime.GOARCH)
+}
+
+// ReadFrame reads the next frame
+func (s *SocketCAN) ReadFrame() (*CANFrame, error) {
+	return nil, fmt.Errorf("SocketCAN is not supported on %s/%s", runtime.GOOS, runtime.GOARCH)
+}
+
+// Close closes the socket
+func (s *SocketCAN) Close() error {
+	return nil
+}
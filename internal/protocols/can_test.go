@@ -0,0 +1,323 @@
+package protocols
+
+import (
+	"os"
+	"testing"
+	"time"
+
+	"github.com/stretchr/testify/assert"
+	"go.uber.org/zap"
+)
+
+func TestCANFrame_RoundTrip(t *testing.T) {
+	frame := &CANFrame{ID: 0x0CF00400, Extended: true, Data: []byte{0xF1, 0xFF, 0xA0, 0xE0, 0x2E}}
+	raw := AppendCANFrame(nil, frame)
+	assert.Len(t, raw, CANFrameSize)
+
+	parsed, err := ParseCANFrame(raw)
+	assert.NoError(t, err)
+	assert.Equal(t, frame, parsed)
+	assert.Equal(t, "0CF00400#F1FFA0E02E", parsed.String())
+
+	remote := &CANFrame{ID: 0x705, Remote: true}
+	parsed, err = ParseCANFrame(AppendCANFrame(nil, remote))
+	assert.NoError(t, err)
+	assert.Equal(t, remote, parsed)
+
+	// Error frames
+	raw[3] |= 0x20
+	_, err = ParseCANFrame(raw)
+	assert.Error(t, err)
+}
+
+func TestParseCandumpLine(t *testing.T) {
+	frame, err := ParseCandumpLine("(1600000000.500000) can0 18FEEE00#FE7DFFFFFFFFFFFF")
+	assert.NoError(t, err)
+	assert.Equal(t, uint32(0x18FEEE00), frame.ID)
+	assert.True(t, frame.Extended)
+	assert.Equal(t, []byte{0xFE, 0x7D, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF}, frame.Data)
+	assert.Equal(t, time.Unix(1600000000, 500000000).UTC(), frame.Timestamp)
+
+	frame, err = ParseCandumpLine("705#05")
+	assert.NoError(t, err)
+	assert.Equal(t, &CANFrame{ID: 0x705, Data: []byte{0x05}}, frame)
+
+	frame, err = ParseCandumpLine("vcan0 605#R")
+	assert.NoError(t, err)
+	assert.True(t, frame.Remote)
+
+	for _, line := range []string{"", "can0 18FEEE00", "800#00", "705#0", "705#001122334455667788", "GGG#00"} {
+		_, err := ParseCandumpLine(line)
+		assert.Error(t, err, line)
+	}
+}
+
+func TestJ1939ID(t *testing.T) {
+	id := ParseJ1939ID(0x0CF00400)
+	assert.Equal(t, J1939ID{Priority: 3, PGN: 61444, Source: 0x00, Destination: J1939GlobalAddress}, id)
+	assert.Equal(t, uint32(0x0CF00400), id.CANID())
+
+	// PDU1 groups carry a destination instead of their low PGN byte
+	id = ParseJ1939ID(0x18EA00F9)
+	assert.Equal(t, J1939ID{Priority: 6, PGN: J1939PGNRequest, Source: 0xF9, Destination: 0x00}, id)
+	assert.Equal(t, uint32(0x18EA00F9), id.CANID())
+}
+
+func TestCANDecoder_J1939(t *testing.T) {
+	decoder := NewCANDecoder()
+
+	// EEC1: driver's demand torque not available, actual torque 35%,
+	// engine speed 1500 rpm
+	signals := decoder.Decode(&CANFrame{
+		ID:       0x0CF00400,
+		Extended: true,
+		Data:     []byte{0xF1, 0xFF, 0xA0, 0xE0, 0x2E, 0xFF, 0xFF, 0xFF},
+	})
+	if assert.Len(t, signals, 2) {
+		assert.Equal(t, "J1939:513@0", signals[0].Address)
+		assert.Equal(t, 35.0, signals[0].Value)
+		assert.Equal(t, "%", signals[0].Unit)
+		assert.Equal(t, "J1939:190@0", signals[1].Address)
+		assert.Equal(t, 1500.0, signals[1].Value)
+		assert.Equal(t, QualityGood, signals[1].Quality)
+	}
+
+	// ET1: coolant temperature sensor in error, the rest not available
+	signals = decoder.Decode(&CANFrame{
+		ID:       0x18FEEE00,
+		Extended: true,
+		Data:     []byte{0xFE, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF},
+	})
+	if assert.Len(t, signals, 1) {
+		assert.Equal(t, "J1939:110@0", signals[0].Address)
+		assert.Nil(t, signals[0].Value)
+		assert.Equal(t, QualityBad, signals[0].Quality)
+	}
+
+	// Standard identifiers are not J1939
+	assert.Empty(t, decoder.Decode(&CANFrame{ID: 0x400, Data: []byte{0xE0, 0x2E}}))
+}
+
+func TestCANDecoder_J1939Transport(t *testing.T) {
+	decoder := NewCANDecoder()
+	decoder.AddSPN(J1939SPN{SPN: 520192, Name: "Boom Angle", PGN: 0xFF00, StartBit: 64, Length: 16, Scale: 0.1, Unit: "deg"})
+
+	announce := J1939ID{Priority: 7, PGN: J1939PGNTPConnection, Source: 0x21, Destination: J1939GlobalAddress}
+	transfer := J1939ID{Priority: 7, PGN: J1939PGNTPData, Source: 0x21, Destination: J1939GlobalAddress}
+
+	// A broadcast of 10 bytes of PGN 0xFF00 in two packets
+	assert.Empty(t, decoder.Decode(&CANFrame{
+		ID:       announce.CANID(),
+		Extended: true,
+		Data:     []byte{j1939TPBroadcast, 10, 0, 2, 0xFF, 0x00, 0xFF, 0x00},
+	}))
+	assert.Empty(t, decoder.Decode(&CANFrame{
+		ID:       transfer.CANID(),
+		Extended: true,
+		Data:     []byte{1, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF},
+	}))
+	signals := decoder.Decode(&CANFrame{
+		ID:       transfer.CANID(),
+		Extended: true,
+		Data:     []byte{2, 0xFF, 0xE8, 0x03, 0xFF, 0xFF, 0xFF, 0xFF},
+	})
+	if assert.Len(t, signals, 1) {
+		assert.Equal(t, "J1939:520192@33", signals[0].Address)
+		assert.InDelta(t, 100.0, signals[0].Value, 1e-9)
+	}
+
+	// Packets out of sequence drop the session
+	decoder.Decode(&CANFrame{ID: announce.CANID(), Extended: true, Data: []byte{j1939TPBroadcast, 10, 0, 2, 0xFF, 0x00, 0xFF, 0x00}})
+	assert.Empty(t, decoder.Decode(&CANFrame{ID: transfer.CANID(), Extended: true, Data: []byte{2, 0xFF, 0xE8, 0x03, 0xFF, 0xFF, 0xFF, 0xFF}}))
+	assert.Empty(t, decoder.Decode(&CANFrame{ID: transfer.CANID(), Extended: true, Data: []byte{1, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF}}))
+}
+
+func TestCANDecoder_CANopen(t *testing.T) {
+	decoder := NewCANDecoder()
+	assert.NoError(t, decoder.MapPDO(0x185, []CANopenMapping{
+		{Index: 0x6041, SubIndex: 0, Type: CANopenUnsigned16},
+		{Index: 0x6064, SubIndex: 0, Type: CANopenInteger32},
+	}))
+	decoder.SetCANopenType(5, 0x1008, 0, CANopenVisibleString)
+
+	// TPDO1 with the statusword and the actual position
+	signals := decoder.Decode(&CANFrame{ID: 0x185, Data: []byte{0x37, 0x06, 0x18, 0xFC, 0xFF, 0xFF}})
+	if assert.Len(t, signals, 2) {
+		assert.Equal(t, "CANOPEN:5:6041.00", signals[0].Address)
+		assert.Equal(t, uint64(0x0637), signals[0].Value)
+		assert.Equal(t, "CANOPEN:5:6064.00", signals[1].Address)
+		assert.Equal(t, int64(-1000), signals[1].Value)
+		assert.Equal(t, DataTypeInt64, signals[1].DataType)
+	}
+
+	// Expedited upload of 2 bytes, typed by the PDO mapping
+	signals = decoder.Decode(&CANFrame{ID: 0x585, Data: []byte{0x4B, 0x41, 0x60, 0x00, 0x40, 0x02, 0x00, 0x00}})
+	if assert.Len(t, signals, 1) {
+		assert.Equal(t, "CANOPEN:5:6041.00", signals[0].Address)
+		assert.Equal(t, uint64(0x0240), signals[0].Value)
+	}
+
+	// Segmented upload of the device name
+	assert.Empty(t, decoder.Decode(&CANFrame{ID: 0x605, Data: []byte{0x40, 0x08, 0x10, 0x00, 0x00, 0x00, 0x00, 0x00}}))
+	assert.Empty(t, decoder.Decode(&CANFrame{ID: 0x585, Data: []byte{0x41, 0x08, 0x10, 0x00, 0x0A, 0x00, 0x00, 0x00}}))
+	assert.Empty(t, decoder.Decode(&CANFrame{ID: 0x585, Data: []byte{0x00, 'B', 'i', 'f', 'r', 'o', 's', 't'}}))
+	signals = decoder.Decode(&CANFrame{ID: 0x585, Data: []byte{0x19, '-', 'I', 'O', 0x00, 0x00, 0x00, 0x00}})
+	if assert.Len(t, signals, 1) {
+		assert.Equal(t, "CANOPEN:5:1008.00", signals[0].Address)
+		assert.Equal(t, "Bifrost-IO", signals[0].Value)
+	}
+
+	// Expedited download, reported once confirmed
+	assert.Empty(t, decoder.Decode(&CANFrame{ID: 0x605, Data: []byte{0x2B, 0x40, 0x60, 0x00, 0x0F, 0x00, 0x00, 0x00}}))
+	signals = decoder.Decode(&CANFrame{ID: 0x585, Data: []byte{0x60, 0x40, 0x60, 0x00, 0x00, 0x00, 0x00, 0x00}})
+	if assert.Len(t, signals, 1) {
+		assert.Equal(t, "CANOPEN:5:6040.00", signals[0].Address)
+		assert.Equal(t, uint64(0x0F), signals[0].Value)
+	}
+
+	// Abort
+	signals = decoder.Decode(&CANFrame{ID: 0x585, Data: []byte{0x80, 0x00, 0x10, 0x00, 0x00, 0x00, 0x02, 0x06}})
+	if assert.Len(t, signals, 1) {
+		assert.Equal(t, "CANOPEN:5:1000.00", signals[0].Address)
+		assert.Equal(t, QualityBad, signals[0].Quality)
+	}
+
+	// Heartbeat and emergency
+	signals = decoder.Decode(&CANFrame{ID: 0x705, Data: []byte{0x05}})
+	if assert.Len(t, signals, 1) {
+		assert.Equal(t, "CANOPEN:5:NMT", signals[0].Address)
+		assert.Equal(t, "OPERATIONAL", signals[0].Value)
+	}
+	signals = decoder.Decode(&CANFrame{ID: 0x085, Data: []byte{0x10, 0x81, 0x11, 0x00, 0x00, 0x00, 0x00, 0x00}})
+	if assert.Len(t, signals, 2) {
+		assert.Equal(t, "CANOPEN:5:EMCY", signals[0].Address)
+		assert.Equal(t, uint16(0x8110), signals[0].Value)
+		assert.Equal(t, "CANOPEN:5:1001.00", signals[1].Address)
+		assert.Equal(t, uint64(0x11), signals[1].Value)
+	}
+
+	// SYNC and unmapped PDOs carry no signals
+	assert.Empty(t, decoder.Decode(&CANFrame{ID: 0x080}))
+	assert.Empty(t, decoder.Decode(&CANFrame{ID: 0x285, Data: []byte{0x01}}))
+
+	assert.Error(t, decoder.MapPDO(0x285, []CANopenMapping{{Index: 0x1008, Type: CANopenVisibleString}}))
+}
+
+func TestParseCANAddress(t *testing.T) {
+	valid := map[string]string{
+		"J1939:190":        "J1939:190",
+		"j1939:190@0":      "J1939:190@0",
+		"J1939:84@0x17":    "J1939:84@23",
+		"CANOPEN:5:6064.0": "CANOPEN:5:6064.00",
+		"canopen:5:nmt":    "CANOPEN:5:NMT",
+		"CANopen:127:EMCY": "CANOPEN:127:EMCY",
+	}
+	for text, expected := range valid {
+		address, err := parseCANAddress(text)
+		assert.NoError(t, err, text)
+		assert.Equal(t, expected, address, text)
+	}
+
+	for _, text := range []string{"", "190", "J1939:", "J1939:190@300", "CANOPEN:0:NMT", "CANOPEN:128:NMT", "CANOPEN:5:6064", "CANOPEN:5:10000.00", "MODBUS:1"} {
+		_, err := parseCANAddress(text)
+		assert.Error(t, err, text)
+	}
+}
+
+// canTestBus feeds frames to a handler
+type canTestBus struct {
+	frames chan *CANFrame
+	closed chan struct{}
+}
+
+func (b *canTestBus) ReadFrame() (*CANFrame, error) {
+	select {
+	case frame := <-b.frames:
+		return frame, nil
+	case <-b.closed:
+		return nil, os.ErrClosed
+	}
+}
+
+func (b *canTestBus) Close() error {
+	close(b.closed)
+	return nil
+}
+
+func TestCANHandler(t *testing.T) {
+	bus := &canTestBus{frames: make(chan *CANFrame), closed: make(chan struct{})}
+	handler := NewCANHandler(zap.NewNop()).(*CANHandler)
+	handler.openBus = func(name string) (CANBus, error) {
+		assert.Equal(t, "can0", name)
+		return bus, nil
+	}
+
+	device := &Device{
+		ID:       "excavator",
+		Protocol: "can",
+		Address:  "can0",
+		Config: map[string]interface{}{
+			"pdos": map[string]interface{}{
+				"0x185": []interface{}{"6041.00:UNSIGNED16", "6064.00:INTEGER32"},
+			},
+			"spns": []interface{}{
+				map[string]interface{}{"spn": 520192, "pgn": 0xFF00, "start_bit": 0, "length": 16, "scale": 0.1, "unit": "deg"},
+			},
+		},
+	}
+	assert.NoError(t, handler.Connect(device))
+	assert.True(t, handler.IsConnected(device))
+	assert.Error(t, handler.Ping(device))
+
+	bus.frames <- &CANFrame{ID: 0x0CF00400, Extended: true, Data: []byte{0xF1, 0xFF, 0xA0, 0xE0, 0x2E, 0xFF, 0xFF, 0xFF}}
+	bus.frames <- &CANFrame{ID: 0x18FF0021, Extended: true, Data: []byte{0xE8, 0x03}}
+	bus.frames <- &CANFrame{ID: 0x185, Data: []byte{0x37, 0x06, 0x18, 0xFC, 0xFF, 0xFF}}
+	bus.frames <- &CANFrame{ID: 0x705, Data: []byte{0x7F}}
+
+	assert.Eventually(t, func() bool {
+		diagnostics, err := handler.GetDiagnostics(device)
+		return err == nil && diagnostics.ProtocolDiagnostics.(map[string]interface{})["frames"] == uint64(4)
+	}, time.Second, 10*time.Millisecond)
+
+	speed := &Tag{ID: "speed", Address: "J1939:190"}
+	value, err := handler.ReadTag(device, speed)
+	assert.NoError(t, err)
+	assert.Equal(t, 1500.0, value)
+	assert.Equal(t, QualityGood, speed.Quality)
+
+	value, err = handler.ReadTag(device, &Tag{Address: "J1939:520192@33"})
+	assert.NoError(t, err)
+	assert.InDelta(t, 100.0, value, 1e-9)
+
+	_, err = handler.ReadTag(device, &Tag{Address: "J1939:190@1"})
+	assert.Error(t, err)
+
+	results, err := handler.ReadMultipleTags(device, []*Tag{
+		{ID: "position", Address: "CANOPEN:5:6064.00"},
+		{ID: "state", Address: "CANOPEN:5:NMT"},
+		{ID: "missing", Address: "CANOPEN:6:NMT"},
+	})
+	assert.NoError(t, err)
+	assert.Equal(t, map[string]interface{}{"position": int64(-1000), "state": "PRE_OPERATIONAL"}, results)
+
+	tags, err := handler.ReadSignals(device)
+	assert.NoError(t, err)
+	assert.Len(t, tags, 6)
+
+	assert.Error(t, handler.WriteTag(device, speed, 1000.0))
+	assert.NoError(t, handler.ValidateTagAddress("CANOPEN:5:6041.00"))
+	assert.Error(t, handler.ValidateTagAddress("CANOPEN:5"))
+	assert.NoError(t, handler.Ping(device))
+
+	diagnostics, err := handler.GetDiagnostics(device)
+	assert.NoError(t, err)
+	assert.True(t, diagnostics.IsHealthy)
+	assert.Equal(t, 1.0, diagnostics.SuccessRate)
+
+	assert.NoError(t, handler.Disconnect(device))
+	assert.False(t, handler.IsConnected(device))
+
+	// Invalid PDO mappings are rejected before the bus is opened
+	device.Config["pdos"] = map[string]interface{}{"0x185": []interface{}{"6041.00:STRING"}}
+	assert.Error(t, handler.Connect(device))
+}
@@ -0,0 +1,259 @@
+package protocols
+
+import (
+	"bufio"
+	"context"
+	"encoding/json"
+	"fmt"
+	"io"
+	"os"
+	"sync"
+	"time"
+)
+
+// Traffic Capture
+//
+// A FrameRecorder set on a transport receives every frame the transport
+// sends or receives, stamped when it went over the wire. A CaptureWriter
+// stores the frames in a capture file, one JSON object per line, and
+// Replay reads a capture back and hands the frames to a decoder at the
+// recorded pace, accelerated, or as fast as possible. Traffic recorded at
+// a site can so be fed through the decoders in regression tests or when
+// analysing an incident.
+//
+// Devices are captured by setting "capture" in their configuration to the
+// path of the capture file; frames are appended to an existing file.
+
+// CaptureDirection tells whether a frame was sent or received
+type CaptureDirection string
+
+const (
+	CaptureSent     CaptureDirection = "sent"
+	CaptureReceived CaptureDirection = "received"
+)
+
+// Captured transports
+const (
+	CaptureTransportModbusTCP = "modbus-tcp"
+	CaptureTransportModbusRTU = "modbus-rtu"
+	CaptureTransportMQTT      = "mqtt"
+)
+
+// CapturedFrame is one frame sent or received by a transport
+type CapturedFrame struct {
+	Time      time.Time        `json:"time"`
+	Transport string           `json:"transport"`
+	Address   string           `json:"address"`         // Device, port or broker address
+	Topic     string           `json:"topic,omitempty"` // MQTT only
+	Direction CaptureDirection `json:"direction"`
+	Data      []byte           `json:"data"`
+}
+
+// FrameRecorder receives the frames of a transport. Data is only valid
+// during the call.
+type FrameRecorder interface {
+	RecordFrame(frame CapturedFrame)
+}
+
+// recordFrame passes a frame to recorder if there is one
+func recordFrame(recorder FrameRecorder, transport, address string, direction CaptureDirection, data []byte) {
+	if recorder == nil {
+		return
+	}
+	recorder.RecordFrame(CapturedFrame{
+		Time:      DefaultClock.Now(),
+		Transport: transport,
+		Address:   address,
+		Direction: direction,
+		Data:      data,
+	})
+}
+
+// CaptureWriter is a FrameRecorder writing frames to a capture file. Each
+// frame is written as it is recorded, so a capture is complete up to the
+// last frame even if the gateway crashes.
+type CaptureWriter struct {
+	mutex  sync.Mutex
+	closer io.Closer
+	writer io.Writer
+	frames uint64
+	err    error
+}
+
+// CreateCaptureFile opens the capture file at path for appending,
+// creating it if needed
+func CreateCaptureFile(path string) (*CaptureWriter, error) {
+	file, err := os.OpenFile(path, os.O_CREATE|os.O_WRONLY|os.O_APPEND, 0o644)
+	if err != nil {
+		return nil, err
+	}
+	writer := NewCaptureWriter(file)
+	writer.closer = file
+	return writer, nil
+}
+
+// NewCaptureWriter creates a CaptureWriter writing to w
+func NewCaptureWriter(w io.Writer) *CaptureWriter {
+	return &CaptureWriter{writer: w}
+}
+
+// RecordFrame writes a frame. The first write error stops the capture and
+// is returned by Err.
+func (c *CaptureWriter) RecordFrame(frame CapturedFrame) {
+	line, err := json.Marshal(frame)
+
+	c.mutex.Lock()
+	defer c.mutex.Unlock()
+	if c.err != nil {
+		return
+	}
+	if err == nil {
+		_, err = c.writer.Write(append(line, '\n'))
+	}
+	if err != nil {
+		c.err = err
+		return
+	}
+	c.frames++
+}
+
+// Frames returns the number of frames written
+func (c *CaptureWriter) Frames() uint64 {
+	c.mutex.Lock()
+	defer c.mutex.Unlock()
+	return c.frames
+}
+
+// Err returns the error that stopped the capture
+func (c *CaptureWriter) Err() error {
+	c.mutex.Lock()
+	defer c.mutex.Unlock()
+	return c.err
+}
+
+// Close closes the file
+func (c *CaptureWriter) Close() error {
+	if c.closer == nil {
+		return nil
+	}
+	return c.closer.Close()
+}
+
+// CaptureReader reads frames from a capture file
+type CaptureReader struct {
+	closer  io.Closer
+	decoder *json.Decoder
+}
+
+// OpenCaptureFile opens the capture file at path for reading
+func OpenCaptureFile(path string) (*CaptureReader, error) {
+	file, err := os.Open(path)
+	if err != nil {
+		return nil, err
+	}
+	reader := NewCaptureReader(file)
+	reader.closer = file
+	return reader, nil
+}
+
+// NewCaptureReader creates a CaptureReader reading from r
+func NewCaptureReader(r io.Reader) *CaptureReader {
+	return &CaptureReader{decoder: json.NewDecoder(bufio.NewReader(r))}
+}
+
+// Next returns the next frame, or io.EOF after the last one
+func (c *CaptureReader) Next() (CapturedFrame, error) {
+	var frame CapturedFrame
+	if err := c.decoder.Decode(&frame); err != nil {
+		if err == io.EOF {
+			return frame, err
+		}
+		return frame, fmt.Errorf("invalid capture: %w", err)
+	}
+	return frame, nil
+}
+
+// Close closes the file
+func (c *CaptureReader) Close() error {
+	if c.closer == nil {
+		return nil
+	}
+	return c.closer.Close()
+}
+
+// Replay passes the frames of a capture to handle in order. With speed 1
+// frames are handed over at the pace they were recorded, with speed 10 ten
+// times as fast and with speed 0 without waiting. It returns the number
+// of frames replayed and stops at the first error of handle.
+func Replay(ctx context.Context, reader *CaptureReader, speed float64, handle func(CapturedFrame) error) (int, error) {
+	var first time.Time
+	start := time.Now()
+	timer := time.NewTimer(0)
+	defer timer.Stop()
+	<-timer.C
+
+	replayed := 0
+	for {
+		frame, err := reader.Next()
+		if err == io.EOF {
+			return replayed, nil
+		}
+		if err != nil {
+			return replayed, err
+		}
+
+		if first.IsZero() {
+			first = frame.Time
+		}
+		if speed > 0 {
+			due := start.Add(time.Duration(float64(frame.Time.Sub(first)) / speed))
+			if wait := time.Until(due); wait > 0 {
+				timer.Reset(wait)
+				select {
+				case <-ctx.Done():
+					return replayed, ctx.Err()
+				case <-timer.C:
+				}
+			}
+		}
+		if err := ctx.Err(); err != nil {
+			return replayed, err
+		}
+
+		if err := handle(frame); err != nil {
+			return replayed, err
+		}
+		replayed++
+	}
+}
+
+// DecodeCapturedFrame decodes a frame without context: Modbus frames with
+// ParseModbusFrame and Sparkplug messages with UnmarshalSparkplugPayload.
+// Stateful decoding, e.g. of Sparkplug metric aliases, feeds the frames to
+// a SparkplugHost instead.
+func DecodeCapturedFrame(frame CapturedFrame) (interface{}, error) {
+	switch frame.Transport {
+	case CaptureTransportModbusTCP, CaptureTransportModbusRTU:
+		return ParseModbusFrame(frame.Data)
+	case CaptureTransportMQTT:
+		if _, err := ParseSparkplugTopic(frame.Topic); err != nil {
+			return nil, err
+		}
+		return UnmarshalSparkplugPayload(frame.Data)
+	}
+	return nil, fmt.Errorf("no decoder for transport %q", frame.Transport)
+}
+
+// openDeviceCapture opens the capture file set by the "capture" entry of
+// the device configuration, or returns nil without one
+func openDeviceCapture(device *Device) (*CaptureWriter, error) {
+	path, ok := device.Config["capture"].(string)
+	if !ok || path == "" {
+		return nil, nil
+	}
+	capture, err := CreateCaptureFile(path)
+	if err != nil {
+		return nil, fmt.Errorf("opening capture file: %w", err)
+	}
+	return capture, nil
+}
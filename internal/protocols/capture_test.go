@@ -0,0 +1,111 @@
+package protocols
+
+import (
+	"bytes"
+	"context"
+	"sort"
+	"sync"
+	"testing"
+	"time"
+
+	"github.com/goburrow/modbus"
+	"github.com/stretchr/testify/assert"
+	"go.uber.org/zap"
+)
+
+func TestCapture_RecordAndReplayModbusTCP(t *testing.T) {
+	address := startReorderingServer(t)
+
+	var buf bytes.Buffer
+	capture := NewCaptureWriter(&buf)
+	transport := NewModbusTCPTransport(address, zap.NewNop())
+	transport.Timeout = 2 * time.Second
+	transport.Recorder = capture
+	assert.NoError(t, transport.Connect())
+	defer transport.Close()
+
+	client := modbus.NewClient(transport)
+	var wg sync.WaitGroup
+	for _, start := range []uint16{0x0010, 0x0020} {
+		wg.Add(1)
+		go func(start uint16) {
+			defer wg.Done()
+			_, err := client.ReadHoldingRegisters(start, 1)
+			assert.NoError(t, err)
+		}(start)
+	}
+	wg.Wait()
+	assert.NoError(t, capture.Err())
+	assert.Equal(t, uint64(4), capture.Frames())
+
+	var directions []string
+	var registers []uint16
+	replayed, err := Replay(context.Background(), NewCaptureReader(&buf), 0, func(frame CapturedFrame) error {
+		assert.Equal(t, CaptureTransportModbusTCP, frame.Transport)
+		assert.Equal(t, address, frame.Address)
+		directions = append(directions, string(frame.Direction))
+
+		decoded, err := DecodeCapturedFrame(frame)
+		if err != nil {
+			return err
+		}
+		if parsed := decoded.(*ParsedModbusFrame); frame.Direction == CaptureReceived {
+			registers = append(registers, parsed.Registers...)
+		}
+		return nil
+	})
+	assert.NoError(t, err)
+	assert.Equal(t, 4, replayed)
+	sort.Strings(directions)
+	assert.Equal(t, []string{"received", "received", "sent", "sent"}, directions)
+	sort.Slice(registers, func(i, j int) bool { return registers[i] < registers[j] })
+	assert.Equal(t, []uint16{0x0010, 0x0020}, registers)
+}
+
+func TestReplay_Speed(t *testing.T) {
+	base := time.Unix(1700000000, 0)
+	var buf bytes.Buffer
+	capture := NewCaptureWriter(&buf)
+	for i := 0; i < 3; i++ {
+		capture.RecordFrame(CapturedFrame{Time: base.Add(time.Duration(i) * 200 * time.Millisecond), Transport: "test", Data: []byte{byte(i)}})
+	}
+	recorded := buf.Bytes()
+
+	// 400ms of traffic replayed ten times as fast
+	start := time.Now()
+	var data []byte
+	replayed, err := Replay(context.Background(), NewCaptureReader(bytes.NewReader(recorded)), 10, func(frame CapturedFrame) error {
+		data = append(data, frame.Data...)
+		return nil
+	})
+	assert.NoError(t, err)
+	assert.Equal(t, 3, replayed)
+	assert.Equal(t, []byte{0, 1, 2}, data)
+	assert.GreaterOrEqual(t, time.Since(start), 40*time.Millisecond)
+	assert.Less(t, time.Since(start), 400*time.Millisecond)
+
+	// At the original pace the replay is cancelled after the first frame
+	ctx, cancel := context.WithTimeout(context.Background(), 50*time.Millisecond)
+	defer cancel()
+	replayed, err = Replay(ctx, NewCaptureReader(bytes.NewReader(recorded)), 1, func(CapturedFrame) error { return nil })
+	assert.ErrorIs(t, err, context.DeadlineExceeded)
+	assert.Equal(t, 1, replayed)
+
+	_, err = Replay(context.Background(), NewCaptureReader(bytes.NewReader([]byte("{"))), 0, func(CapturedFrame) error { return nil })
+	assert.Error(t, err)
+}
+
+func TestDecodeCapturedFrame_Sparkplug(t *testing.T) {
+	payload := &SparkplugPayload{Timestamp: 1700000000000, Seq: 1, HasSeq: true}
+	data, err := payload.Marshal()
+	assert.NoError(t, err)
+
+	decoded, err := DecodeCapturedFrame(CapturedFrame{Transport: CaptureTransportMQTT, Topic: "spBv1.0/plant/NDATA/edge1", Data: data})
+	assert.NoError(t, err)
+	assert.Equal(t, uint64(1), decoded.(*SparkplugPayload).Seq)
+
+	_, err = DecodeCapturedFrame(CapturedFrame{Transport: CaptureTransportMQTT, Topic: "other/topic", Data: data})
+	assert.Error(t, err)
+	_, err = DecodeCapturedFrame(CapturedFrame{Transport: "opcua"})
+	assert.Error(t, err)
+}
@@ -0,0 +1,293 @@
+package protocols
+
+import (
+	"math"
+	"sync"
+	"time"
+)
+
+// Clock Sources
+//
+// HybridClock stamps values with monotonic time anchored to the wall clock.
+// The wall clock is compared with the monotonic clock on every check: small
+// differences are slewed out gradually so timestamps never run backwards,
+// while steps larger than the step threshold re-anchor the clock and are
+// reported as events, together with excessive drift.
+//
+// Components that expire or age data take a Clock instead of calling
+// time.Now, defaulting to DefaultClock. Tests and the simulator inject a
+// ManualClock and advance it instead of sleeping.
+
+// Clock returns the time used to stamp values
+type Clock interface {
+	Now() time.Time
+}
+
+// ClockEventType identifies a clock event
+type ClockEventType string
+
+const (
+	// ClockEventStep is reported when the wall clock jumped, e.g. when NTP
+	// stepped it
+	ClockEventStep ClockEventType = "step"
+	// ClockEventDrift is reported when the drift of the wall clock against
+	// the monotonic clock exceeds the drift threshold
+	ClockEventDrift ClockEventType = "drift"
+)
+
+// ClockEvent is a step or drift of the wall clock. Offset is the size of a
+// step, Drift the measured drift in parts per million.
+type ClockEvent struct {
+	Type   ClockEventType `json:"type"`
+	Time   time.Time      `json:"time"`
+	Offset time.Duration  `json:"offset"`
+	Drift  float64        `json:"drift_ppm"`
+}
+
+// ClockSync is how well the clock that stamped a value was synchronized
+// to a time source, e.g. by NTP or PTP
+type ClockSync string
+
+const (
+	ClockSyncSynchronized   ClockSync = "synchronized"
+	ClockSyncDegraded       ClockSync = "degraded" // Synchronized, but with an offset beyond tolerance
+	ClockSyncUnsynchronized ClockSync = "unsynchronized"
+	ClockSyncCorrected      ClockSync = "corrected" // Re-stamped after the clock was found wrong
+)
+
+// ClockStatus is the state of a HybridClock
+type ClockStatus struct {
+	Offset    time.Duration `json:"offset"`
+	Drift     float64       `json:"drift_ppm"`
+	Drifting  bool          `json:"drifting"`
+	Steps     int           `json:"steps"`
+	LastStep  time.Time     `json:"last_step,omitempty"`
+	LastCheck time.Time     `json:"last_check"`
+}
+
+// HybridClockConfig configures a HybridClock. Zero values use the defaults.
+type HybridClockConfig struct {
+	CheckInterval  time.Duration `yaml:"check_interval"`
+	StepThreshold  time.Duration `yaml:"step_threshold"`
+	MaxSlewRate    float64       `yaml:"max_slew_rate"`
+	DriftThreshold float64       `yaml:"drift_threshold_ppm"`
+}
+
+const (
+	defaultClockCheckInterval  = time.Second
+	defaultClockStepThreshold  = time.Second
+	defaultClockMaxSlewRate    = 500e-6 // 500 ppm, as ntpd
+	defaultClockDriftThreshold = 100    // ppm
+
+	// clockDriftSmoothing weighs new drift measurements
+	clockDriftSmoothing = 0.1
+)
+
+// DefaultClock stamps values read by the drivers and pollers
+var DefaultClock Clock = NewHybridClock(HybridClockConfig{})
+
+// ManualClock is a Clock that only moves when advanced
+type ManualClock struct {
+	mutex sync.Mutex
+	now   time.Time
+}
+
+// NewManualClock creates a clock standing at start
+func NewManualClock(start time.Time) *ManualClock {
+	return &ManualClock{now: start}
+}
+
+// Now returns the time the clock stands at
+func (c *ManualClock) Now() time.Time {
+	c.mutex.Lock()
+	defer c.mutex.Unlock()
+	return c.now
+}
+
+// Advance moves the clock forward by d and returns the new time
+func (c *ManualClock) Advance(d time.Duration) time.Time {
+	c.mutex.Lock()
+	defer c.mutex.Unlock()
+	c.now = c.now.Add(d)
+	return c.now
+}
+
+// Set moves the clock to t, which may be in the past
+func (c *ManualClock) Set(t time.Time) {
+	c.mutex.Lock()
+	defer c.mutex.Unlock()
+	c.now = t
+}
+
+// HybridClock is a Clock that is monotonic between wall clock steps
+type HybridClock struct {
+	config HybridClockConfig
+
+	// read returns the wall clock and the monotonic time since start
+	read func() (time.Time, time.Duration)
+
+	mutex      sync.Mutex
+	anchor     time.Duration // monotonic time of anchorWall
+	anchorWall time.Time
+	checkWall  time.Time
+	checkMono  time.Duration
+	last       time.Time
+	status     ClockStatus
+	onEvent    func(ClockEvent)
+}
+
+// NewHybridClock creates a clock anchored to the current wall clock
+func NewHybridClock(config HybridClockConfig) *HybridClock {
+	start := time.Now()
+	return newHybridClock(config, func() (time.Time, time.Duration) {
+		now := time.Now()
+		return now.Round(0), now.Sub(start)
+	})
+}
+
+func newHybridClock(config HybridClockConfig, read func() (time.Time, time.Duration)) *HybridClock {
+	if config.CheckInterval <= 0 {
+		config.CheckInterval = defaultClockCheckInterval
+	}
+	if config.StepThreshold <= 0 {
+		config.StepThreshold = defaultClockStepThreshold
+	}
+	if config.MaxSlewRate <= 0 {
+		config.MaxSlewRate = defaultClockMaxSlewRate
+	}
+	if config.DriftThreshold <= 0 {
+		config.DriftThreshold = defaultClockDriftThreshold
+	}
+
+	wall, mono := read()
+	return &HybridClock{
+		config:     config,
+		read:       read,
+		anchor:     mono,
+		anchorWall: wall,
+		checkWall:  wall,
+		checkMono:  mono,
+		status:     ClockStatus{LastCheck: wall},
+	}
+}
+
+// OnEvent sets the function called with step and drift events. It is called
+// from Now and Check and must not call the clock.
+func (c *HybridClock) OnEvent(fn func(ClockEvent)) {
+	c.mutex.Lock()
+	defer c.mutex.Unlock()
+	c.onEvent = fn
+}
+
+// Now returns the current time. It does not run backwards unless the wall
+// clock was stepped back.
+func (c *HybridClock) Now() time.Time {
+	wall, mono := c.read()
+
+	c.mutex.Lock()
+	var events []ClockEvent
+	if mono-c.checkMono >= c.config.CheckInterval {
+		events = c.check(wall, mono)
+	}
+	now := c.at(mono)
+	if now.Before(c.last) {
+		now = c.last
+	}
+	c.last = now
+	onEvent := c.onEvent
+	c.mutex.Unlock()
+
+	c.emit(onEvent, events)
+	return now
+}
+
+// Check compares the wall clock with the monotonic clock now. Now checks
+// at the check interval by itself.
+func (c *HybridClock) Check() ClockStatus {
+	wall, mono := c.read()
+
+	c.mutex.Lock()
+	events := c.check(wall, mono)
+	status := c.status
+	onEvent := c.onEvent
+	c.mutex.Unlock()
+
+	c.emit(onEvent, events)
+	return status
+}
+
+// Status returns the offset, drift and steps seen at the last check
+func (c *HybridClock) Status() ClockStatus {
+	c.mutex.Lock()
+	defer c.mutex.Unlock()
+	return c.status
+}
+
+// Monotonic returns the time elapsed since the clock was created. Unlike
+// differences between timestamps it is not affected by wall clock steps.
+func (c *HybridClock) Monotonic() time.Duration {
+	_, mono := c.read()
+	return mono
+}
+
+func (c *HybridClock) at(mono time.Duration) time.Time {
+	return c.anchorWall.Add(mono - c.anchor)
+}
+
+// check measures the wall clock against the monotonic clock since the last
+// check, then follows a step or slews towards the wall clock
+func (c *HybridClock) check(wall time.Time, mono time.Duration) []ClockEvent {
+	elapsed := mono - c.checkMono
+	jump := wall.Sub(c.checkWall) - elapsed
+	c.checkWall = wall
+	c.checkMono = mono
+	c.status.LastCheck = wall
+	if elapsed <= 0 {
+		return nil
+	}
+
+	if jump >= c.config.StepThreshold || jump <= -c.config.StepThreshold {
+		c.anchor = mono
+		c.anchorWall = wall
+		c.last = time.Time{}
+		c.status.Offset = 0
+		c.status.Steps++
+		c.status.LastStep = wall
+		return []ClockEvent{{Type: ClockEventStep, Time: wall, Offset: jump, Drift: c.status.Drift}}
+	}
+
+	drift := float64(jump) / float64(elapsed) * 1e6
+	c.status.Drift += clockDriftSmoothing * (drift - c.status.Drift)
+
+	// Slew at most MaxSlewRate of the elapsed time towards the wall clock
+	offset := wall.Sub(c.at(mono))
+	limit := time.Duration(c.config.MaxSlewRate * float64(elapsed))
+	correction := offset
+	if correction > limit {
+		correction = limit
+	} else if correction < -limit {
+		correction = -limit
+	}
+	c.anchorWall = c.at(mono).Add(correction)
+	c.anchor = mono
+	c.status.Offset = offset - correction
+
+	drifting := math.Abs(c.status.Drift) > c.config.DriftThreshold
+	if drifting == c.status.Drifting {
+		return nil
+	}
+	c.status.Drifting = drifting
+	if !drifting {
+		return nil
+	}
+	return []ClockEvent{{Type: ClockEventDrift, Time: wall, Offset: c.status.Offset, Drift: c.status.Drift}}
+}
+
+func (c *HybridClock) emit(onEvent func(ClockEvent), events []ClockEvent) {
+	if onEvent == nil {
+		return
+	}
+	for _, event := range events {
+		onEvent(event)
+	}
+}
@@ -0,0 +1,111 @@
+package protocols
+
+import (
+	"testing"
+	"time"
+
+	"github.com/stretchr/testify/assert"
+)
+
+// clockTestSource is a system clock whose wall and monotonic time are
+// advanced separately
+type clockTestSource struct {
+	wall time.Time
+	mono time.Duration
+}
+
+func (s *clockTestSource) read() (time.Time, time.Duration) {
+	return s.wall, s.mono
+}
+
+func (s *clockTestSource) advance(wall, mono time.Duration) {
+	s.wall = s.wall.Add(wall)
+	s.mono += mono
+}
+
+func newTestHybridClock() (*HybridClock, *clockTestSource, *[]ClockEvent) {
+	source := &clockTestSource{wall: time.Date(2024, 1, 1, 0, 0, 0, 0, time.UTC)}
+	clock := newHybridClock(HybridClockConfig{}, source.read)
+	events := &[]ClockEvent{}
+	clock.OnEvent(func(event ClockEvent) {
+		*events = append(*events, event)
+	})
+	return clock, source, events
+}
+
+func TestHybridClock_FollowsWallClock(t *testing.T) {
+	clock, source, events := newTestHybridClock()
+	start := clock.Now()
+	assert.Equal(t, source.wall, start)
+
+	for i := 0; i < 5; i++ {
+		source.advance(time.Second, time.Second)
+		assert.Equal(t, source.wall, clock.Now())
+	}
+	assert.Empty(t, *events)
+	assert.Equal(t, time.Duration(0), clock.Status().Offset)
+	assert.Equal(t, 5*time.Second, clock.Monotonic())
+}
+
+func TestHybridClock_Step(t *testing.T) {
+	clock, source, events := newTestHybridClock()
+	clock.Now()
+
+	// NTP steps the clock forward by an hour
+	source.advance(time.Hour+time.Second, time.Second)
+	assert.Equal(t, source.wall, clock.Now())
+	if assert.Len(t, *events, 1) {
+		assert.Equal(t, ClockEventStep, (*events)[0].Type)
+		assert.Equal(t, time.Hour, (*events)[0].Offset)
+	}
+
+	// Steps back are followed as well
+	source.advance(-time.Minute, time.Second)
+	assert.Equal(t, source.wall, clock.Now())
+	if assert.Len(t, *events, 2) {
+		assert.Equal(t, -time.Minute-time.Second, (*events)[1].Offset)
+	}
+
+	status := clock.Status()
+	assert.Equal(t, 2, status.Steps)
+	assert.Equal(t, source.wall, status.LastStep)
+}
+
+func TestHybridClock_SlewsSmallOffsets(t *testing.T) {
+	clock, source, events := newTestHybridClock()
+	start := clock.Now()
+
+	// A 100ms adjustment is slewed at 500 ppm instead of jumping
+	source.advance(time.Second+100*time.Millisecond, time.Second)
+	now := clock.Now()
+	assert.Equal(t, start.Add(time.Second+500*time.Microsecond), now)
+	assert.Equal(t, 100*time.Millisecond-500*time.Microsecond, clock.Status().Offset)
+	if assert.Len(t, *events, 1) {
+		assert.Equal(t, ClockEventDrift, (*events)[0].Type)
+	}
+
+	// Between checks timestamps advance with the monotonic clock
+	source.advance(-50*time.Millisecond, 100*time.Millisecond)
+	next := clock.Now()
+	assert.Equal(t, now.Add(100*time.Millisecond), next)
+
+	status := clock.Check()
+	assert.Equal(t, 0, status.Steps)
+	assert.True(t, status.Drifting)
+
+	// Slewing back holds the time instead of running backwards
+	assert.Equal(t, next, clock.Now())
+}
+
+func TestManualClock(t *testing.T) {
+	start := time.Date(2024, 1, 1, 0, 0, 0, 0, time.UTC)
+	clock := NewManualClock(start)
+	assert.Equal(t, start, clock.Now())
+	assert.Equal(t, start, clock.Now())
+
+	assert.Equal(t, start.Add(time.Minute), clock.Advance(time.Minute))
+	assert.Equal(t, start.Add(time.Minute), clock.Now())
+
+	clock.Set(start)
+	assert.Equal(t, start, clock.Now())
+}
@@ -0,0 +1,196 @@
+package protocols
+
+import (
+	"fmt"
+	"path"
+	"strings"
+)
+
+// Device Identity
+//
+// DeviceID is the canonical identity of a device: a hierarchical name such
+// as "site/line/cell/device" whose segments are namespaces. DeviceIDPattern
+// selects devices with globs per segment, e.g. "plant1/*/press-?" or
+// "plant1/**" for everything below a namespace.
+
+const (
+	// DeviceIDSeparator separates the namespaces of a device ID
+	DeviceIDSeparator = "/"
+	// MaxDeviceIDLength is the maximum length of a device ID
+	MaxDeviceIDLength = 255
+	// MaxDeviceIDSegments is the maximum namespace depth of a device ID
+	MaxDeviceIDSegments = 8
+)
+
+// DeviceID is a validated device identity. It is comparable and can be used
+// as a map key; its text form is used in JSON and YAML.
+type DeviceID string
+
+// ParseDeviceID validates a device ID. Segments are non-empty and consist
+// of letters, digits, '-', '_', '.' and ':'.
+func ParseDeviceID(id string) (DeviceID, error) {
+	if id == "" {
+		return "", fmt.Errorf("device ID must not be empty")
+	}
+	if len(id) > MaxDeviceIDLength {
+		return "", fmt.Errorf("device ID longer than %d characters", MaxDeviceIDLength)
+	}
+
+	segments := strings.Split(id, DeviceIDSeparator)
+	if len(segments) > MaxDeviceIDSegments {
+		return "", fmt.Errorf("device ID %q has more than %d segments", id, MaxDeviceIDSegments)
+	}
+	for _, segment := range segments {
+		if err := validateDeviceIDSegment(segment, false); err != nil {
+			return "", fmt.Errorf("device ID %q: %w", id, err)
+		}
+	}
+	return DeviceID(id), nil
+}
+
+// MustParseDeviceID parses a device ID and panics if it is invalid
+func MustParseDeviceID(id string) DeviceID {
+	deviceID, err := ParseDeviceID(id)
+	if err != nil {
+		panic(err)
+	}
+	return deviceID
+}
+
+func validateDeviceIDSegment(segment string, pattern bool) error {
+	if segment == "" {
+		return fmt.Errorf("empty segment")
+	}
+	if segment == "." || segment == ".." {
+		return fmt.Errorf("invalid segment %q", segment)
+	}
+	for _, r := range segment {
+		switch {
+		case r >= 'a' && r <= 'z', r >= 'A' && r <= 'Z', r >= '0' && r <= '9':
+		case r == '-' || r == '_' || r == '.' || r == ':':
+		case pattern && (r == '*' || r == '?' || r == '[' || r == ']' || r == '^'):
+		default:
+			return fmt.Errorf("invalid character %q in segment %q", r, segment)
+		}
+	}
+	return nil
+}
+
+// String returns the ID
+func (id DeviceID) String() string {
+	return string(id)
+}
+
+// Segments returns the namespaces of the ID followed by the device name
+func (id DeviceID) Segments() []string {
+	if id == "" {
+		return nil
+	}
+	return strings.Split(string(id), DeviceIDSeparator)
+}
+
+// Name returns the last segment of the ID
+func (id DeviceID) Name() string {
+	return string(id)[strings.LastIndex(string(id), DeviceIDSeparator)+1:]
+}
+
+// Namespace returns the ID without its last segment, "" for top-level IDs
+func (id DeviceID) Namespace() DeviceID {
+	index := strings.LastIndex(string(id), DeviceIDSeparator)
+	if index < 0 {
+		return ""
+	}
+	return id[:index]
+}
+
+// Child returns the ID of a device in the namespace id
+func (id DeviceID) Child(name string) (DeviceID, error) {
+	if id == "" {
+		return ParseDeviceID(name)
+	}
+	return ParseDeviceID(string(id) + DeviceIDSeparator + name)
+}
+
+// IsWithin reports whether the ID is in namespace, directly or nested
+func (id DeviceID) IsWithin(namespace DeviceID) bool {
+	return namespace == "" || strings.HasPrefix(string(id), string(namespace)+DeviceIDSeparator)
+}
+
+// MarshalText implements encoding.TextMarshaler
+func (id DeviceID) MarshalText() ([]byte, error) {
+	return []byte(id), nil
+}
+
+// UnmarshalText implements encoding.TextUnmarshaler and validates the ID
+func (id *DeviceID) UnmarshalText(text []byte) error {
+	parsed, err := ParseDeviceID(string(text))
+	if err != nil {
+		return err
+	}
+	*id = parsed
+	return nil
+}
+
+// DeviceIDPattern matches device IDs segment by segment. Segments are
+// path.Match globs; a "**" segment matches any number of segments.
+type DeviceIDPattern struct {
+	pattern  string
+	segments []string
+}
+
+// ParseDeviceIDPattern validates a pattern
+func ParseDeviceIDPattern(pattern string) (*DeviceIDPattern, error) {
+	if pattern == "" {
+		return nil, fmt.Errorf("device ID pattern must not be empty")
+	}
+
+	segments := strings.Split(pattern, DeviceIDSeparator)
+	for _, segment := range segments {
+		if segment == "**" {
+			continue
+		}
+		if err := validateDeviceIDSegment(segment, true); err != nil {
+			return nil, fmt.Errorf("device ID pattern %q: %w", pattern, err)
+		}
+		if _, err := path.Match(segment, ""); err != nil {
+			return nil, fmt.Errorf("device ID pattern %q: %w", pattern, err)
+		}
+	}
+	return &DeviceIDPattern{pattern: pattern, segments: segments}, nil
+}
+
+// String returns the pattern
+func (p *DeviceIDPattern) String() string {
+	return p.pattern
+}
+
+// Match reports whether id matches the pattern
+func (p *DeviceIDPattern) Match(id DeviceID) bool {
+	return matchDeviceIDSegments(p.segments, id.Segments())
+}
+
+// MatchString reports whether a device ID string matches the pattern
+func (p *DeviceIDPattern) MatchString(id string) bool {
+	return matchDeviceIDSegments(p.segments, strings.Split(id, DeviceIDSeparator))
+}
+
+func matchDeviceIDSegments(pattern, segments []string) bool {
+	for len(pattern) > 0 {
+		if pattern[0] == "**" {
+			for skip := 0; skip <= len(segments); skip++ {
+				if matchDeviceIDSegments(pattern[1:], segments[skip:]) {
+					return true
+				}
+			}
+			return false
+		}
+		if len(segments) == 0 {
+			return false
+		}
+		if matched, _ := path.Match(pattern[0], segments[0]); !matched {
+			return false
+		}
+		pattern, segments = pattern[1:], segments[1:]
+	}
+	return len(segments) == 0
+}
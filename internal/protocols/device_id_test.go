@@ -0,0 +1,81 @@
+package protocols
+
+import (
+	"encoding/json"
+	"strings"
+	"testing"
+
+	"github.com/stretchr/testify/assert"
+)
+
+func TestParseDeviceID(t *testing.T) {
+	id, err := ParseDeviceID("plant1/line-2/cell_3/press:01")
+	assert.NoError(t, err)
+	assert.Equal(t, []string{"plant1", "line-2", "cell_3", "press:01"}, id.Segments())
+	assert.Equal(t, "press:01", id.Name())
+	assert.Equal(t, DeviceID("plant1/line-2/cell_3"), id.Namespace())
+	assert.True(t, id.IsWithin("plant1"))
+	assert.True(t, id.IsWithin("plant1/line-2"))
+	assert.False(t, id.IsWithin("plant"))
+
+	plc := MustParseDeviceID("plc1")
+	assert.Equal(t, "plc1", plc.Name())
+	assert.Equal(t, DeviceID(""), plc.Namespace())
+
+	child, err := id.Namespace().Child("press:02")
+	assert.NoError(t, err)
+	assert.Equal(t, DeviceID("plant1/line-2/cell_3/press:02"), child)
+
+	for _, invalid := range []string{"", "/plc", "plc/", "a//b", "a/../b", "plc 1", "plc/*", strings.Repeat("a/", 8) + "a", strings.Repeat("a", 256)} {
+		_, err := ParseDeviceID(invalid)
+		assert.Error(t, err, invalid)
+	}
+}
+
+func TestDeviceID_JSON(t *testing.T) {
+	var decoded struct {
+		Device DeviceID `json:"device"`
+	}
+	assert.NoError(t, json.Unmarshal([]byte(`{"device":"site/plc1"}`), &decoded))
+	assert.Equal(t, DeviceID("site/plc1"), decoded.Device)
+	assert.Error(t, json.Unmarshal([]byte(`{"device":"site//plc1"}`), &decoded))
+
+	encoded, err := json.Marshal(decoded)
+	assert.NoError(t, err)
+	assert.JSONEq(t, `{"device":"site/plc1"}`, string(encoded))
+}
+
+func TestDeviceIDPattern(t *testing.T) {
+	tests := []struct {
+		pattern string
+		id      string
+		want    bool
+	}{
+		{"plant1/line1/plc1", "plant1/line1/plc1", true},
+		{"plant1/*/plc1", "plant1/line2/plc1", true},
+		{"plant1/*/plc1", "plant1/line2/cell1/plc1", false},
+		{"plant1/line?/plc[0-9]", "plant1/line3/plc7", true},
+		{"plant1/**", "plant1/line1/cell1/plc1", true},
+		{"plant1/**", "plant1", true},
+		{"plant1/**", "plant2/line1", false},
+		{"**/plc1", "plant1/line1/plc1", true},
+		{"**/plc1", "plc1", true},
+		{"plant1/**/plc*", "plant1/line1/cell2/plc3", true},
+		{"plant1/**/plc*", "plant1/line1/drive1", false},
+	}
+
+	for _, tt := range tests {
+		t.Run(tt.pattern+" "+tt.id, func(t *testing.T) {
+			pattern, err := ParseDeviceIDPattern(tt.pattern)
+			if assert.NoError(t, err) {
+				assert.Equal(t, tt.want, pattern.Match(DeviceID(tt.id)))
+				assert.Equal(t, tt.want, pattern.MatchString(tt.id))
+			}
+		})
+	}
+
+	for _, invalid := range []string{"", "plant1//plc", "plant1/[", "plant 1/*"} {
+		_, err := ParseDeviceIDPattern(invalid)
+		assert.Error(t, err, invalid)
+	}
+}
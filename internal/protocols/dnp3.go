@@ -0,0 +1,356 @@
+package protocols
+
+import (
+	"context"
+	"fmt"
+	"net"
+	"strconv"
+	"strings"
+	"sync"
+	"time"
+
+	"go.uber.org/zap"
+)
+
+// DNP3Handler implements ProtocolHandler for DNP3 outstations over TCP.
+// Tag addresses are "<type>:<index>" with type BI, BO, CT, AI or AO, e.g.
+// "AI:12". The config keys "master_address" and "outstation_address" set
+// the link addresses.
+type DNP3Handler struct {
+	logger      *zap.Logger
+	config      *DNP3Config
+	connections sync.Map // map[string]*DNP3Connection
+}
+
+// DNP3Connection is a master session with one outstation
+type DNP3Connection struct {
+	master    *DNP3Master
+	conn      net.Conn
+	createdAt time.Time
+
+	mutex    sync.RWMutex
+	lastUsed time.Time
+	requests uint64
+	errors   uint64
+}
+
+// DNP3Config holds DNP3-specific configuration
+type DNP3Config struct {
+	DefaultTimeout           time.Duration `yaml:"default_timeout"`
+	ConnectionTimeout        time.Duration `yaml:"connection_timeout"`
+	DefaultMasterAddress     uint16        `yaml:"default_master_address"`
+	DefaultOutstationAddress uint16        `yaml:"default_outstation_address"`
+}
+
+// NewDNP3Handler creates a new DNP3 protocol handler
+func NewDNP3Handler(logger *zap.Logger) ProtocolHandler {
+	return &DNP3Handler{
+		logger: logger,
+		config: &DNP3Config{
+			DefaultTimeout:           5 * time.Second,
+			ConnectionTimeout:        10 * time.Second,
+			DefaultMasterAddress:     1,
+			DefaultOutstationAddress: 10,
+		},
+	}
+}
+
+// Connect opens a master session with an outstation
+func (d *DNP3Handler) Connect(device *Device) error {
+	port := device.Port
+	if port == 0 {
+		port = 20000
+	}
+	masterAddress := d.configAddress(device, "master_address", d.config.DefaultMasterAddress)
+	outstationAddress := d.configAddress(device, "outstation_address", d.config.DefaultOutstationAddress)
+	connectionKey := fmt.Sprintf("%s:%d/%d", device.Address, port, outstationAddress)
+
+	if _, exists := d.connections.Load(connectionKey); exists {
+		device.ConnectionID = connectionKey
+		return nil
+	}
+
+	conn, err := net.DialTimeout("tcp", fmt.Sprintf("%s:%d", device.Address, port), d.config.ConnectionTimeout)
+	if err != nil {
+		return fmt.Errorf("failed to connect to DNP3 outstation: %w", err)
+	}
+
+	connection := &DNP3Connection{
+		master:    NewDNP3Master(conn, masterAddress, outstationAddress, d.config.DefaultTimeout),
+		conn:      conn,
+		createdAt: time.Now(),
+		lastUsed:  time.Now(),
+	}
+	d.connections.Store(connectionKey, connection)
+	device.ConnectionID = connectionKey
+
+	d.logger.Info("DNP3 connection established",
+		zap.String("device_id", device.ID),
+		zap.String("address", device.Address),
+		zap.Int("port", port),
+		zap.Uint16("outstation_address", outstationAddress),
+	)
+
+	return nil
+}
+
+// Disconnect closes the session with an outstation
+func (d *DNP3Handler) Disconnect(device *Device) error {
+	if device.ConnectionID == "" {
+		return nil
+	}
+
+	connInterface, exists := d.connections.LoadAndDelete(device.ConnectionID)
+	if !exists {
+		return nil
+	}
+
+	device.ConnectionID = ""
+	return connInterface.(*DNP3Connection).conn.Close()
+}
+
+// IsConnected checks if the device has a session
+func (d *DNP3Handler) IsConnected(device *Device) bool {
+	_, err := d.getConnection(device)
+	return err == nil
+}
+
+// ReadTag reads the static value of one point. The tag's quality is set
+// from the point flags.
+func (d *DNP3Handler) ReadTag(device *Device, tag *Tag) (interface{}, error) {
+	conn, err := d.getConnection(device)
+	if err != nil {
+		return nil, err
+	}
+
+	pointType, index, err := parseDNP3Address(tag.Address)
+	if err != nil {
+		return nil, err
+	}
+
+	point, err := conn.master.ReadPoint(pointType, index)
+	conn.record(err)
+	if err != nil {
+		return nil, err
+	}
+
+	tag.SetStatus(point.QualityStatus())
+	return point.Value, nil
+}
+
+// WriteTag operates a binary or analog output
+func (d *DNP3Handler) WriteTag(device *Device, tag *Tag, value interface{}) error {
+	if !tag.Writable {
+		return fmt.Errorf("tag %s is not writable", tag.ID)
+	}
+
+	conn, err := d.getConnection(device)
+	if err != nil {
+		return err
+	}
+
+	pointType, index, err := parseDNP3Address(tag.Address)
+	if err != nil {
+		return err
+	}
+
+	switch pointType {
+	case DNP3BinaryOutput:
+		on, ok := value.(bool)
+		if !ok {
+			return fmt.Errorf("binary output %s requires a bool value, got %T", tag.Address, value)
+		}
+		err = conn.master.OperateBinary(index, on)
+	case DNP3AnalogOutput:
+		err = conn.master.OperateAnalog(index, value)
+	default:
+		return fmt.Errorf("%s points cannot be written", pointType)
+	}
+
+	conn.record(err)
+	return err
+}
+
+// ReadMultipleTags reads all tags with one integrity poll
+func (d *DNP3Handler) ReadMultipleTags(device *Device, tags []*Tag) (map[string]interface{}, error) {
+	conn, err := d.getConnection(device)
+	if err != nil {
+		return nil, err
+	}
+
+	points, err := conn.master.IntegrityPoll()
+	conn.record(err)
+	if err != nil {
+		return nil, err
+	}
+
+	// Static values follow the events in an integrity poll, so the last
+	// value of each point is the current one
+	values := make(map[string]*DNP3Point, len(points))
+	for _, point := range points {
+		values[fmt.Sprintf("%s:%d", point.Type, point.Index)] = point
+	}
+
+	results := make(map[string]interface{})
+	for _, tag := range tags {
+		pointType, index, err := parseDNP3Address(tag.Address)
+		if err != nil {
+			continue
+		}
+		if point, exists := values[fmt.Sprintf("%s:%d", pointType, index)]; exists {
+			tag.SetStatus(point.QualityStatus())
+			results[tag.ID] = point.Value
+		}
+	}
+	return results, nil
+}
+
+// PollEvents reads the buffered events of the given classes (all when
+// none are given) and returns them as tags carrying the outstation's
+// timestamps and quality flags
+func (d *DNP3Handler) PollEvents(device *Device, classes ...int) ([]*Tag, error) {
+	conn, err := d.getConnection(device)
+	if err != nil {
+		return nil, err
+	}
+
+	points, err := conn.master.PollEvents(classes...)
+	conn.record(err)
+	if err != nil {
+		return nil, err
+	}
+
+	tags := make([]*Tag, 0, len(points))
+	for _, point := range points {
+		tags = append(tags, point.Tag())
+	}
+	return tags, nil
+}
+
+// DiscoverDevices is not supported; DNP3 outstations only answer to their
+// configured link address
+func (d *DNP3Handler) DiscoverDevices(ctx context.Context, networkRange string) ([]*Device, error) {
+	return nil, fmt.Errorf("DNP3 does not support discovery; configure outstations explicitly")
+}
+
+// GetDeviceInfo returns information about a DNP3 outstation
+func (d *DNP3Handler) GetDeviceInfo(device *Device) (*DeviceInfo, error) {
+	info := &DeviceInfo{
+		Vendor:         "Unknown",
+		Model:          "DNP3 Outstation",
+		Capabilities:   []string{"dnp3", "class-polling", "events", "direct-operate"},
+		MaxConnections: 1,
+		CustomInfo:     make(map[string]string),
+	}
+
+	if conn, err := d.getConnection(device); err == nil {
+		info.CustomInfo["master_address"] = strconv.Itoa(int(conn.master.MasterAddress))
+		info.CustomInfo["outstation_address"] = strconv.Itoa(int(conn.master.OutstationAddress))
+		info.CustomInfo["iin"] = fmt.Sprintf("0x%04X", uint16(conn.master.IIN()))
+	}
+	return info, nil
+}
+
+// GetSupportedDataTypes returns the data types of DNP3 point values
+func (d *DNP3Handler) GetSupportedDataTypes() []string {
+	return []string{
+		string(DataTypeBool),
+		string(DataTypeInt32),
+		string(DataTypeUInt32),
+		string(DataTypeFloat32),
+		string(DataTypeFloat64),
+	}
+}
+
+// ValidateTagAddress validates a DNP3 point address
+func (d *DNP3Handler) ValidateTagAddress(address string) error {
+	_, _, err := parseDNP3Address(address)
+	return err
+}
+
+// Ping sends a read without objects, which the outstation answers with
+// its internal indications only
+func (d *DNP3Handler) Ping(device *Device) error {
+	conn, err := d.getConnection(device)
+	if err != nil {
+		return err
+	}
+
+	_, err = conn.master.transact(DNP3Read, nil)
+	conn.record(err)
+	return err
+}
+
+// GetDiagnostics returns diagnostic information for a DNP3 session
+func (d *DNP3Handler) GetDiagnostics(device *Device) (*Diagnostics, error) {
+	conn, err := d.getConnection(device)
+	if err != nil {
+		return nil, err
+	}
+
+	conn.mutex.RLock()
+	defer conn.mutex.RUnlock()
+
+	diagnostics := &Diagnostics{
+		IsHealthy:         conn.master.IIN()&DNP3IINDeviceTrouble == 0,
+		LastCommunication: conn.lastUsed,
+		ErrorCount:        conn.errors,
+		ConnectionUptime:  time.Since(conn.createdAt),
+		ProtocolDiagnostics: map[string]interface{}{
+			"iin": uint16(conn.master.IIN()),
+		},
+	}
+	if conn.requests > 0 {
+		diagnostics.SuccessRate = float64(conn.requests-conn.errors) / float64(conn.requests)
+	}
+	return diagnostics, nil
+}
+
+func (d *DNP3Handler) getConnection(device *Device) (*DNP3Connection, error) {
+	if device.ConnectionID == "" {
+		return nil, fmt.Errorf("device not connected")
+	}
+
+	connInterface, exists := d.connections.Load(device.ConnectionID)
+	if !exists {
+		return nil, fmt.Errorf("connection not found")
+	}
+	return connInterface.(*DNP3Connection), nil
+}
+
+func (d *DNP3Handler) configAddress(device *Device, key string, defaultAddress uint16) uint16 {
+	if address, ok := device.Config[key].(int); ok && address >= 0 && address <= 0xFFEF {
+		return uint16(address)
+	}
+	return defaultAddress
+}
+
+func (c *DNP3Connection) record(err error) {
+	c.mutex.Lock()
+	defer c.mutex.Unlock()
+
+	c.requests++
+	c.lastUsed = time.Now()
+	if err != nil {
+		c.errors++
+	}
+}
+
+// parseDNP3Address parses "<type>:<index>" point addresses
+func parseDNP3Address(address string) (DNP3PointType, uint16, error) {
+	parts := strings.SplitN(strings.TrimSpace(address), ":", 2)
+	if len(parts) != 2 {
+		return "", 0, fmt.Errorf("invalid DNP3 address %q, expected <type>:<index>", address)
+	}
+
+	pointType := DNP3PointType(strings.ToUpper(parts[0]))
+	if _, exists := dnp3StaticGroups[pointType]; !exists {
+		return "", 0, fmt.Errorf("invalid DNP3 point type %q", parts[0])
+	}
+
+	index, err := strconv.ParseUint(parts[1], 10, 16)
+	if err != nil {
+		return "", 0, fmt.Errorf("invalid DNP3 point index %q: %w", parts[1], err)
+	}
+	return pointType, uint16(index), nil
+}
@@ -0,0 +1,589 @@
+package protocols
+
+import (
+	"encoding/binary"
+	"fmt"
+	"math"
+	"time"
+)
+
+// DNP3 Application Layer
+//
+// An application fragment is a control byte, a function code, the internal
+// indications (responses only) and a sequence of object headers, each
+// followed by its objects. Object sizes depend on the group and variation,
+// so only the formats in dnp3Formats can be decoded; an unknown format ends
+// decoding of the fragment since the rest cannot be located.
+
+// DNP3FunctionCode is an application layer function code
+type DNP3FunctionCode byte
+
+const (
+	DNP3Confirm             DNP3FunctionCode = 0x00
+	DNP3Read                DNP3FunctionCode = 0x01
+	DNP3Write               DNP3FunctionCode = 0x02
+	DNP3Select              DNP3FunctionCode = 0x03
+	DNP3Operate             DNP3FunctionCode = 0x04
+	DNP3DirectOperate       DNP3FunctionCode = 0x05
+	DNP3EnableUnsolicited   DNP3FunctionCode = 0x14
+	DNP3DisableUnsolicited  DNP3FunctionCode = 0x15
+	DNP3Response            DNP3FunctionCode = 0x81
+	DNP3UnsolicitedResponse DNP3FunctionCode = 0x82
+)
+
+// Application control bits
+const (
+	dnp3AppFIR     = 0x80
+	dnp3AppFIN     = 0x40
+	dnp3AppCON     = 0x20
+	dnp3AppUNS     = 0x10
+	dnp3AppSeqMask = 0x0F
+)
+
+// Object header qualifiers
+const (
+	dnp3Qualifier8BitStartStop  = 0x00
+	dnp3Qualifier16BitStartStop = 0x01
+	dnp3QualifierAll            = 0x06
+	dnp3Qualifier8BitCount      = 0x07
+	dnp3Qualifier16BitCount     = 0x08
+	dnp3Qualifier8BitIndexed    = 0x17 // 8 bit count, 8 bit index prefix
+	dnp3Qualifier16BitIndexed   = 0x28 // 16 bit count, 16 bit index prefix
+)
+
+// DNP3IIN holds the internal indications of a response, IIN1 in the high
+// byte and IIN2 in the low byte
+type DNP3IIN uint16
+
+const (
+	DNP3IINBroadcast      DNP3IIN = 0x0100
+	DNP3IINClass1Events   DNP3IIN = 0x0200
+	DNP3IINClass2Events   DNP3IIN = 0x0400
+	DNP3IINClass3Events   DNP3IIN = 0x0800
+	DNP3IINNeedTime       DNP3IIN = 0x1000
+	DNP3IINLocalControl   DNP3IIN = 0x2000
+	DNP3IINDeviceTrouble  DNP3IIN = 0x4000
+	DNP3IINDeviceRestart  DNP3IIN = 0x8000
+	DNP3IINNoFuncSupport  DNP3IIN = 0x0001
+	DNP3IINObjectUnknown  DNP3IIN = 0x0002
+	DNP3IINParameterError DNP3IIN = 0x0004
+	DNP3IINEventOverflow  DNP3IIN = 0x0008
+	DNP3IINAlreadyRunning DNP3IIN = 0x0010
+	DNP3IINConfigCorrupt  DNP3IIN = 0x0020
+)
+
+// Err returns an error for the indications that reject a request
+func (i DNP3IIN) Err() error {
+	switch {
+	case i&DNP3IINNoFuncSupport != 0:
+		return fmt.Errorf("outstation does not support the function code")
+	case i&DNP3IINObjectUnknown != 0:
+		return fmt.Errorf("outstation does not support the requested objects")
+	case i&DNP3IINParameterError != 0:
+		return fmt.Errorf("outstation rejected the request parameters")
+	case i&DNP3IINAlreadyRunning != 0:
+		return fmt.Errorf("outstation is already executing the request")
+	}
+	return nil
+}
+
+// HasEvents reports whether the outstation has buffered events in any class
+func (i DNP3IIN) HasEvents() bool {
+	return i&(DNP3IINClass1Events|DNP3IINClass2Events|DNP3IINClass3Events) != 0
+}
+
+// DNP3PointType is the kind of a DNP3 point. The values are also the tag
+// address prefixes.
+type DNP3PointType string
+
+const (
+	DNP3BinaryInput  DNP3PointType = "BI"
+	DNP3BinaryOutput DNP3PointType = "BO"
+	DNP3Counter      DNP3PointType = "CT"
+	DNP3AnalogInput  DNP3PointType = "AI"
+	DNP3AnalogOutput DNP3PointType = "AO"
+)
+
+// Point flag bits. Bit 7 carries the state of binary points.
+const (
+	DNP3FlagOnline        = 0x01
+	DNP3FlagRestart       = 0x02
+	DNP3FlagCommLost      = 0x04
+	DNP3FlagRemoteForced  = 0x08
+	DNP3FlagLocalForced   = 0x10
+	DNP3FlagOverRange     = 0x20 // Chatter filter on binary points
+	DNP3FlagReferenceErr  = 0x40 // Analog points only
+	DNP3FlagBinaryState   = 0x80
+	dnp3FlagsWithoutState = 0x7F
+)
+
+// DNP3Point is one decoded point value
+type DNP3Point struct {
+	Type      DNP3PointType `json:"type"`
+	Index     uint16        `json:"index"`
+	Value     interface{}   `json:"value"` // bool, int32, uint32, float32 or float64
+	Flags     byte          `json:"flags"`
+	Event     bool          `json:"event"`
+	Timestamp time.Time     `json:"timestamp"` // Zero unless the object carries a time
+}
+
+// Quality maps the point flags onto tag quality
+func (p *DNP3Point) Quality() Quality {
+	return p.QualityStatus().Quality
+}
+
+// QualityStatus maps the point flags onto a quality status. Forced values
+// are good but overridden.
+func (p *DNP3Point) QualityStatus() QualityStatus {
+	switch {
+	case p.Flags&DNP3FlagOnline == 0:
+		return NewQualityStatus(QualityBad, QualitySubstatusOutOfService, QualitySourceDevice)
+	case p.Flags&DNP3FlagCommLost != 0:
+		return NewQualityStatus(QualityStale, QualitySubstatusCommFailure, QualitySourceDevice)
+	case p.Flags&DNP3FlagRestart != 0:
+		return NewQualityStatus(QualityUncertain, "", QualitySourceDevice)
+	case p.Flags&DNP3FlagOverRange != 0:
+		return NewQualityStatus(QualityUncertain, QualitySubstatusOutOfRange, QualitySourceDevice)
+	case p.Flags&DNP3FlagReferenceErr != 0:
+		return NewQualityStatus(QualityUncertain, QualitySubstatusInaccurate, QualitySourceDevice)
+	case p.Flags&(DNP3FlagRemoteForced|DNP3FlagLocalForced) != 0:
+		return NewQualityStatus(QualityGood, QualitySubstatusOverridden, QualitySourceDevice)
+	}
+	return GoodQuality
+}
+
+// Tag returns the point as a tag addressed "<type>:<index>"
+func (p *DNP3Point) Tag() *Tag {
+	address := fmt.Sprintf("%s:%d", p.Type, p.Index)
+	timestamp := p.Timestamp
+	if timestamp.IsZero() {
+		timestamp = time.Now()
+	}
+	tag := &Tag{
+		ID:        address,
+		Name:      address,
+		Address:   address,
+		Value:     p.Value,
+		Timestamp: timestamp,
+	}
+	tag.SetStatus(p.QualityStatus())
+	return tag
+}
+
+// dnp3ValueKind is the encoding of the value field of an object
+type dnp3ValueKind int
+
+const (
+	dnp3ValueNone dnp3ValueKind = iota // Binary state in the flags
+	dnp3ValueInt16
+	dnp3ValueInt32
+	dnp3ValueUint16
+	dnp3ValueUint32
+	dnp3ValueFloat32
+	dnp3ValueFloat64
+)
+
+// dnp3ObjectFormat describes the layout of one group and variation
+type dnp3ObjectFormat struct {
+	pointType DNP3PointType
+	flags     bool
+	value     dnp3ValueKind
+	time      bool
+	event     bool
+}
+
+// dnp3Formats are the point objects that can be decoded and encoded,
+// keyed by group << 8 | variation
+var dnp3Formats = map[uint16]dnp3ObjectFormat{
+	1<<8 | 2:  {DNP3BinaryInput, true, dnp3ValueNone, false, false},
+	2<<8 | 1:  {DNP3BinaryInput, true, dnp3ValueNone, false, true},
+	2<<8 | 2:  {DNP3BinaryInput, true, dnp3ValueNone, true, true},
+	10<<8 | 2: {DNP3BinaryOutput, true, dnp3ValueNone, false, false},
+	20<<8 | 1: {DNP3Counter, true, dnp3ValueUint32, false, false},
+	20<<8 | 2: {DNP3Counter, true, dnp3ValueUint16, false, false},
+	20<<8 | 5: {DNP3Counter, false, dnp3ValueUint32, false, false},
+	20<<8 | 6: {DNP3Counter, false, dnp3ValueUint16, false, false},
+	22<<8 | 1: {DNP3Counter, true, dnp3ValueUint32, false, true},
+	22<<8 | 2: {DNP3Counter, true, dnp3ValueUint16, false, true},
+	22<<8 | 5: {DNP3Counter, true, dnp3ValueUint32, true, true},
+	22<<8 | 6: {DNP3Counter, true, dnp3ValueUint16, true, true},
+	30<<8 | 1: {DNP3AnalogInput, true, dnp3ValueInt32, false, false},
+	30<<8 | 2: {DNP3AnalogInput, true, dnp3ValueInt16, false, false},
+	30<<8 | 3: {DNP3AnalogInput, false, dnp3ValueInt32, false, false},
+	30<<8 | 4: {DNP3AnalogInput, false, dnp3ValueInt16, false, false},
+	30<<8 | 5: {DNP3AnalogInput, true, dnp3ValueFloat32, false, false},
+	30<<8 | 6: {DNP3AnalogInput, true, dnp3ValueFloat64, false, false},
+	32<<8 | 1: {DNP3AnalogInput, true, dnp3ValueInt32, false, true},
+	32<<8 | 2: {DNP3AnalogInput, true, dnp3ValueInt16, false, true},
+	32<<8 | 3: {DNP3AnalogInput, true, dnp3ValueInt32, true, true},
+	32<<8 | 4: {DNP3AnalogInput, true, dnp3ValueInt16, true, true},
+	32<<8 | 5: {DNP3AnalogInput, true, dnp3ValueFloat32, false, true},
+	32<<8 | 6: {DNP3AnalogInput, true, dnp3ValueFloat64, false, true},
+	32<<8 | 7: {DNP3AnalogInput, true, dnp3ValueFloat32, true, true},
+	32<<8 | 8: {DNP3AnalogInput, true, dnp3ValueFloat64, true, true},
+	40<<8 | 1: {DNP3AnalogOutput, true, dnp3ValueInt32, false, false},
+	40<<8 | 2: {DNP3AnalogOutput, true, dnp3ValueInt16, false, false},
+	40<<8 | 3: {DNP3AnalogOutput, true, dnp3ValueFloat32, false, false},
+	40<<8 | 4: {DNP3AnalogOutput, true, dnp3ValueFloat64, false, false},
+}
+
+// Objects that are not points but must be skipped in responses, by size
+var dnp3SkippedObjects = map[uint16]int{
+	12<<8 | 1: 11, // Control relay output block
+	41<<8 | 1: 5,  // Analog output, 32 bit
+	41<<8 | 2: 3,  // Analog output, 16 bit
+	41<<8 | 3: 5,  // Analog output, float
+	41<<8 | 4: 9,  // Analog output, double
+	50<<8 | 1: 6,  // Absolute time
+	51<<8 | 1: 6,  // Common time of occurrence, synchronized
+	51<<8 | 2: 6,  // Common time of occurrence, unsynchronized
+	52<<8 | 2: 2,  // Time delay, fine
+}
+
+// Static groups read by ReadPoint, by point type
+var dnp3StaticGroups = map[DNP3PointType]byte{
+	DNP3BinaryInput:  1,
+	DNP3BinaryOutput: 10,
+	DNP3Counter:      20,
+	DNP3AnalogInput:  30,
+	DNP3AnalogOutput: 40,
+}
+
+func (f dnp3ObjectFormat) size() int {
+	size := 0
+	if f.flags {
+		size++
+	}
+	switch f.value {
+	case dnp3ValueInt16, dnp3ValueUint16:
+		size += 2
+	case dnp3ValueInt32, dnp3ValueUint32, dnp3ValueFloat32:
+		size += 4
+	case dnp3ValueFloat64:
+		size += 8
+	}
+	if f.time {
+		size += 6
+	}
+	return size
+}
+
+func (f dnp3ObjectFormat) decode(data []byte, point *DNP3Point) {
+	point.Type, point.Event, point.Flags = f.pointType, f.event, DNP3FlagOnline
+	if f.flags {
+		point.Flags, data = data[0], data[1:]
+	}
+
+	switch f.value {
+	case dnp3ValueNone:
+		point.Value = point.Flags&DNP3FlagBinaryState != 0
+	case dnp3ValueInt16:
+		point.Value, data = int32(int16(binary.LittleEndian.Uint16(data))), data[2:]
+	case dnp3ValueInt32:
+		point.Value, data = int32(binary.LittleEndian.Uint32(data)), data[4:]
+	case dnp3ValueUint16:
+		point.Value, data = uint32(binary.LittleEndian.Uint16(data)), data[2:]
+	case dnp3ValueUint32:
+		point.Value, data = binary.LittleEndian.Uint32(data), data[4:]
+	case dnp3ValueFloat32:
+		point.Value, data = math.Float32frombits(binary.LittleEndian.Uint32(data)), data[4:]
+	case dnp3ValueFloat64:
+		point.Value, data = math.Float64frombits(binary.LittleEndian.Uint64(data)), data[8:]
+	}
+
+	if f.time {
+		point.Timestamp = decodeDNP3Time(data)
+	}
+}
+
+func (f dnp3ObjectFormat) encode(dst []byte, point *DNP3Point) ([]byte, error) {
+	if f.flags {
+		flags := point.Flags
+		if f.value == dnp3ValueNone {
+			flags &= dnp3FlagsWithoutState
+			if point.Value == true {
+				flags |= DNP3FlagBinaryState
+			}
+		}
+		dst = append(dst, flags)
+	}
+
+	switch f.value {
+	case dnp3ValueInt16:
+		v, err := integerValue(point.Value, math.MinInt16, math.MaxInt16)
+		if err != nil {
+			return nil, err
+		}
+		dst = binary.LittleEndian.AppendUint16(dst, uint16(v))
+	case dnp3ValueInt32:
+		v, err := integerValue(point.Value, math.MinInt32, math.MaxInt32)
+		if err != nil {
+			return nil, err
+		}
+		dst = binary.LittleEndian.AppendUint32(dst, uint32(v))
+	case dnp3ValueUint16:
+		v, err := integerValue(point.Value, 0, math.MaxUint16)
+		if err != nil {
+			return nil, err
+		}
+		dst = binary.LittleEndian.AppendUint16(dst, uint16(v))
+	case dnp3ValueUint32:
+		v, err := integerValue(point.Value, 0, math.MaxUint32)
+		if err != nil {
+			return nil, err
+		}
+		dst = binary.LittleEndian.AppendUint32(dst, uint32(v))
+	case dnp3ValueFloat32:
+		v, err := floatValue(point.Value)
+		if err != nil {
+			return nil, err
+		}
+		dst = binary.LittleEndian.AppendUint32(dst, math.Float32bits(float32(v)))
+	case dnp3ValueFloat64:
+		v, err := floatValue(point.Value)
+		if err != nil {
+			return nil, err
+		}
+		dst = binary.LittleEndian.AppendUint64(dst, math.Float64bits(v))
+	}
+
+	if f.time {
+		dst = appendDNP3Time(dst, point.Timestamp)
+	}
+	return dst, nil
+}
+
+// decodeDNP3Time decodes a 48 bit count of milliseconds since the epoch
+func decodeDNP3Time(data []byte) time.Time {
+	var ms uint64
+	for i := 5; i >= 0; i-- {
+		ms = ms<<8 | uint64(data[i])
+	}
+	return time.UnixMilli(int64(ms)).UTC()
+}
+
+func appendDNP3Time(dst []byte, t time.Time) []byte {
+	ms := uint64(t.UnixMilli())
+	for i := 0; i < 6; i++ {
+		dst = append(dst, byte(ms>>(8*i)))
+	}
+	return dst
+}
+
+// dnp3Fragment is a decoded application fragment
+type dnp3Fragment struct {
+	Control  byte
+	Function DNP3FunctionCode
+	IIN      DNP3IIN // Responses only
+	Objects  []byte  // Object headers and objects
+}
+
+// seq returns the application sequence number
+func (f *dnp3Fragment) seq() byte {
+	return f.Control & dnp3AppSeqMask
+}
+
+func (f *dnp3Fragment) isResponse() bool {
+	return f.Function == DNP3Response || f.Function == DNP3UnsolicitedResponse
+}
+
+// appendDNP3Fragment encodes an application fragment
+func appendDNP3Fragment(dst []byte, fragment *dnp3Fragment) []byte {
+	dst = append(dst, fragment.Control, byte(fragment.Function))
+	if fragment.isResponse() {
+		dst = append(dst, byte(fragment.IIN>>8), byte(fragment.IIN))
+	}
+	return append(dst, fragment.Objects...)
+}
+
+// parseDNP3Fragment decodes the application header of a fragment
+func parseDNP3Fragment(data []byte) (*dnp3Fragment, error) {
+	if len(data) < 2 {
+		return nil, fmt.Errorf("application fragment of %d bytes is too short", len(data))
+	}
+
+	fragment := &dnp3Fragment{Control: data[0], Function: DNP3FunctionCode(data[1]), Objects: data[2:]}
+	if fragment.isResponse() {
+		if len(data) < 4 {
+			return nil, fmt.Errorf("response fragment of %d bytes has no internal indications", len(data))
+		}
+		fragment.IIN = DNP3IIN(binary.BigEndian.Uint16(data[2:4]))
+		fragment.Objects = data[4:]
+	}
+	return fragment, nil
+}
+
+// dnp3ObjectHeader is a decoded object header
+type dnp3ObjectHeader struct {
+	Group     byte
+	Variation byte
+	Qualifier byte
+	Indexes   []uint16 // Index of each object; empty for qualifier 0x06
+}
+
+// appendDNP3ObjectHeader encodes an object header without range or count,
+// as used for class polls
+func appendDNP3ObjectHeader(dst []byte, group, variation byte) []byte {
+	return append(dst, group, variation, dnp3QualifierAll)
+}
+
+// appendDNP3RangeHeader encodes an object header for a range of indexes
+func appendDNP3RangeHeader(dst []byte, group, variation byte, start, stop uint16) []byte {
+	if stop <= math.MaxUint8 {
+		return append(dst, group, variation, dnp3Qualifier8BitStartStop, byte(start), byte(stop))
+	}
+	dst = append(dst, group, variation, dnp3Qualifier16BitStartStop)
+	dst = binary.LittleEndian.AppendUint16(dst, start)
+	return binary.LittleEndian.AppendUint16(dst, stop)
+}
+
+// appendDNP3Points encodes points of one format with 16 bit index
+// prefixes, as outstations report events
+func appendDNP3Points(dst []byte, group, variation byte, points []*DNP3Point) ([]byte, error) {
+	format, exists := dnp3Formats[uint16(group)<<8|uint16(variation)]
+	if !exists {
+		return nil, fmt.Errorf("unsupported object g%dv%d", group, variation)
+	}
+	if len(points) > math.MaxUint16 {
+		return nil, fmt.Errorf("too many objects in one header")
+	}
+
+	dst = append(dst, group, variation, dnp3Qualifier16BitIndexed)
+	dst = binary.LittleEndian.AppendUint16(dst, uint16(len(points)))
+	for _, point := range points {
+		dst = binary.LittleEndian.AppendUint16(dst, point.Index)
+		var err error
+		if dst, err = format.encode(dst, point); err != nil {
+			return nil, fmt.Errorf("%s:%d: %w", point.Type, point.Index, err)
+		}
+	}
+	return dst, nil
+}
+
+// walkDNP3Objects calls visit for every object header of a fragment with
+// the bytes of each object. objectSize returns the size of one object of
+// a group and variation, or -1 if it is not known; headers of requests
+// that carry no objects (reads) pass a function returning 0.
+func walkDNP3Objects(data []byte, objectSize func(group, variation byte) int, visit func(header *dnp3ObjectHeader, objects [][]byte) error) error {
+	for len(data) > 0 {
+		if len(data) < 3 {
+			return fmt.Errorf("truncated object header")
+		}
+		header := &dnp3ObjectHeader{Group: data[0], Variation: data[1], Qualifier: data[2]}
+		data = data[3:]
+
+		size := objectSize(header.Group, header.Variation)
+		if size < 0 {
+			return fmt.Errorf("unsupported object g%dv%d", header.Group, header.Variation)
+		}
+
+		var count, prefix int
+		switch header.Qualifier {
+		case dnp3QualifierAll:
+			if err := visit(header, nil); err != nil {
+				return err
+			}
+			continue
+
+		case dnp3Qualifier8BitStartStop, dnp3Qualifier16BitStartStop:
+			var start, stop int
+			if header.Qualifier == dnp3Qualifier8BitStartStop {
+				if len(data) < 2 {
+					return fmt.Errorf("truncated range")
+				}
+				start, stop, data = int(data[0]), int(data[1]), data[2:]
+			} else {
+				if len(data) < 4 {
+					return fmt.Errorf("truncated range")
+				}
+				start, stop = int(binary.LittleEndian.Uint16(data[0:2])), int(binary.LittleEndian.Uint16(data[2:4]))
+				data = data[4:]
+			}
+			if stop < start {
+				return fmt.Errorf("invalid range %d-%d", start, stop)
+			}
+			for i := start; i <= stop; i++ {
+				header.Indexes = append(header.Indexes, uint16(i))
+			}
+			count = stop - start + 1
+
+		case dnp3Qualifier8BitCount, dnp3Qualifier8BitIndexed:
+			if len(data) < 1 {
+				return fmt.Errorf("truncated count")
+			}
+			count, data = int(data[0]), data[1:]
+			if header.Qualifier == dnp3Qualifier8BitIndexed {
+				prefix = 1
+			}
+
+		case dnp3Qualifier16BitCount, dnp3Qualifier16BitIndexed:
+			if len(data) < 2 {
+				return fmt.Errorf("truncated count")
+			}
+			count, data = int(binary.LittleEndian.Uint16(data)), data[2:]
+			if header.Qualifier == dnp3Qualifier16BitIndexed {
+				prefix = 2
+			}
+
+		default:
+			return fmt.Errorf("unsupported qualifier 0x%02X", header.Qualifier)
+		}
+
+		if prefix == 0 && len(header.Indexes) == 0 {
+			for i := 0; i < count; i++ {
+				header.Indexes = append(header.Indexes, uint16(i))
+			}
+		}
+
+		objects := make([][]byte, 0, count)
+		for i := 0; i < count; i++ {
+			if len(data) < prefix+size {
+				return fmt.Errorf("truncated g%dv%d object", header.Group, header.Variation)
+			}
+			switch prefix {
+			case 1:
+				header.Indexes = append(header.Indexes, uint16(data[0]))
+			case 2:
+				header.Indexes = append(header.Indexes, binary.LittleEndian.Uint16(data))
+			}
+			objects = append(objects, data[prefix:prefix+size])
+			data = data[prefix+size:]
+		}
+
+		if err := visit(header, objects); err != nil {
+			return err
+		}
+	}
+	return nil
+}
+
+// dnp3ResponseObjectSize returns the size of the objects in a response
+func dnp3ResponseObjectSize(group, variation byte) int {
+	key := uint16(group)<<8 | uint16(variation)
+	if format, exists := dnp3Formats[key]; exists {
+		return format.size()
+	}
+	if size, exists := dnp3SkippedObjects[key]; exists {
+		return size
+	}
+	return -1
+}
+
+// decodeDNP3Points decodes the point objects of a response. Common time of
+// occurrence and packed binary inputs are not supported.
+func decodeDNP3Points(objects []byte) ([]*DNP3Point, error) {
+	var points []*DNP3Point
+	err := walkDNP3Objects(objects, dnp3ResponseObjectSize, func(header *dnp3ObjectHeader, data [][]byte) error {
+		format, exists := dnp3Formats[uint16(header.Group)<<8|uint16(header.Variation)]
+		if !exists {
+			return nil
+		}
+		for i, object := range data {
+			point := &DNP3Point{Index: header.Indexes[i]}
+			format.decode(object, point)
+			points = append(points, point)
+		}
+		return nil
+	})
+	return points, err
+}
@@ -0,0 +1,210 @@
+package protocols
+
+import (
+	"encoding/binary"
+	"fmt"
+	"io"
+)
+
+// DNP3 Data Link and Transport Layers
+//
+// A link frame is a 10 byte header (start bytes 0x05 0x64, length, control,
+// destination, source and a CRC) followed by up to 250 bytes of user data,
+// sent in blocks of 16 bytes that each carry their own CRC. Addresses and
+// CRCs are little-endian. The transport layer adds a one byte header to
+// every frame so application fragments larger than one frame can be split
+// into segments and reassembled in order.
+
+const (
+	dnp3Start0         = 0x05
+	dnp3Start1         = 0x64
+	dnp3LinkHeaderSize = 10  // start bytes + length + control + addresses + CRC
+	dnp3BlockSize      = 16  // user data bytes per CRC block
+	dnp3MaxUserData    = 250 // user data bytes per frame
+	dnp3MaxSegmentData = dnp3MaxUserData - 1
+
+	// dnp3LinkLengthOverhead is counted by the length byte: control and
+	// addresses
+	dnp3LinkLengthOverhead = 5
+)
+
+// Link control field bits and the function codes used
+const (
+	dnp3LinkDIR = 0x80 // Set on frames from the master
+	dnp3LinkPRM = 0x40 // Set on frames from the primary station
+
+	dnp3LinkUnconfirmedUserData = 0x04
+	dnp3LinkRequestStatus       = 0x09
+	dnp3LinkStatus              = 0x0B
+)
+
+// Transport header bits
+const (
+	dnp3TransportFIN     = 0x80
+	dnp3TransportFIR     = 0x40
+	dnp3TransportSeqMask = 0x3F
+)
+
+// dnp3LinkFrame is a decoded data link frame
+type dnp3LinkFrame struct {
+	Control     byte
+	Destination uint16
+	Source      uint16
+	Data        []byte
+}
+
+// dnp3CRC computes the DNP3 CRC-16 (polynomial 0x3D65, reflected, inverted)
+func dnp3CRC(data []byte) uint16 {
+	crc := uint16(0)
+	for _, b := range data {
+		crc ^= uint16(b)
+		for i := 0; i < 8; i++ {
+			if crc&1 != 0 {
+				crc = crc>>1 ^ 0xA6BC
+			} else {
+				crc >>= 1
+			}
+		}
+	}
+	return ^crc
+}
+
+// appendDNP3LinkFrame appends an encoded link frame to dst
+func appendDNP3LinkFrame(dst []byte, frame *dnp3LinkFrame) ([]byte, error) {
+	if len(frame.Data) > dnp3MaxUserData {
+		return nil, fmt.Errorf("link frame user data of %d bytes exceeds %d", len(frame.Data), dnp3MaxUserData)
+	}
+
+	start := len(dst)
+	dst = append(dst, dnp3Start0, dnp3Start1, byte(dnp3LinkLengthOverhead+len(frame.Data)), frame.Control)
+	dst = binary.LittleEndian.AppendUint16(dst, frame.Destination)
+	dst = binary.LittleEndian.AppendUint16(dst, frame.Source)
+	dst = binary.LittleEndian.AppendUint16(dst, dnp3CRC(dst[start:]))
+
+	for data := frame.Data; len(data) > 0; {
+		n := len(data)
+		if n > dnp3BlockSize {
+			n = dnp3BlockSize
+		}
+		dst = append(dst, data[:n]...)
+		dst = binary.LittleEndian.AppendUint16(dst, dnp3CRC(data[:n]))
+		data = data[n:]
+	}
+	return dst, nil
+}
+
+// readDNP3LinkFrame reads one link frame, checking every CRC
+func readDNP3LinkFrame(r io.Reader) (*dnp3LinkFrame, error) {
+	var header [dnp3LinkHeaderSize]byte
+	if _, err := io.ReadFull(r, header[:]); err != nil {
+		return nil, err
+	}
+	if header[0] != dnp3Start0 || header[1] != dnp3Start1 {
+		return nil, fmt.Errorf("invalid link start bytes %02X %02X", header[0], header[1])
+	}
+	if crc := binary.LittleEndian.Uint16(header[8:10]); crc != dnp3CRC(header[:8]) {
+		return nil, fmt.Errorf("link header CRC mismatch")
+	}
+	if header[2] < dnp3LinkLengthOverhead {
+		return nil, fmt.Errorf("invalid link length %d", header[2])
+	}
+
+	frame := &dnp3LinkFrame{
+		Control:     header[3],
+		Destination: binary.LittleEndian.Uint16(header[4:6]),
+		Source:      binary.LittleEndian.Uint16(header[6:8]),
+	}
+
+	remaining := int(header[2]) - dnp3LinkLengthOverhead
+	blocks := (remaining + dnp3BlockSize - 1) / dnp3BlockSize
+	body := make([]byte, remaining+2*blocks)
+	if _, err := io.ReadFull(r, body); err != nil {
+		return nil, err
+	}
+
+	frame.Data = make([]byte, 0, remaining)
+	for len(body) > 0 {
+		n := len(body) - 2
+		if n > dnp3BlockSize {
+			n = dnp3BlockSize
+		}
+		if binary.LittleEndian.Uint16(body[n:n+2]) != dnp3CRC(body[:n]) {
+			return nil, fmt.Errorf("link data block CRC mismatch")
+		}
+		frame.Data = append(frame.Data, body[:n]...)
+		body = body[n+2:]
+	}
+	return frame, nil
+}
+
+// segmentDNP3Fragment splits an application fragment into transport
+// segments, starting at sequence number seq. It returns the segments and
+// the next sequence number.
+func segmentDNP3Fragment(fragment []byte, seq byte) ([][]byte, byte) {
+	var segments [][]byte
+	first := true
+	for {
+		n := len(fragment)
+		if n > dnp3MaxSegmentData {
+			n = dnp3MaxSegmentData
+		}
+
+		header := seq & dnp3TransportSeqMask
+		if first {
+			header |= dnp3TransportFIR
+		}
+		if n == len(fragment) {
+			header |= dnp3TransportFIN
+		}
+
+		segment := make([]byte, 0, n+1)
+		segment = append(segment, header)
+		segments = append(segments, append(segment, fragment[:n]...))
+
+		fragment = fragment[n:]
+		seq = (seq + 1) & dnp3TransportSeqMask
+		first = false
+		if len(fragment) == 0 {
+			return segments, seq
+		}
+	}
+}
+
+// dnp3Reassembler rebuilds application fragments from transport segments
+type dnp3Reassembler struct {
+	buffer []byte
+	seq    byte
+	active bool
+}
+
+// add processes one segment. It returns the fragment once its final
+// segment has arrived. Out of sequence segments discard the partial
+// fragment.
+func (r *dnp3Reassembler) add(segment []byte) ([]byte, error) {
+	if len(segment) == 0 {
+		return nil, fmt.Errorf("empty transport segment")
+	}
+
+	header, data := segment[0], segment[1:]
+	seq := header & dnp3TransportSeqMask
+
+	switch {
+	case header&dnp3TransportFIR != 0:
+		r.buffer = append(r.buffer[:0], data...)
+		r.active = true
+	case !r.active:
+		return nil, fmt.Errorf("transport segment %d without a first segment", seq)
+	case seq != (r.seq+1)&dnp3TransportSeqMask:
+		r.active = false
+		return nil, fmt.Errorf("transport segment %d out of sequence, expected %d", seq, (r.seq+1)&dnp3TransportSeqMask)
+	default:
+		r.buffer = append(r.buffer, data...)
+	}
+	r.seq = seq
+
+	if header&dnp3TransportFIN == 0 {
+		return nil, nil
+	}
+	r.active = false
+	return append([]byte(nil), r.buffer...), nil
+}
@@ -0,0 +1,343 @@
+package protocols
+
+import (
+	"encoding/binary"
+	"fmt"
+	"math"
+	"net"
+	"sync"
+	"time"
+)
+
+// DNP3 Master
+//
+// DNP3Master runs application requests against one outstation over a
+// stream connection (DNP3 over TCP, or a serial port bridged to one).
+// Requests are serialized. Unsolicited responses that arrive while waiting
+// for a response are confirmed and handed to OnUnsolicited.
+
+// Control relay output block codes
+const (
+	dnp3ControlLatchOn  = 0x03
+	dnp3ControlLatchOff = 0x04
+)
+
+// DNP3Master is a DNP3 master session with one outstation
+type DNP3Master struct {
+	MasterAddress     uint16
+	OutstationAddress uint16
+	Timeout           time.Duration
+
+	// OnUnsolicited receives the points of unsolicited responses. It is
+	// called while a request is in progress and must not use the master.
+	OnUnsolicited func(points []*DNP3Point)
+
+	conn net.Conn
+
+	mutex        sync.Mutex // serializes requests
+	appSeq       byte
+	transportSeq byte
+	reassembler  dnp3Reassembler
+	iin          DNP3IIN
+	buffer       []byte
+}
+
+// NewDNP3Master creates a master session on an open connection
+func NewDNP3Master(conn net.Conn, masterAddress, outstationAddress uint16, timeout time.Duration) *DNP3Master {
+	return &DNP3Master{
+		MasterAddress:     masterAddress,
+		OutstationAddress: outstationAddress,
+		Timeout:           timeout,
+		conn:              conn,
+	}
+}
+
+// IIN returns the internal indications of the last response
+func (m *DNP3Master) IIN() DNP3IIN {
+	m.mutex.Lock()
+	defer m.mutex.Unlock()
+	return m.iin
+}
+
+// IntegrityPoll reads all events and then all static values (class 1, 2,
+// 3 and 0)
+func (m *DNP3Master) IntegrityPoll() ([]*DNP3Point, error) {
+	var objects []byte
+	for variation := byte(2); variation <= 4; variation++ {
+		objects = appendDNP3ObjectHeader(objects, 60, variation)
+	}
+	objects = appendDNP3ObjectHeader(objects, 60, 1)
+	return m.read(objects)
+}
+
+// PollEvents reads the buffered events of the given classes (1 to 3)
+func (m *DNP3Master) PollEvents(classes ...int) ([]*DNP3Point, error) {
+	if len(classes) == 0 {
+		classes = []int{1, 2, 3}
+	}
+
+	var objects []byte
+	for _, class := range classes {
+		if class < 1 || class > 3 {
+			return nil, fmt.Errorf("invalid event class %d", class)
+		}
+		objects = appendDNP3ObjectHeader(objects, 60, byte(class+1))
+	}
+	return m.read(objects)
+}
+
+// ReadPoint reads the static value of one point
+func (m *DNP3Master) ReadPoint(pointType DNP3PointType, index uint16) (*DNP3Point, error) {
+	group, exists := dnp3StaticGroups[pointType]
+	if !exists {
+		return nil, fmt.Errorf("unsupported point type %q", pointType)
+	}
+
+	points, err := m.read(appendDNP3RangeHeader(nil, group, 0, index, index))
+	if err != nil {
+		return nil, err
+	}
+	for _, point := range points {
+		if point.Type == pointType && point.Index == index {
+			return point, nil
+		}
+	}
+	return nil, fmt.Errorf("outstation returned no value for %s:%d", pointType, index)
+}
+
+// OperateBinary latches a binary output on or off with a direct operate
+func (m *DNP3Master) OperateBinary(index uint16, on bool) error {
+	code := byte(dnp3ControlLatchOff)
+	if on {
+		code = dnp3ControlLatchOn
+	}
+
+	object := make([]byte, 0, 11)
+	object = append(object, code, 1)
+	object = binary.LittleEndian.AppendUint32(object, 0) // on time
+	object = binary.LittleEndian.AppendUint32(object, 0) // off time
+	object = append(object, 0)                           // status
+
+	return m.operate(12, 1, index, object)
+}
+
+// OperateAnalog sets an analog output with a direct operate. Floating
+// point values are sent as single precision, integers as 32 bit.
+func (m *DNP3Master) OperateAnalog(index uint16, value interface{}) error {
+	switch value.(type) {
+	case float32, float64:
+		v, err := floatValue(value)
+		if err != nil {
+			return err
+		}
+		object := binary.LittleEndian.AppendUint32(nil, math.Float32bits(float32(v)))
+		return m.operate(41, 3, index, append(object, 0))
+	}
+
+	v, err := integerValue(value, math.MinInt32, math.MaxInt32)
+	if err != nil {
+		return err
+	}
+	object := binary.LittleEndian.AppendUint32(nil, uint32(v))
+	return m.operate(41, 1, index, append(object, 0))
+}
+
+// operate sends a direct operate for one control object and checks the
+// status the outstation echoes back
+func (m *DNP3Master) operate(group, variation byte, index uint16, object []byte) error {
+	request := append([]byte{}, group, variation, dnp3Qualifier16BitIndexed)
+	request = binary.LittleEndian.AppendUint16(request, 1)
+	request = binary.LittleEndian.AppendUint16(request, index)
+	request = append(request, object...)
+
+	responses, err := m.transact(DNP3DirectOperate, request)
+	if err != nil {
+		return err
+	}
+
+	for _, response := range responses {
+		err := walkDNP3Objects(response.Objects, dnp3ResponseObjectSize, func(header *dnp3ObjectHeader, objects [][]byte) error {
+			if header.Group != group {
+				return nil
+			}
+			for i, echoed := range objects {
+				if status := echoed[len(echoed)-1]; header.Indexes[i] == index && status != 0 {
+					return fmt.Errorf("outstation rejected control of g%d index %d with status %d", group, index, status)
+				}
+			}
+			return nil
+		})
+		if err != nil {
+			return err
+		}
+	}
+	return nil
+}
+
+// read sends a read request and decodes the points of every response
+// fragment
+func (m *DNP3Master) read(objects []byte) ([]*DNP3Point, error) {
+	responses, err := m.transact(DNP3Read, objects)
+	if err != nil {
+		return nil, err
+	}
+
+	var points []*DNP3Point
+	for _, response := range responses {
+		decoded, err := decodeDNP3Points(response.Objects)
+		if err != nil {
+			return points, err
+		}
+		points = append(points, decoded...)
+	}
+	return points, nil
+}
+
+// transact sends a request and collects the fragments of its response
+func (m *DNP3Master) transact(function DNP3FunctionCode, objects []byte) ([]*dnp3Fragment, error) {
+	m.mutex.Lock()
+	defer m.mutex.Unlock()
+
+	seq := m.appSeq
+	m.appSeq = (m.appSeq + 1) & dnp3AppSeqMask
+
+	request := &dnp3Fragment{Control: dnp3AppFIR | dnp3AppFIN | seq, Function: function, Objects: objects}
+	if err := m.send(appendDNP3Fragment(nil, request)); err != nil {
+		return nil, err
+	}
+
+	if err := m.conn.SetReadDeadline(time.Now().Add(m.Timeout)); err != nil {
+		return nil, err
+	}
+	defer func() { _ = m.conn.SetReadDeadline(time.Time{}) }()
+
+	var responses []*dnp3Fragment
+	expected := seq
+	for {
+		response, err := m.receive()
+		if err != nil {
+			return nil, fmt.Errorf("waiting for response to function 0x%02X: %w", byte(function), err)
+		}
+
+		if response.Function == DNP3UnsolicitedResponse {
+			if err := m.handleUnsolicited(response); err != nil {
+				return nil, err
+			}
+			continue
+		}
+		if response.Function != DNP3Response || response.seq() != expected {
+			continue // A late response to an earlier request
+		}
+
+		m.iin = response.IIN
+		if response.Control&dnp3AppCON != 0 {
+			if err := m.confirm(response.seq(), false); err != nil {
+				return nil, err
+			}
+		}
+		responses = append(responses, response)
+
+		if response.Control&dnp3AppFIN != 0 {
+			break
+		}
+		expected = (expected + 1) & dnp3AppSeqMask
+	}
+
+	if err := responses[0].IIN.Err(); err != nil {
+		return nil, err
+	}
+	return responses, nil
+}
+
+// handleUnsolicited confirms an unsolicited response and reports its points
+func (m *DNP3Master) handleUnsolicited(response *dnp3Fragment) error {
+	if response.Control&dnp3AppCON != 0 {
+		if err := m.confirm(response.seq(), true); err != nil {
+			return err
+		}
+	}
+
+	points, err := decodeDNP3Points(response.Objects)
+	if err == nil && len(points) > 0 && m.OnUnsolicited != nil {
+		m.OnUnsolicited(points)
+	}
+	return nil
+}
+
+func (m *DNP3Master) confirm(seq byte, unsolicited bool) error {
+	control := dnp3AppFIR | dnp3AppFIN | seq
+	if unsolicited {
+		control |= dnp3AppUNS
+	}
+	return m.send(appendDNP3Fragment(nil, &dnp3Fragment{Control: control, Function: DNP3Confirm}))
+}
+
+// send segments an application fragment into link frames and writes them
+func (m *DNP3Master) send(fragment []byte) error {
+	var segments [][]byte
+	segments, m.transportSeq = segmentDNP3Fragment(fragment, m.transportSeq)
+
+	if err := m.conn.SetWriteDeadline(time.Now().Add(m.Timeout)); err != nil {
+		return err
+	}
+	defer func() { _ = m.conn.SetWriteDeadline(time.Time{}) }()
+
+	for _, segment := range segments {
+		var err error
+		m.buffer, err = appendDNP3LinkFrame(m.buffer[:0], &dnp3LinkFrame{
+			Control:     dnp3LinkDIR | dnp3LinkPRM | dnp3LinkUnconfirmedUserData,
+			Destination: m.OutstationAddress,
+			Source:      m.MasterAddress,
+			Data:        segment,
+		})
+		if err != nil {
+			return err
+		}
+		if _, err := m.conn.Write(m.buffer); err != nil {
+			return err
+		}
+	}
+	return nil
+}
+
+// receive reads link frames until an application fragment from the
+// outstation is complete. Link status requests are answered on the way.
+func (m *DNP3Master) receive() (*dnp3Fragment, error) {
+	for {
+		frame, err := readDNP3LinkFrame(m.conn)
+		if err != nil {
+			return nil, err
+		}
+		if frame.Source != m.OutstationAddress || frame.Destination != m.MasterAddress {
+			continue
+		}
+
+		switch {
+		case frame.Control&dnp3LinkPRM == 0:
+			continue // Secondary frames answer requests the master never sends
+
+		case frame.Control&0x0F == dnp3LinkRequestStatus:
+			status, err := appendDNP3LinkFrame(nil, &dnp3LinkFrame{
+				Control:     dnp3LinkDIR | dnp3LinkStatus,
+				Destination: m.OutstationAddress,
+				Source:      m.MasterAddress,
+			})
+			if err != nil {
+				return nil, err
+			}
+			if _, err := m.conn.Write(status); err != nil {
+				return nil, err
+			}
+			continue
+
+		case frame.Control&0x0F != dnp3LinkUnconfirmedUserData:
+			continue
+		}
+
+		fragment, err := m.reassembler.add(frame.Data)
+		if err != nil || fragment == nil {
+			continue // Partial or discarded fragment
+		}
+		return parseDNP3Fragment(fragment)
+	}
+}
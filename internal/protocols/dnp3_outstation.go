@@ -0,0 +1,352 @@
+package protocols
+
+import (
+	"encoding/binary"
+	"errors"
+	"io"
+	"net"
+	"sort"
+	"sync"
+	"time"
+)
+
+// DNP3 Outstation
+//
+// DNP3Outstation serves a point database to a master: class 0 and static
+// range reads, class 1 to 3 event polls and direct operate controls. It is
+// the outstation side of the codec, used to simulate field devices and to
+// re-publish gateway data to SCADA masters. Select-before-operate,
+// unsolicited reporting and time synchronization are not implemented.
+
+// Control status codes returned by Operate
+const (
+	DNP3StatusSuccess      byte = 0
+	DNP3StatusNotSupported byte = 4
+)
+
+// DNP3Outstation is an outstation point database
+type DNP3Outstation struct {
+	Address       uint16
+	MasterAddress uint16
+
+	// Operate handles controls on binary (bool) and analog outputs and
+	// returns a control status; nil rejects all controls
+	Operate func(pointType DNP3PointType, index uint16, value interface{}) byte
+
+	mutex        sync.Mutex
+	points       map[DNP3PointType]map[uint16]*DNP3Point
+	events       [4][]*DNP3Point // By class; class 0 is unused
+	transportSeq byte
+	reassembler  dnp3Reassembler
+}
+
+// NewDNP3Outstation creates an outstation with an empty point database
+func NewDNP3Outstation(address, masterAddress uint16) *DNP3Outstation {
+	return &DNP3Outstation{
+		Address:       address,
+		MasterAddress: masterAddress,
+		points:        make(map[DNP3PointType]map[uint16]*DNP3Point),
+	}
+}
+
+// Update sets the static value of a point and, for classes 1 to 3, queues
+// an event carrying the point's timestamp (or the current time)
+func (o *DNP3Outstation) Update(point *DNP3Point, class int) {
+	o.mutex.Lock()
+	defer o.mutex.Unlock()
+
+	static := *point
+	static.Event, static.Timestamp = false, time.Time{}
+	if o.points[point.Type] == nil {
+		o.points[point.Type] = make(map[uint16]*DNP3Point)
+	}
+	o.points[point.Type][point.Index] = &static
+
+	if class >= 1 && class <= 3 {
+		event := *point
+		event.Event = true
+		if event.Timestamp.IsZero() {
+			event.Timestamp = time.Now()
+		}
+		o.events[class] = append(o.events[class], &event)
+	}
+}
+
+// Serve answers requests on conn until it is closed
+func (o *DNP3Outstation) Serve(conn net.Conn) error {
+	for {
+		frame, err := readDNP3LinkFrame(conn)
+		if errors.Is(err, io.EOF) {
+			return nil
+		}
+		if err != nil {
+			return err
+		}
+		if frame.Destination != o.Address || frame.Control&dnp3LinkPRM == 0 || frame.Control&0x0F != dnp3LinkUnconfirmedUserData {
+			continue
+		}
+
+		data, err := o.reassembler.add(frame.Data)
+		if err != nil || data == nil {
+			continue
+		}
+		request, err := parseDNP3Fragment(data)
+		if err != nil || request.Function == DNP3Confirm {
+			continue
+		}
+
+		response := o.handle(request)
+		if err := o.send(conn, appendDNP3Fragment(nil, response)); err != nil {
+			return err
+		}
+	}
+}
+
+// handle builds the response to one request
+func (o *DNP3Outstation) handle(request *dnp3Fragment) *dnp3Fragment {
+	response := &dnp3Fragment{Control: dnp3AppFIR | dnp3AppFIN | request.seq(), Function: DNP3Response}
+
+	var err error
+	switch request.Function {
+	case DNP3Read:
+		var events bool
+		o.mutex.Lock()
+		response.Objects, events, err = o.read(request.Objects)
+		o.mutex.Unlock()
+		if events {
+			// Events are discarded as they are reported; the confirm is
+			// requested for conformance but not waited for
+			response.Control |= dnp3AppCON
+		}
+	case DNP3DirectOperate:
+		// Not under the lock, so Operate can update the database
+		response.Objects, err = o.operate(request.Objects)
+	default:
+		response.IIN |= DNP3IINNoFuncSupport
+	}
+	if err != nil {
+		response.Objects = nil
+		response.IIN |= DNP3IINObjectUnknown
+	}
+
+	o.mutex.Lock()
+	for class, flag := range []DNP3IIN{0, DNP3IINClass1Events, DNP3IINClass2Events, DNP3IINClass3Events} {
+		if len(o.events[class]) > 0 {
+			response.IIN |= flag
+		}
+	}
+	o.mutex.Unlock()
+	return response
+}
+
+// read answers class polls and static reads
+func (o *DNP3Outstation) read(objects []byte) ([]byte, bool, error) {
+	var response []byte
+	events := false
+
+	err := walkDNP3Objects(objects, func(group, variation byte) int { return 0 }, func(header *dnp3ObjectHeader, _ [][]byte) error {
+		var points []*DNP3Point
+		switch {
+		case header.Group == 60 && header.Variation == 1:
+			for _, pointType := range []DNP3PointType{DNP3BinaryInput, DNP3BinaryOutput, DNP3Counter, DNP3AnalogInput, DNP3AnalogOutput} {
+				points = append(points, o.staticPoints(pointType, nil)...)
+			}
+		case header.Group == 60 && header.Variation >= 2 && header.Variation <= 4:
+			class := header.Variation - 1
+			points, o.events[class] = o.events[class], nil
+			events = events || len(points) > 0
+		default:
+			pointType, exists := dnp3StaticPointType(header.Group)
+			if !exists {
+				return errors.New("unsupported object")
+			}
+			var indexes []uint16
+			if header.Qualifier != dnp3QualifierAll {
+				indexes = header.Indexes
+			}
+			points = o.staticPoints(pointType, indexes)
+		}
+
+		var err error
+		response, err = appendDNP3PointsGrouped(response, points)
+		return err
+	})
+	return response, events, err
+}
+
+// operate executes controls and echoes them with their status
+func (o *DNP3Outstation) operate(objects []byte) ([]byte, error) {
+	var response []byte
+	err := walkDNP3Objects(objects, dnp3ResponseObjectSize, func(header *dnp3ObjectHeader, controls [][]byte) error {
+		if header.Qualifier != dnp3Qualifier16BitIndexed && header.Qualifier != dnp3Qualifier8BitIndexed {
+			return errors.New("unsupported qualifier")
+		}
+
+		response = append(response, header.Group, header.Variation, header.Qualifier)
+		if header.Qualifier == dnp3Qualifier8BitIndexed {
+			response = append(response, byte(len(controls)))
+		} else {
+			response = binary.LittleEndian.AppendUint16(response, uint16(len(controls)))
+		}
+
+		for i, control := range controls {
+			index := header.Indexes[i]
+			status := o.control(header.Group, header.Variation, index, control)
+
+			if header.Qualifier == dnp3Qualifier8BitIndexed {
+				response = append(response, byte(index))
+			} else {
+				response = binary.LittleEndian.AppendUint16(response, index)
+			}
+			response = append(response, control[:len(control)-1]...)
+			response = append(response, status)
+		}
+		return nil
+	})
+	return response, err
+}
+
+// control decodes one control object and passes it to Operate
+func (o *DNP3Outstation) control(group, variation byte, index uint16, object []byte) byte {
+	if o.Operate == nil {
+		return DNP3StatusNotSupported
+	}
+
+	switch uint16(group)<<8 | uint16(variation) {
+	case 12<<8 | 1:
+		switch object[0] & 0x0F {
+		case dnp3ControlLatchOn:
+			return o.Operate(DNP3BinaryOutput, index, true)
+		case dnp3ControlLatchOff:
+			return o.Operate(DNP3BinaryOutput, index, false)
+		}
+	case 41<<8 | 1:
+		return o.Operate(DNP3AnalogOutput, index, int32(binary.LittleEndian.Uint32(object)))
+	case 41<<8 | 2:
+		return o.Operate(DNP3AnalogOutput, index, int32(int16(binary.LittleEndian.Uint16(object))))
+	case 41<<8 | 3:
+		point := &DNP3Point{}
+		dnp3Formats[40<<8|3].decode(append([]byte{0}, object[:4]...), point)
+		return o.Operate(DNP3AnalogOutput, index, point.Value)
+	case 41<<8 | 4:
+		point := &DNP3Point{}
+		dnp3Formats[40<<8|4].decode(append([]byte{0}, object[:8]...), point)
+		return o.Operate(DNP3AnalogOutput, index, point.Value)
+	}
+	return DNP3StatusNotSupported
+}
+
+// staticPoints returns the points of a type, all of them when indexes is
+// nil, ordered by index
+func (o *DNP3Outstation) staticPoints(pointType DNP3PointType, indexes []uint16) []*DNP3Point {
+	var points []*DNP3Point
+	if indexes == nil {
+		for _, point := range o.points[pointType] {
+			points = append(points, point)
+		}
+		sort.Slice(points, func(i, j int) bool { return points[i].Index < points[j].Index })
+		return points
+	}
+
+	for _, index := range indexes {
+		if point, exists := o.points[pointType][index]; exists {
+			points = append(points, point)
+		}
+	}
+	return points
+}
+
+func (o *DNP3Outstation) send(conn net.Conn, fragment []byte) error {
+	var segments [][]byte
+	segments, o.transportSeq = segmentDNP3Fragment(fragment, o.transportSeq)
+
+	for _, segment := range segments {
+		frame, err := appendDNP3LinkFrame(nil, &dnp3LinkFrame{
+			Control:     dnp3LinkPRM | dnp3LinkUnconfirmedUserData,
+			Destination: o.MasterAddress,
+			Source:      o.Address,
+			Data:        segment,
+		})
+		if err != nil {
+			return err
+		}
+		if _, err := conn.Write(frame); err != nil {
+			return err
+		}
+	}
+	return nil
+}
+
+// appendDNP3PointsGrouped encodes points in one object header per run of
+// points sharing a group and variation
+func appendDNP3PointsGrouped(dst []byte, points []*DNP3Point) ([]byte, error) {
+	for len(points) > 0 {
+		group, variation := dnp3Variation(points[0])
+		n := 1
+		for n < len(points) {
+			if g, v := dnp3Variation(points[n]); g != group || v != variation {
+				break
+			}
+			n++
+		}
+
+		var err error
+		if dst, err = appendDNP3Points(dst, group, variation, points[:n]); err != nil {
+			return nil, err
+		}
+		points = points[n:]
+	}
+	return dst, nil
+}
+
+// dnp3Variation returns the group and variation an outstation reports a
+// point with: flags always, time on events, and floating point for
+// analogs holding floats
+func dnp3Variation(point *DNP3Point) (byte, byte) {
+	float := false
+	switch point.Value.(type) {
+	case float32, float64:
+		float = true
+	}
+
+	switch point.Type {
+	case DNP3BinaryInput:
+		if point.Event {
+			return 2, 2
+		}
+		return 1, 2
+	case DNP3BinaryOutput:
+		return 10, 2
+	case DNP3Counter:
+		if point.Event {
+			return 22, 5
+		}
+		return 20, 1
+	case DNP3AnalogInput:
+		switch {
+		case point.Event && float:
+			return 32, 7
+		case point.Event:
+			return 32, 3
+		case float:
+			return 30, 5
+		}
+		return 30, 1
+	case DNP3AnalogOutput:
+		if float {
+			return 40, 3
+		}
+		return 40, 1
+	}
+	return 0, 0
+}
+
+// dnp3StaticPointType returns the point type of a static group
+func dnp3StaticPointType(group byte) (DNP3PointType, bool) {
+	for pointType, staticGroup := range dnp3StaticGroups {
+		if staticGroup == group {
+			return pointType, true
+		}
+	}
+	return "", false
+}
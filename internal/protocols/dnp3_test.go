@@ -0,0 +1,179 @@
+package protocols
+
+import (
+	"bytes"
+	"net"
+	"testing"
+	"time"
+
+	"github.com/stretchr/testify/assert"
+)
+
+func TestDNP3CRC(t *testing.T) {
+	assert.Equal(t, uint16(0xEA82), dnp3CRC([]byte("123456789")))
+}
+
+func TestDNP3LinkFrame_RoundTrip(t *testing.T) {
+	data := make([]byte, 40) // Three CRC blocks
+	for i := range data {
+		data[i] = byte(i)
+	}
+
+	encoded, err := appendDNP3LinkFrame(nil, &dnp3LinkFrame{Control: 0xC4, Destination: 10, Source: 1, Data: data})
+	assert.NoError(t, err)
+	assert.Equal(t, dnp3LinkHeaderSize+len(data)+3*2, len(encoded))
+
+	frame, err := readDNP3LinkFrame(bytes.NewReader(encoded))
+	assert.NoError(t, err)
+	assert.Equal(t, &dnp3LinkFrame{Control: 0xC4, Destination: 10, Source: 1, Data: data}, frame)
+
+	encoded[20] ^= 0xFF
+	_, err = readDNP3LinkFrame(bytes.NewReader(encoded))
+	assert.Error(t, err)
+
+	_, err = appendDNP3LinkFrame(nil, &dnp3LinkFrame{Data: make([]byte, dnp3MaxUserData+1)})
+	assert.Error(t, err)
+}
+
+func TestDNP3Transport_Reassembly(t *testing.T) {
+	fragment := make([]byte, 600)
+	for i := range fragment {
+		fragment[i] = byte(i * 7)
+	}
+
+	segments, next := segmentDNP3Fragment(fragment, 62)
+	assert.Len(t, segments, 3)
+	assert.Equal(t, byte(1), next) // Wraps at 64
+
+	var r dnp3Reassembler
+	for i, segment := range segments {
+		result, err := r.add(segment)
+		assert.NoError(t, err)
+		if i < len(segments)-1 {
+			assert.Nil(t, result)
+		} else {
+			assert.Equal(t, fragment, result)
+		}
+	}
+
+	// A lost segment discards the fragment
+	_, err := r.add(segments[0])
+	assert.NoError(t, err)
+	_, err = r.add(segments[2])
+	assert.Error(t, err)
+}
+
+func TestDNP3Points_RoundTrip(t *testing.T) {
+	timestamp := time.UnixMilli(1700000000123).UTC()
+	points := []*DNP3Point{
+		{Type: DNP3AnalogInput, Index: 3, Value: float32(12.5), Flags: DNP3FlagOnline, Event: true, Timestamp: timestamp},
+		{Type: DNP3AnalogInput, Index: 300, Value: float32(-1), Flags: DNP3FlagOnline | DNP3FlagOverRange, Event: true, Timestamp: timestamp},
+	}
+
+	encoded, err := appendDNP3Points(nil, 32, 7, points)
+	assert.NoError(t, err)
+
+	decoded, err := decodeDNP3Points(encoded)
+	assert.NoError(t, err)
+	assert.Equal(t, points, decoded)
+	assert.Equal(t, QualityUncertain, decoded[1].Quality())
+
+	// Static analogs without flags are reported online
+	decoded, err = decodeDNP3Points([]byte{30, 4, dnp3Qualifier8BitStartStop, 5, 6, 0x01, 0x00, 0xFF, 0xFF})
+	assert.NoError(t, err)
+	assert.Equal(t, []*DNP3Point{
+		{Type: DNP3AnalogInput, Index: 5, Value: int32(1), Flags: DNP3FlagOnline},
+		{Type: DNP3AnalogInput, Index: 6, Value: int32(-1), Flags: DNP3FlagOnline},
+	}, decoded)
+
+	_, err = decodeDNP3Points([]byte{99, 1, dnp3Qualifier8BitStartStop, 0, 0, 0x00})
+	assert.Error(t, err)
+}
+
+// newDNP3TestSession connects a master to an outstation over a pipe
+func newDNP3TestSession(t *testing.T) (*DNP3Master, *DNP3Outstation) {
+	masterConn, outstationConn := net.Pipe()
+	t.Cleanup(func() {
+		masterConn.Close()
+		outstationConn.Close()
+	})
+
+	outstation := NewDNP3Outstation(10, 1)
+	go func() { _ = outstation.Serve(outstationConn) }()
+
+	return NewDNP3Master(masterConn, 1, 10, time.Second), outstation
+}
+
+func TestDNP3Master_Polling(t *testing.T) {
+	master, outstation := newDNP3TestSession(t)
+
+	eventTime := time.UnixMilli(1700000000000).UTC()
+	outstation.Update(&DNP3Point{Type: DNP3BinaryInput, Index: 0, Value: true, Flags: DNP3FlagOnline}, 0)
+	outstation.Update(&DNP3Point{Type: DNP3AnalogInput, Index: 1, Value: float32(4.5), Flags: DNP3FlagOnline}, 0)
+	outstation.Update(&DNP3Point{Type: DNP3Counter, Index: 2, Value: uint32(1000), Flags: DNP3FlagOnline | DNP3FlagCommLost}, 0)
+	outstation.Update(&DNP3Point{Type: DNP3AnalogInput, Index: 1, Value: float32(7.25), Flags: DNP3FlagOnline, Timestamp: eventTime}, 2)
+
+	point, err := master.ReadPoint(DNP3AnalogInput, 1)
+	assert.NoError(t, err)
+	assert.Equal(t, float32(7.25), point.Value)
+	assert.True(t, master.IIN()&DNP3IINClass2Events != 0)
+
+	events, err := master.PollEvents(2)
+	assert.NoError(t, err)
+	if assert.Len(t, events, 1) {
+		assert.True(t, events[0].Event)
+		assert.Equal(t, eventTime, events[0].Timestamp)
+		assert.Equal(t, QualityGood, events[0].Quality())
+	}
+	assert.False(t, master.IIN().HasEvents())
+
+	points, err := master.IntegrityPoll()
+	assert.NoError(t, err)
+	assert.Len(t, points, 3)
+	for _, point := range points {
+		if point.Type == DNP3Counter {
+			assert.Equal(t, uint32(1000), point.Value)
+			assert.Equal(t, QualityStale, point.Quality())
+		}
+	}
+
+	_, err = master.read(appendDNP3RangeHeader(nil, 99, 0, 0, 0))
+	assert.Error(t, err)
+}
+
+func TestDNP3Master_Operate(t *testing.T) {
+	master, outstation := newDNP3TestSession(t)
+
+	var operated []interface{}
+	outstation.Operate = func(pointType DNP3PointType, index uint16, value interface{}) byte {
+		if index != 0 {
+			return DNP3StatusNotSupported
+		}
+		operated = append(operated, value)
+		outstation.Update(&DNP3Point{Type: pointType, Index: index, Value: value, Flags: DNP3FlagOnline}, 0)
+		return DNP3StatusSuccess
+	}
+
+	assert.NoError(t, master.OperateBinary(0, true))
+	assert.NoError(t, master.OperateAnalog(0, 42))
+	assert.NoError(t, master.OperateAnalog(0, 1.5))
+	assert.Equal(t, []interface{}{true, int32(42), float32(1.5)}, operated)
+
+	point, err := master.ReadPoint(DNP3BinaryOutput, 0)
+	assert.NoError(t, err)
+	assert.Equal(t, true, point.Value)
+
+	assert.Error(t, master.OperateBinary(5, true))
+}
+
+func TestParseDNP3Address(t *testing.T) {
+	pointType, index, err := parseDNP3Address("ai:12")
+	assert.NoError(t, err)
+	assert.Equal(t, DNP3AnalogInput, pointType)
+	assert.Equal(t, uint16(12), index)
+
+	for _, invalid := range []string{"AI", "XX:1", "AI:-1", "AI:70000"} {
+		_, _, err := parseDNP3Address(invalid)
+		assert.Error(t, err, invalid)
+	}
+}
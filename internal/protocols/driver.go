@@ -0,0 +1,282 @@
+package protocols
+
+import (
+	"context"
+	"fmt"
+	"reflect"
+	"sort"
+	"sync"
+	"time"
+
+	"go.uber.org/zap"
+)
+
+// ProtocolDriver is the interface the gateway works against for every
+// protocol: the handler operations for connecting, discovering, reading
+// and writing, plus subscriptions to tag changes and a health check.
+// Drivers are created from handlers by the DriverRegistry.
+type ProtocolDriver interface {
+	ProtocolHandler
+
+	// Protocol returns the name the driver is registered under
+	Protocol() string
+
+	// Subscribe calls onChange with the tags whose value or quality
+	// changed until ctx is done or the subscription is cancelled
+	Subscribe(ctx context.Context, device *Device, tags []*Tag, interval time.Duration, onChange TagChangeFunc) (Subscription, error)
+
+	// Health pings a device and returns its diagnostics
+	Health(device *Device) *DriverHealth
+}
+
+// TagChangeFunc receives changed tags. The tags are copies owned by the
+// callee.
+type TagChangeFunc func(device *Device, tags []*Tag)
+
+// Subscription is an active subscription to tag changes
+type Subscription interface {
+	// Cancel stops the subscription and waits until onChange is no
+	// longer called
+	Cancel()
+}
+
+// TagSubscriber is implemented by handlers whose protocol reports changes
+// itself, such as MQTT or KNX group monitoring; drivers of other handlers
+// poll at the subscription interval.
+type TagSubscriber interface {
+	SubscribeTags(ctx context.Context, device *Device, tags []*Tag, onChange TagChangeFunc) (Subscription, error)
+}
+
+// DriverHealth is the result of a driver health check
+type DriverHealth struct {
+	Protocol    string        `json:"protocol"`
+	Connected   bool          `json:"connected"`
+	Healthy     bool          `json:"healthy"`
+	Latency     time.Duration `json:"latency"`
+	Error       string        `json:"error,omitempty"`
+	Diagnostics *Diagnostics  `json:"diagnostics,omitempty"`
+	CheckedAt   time.Time     `json:"checked_at"`
+}
+
+// DriverFactory creates the handler of a protocol, e.g. NewModbusHandler
+type DriverFactory func(logger *zap.Logger) ProtocolHandler
+
+// DriverRegistry creates the drivers of registered protocols. Each
+// factory is called once, on first use, and its driver is shared by the
+// protocol's aliases.
+type DriverRegistry struct {
+	logger *zap.Logger
+
+	mutex    sync.RWMutex
+	entries  map[string]*driverEntry
+	onCreate func(protocol string, handler ProtocolHandler)
+}
+
+type driverEntry struct {
+	name    string
+	factory DriverFactory
+	once    sync.Once
+	driver  ProtocolDriver
+}
+
+// NewDriverRegistry creates an empty registry
+func NewDriverRegistry(logger *zap.Logger) *DriverRegistry {
+	return &DriverRegistry{
+		logger:  logger,
+		entries: make(map[string]*driverEntry),
+	}
+}
+
+// Register registers a protocol and its aliases
+func (r *DriverRegistry) Register(name string, factory DriverFactory, aliases ...string) error {
+	r.mutex.Lock()
+	defer r.mutex.Unlock()
+
+	names := append([]string{name}, aliases...)
+	for _, registered := range names {
+		if registered == "" {
+			return fmt.Errorf("protocol name must not be empty")
+		}
+		if _, exists := r.entries[registered]; exists {
+			return fmt.Errorf("protocol %s is already registered", registered)
+		}
+	}
+
+	entry := &driverEntry{name: name, factory: factory}
+	for _, registered := range names {
+		r.entries[registered] = entry
+	}
+	return nil
+}
+
+// MustRegister registers a protocol and panics if its name is taken
+func (r *DriverRegistry) MustRegister(name string, factory DriverFactory, aliases ...string) {
+	if err := r.Register(name, factory, aliases...); err != nil {
+		panic(err)
+	}
+}
+
+// Driver returns the driver of a protocol or one of its aliases
+func (r *DriverRegistry) Driver(protocol string) (ProtocolDriver, bool) {
+	r.mutex.RLock()
+	entry, exists := r.entries[protocol]
+	r.mutex.RUnlock()
+	if !exists {
+		return nil, false
+	}
+
+	entry.once.Do(func() {
+		handler := entry.factory(r.logger.With(zap.String("protocol", entry.name)))
+		entry.driver = NewProtocolDriver(entry.name, handler)
+
+		r.mutex.RLock()
+		onCreate := r.onCreate
+		r.mutex.RUnlock()
+		if onCreate != nil {
+			onCreate(entry.name, handler)
+		}
+	})
+	return entry.driver, true
+}
+
+// OnCreate sets a function called with the handler of each driver the
+// registry creates, e.g. to account the memory of its caches
+func (r *DriverRegistry) OnCreate(fn func(protocol string, handler ProtocolHandler)) {
+	r.mutex.Lock()
+	defer r.mutex.Unlock()
+	r.onCreate = fn
+}
+
+// Protocols returns the registered protocol names and aliases in order
+func (r *DriverRegistry) Protocols() []string {
+	r.mutex.RLock()
+	defer r.mutex.RUnlock()
+
+	names := make([]string, 0, len(r.entries))
+	for name := range r.entries {
+		names = append(names, name)
+	}
+	sort.Strings(names)
+	return names
+}
+
+// NewProtocolDriver creates the driver of a handler
+func NewProtocolDriver(protocol string, handler ProtocolHandler) ProtocolDriver {
+	if driver, ok := handler.(ProtocolDriver); ok {
+		return driver
+	}
+	return &handlerDriver{ProtocolHandler: handler, protocol: protocol}
+}
+
+// handlerDriver adapts a ProtocolHandler to a ProtocolDriver
+type handlerDriver struct {
+	ProtocolHandler
+	protocol string
+}
+
+func (d *handlerDriver) Protocol() string {
+	return d.protocol
+}
+
+// Subscribe uses the handler's subscriptions if it has them and polls the
+// tags otherwise
+func (d *handlerDriver) Subscribe(ctx context.Context, device *Device, tags []*Tag, interval time.Duration, onChange TagChangeFunc) (Subscription, error) {
+	if !d.IsConnected(device) {
+		return nil, fmt.Errorf("device not connected")
+	}
+	if subscriber, ok := d.ProtocolHandler.(TagSubscriber); ok {
+		return subscriber.SubscribeTags(ctx, device, tags, onChange)
+	}
+	if interval <= 0 {
+		return nil, fmt.Errorf("invalid poll interval %s", interval)
+	}
+
+	ctx, cancel := context.WithCancel(ctx)
+	subscription := &pollingSubscription{cancel: cancel, done: make(chan struct{})}
+	go subscription.poll(ctx, d, device, tags, interval, onChange)
+	return subscription, nil
+}
+
+// Health pings a device and returns its diagnostics
+func (d *handlerDriver) Health(device *Device) *DriverHealth {
+	health := &DriverHealth{
+		Protocol:  d.protocol,
+		Connected: d.IsConnected(device),
+		CheckedAt: time.Now(),
+	}
+	if !health.Connected {
+		health.Error = "device not connected"
+		return health
+	}
+
+	start := time.Now()
+	err := d.Ping(device)
+	health.Latency = time.Since(start)
+	if err != nil {
+		health.Error = err.Error()
+	}
+
+	health.Healthy = err == nil
+	if diagnostics, diagErr := d.GetDiagnostics(device); diagErr == nil {
+		health.Diagnostics = diagnostics
+		health.Healthy = health.Healthy && diagnostics.IsHealthy
+	}
+	return health
+}
+
+// pollingSubscription reads tags at an interval and reports changes
+type pollingSubscription struct {
+	cancel context.CancelFunc
+	done   chan struct{}
+}
+
+func (s *pollingSubscription) Cancel() {
+	s.cancel()
+	<-s.done
+}
+
+func (s *pollingSubscription) poll(ctx context.Context, driver ProtocolDriver, device *Device, tags []*Tag, interval time.Duration, onChange TagChangeFunc) {
+	defer close(s.done)
+
+	ticker := time.NewTicker(interval)
+	defer ticker.Stop()
+
+	last := make(map[string]*Tag, len(tags))
+	for {
+		values, err := driver.ReadMultipleTags(device, tags)
+		now := DefaultClock.Now()
+
+		var changed []*Tag
+		for _, tag := range tags {
+			current := *tag
+			current.Timestamp = now
+			if value, exists := values[tag.ID]; exists && err == nil {
+				current.Value = value
+				if current.Quality == "" {
+					current.SetStatus(GoodQuality)
+				}
+			} else {
+				current.SetStatus(NewQualityStatus(QualityBad, QualitySubstatusCommFailure, QualitySourceDriver))
+				if previous, exists := last[tag.ID]; exists {
+					current.Value = previous.Value
+				}
+			}
+
+			previous, exists := last[tag.ID]
+			if !exists || previous.Status() != current.Status() || !reflect.DeepEqual(previous.Value, current.Value) {
+				last[tag.ID] = &current
+				update := current
+				changed = append(changed, &update)
+			}
+		}
+		if len(changed) > 0 {
+			onChange(device, changed)
+		}
+
+		select {
+		case <-ctx.Done():
+			return
+		case <-ticker.C:
+		}
+	}
+}
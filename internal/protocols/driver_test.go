@@ -0,0 +1,249 @@
+package protocols
+
+import (
+	"context"
+	"errors"
+	"sync"
+	"testing"
+	"time"
+
+	"github.com/stretchr/testify/assert"
+	"go.uber.org/zap"
+)
+
+// driverTestHandler is a handler whose tag values are set by the test
+type driverTestHandler struct {
+	mutex     sync.Mutex
+	connected bool
+	values    map[string]interface{}
+	pingErr   error
+}
+
+func newDriverTestHandler(logger *zap.Logger) ProtocolHandler {
+	return &driverTestHandler{values: make(map[string]interface{})}
+}
+
+func (h *driverTestHandler) set(address string, value interface{}) {
+	h.mutex.Lock()
+	defer h.mutex.Unlock()
+	if value == nil {
+		delete(h.values, address)
+	} else {
+		h.values[address] = value
+	}
+}
+
+func (h *driverTestHandler) Connect(device *Device) error {
+	h.mutex.Lock()
+	defer h.mutex.Unlock()
+	h.connected = true
+	return nil
+}
+
+func (h *driverTestHandler) Disconnect(device *Device) error {
+	h.mutex.Lock()
+	defer h.mutex.Unlock()
+	h.connected = false
+	return nil
+}
+
+func (h *driverTestHandler) IsConnected(device *Device) bool {
+	h.mutex.Lock()
+	defer h.mutex.Unlock()
+	return h.connected
+}
+
+func (h *driverTestHandler) ReadTag(device *Device, tag *Tag) (interface{}, error) {
+	values, _ := h.ReadMultipleTags(device, []*Tag{tag})
+	if value, exists := values[tag.ID]; exists {
+		return value, nil
+	}
+	return nil, errors.New("no value")
+}
+
+func (h *driverTestHandler) WriteTag(device *Device, tag *Tag, value interface{}) error {
+	h.set(tag.Address, value)
+	return nil
+}
+
+func (h *driverTestHandler) ReadMultipleTags(device *Device, tags []*Tag) (map[string]interface{}, error) {
+	h.mutex.Lock()
+	defer h.mutex.Unlock()
+
+	results := make(map[string]interface{})
+	for _, tag := range tags {
+		if value, exists := h.values[tag.Address]; exists {
+			results[tag.ID] = value
+		}
+	}
+	return results, nil
+}
+
+func (h *driverTestHandler) DiscoverDevices(ctx context.Context, networkRange string) ([]*Device, error) {
+	return nil, nil
+}
+
+func (h *driverTestHandler) GetDeviceInfo(device *Device) (*DeviceInfo, error) {
+	return &DeviceInfo{}, nil
+}
+
+func (h *driverTestHandler) GetSupportedDataTypes() []string {
+	return []string{string(DataTypeFloat64)}
+}
+
+func (h *driverTestHandler) ValidateTagAddress(address string) error {
+	return nil
+}
+
+func (h *driverTestHandler) Ping(device *Device) error {
+	h.mutex.Lock()
+	defer h.mutex.Unlock()
+	return h.pingErr
+}
+
+func (h *driverTestHandler) GetDiagnostics(device *Device) (*Diagnostics, error) {
+	return &Diagnostics{IsHealthy: true}, nil
+}
+
+// driverTestSubscriber reports changes itself
+type driverTestSubscriber struct {
+	*driverTestHandler
+	onChange TagChangeFunc
+}
+
+func (s *driverTestSubscriber) SubscribeTags(ctx context.Context, device *Device, tags []*Tag, onChange TagChangeFunc) (Subscription, error) {
+	s.onChange = onChange
+	return &pollingSubscription{cancel: func() {}, done: make(chan struct{})}, nil
+}
+
+func TestDriverRegistry(t *testing.T) {
+	registry := NewDriverRegistry(zap.NewNop())
+	created := 0
+	factory := func(logger *zap.Logger) ProtocolHandler {
+		created++
+		return newDriverTestHandler(logger)
+	}
+
+	assert.NoError(t, registry.Register("test-tcp", factory, "test-rtu"))
+	assert.Error(t, registry.Register("test-rtu", factory))
+	assert.Error(t, registry.Register("other", factory, "test-tcp"))
+	assert.Error(t, registry.Register("", factory))
+	assert.Panics(t, func() { registry.MustRegister("test-tcp", factory) })
+	assert.Equal(t, []string{"test-rtu", "test-tcp"}, registry.Protocols())
+	assert.Equal(t, 0, created)
+
+	driver, exists := registry.Driver("test-rtu")
+	assert.True(t, exists)
+	assert.Equal(t, "test-tcp", driver.Protocol())
+	other, _ := registry.Driver("test-tcp")
+	assert.Same(t, driver, other)
+	assert.Equal(t, 1, created)
+
+	_, exists = registry.Driver("missing")
+	assert.False(t, exists)
+}
+
+func TestDriverRegistry_OnCreate(t *testing.T) {
+	registry := NewDriverRegistry(zap.NewNop())
+	registry.MustRegister("test-tcp", newDriverTestHandler, "test-rtu")
+
+	created := make(map[string]ProtocolHandler)
+	registry.OnCreate(func(protocol string, handler ProtocolHandler) {
+		created[protocol] = handler
+	})
+	assert.Empty(t, created)
+
+	registry.Driver("test-rtu")
+	registry.Driver("test-tcp")
+	if assert.Len(t, created, 1) {
+		assert.IsType(t, &driverTestHandler{}, created["test-tcp"])
+	}
+}
+
+func TestProtocolDriver_Health(t *testing.T) {
+	handler := newDriverTestHandler(zap.NewNop()).(*driverTestHandler)
+	driver := NewProtocolDriver("test", handler)
+	device := &Device{ID: "device"}
+
+	health := driver.Health(device)
+	assert.False(t, health.Connected)
+	assert.False(t, health.Healthy)
+	assert.Equal(t, "test", health.Protocol)
+
+	assert.NoError(t, driver.Connect(device))
+	health = driver.Health(device)
+	assert.True(t, health.Healthy)
+	assert.NotNil(t, health.Diagnostics)
+
+	handler.mutex.Lock()
+	handler.pingErr = errors.New("timeout")
+	handler.mutex.Unlock()
+	health = driver.Health(device)
+	assert.True(t, health.Connected)
+	assert.False(t, health.Healthy)
+	assert.Equal(t, "timeout", health.Error)
+}
+
+func TestProtocolDriver_Subscribe(t *testing.T) {
+	handler := newDriverTestHandler(zap.NewNop()).(*driverTestHandler)
+	driver := NewProtocolDriver("test", handler)
+	device := &Device{ID: "device"}
+	tags := []*Tag{{ID: "a", Address: "a"}, {ID: "b", Address: "b"}}
+
+	_, err := driver.Subscribe(context.Background(), device, tags, 10*time.Millisecond, func(*Device, []*Tag) {})
+	assert.Error(t, err)
+	assert.NoError(t, driver.Connect(device))
+	_, err = driver.Subscribe(context.Background(), device, tags, 0, func(*Device, []*Tag) {})
+	assert.Error(t, err)
+
+	changes := make(chan []*Tag, 10)
+	handler.set("a", 1.0)
+	subscription, err := driver.Subscribe(context.Background(), device, tags, 10*time.Millisecond, func(_ *Device, changed []*Tag) {
+		changes <- changed
+	})
+	assert.NoError(t, err)
+
+	// The first poll reports every tag
+	changed := <-changes
+	if assert.Len(t, changed, 2) {
+		assert.Equal(t, 1.0, changed[0].Value)
+		assert.Equal(t, QualityGood, changed[0].Quality)
+		assert.Equal(t, QualityBad, changed[1].Quality)
+	}
+
+	// Later polls report only changes
+	handler.set("b", 2.0)
+	changed = <-changes
+	if assert.Len(t, changed, 1) {
+		assert.Equal(t, "b", changed[0].ID)
+		assert.Equal(t, 2.0, changed[0].Value)
+	}
+	handler.set("a", nil)
+	changed = <-changes
+	if assert.Len(t, changed, 1) {
+		assert.Equal(t, QualityBad, changed[0].Quality)
+		assert.Equal(t, 1.0, changed[0].Value)
+	}
+
+	subscription.Cancel()
+	handler.set("a", 3.0)
+	select {
+	case changed = <-changes:
+		t.Fatalf("change after cancel: %v", changed)
+	case <-time.After(50 * time.Millisecond):
+	}
+
+	// Handlers with their own subscriptions are not polled
+	subscriber := &driverTestSubscriber{driverTestHandler: handler}
+	driver = NewProtocolDriver("test", subscriber)
+	_, err = driver.Subscribe(context.Background(), device, tags, time.Millisecond, func(_ *Device, changed []*Tag) {
+		changes <- changed
+	})
+	assert.NoError(t, err)
+	assert.NotNil(t, subscriber.onChange)
+	select {
+	case changed = <-changes:
+		t.Fatalf("polled change: %v", changed)
+	case <-time.After(20 * time.Millisecond):
+	}
+}
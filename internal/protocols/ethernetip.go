@@ -3,6 +3,7 @@ package protocols
 import (
 	"context"
 	"fmt"
+	"math/rand"
 	"net"
 	"strconv"
 	"strings"
@@ -18,6 +19,9 @@ type EtherNetIPHandler struct {
 	connections sync.Map // map[string]*EtherNetIPConnection
 	config      *EtherNetIPConfig
 	sessions    sync.Map // map[string]*CIPSession
+
+	// originatorSerial identifies this gateway in Forward Open requests
+	originatorSerial uint32
 }
 
 // EtherNetIPConnection represents an EtherNet/IP connection with CIP session management
@@ -36,6 +40,13 @@ type EtherNetIPConnection struct {
 	createdAt      time.Time
 	sequenceNumber uint16
 
+	// Connected messaging state; connectionID is zero when requests are
+	// sent unconnected
+	route            []byte // Port segment to the controller, e.g. backplane slot
+	toConnectionID   uint32
+	connectionSerial uint16
+	tagTypes         map[string]logixTagType
+
 	// CIP-specific state
 	vendorID     uint16
 	deviceType   uint16
@@ -128,6 +139,7 @@ const (
 	CIPDataTypeReal   = 0xCA
 	CIPDataTypeLreal  = 0xCB
 	CIPDataTypeString = 0xD0
+	CIPDataTypeDword  = 0xD3
 	CIPDataTypeStruct = 0xA0
 
 	// Default ports
@@ -145,12 +157,12 @@ type CIPEncapsulationHeader struct {
 	Options       uint32
 }
 
-// CIP Common Packet Format
+// CIP Common Packet Format with an address item and a data item
 type CIPCommonPacketFormat struct {
-	ItemCount uint16
-	TypeID    uint16
-	Length    uint16
-	Data      []byte
+	AddressType uint16
+	AddressData []byte
+	DataType    uint16
+	Data        []byte
 }
 
 // CIP Request/Response structures
@@ -206,6 +218,7 @@ func NewEtherNetIPHandler(logger *zap.Logger) ProtocolHandler {
 			EnableImplicitIO:  true,
 			MaxPacketSize:     1500,
 		},
+		originatorSerial: rand.Uint32(),
 	}
 }
 
@@ -256,6 +269,12 @@ func (e *EtherNetIPHandler) Connect(device *Device) error {
 
 	conn.sessionID = sessionID
 
+	// Controllers in a chassis are reached through the backplane port of
+	// the Ethernet module (port 1, slot address)
+	if slot, ok := device.Config["slot"].(int); ok {
+		conn.route = []byte{0x01, byte(slot)}
+	}
+
 	// Get device identity information
 	identity, err := e.getDeviceIdentity(conn)
 	if err != nil {
@@ -269,6 +288,10 @@ func (e *EtherNetIPHandler) Connect(device *Device) error {
 		conn.productName = identity.ProductName
 	}
 
+	if connected, ok := device.Config["connected"].(bool); !ok || connected {
+		e.openConnection(conn)
+	}
+
 	e.connections.Store(connectionKey, conn)
 	device.ConnectionID = connectionKey
 
@@ -277,6 +300,7 @@ func (e *EtherNetIPHandler) Connect(device *Device) error {
 		zap.String("address", device.Address),
 		zap.Int("port", port),
 		zap.Uint32("session_id", sessionID),
+		zap.Bool("connected_messaging", conn.connectionID != 0),
 		zap.String("product_name", conn.productName),
 	)
 
@@ -298,7 +322,12 @@ func (e *EtherNetIPHandler) Disconnect(device *Device) error {
 	conn.mutex.Lock()
 	defer conn.mutex.Unlock()
 
-	// Unregister CIP session
+	// Close the connection, then the CIP session
+	if conn.connectionID != 0 {
+		if err := e.forwardClose(conn); err != nil {
+			e.logger.Debug("Forward close failed", zap.Error(err))
+		}
+	}
 	if conn.sessionID != 0 {
 		e.unregisterSession(conn)
 	}
@@ -355,22 +384,16 @@ func (e *EtherNetIPHandler) ReadTag(device *Device, tag *Tag) (interface{}, erro
 
 	conn.lastUsed = time.Now()
 
-	// Build CIP request for tag read
-	var request *CIPRequest
+	// Symbolic addresses are Logix tags
 	if addr.IsSymbolic {
-		// Use symbolic addressing (tag name)
-		request = &CIPRequest{
-			Service:     CIPServiceGetAttributeSingle,
-			RequestPath: e.buildSymbolicPath(addr.TagName),
-			RequestData: []byte{},
-		}
-	} else {
-		// Use instance-based addressing
-		request = &CIPRequest{
-			Service:     CIPServiceGetAttributeSingle,
-			RequestPath: e.buildInstancePath(CIPClassSymbol, addr.InstanceID, addr.AttributeID),
-			RequestData: []byte{},
-		}
+		return e.readLogixTag(conn, tag.Address)
+	}
+
+	// Instance addresses read an attribute
+	request := &CIPRequest{
+		Service:     CIPServiceGetAttributeSingle,
+		RequestPath: e.buildInstancePath(CIPClassSymbol, addr.InstanceID, addr.AttributeID),
+		RequestData: []byte{},
 	}
 
 	// Send CIP request
@@ -379,8 +402,8 @@ func (e *EtherNetIPHandler) ReadTag(device *Device, tag *Tag) (interface{}, erro
 		return nil, err
 	}
 
-	if response.GeneralStatus != 0 {
-		return nil, fmt.Errorf("CIP error: status 0x%02X", response.GeneralStatus)
+	if response.GeneralStatus != CIPStatusSuccess {
+		return nil, NewCIPError(response.GeneralStatus, response.ExtendedStatus, "attribute read failed", "get_attribute_single")
 	}
 
 	// Convert response data to appropriate Go type
@@ -408,26 +431,22 @@ func (e *EtherNetIPHandler) WriteTag(device *Device, tag *Tag, value interface{}
 
 	conn.lastUsed = time.Now()
 
+	// Symbolic addresses are Logix tags
+	if addr.IsSymbolic {
+		return e.writeLogixTag(conn, tag.Address, value)
+	}
+
 	// Convert value to CIP format
 	cipData, err := e.convertToCIP(value, addr.DataType)
 	if err != nil {
 		return err
 	}
 
-	// Build CIP request for tag write
-	var request *CIPRequest
-	if addr.IsSymbolic {
-		request = &CIPRequest{
-			Service:     CIPServiceSetAttributeSingle,
-			RequestPath: e.buildSymbolicPath(addr.TagName),
-			RequestData: cipData,
-		}
-	} else {
-		request = &CIPRequest{
-			Service:     CIPServiceSetAttributeSingle,
-			RequestPath: e.buildInstancePath(CIPClassSymbol, addr.InstanceID, addr.AttributeID),
-			RequestData: cipData,
-		}
+	// Instance addresses write an attribute
+	request := &CIPRequest{
+		Service:     CIPServiceSetAttributeSingle,
+		RequestPath: e.buildInstancePath(CIPClassSymbol, addr.InstanceID, addr.AttributeID),
+		RequestData: cipData,
 	}
 
 	// Send CIP request
@@ -436,8 +455,8 @@ func (e *EtherNetIPHandler) WriteTag(device *Device, tag *Tag, value interface{}
 		return err
 	}
 
-	if response.GeneralStatus != 0 {
-		return fmt.Errorf("CIP write error: status 0x%02X", response.GeneralStatus)
+	if response.GeneralStatus != CIPStatusSuccess {
+		return NewCIPError(response.GeneralStatus, response.ExtendedStatus, "attribute write failed", "set_attribute_single")
 	}
 
 	return nil
@@ -462,6 +481,7 @@ func (e *EtherNetIPHandler) ReadMultipleTags(device *Device, tags []*Tag) (map[s
 	conn.mutex.Lock()
 	defer conn.mutex.Unlock()
 
+	conn.lastUsed = time.Now()
 	for _, batch := range batches {
 		batchResults, err := e.readTagBatch(conn, batch)
 		if err != nil {
@@ -528,7 +548,7 @@ func (e *EtherNetIPHandler) GetDeviceInfo(device *Device) (*DeviceInfo, error) {
 		Vendor:          e.getVendorName(conn.vendorID),
 		Model:           conn.productName,
 		SerialNumber:    fmt.Sprintf("%d", conn.serialNumber),
-		FirmwareVersion: fmt.Sprintf("%d.%d", conn.revision>>8, conn.revision&0xFF),
+		FirmwareVersion: fmt.Sprintf("%d.%d", conn.revision&0xFF, conn.revision>>8),
 		Capabilities:    []string{"ethernet-ip", "cip-explicit", "cip-implicit"},
 		MaxConnections:  10,                   // Typical for Allen-Bradley PLCs
 		SupportedRates:  []int{10, 100, 1000}, // Ethernet speeds in Mbps
@@ -574,24 +594,19 @@ func (e *EtherNetIPHandler) Ping(device *Device) error {
 	conn.mutex.Lock()
 	defer conn.mutex.Unlock()
 
-	// Send NOP command as ping
-	header := CIPEncapsulationHeader{
-		Command:       CIPCommandNOP,
-		Length:        0,
-		SessionHandle: conn.sessionID,
-		Status:        0,
-		Options:       0,
+	// Read the identity object's vendor ID; unlike NOP this gets a reply
+	request := &CIPRequest{
+		Service:     CIPServiceGetAttributeSingle,
+		RequestPath: e.buildInstancePath(CIPClassIdentity, 1, 1),
+		RequestData: []byte{},
 	}
 
-	err = e.sendEncapsulationHeader(conn, &header)
+	response, err := e.sendCIPRequest(conn, request)
 	if err != nil {
 		return fmt.Errorf("ping failed: %w", err)
 	}
-
-	// Read response
-	_, err = e.readEncapsulationHeader(conn)
-	if err != nil {
-		return fmt.Errorf("ping failed: %w", err)
+	if response.GeneralStatus != CIPStatusSuccess {
+		return NewCIPError(response.GeneralStatus, response.ExtendedStatus, "ping failed", "ping")
 	}
 
 	conn.lastUsed = time.Now()
@@ -620,12 +635,13 @@ func (e *EtherNetIPHandler) GetDiagnostics(device *Device) (*Diagnostics, error)
 			"product_code":    conn.productCode,
 			"product_name":    conn.productName,
 			"sequence_number": conn.sequenceNumber,
+			"route":           fmt.Sprintf("% X", conn.route),
 		},
 	}, nil
 }
 
-// Helper methods will be implemented in the next part due to length constraints
-// This includes CIP session management, packet building, data conversion, etc.
+// CIP session management, packet building and data conversion are in
+// ethernetip_cip.go; Logix tag services in ethernetip_logix.go
 
 // getConnection retrieves an active connection for a device
 func (e *EtherNetIPHandler) getConnection(device *Device) (*EtherNetIPConnection, error) {
@@ -4,6 +4,8 @@ import (
 	"context"
 	"encoding/binary"
 	"fmt"
+	"io"
+	"math"
 	"net"
 	"strconv"
 	"strings"
@@ -79,10 +81,10 @@ func (e *EtherNetIPHandler) unregisterSession(conn *EtherNetIPConnection) error
 
 // getDeviceIdentity retrieves device identity information
 func (e *EtherNetIPHandler) getDeviceIdentity(conn *EtherNetIPConnection) (*CIPIdentityObject, error) {
-	// Build request to read Identity Object attributes
+	// Build request to read all Identity Object attributes of instance 1
 	request := &CIPRequest{
-		Service:     CIPServiceGetAttributeSingle,
-		RequestPath: e.buildInstancePath(CIPClassIdentity, 1, 1), // Identity Object Instance 1, Attribute 1
+		Service:     CIPServiceGetAll,
+		RequestPath: []byte{0x20, CIPClassIdentity, 0x24, 0x01},
 		RequestData: []byte{},
 	}
 
@@ -100,63 +102,70 @@ func (e *EtherNetIPHandler) getDeviceIdentity(conn *EtherNetIPConnection) (*CIPI
 
 // sendCIPRequest sends a CIP request and returns the response
 func (e *EtherNetIPHandler) sendCIPRequest(conn *EtherNetIPConnection, request *CIPRequest) (*CIPResponse, error) {
-	// Build CIP request packet
-	requestData := e.buildCIPRequestData(request)
+	return e.sendRequestData(conn, e.buildCIPRequestData(request))
+}
+
+// sendRequestData sends an encoded CIP request over the connection opened
+// with Forward Open, or unconnected along the route to the target
+func (e *EtherNetIPHandler) sendRequestData(conn *EtherNetIPConnection, requestData []byte) (*CIPResponse, error) {
+	if conn.connectionID != 0 {
+		return e.sendConnected(conn, requestData)
+	}
+	if len(conn.route) > 0 {
+		requestData = e.buildUnconnectedSend(requestData, conn.route)
+	}
+	return e.sendUnconnected(conn, requestData)
+}
 
-	// Build Common Packet Format
+// sendUnconnected sends an unconnected request with SendRRData
+func (e *EtherNetIPHandler) sendUnconnected(conn *EtherNetIPConnection, requestData []byte) (*CIPResponse, error) {
 	cpf := &CIPCommonPacketFormat{
-		ItemCount: 2,
-		TypeID:    0x0000, // NULL Address Type
-		Length:    0,
-		Data:      requestData,
+		AddressType: CPFItemNullAddress,
+		DataType:    CPFItemUnconnectedData,
+		Data:        requestData,
 	}
+	return e.exchange(conn, CIPCommandSendRRData, cpf)
+}
 
-	cpfData := e.buildCPFData(cpf)
+// exchange sends a SendRRData or SendUnitData command and parses the CIP
+// response it carries
+func (e *EtherNetIPHandler) exchange(conn *EtherNetIPConnection, command uint16, cpf *CIPCommonPacketFormat) (*CIPResponse, error) {
+	// Interface handle (always 0 for CIP) and timeout precede the items
+	data := make([]byte, 6)
+	data = append(data, e.buildCPFData(cpf)...)
 
-	// Build encapsulation header
 	header := CIPEncapsulationHeader{
-		Command:       CIPCommandSendRRData,
-		Length:        uint16(len(cpfData)),
+		Command:       command,
 		SessionHandle: conn.sessionID,
-		Status:        0,
-		Options:       0,
 	}
-
-	// Send request
-	if err := e.sendEncapsulationRequest(conn, &header, cpfData); err != nil {
+	if err := e.sendEncapsulationRequest(conn, &header, data); err != nil {
 		return nil, err
 	}
 
-	// Read response
 	respHeader, respData, err := e.readEncapsulationResponse(conn)
 	if err != nil {
 		return nil, err
 	}
-
-	if respHeader.Status != 0 {
-		return nil, fmt.Errorf("CIP request failed with status: 0x%08X", respHeader.Status)
+	if respHeader.Status != EncapStatusSuccess {
+		return nil, NewEncapsulationError(respHeader.Status, "CIP request failed", "send_data")
+	}
+	if respHeader.Command != command || len(respData) < 6 {
+		return nil, fmt.Errorf("unexpected encapsulation response 0x%04X", respHeader.Command)
 	}
 
-	// Parse CIP response
-	return e.parseCIPResponse(respData)
+	return e.parseCIPResponse(respData[6:])
 }
 
 // sendEncapsulationRequest sends an encapsulation request
 func (e *EtherNetIPHandler) sendEncapsulationRequest(conn *EtherNetIPConnection, header *CIPEncapsulationHeader, data []byte) error {
 	// Set connection timeout
-	conn.tcpConn.SetWriteDeadline(time.Now().Add(e.config.DefaultTimeout))
-
-	// Send header
-	if err := e.sendEncapsulationHeader(conn, header); err != nil {
-		return err
-	}
+	_ = conn.tcpConn.SetWriteDeadline(time.Now().Add(e.config.DefaultTimeout))
 
-	// Send data if present
-	if len(data) > 0 {
-		_, err := conn.tcpConn.Write(data)
-		if err != nil {
-			return fmt.Errorf("failed to send encapsulation data: %w", err)
-		}
+	// Send header and data in one write
+	header.Length = uint16(len(data))
+	packet := append(e.encodeEncapsulationHeader(header), data...)
+	if _, err := conn.tcpConn.Write(packet); err != nil {
+		return fmt.Errorf("failed to send encapsulation request: %w", err)
 	}
 
 	return nil
@@ -165,7 +174,7 @@ func (e *EtherNetIPHandler) sendEncapsulationRequest(conn *EtherNetIPConnection,
 // readEncapsulationResponse reads an encapsulation response
 func (e *EtherNetIPHandler) readEncapsulationResponse(conn *EtherNetIPConnection) (*CIPEncapsulationHeader, []byte, error) {
 	// Set connection timeout
-	conn.tcpConn.SetReadDeadline(time.Now().Add(e.config.DefaultTimeout))
+	_ = conn.tcpConn.SetReadDeadline(time.Now().Add(e.config.DefaultTimeout))
 
 	// Read header
 	header, err := e.readEncapsulationHeader(conn)
@@ -177,8 +186,7 @@ func (e *EtherNetIPHandler) readEncapsulationResponse(conn *EtherNetIPConnection
 	var data []byte
 	if header.Length > 0 {
 		data = make([]byte, header.Length)
-		_, err := conn.tcpConn.Read(data)
-		if err != nil {
+		if _, err := io.ReadFull(conn.tcpConn, data); err != nil {
 			return nil, nil, fmt.Errorf("failed to read encapsulation data: %w", err)
 		}
 	}
@@ -186,8 +194,8 @@ func (e *EtherNetIPHandler) readEncapsulationResponse(conn *EtherNetIPConnection
 	return header, data, nil
 }
 
-// sendEncapsulationHeader sends an encapsulation header
-func (e *EtherNetIPHandler) sendEncapsulationHeader(conn *EtherNetIPConnection, header *CIPEncapsulationHeader) error {
+// encodeEncapsulationHeader encodes an encapsulation header
+func (e *EtherNetIPHandler) encodeEncapsulationHeader(header *CIPEncapsulationHeader) []byte {
 	buf := make([]byte, 24) // Encapsulation header size
 
 	binary.LittleEndian.PutUint16(buf[0:2], header.Command)
@@ -197,18 +205,13 @@ func (e *EtherNetIPHandler) sendEncapsulationHeader(conn *EtherNetIPConnection,
 	copy(buf[12:20], header.Context[:])
 	binary.LittleEndian.PutUint32(buf[20:24], header.Options)
 
-	_, err := conn.tcpConn.Write(buf)
-	if err != nil {
-		return fmt.Errorf("failed to send encapsulation header: %w", err)
-	}
-
-	return nil
+	return buf
 }
 
 // readEncapsulationHeader reads an encapsulation header
 func (e *EtherNetIPHandler) readEncapsulationHeader(conn *EtherNetIPConnection) (*CIPEncapsulationHeader, error) {
 	buf := make([]byte, 24)
-	_, err := conn.tcpConn.Read(buf)
+	_, err := io.ReadFull(conn.tcpConn, buf)
 	if err != nil {
 		return nil, fmt.Errorf("failed to read encapsulation header: %w", err)
 	}
@@ -380,43 +383,33 @@ func (e *EtherNetIPHandler) buildCIPRequestData(request *CIPRequest) []byte {
 	return data
 }
 
-// buildCPFData builds Common Packet Format data
+// buildCPFData builds Common Packet Format data: an address item and a
+// data item
 func (e *EtherNetIPHandler) buildCPFData(cpf *CIPCommonPacketFormat) []byte {
-	var data []byte
-
-	// Item count
-	binary.LittleEndian.PutUint16(data[0:2], cpf.ItemCount)
-	data = append(data, make([]byte, 2)...)
-	binary.LittleEndian.PutUint16(data[0:2], cpf.ItemCount)
+	data := binary.LittleEndian.AppendUint16(nil, 2) // Item count
 
 	// Address item
-	data = append(data, make([]byte, 4)...)
-	binary.LittleEndian.PutUint16(data[len(data)-4:], 0x0000) // NULL Address Type
-	binary.LittleEndian.PutUint16(data[len(data)-2:], 0x0000) // Length = 0
+	data = binary.LittleEndian.AppendUint16(data, cpf.AddressType)
+	data = binary.LittleEndian.AppendUint16(data, uint16(len(cpf.AddressData)))
+	data = append(data, cpf.AddressData...)
 
 	// Data item
-	data = append(data, make([]byte, 4)...)
-	binary.LittleEndian.PutUint16(data[len(data)-4:], 0x00B2) // Unconnected Data Item
-	binary.LittleEndian.PutUint16(data[len(data)-2:], uint16(len(cpf.Data)))
-
-	// Data
+	data = binary.LittleEndian.AppendUint16(data, cpf.DataType)
+	data = binary.LittleEndian.AppendUint16(data, uint16(len(cpf.Data)))
 	data = append(data, cpf.Data...)
 
 	return data
 }
 
-// parseCIPResponse parses a CIP response
+// parseCIPResponse parses the CIP response in Common Packet Format data
 func (e *EtherNetIPHandler) parseCIPResponse(data []byte) (*CIPResponse, error) {
-	if len(data) < 6 {
+	if len(data) < 2 {
 		return nil, fmt.Errorf("response too short")
 	}
 
-	// Skip CPF header (6 bytes minimum)
-	offset := 6
-
 	// Find data item
 	itemCount := binary.LittleEndian.Uint16(data[0:2])
-	offset = 2
+	offset := 2
 
 	for i := 0; i < int(itemCount); i++ {
 		if offset+4 > len(data) {
@@ -427,13 +420,19 @@ func (e *EtherNetIPHandler) parseCIPResponse(data []byte) (*CIPResponse, error)
 		length := binary.LittleEndian.Uint16(data[offset+2 : offset+4])
 		offset += 4
 
-		if typeID == 0x00B2 { // Unconnected Data Item
-			if offset+int(length) > len(data) {
-				return nil, fmt.Errorf("invalid data item length")
-			}
+		if offset+int(length) > len(data) {
+			return nil, fmt.Errorf("invalid data item length")
+		}
+		item := data[offset : offset+int(length)]
 
-			responseData := data[offset : offset+int(length)]
-			return e.parseCIPResponseData(responseData)
+		switch typeID {
+		case CPFItemUnconnectedData:
+			return e.parseCIPResponseData(item)
+		case CPFItemConnectedData:
+			if len(item) < 2 {
+				return nil, fmt.Errorf("invalid connected data item")
+			}
+			return e.parseCIPResponseData(item[2:]) // Skip the sequence count
 		}
 
 		offset += int(length)
@@ -442,41 +441,32 @@ func (e *EtherNetIPHandler) parseCIPResponse(data []byte) (*CIPResponse, error)
 	return nil, fmt.Errorf("no data item found in response")
 }
 
-// parseCIPResponseData parses CIP response data
+// parseCIPResponseData parses CIP response data: reply service, reserved
+// byte, general status, extended status size in words, extended status
+// and reply data
 func (e *EtherNetIPHandler) parseCIPResponseData(data []byte) (*CIPResponse, error) {
-	if len(data) < 2 {
+	if len(data) < 4 {
 		return nil, fmt.Errorf("response data too short")
 	}
 
 	response := &CIPResponse{
 		Service:       data[0],
-		GeneralStatus: data[1],
+		GeneralStatus: data[2],
 	}
 
-	offset := 2
-
-	// Check for extended status
-	if len(data) > offset {
-		extStatusSize := int(data[offset])
-		offset++
-
-		if len(data) >= offset+extStatusSize*2 {
-			response.ExtendedStatus = data[offset : offset+extStatusSize*2]
-			offset += extStatusSize * 2
-		}
-	}
-
-	// Remaining data is response data
-	if len(data) > offset {
-		response.ResponseData = data[offset:]
+	offset := 4 + int(data[3])*2
+	if offset > len(data) {
+		return nil, fmt.Errorf("truncated extended status")
 	}
+	response.ExtendedStatus = data[4:offset]
+	response.ResponseData = data[offset:]
 
 	return response, nil
 }
 
 // parseIdentityObject parses Identity Object data
 func (e *EtherNetIPHandler) parseIdentityObject(data []byte) (*CIPIdentityObject, error) {
-	if len(data) < 24 {
+	if len(data) < 15 {
 		return nil, fmt.Errorf("identity object data too short")
 	}
 
@@ -487,15 +477,15 @@ func (e *EtherNetIPHandler) parseIdentityObject(data []byte) (*CIPIdentityObject
 		Revision:     binary.LittleEndian.Uint16(data[6:8]),
 		Status:       binary.LittleEndian.Uint16(data[8:10]),
 		SerialNumber: binary.LittleEndian.Uint32(data[10:14]),
-		State:        data[23],
 	}
 
-	// Parse product name (starts at offset 14)
-	if len(data) > 14 {
-		nameLen := int(data[14])
-		if len(data) >= 15+nameLen {
-			identity.ProductName = string(data[15 : 15+nameLen])
-		}
+	// Parse product name (starts at offset 14) and the state following it
+	nameLen := int(data[14])
+	if len(data) >= 15+nameLen {
+		identity.ProductName = string(data[15 : 15+nameLen])
+	}
+	if len(data) > 15+nameLen {
+		identity.State = data[15+nameLen]
 	}
 
 	return identity, nil
@@ -548,7 +538,7 @@ func (e *EtherNetIPHandler) convertFromCIP(data []byte, dataType uint8) (interfa
 		}
 		return binary.LittleEndian.Uint16(data), nil
 
-	case CIPDataTypeUdint:
+	case CIPDataTypeUdint, CIPDataTypeDword:
 		if len(data) < 4 {
 			return nil, fmt.Errorf("insufficient data for udint")
 		}
@@ -564,15 +554,13 @@ func (e *EtherNetIPHandler) convertFromCIP(data []byte, dataType uint8) (interfa
 		if len(data) < 4 {
 			return nil, fmt.Errorf("insufficient data for real")
 		}
-		bits := binary.LittleEndian.Uint32(data)
-		return float32(bits), nil
+		return math.Float32frombits(binary.LittleEndian.Uint32(data)), nil
 
 	case CIPDataTypeLreal:
 		if len(data) < 8 {
 			return nil, fmt.Errorf("insufficient data for lreal")
 		}
-		bits := binary.LittleEndian.Uint64(data)
-		return float64(bits), nil
+		return math.Float64frombits(binary.LittleEndian.Uint64(data)), nil
 
 	case CIPDataTypeString:
 		if len(data) < 2 {
@@ -601,37 +589,39 @@ func (e *EtherNetIPHandler) convertToCIP(value interface{}, dataType uint8) ([]b
 		}
 		return nil, fmt.Errorf("expected boolean value")
 
-	case CIPDataTypeDint:
-		var intVal int32
-		switch v := value.(type) {
-		case int:
-			intVal = int32(v)
-		case int32:
-			intVal = v
-		case int64:
-			intVal = int32(v)
-		default:
-			return nil, fmt.Errorf("expected integer value")
-		}
-
-		buf := make([]byte, 4)
-		binary.LittleEndian.PutUint32(buf, uint32(intVal))
-		return buf, nil
+	case CIPDataTypeSint, CIPDataTypeInt, CIPDataTypeDint, CIPDataTypeLint:
+		size := cipDataTypeSize(dataType)
+		limit := int64(math.MaxInt64) >> (64 - 8*size)
+		intVal, err := integerValue(value, -limit-1, limit)
+		if err != nil {
+			return nil, err
+		}
+		return binary.LittleEndian.AppendUint64(nil, uint64(intVal))[:size], nil
+
+	case CIPDataTypeUsint, CIPDataTypeUint, CIPDataTypeUdint, CIPDataTypeDword, CIPDataTypeUlint:
+		size := cipDataTypeSize(dataType)
+		uintVal, err := unsigned64Value(value)
+		if err != nil {
+			return nil, err
+		}
+		if uintVal > uint64(math.MaxUint64)>>(64-8*size) {
+			return nil, fmt.Errorf("value %d out of range for %d byte unsigned integer", uintVal, size)
+		}
+		return binary.LittleEndian.AppendUint64(nil, uintVal)[:size], nil
 
 	case CIPDataTypeReal:
-		var floatVal float32
-		switch v := value.(type) {
-		case float32:
-			floatVal = v
-		case float64:
-			floatVal = float32(v)
-		default:
+		floatVal, err := floatValue(value)
+		if err != nil {
 			return nil, fmt.Errorf("expected float value")
 		}
+		return binary.LittleEndian.AppendUint32(nil, math.Float32bits(float32(floatVal))), nil
 
-		buf := make([]byte, 4)
-		binary.LittleEndian.PutUint32(buf, uint32(floatVal))
-		return buf, nil
+	case CIPDataTypeLreal:
+		floatVal, err := floatValue(value)
+		if err != nil {
+			return nil, fmt.Errorf("expected float value")
+		}
+		return binary.LittleEndian.AppendUint64(nil, math.Float64bits(floatVal)), nil
 
 	case CIPDataTypeString:
 		strVal, ok := value.(string)
@@ -650,6 +640,20 @@ func (e *EtherNetIPHandler) convertToCIP(value interface{}, dataType uint8) ([]b
 	}
 }
 
+// cipDataTypeSize returns the size in bytes of an integer CIP data type
+func cipDataTypeSize(dataType uint8) int {
+	switch dataType {
+	case CIPDataTypeSint, CIPDataTypeUsint:
+		return 1
+	case CIPDataTypeInt, CIPDataTypeUint:
+		return 2
+	case CIPDataTypeDint, CIPDataTypeUdint, CIPDataTypeDword:
+		return 4
+	default:
+		return 8
+	}
+}
+
 // getVendorName returns vendor name from vendor ID
 func (e *EtherNetIPHandler) getVendorName(vendorID uint16) string {
 	switch vendorID {
@@ -697,39 +701,51 @@ func (e *EtherNetIPHandler) groupTagsForBatchRead(tags []*Tag, maxBatchSize int)
 
 // readTagBatch reads a batch of tags using Multiple Service Packet
 func (e *EtherNetIPHandler) readTagBatch(conn *EtherNetIPConnection, tags []*Tag) (map[string]interface{}, error) {
+	var symbolic []*Tag
 	results := make(map[string]interface{})
 
-	// For now, fall back to individual reads
-	// Full Multiple Service Packet implementation would be more complex
+	// Instance addresses are generic CIP attribute reads and go one by one
 	for _, tag := range tags {
-		if value, err := e.readSingleTag(conn, tag); err == nil {
+		addr, err := e.parseAddress(tag.Address)
+		if err != nil {
+			continue
+		}
+		if addr.IsSymbolic {
+			symbolic = append(symbolic, tag)
+		} else if value, err := e.readSingleTag(conn, tag); err == nil {
 			results[tag.ID] = value
 		}
 	}
 
+	if len(symbolic) > 0 {
+		values, err := e.readLogixTags(conn, symbolic)
+		if err != nil {
+			return nil, err
+		}
+		for tagID, value := range values {
+			results[tagID] = value
+		}
+	}
+
 	return results, nil
 }
 
-// readSingleTag reads a single tag (helper method)
+// readSingleTag reads a single tag: Read Tag for symbolic addresses, Get
+// Attribute Single for instance addresses
 func (e *EtherNetIPHandler) readSingleTag(conn *EtherNetIPConnection, tag *Tag) (interface{}, error) {
-	addr, err := e.parseAddressCIP(tag.Address)
+	addr, err := e.parseAddress(tag.Address)
 	if err != nil {
 		return nil, err
 	}
 
-	var request *CIPRequest
 	if addr.IsSymbolic {
-		request = &CIPRequest{
-			Service:     CIPServiceGetAttributeSingle,
-			RequestPath: e.buildSymbolicPath(addr.TagName),
-			RequestData: []byte{},
-		}
-	} else {
-		request = &CIPRequest{
-			Service:     CIPServiceGetAttributeSingle,
-			RequestPath: e.buildInstancePath(CIPClassSymbol, addr.InstanceID, addr.AttributeID),
-			RequestData: []byte{},
-		}
+		return e.readLogixTag(conn, tag.Address)
+	}
+
+	request := &CIPRequest{
+		Service:     CIPServiceGetAttributeSingle,
+		RequestPath: e.buildInstancePath(CIPClassSymbol, addr.InstanceID, addr.AttributeID),
+		RequestData: []byte{},
 	}
 
 	response, err := e.sendCIPRequest(conn, request)
@@ -738,7 +754,7 @@ func (e *EtherNetIPHandler) readSingleTag(conn *EtherNetIPConnection, tag *Tag)
 	}
 
 	if response.GeneralStatus != 0 {
-		return nil, fmt.Errorf("CIP error: status 0x%02X", response.GeneralStatus)
+		return nil, NewCIPError(response.GeneralStatus, response.ExtendedStatus, "attribute read failed", "get_attribute_single")
 	}
 
 	return e.convertFromCIP(response.ResponseData, addr.DataType)
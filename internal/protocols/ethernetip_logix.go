@@ -0,0 +1,471 @@
+package protocols
+
+import (
+	"encoding/binary"
+	"fmt"
+	"math/rand"
+	"strconv"
+	"strings"
+
+	"go.uber.org/zap"
+)
+
+// Logix Tag Services and Connected Messaging
+//
+// Logix controllers (ControlLogix, CompactLogix) expose tags by name
+// through the Read Tag (0x4C) and Write Tag (0x4D) services, whose replies
+// carry the tag's type code. Requests are sent over a class 3 connection
+// opened with Forward Open when the controller allows it, and otherwise
+// as unconnected messages routed with Unconnected Send.
+
+// Logix and Connection Manager services
+const (
+	CIPServiceReadTag         = 0x4C
+	CIPServiceWriteTag        = 0x4D
+	CIPServiceForwardClose    = 0x4E
+	CIPServiceUnconnectedSend = 0x52
+	CIPServiceForwardOpen     = 0x54
+)
+
+// Common Packet Format item types
+const (
+	CPFItemNullAddress      = 0x0000
+	CPFItemConnectedAddress = 0x00A1
+	CPFItemConnectedData    = 0x00B1
+	CPFItemUnconnectedData  = 0x00B2
+)
+
+const (
+	// logixStructType is the type code of structured tags, followed by the
+	// structure handle
+	logixStructType = 0x02A0
+
+	// logixStringHandle is the structure handle of the built-in STRING
+	// type: a DINT length and 82 characters, padded to 88 bytes
+	logixStringHandle  = 0x0FCE
+	logixStringMaxSize = 82
+	logixStringSize    = 88
+
+	// logixMaxMessageSize bounds requests and replies; it is the connection
+	// size requested with Forward Open and the limit for unconnected
+	// messages
+	logixMaxMessageSize = 504
+
+	logixOriginatorVendor = 0x0001
+)
+
+// messageRouterPath addresses the Message Router (class 2, instance 1)
+var messageRouterPath = []byte{0x20, CIPClassMessageRouter, 0x24, 0x01}
+
+// logixTagType is the type of a tag as reported by Read Tag
+type logixTagType struct {
+	Code   uint16
+	Handle uint16 // Structure handle; structured types only
+}
+
+// forwardOpen opens a class 3 explicit messaging connection to the
+// Message Router at the end of the connection's route
+func (e *EtherNetIPHandler) forwardOpen(conn *EtherNetIPConnection) error {
+	conn.toConnectionID = rand.Uint32()
+	conn.connectionSerial = uint16(rand.Uint32())
+
+	path := append(append([]byte(nil), conn.route...), messageRouterPath...)
+	connectionParameters := uint16(0x4200 | logixMaxMessageSize) // Point to point, variable size
+
+	// Priority/tick time and timeout ticks (about 14s), the connection IDs
+	// (O->T chosen by the target), the connection triad, the timeout
+	// multiplier (x32), and the RPI and parameters of both directions
+	data := []byte{0x0A, 0x0E}
+	data = binary.LittleEndian.AppendUint32(data, 0)
+	data = binary.LittleEndian.AppendUint32(data, conn.toConnectionID)
+	data = binary.LittleEndian.AppendUint16(data, conn.connectionSerial)
+	data = binary.LittleEndian.AppendUint16(data, logixOriginatorVendor)
+	data = binary.LittleEndian.AppendUint32(data, e.originatorSerial)
+	data = append(data, 0x03, 0, 0, 0)
+	data = binary.LittleEndian.AppendUint32(data, 2000000)
+	data = binary.LittleEndian.AppendUint16(data, connectionParameters)
+	data = binary.LittleEndian.AppendUint32(data, 2000000)
+	data = binary.LittleEndian.AppendUint16(data, connectionParameters)
+
+	// Server transport, application trigger, class 3
+	data = append(data, 0xA3)
+	data = append(data, byte(len(path)/2))
+	data = append(data, path...)
+
+	request := &CIPRequest{
+		Service:     CIPServiceForwardOpen,
+		RequestPath: []byte{0x20, CIPClassConnectionManager, 0x24, 0x01},
+		RequestData: data,
+	}
+	response, err := e.sendUnconnected(conn, e.buildCIPRequestData(request))
+	if err != nil {
+		return err
+	}
+	if response.GeneralStatus != CIPStatusSuccess {
+		return NewCIPError(response.GeneralStatus, response.ExtendedStatus, "forward open failed", "forward_open")
+	}
+	if len(response.ResponseData) < 8 {
+		return fmt.Errorf("invalid forward open response")
+	}
+
+	conn.connectionID = binary.LittleEndian.Uint32(response.ResponseData[0:4])
+	conn.sequenceNumber = 0
+	return nil
+}
+
+// forwardClose closes the connection opened by forwardOpen
+func (e *EtherNetIPHandler) forwardClose(conn *EtherNetIPConnection) error {
+	path := append(append([]byte(nil), conn.route...), messageRouterPath...)
+
+	data := []byte{0x0A, 0x0E}
+	data = binary.LittleEndian.AppendUint16(data, conn.connectionSerial)
+	data = binary.LittleEndian.AppendUint16(data, logixOriginatorVendor)
+	data = binary.LittleEndian.AppendUint32(data, e.originatorSerial)
+	data = append(data, byte(len(path)/2), 0)
+	data = append(data, path...)
+
+	request := &CIPRequest{
+		Service:     CIPServiceForwardClose,
+		RequestPath: []byte{0x20, CIPClassConnectionManager, 0x24, 0x01},
+		RequestData: data,
+	}
+	conn.connectionID = 0
+
+	response, err := e.sendUnconnected(conn, e.buildCIPRequestData(request))
+	if err != nil {
+		return err
+	}
+	if response.GeneralStatus != CIPStatusSuccess {
+		return NewCIPError(response.GeneralStatus, response.ExtendedStatus, "forward close failed", "forward_close")
+	}
+	return nil
+}
+
+// sendConnected sends a request over the open connection
+func (e *EtherNetIPHandler) sendConnected(conn *EtherNetIPConnection, requestData []byte) (*CIPResponse, error) {
+	conn.sequenceNumber++
+	item := binary.LittleEndian.AppendUint16(nil, conn.sequenceNumber)
+
+	cpf := &CIPCommonPacketFormat{
+		AddressType: CPFItemConnectedAddress,
+		AddressData: binary.LittleEndian.AppendUint32(nil, conn.connectionID),
+		DataType:    CPFItemConnectedData,
+		Data:        append(item, requestData...),
+	}
+	return e.exchange(conn, CIPCommandSendUnitData, cpf)
+}
+
+// buildUnconnectedSend wraps a request in an Unconnected Send to the
+// Connection Manager, which forwards it along route
+func (e *EtherNetIPHandler) buildUnconnectedSend(requestData []byte, route []byte) []byte {
+	data := []byte{0x0A, 0x0E}
+	data = binary.LittleEndian.AppendUint16(data, uint16(len(requestData)))
+	data = append(data, requestData...)
+	if len(requestData)%2 != 0 {
+		data = append(data, 0)
+	}
+	data = append(data, byte(len(route)/2), 0)
+	data = append(data, route...)
+
+	return e.buildCIPRequestData(&CIPRequest{
+		Service:     CIPServiceUnconnectedSend,
+		RequestPath: []byte{0x20, CIPClassConnectionManager, 0x24, 0x01},
+		RequestData: data,
+	})
+}
+
+// buildLogixTagPath builds the request path of a tag name such as
+// "Program:Main.Recipe[2].Speed": a symbolic segment per member and an
+// element segment per array index
+func (e *EtherNetIPHandler) buildLogixTagPath(name string) ([]byte, error) {
+	var path []byte
+	for _, member := range strings.Split(strings.TrimSpace(name), ".") {
+		symbol, indexes, indexed := strings.Cut(member, "[")
+		if symbol == "" {
+			return nil, fmt.Errorf("invalid tag name %q", name)
+		}
+		if _, err := strconv.Atoi(symbol); err == nil {
+			return nil, fmt.Errorf("bit access is not supported in tag %q", name)
+		}
+		path = append(path, e.buildSymbolicPath(symbol)...)
+
+		if !indexed {
+			continue
+		}
+		if !strings.HasSuffix(indexes, "]") {
+			return nil, fmt.Errorf("invalid array index in tag %q", name)
+		}
+		for _, index := range strings.Split(strings.TrimSuffix(indexes, "]"), ",") {
+			n, err := strconv.ParseUint(strings.TrimSpace(index), 10, 32)
+			if err != nil {
+				return nil, fmt.Errorf("invalid array index in tag %q", name)
+			}
+			switch {
+			case n <= 0xFF:
+				path = append(path, 0x28, byte(n))
+			case n <= 0xFFFF:
+				path = append(path, 0x29, 0)
+				path = binary.LittleEndian.AppendUint16(path, uint16(n))
+			default:
+				path = append(path, 0x2A, 0)
+				path = binary.LittleEndian.AppendUint32(path, uint32(n))
+			}
+		}
+	}
+	return path, nil
+}
+
+// buildReadTagRequest builds a Read Tag request for one element
+func (e *EtherNetIPHandler) buildReadTagRequest(name string) ([]byte, error) {
+	path, err := e.buildLogixTagPath(name)
+	if err != nil {
+		return nil, err
+	}
+	return e.buildCIPRequestData(&CIPRequest{
+		Service:     CIPServiceReadTag,
+		RequestPath: path,
+		RequestData: []byte{0x01, 0x00},
+	}), nil
+}
+
+// readLogixTag reads one tag and returns its decoded value
+func (e *EtherNetIPHandler) readLogixTag(conn *EtherNetIPConnection, name string) (interface{}, error) {
+	request, err := e.buildReadTagRequest(name)
+	if err != nil {
+		return nil, err
+	}
+
+	response, err := e.sendRequestData(conn, request)
+	if err != nil {
+		return nil, err
+	}
+	return e.decodeReadTagResponse(conn, name, response)
+}
+
+// decodeReadTagResponse decodes a Read Tag reply and remembers the tag's
+// type for later writes
+func (e *EtherNetIPHandler) decodeReadTagResponse(conn *EtherNetIPConnection, name string, response *CIPResponse) (interface{}, error) {
+	if response.GeneralStatus != CIPStatusSuccess {
+		return nil, NewCIPError(response.GeneralStatus, response.ExtendedStatus, fmt.Sprintf("read of tag %s failed", name), "read_tag")
+	}
+
+	data := response.ResponseData
+	if len(data) < 2 {
+		return nil, fmt.Errorf("invalid read tag response for %s", name)
+	}
+	tagType := logixTagType{Code: binary.LittleEndian.Uint16(data)}
+	data = data[2:]
+	if tagType.Code == logixStructType {
+		if len(data) < 2 {
+			return nil, fmt.Errorf("invalid read tag response for %s", name)
+		}
+		tagType.Handle = binary.LittleEndian.Uint16(data)
+		data = data[2:]
+	}
+
+	if conn.tagTypes == nil {
+		conn.tagTypes = make(map[string]logixTagType)
+	}
+	conn.tagTypes[name] = tagType
+	return e.decodeLogixValue(tagType, data)
+}
+
+// writeLogixTag writes one tag. The tag's type is learned by reading it
+// first if it has not been read before.
+func (e *EtherNetIPHandler) writeLogixTag(conn *EtherNetIPConnection, name string, value interface{}) error {
+	tagType, known := conn.tagTypes[name]
+	if !known {
+		if _, err := e.readLogixTag(conn, name); err != nil {
+			return err
+		}
+		tagType = conn.tagTypes[name]
+	}
+
+	path, err := e.buildLogixTagPath(name)
+	if err != nil {
+		return err
+	}
+	encoded, err := e.encodeLogixValue(tagType, value)
+	if err != nil {
+		return err
+	}
+
+	data := binary.LittleEndian.AppendUint16(nil, tagType.Code)
+	if tagType.Code == logixStructType {
+		data = binary.LittleEndian.AppendUint16(data, tagType.Handle)
+	}
+	data = binary.LittleEndian.AppendUint16(data, 1) // Element count
+	data = append(data, encoded...)
+
+	response, err := e.sendRequestData(conn, e.buildCIPRequestData(&CIPRequest{
+		Service:     CIPServiceWriteTag,
+		RequestPath: path,
+		RequestData: data,
+	}))
+	if err != nil {
+		return err
+	}
+	if response.GeneralStatus != CIPStatusSuccess {
+		return NewCIPError(response.GeneralStatus, response.ExtendedStatus, fmt.Sprintf("write of tag %s failed", name), "write_tag")
+	}
+	return nil
+}
+
+// readLogixTags reads tags with Multiple Service Packets, as many per
+// packet as fit the message size. Tags that fail are left out of the
+// results.
+func (e *EtherNetIPHandler) readLogixTags(conn *EtherNetIPConnection, tags []*Tag) (map[string]interface{}, error) {
+	results := make(map[string]interface{})
+
+	var batch []*Tag
+	var requests [][]byte
+	size := 0
+	flush := func() error {
+		if len(requests) == 0 {
+			return nil
+		}
+		responses, err := e.sendMultipleServicePacket(conn, requests)
+		if err != nil {
+			return err
+		}
+		for i, response := range responses {
+			tag := batch[i]
+			if response.GeneralStatus == CIPStatusReplyDataTooLarge {
+				// Too large to share a packet; read on its own
+				if value, err := e.readLogixTag(conn, tag.Address); err == nil {
+					results[tag.ID] = value
+				}
+				continue
+			}
+			if value, err := e.decodeReadTagResponse(conn, tag.Address, response); err == nil {
+				results[tag.ID] = value
+			}
+		}
+		batch, requests, size = nil, nil, 0
+		return nil
+	}
+
+	for _, tag := range tags {
+		request, err := e.buildReadTagRequest(tag.Address)
+		if err != nil {
+			continue
+		}
+		// Each service costs its length plus an offset entry; 10 bytes
+		// cover the packet's own header and count
+		if size+len(request)+2 > logixMaxMessageSize-10 {
+			if err := flush(); err != nil {
+				return nil, err
+			}
+		}
+		batch = append(batch, tag)
+		requests = append(requests, request)
+		size += len(request) + 2
+	}
+	if err := flush(); err != nil {
+		return nil, err
+	}
+	return results, nil
+}
+
+// sendMultipleServicePacket sends several requests to the Message Router
+// in one packet and returns their replies in order
+func (e *EtherNetIPHandler) sendMultipleServicePacket(conn *EtherNetIPConnection, requests [][]byte) ([]*CIPResponse, error) {
+	data := binary.LittleEndian.AppendUint16(nil, uint16(len(requests)))
+	offset := 2 + 2*len(requests)
+	for _, request := range requests {
+		data = binary.LittleEndian.AppendUint16(data, uint16(offset))
+		offset += len(request)
+	}
+	for _, request := range requests {
+		data = append(data, request...)
+	}
+
+	response, err := e.sendRequestData(conn, e.buildCIPRequestData(&CIPRequest{
+		Service:     CIPServiceMultipleServicePacket,
+		RequestPath: messageRouterPath,
+		RequestData: data,
+	}))
+	if err != nil {
+		return nil, err
+	}
+
+	// Embedded service errors are reported per reply
+	reply := response.ResponseData
+	if len(reply) < 2 {
+		return nil, NewCIPError(response.GeneralStatus, response.ExtendedStatus, "multiple service packet failed", "multiple_service_packet")
+	}
+	count := int(binary.LittleEndian.Uint16(reply))
+	if count != len(requests) || len(reply) < 2+2*count {
+		return nil, fmt.Errorf("multiple service packet returned %d replies for %d requests", count, len(requests))
+	}
+
+	responses := make([]*CIPResponse, count)
+	for i := range responses {
+		start := int(binary.LittleEndian.Uint16(reply[2+2*i:]))
+		end := len(reply)
+		if i+1 < count {
+			end = int(binary.LittleEndian.Uint16(reply[2+2*(i+1):]))
+		}
+		if start > end || end > len(reply) {
+			return nil, fmt.Errorf("invalid multiple service packet reply offsets")
+		}
+		if responses[i], err = e.parseCIPResponseData(reply[start:end]); err != nil {
+			return nil, err
+		}
+	}
+	return responses, nil
+}
+
+// decodeLogixValue decodes one element of a tag's value
+func (e *EtherNetIPHandler) decodeLogixValue(tagType logixTagType, data []byte) (interface{}, error) {
+	if tagType.Code != logixStructType {
+		return e.convertFromCIP(data, uint8(tagType.Code))
+	}
+
+	if tagType.Handle == logixStringHandle {
+		if len(data) < 4 {
+			return nil, fmt.Errorf("insufficient data for string")
+		}
+		length := int(binary.LittleEndian.Uint32(data))
+		if length > len(data)-4 {
+			return nil, fmt.Errorf("insufficient data for string content")
+		}
+		return string(data[4 : 4+length]), nil
+	}
+
+	// Other structures are returned as their raw bytes
+	return append([]byte(nil), data...), nil
+}
+
+// encodeLogixValue encodes a value for a tag of the given type
+func (e *EtherNetIPHandler) encodeLogixValue(tagType logixTagType, value interface{}) ([]byte, error) {
+	if tagType.Code != logixStructType {
+		return e.convertToCIP(value, uint8(tagType.Code))
+	}
+
+	if tagType.Handle != logixStringHandle {
+		return nil, fmt.Errorf("writing structure 0x%04X is not supported", tagType.Handle)
+	}
+	s, ok := value.(string)
+	if !ok {
+		return nil, fmt.Errorf("expected string value")
+	}
+	if len(s) > logixStringMaxSize {
+		return nil, fmt.Errorf("string of %d characters exceeds %d", len(s), logixStringMaxSize)
+	}
+	data := binary.LittleEndian.AppendUint32(nil, uint32(len(s)))
+	data = append(data, s...)
+	return append(data, make([]byte, logixStringSize-len(data))...), nil
+}
+
+// openConnection opens a connected session, falling back to unconnected
+// messaging for targets that refuse it
+func (e *EtherNetIPHandler) openConnection(conn *EtherNetIPConnection) {
+	if err := e.forwardOpen(conn); err != nil {
+		conn.connectionID = 0
+		e.logger.Debug("Forward open refused, using unconnected messaging",
+			zap.String("device_id", conn.deviceID),
+			zap.Error(err),
+		)
+	}
+}
@@ -0,0 +1,427 @@
+package protocols
+
+import (
+	"encoding/binary"
+	"fmt"
+	"io"
+	"math"
+	"net"
+	"strings"
+	"sync"
+	"testing"
+
+	"github.com/stretchr/testify/assert"
+	"go.uber.org/zap"
+)
+
+func TestBuildLogixTagPath(t *testing.T) {
+	handler := NewEtherNetIPHandler(zap.NewNop()).(*EtherNetIPHandler)
+
+	path, err := handler.buildLogixTagPath("Motor.Speed")
+	assert.NoError(t, err)
+	assert.Equal(t, []byte{0x91, 5, 'M', 'o', 't', 'o', 'r', 0, 0x91, 5, 'S', 'p', 'e', 'e', 'd', 0}, path)
+
+	path, err = handler.buildLogixTagPath("Data[3]")
+	assert.NoError(t, err)
+	assert.Equal(t, []byte{0x91, 4, 'D', 'a', 't', 'a', 0x28, 3}, path)
+
+	path, err = handler.buildLogixTagPath("Grid[1,300]")
+	assert.NoError(t, err)
+	assert.Equal(t, []byte{0x91, 4, 'G', 'r', 'i', 'd', 0x28, 1, 0x29, 0, 0x2C, 0x01}, path)
+
+	path, err = handler.buildLogixTagPath("Recipe[70000].Step")
+	assert.NoError(t, err)
+	assert.Equal(t, []byte{0x91, 6, 'R', 'e', 'c', 'i', 'p', 'e', 0x2A, 0, 0x70, 0x11, 0x01, 0x00, 0x91, 4, 'S', 't', 'e', 'p'}, path)
+
+	for _, name := range []string{"", "Flags.3", "Data[x]", "Data[3", ".Speed"} {
+		_, err := handler.buildLogixTagPath(name)
+		assert.Error(t, err, name)
+	}
+}
+
+func TestLogixValues(t *testing.T) {
+	handler := NewEtherNetIPHandler(zap.NewNop()).(*EtherNetIPHandler)
+	stringType := logixTagType{Code: logixStructType, Handle: logixStringHandle}
+
+	data, err := handler.encodeLogixValue(stringType, "hello")
+	assert.NoError(t, err)
+	assert.Len(t, data, logixStringSize)
+	assert.Equal(t, uint32(5), binary.LittleEndian.Uint32(data))
+
+	value, err := handler.decodeLogixValue(stringType, data)
+	assert.NoError(t, err)
+	assert.Equal(t, "hello", value)
+
+	_, err = handler.encodeLogixValue(stringType, strings.Repeat("x", logixStringMaxSize+1))
+	assert.Error(t, err)
+	_, err = handler.encodeLogixValue(logixTagType{Code: logixStructType, Handle: 0x1234}, "hello")
+	assert.Error(t, err)
+
+	// Atomic types use the CIP conversions
+	data, err = handler.encodeLogixValue(logixTagType{Code: CIPDataTypeReal}, 12.5)
+	assert.NoError(t, err)
+	value, err = handler.decodeLogixValue(logixTagType{Code: CIPDataTypeReal}, data)
+	assert.NoError(t, err)
+	assert.Equal(t, float32(12.5), value)
+
+	_, err = handler.encodeLogixValue(logixTagType{Code: CIPDataTypeSint}, 200)
+	assert.Error(t, err)
+}
+
+func TestEtherNetIPHandler_LogixConnected(t *testing.T) {
+	controller := newLogixTestController(t)
+	handler := NewEtherNetIPHandler(zap.NewNop()).(*EtherNetIPHandler)
+	device := controller.device(map[string]interface{}{"slot": 2})
+
+	assert.NoError(t, handler.Connect(device))
+	conn, err := handler.getConnection(device)
+	assert.NoError(t, err)
+	assert.Equal(t, uint32(0x7E570001), conn.connectionID)
+
+	info, err := handler.GetDeviceInfo(device)
+	assert.NoError(t, err)
+	assert.Equal(t, "20.11", info.FirmwareVersion)
+	assert.Equal(t, "1756-L83E", info.Model)
+
+	value, err := handler.ReadTag(device, &Tag{ID: "count", Address: "Counter"})
+	assert.NoError(t, err)
+	assert.Equal(t, int32(42), value)
+
+	value, err = handler.ReadTag(device, &Tag{ID: "speed", Address: "Line.Speed"})
+	assert.NoError(t, err)
+	assert.Equal(t, float32(12.5), value)
+
+	value, err = handler.ReadTag(device, &Tag{ID: "name", Address: "Name"})
+	assert.NoError(t, err)
+	assert.Equal(t, "line 1", value)
+
+	_, err = handler.ReadTag(device, &Tag{ID: "missing", Address: "Missing"})
+	assert.Error(t, err)
+
+	// Counter's type is known from the read; Level's is learned first
+	assert.NoError(t, handler.WriteTag(device, &Tag{ID: "count", Address: "Counter", Writable: true}, 7))
+	assert.NoError(t, handler.WriteTag(device, &Tag{ID: "level", Address: "Levels[1]", Writable: true}, 3.25))
+	assert.NoError(t, handler.WriteTag(device, &Tag{ID: "name", Address: "Name", Writable: true}, "line 2"))
+	assert.Error(t, handler.WriteTag(device, &Tag{ID: "count", Address: "Counter", Writable: true}, "seven"))
+
+	assert.Equal(t, []byte{7, 0, 0, 0}, controller.value("Counter"))
+	assert.Equal(t, binary.LittleEndian.AppendUint32(nil, math.Float32bits(3.25)), controller.value("Levels[1]"))
+	value, err = handler.ReadTag(device, &Tag{ID: "name", Address: "Name"})
+	assert.NoError(t, err)
+	assert.Equal(t, "line 2", value)
+
+	// Enough tags for several Multiple Service Packets
+	tags := []*Tag{{ID: "missing", Address: "Missing"}}
+	for i := 0; i < 60; i++ {
+		tags = append(tags, &Tag{ID: fmt.Sprintf("point%d", i), Address: fmt.Sprintf("Points[%d]", i)})
+	}
+	results, err := handler.ReadMultipleTags(device, tags)
+	assert.NoError(t, err)
+	assert.Len(t, results, 60)
+	assert.Equal(t, int32(59), results["point59"])
+	assert.Greater(t, controller.count(CIPServiceMultipleServicePacket), 1)
+
+	assert.NoError(t, handler.Ping(device))
+	assert.NoError(t, handler.Disconnect(device))
+	assert.Equal(t, 1, controller.count(CIPServiceForwardClose))
+
+	// Only the identity read preceding Forward Open was routed unconnected
+	assert.Equal(t, 1, controller.count(CIPServiceUnconnectedSend))
+}
+
+func TestEtherNetIPHandler_LogixUnconnected(t *testing.T) {
+	controller := newLogixTestController(t)
+	controller.refuseForwardOpen = true
+	handler := NewEtherNetIPHandler(zap.NewNop()).(*EtherNetIPHandler)
+	device := controller.device(map[string]interface{}{"slot": 0})
+
+	assert.NoError(t, handler.Connect(device))
+	conn, err := handler.getConnection(device)
+	assert.NoError(t, err)
+	assert.Equal(t, uint32(0), conn.connectionID)
+
+	value, err := handler.ReadTag(device, &Tag{ID: "count", Address: "Counter"})
+	assert.NoError(t, err)
+	assert.Equal(t, int32(42), value)
+	assert.NoError(t, handler.WriteTag(device, &Tag{ID: "count", Address: "Counter", Writable: true}, int32(-5)))
+	assert.Equal(t, []byte{0xFB, 0xFF, 0xFF, 0xFF}, controller.value("Counter"))
+
+	// Requests are routed to the slot with Unconnected Send
+	assert.Greater(t, controller.count(CIPServiceUnconnectedSend), 1)
+	assert.NoError(t, handler.Disconnect(device))
+	assert.Equal(t, 0, controller.count(CIPServiceForwardClose))
+}
+
+// logixTestController is a minimal Logix controller serving tags over
+// EtherNet/IP, connected or unconnected
+type logixTestController struct {
+	t        *testing.T
+	listener net.Listener
+
+	refuseForwardOpen bool
+
+	mutex    sync.Mutex
+	tags     map[string]logixTestTag
+	services map[byte]int
+}
+
+type logixTestTag struct {
+	Type logixTagType
+	Data []byte
+}
+
+func newLogixTestController(t *testing.T) *logixTestController {
+	listener, err := net.Listen("tcp", "127.0.0.1:0")
+	assert.NoError(t, err)
+	t.Cleanup(func() { listener.Close() })
+
+	name := binary.LittleEndian.AppendUint32(nil, 6)
+	name = append(name, "line 1"...)
+	name = append(name, make([]byte, logixStringSize-len(name))...)
+
+	c := &logixTestController{
+		t:        t,
+		listener: listener,
+		tags: map[string]logixTestTag{
+			"Counter":    {logixTagType{Code: CIPDataTypeDint}, []byte{42, 0, 0, 0}},
+			"Line.Speed": {logixTagType{Code: CIPDataTypeReal}, binary.LittleEndian.AppendUint32(nil, math.Float32bits(12.5))},
+			"Name":       {logixTagType{Code: logixStructType, Handle: logixStringHandle}, name},
+			"Levels[1]":  {logixTagType{Code: CIPDataTypeReal}, make([]byte, 4)},
+		},
+		services: make(map[byte]int),
+	}
+	for i := 0; i < 60; i++ {
+		c.tags[fmt.Sprintf("Points[%d]", i)] = logixTestTag{logixTagType{Code: CIPDataTypeDint}, binary.LittleEndian.AppendUint32(nil, uint32(i))}
+	}
+
+	go func() {
+		for {
+			conn, err := listener.Accept()
+			if err != nil {
+				return
+			}
+			go c.serve(conn)
+		}
+	}()
+	return c
+}
+
+func (c *logixTestController) device(config map[string]interface{}) *Device {
+	address := c.listener.Addr().(*net.TCPAddr)
+	return &Device{ID: "plc", Address: address.IP.String(), Port: address.Port, Config: config}
+}
+
+func (c *logixTestController) value(name string) []byte {
+	c.mutex.Lock()
+	defer c.mutex.Unlock()
+	return c.tags[name].Data
+}
+
+func (c *logixTestController) count(service byte) int {
+	c.mutex.Lock()
+	defer c.mutex.Unlock()
+	return c.services[service]
+}
+
+func (c *logixTestController) serve(conn net.Conn) {
+	defer conn.Close()
+
+	header := make([]byte, 24)
+	for {
+		if _, err := io.ReadFull(conn, header); err != nil {
+			return
+		}
+		data := make([]byte, binary.LittleEndian.Uint16(header[2:4]))
+		if _, err := io.ReadFull(conn, data); err != nil {
+			return
+		}
+
+		var reply []byte
+		switch binary.LittleEndian.Uint16(header[0:2]) {
+		case CIPCommandRegisterSession:
+			binary.LittleEndian.PutUint32(header[4:8], 0x00C0FFEE)
+			reply = data
+		case CIPCommandUnregisterSession:
+			return
+		case CIPCommandSendRRData:
+			request := c.dataItem(data, CPFItemUnconnectedData)
+			reply = c.packet(CPFItemNullAddress, nil, CPFItemUnconnectedData, c.handleUnconnected(request))
+		case CIPCommandSendUnitData:
+			item := c.dataItem(data, CPFItemConnectedData)
+			response := append(append([]byte(nil), item[:2]...), c.handle(item[2:])...)
+			reply = c.packet(CPFItemConnectedAddress, []byte{1, 0, 0x57, 0x7E}, CPFItemConnectedData, response)
+		default:
+			c.t.Errorf("unexpected encapsulation command %x", header[0:2])
+			return
+		}
+
+		binary.LittleEndian.PutUint16(header[2:4], uint16(len(reply)))
+		if _, err := conn.Write(append(append([]byte(nil), header...), reply...)); err != nil {
+			return
+		}
+	}
+}
+
+// dataItem returns the data item of SendRRData or SendUnitData data
+func (c *logixTestController) dataItem(data []byte, itemType uint16) []byte {
+	offset := 8
+	for i := 0; i < int(binary.LittleEndian.Uint16(data[6:8])); i++ {
+		length := int(binary.LittleEndian.Uint16(data[offset+2:]))
+		if binary.LittleEndian.Uint16(data[offset:]) == itemType {
+			return data[offset+4 : offset+4+length]
+		}
+		offset += 4 + length
+	}
+	c.t.Errorf("no data item of type 0x%04X", itemType)
+	return nil
+}
+
+func (c *logixTestController) packet(addressType uint16, address []byte, dataType uint16, data []byte) []byte {
+	packet := make([]byte, 6)
+	packet = binary.LittleEndian.AppendUint16(packet, 2)
+	packet = binary.LittleEndian.AppendUint16(packet, addressType)
+	packet = binary.LittleEndian.AppendUint16(packet, uint16(len(address)))
+	packet = append(packet, address...)
+	packet = binary.LittleEndian.AppendUint16(packet, dataType)
+	packet = binary.LittleEndian.AppendUint16(packet, uint16(len(data)))
+	return append(packet, data...)
+}
+
+// handleUnconnected serves the Connection Manager and passes anything else
+// to handle
+func (c *logixTestController) handleUnconnected(request []byte) []byte {
+	service := request[0]
+	data := request[2+2*int(request[1]):]
+
+	switch service {
+	case CIPServiceForwardOpen:
+		c.record(service)
+		if c.refuseForwardOpen {
+			return c.reply(service, CIPStatusConnectionFailure, nil)
+		}
+		response := binary.LittleEndian.AppendUint32(nil, 0x7E570001)
+		response = append(response, data[6:10]...)
+		return c.reply(service, CIPStatusSuccess, append(response, make([]byte, 18)...))
+	case CIPServiceForwardClose:
+		c.record(service)
+		return c.reply(service, CIPStatusSuccess, make([]byte, 10))
+	case CIPServiceUnconnectedSend:
+		c.record(service)
+		length := int(binary.LittleEndian.Uint16(data[2:4]))
+		return c.handle(data[4 : 4+length])
+	}
+	return c.handle(request)
+}
+
+// handle serves a Message Router request
+func (c *logixTestController) handle(request []byte) []byte {
+	service := request[0]
+	path := request[2 : 2+2*int(request[1])]
+	data := request[2+len(path):]
+	c.record(service)
+
+	c.mutex.Lock()
+	defer c.mutex.Unlock()
+
+	switch service {
+	case CIPServiceGetAll:
+		identity := []byte{0x01, 0x00, 0x0E, 0x00, 0x6B, 0x00, 20, 11, 0x60, 0x30, 0x78, 0x56, 0x34, 0x12, 9}
+		return c.reply(service, CIPStatusSuccess, append(append(identity, "1756-L83E"...), 0x03))
+
+	case CIPServiceGetAttributeSingle:
+		return c.reply(service, CIPStatusSuccess, []byte{0x01, 0x00})
+
+	case CIPServiceReadTag:
+		tag, exists := c.tags[logixTestTagName(path)]
+		if !exists {
+			return c.reply(service, CIPStatusPathSegmentError, nil)
+		}
+		response := binary.LittleEndian.AppendUint16(nil, tag.Type.Code)
+		if tag.Type.Code == logixStructType {
+			response = binary.LittleEndian.AppendUint16(response, tag.Type.Handle)
+		}
+		return c.reply(service, CIPStatusSuccess, append(response, tag.Data...))
+
+	case CIPServiceWriteTag:
+		name := logixTestTagName(path)
+		tag, exists := c.tags[name]
+		if !exists || binary.LittleEndian.Uint16(data) != tag.Type.Code {
+			return c.reply(service, CIPStatusPathSegmentError, nil)
+		}
+		offset := 4
+		if tag.Type.Code == logixStructType {
+			offset = 6
+		}
+		if len(data)-offset != len(tag.Data) {
+			return c.reply(service, CIPStatusNotEnoughData, nil)
+		}
+		c.tags[name] = logixTestTag{tag.Type, append([]byte(nil), data[offset:]...)}
+		return c.reply(service, CIPStatusSuccess, nil)
+
+	case CIPServiceMultipleServicePacket:
+		c.mutex.Unlock()
+		defer c.mutex.Lock()
+
+		count := int(binary.LittleEndian.Uint16(data))
+		status := CIPStatusSuccess
+		var replies [][]byte
+		for i := 0; i < count; i++ {
+			end := len(data)
+			if i+1 < count {
+				end = int(binary.LittleEndian.Uint16(data[2+2*(i+1):]))
+			}
+			reply := c.handle(data[binary.LittleEndian.Uint16(data[2+2*i:]):end])
+			if reply[2] != CIPStatusSuccess {
+				status = 0x1E // Embedded service error
+			}
+			replies = append(replies, reply)
+		}
+
+		response := binary.LittleEndian.AppendUint16(nil, uint16(count))
+		offset := 2 + 2*count
+		for _, reply := range replies {
+			response = binary.LittleEndian.AppendUint16(response, uint16(offset))
+			offset += len(reply)
+		}
+		for _, reply := range replies {
+			response = append(response, reply...)
+		}
+		if len(response) > logixMaxMessageSize {
+			c.t.Errorf("multiple service packet reply of %d bytes", len(response))
+		}
+		return c.reply(service, status, response)
+	}
+	return c.reply(service, CIPStatusServiceNotSupported, nil)
+}
+
+func (c *logixTestController) reply(service byte, status uint8, data []byte) []byte {
+	return append([]byte{service | 0x80, 0, status, 0}, data...)
+}
+
+func (c *logixTestController) record(service byte) {
+	c.mutex.Lock()
+	c.services[service]++
+	c.mutex.Unlock()
+}
+
+// logixTestTagName decodes symbolic and 8-bit element segments
+func logixTestTagName(path []byte) string {
+	var name strings.Builder
+	for len(path) >= 2 {
+		switch path[0] {
+		case 0x91:
+			length := int(path[1])
+			if name.Len() > 0 {
+				name.WriteByte('.')
+			}
+			name.Write(path[2 : 2+length])
+			path = path[2+length+length%2:]
+		case 0x28:
+			fmt.Fprintf(&name, "[%d]", path[1])
+			path = path[2:]
+		default:
+			return ""
+		}
+	}
+	return name.String()
+}
@@ -80,6 +80,7 @@ type EtherNetIPTagCache struct {
 	defaultTTL  time.Duration
 	cleanupStop chan struct{}
 	logger      *zap.Logger
+	clock       Clock // DefaultClock if nil
 }
 
 // CachedTag represents a cached tag value
@@ -484,9 +485,9 @@ func (cache *EtherNetIPTagCache) Get(deviceID, tagID string) (interface{}, bool)
 		cached.Mutex.RLock()
 		defer cached.Mutex.RUnlock()
 
-		if time.Now().Before(cached.ExpiresAt) {
+		if now := cache.now(); now.Before(cached.ExpiresAt) {
 			atomic.AddUint64(&cached.AccessCount, 1)
-			cached.LastAccess = time.Now()
+			cached.LastAccess = now
 			atomic.AddUint64(&cache.metrics.TotalReads, 1)
 			return cached.Value, true
 		}
@@ -504,13 +505,14 @@ func (cache *EtherNetIPTagCache) Get(deviceID, tagID string) (interface{}, bool)
 func (cache *EtherNetIPTagCache) Set(deviceID, tagID string, value interface{}, ttl time.Duration) {
 	key := fmt.Sprintf("%s:%s", deviceID, tagID)
 
+	now := cache.now()
 	cached := &CachedTag{
 		Value:       value,
 		Quality:     QualityGood,
-		Timestamp:   time.Now(),
-		ExpiresAt:   time.Now().Add(ttl),
+		Timestamp:   now,
+		ExpiresAt:   now.Add(ttl),
 		AccessCount: 1,
-		LastAccess:  time.Now(),
+		LastAccess:  now,
 	}
 
 	// Check cache size limit
@@ -526,7 +528,7 @@ func (cache *EtherNetIPTagCache) Set(deviceID, tagID string, value interface{},
 // evictLeastRecentlyUsed removes the least recently used entry
 func (cache *EtherNetIPTagCache) evictLeastRecentlyUsed() {
 	var oldestKey interface{}
-	var oldestTime time.Time = time.Now()
+	var oldestTime time.Time
 
 	cache.cache.Range(func(key, value interface{}) bool {
 		cached := value.(*CachedTag)
@@ -534,7 +536,7 @@ func (cache *EtherNetIPTagCache) evictLeastRecentlyUsed() {
 		lastAccess := cached.LastAccess
 		cached.Mutex.RUnlock()
 
-		if lastAccess.Before(oldestTime) {
+		if oldestKey == nil || lastAccess.Before(oldestTime) {
 			oldestTime = lastAccess
 			oldestKey = key
 		}
@@ -548,6 +550,86 @@ func (cache *EtherNetIPTagCache) evictLeastRecentlyUsed() {
 	}
 }
 
+// cachedTagSize is the estimated memory of a cache entry
+const cachedTagSize = 256
+
+// MemoryUsage estimates the bytes held by the cache
+func (cache *EtherNetIPTagCache) MemoryUsage() int64 {
+	return int64(atomic.LoadUint64(&cache.metrics.Size)) * cachedTagSize
+}
+
+// Shrink evicts the least recently used entries holding about bytes, e.g.
+// for a memory budget, and returns the bytes freed
+func (cache *EtherNetIPTagCache) Shrink(bytes int64) int64 {
+	type entry struct {
+		key        interface{}
+		lastAccess time.Time
+	}
+	var entries []entry
+	cache.cache.Range(func(key, value interface{}) bool {
+		cached := value.(*CachedTag)
+		cached.Mutex.RLock()
+		entries = append(entries, entry{key: key, lastAccess: cached.LastAccess})
+		cached.Mutex.RUnlock()
+		return true
+	})
+	sort.Slice(entries, func(i, j int) bool { return entries[i].lastAccess.Before(entries[j].lastAccess) })
+
+	var freed int64
+	for _, e := range entries {
+		if freed >= bytes {
+			break
+		}
+		if _, loaded := cache.cache.LoadAndDelete(e.key); loaded {
+			atomic.AddUint64(&cache.metrics.Evictions, 1)
+			atomic.AddUint64(&cache.metrics.Size, ^uint64(0)) // Decrement
+			freed += cachedTagSize
+		}
+	}
+	return freed
+}
+
+// SetClock sets the clock expiring entries. It must be called before the
+// cache is used.
+func (cache *EtherNetIPTagCache) SetClock(clock Clock) {
+	cache.clock = clock
+}
+
+func (cache *EtherNetIPTagCache) now() time.Time {
+	if cache.clock != nil {
+		return cache.clock.Now()
+	}
+	return DefaultClock.Now()
+}
+
+// RemoveExpired removes the expired entries and returns how many it removed
+func (cache *EtherNetIPTagCache) RemoveExpired() int {
+	now := cache.now()
+	var keysToDelete []interface{}
+
+	cache.cache.Range(func(key, value interface{}) bool {
+		cached := value.(*CachedTag)
+		cached.Mutex.RLock()
+		expired := !now.Before(cached.ExpiresAt)
+		cached.Mutex.RUnlock()
+
+		if expired {
+			keysToDelete = append(keysToDelete, key)
+		}
+		return true
+	})
+
+	removed := 0
+	for _, key := range keysToDelete {
+		if _, loaded := cache.cache.LoadAndDelete(key); loaded {
+			atomic.AddUint64(&cache.metrics.Evictions, 1)
+			atomic.AddUint64(&cache.metrics.Size, ^uint64(0)) // Decrement
+			removed++
+		}
+	}
+	return removed
+}
+
 // cleanupExpiredEntries periodically removes expired cache entries
 func (cache *EtherNetIPTagCache) cleanupExpiredEntries() {
 	ticker := time.NewTicker(30 * time.Second)
@@ -556,29 +638,8 @@ func (cache *EtherNetIPTagCache) cleanupExpiredEntries() {
 	for {
 		select {
 		case <-ticker.C:
-			now := time.Now()
-			var keysToDelete []interface{}
-
-			cache.cache.Range(func(key, value interface{}) bool {
-				cached := value.(*CachedTag)
-				cached.Mutex.RLock()
-				expired := now.After(cached.ExpiresAt)
-				cached.Mutex.RUnlock()
-
-				if expired {
-					keysToDelete = append(keysToDelete, key)
-				}
-				return true
-			})
-
-			for _, key := range keysToDelete {
-				cache.cache.Delete(key)
-				atomic.AddUint64(&cache.metrics.Evictions, 1)
-				atomic.AddUint64(&cache.metrics.Size, ^uint64(0)) // Decrement
-			}
-
-			if len(keysToDelete) > 0 {
-				cache.logger.Debug("Cleaned up expired cache entries", zap.Int("count", len(keysToDelete)))
+			if removed := cache.RemoveExpired(); removed > 0 {
+				cache.logger.Debug("Cleaned up expired cache entries", zap.Int("count", removed))
 			}
 
 		case <-cache.cleanupStop:
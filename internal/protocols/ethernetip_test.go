@@ -533,3 +533,46 @@ func BenchmarkSymbolicPathBuilding(b *testing.B) {
 		}
 	}
 }
+
+func TestEtherNetIPTagCache_Shrink(t *testing.T) {
+	cache := &EtherNetIPTagCache{metrics: &CacheMetrics{}, maxSize: 10, defaultTTL: time.Minute, logger: zap.NewNop()}
+	for i := 0; i < 4; i++ {
+		cache.Set("plc", fmt.Sprintf("tag%d", i), i, time.Minute)
+	}
+	cache.Get("plc", "tag0")
+	assert.Equal(t, int64(4*cachedTagSize), cache.MemoryUsage())
+
+	// The least recently used entries are evicted first
+	assert.Equal(t, int64(2*cachedTagSize), cache.Shrink(cachedTagSize+1))
+	_, found := cache.Get("plc", "tag0")
+	assert.True(t, found)
+	_, found = cache.Get("plc", "tag1")
+	assert.False(t, found)
+	assert.Equal(t, int64(2*cachedTagSize), cache.MemoryUsage())
+}
+
+func TestEtherNetIPTagCache_RemoveExpired(t *testing.T) {
+	clock := NewManualClock(time.Date(2024, 1, 1, 0, 0, 0, 0, time.UTC))
+	cache := &EtherNetIPTagCache{metrics: &CacheMetrics{}, maxSize: 10, defaultTTL: time.Minute, logger: zap.NewNop()}
+	cache.SetClock(clock)
+	cache.Set("plc", "fast", 1, time.Second)
+	cache.Set("plc", "slow", 2, time.Minute)
+
+	assert.Equal(t, 0, cache.RemoveExpired())
+	value, found := cache.Get("plc", "fast")
+	assert.True(t, found)
+	assert.Equal(t, 1, value)
+
+	// Entries expire exactly at their TTL
+	clock.Advance(time.Second)
+	_, found = cache.Get("plc", "fast")
+	assert.False(t, found)
+	cache.Set("plc", "fast", 1, time.Second)
+	clock.Advance(time.Second)
+	assert.Equal(t, 1, cache.RemoveExpired())
+	assert.Equal(t, int64(cachedTagSize), cache.MemoryUsage())
+
+	clock.Advance(time.Minute)
+	assert.Equal(t, 1, cache.RemoveExpired())
+	assert.Equal(t, int64(0), cache.MemoryUsage())
+}
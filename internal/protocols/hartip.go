@@ -0,0 +1,620 @@
+package protocols
+
+import (
+	"context"
+	"errors"
+	"fmt"
+	"net"
+	"strconv"
+	"strings"
+	"sync"
+	"time"
+
+	"go.uber.org/zap"
+)
+
+// HARTIPHandler implements ProtocolHandler for HART field devices reached
+// through a HART-IP server, usually a WirelessHART gateway. The device
+// address is the server's host; field devices behind a gateway are
+// selected by the config key "unique_id" (10 hex digits), other devices
+// by "polling_address", 0 by default. "transport" selects "tcp" (the
+// default) or "udp". Devices on the same server share one session. Tag
+// addresses are the dynamic variables "pv", "sv", "tv" and "qv", the
+// loop current "loop_current" in mA, "percent_range", and device
+// variables read with command 9, e.g. "dv:5".
+type HARTIPHandler struct {
+	logger      *zap.Logger
+	config      *HARTIPConfig
+	mutex       sync.Mutex // serializes opening and closing sessions
+	connections sync.Map   // map[string]*HARTIPConnection
+}
+
+// HARTIPConnection is a session with one HART-IP server
+type HARTIPConnection struct {
+	client    *HARTIPClient
+	address   string
+	transport string
+	createdAt time.Time
+	users     int // guarded by the handler's mutex
+
+	mutex     sync.RWMutex
+	addresses map[byte]HARTAddress
+	lastUsed  time.Time
+	requests  uint64
+	errors    uint64
+}
+
+// HARTIPConfig holds HART-IP-specific configuration
+type HARTIPConfig struct {
+	DefaultTransport  string        `yaml:"default_transport"`
+	Timeout           time.Duration `yaml:"timeout"`
+	InactivityTimeout time.Duration `yaml:"inactivity_timeout"`
+}
+
+// hartTagAddress is a parsed tag address: a dynamic variable index, the
+// loop current or percent of range, or a device variable code
+type hartTagAddress struct {
+	dynamic        int
+	loopCurrent    bool
+	percentRange   bool
+	deviceVariable int
+}
+
+// hartDynamicVariableNames are the tag addresses of the dynamic
+// variables
+var hartDynamicVariableNames = []string{"pv", "sv", "tv", "qv"}
+
+// NewHARTIPHandler creates a new HART-IP protocol handler
+func NewHARTIPHandler(logger *zap.Logger) ProtocolHandler {
+	return &HARTIPHandler{
+		logger: logger,
+		config: &HARTIPConfig{
+			DefaultTransport:  "tcp",
+			Timeout:           5 * time.Second,
+			InactivityTimeout: 60 * time.Second,
+		},
+	}
+}
+
+// Connect opens a session with a device's HART-IP server, unless another
+// device on it is connected
+func (h *HARTIPHandler) Connect(device *Device) error {
+	if _, err := h.fieldDeviceConfig(device); err != nil {
+		return err
+	}
+	port := device.Port
+	if port == 0 {
+		port = HARTIPPort
+	}
+	transport := h.config.DefaultTransport
+	if value, ok := device.Config["transport"].(string); ok && value != "" {
+		transport = value
+	}
+	if transport != "tcp" && transport != "udp" {
+		return fmt.Errorf("unsupported HART-IP transport %q", transport)
+	}
+
+	h.mutex.Lock()
+	defer h.mutex.Unlock()
+
+	connectionKey := fmt.Sprintf("%s/%s", transport, net.JoinHostPort(device.Address, strconv.Itoa(port)))
+	if connInterface, exists := h.connections.Load(connectionKey); exists {
+		if device.ConnectionID != connectionKey {
+			connInterface.(*HARTIPConnection).users++
+		}
+		device.ConnectionID = connectionKey
+		return nil
+	}
+
+	conn, err := net.DialTimeout(transport, net.JoinHostPort(device.Address, strconv.Itoa(port)), h.config.Timeout)
+	if err != nil {
+		return fmt.Errorf("failed to connect to HART-IP server: %w", err)
+	}
+	client := NewHARTIPClient(conn, h.config.Timeout)
+	if err := client.Open(h.config.InactivityTimeout); err != nil {
+		conn.Close()
+		return fmt.Errorf("failed to open HART-IP session: %w", err)
+	}
+
+	connection := &HARTIPConnection{
+		client:    client,
+		address:   net.JoinHostPort(device.Address, strconv.Itoa(port)),
+		transport: transport,
+		createdAt: time.Now(),
+		users:     1,
+		addresses: make(map[byte]HARTAddress),
+		lastUsed:  time.Now(),
+	}
+	h.connections.Store(connectionKey, connection)
+	device.ConnectionID = connectionKey
+
+	h.logger.Info("HART-IP session opened",
+		zap.String("device_id", device.ID),
+		zap.String("address", connection.address),
+		zap.String("transport", transport),
+	)
+
+	return nil
+}
+
+// Disconnect closes the session once no device on its server is
+// connected
+func (h *HARTIPHandler) Disconnect(device *Device) error {
+	if device.ConnectionID == "" {
+		return nil
+	}
+
+	h.mutex.Lock()
+	defer h.mutex.Unlock()
+
+	connInterface, exists := h.connections.Load(device.ConnectionID)
+	if !exists {
+		device.ConnectionID = ""
+		return nil
+	}
+	conn := connInterface.(*HARTIPConnection)
+
+	device.ConnectionID = ""
+	conn.users--
+	if conn.users > 0 {
+		return nil
+	}
+	h.connections.Delete(fmt.Sprintf("%s/%s", conn.transport, conn.address))
+	return conn.client.Close()
+}
+
+// IsConnected checks if the session with a device's server is open
+func (h *HARTIPHandler) IsConnected(device *Device) bool {
+	_, err := h.getConnection(device)
+	return err == nil
+}
+
+// ReadTag reads one variable of a field device
+func (h *HARTIPHandler) ReadTag(device *Device, tag *Tag) (interface{}, error) {
+	results, err := h.read(device, []*Tag{tag})
+	if err != nil {
+		return nil, err
+	}
+	return results[tag.ID].Value, nil
+}
+
+// WriteTag is not supported; the handler only collects process values
+func (h *HARTIPHandler) WriteTag(device *Device, tag *Tag, value interface{}) error {
+	return fmt.Errorf("HART variables are read-only")
+}
+
+// ReadMultipleTags reads variables with one command 3, one command 2 and
+// one command 9 per eight device variables, as the tags require.
+// Variables the device does not return are left out of the results.
+func (h *HARTIPHandler) ReadMultipleTags(device *Device, tags []*Tag) (map[string]interface{}, error) {
+	variables, err := h.read(device, tags)
+	if err != nil && len(variables) == 0 {
+		return nil, err
+	}
+
+	results := make(map[string]interface{}, len(variables))
+	for id, variable := range variables {
+		results[id] = variable.Value
+	}
+	return results, nil
+}
+
+// ReadDeviceVariables reads device variables with command 9 and returns
+// them as tags with their units and quality
+func (h *HARTIPHandler) ReadDeviceVariables(device *Device, codes ...byte) ([]*Tag, error) {
+	conn, err := h.getConnection(device)
+	if err != nil {
+		return nil, err
+	}
+	address, err := h.fieldDevice(conn, device)
+	if err != nil {
+		return nil, err
+	}
+
+	now := time.Now()
+	tags := make([]*Tag, 0, len(codes))
+	for start := 0; start < len(codes); start += hartMaxDeviceVariableSlots {
+		end := start + hartMaxDeviceVariableSlots
+		if end > len(codes) {
+			end = len(codes)
+		}
+		_, variables, err := conn.client.ReadDeviceVariables(address, codes[start:end]...)
+		conn.record(err)
+		if err != nil {
+			return tags, err
+		}
+		for i := range variables {
+			tagAddress := fmt.Sprintf("dv:%d", variables[i].Code)
+			tag := &Tag{
+				ID:        tagAddress,
+				Name:      tagAddress,
+				Address:   tagAddress,
+				DataType:  string(DataTypeFloat64),
+				Value:     variables[i].Value,
+				Timestamp: now,
+				Unit:      variables[i].Unit(),
+			}
+			tag.SetStatus(variables[i].QualityStatus())
+			tags = append(tags, tag)
+		}
+	}
+	return tags, nil
+}
+
+// DiscoverDevices lists the devices of the HART-IP server given as the
+// network range, "host" or "host:port": the device at polling address 0
+// and, for gateways, the field devices behind it
+func (h *HARTIPHandler) DiscoverDevices(ctx context.Context, networkRange string) ([]*Device, error) {
+	host, port := networkRange, HARTIPPort
+	if splitHost, portText, err := net.SplitHostPort(networkRange); err == nil {
+		host = splitHost
+		if port, err = strconv.Atoi(portText); err != nil {
+			return nil, fmt.Errorf("invalid HART-IP server address %q", networkRange)
+		}
+	}
+
+	probe := &Device{
+		ID:       fmt.Sprintf("hart-ip-scan-%s", networkRange),
+		Address:  host,
+		Port:     port,
+		Config:   map[string]interface{}{"polling_address": 0},
+		Protocol: "hart-ip",
+	}
+	if err := h.Connect(probe); err != nil {
+		return nil, err
+	}
+	defer h.Disconnect(probe)
+
+	conn, err := h.getConnection(probe)
+	if err != nil {
+		return nil, err
+	}
+	identity, err := conn.client.ReadIdentity(HARTPollingAddress(0))
+	conn.record(err)
+	if err != nil {
+		return nil, err
+	}
+
+	uniqueID := identity.UniqueID()
+	devices := []*Device{{
+		ID:       fmt.Sprintf("hart-%s", uniqueID),
+		Name:     fmt.Sprintf("HART device %s", uniqueID),
+		Protocol: "hart-ip",
+		Address:  host,
+		Port:     port,
+		Config: map[string]interface{}{
+			"polling_address": 0,
+		},
+		LastSeen: time.Now(),
+	}}
+
+	subDevices, err := conn.client.ReadSubDevices(uniqueID)
+	var responseError *HARTResponseError
+	if errors.As(err, &responseError) && len(subDevices) == 0 {
+		// Field devices without sub-devices do not implement command 74
+		return devices, ctx.Err()
+	}
+	conn.record(err)
+
+	for _, subDevice := range subDevices {
+		uniqueID := subDevice.UniqueID()
+		name := subDevice.LongTag
+		if name == "" {
+			name = fmt.Sprintf("HART device %s", uniqueID)
+		}
+		devices = append(devices, &Device{
+			ID:       fmt.Sprintf("hart-%s", uniqueID),
+			Name:     name,
+			Protocol: "hart-ip",
+			Address:  host,
+			Port:     port,
+			Config: map[string]interface{}{
+				"unique_id": uniqueID.String(),
+			},
+			LastSeen: time.Now(),
+		})
+	}
+	if err == nil {
+		err = ctx.Err()
+	}
+	return devices, err
+}
+
+// GetDeviceInfo returns information about a field device from its
+// identity
+func (h *HARTIPHandler) GetDeviceInfo(device *Device) (*DeviceInfo, error) {
+	info := &DeviceInfo{
+		Vendor:         "Unknown",
+		Model:          "HART Field Device",
+		Capabilities:   []string{"hart-ip", "universal-commands", "device-variables"},
+		MaxConnections: 0, // Sessions are shared per server
+		CustomInfo:     make(map[string]string),
+	}
+
+	conn, err := h.getConnection(device)
+	if err != nil {
+		return info, nil
+	}
+	address, err := h.fieldDevice(conn, device)
+	if err != nil {
+		return info, nil
+	}
+	identity, err := conn.client.ReadIdentity(address)
+	conn.record(err)
+	if err != nil {
+		h.logger.Debug("HART identity not available",
+			zap.String("device_id", device.ID),
+			zap.Error(err),
+		)
+		return info, nil
+	}
+
+	info.Vendor = fmt.Sprintf("HART manufacturer 0x%04X", identity.ManufacturerID)
+	info.Model = fmt.Sprintf("HART device type 0x%04X", identity.ExpandedDeviceType)
+	info.SerialNumber = fmt.Sprintf("%06X", identity.DeviceID)
+	info.FirmwareVersion = strconv.Itoa(int(identity.SoftwareRevision))
+	info.CustomInfo["unique_id"] = identity.UniqueID().String()
+	info.CustomInfo["hart_revision"] = strconv.Itoa(int(identity.UniversalRevision))
+	info.CustomInfo["device_revision"] = strconv.Itoa(int(identity.DeviceRevision))
+	info.CustomInfo["hardware_revision"] = strconv.Itoa(int(identity.HardwareRevision))
+	info.CustomInfo["config_change_counter"] = strconv.Itoa(int(identity.ConfigChangeCounter))
+	return info, nil
+}
+
+// GetSupportedDataTypes returns the data types of HART values
+func (h *HARTIPHandler) GetSupportedDataTypes() []string {
+	return []string{
+		string(DataTypeFloat64),
+	}
+}
+
+// ValidateTagAddress validates a variable address
+func (h *HARTIPHandler) ValidateTagAddress(address string) error {
+	_, err := parseHARTTagAddress(address)
+	return err
+}
+
+// Ping reads a field device's identity
+func (h *HARTIPHandler) Ping(device *Device) error {
+	conn, err := h.getConnection(device)
+	if err != nil {
+		return err
+	}
+	address, err := h.fieldDevice(conn, device)
+	if err != nil {
+		return err
+	}
+
+	_, err = conn.client.ReadIdentity(address)
+	conn.record(err)
+	return err
+}
+
+// GetDiagnostics returns diagnostic information for a field device and
+// its session; the device is unhealthy while it reports a malfunction
+func (h *HARTIPHandler) GetDiagnostics(device *Device) (*Diagnostics, error) {
+	conn, err := h.getConnection(device)
+	if err != nil {
+		return nil, err
+	}
+
+	protocolDiagnostics := map[string]interface{}{
+		"server":    conn.address,
+		"transport": conn.transport,
+	}
+	malfunction := false
+	if address, err := h.fieldDevice(conn, device); err == nil {
+		if status, exists := conn.client.DeviceStatus(address); exists {
+			protocolDiagnostics["device_status"] = HARTDeviceStatus(status)
+			malfunction = status&HARTStatusMalfunction != 0
+		}
+		protocolDiagnostics["unique_id"] = address.String()
+	}
+
+	conn.mutex.RLock()
+	defer conn.mutex.RUnlock()
+
+	protocolDiagnostics["requests"] = conn.requests
+	diagnostics := &Diagnostics{
+		IsHealthy:           !malfunction,
+		LastCommunication:   conn.lastUsed,
+		ErrorCount:          conn.errors,
+		ConnectionUptime:    time.Since(conn.createdAt),
+		ProtocolDiagnostics: protocolDiagnostics,
+	}
+	if conn.requests > 0 {
+		diagnostics.SuccessRate = float64(conn.requests-conn.errors) / float64(conn.requests)
+		diagnostics.IsHealthy = diagnostics.IsHealthy && diagnostics.SuccessRate > 0.5
+	}
+	return diagnostics, nil
+}
+
+func (h *HARTIPHandler) getConnection(device *Device) (*HARTIPConnection, error) {
+	if device.ConnectionID == "" {
+		return nil, fmt.Errorf("device not connected")
+	}
+
+	connInterface, exists := h.connections.Load(device.ConnectionID)
+	if !exists {
+		return nil, fmt.Errorf("connection not found")
+	}
+	return connInterface.(*HARTIPConnection), nil
+}
+
+// read reads the variables of tags and returns them by tag ID
+func (h *HARTIPHandler) read(device *Device, tags []*Tag) (map[string]*HARTVariable, error) {
+	conn, err := h.getConnection(device)
+	if err != nil {
+		return nil, err
+	}
+	address, err := h.fieldDevice(conn, device)
+	if err != nil {
+		return nil, err
+	}
+
+	addresses := make(map[string]hartTagAddress, len(tags))
+	var dynamic, loop bool
+	var codes []byte
+	for _, tag := range tags {
+		tagAddress, err := parseHARTTagAddress(tag.Address)
+		if err != nil {
+			return nil, err
+		}
+		addresses[tag.ID] = tagAddress
+		switch {
+		case tagAddress.percentRange:
+			loop = true
+		case tagAddress.deviceVariable >= 0:
+			codes = append(codes, byte(tagAddress.deviceVariable))
+		default:
+			dynamic = true
+		}
+	}
+
+	var firstErr error
+	keep := func(err error) {
+		conn.record(err)
+		if firstErr == nil {
+			firstErr = err
+		}
+	}
+
+	var dynamicVariables *HARTDynamicVariables
+	if dynamic {
+		dynamicVariables, err = conn.client.ReadDynamicVariables(address)
+		keep(err)
+	}
+	var percentRange float64
+	if loop {
+		_, percentRange, err = conn.client.ReadLoopCurrent(address)
+		keep(err)
+		if err != nil {
+			loop = false
+		}
+	}
+	deviceVariables := make(map[byte]HARTVariable)
+	for start := 0; start < len(codes); start += hartMaxDeviceVariableSlots {
+		end := start + hartMaxDeviceVariableSlots
+		if end > len(codes) {
+			end = len(codes)
+		}
+		_, variables, err := conn.client.ReadDeviceVariables(address, codes[start:end]...)
+		keep(err)
+		for _, variable := range variables {
+			deviceVariables[variable.Code] = variable
+		}
+	}
+
+	results := make(map[string]*HARTVariable)
+	for id, tagAddress := range addresses {
+		switch {
+		case tagAddress.percentRange:
+			if loop {
+				results[id] = &HARTVariable{Units: 57, Value: percentRange, Status: hartDeviceVariableGood}
+			}
+		case tagAddress.deviceVariable >= 0:
+			if variable, exists := deviceVariables[byte(tagAddress.deviceVariable)]; exists {
+				results[id] = &variable
+			}
+		case dynamicVariables == nil:
+		case tagAddress.loopCurrent:
+			results[id] = &HARTVariable{Units: 39, Value: dynamicVariables.LoopCurrent, Status: hartDeviceVariableGood}
+		case tagAddress.dynamic < len(dynamicVariables.Variables):
+			results[id] = &dynamicVariables.Variables[tagAddress.dynamic]
+		}
+	}
+	if len(results) < len(addresses) && firstErr == nil {
+		firstErr = fmt.Errorf("device did not return all variables")
+	}
+	return results, firstErr
+}
+
+// fieldDevice returns a device's long address; devices configured by
+// polling address are identified with command 0 once per session
+func (h *HARTIPHandler) fieldDevice(conn *HARTIPConnection, device *Device) (HARTAddress, error) {
+	address, err := h.fieldDeviceConfig(device)
+	if err != nil || address.Long() {
+		return address, err
+	}
+
+	polling := address[0]
+	conn.mutex.RLock()
+	uniqueID, exists := conn.addresses[polling]
+	conn.mutex.RUnlock()
+	if exists {
+		return uniqueID, nil
+	}
+
+	identity, err := conn.client.ReadIdentity(address)
+	conn.record(err)
+	if err != nil {
+		return nil, err
+	}
+	uniqueID = identity.UniqueID()
+
+	conn.mutex.Lock()
+	conn.addresses[polling] = uniqueID
+	conn.mutex.Unlock()
+	return uniqueID, nil
+}
+
+// fieldDeviceConfig returns the unique ID or polling address of a
+// device's config
+func (h *HARTIPHandler) fieldDeviceConfig(device *Device) (HARTAddress, error) {
+	if uniqueID, ok := device.Config["unique_id"].(string); ok && uniqueID != "" {
+		return ParseHARTUniqueID(uniqueID)
+	}
+
+	switch polling := device.Config["polling_address"].(type) {
+	case nil:
+		return HARTPollingAddress(0), nil
+	case int:
+		if polling < 0 || polling > hartMaxPollingNumber {
+			return nil, fmt.Errorf("invalid HART polling address %d", polling)
+		}
+		return HARTPollingAddress(byte(polling)), nil
+	default:
+		return nil, fmt.Errorf("invalid HART polling address %v", polling)
+	}
+}
+
+func (c *HARTIPConnection) record(err error) {
+	c.mutex.Lock()
+	defer c.mutex.Unlock()
+
+	c.requests++
+	c.lastUsed = time.Now()
+	if err != nil {
+		c.errors++
+	}
+}
+
+// parseHARTTagAddress parses a variable address
+func parseHARTTagAddress(address string) (hartTagAddress, error) {
+	parsed := hartTagAddress{dynamic: -1, deviceVariable: -1}
+	normalized := strings.ToLower(address)
+	switch normalized {
+	case "loop_current":
+		parsed.loopCurrent = true
+		return parsed, nil
+	case "percent_range":
+		parsed.percentRange = true
+		return parsed, nil
+	}
+	for i, name := range hartDynamicVariableNames {
+		if normalized == name {
+			parsed.dynamic = i
+			return parsed, nil
+		}
+	}
+
+	if codeText, found := strings.CutPrefix(normalized, "dv:"); found {
+		code, err := strconv.ParseUint(codeText, 10, 8)
+		if err != nil || code >= hartDeviceVariableNotUsed {
+			return parsed, fmt.Errorf("invalid HART device variable %q", address)
+		}
+		parsed.deviceVariable = int(code)
+		return parsed, nil
+	}
+	return parsed, fmt.Errorf("invalid HART variable address %q", address)
+}
@@ -0,0 +1,311 @@
+package protocols
+
+import (
+	"encoding/binary"
+	"errors"
+	"fmt"
+	"io"
+	"net"
+	"sync"
+	"time"
+)
+
+// HARTIPClient is a primary master session with a HART-IP server such as
+// a WirelessHART gateway. The session is kept open with keep-alive
+// messages while no commands are sent.
+type HARTIPClient struct {
+	Timeout time.Duration
+
+	conn   net.Conn
+	stream bool
+
+	mutex    sync.Mutex // serializes messages and guards the fields below
+	sequence uint16
+	lastSent time.Time
+	buffer   []byte
+	statuses map[string]byte
+	closed   bool
+	done     chan struct{}
+}
+
+// NewHARTIPClient creates a client on a TCP or UDP connection to a
+// HART-IP server
+func NewHARTIPClient(conn net.Conn, timeout time.Duration) *HARTIPClient {
+	_, packet := conn.(net.PacketConn)
+	return &HARTIPClient{
+		Timeout:  timeout,
+		conn:     conn,
+		stream:   !packet,
+		buffer:   make([]byte, hartIPMaxMessage),
+		statuses: make(map[string]byte),
+		done:     make(chan struct{}),
+	}
+}
+
+// Open initiates the session. The server closes it when no message is
+// received within the inactivity time, so the client sends keep-alives
+// at a third of the time the server accepted.
+func (c *HARTIPClient) Open(inactivity time.Duration) error {
+	body := []byte{hartIPPrimaryMaster}
+	body = binary.BigEndian.AppendUint32(body, uint32(inactivity/time.Millisecond))
+
+	c.mutex.Lock()
+	header, response, err := c.transact(hartIPSessionInitiate, body)
+	c.mutex.Unlock()
+	if err != nil {
+		return err
+	}
+	// Status 8 tells that the server set a different inactivity time
+	if header.Status != 0 && header.Status != 8 {
+		return fmt.Errorf("HART-IP session refused with status %d", header.Status)
+	}
+	if len(response) >= 5 {
+		inactivity = time.Duration(binary.BigEndian.Uint32(response[1:5])) * time.Millisecond
+	}
+
+	if interval := inactivity / 3; interval > 0 {
+		go c.keepAlive(interval)
+	}
+	return nil
+}
+
+// Close closes the session and the connection
+func (c *HARTIPClient) Close() error {
+	c.mutex.Lock()
+	if !c.closed {
+		c.closed = true
+		close(c.done)
+		_, _, _ = c.transact(hartIPSessionClose, nil)
+	}
+	c.mutex.Unlock()
+	return c.conn.Close()
+}
+
+// KeepAlive sends a keep-alive message
+func (c *HARTIPClient) KeepAlive() error {
+	c.mutex.Lock()
+	defer c.mutex.Unlock()
+
+	header, _, err := c.transact(hartIPKeepAlive, nil)
+	if err == nil && header.Status != 0 {
+		err = fmt.Errorf("HART-IP keep-alive failed with status %d", header.Status)
+	}
+	return err
+}
+
+// Command sends a HART command to a field device and returns its
+// response. Error response codes are returned as HARTResponseError;
+// warnings are returned with the response.
+func (c *HARTIPClient) Command(address HARTAddress, command byte, data []byte) (*HARTFrame, error) {
+	pdu, err := AppendHARTFrame(nil, NewHARTRequest(address, command, data))
+	if err != nil {
+		return nil, err
+	}
+
+	c.mutex.Lock()
+	defer c.mutex.Unlock()
+
+	header, body, err := c.transact(hartIPPassThrough, pdu)
+	if err != nil {
+		return nil, err
+	}
+	if header.Status != 0 {
+		return nil, fmt.Errorf("HART-IP request for command %d failed with status %d", command, header.Status)
+	}
+
+	response, err := ParseHARTFrame(body)
+	if err != nil {
+		return nil, err
+	}
+	if !response.IsResponse() || response.Command != command {
+		return nil, fmt.Errorf("unexpected HART response to command %d", command)
+	}
+	c.statuses[address.String()] = response.DeviceStatus
+	if response.ResponseCode != 0 && !hartResponseWarning(response.ResponseCode) {
+		return response, &HARTResponseError{Command: command, Code: response.ResponseCode}
+	}
+	return response, nil
+}
+
+// DeviceStatus returns the field device status of the last response of
+// a device
+func (c *HARTIPClient) DeviceStatus(address HARTAddress) (byte, bool) {
+	c.mutex.Lock()
+	defer c.mutex.Unlock()
+
+	status, exists := c.statuses[address.String()]
+	return status, exists
+}
+
+// ReadIdentity reads a field device's identity with command 0
+func (c *HARTIPClient) ReadIdentity(address HARTAddress) (*HARTIdentity, error) {
+	response, err := c.Command(address, HARTReadUniqueIdentifier, nil)
+	if err != nil {
+		return nil, err
+	}
+	return ParseHARTIdentity(response.Data)
+}
+
+// ReadPrimaryVariable reads the primary variable with command 1
+func (c *HARTIPClient) ReadPrimaryVariable(address HARTAddress) (*HARTVariable, error) {
+	response, err := c.Command(address, HARTReadPrimaryVariable, nil)
+	if err != nil {
+		return nil, err
+	}
+	return ParseHARTPrimaryVariable(response.Data)
+}
+
+// ReadLoopCurrent reads the loop current in mA and the percent of range
+// with command 2
+func (c *HARTIPClient) ReadLoopCurrent(address HARTAddress) (float64, float64, error) {
+	response, err := c.Command(address, HARTReadLoopCurrent, nil)
+	if err != nil {
+		return 0, 0, err
+	}
+	return ParseHARTLoopCurrent(response.Data)
+}
+
+// ReadDynamicVariables reads the loop current and the dynamic variables
+// with command 3
+func (c *HARTIPClient) ReadDynamicVariables(address HARTAddress) (*HARTDynamicVariables, error) {
+	response, err := c.Command(address, HARTReadDynamicVariables, nil)
+	if err != nil {
+		return nil, err
+	}
+	return ParseHARTDynamicVariables(response.Data)
+}
+
+// ReadDeviceVariables reads up to eight device variables with command 9
+// and returns them with the extended field device status
+func (c *HARTIPClient) ReadDeviceVariables(address HARTAddress, codes ...byte) (byte, []HARTVariable, error) {
+	if len(codes) == 0 || len(codes) > hartMaxDeviceVariableSlots {
+		return 0, nil, fmt.Errorf("HART command 9 reads 1 to %d device variables", hartMaxDeviceVariableSlots)
+	}
+	response, err := c.Command(address, HARTReadDeviceVariables, codes)
+	if err != nil {
+		return 0, nil, err
+	}
+	return ParseHARTDeviceVariables(response.Data)
+}
+
+// ReadSubDevices lists the field devices behind a gateway with commands
+// 74 and 84. The gateway itself, index 0, is not listed.
+func (c *HARTIPClient) ReadSubDevices(gateway HARTAddress) ([]*HARTSubDevice, error) {
+	response, err := c.Command(gateway, HARTReadIOSystem, nil)
+	if err != nil {
+		return nil, err
+	}
+	count, err := ParseHARTIOSystem(response.Data)
+	if err != nil {
+		return nil, err
+	}
+
+	devices := make([]*HARTSubDevice, 0, count)
+	for index := uint16(1); index < count; index++ {
+		response, err := c.Command(gateway, HARTReadSubDeviceIdentity, binary.BigEndian.AppendUint16(nil, index))
+		if err != nil {
+			return devices, err
+		}
+		device, err := ParseHARTSubDevice(response.Data)
+		if err != nil {
+			return devices, err
+		}
+		devices = append(devices, device)
+	}
+	return devices, nil
+}
+
+// transact sends a request and returns the response with its sequence
+// number; the caller must hold the mutex
+func (c *HARTIPClient) transact(messageID byte, body []byte) (HARTIPHeader, []byte, error) {
+	if c.closed && messageID != hartIPSessionClose {
+		return HARTIPHeader{}, nil, errors.New("HART-IP session closed")
+	}
+
+	c.sequence++
+	request := AppendHARTIPMessage(nil, HARTIPHeader{
+		MessageType: hartIPRequest,
+		MessageID:   messageID,
+		Sequence:    c.sequence,
+	}, body)
+
+	deadline := time.Now().Add(c.Timeout)
+	_ = c.conn.SetDeadline(deadline)
+	defer c.conn.SetDeadline(time.Time{})
+
+	if _, err := c.conn.Write(request); err != nil {
+		return HARTIPHeader{}, nil, fmt.Errorf("failed to send HART-IP request: %w", err)
+	}
+	c.lastSent = time.Now()
+
+	for {
+		message, err := c.receive()
+		if err != nil {
+			if isTimeoutError(err) {
+				return HARTIPHeader{}, nil, fmt.Errorf("no HART-IP response to message %d: %w", messageID, err)
+			}
+			return HARTIPHeader{}, nil, err
+		}
+		header, response, err := ParseHARTIPMessage(message)
+		if err != nil {
+			if c.stream {
+				return HARTIPHeader{}, nil, err
+			}
+			continue
+		}
+
+		// Published burst messages and stale responses are skipped
+		if header.MessageType == hartIPPublish || header.Sequence != c.sequence || header.MessageID != messageID {
+			continue
+		}
+		switch header.MessageType {
+		case hartIPResponse:
+			return header, append([]byte(nil), response...), nil
+		case hartIPError, hartIPNAK:
+			return header, nil, fmt.Errorf("HART-IP message %d rejected with status %d", messageID, header.Status)
+		}
+	}
+}
+
+// receive reads one message from the connection
+func (c *HARTIPClient) receive() ([]byte, error) {
+	if !c.stream {
+		n, err := c.conn.Read(c.buffer)
+		if err != nil {
+			return nil, err
+		}
+		return c.buffer[:n], nil
+	}
+
+	if _, err := io.ReadFull(c.conn, c.buffer[:hartIPHeaderSize]); err != nil {
+		return nil, err
+	}
+	length := int(binary.BigEndian.Uint16(c.buffer[6:8]))
+	if length < hartIPHeaderSize || length > len(c.buffer) {
+		return nil, fmt.Errorf("invalid HART-IP message length %d", length)
+	}
+	if _, err := io.ReadFull(c.conn, c.buffer[hartIPHeaderSize:length]); err != nil {
+		return nil, err
+	}
+	return c.buffer[:length], nil
+}
+
+// keepAlive sends keep-alives while the session is idle
+func (c *HARTIPClient) keepAlive(interval time.Duration) {
+	ticker := time.NewTicker(interval / 2)
+	defer ticker.Stop()
+
+	for {
+		select {
+		case <-ticker.C:
+			c.mutex.Lock()
+			idle := time.Since(c.lastSent) >= interval
+			c.mutex.Unlock()
+			if idle {
+				_ = c.KeepAlive()
+			}
+		case <-c.done:
+			return
+		}
+	}
+}
@@ -0,0 +1,390 @@
+package protocols
+
+import (
+	"encoding/binary"
+	"fmt"
+	"math"
+	"strings"
+)
+
+// HART Universal Commands
+//
+// Every HART field device implements the universal commands. Command 0
+// returns the device's identity and with it the unique ID used for long
+// frames, commands 1, 2 and 3 the primary variable, the loop current and
+// the dynamic variables PV, SV, TV and QV, and command 9 up to eight
+// device variables with their status. Gateways of WirelessHART networks
+// and multiplexers list the field devices behind them with commands 74
+// and 84. Values are IEEE 754 floats; units are HART unit codes.
+
+// HART universal and common practice commands
+const (
+	HARTReadUniqueIdentifier  = 0
+	HARTReadPrimaryVariable   = 1
+	HARTReadLoopCurrent       = 2
+	HARTReadDynamicVariables  = 3
+	HARTReadDeviceVariables   = 9
+	HARTReadIOSystem          = 74
+	HARTReadSubDeviceIdentity = 84
+)
+
+// Field device status bits, returned with every response
+const (
+	HARTStatusMalfunction      = 0x80
+	HARTStatusConfigChanged    = 0x40
+	HARTStatusColdStart        = 0x20
+	HARTStatusMoreStatus       = 0x10
+	HARTStatusLoopCurrentFixed = 0x08
+	HARTStatusLoopCurrentLimit = 0x04
+	HARTStatusVariableLimit    = 0x02
+	HARTStatusPrimaryLimit     = 0x01
+)
+
+// Response codes, device variable status and minimum response sizes
+const (
+	hartCommunicationError      = 0x80
+	hartResponseUpdateFailure   = 8
+	hartResponseTruncated       = 30
+	hartDeviceVariableQuality   = 0xC0
+	hartDeviceVariableGood      = 0xC0
+	hartDeviceVariableManual    = 0x80
+	hartDeviceVariablePoor      = 0x40
+	hartDeviceVariableBad       = 0x00
+	hartDeviceVariableNotUsed   = 250
+	hartMaxDeviceVariableSlots  = 8
+	hartDeviceVariableSlotSize  = 8
+	hartDynamicVariableSlotSize = 5
+	hartSubDeviceIdentitySize   = 44
+)
+
+// hartStatusNames are the names of field device status bits
+var hartStatusNames = []struct {
+	bit  byte
+	name string
+}{
+	{HARTStatusMalfunction, "device malfunction"},
+	{HARTStatusConfigChanged, "configuration changed"},
+	{HARTStatusColdStart, "cold start"},
+	{HARTStatusMoreStatus, "more status available"},
+	{HARTStatusLoopCurrentFixed, "loop current fixed"},
+	{HARTStatusLoopCurrentLimit, "loop current saturated"},
+	{HARTStatusVariableLimit, "non-primary variable out of limits"},
+	{HARTStatusPrimaryLimit, "primary variable out of limits"},
+}
+
+// hartResponseNames are the names of common command response codes
+var hartResponseNames = map[byte]string{
+	2:  "invalid selection",
+	5:  "too few data bytes received",
+	6:  "device-specific command error",
+	7:  "in write protect mode",
+	16: "access restricted",
+	32: "busy",
+	33: "delayed response initiated",
+	34: "delayed response running",
+	35: "delayed response dead",
+	36: "delayed response conflict",
+	64: "command not implemented",
+}
+
+// hartUnits are the symbols of common HART unit codes
+var hartUnits = map[byte]string{
+	1:   "inH2O",
+	2:   "inHg",
+	3:   "ftH2O",
+	4:   "mmH2O",
+	5:   "mmHg",
+	6:   "psi",
+	7:   "bar",
+	8:   "mbar",
+	11:  "Pa",
+	12:  "kPa",
+	13:  "torr",
+	14:  "atm",
+	17:  "l/min",
+	19:  "m³/h",
+	24:  "l/s",
+	32:  "°C",
+	33:  "°F",
+	35:  "K",
+	36:  "mV",
+	37:  "Ω",
+	38:  "Hz",
+	39:  "mA",
+	41:  "l",
+	43:  "m³",
+	45:  "m",
+	49:  "mm",
+	57:  "%",
+	58:  "V",
+	59:  "pH",
+	70:  "g/s",
+	73:  "kg/s",
+	75:  "kg/h",
+	237: "MPa",
+}
+
+// HARTUnit returns the symbol of a HART unit code, or the code itself if
+// it is not known
+func HARTUnit(code byte) string {
+	if unit, exists := hartUnits[code]; exists {
+		return unit
+	}
+	if code == 251 {
+		return ""
+	}
+	return fmt.Sprintf("unit %d", code)
+}
+
+// HARTResponseError is an error response code returned by a field device
+type HARTResponseError struct {
+	Command byte
+	Code    byte
+}
+
+func (e *HARTResponseError) Error() string {
+	if e.Code&hartCommunicationError != 0 {
+		return fmt.Sprintf("HART command %d failed with communication error 0x%02X", e.Command, e.Code&0x7F)
+	}
+	if name, exists := hartResponseNames[e.Code]; exists {
+		return fmt.Sprintf("HART command %d failed: %s", e.Command, name)
+	}
+	return fmt.Sprintf("HART command %d failed with response code %d", e.Command, e.Code)
+}
+
+// hartResponseWarning reports whether a response code is a warning that
+// comes with valid data
+func hartResponseWarning(code byte) bool {
+	return code == hartResponseUpdateFailure || code == hartResponseTruncated
+}
+
+// HARTDeviceStatus returns the names of the bits set in a field device
+// status
+func HARTDeviceStatus(status byte) []string {
+	names := make([]string, 0)
+	for _, entry := range hartStatusNames {
+		if status&entry.bit != 0 {
+			names = append(names, entry.name)
+		}
+	}
+	return names
+}
+
+// HARTIdentity is a field device's identity as returned by command 0
+type HARTIdentity struct {
+	ExpandedDeviceType  uint16
+	ManufacturerID      uint16
+	DeviceID            uint32
+	UniversalRevision   byte
+	DeviceRevision      byte
+	SoftwareRevision    byte
+	HardwareRevision    byte
+	MaxDeviceVariables  byte
+	ConfigChangeCounter uint16
+	PrivateLabel        uint16
+}
+
+// UniqueID returns the device's long address
+func (i *HARTIdentity) UniqueID() HARTAddress {
+	return HARTAddress{
+		byte(i.ExpandedDeviceType>>8) & hartAddressPolling,
+		byte(i.ExpandedDeviceType),
+		byte(i.DeviceID >> 16),
+		byte(i.DeviceID >> 8),
+		byte(i.DeviceID),
+	}
+}
+
+// ParseHARTIdentity parses the response data of command 0; fields
+// introduced after the device's HART revision are left zero
+func ParseHARTIdentity(data []byte) (*HARTIdentity, error) {
+	if len(data) < 12 || data[0] != 254 {
+		return nil, fmt.Errorf("invalid HART identity of %d bytes", len(data))
+	}
+
+	identity := &HARTIdentity{
+		ExpandedDeviceType: binary.BigEndian.Uint16(data[1:3]),
+		ManufacturerID:     uint16(data[1]),
+		UniversalRevision:  data[4],
+		DeviceRevision:     data[5],
+		SoftwareRevision:   data[6],
+		HardwareRevision:   data[7] >> 3,
+		DeviceID:           uint32(data[9])<<16 | uint32(data[10])<<8 | uint32(data[11]),
+	}
+	if len(data) >= 16 {
+		identity.MaxDeviceVariables = data[13]
+		identity.ConfigChangeCounter = binary.BigEndian.Uint16(data[14:16])
+	}
+	if len(data) >= 21 && identity.UniversalRevision >= 7 {
+		identity.ManufacturerID = binary.BigEndian.Uint16(data[17:19])
+		identity.PrivateLabel = binary.BigEndian.Uint16(data[19:21])
+	}
+	return identity, nil
+}
+
+// HARTVariable is a process value with its unit code. Status is the
+// device variable status of command 9 and good for other commands.
+type HARTVariable struct {
+	Code           byte
+	Classification byte
+	Units          byte
+	Value          float64
+	Status         byte
+}
+
+// Unit returns the symbol of the variable's unit
+func (v *HARTVariable) Unit() string {
+	return HARTUnit(v.Units)
+}
+
+// Quality returns the quality of the variable's process data
+func (v *HARTVariable) Quality() Quality {
+	return v.QualityStatus().Quality
+}
+
+// QualityStatus interprets the variable's status byte
+func (v *HARTVariable) QualityStatus() QualityStatus {
+	if math.IsNaN(v.Value) {
+		return NewQualityStatus(QualityBad, QualitySubstatusSensorFailure, QualitySourceDevice)
+	}
+	switch v.Status & hartDeviceVariableQuality {
+	case hartDeviceVariableGood:
+		return GoodQuality
+	case hartDeviceVariableManual:
+		return NewQualityStatus(QualityUncertain, QualitySubstatusOverridden, QualitySourceDevice)
+	case hartDeviceVariablePoor:
+		return NewQualityStatus(QualityUncertain, QualitySubstatusInaccurate, QualitySourceDevice)
+	}
+	return NewQualityStatus(QualityBad, QualitySubstatusDeviceFailure, QualitySourceDevice)
+}
+
+// HARTDynamicVariables are the loop current and the dynamic variables of
+// command 3; devices return between one and four variables
+type HARTDynamicVariables struct {
+	LoopCurrent float64
+	Variables   []HARTVariable
+}
+
+// ParseHARTPrimaryVariable parses the response data of command 1
+func ParseHARTPrimaryVariable(data []byte) (*HARTVariable, error) {
+	if len(data) < 5 {
+		return nil, fmt.Errorf("invalid HART primary variable of %d bytes", len(data))
+	}
+	return &HARTVariable{
+		Units:  data[0],
+		Value:  hartFloat(data[1:5]),
+		Status: hartDeviceVariableGood,
+	}, nil
+}
+
+// ParseHARTLoopCurrent parses the response data of command 2 into the
+// loop current in mA and the percent of range
+func ParseHARTLoopCurrent(data []byte) (float64, float64, error) {
+	if len(data) < 8 {
+		return 0, 0, fmt.Errorf("invalid HART loop current of %d bytes", len(data))
+	}
+	return hartFloat(data[0:4]), hartFloat(data[4:8]), nil
+}
+
+// ParseHARTDynamicVariables parses the response data of command 3
+func ParseHARTDynamicVariables(data []byte) (*HARTDynamicVariables, error) {
+	if len(data) < 4+hartDynamicVariableSlotSize {
+		return nil, fmt.Errorf("invalid HART dynamic variables of %d bytes", len(data))
+	}
+
+	variables := &HARTDynamicVariables{LoopCurrent: hartFloat(data[0:4])}
+	for offset := 4; offset+hartDynamicVariableSlotSize <= len(data) && len(variables.Variables) < 4; offset += hartDynamicVariableSlotSize {
+		variables.Variables = append(variables.Variables, HARTVariable{
+			Code:   byte(len(variables.Variables)),
+			Units:  data[offset],
+			Value:  hartFloat(data[offset+1 : offset+5]),
+			Status: hartDeviceVariableGood,
+		})
+	}
+	return variables, nil
+}
+
+// ParseHARTDeviceVariables parses the response data of command 9 into
+// its extended field device status and variables. Slots of variables the
+// device does not have are left out.
+func ParseHARTDeviceVariables(data []byte) (byte, []HARTVariable, error) {
+	if len(data) < 1+hartDeviceVariableSlotSize {
+		return 0, nil, fmt.Errorf("invalid HART device variables of %d bytes", len(data))
+	}
+
+	var variables []HARTVariable
+	for offset := 1; offset+hartDeviceVariableSlotSize <= len(data) && len(variables) < hartMaxDeviceVariableSlots; offset += hartDeviceVariableSlotSize {
+		slot := data[offset : offset+hartDeviceVariableSlotSize]
+		if slot[0] == hartDeviceVariableNotUsed {
+			continue
+		}
+		variables = append(variables, HARTVariable{
+			Code:           slot[0],
+			Classification: slot[1],
+			Units:          slot[2],
+			Value:          hartFloat(slot[3:7]),
+			Status:         slot[7],
+		})
+	}
+	return data[0], variables, nil
+}
+
+// HARTSubDevice is a field device behind a gateway as returned by
+// command 84
+type HARTSubDevice struct {
+	Index              uint16
+	IOCard             byte
+	Channel            byte
+	ManufacturerID     uint16
+	ExpandedDeviceType uint16
+	DeviceID           uint32
+	UniversalRevision  byte
+	LongTag            string
+}
+
+// UniqueID returns the sub-device's long address
+func (s *HARTSubDevice) UniqueID() HARTAddress {
+	identity := HARTIdentity{ExpandedDeviceType: s.ExpandedDeviceType, DeviceID: s.DeviceID}
+	return identity.UniqueID()
+}
+
+// ParseHARTSubDevice parses the response data of command 84
+func ParseHARTSubDevice(data []byte) (*HARTSubDevice, error) {
+	if len(data) < hartSubDeviceIdentitySize {
+		return nil, fmt.Errorf("invalid HART sub-device identity of %d bytes", len(data))
+	}
+	return &HARTSubDevice{
+		Index:              binary.BigEndian.Uint16(data[0:2]),
+		IOCard:             data[2],
+		Channel:            data[3],
+		ManufacturerID:     binary.BigEndian.Uint16(data[4:6]),
+		ExpandedDeviceType: binary.BigEndian.Uint16(data[6:8]),
+		DeviceID:           uint32(data[8])<<16 | uint32(data[9])<<8 | uint32(data[10]),
+		UniversalRevision:  data[11],
+		LongTag:            hartLatin1(data[12:44]),
+	}, nil
+}
+
+// ParseHARTIOSystem parses the number of devices detected by a gateway,
+// including the gateway itself, from the response data of command 74
+func ParseHARTIOSystem(data []byte) (uint16, error) {
+	if len(data) < 5 {
+		return 0, fmt.Errorf("invalid HART I/O system capabilities of %d bytes", len(data))
+	}
+	return binary.BigEndian.Uint16(data[3:5]), nil
+}
+
+// hartFloat decodes an IEEE 754 float; HART uses 0x7FA00000 for values
+// that are not available, which decodes as NaN
+func hartFloat(data []byte) float64 {
+	return float64(math.Float32frombits(binary.BigEndian.Uint32(data)))
+}
+
+// hartLatin1 decodes a space or zero padded ISO Latin-1 string
+func hartLatin1(data []byte) string {
+	runes := make([]rune, 0, len(data))
+	for _, b := range data {
+		runes = append(runes, rune(b))
+	}
+	return strings.TrimRight(string(runes), " \x00")
+}
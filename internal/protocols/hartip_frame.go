@@ -0,0 +1,243 @@
+package protocols
+
+import (
+	"encoding/binary"
+	"encoding/hex"
+	"fmt"
+	"io"
+	"strings"
+)
+
+// HART-IP Framing
+//
+// HART-IP carries HART token-passing PDUs over TCP or UDP port 5094. Each
+// message starts with an eight byte header: version, message type,
+// message ID, status, sequence number and the byte count of the whole
+// message. A session is opened with a session initiate request; HART
+// commands are then sent as token-passing PDU messages. The PDU is the
+// frame HART sends on the 4-20 mA loop without its preambles: delimiter,
+// short or long address, command, byte count, data and a longitudinal
+// parity checksum. Responses start their data with two status bytes.
+
+// HARTIPPort is the default HART-IP port
+const HARTIPPort = 5094
+
+// HART-IP message types
+const (
+	hartIPRequest  = 0
+	hartIPResponse = 1
+	hartIPPublish  = 2
+	hartIPError    = 3
+	hartIPNAK      = 15
+)
+
+// HART-IP message IDs
+const (
+	hartIPSessionInitiate = 0
+	hartIPSessionClose    = 1
+	hartIPKeepAlive       = 2
+	hartIPPassThrough     = 3
+)
+
+const (
+	hartIPVersion    = 1
+	hartIPHeaderSize = 8
+	hartIPMaxMessage = 1024
+
+	// HART primary master session
+	hartIPPrimaryMaster = 1
+)
+
+// HART PDU delimiters
+const (
+	hartDelimiterSTX     = 0x02 // master to field device
+	hartDelimiterACK     = 0x06 // field device to master
+	hartDelimiterBurst   = 0x01 // published by a field device
+	hartDelimiterLong    = 0x80
+	hartDelimiterTypes   = 0x07
+	hartAddressMaster    = 0x80 // primary master
+	hartAddressPolling   = 0x3F
+	hartMaxPollingNumber = 63
+)
+
+// HARTIPHeader is the header of a HART-IP message
+type HARTIPHeader struct {
+	MessageType byte
+	MessageID   byte
+	Status      byte
+	Sequence    uint16
+}
+
+// AppendHARTIPMessage appends a HART-IP message
+func AppendHARTIPMessage(dst []byte, header HARTIPHeader, body []byte) []byte {
+	dst = append(dst, hartIPVersion, header.MessageType, header.MessageID, header.Status)
+	dst = binary.BigEndian.AppendUint16(dst, header.Sequence)
+	dst = binary.BigEndian.AppendUint16(dst, uint16(hartIPHeaderSize+len(body)))
+	return append(dst, body...)
+}
+
+// ParseHARTIPMessage parses a HART-IP message into its header and body.
+// The body aliases data.
+func ParseHARTIPMessage(data []byte) (HARTIPHeader, []byte, error) {
+	if len(data) < hartIPHeaderSize {
+		return HARTIPHeader{}, nil, io.ErrUnexpectedEOF
+	}
+	if data[0] != hartIPVersion && data[0] != 2 {
+		return HARTIPHeader{}, nil, fmt.Errorf("unsupported HART-IP version %d", data[0])
+	}
+	length := int(binary.BigEndian.Uint16(data[6:8]))
+	if length < hartIPHeaderSize || length > hartIPMaxMessage {
+		return HARTIPHeader{}, nil, fmt.Errorf("invalid HART-IP message length %d", length)
+	}
+	if len(data) < length {
+		return HARTIPHeader{}, nil, io.ErrUnexpectedEOF
+	}
+
+	header := HARTIPHeader{
+		MessageType: data[1],
+		MessageID:   data[2],
+		Status:      data[3],
+		Sequence:    binary.BigEndian.Uint16(data[4:6]),
+	}
+	return header, data[hartIPHeaderSize:length], nil
+}
+
+// HARTAddress is the address of a field device: one byte with a polling
+// address or the five byte unique ID of a long frame
+type HARTAddress []byte
+
+// HARTPollingAddress returns the short address of a polling address
+func HARTPollingAddress(polling byte) HARTAddress {
+	return HARTAddress{polling & hartAddressPolling}
+}
+
+// ParseHARTUniqueID parses a unique ID of 10 hex digits, e.g. "2631A0B1C2"
+func ParseHARTUniqueID(text string) (HARTAddress, error) {
+	address, err := hex.DecodeString(text)
+	if err != nil || len(address) != 5 {
+		return nil, fmt.Errorf("invalid HART unique ID %q", text)
+	}
+	address[0] &= hartAddressPolling
+	return HARTAddress(address), nil
+}
+
+// Long reports whether the address is a unique ID
+func (a HARTAddress) Long() bool {
+	return len(a) == 5
+}
+
+func (a HARTAddress) String() string {
+	if a.Long() {
+		return strings.ToUpper(hex.EncodeToString(a))
+	}
+	if len(a) == 1 {
+		return fmt.Sprintf("polling address %d", a[0]&hartAddressPolling)
+	}
+	return "invalid address"
+}
+
+// HARTFrame is a HART token-passing PDU. Responses hold their two status
+// bytes in ResponseCode and DeviceStatus rather than in Data.
+type HARTFrame struct {
+	Delimiter    byte
+	Address      HARTAddress
+	Command      byte
+	ResponseCode byte
+	DeviceStatus byte
+	Data         []byte
+}
+
+// NewHARTRequest creates a request frame from the primary master
+func NewHARTRequest(address HARTAddress, command byte, data []byte) *HARTFrame {
+	delimiter := byte(hartDelimiterSTX)
+	if address.Long() {
+		delimiter |= hartDelimiterLong
+	}
+	return &HARTFrame{
+		Delimiter: delimiter,
+		Address:   address,
+		Command:   command,
+		Data:      data,
+	}
+}
+
+// IsResponse reports whether the frame is sent by a field device
+func (f *HARTFrame) IsResponse() bool {
+	frameType := f.Delimiter & hartDelimiterTypes
+	return frameType == hartDelimiterACK || frameType == hartDelimiterBurst
+}
+
+// AppendHARTFrame appends a PDU with its checksum
+func AppendHARTFrame(dst []byte, f *HARTFrame) ([]byte, error) {
+	if f.Address.Long() != (f.Delimiter&hartDelimiterLong != 0) || (len(f.Address) != 1 && len(f.Address) != 5) {
+		return dst, fmt.Errorf("HART address %s does not match delimiter 0x%02X", f.Address, f.Delimiter)
+	}
+	count := len(f.Data)
+	if f.IsResponse() {
+		count += 2
+	}
+	if count > 255 {
+		return dst, fmt.Errorf("HART data of %d bytes exceeds maximum", len(f.Data))
+	}
+
+	start := len(dst)
+	dst = append(dst, f.Delimiter, f.Address[0]|hartAddressMaster)
+	dst = append(dst, f.Address[1:]...)
+	dst = append(dst, f.Command, byte(count))
+	if f.IsResponse() {
+		dst = append(dst, f.ResponseCode, f.DeviceStatus)
+	}
+	dst = append(dst, f.Data...)
+	return append(dst, hartChecksum(dst[start:])), nil
+}
+
+// ParseHARTFrame parses a PDU and checks its checksum. The frame's data
+// aliases data.
+func ParseHARTFrame(data []byte) (*HARTFrame, error) {
+	if len(data) < 1 {
+		return nil, io.ErrUnexpectedEOF
+	}
+	f := &HARTFrame{Delimiter: data[0]}
+	if data[0]&0x60 != 0 {
+		return nil, fmt.Errorf("unsupported HART delimiter 0x%02X", data[0])
+	}
+	addressLength := 1
+	if f.Delimiter&hartDelimiterLong != 0 {
+		addressLength = 5
+	}
+	if len(data) < 1+addressLength+2 {
+		return nil, io.ErrUnexpectedEOF
+	}
+
+	address := append(HARTAddress(nil), data[1:1+addressLength]...)
+	address[0] &= hartAddressPolling
+	f.Address = address
+	f.Command = data[1+addressLength]
+	count := int(data[2+addressLength])
+	body := data[3+addressLength:]
+	if len(body) < count+1 {
+		return nil, io.ErrUnexpectedEOF
+	}
+	if checksum := hartChecksum(data[:3+addressLength+count]); checksum != body[count] {
+		return nil, fmt.Errorf("HART checksum 0x%02X does not match 0x%02X", body[count], checksum)
+	}
+
+	body = body[:count]
+	if f.IsResponse() {
+		if count < 2 {
+			return nil, fmt.Errorf("HART response without status")
+		}
+		f.ResponseCode, f.DeviceStatus, body = body[0], body[1], body[2:]
+	}
+	f.Data = body
+	return f, nil
+}
+
+// hartChecksum is the exclusive or of all bytes of a PDU
+func hartChecksum(data []byte) byte {
+	var checksum byte
+	for _, b := range data {
+		checksum ^= b
+	}
+	return checksum
+}
@@ -0,0 +1,412 @@
+package protocols
+
+import (
+	"context"
+	"encoding/binary"
+	"io"
+	"math"
+	"net"
+	"strconv"
+	"sync"
+	"testing"
+	"time"
+
+	"github.com/stretchr/testify/assert"
+	"go.uber.org/zap"
+)
+
+func TestHARTFrame_RoundTrip(t *testing.T) {
+	frame, err := AppendHARTFrame(nil, NewHARTRequest(HARTPollingAddress(0), HARTReadUniqueIdentifier, nil))
+	assert.NoError(t, err)
+	assert.Equal(t, []byte{0x02, 0x80, 0x00, 0x00, 0x82}, frame)
+
+	uniqueID, err := ParseHARTUniqueID("2608ABCDEF")
+	assert.NoError(t, err)
+	assert.True(t, uniqueID.Long())
+	assert.Equal(t, "2608ABCDEF", uniqueID.String())
+	frame, err = AppendHARTFrame(nil, NewHARTRequest(uniqueID, HARTReadDynamicVariables, nil))
+	assert.NoError(t, err)
+	assert.Equal(t, []byte{0x82, 0xA6, 0x08, 0xAB, 0xCD, 0xEF, 0x03, 0x00, 0xA6}, frame)
+
+	response := &HARTFrame{
+		Delimiter:    hartDelimiterACK | hartDelimiterLong,
+		Address:      uniqueID,
+		Command:      HARTReadPrimaryVariable,
+		DeviceStatus: HARTStatusConfigChanged,
+		Data:         []byte{32, 0x41, 0xAC, 0x00, 0x00},
+	}
+	frame, err = AppendHARTFrame(nil, response)
+	assert.NoError(t, err)
+	parsed, err := ParseHARTFrame(frame)
+	assert.NoError(t, err)
+	assert.True(t, parsed.IsResponse())
+	assert.Equal(t, response, parsed)
+
+	frame[len(frame)-1]++
+	_, err = ParseHARTFrame(frame)
+	assert.ErrorContains(t, err, "checksum")
+	_, err = ParseHARTFrame(frame[:6])
+	assert.ErrorIs(t, err, io.ErrUnexpectedEOF)
+	_, err = ParseHARTUniqueID("2608AB")
+	assert.Error(t, err)
+
+	message := AppendHARTIPMessage(nil, HARTIPHeader{MessageType: hartIPResponse, MessageID: hartIPPassThrough, Sequence: 7}, []byte{1, 2})
+	assert.Equal(t, []byte{0x01, 0x01, 0x03, 0x00, 0x00, 0x07, 0x00, 0x0A, 0x01, 0x02}, message)
+	header, body, err := ParseHARTIPMessage(message)
+	assert.NoError(t, err)
+	assert.Equal(t, uint16(7), header.Sequence)
+	assert.Equal(t, []byte{1, 2}, body)
+	_, _, err = ParseHARTIPMessage(message[:9])
+	assert.ErrorIs(t, err, io.ErrUnexpectedEOF)
+}
+
+func TestHARTCommands(t *testing.T) {
+	identity, err := ParseHARTIdentity(hartTestIdentity(0x2608, 0xABCDEF))
+	assert.NoError(t, err)
+	assert.Equal(t, uint16(0x2608), identity.ExpandedDeviceType)
+	assert.Equal(t, uint16(0x0026), identity.ManufacturerID)
+	assert.Equal(t, uint32(0xABCDEF), identity.DeviceID)
+	assert.Equal(t, byte(7), identity.UniversalRevision)
+	assert.Equal(t, byte(3), identity.HardwareRevision)
+	assert.Equal(t, uint16(12), identity.ConfigChangeCounter)
+	assert.Equal(t, "2608ABCDEF", identity.UniqueID().String())
+
+	// HART 5 devices end after the device ID
+	identity, err = ParseHARTIdentity([]byte{254, 0x26, 0x06, 5, 5, 1, 2, 0x08, 0x00, 0x12, 0x34, 0x56})
+	assert.NoError(t, err)
+	assert.Equal(t, uint16(0x26), identity.ManufacturerID)
+	assert.Equal(t, "2606123456", identity.UniqueID().String())
+	_, err = ParseHARTIdentity([]byte{253, 0x26})
+	assert.Error(t, err)
+
+	variables, err := ParseHARTDynamicVariables(hartTestDynamicVariables())
+	assert.NoError(t, err)
+	assert.Equal(t, 12.0, variables.LoopCurrent)
+	assert.Len(t, variables.Variables, 4)
+	assert.Equal(t, 21.5, variables.Variables[0].Value)
+	assert.Equal(t, "°C", variables.Variables[0].Unit())
+	assert.Equal(t, "%", variables.Variables[3].Unit())
+	assert.Equal(t, QualityGood, variables.Variables[0].Quality())
+
+	status, deviceVariables, err := ParseHARTDeviceVariables(hartTestDeviceVariables([]byte{0, 1, 9}))
+	assert.NoError(t, err)
+	assert.Equal(t, byte(0), status)
+	assert.Len(t, deviceVariables, 2)
+	assert.Equal(t, QualityGood, deviceVariables[0].Quality())
+	assert.Equal(t, QualityUncertain, deviceVariables[1].Quality())
+	assert.Equal(t, "psi", deviceVariables[1].Unit())
+
+	nan := HARTVariable{Value: math.NaN(), Status: hartDeviceVariableGood}
+	assert.Equal(t, QualityBad, nan.Quality())
+	assert.Equal(t, "unit 200", HARTUnit(200))
+	assert.Equal(t, []string{"device malfunction", "primary variable out of limits"}, HARTDeviceStatus(0x81))
+	assert.Equal(t, "HART command 9 failed: command not implemented", (&HARTResponseError{Command: 9, Code: 64}).Error())
+}
+
+// hartTestIdentity returns the command 0 response of a HART 7 device
+func hartTestIdentity(deviceType uint16, deviceID uint32) []byte {
+	data := []byte{254, byte(deviceType >> 8), byte(deviceType), 5, 7, 1, 2, 3 << 3, 0x00,
+		byte(deviceID >> 16), byte(deviceID >> 8), byte(deviceID), 5, 4, 0x00, 12, 0x00}
+	return append(data, 0x00, 0x26, 0x00, 0x26, 0x81)
+}
+
+func hartTestFloat(dst []byte, value float32) []byte {
+	return binary.BigEndian.AppendUint32(dst, math.Float32bits(value))
+}
+
+func hartTestDynamicVariables() []byte {
+	data := hartTestFloat(nil, 12)
+	data = hartTestFloat(append(data, 32), 21.5)
+	data = hartTestFloat(append(data, 33), 70.7)
+	data = hartTestFloat(append(data, 39), 12)
+	return hartTestFloat(append(data, 57), 50)
+}
+
+// hartTestDeviceVariables returns the command 9 response for device
+// variables 0 and 1; other codes are not used
+func hartTestDeviceVariables(codes []byte) []byte {
+	data := []byte{0x00}
+	for _, code := range codes {
+		switch code {
+		case 0:
+			data = hartTestFloat(append(data, 0, 64, 32), 21.5)
+			data = append(data, 0xC0)
+		case 1:
+			data = hartTestFloat(append(data, 1, 65, 6), 14.7)
+			data = append(data, 0x40)
+		default:
+			data = hartTestFloat(append(data, 250, 0, 250), float32(math.NaN()))
+			data = append(data, 0x30)
+		}
+	}
+	return append(data, 0x00, 0x00, 0x00, 0x00)
+}
+
+// hartTestServer is a WirelessHART gateway, polling address 0 and unique
+// ID 2681000102, with one field device, 2608ABCDEF, behind it
+type hartTestServer struct {
+	listener net.Listener
+
+	mutex     sync.Mutex
+	keepAlive int
+	closed    int
+}
+
+var (
+	hartTestGateway     = HARTAddress{0x26, 0x81, 0x00, 0x01, 0x02}
+	hartTestFieldDevice = HARTAddress{0x26, 0x08, 0xAB, 0xCD, 0xEF}
+)
+
+func newHARTTestServer(t *testing.T) *hartTestServer {
+	listener, err := net.Listen("tcp", "127.0.0.1:0")
+	if err != nil {
+		t.Fatalf("failed to listen: %v", err)
+	}
+	server := &hartTestServer{listener: listener}
+	go func() {
+		for {
+			conn, err := listener.Accept()
+			if err != nil {
+				return
+			}
+			go server.serve(conn)
+		}
+	}()
+	t.Cleanup(func() { listener.Close() })
+	return server
+}
+
+func (s *hartTestServer) port() int {
+	return s.listener.Addr().(*net.TCPAddr).Port
+}
+
+func (s *hartTestServer) serve(conn net.Conn) {
+	defer conn.Close()
+	buffer := make([]byte, hartIPMaxMessage)
+	for {
+		if _, err := io.ReadFull(conn, buffer[:hartIPHeaderSize]); err != nil {
+			return
+		}
+		length := int(binary.BigEndian.Uint16(buffer[6:8]))
+		if _, err := io.ReadFull(conn, buffer[hartIPHeaderSize:length]); err != nil {
+			return
+		}
+		header, body, err := ParseHARTIPMessage(buffer[:length])
+		if err != nil {
+			return
+		}
+
+		response := HARTIPHeader{MessageType: hartIPResponse, MessageID: header.MessageID, Sequence: header.Sequence}
+		var responseBody []byte
+		switch header.MessageID {
+		case hartIPSessionInitiate:
+			responseBody = body
+		case hartIPKeepAlive:
+			s.mutex.Lock()
+			s.keepAlive++
+			s.mutex.Unlock()
+		case hartIPSessionClose:
+			s.mutex.Lock()
+			s.closed++
+			s.mutex.Unlock()
+		case hartIPPassThrough:
+			request, err := ParseHARTFrame(body)
+			if err != nil {
+				return
+			}
+			// A burst message published before the response
+			if request.Command == HARTReadPrimaryVariable {
+				publish, _ := AppendHARTFrame(nil, &HARTFrame{Delimiter: hartDelimiterBurst | hartDelimiterLong, Address: hartTestFieldDevice, Command: 1, Data: []byte{32, 0, 0, 0, 0}})
+				_, _ = conn.Write(AppendHARTIPMessage(nil, HARTIPHeader{MessageType: hartIPPublish, MessageID: hartIPPassThrough}, publish))
+			}
+			responseBody, _ = AppendHARTFrame(nil, s.command(request))
+		}
+		if _, err := conn.Write(AppendHARTIPMessage(nil, response, responseBody)); err != nil {
+			return
+		}
+	}
+}
+
+func (s *hartTestServer) command(request *HARTFrame) *HARTFrame {
+	response := &HARTFrame{
+		Delimiter: hartDelimiterACK | request.Delimiter&hartDelimiterLong,
+		Address:   request.Address,
+		Command:   request.Command,
+	}
+	gateway := !request.Address.Long() && request.Address[0] == 0 || request.Address.String() == hartTestGateway.String()
+	fieldDevice := request.Address.String() == hartTestFieldDevice.String()
+	if !gateway && !fieldDevice {
+		response.ResponseCode = 2
+		return response
+	}
+	if fieldDevice {
+		response.DeviceStatus = HARTStatusConfigChanged
+	}
+
+	switch {
+	case request.Command == HARTReadUniqueIdentifier && gateway:
+		response.Data = hartTestIdentity(0x2681, 0x000102)
+	case request.Command == HARTReadUniqueIdentifier:
+		response.Data = hartTestIdentity(0x2608, 0xABCDEF)
+	case request.Command == HARTReadIOSystem && gateway:
+		response.Data = []byte{4, 1, 16, 0x00, 0x02, 0, 1, 3}
+	case request.Command == HARTReadSubDeviceIdentity && gateway:
+		if binary.BigEndian.Uint16(request.Data) != 1 {
+			response.ResponseCode = 2
+			break
+		}
+		data := []byte{0x00, 0x01, 0, 0, 0x00, 0x26, 0x26, 0x08, 0xAB, 0xCD, 0xEF, 7}
+		tag := []byte("TT-101                          ")
+		response.Data = append(append(data, tag...), 1, 0x81, 0x00, 0x26)
+	case request.Command == HARTReadPrimaryVariable && fieldDevice:
+		response.Data = hartTestFloat([]byte{32}, 21.5)
+	case request.Command == HARTReadLoopCurrent && fieldDevice:
+		response.Data = hartTestFloat(hartTestFloat(nil, 12), 50)
+	case request.Command == HARTReadDynamicVariables && fieldDevice:
+		response.Data = hartTestDynamicVariables()
+	case request.Command == HARTReadDeviceVariables && fieldDevice:
+		response.Data = hartTestDeviceVariables(request.Data)
+	default:
+		response.ResponseCode = 64
+	}
+	return response
+}
+
+func TestHARTIPClient(t *testing.T) {
+	server := newHARTTestServer(t)
+	conn, err := net.Dial("tcp", server.listener.Addr().String())
+	assert.NoError(t, err)
+
+	client := NewHARTIPClient(conn, time.Second)
+	assert.NoError(t, client.Open(150*time.Millisecond))
+
+	identity, err := client.ReadIdentity(HARTPollingAddress(0))
+	assert.NoError(t, err)
+	assert.Equal(t, hartTestGateway, identity.UniqueID())
+
+	variable, err := client.ReadPrimaryVariable(hartTestFieldDevice)
+	assert.NoError(t, err)
+	assert.Equal(t, 21.5, variable.Value)
+	assert.Equal(t, "°C", variable.Unit())
+	status, exists := client.DeviceStatus(hartTestFieldDevice)
+	assert.True(t, exists)
+	assert.Equal(t, byte(HARTStatusConfigChanged), status)
+
+	current, percent, err := client.ReadLoopCurrent(hartTestFieldDevice)
+	assert.NoError(t, err)
+	assert.Equal(t, 12.0, current)
+	assert.Equal(t, 50.0, percent)
+
+	_, variables, err := client.ReadDeviceVariables(hartTestFieldDevice, 0, 1)
+	assert.NoError(t, err)
+	assert.Len(t, variables, 2)
+	_, _, err = client.ReadDeviceVariables(hartTestFieldDevice)
+	assert.Error(t, err)
+
+	subDevices, err := client.ReadSubDevices(hartTestGateway)
+	assert.NoError(t, err)
+	if assert.Len(t, subDevices, 1) {
+		assert.Equal(t, "TT-101", subDevices[0].LongTag)
+		assert.Equal(t, hartTestFieldDevice, subDevices[0].UniqueID())
+	}
+
+	_, err = client.ReadSubDevices(hartTestFieldDevice)
+	var responseError *HARTResponseError
+	assert.ErrorAs(t, err, &responseError)
+	assert.Equal(t, byte(64), responseError.Code)
+
+	// Keep-alives are sent while the session is idle
+	assert.Eventually(t, func() bool {
+		server.mutex.Lock()
+		defer server.mutex.Unlock()
+		return server.keepAlive > 0
+	}, time.Second, 10*time.Millisecond)
+
+	assert.NoError(t, client.Close())
+	server.mutex.Lock()
+	assert.Equal(t, 1, server.closed)
+	server.mutex.Unlock()
+	_, err = client.ReadIdentity(HARTPollingAddress(0))
+	assert.Error(t, err)
+}
+
+func TestHARTIPHandler(t *testing.T) {
+	server := newHARTTestServer(t)
+	handler := NewHARTIPHandler(zap.NewNop()).(*HARTIPHandler)
+	handler.config.Timeout = time.Second
+
+	address := "127.0.0.1:" + strconv.Itoa(server.port())
+	devices, err := handler.DiscoverDevices(context.Background(), address)
+	assert.NoError(t, err)
+	if assert.Len(t, devices, 2) {
+		assert.Equal(t, "hart-2681000102", devices[0].ID)
+		assert.Equal(t, "TT-101", devices[1].Name)
+		assert.Equal(t, "2608ABCDEF", devices[1].Config["unique_id"])
+	}
+
+	gateway := &Device{ID: "gateway", Address: "127.0.0.1", Port: server.port(), Config: map[string]interface{}{}}
+	device := &Device{
+		ID:      "tt-101",
+		Address: "127.0.0.1",
+		Port:    server.port(),
+		Config:  map[string]interface{}{"unique_id": "2608ABCDEF"},
+	}
+	assert.NoError(t, handler.Connect(gateway))
+	assert.NoError(t, handler.Connect(device))
+	assert.Equal(t, gateway.ConnectionID, device.ConnectionID)
+
+	value, err := handler.ReadTag(device, &Tag{ID: "pv", Address: "PV"})
+	assert.NoError(t, err)
+	assert.Equal(t, 21.5, value)
+
+	results, err := handler.ReadMultipleTags(device, []*Tag{
+		{ID: "temperature", Address: "pv"},
+		{ID: "current", Address: "loop_current"},
+		{ID: "range", Address: "percent_range"},
+		{ID: "pressure", Address: "dv:1"},
+		{ID: "missing", Address: "dv:9"},
+	})
+	assert.NoError(t, err)
+	assert.Equal(t, map[string]interface{}{
+		"temperature": 21.5,
+		"current":     12.0,
+		"range":       50.0,
+		"pressure":    float64(float32(14.7)),
+	}, results)
+
+	tags, err := handler.ReadDeviceVariables(device, 0, 1)
+	assert.NoError(t, err)
+	if assert.Len(t, tags, 2) {
+		assert.Equal(t, "dv:0", tags[0].Address)
+		assert.Equal(t, "°C", tags[0].Unit)
+		assert.Equal(t, QualityUncertain, tags[1].Quality)
+	}
+
+	_, err = handler.ReadTag(gateway, &Tag{Address: "pv"})
+	assert.ErrorContains(t, err, "command not implemented")
+
+	info, err := handler.GetDeviceInfo(device)
+	assert.NoError(t, err)
+	assert.Equal(t, "HART manufacturer 0x0026", info.Vendor)
+	assert.Equal(t, "ABCDEF", info.SerialNumber)
+	assert.Equal(t, "2608ABCDEF", info.CustomInfo["unique_id"])
+
+	assert.NoError(t, handler.Ping(device))
+	diagnostics, err := handler.GetDiagnostics(device)
+	assert.NoError(t, err)
+	assert.True(t, diagnostics.IsHealthy)
+	assert.Equal(t, []string{"configuration changed"}, diagnostics.ProtocolDiagnostics.(map[string]interface{})["device_status"])
+
+	assert.NoError(t, handler.ValidateTagAddress("qv"))
+	assert.NoError(t, handler.ValidateTagAddress("dv:246"))
+	assert.Error(t, handler.ValidateTagAddress("dv:250"))
+	assert.Error(t, handler.ValidateTagAddress("fv"))
+	assert.Error(t, handler.WriteTag(device, &Tag{Address: "pv"}, 1.0))
+	assert.Error(t, handler.Connect(&Device{Address: "127.0.0.1", Config: map[string]interface{}{"polling_address": 64}}))
+
+	// The session stays open until its last device disconnects
+	assert.NoError(t, handler.Disconnect(gateway))
+	assert.True(t, handler.IsConnected(device))
+	assert.NoError(t, handler.Disconnect(device))
+	assert.False(t, handler.IsConnected(device))
+}
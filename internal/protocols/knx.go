@@ -0,0 +1,597 @@
+package protocols
+
+import (
+	"context"
+	"encoding/binary"
+	"fmt"
+	"net"
+	"sort"
+	"strconv"
+	"strings"
+	"sync"
+	"time"
+
+	"go.uber.org/zap"
+)
+
+// KNXHandler implements ProtocolHandler for KNX installations reached
+// through a KNXnet/IP gateway. The device address is the gateway's IP
+// address. Tag addresses are group addresses with their datapoint type,
+// e.g. "1/2/3:9.001"; the config key "datapoints" maps group addresses to
+// datapoint types for tags addressed by group address alone. Group values
+// written on the bus are monitored, so reads are answered from values
+// seen within the cache age and sent as group value reads otherwise.
+type KNXHandler struct {
+	logger        *zap.Logger
+	config        *KNXConfig
+	connections   sync.Map // map[string]*KNXConnection
+	searchAddress string
+}
+
+// KNXConnection is a tunnel to one gateway
+type KNXConnection struct {
+	tunnel     *KNXTunnel
+	address    string
+	datapoints map[KNXGroupAddress]KNXDatapointType
+	createdAt  time.Time
+
+	mutex     sync.RWMutex
+	values    map[KNXGroupAddress]*KNXGroupTelegram
+	lastUsed  time.Time
+	requests  uint64
+	errors    uint64
+	telegrams uint64
+}
+
+// KNXConfig holds KNX-specific configuration
+type KNXConfig struct {
+	Timeout       time.Duration `yaml:"timeout"`
+	CacheMaxAge   time.Duration `yaml:"cache_max_age"`
+	SearchTimeout time.Duration `yaml:"search_timeout"`
+}
+
+// NewKNXHandler creates a new KNX protocol handler
+func NewKNXHandler(logger *zap.Logger) ProtocolHandler {
+	return &KNXHandler{
+		logger: logger,
+		config: &KNXConfig{
+			Timeout:       3 * time.Second,
+			CacheMaxAge:   60 * time.Second,
+			SearchTimeout: 3 * time.Second,
+		},
+		searchAddress: knxMulticastAddress,
+	}
+}
+
+// Connect opens a tunnel to a gateway
+func (k *KNXHandler) Connect(device *Device) error {
+	port := device.Port
+	if port == 0 {
+		port = KNXnetIPPort
+	}
+	connectionKey := net.JoinHostPort(device.Address, strconv.Itoa(port))
+
+	if _, exists := k.connections.Load(connectionKey); exists {
+		device.ConnectionID = connectionKey
+		return nil
+	}
+
+	datapoints, err := k.datapoints(device)
+	if err != nil {
+		return err
+	}
+
+	conn, err := net.Dial("udp", connectionKey)
+	if err != nil {
+		return fmt.Errorf("failed to open KNXnet/IP socket: %w", err)
+	}
+
+	connection := &KNXConnection{
+		address:    connectionKey,
+		datapoints: datapoints,
+		createdAt:  time.Now(),
+		values:     make(map[KNXGroupAddress]*KNXGroupTelegram),
+		lastUsed:   time.Now(),
+	}
+	tunnel, err := NewKNXTunnel(conn, k.config.Timeout, connection.observe)
+	if err != nil {
+		conn.Close()
+		return fmt.Errorf("failed to connect to KNXnet/IP gateway %s: %w", connectionKey, err)
+	}
+	connection.tunnel = tunnel
+	k.connections.Store(connectionKey, connection)
+	device.ConnectionID = connectionKey
+
+	k.logger.Info("KNXnet/IP tunnel connected",
+		zap.String("device_id", device.ID),
+		zap.String("address", connectionKey),
+		zap.String("individual_address", tunnel.Address().String()),
+		zap.Uint8("channel", tunnel.Channel()),
+	)
+
+	return nil
+}
+
+// Disconnect closes the tunnel to a gateway
+func (k *KNXHandler) Disconnect(device *Device) error {
+	if device.ConnectionID == "" {
+		return nil
+	}
+
+	connInterface, exists := k.connections.LoadAndDelete(device.ConnectionID)
+	if !exists {
+		return nil
+	}
+
+	device.ConnectionID = ""
+	return connInterface.(*KNXConnection).tunnel.Close()
+}
+
+// IsConnected checks if the tunnel to a gateway is open
+func (k *KNXHandler) IsConnected(device *Device) bool {
+	conn, err := k.getConnection(device)
+	return err == nil && conn.tunnel.Err() == nil
+}
+
+// ReadTag returns the value of a group address
+func (k *KNXHandler) ReadTag(device *Device, tag *Tag) (interface{}, error) {
+	conn, err := k.getConnection(device)
+	if err != nil {
+		return nil, err
+	}
+	address, dpt, err := conn.datapoint(tag.Address)
+	if err != nil {
+		return nil, err
+	}
+
+	telegram := conn.value(address, k.config.CacheMaxAge)
+	if telegram == nil {
+		telegram, err = conn.tunnel.GroupRead(address)
+		conn.record(err)
+		if err != nil {
+			return nil, err
+		}
+	}
+	return dpt.Decode(telegram.Data, telegram.Small)
+}
+
+// WriteTag writes a value to a group address
+func (k *KNXHandler) WriteTag(device *Device, tag *Tag, value interface{}) error {
+	conn, err := k.getConnection(device)
+	if err != nil {
+		return err
+	}
+	address, dpt, err := conn.datapoint(tag.Address)
+	if err != nil {
+		return err
+	}
+
+	data, small, err := dpt.Encode(value)
+	if err != nil {
+		return err
+	}
+	err = conn.tunnel.GroupWrite(address, data, small)
+	conn.record(err)
+	if err != nil {
+		return err
+	}
+
+	// The gateway does not indicate our own writes back to us
+	conn.observe(&KNXGroupTelegram{
+		Source:      conn.tunnel.Address(),
+		Destination: address,
+		APCI:        KNXGroupValueWrite,
+		Data:        data,
+		Small:       small,
+		Timestamp:   time.Now(),
+	})
+	return nil
+}
+
+// ReadMultipleTags returns the values of group addresses. Group
+// addresses that do not answer are left out of the results.
+func (k *KNXHandler) ReadMultipleTags(device *Device, tags []*Tag) (map[string]interface{}, error) {
+	if _, err := k.getConnection(device); err != nil {
+		return nil, err
+	}
+
+	results := make(map[string]interface{})
+	for _, tag := range tags {
+		value, err := k.ReadTag(device, tag)
+		if err != nil {
+			k.logger.Debug("KNX group value not available",
+				zap.String("device_id", device.ID),
+				zap.String("address", tag.Address),
+				zap.Error(err),
+			)
+			continue
+		}
+		results[tag.ID] = value
+	}
+	return results, nil
+}
+
+// ReadGroupValues returns the last value seen on each group address as a
+// tag. Values of group addresses without a datapoint type are returned as
+// bytes.
+func (k *KNXHandler) ReadGroupValues(device *Device) ([]*Tag, error) {
+	conn, err := k.getConnection(device)
+	if err != nil {
+		return nil, err
+	}
+
+	conn.mutex.RLock()
+	telegrams := make([]*KNXGroupTelegram, 0, len(conn.values))
+	for _, telegram := range conn.values {
+		telegrams = append(telegrams, telegram)
+	}
+	conn.mutex.RUnlock()
+	sort.Slice(telegrams, func(i, j int) bool {
+		return telegrams[i].Destination < telegrams[j].Destination
+	})
+
+	tags := make([]*Tag, 0, len(telegrams))
+	for _, telegram := range telegrams {
+		address := telegram.Destination.String()
+		tag := &Tag{
+			ID:          address,
+			Name:        address,
+			Address:     address,
+			DataType:    string(DataTypeBytes),
+			Value:       telegram.Data,
+			Quality:     QualityGood,
+			Timestamp:   telegram.Timestamp,
+			Writable:    true,
+			Description: fmt.Sprintf("written by %s", telegram.Source),
+		}
+		if dpt, exists := conn.datapoints[telegram.Destination]; exists {
+			if value, err := dpt.Decode(telegram.Data, telegram.Small); err == nil {
+				tag.DataType = string(dpt.DataType())
+				tag.Value = value
+				tag.Unit = dpt.Unit()
+			} else {
+				tag.SetStatus(NewQualityStatus(QualityBad, QualitySubstatusConfigError, QualitySourceDriver))
+			}
+		}
+		tags = append(tags, tag)
+	}
+	return tags, nil
+}
+
+// DiscoverDevices searches for KNXnet/IP gateways from the local address
+// in the network range
+func (k *KNXHandler) DiscoverDevices(ctx context.Context, networkRange string) ([]*Device, error) {
+	_, network, err := net.ParseCIDR(networkRange)
+	if err != nil || network.IP.To4() == nil {
+		return nil, fmt.Errorf("invalid network range %q", networkRange)
+	}
+	localIP, err := k.localAddress(network)
+	if err != nil {
+		return nil, err
+	}
+	searchAddress, err := net.ResolveUDPAddr("udp4", k.searchAddress)
+	if err != nil {
+		return nil, err
+	}
+
+	conn, err := net.ListenUDP("udp4", &net.UDPAddr{IP: localIP})
+	if err != nil {
+		return nil, fmt.Errorf("failed to open KNXnet/IP search socket: %w", err)
+	}
+	defer conn.Close()
+
+	request := appendKNXFrame(nil, knxSearchRequest, appendKNXEndpoint(nil, conn.LocalAddr().(*net.UDPAddr)))
+	if _, err := conn.WriteToUDP(request, searchAddress); err != nil {
+		return nil, fmt.Errorf("failed to send KNXnet/IP search request: %w", err)
+	}
+
+	deadline := time.Now().Add(k.config.SearchTimeout)
+	if ctxDeadline, ok := ctx.Deadline(); ok && ctxDeadline.Before(deadline) {
+		deadline = ctxDeadline
+	}
+	_ = conn.SetReadDeadline(deadline)
+
+	devices := make([]*Device, 0)
+	found := make(map[string]bool)
+	buffer := make([]byte, knxMaxFrameSize)
+	for ctx.Err() == nil {
+		n, _, err := conn.ReadFromUDP(buffer)
+		if err != nil {
+			break
+		}
+		service, body, err := parseKNXFrame(buffer[:n])
+		if err != nil || service != knxSearchResponse {
+			continue
+		}
+		device, err := k.parseSearchResponse(body)
+		if err != nil || found[device.ID] {
+			continue
+		}
+		found[device.ID] = true
+		devices = append(devices, device)
+	}
+	return devices, ctx.Err()
+}
+
+// GetDeviceInfo returns information about the tunnel to a gateway
+func (k *KNXHandler) GetDeviceInfo(device *Device) (*DeviceInfo, error) {
+	info := &DeviceInfo{
+		Vendor:         "Unknown",
+		Model:          "KNXnet/IP Gateway",
+		Capabilities:   []string{"tunneling", "group-read", "group-write", "group-monitor"},
+		MaxConnections: 4,
+		CustomInfo:     make(map[string]string),
+	}
+
+	conn, err := k.getConnection(device)
+	if err != nil {
+		return info, nil
+	}
+	info.CustomInfo["individual_address"] = conn.tunnel.Address().String()
+	info.CustomInfo["channel"] = strconv.Itoa(int(conn.tunnel.Channel()))
+	info.CustomInfo["datapoints"] = strconv.Itoa(len(conn.datapoints))
+	return info, nil
+}
+
+// GetSupportedDataTypes returns the data types of decoded group values
+func (k *KNXHandler) GetSupportedDataTypes() []string {
+	return []string{
+		string(DataTypeBool),
+		string(DataTypeInt64),
+		string(DataTypeFloat64),
+		string(DataTypeString),
+		string(DataTypeBytes),
+	}
+}
+
+// ValidateTagAddress validates a group address and its datapoint type
+func (k *KNXHandler) ValidateTagAddress(address string) error {
+	_, _, err := parseKNXTagAddress(address)
+	return err
+}
+
+// Ping checks the tunnel with a connection state request
+func (k *KNXHandler) Ping(device *Device) error {
+	conn, err := k.getConnection(device)
+	if err != nil {
+		return err
+	}
+
+	err = conn.tunnel.Heartbeat()
+	conn.record(err)
+	return err
+}
+
+// GetDiagnostics returns diagnostic information for the tunnel to a
+// gateway
+func (k *KNXHandler) GetDiagnostics(device *Device) (*Diagnostics, error) {
+	conn, err := k.getConnection(device)
+	if err != nil {
+		return nil, err
+	}
+
+	conn.mutex.RLock()
+	defer conn.mutex.RUnlock()
+
+	diagnostics := &Diagnostics{
+		IsHealthy:         conn.tunnel.Err() == nil,
+		LastCommunication: conn.lastUsed,
+		ErrorCount:        conn.errors,
+		ConnectionUptime:  time.Since(conn.createdAt),
+		ProtocolDiagnostics: map[string]interface{}{
+			"individual_address": conn.tunnel.Address().String(),
+			"channel":            conn.tunnel.Channel(),
+			"telegrams":          conn.telegrams,
+			"group_addresses":    len(conn.values),
+		},
+	}
+	if conn.requests > 0 {
+		diagnostics.SuccessRate = float64(conn.requests-conn.errors) / float64(conn.requests)
+		diagnostics.IsHealthy = diagnostics.IsHealthy && diagnostics.SuccessRate > 0.5
+	}
+	return diagnostics, nil
+}
+
+// knxValueSize is the estimated memory of a cached group value besides
+// its data
+const knxValueSize = 96
+
+// MemoryUsage estimates the bytes of the group values cached by the
+// tunnels
+func (k *KNXHandler) MemoryUsage() int64 {
+	var usage int64
+	k.connections.Range(func(_, value interface{}) bool {
+		conn := value.(*KNXConnection)
+		conn.mutex.RLock()
+		for _, telegram := range conn.values {
+			usage += knxValueSize + int64(len(telegram.Data))
+		}
+		conn.mutex.RUnlock()
+		return true
+	})
+	return usage
+}
+
+// Shrink evicts the oldest cached group values holding about bytes, e.g.
+// for a memory budget, and returns the bytes freed. Evicted group
+// addresses are read from the bus again.
+func (k *KNXHandler) Shrink(bytes int64) int64 {
+	type entry struct {
+		conn     *KNXConnection
+		telegram *KNXGroupTelegram
+	}
+	var entries []entry
+	k.connections.Range(func(_, value interface{}) bool {
+		conn := value.(*KNXConnection)
+		conn.mutex.RLock()
+		for _, telegram := range conn.values {
+			entries = append(entries, entry{conn: conn, telegram: telegram})
+		}
+		conn.mutex.RUnlock()
+		return true
+	})
+	sort.Slice(entries, func(i, j int) bool {
+		return entries[i].telegram.Timestamp.Before(entries[j].telegram.Timestamp)
+	})
+
+	var freed int64
+	for _, e := range entries {
+		if freed >= bytes {
+			break
+		}
+		e.conn.mutex.Lock()
+		// Skip values replaced since they were collected
+		if e.conn.values[e.telegram.Destination] == e.telegram {
+			delete(e.conn.values, e.telegram.Destination)
+			freed += knxValueSize + int64(len(e.telegram.Data))
+		}
+		e.conn.mutex.Unlock()
+	}
+	return freed
+}
+
+func (k *KNXHandler) getConnection(device *Device) (*KNXConnection, error) {
+	if device.ConnectionID == "" {
+		return nil, fmt.Errorf("device not connected")
+	}
+
+	connInterface, exists := k.connections.Load(device.ConnectionID)
+	if !exists {
+		return nil, fmt.Errorf("connection not found")
+	}
+	return connInterface.(*KNXConnection), nil
+}
+
+// datapoints parses the datapoint types of a device's config
+func (k *KNXHandler) datapoints(device *Device) (map[KNXGroupAddress]KNXDatapointType, error) {
+	datapoints := make(map[KNXGroupAddress]KNXDatapointType)
+	entries, _ := device.Config["datapoints"].(map[string]interface{})
+	for text, dptValue := range entries {
+		address, err := ParseKNXGroupAddress(text)
+		if err != nil {
+			return nil, err
+		}
+		dpt, err := ParseKNXDatapointType(fmt.Sprint(dptValue))
+		if err != nil {
+			return nil, err
+		}
+		datapoints[address] = dpt
+	}
+	return datapoints, nil
+}
+
+// localAddress returns the address of a local interface in a network
+func (k *KNXHandler) localAddress(network *net.IPNet) (net.IP, error) {
+	addresses, err := net.InterfaceAddrs()
+	if err != nil {
+		return nil, err
+	}
+	for _, address := range addresses {
+		if ipNet, ok := address.(*net.IPNet); ok && ipNet.IP.To4() != nil && network.Contains(ipNet.IP) {
+			return ipNet.IP.To4(), nil
+		}
+	}
+	return nil, fmt.Errorf("no local address in network range %s", network)
+}
+
+// parseSearchResponse returns the gateway described by a search
+// response: its control endpoint followed by the device information DIB
+func (k *KNXHandler) parseSearchResponse(body []byte) (*Device, error) {
+	endpoint, err := parseKNXEndpoint(body)
+	if err != nil {
+		return nil, err
+	}
+	dib := body[8:]
+	if len(dib) < 54 || dib[0] < 54 || dib[1] != 0x01 {
+		return nil, fmt.Errorf("invalid KNXnet/IP device information")
+	}
+
+	individualAddress := KNXIndividualAddress(binary.BigEndian.Uint16(dib[4:6]))
+	serialNumber := fmt.Sprintf("%X", dib[8:14])
+	name := strings.TrimRight(string(dib[24:54]), "\x00")
+	if name == "" {
+		name = fmt.Sprintf("KNXnet/IP gateway %s", endpoint.IP)
+	}
+	return &Device{
+		ID:       fmt.Sprintf("knx-%s", endpoint.IP),
+		Name:     name,
+		Protocol: "knx",
+		Address:  endpoint.IP.String(),
+		Port:     endpoint.Port,
+		Config: map[string]interface{}{
+			"individual_address": individualAddress.String(),
+			"serial_number":      serialNumber,
+		},
+		LastSeen: time.Now(),
+	}, nil
+}
+
+// datapoint returns the group address and datapoint type of a tag
+// address, looking up the type in the device's datapoints if the
+// address has none
+func (c *KNXConnection) datapoint(tagAddress string) (KNXGroupAddress, KNXDatapointType, error) {
+	address, dpt, err := parseKNXTagAddress(tagAddress)
+	if err != nil {
+		return 0, KNXDatapointType{}, err
+	}
+	if dpt != nil {
+		return address, *dpt, nil
+	}
+	if configured, exists := c.datapoints[address]; exists {
+		return address, configured, nil
+	}
+	return 0, KNXDatapointType{}, fmt.Errorf("no datapoint type for KNX group address %s", address)
+}
+
+// observe stores the values of group writes and responses
+func (c *KNXConnection) observe(telegram *KNXGroupTelegram) {
+	c.mutex.Lock()
+	defer c.mutex.Unlock()
+
+	c.telegrams++
+	if telegram.APCI == KNXGroupValueWrite || telegram.APCI == KNXGroupValueResponse {
+		c.values[telegram.Destination] = telegram
+	}
+}
+
+// value returns the last value of a group address if it was seen within
+// maxAge
+func (c *KNXConnection) value(address KNXGroupAddress, maxAge time.Duration) *KNXGroupTelegram {
+	c.mutex.RLock()
+	defer c.mutex.RUnlock()
+
+	telegram, exists := c.values[address]
+	if !exists || time.Since(telegram.Timestamp) > maxAge {
+		return nil
+	}
+	return telegram
+}
+
+func (c *KNXConnection) record(err error) {
+	c.mutex.Lock()
+	defer c.mutex.Unlock()
+
+	c.requests++
+	c.lastUsed = time.Now()
+	if err != nil {
+		c.errors++
+	}
+}
+
+// parseKNXTagAddress parses a tag address into a group address and an
+// optional datapoint type, e.g. "1/2/3:9.001"
+func parseKNXTagAddress(address string) (KNXGroupAddress, *KNXDatapointType, error) {
+	groupText, dptText, hasDPT := strings.Cut(address, ":")
+	groupAddress, err := ParseKNXGroupAddress(groupText)
+	if err != nil {
+		return 0, nil, err
+	}
+	if !hasDPT {
+		return groupAddress, nil, nil
+	}
+	dpt, err := ParseKNXDatapointType(dptText)
+	if err != nil {
+		return 0, nil, err
+	}
+	return groupAddress, &dpt, nil
+}
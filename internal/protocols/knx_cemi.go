@@ -0,0 +1,214 @@
+package protocols
+
+import (
+	"fmt"
+	"strconv"
+	"strings"
+	"time"
+)
+
+// KNX cEMI Frames
+//
+// KNXnet/IP tunnels carry KNX telegrams as cEMI frames: a message code, a
+// length prefixed additional information block and the L_Data frame, two
+// control fields, the source individual address, the destination group
+// or individual address and the TPDU. The TPDU's APCI selects a group
+// value read, response or write; values of up to 6 bits are packed into
+// the APCI's low bits, longer values follow it.
+
+// cEMI message codes
+const (
+	knxLDataReq = 0x11
+	knxLDataCon = 0x2E
+	knxLDataInd = 0x29
+)
+
+// KNX group value services
+const (
+	KNXGroupValueRead     = 0x0000
+	KNXGroupValueResponse = 0x0040
+	KNXGroupValueWrite    = 0x0080
+)
+
+// cEMI control fields
+const (
+	// Standard frame, no repeat, system broadcast, low priority
+	knxControl1Default = 0xBC
+	knxControl1Error   = 0x01 // set in L_Data.con when not confirmed
+
+	// Group destination, hop count 6
+	knxControl2Group = 0xE0
+	knxControl2Flag  = 0x80
+)
+
+// KNXGroupAddress is a group address, written in three levels as
+// "main/middle/sub"
+type KNXGroupAddress uint16
+
+// ParseKNXGroupAddress parses a three level ("1/2/3"), two level ("1/515")
+// or free ("2563") group address
+func ParseKNXGroupAddress(text string) (KNXGroupAddress, error) {
+	parts := strings.Split(text, "/")
+	limits := map[int][]uint64{
+		1: {0xFFFF},
+		2: {0x1F, 0x7FF},
+		3: {0x1F, 0x07, 0xFF},
+	}[len(parts)]
+	if limits == nil {
+		return 0, fmt.Errorf("invalid KNX group address %q", text)
+	}
+
+	var address uint64
+	for i, part := range parts {
+		value, err := strconv.ParseUint(part, 10, 16)
+		if err != nil || value > limits[i] {
+			return 0, fmt.Errorf("invalid KNX group address %q", text)
+		}
+		shift := 0
+		for limit := limits[i] + 1; limit > 1; limit >>= 1 {
+			shift++
+		}
+		address = address<<shift | value
+	}
+	return KNXGroupAddress(address), nil
+}
+
+func (a KNXGroupAddress) String() string {
+	return fmt.Sprintf("%d/%d/%d", a>>11, a>>8&0x07, a&0xFF)
+}
+
+// KNXIndividualAddress is the address of a device, written as
+// "area.line.device"
+type KNXIndividualAddress uint16
+
+// ParseKNXIndividualAddress parses an individual address such as "1.1.5"
+func ParseKNXIndividualAddress(text string) (KNXIndividualAddress, error) {
+	parts := strings.Split(text, ".")
+	if len(parts) != 3 {
+		return 0, fmt.Errorf("invalid KNX individual address %q", text)
+	}
+
+	var address uint16
+	for i, limit := range []uint64{0x0F, 0x0F, 0xFF} {
+		value, err := strconv.ParseUint(parts[i], 10, 8)
+		if err != nil || value > limit {
+			return 0, fmt.Errorf("invalid KNX individual address %q", text)
+		}
+		if i == 2 {
+			address = address<<8 | uint16(value)
+		} else {
+			address = address<<4 | uint16(value)
+		}
+	}
+	return KNXIndividualAddress(address), nil
+}
+
+func (a KNXIndividualAddress) String() string {
+	return fmt.Sprintf("%d.%d.%d", a>>12, a>>8&0x0F, a&0xFF)
+}
+
+// KNXCEMI is an L_Data cEMI frame. Small holds whether Data is a value of
+// up to 6 bits packed into the APCI, in which case Data has one byte.
+type KNXCEMI struct {
+	MessageCode byte
+	Control1    byte
+	Control2    byte
+	Source      KNXIndividualAddress
+	Destination uint16
+	APCI        uint16
+	Data        []byte
+	Small       bool
+}
+
+// NewKNXGroupCEMI creates an L_Data.req frame for a group service
+func NewKNXGroupCEMI(destination KNXGroupAddress, apci uint16, data []byte, small bool) *KNXCEMI {
+	return &KNXCEMI{
+		MessageCode: knxLDataReq,
+		Control1:    knxControl1Default,
+		Control2:    knxControl2Group,
+		Destination: uint16(destination),
+		APCI:        apci,
+		Data:        data,
+		Small:       small,
+	}
+}
+
+// GroupAddressed reports whether the destination is a group address
+func (c *KNXCEMI) GroupAddressed() bool {
+	return c.Control2&knxControl2Flag != 0
+}
+
+// AppendKNXCEMI appends a cEMI frame without additional information
+func AppendKNXCEMI(dst []byte, c *KNXCEMI) ([]byte, error) {
+	length := 1 + len(c.Data)
+	low := byte(c.APCI)
+	if c.Small || len(c.Data) == 0 {
+		if len(c.Data) > 1 || (len(c.Data) == 1 && c.Data[0] > 0x3F) {
+			return dst, fmt.Errorf("KNX value does not fit into 6 bits")
+		}
+		length = 1
+		if len(c.Data) == 1 {
+			low |= c.Data[0]
+		}
+	}
+	if length > 255 {
+		return dst, fmt.Errorf("KNX value of %d bytes exceeds maximum", len(c.Data))
+	}
+
+	dst = append(dst, c.MessageCode, 0x00, c.Control1, c.Control2,
+		byte(c.Source>>8), byte(c.Source), byte(c.Destination>>8), byte(c.Destination),
+		byte(length), byte(c.APCI>>8)&0x03, low)
+	if length > 1 {
+		dst = append(dst, c.Data...)
+	}
+	return dst, nil
+}
+
+// ParseKNXCEMI parses an L_Data cEMI frame. The frame's data aliases data.
+func ParseKNXCEMI(data []byte) (*KNXCEMI, error) {
+	if len(data) < 2 {
+		return nil, fmt.Errorf("KNX cEMI frame of %d bytes", len(data))
+	}
+	c := &KNXCEMI{MessageCode: data[0]}
+	switch c.MessageCode {
+	case knxLDataReq, knxLDataCon, knxLDataInd:
+	default:
+		return nil, fmt.Errorf("unsupported cEMI message code 0x%02X", c.MessageCode)
+	}
+
+	if len(data) < 2+int(data[1])+7 {
+		return nil, fmt.Errorf("truncated KNX cEMI frame")
+	}
+	frame := data[2+int(data[1]):]
+	c.Control1, c.Control2 = frame[0], frame[1]
+	c.Source = KNXIndividualAddress(uint16(frame[2])<<8 | uint16(frame[3]))
+	c.Destination = uint16(frame[4])<<8 | uint16(frame[5])
+
+	length := int(frame[6])
+	tpdu := frame[7:]
+	if length == 0 {
+		// Transport layer control frames carry no APCI
+		return c, nil
+	}
+	if len(tpdu) < length+1 {
+		return nil, fmt.Errorf("truncated KNX TPDU")
+	}
+	c.APCI = uint16(tpdu[0]&0x03)<<8 | uint16(tpdu[1]&0xC0)
+	if length == 1 {
+		c.Data, c.Small = []byte{tpdu[1] & 0x3F}, true
+	} else {
+		c.Data = tpdu[2 : length+1]
+	}
+	return c, nil
+}
+
+// KNXGroupTelegram is a group value read, response or write seen on the
+// bus
+type KNXGroupTelegram struct {
+	Source      KNXIndividualAddress
+	Destination KNXGroupAddress
+	APCI        uint16
+	Data        []byte
+	Small       bool
+	Timestamp   time.Time
+}
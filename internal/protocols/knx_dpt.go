@@ -0,0 +1,277 @@
+package protocols
+
+import (
+	"encoding/binary"
+	"fmt"
+	"math"
+	"strconv"
+	"strings"
+)
+
+// KNX Datapoint Types
+//
+// Group values carry no type information; the datapoint type (DPT) of a
+// group address, such as 9.001 for a temperature in °C, is part of the
+// installation's project. The main number selects the encoding, the sub
+// number the quantity and unit.
+
+// KNXDatapointType is a datapoint type such as 9.001
+type KNXDatapointType struct {
+	Main int
+	Sub  int
+}
+
+// knxDatapointSizes are the value sizes of the supported main types in
+// bytes; 0 marks values of up to 6 bits
+var knxDatapointSizes = map[int]int{
+	1:  0,  // boolean
+	3:  0,  // 3-bit controlled step
+	5:  1,  // 8-bit unsigned
+	6:  1,  // 8-bit signed
+	7:  2,  // 16-bit unsigned
+	8:  2,  // 16-bit signed
+	9:  2,  // 16-bit float
+	12: 4,  // 32-bit unsigned
+	13: 4,  // 32-bit signed
+	14: 4,  // IEEE 754 float
+	16: 14, // string
+	17: 1,  // scene number
+	20: 1,  // 8-bit enumeration
+}
+
+// knxDatapointUnits are the units of common datapoint types
+var knxDatapointUnits = map[KNXDatapointType]string{
+	{5, 1}:   "%",
+	{5, 3}:   "°",
+	{7, 12}:  "mA",
+	{7, 13}:  "lx",
+	{7, 600}: "K",
+	{9, 1}:   "°C",
+	{9, 2}:   "K",
+	{9, 4}:   "lx",
+	{9, 5}:   "m/s",
+	{9, 6}:   "Pa",
+	{9, 7}:   "%",
+	{9, 8}:   "ppm",
+	{9, 20}:  "mV",
+	{9, 21}:  "mA",
+	{9, 24}:  "kW",
+	{9, 25}:  "l/h",
+	{9, 27}:  "°F",
+	{9, 28}:  "km/h",
+	{13, 10}: "Wh",
+	{13, 13}: "kWh",
+	{14, 19}: "A",
+	{14, 27}: "V",
+	{14, 56}: "W",
+	{14, 68}: "°C",
+}
+
+// ParseKNXDatapointType parses a datapoint type such as "9.001",
+// "DPT9.001" or "DPST-9-1"
+func ParseKNXDatapointType(text string) (KNXDatapointType, error) {
+	normalized := strings.ToUpper(strings.TrimSpace(text))
+	separator := "."
+	switch {
+	case strings.HasPrefix(normalized, "DPST-"):
+		normalized, separator = normalized[5:], "-"
+	case strings.HasPrefix(normalized, "DPT-"):
+		normalized, separator = normalized[4:], "-"
+	case strings.HasPrefix(normalized, "DPT"):
+		normalized = normalized[3:]
+	}
+
+	mainText, subText, hasSub := strings.Cut(normalized, separator)
+	main, err := strconv.Atoi(mainText)
+	if err != nil {
+		return KNXDatapointType{}, fmt.Errorf("invalid KNX datapoint type %q", text)
+	}
+	dpt := KNXDatapointType{Main: main}
+	if hasSub {
+		if dpt.Sub, err = strconv.Atoi(subText); err != nil {
+			return KNXDatapointType{}, fmt.Errorf("invalid KNX datapoint type %q", text)
+		}
+	}
+	if _, supported := knxDatapointSizes[main]; !supported {
+		return KNXDatapointType{}, fmt.Errorf("unsupported KNX datapoint type %q", text)
+	}
+	return dpt, nil
+}
+
+func (d KNXDatapointType) String() string {
+	return fmt.Sprintf("%d.%03d", d.Main, d.Sub)
+}
+
+// Unit returns the unit of the datapoint type's values, if it has one
+func (d KNXDatapointType) Unit() string {
+	return knxDatapointUnits[d]
+}
+
+// DataType returns the tag data type of decoded values
+func (d KNXDatapointType) DataType() DataType {
+	switch {
+	case d.Main == 1:
+		return DataTypeBool
+	case d.Main == 9 || d.Main == 14 || d.Main == 5 && (d.Sub == 1 || d.Sub == 3):
+		return DataTypeFloat64
+	case d.Main == 16:
+		return DataTypeString
+	}
+	return DataTypeInt64
+}
+
+// Decode decodes a group value; small tells whether data is a value of up
+// to 6 bits packed into the APCI
+func (d KNXDatapointType) Decode(data []byte, small bool) (interface{}, error) {
+	size := knxDatapointSizes[d.Main]
+	if small != (size == 0) || (size == 0 && len(data) != 1) || (size > 0 && len(data) != size) {
+		return nil, fmt.Errorf("KNX value % X does not match DPT %s", data, d)
+	}
+
+	switch d.Main {
+	case 1:
+		return data[0]&0x01 != 0, nil
+	case 3:
+		return int64(data[0] & 0x0F), nil
+	case 5:
+		switch d.Sub {
+		case 1:
+			return float64(data[0]) * 100 / 255, nil
+		case 3:
+			return float64(data[0]) * 360 / 255, nil
+		}
+		return int64(data[0]), nil
+	case 6:
+		return int64(int8(data[0])), nil
+	case 7:
+		return int64(binary.BigEndian.Uint16(data)), nil
+	case 8:
+		return int64(int16(binary.BigEndian.Uint16(data))), nil
+	case 9:
+		return knxFloat16(binary.BigEndian.Uint16(data)), nil
+	case 12:
+		return int64(binary.BigEndian.Uint32(data)), nil
+	case 13:
+		return int64(int32(binary.BigEndian.Uint32(data))), nil
+	case 14:
+		return float64(math.Float32frombits(binary.BigEndian.Uint32(data))), nil
+	case 16:
+		return strings.TrimRight(string(data), "\x00"), nil
+	case 17:
+		return int64(data[0] & 0x3F), nil
+	}
+	return int64(data[0]), nil
+}
+
+// Encode encodes a group value and reports whether it is packed into the
+// APCI
+func (d KNXDatapointType) Encode(value interface{}) ([]byte, bool, error) {
+	switch d.Main {
+	case 1:
+		b, ok := value.(bool)
+		if !ok {
+			number, err := floatValue(value)
+			if err != nil {
+				return nil, false, err
+			}
+			b = number != 0
+		}
+		if b {
+			return []byte{1}, true, nil
+		}
+		return []byte{0}, true, nil
+	case 16:
+		text := fmt.Sprint(value)
+		if len(text) > 14 {
+			return nil, false, fmt.Errorf("KNX string %q exceeds 14 characters", text)
+		}
+		data := make([]byte, 14)
+		copy(data, text)
+		return data, false, nil
+	case 9, 14:
+		number, err := floatValue(value)
+		if err != nil {
+			return nil, false, err
+		}
+		if d.Main == 14 {
+			return binary.BigEndian.AppendUint32(nil, math.Float32bits(float32(number))), false, nil
+		}
+		raw, err := knxEncodeFloat16(number)
+		if err != nil {
+			return nil, false, err
+		}
+		return binary.BigEndian.AppendUint16(nil, raw), false, nil
+	case 5:
+		if d.Sub == 1 || d.Sub == 3 {
+			number, err := floatValue(value)
+			if err != nil {
+				return nil, false, err
+			}
+			full := 100.0
+			if d.Sub == 3 {
+				full = 360
+			}
+			if number < 0 || number > full {
+				return nil, false, fmt.Errorf("KNX value %v out of range for DPT %s", value, d)
+			}
+			return []byte{byte(math.Round(number * 255 / full))}, false, nil
+		}
+	}
+
+	limits := map[int][2]int64{
+		3:  {0, 0x0F},
+		5:  {0, math.MaxUint8},
+		6:  {math.MinInt8, math.MaxInt8},
+		7:  {0, math.MaxUint16},
+		8:  {math.MinInt16, math.MaxInt16},
+		12: {0, math.MaxUint32},
+		13: {math.MinInt32, math.MaxInt32},
+		17: {0, 0x3F},
+		20: {0, math.MaxUint8},
+	}[d.Main]
+	number, err := integerValue(value, limits[0], limits[1])
+	if err != nil {
+		return nil, false, err
+	}
+	switch knxDatapointSizes[d.Main] {
+	case 0:
+		return []byte{byte(number)}, true, nil
+	case 1:
+		return []byte{byte(number)}, false, nil
+	case 2:
+		return binary.BigEndian.AppendUint16(nil, uint16(number)), false, nil
+	}
+	return binary.BigEndian.AppendUint32(nil, uint32(number)), false, nil
+}
+
+// knxFloat16 decodes a KNX 16-bit float, 0.01 × M × 2^E with a 12-bit two's
+// complement mantissa whose sign is the top bit
+func knxFloat16(raw uint16) float64 {
+	mantissa := int(raw & 0x07FF)
+	if raw&0x8000 != 0 {
+		mantissa -= 0x0800
+	}
+	exponent := int(raw >> 11 & 0x0F)
+	return float64(mantissa<<exponent) / 100
+}
+
+// knxEncodeFloat16 encodes a KNX 16-bit float with the smallest exponent
+// that fits the mantissa
+func knxEncodeFloat16(value float64) (uint16, error) {
+	mantissa := math.Round(value * 100)
+	exponent := 0
+	for mantissa < -2048 || mantissa > 2047 {
+		mantissa = math.Round(mantissa / 2)
+		exponent++
+	}
+	if exponent > 15 {
+		return 0, fmt.Errorf("KNX value %v out of range for a 16-bit float", value)
+	}
+
+	m := int(mantissa)
+	raw := uint16(m)&0x07FF | uint16(exponent)<<11
+	if m < 0 {
+		raw |= 0x8000
+	}
+	return raw, nil
+}
@@ -0,0 +1,468 @@
+package protocols
+
+import (
+	"context"
+	"net"
+	"sync"
+	"testing"
+	"time"
+
+	"github.com/stretchr/testify/assert"
+	"go.uber.org/zap"
+)
+
+func TestParseKNXGroupAddress(t *testing.T) {
+	address, err := ParseKNXGroupAddress("1/2/3")
+	assert.NoError(t, err)
+	assert.Equal(t, KNXGroupAddress(0x0A03), address)
+	assert.Equal(t, "1/2/3", address.String())
+
+	address, err = ParseKNXGroupAddress("1/515")
+	assert.NoError(t, err)
+	assert.Equal(t, KNXGroupAddress(0x0A03), address)
+
+	address, err = ParseKNXGroupAddress("2563")
+	assert.NoError(t, err)
+	assert.Equal(t, KNXGroupAddress(0x0A03), address)
+
+	for _, invalid := range []string{"", "32/0/0", "1/8/0", "1/2/256", "1/2/3/4", "a/b/c"} {
+		_, err = ParseKNXGroupAddress(invalid)
+		assert.Error(t, err, invalid)
+	}
+
+	individual, err := ParseKNXIndividualAddress("1.1.5")
+	assert.NoError(t, err)
+	assert.Equal(t, KNXIndividualAddress(0x1105), individual)
+	assert.Equal(t, "1.1.5", individual.String())
+	_, err = ParseKNXIndividualAddress("16.1.5")
+	assert.Error(t, err)
+}
+
+func TestKNXCEMI_RoundTrip(t *testing.T) {
+	// Switching 1/2/3 on packs the value into the APCI
+	frame, err := AppendKNXCEMI(nil, NewKNXGroupCEMI(0x0A03, KNXGroupValueWrite, []byte{1}, true))
+	assert.NoError(t, err)
+	assert.Equal(t, []byte{0x11, 0x00, 0xBC, 0xE0, 0x00, 0x00, 0x0A, 0x03, 0x01, 0x00, 0x81}, frame)
+
+	parsed, err := ParseKNXCEMI(frame)
+	assert.NoError(t, err)
+	assert.True(t, parsed.GroupAddressed())
+	assert.Equal(t, uint16(KNXGroupValueWrite), parsed.APCI)
+	assert.Equal(t, []byte{1}, parsed.Data)
+	assert.True(t, parsed.Small)
+
+	// A temperature response from 1.1.5 with additional information
+	parsed, err = ParseKNXCEMI([]byte{0x29, 0x02, 0xAA, 0xBB, 0xBC, 0xE0, 0x11, 0x05, 0x0A, 0x03, 0x03, 0x00, 0x40, 0x0C, 0x1A})
+	assert.NoError(t, err)
+	assert.Equal(t, byte(knxLDataInd), parsed.MessageCode)
+	assert.Equal(t, "1.1.5", parsed.Source.String())
+	assert.Equal(t, uint16(KNXGroupValueResponse), parsed.APCI)
+	assert.Equal(t, []byte{0x0C, 0x1A}, parsed.Data)
+	assert.False(t, parsed.Small)
+
+	_, err = AppendKNXCEMI(nil, NewKNXGroupCEMI(0x0A03, KNXGroupValueWrite, []byte{0x40}, true))
+	assert.Error(t, err)
+	_, err = ParseKNXCEMI([]byte{0x29, 0x00, 0xBC, 0xE0, 0x11, 0x05, 0x0A, 0x03, 0x03, 0x00})
+	assert.Error(t, err)
+	_, err = ParseKNXCEMI([]byte{0xFC, 0x00})
+	assert.Error(t, err)
+}
+
+func TestKNXDatapointType(t *testing.T) {
+	for _, text := range []string{"9.001", "DPT9.001", "DPST-9-1", "dpt-9-1"} {
+		dpt, err := ParseKNXDatapointType(text)
+		assert.NoError(t, err, text)
+		assert.Equal(t, KNXDatapointType{Main: 9, Sub: 1}, dpt)
+	}
+	dpt, _ := ParseKNXDatapointType("9.001")
+	assert.Equal(t, "9.001", dpt.String())
+	assert.Equal(t, "°C", dpt.Unit())
+	assert.Equal(t, DataTypeFloat64, dpt.DataType())
+	_, err := ParseKNXDatapointType("10.001")
+	assert.Error(t, err)
+
+	tests := []struct {
+		dpt   string
+		value interface{}
+		data  []byte
+		small bool
+	}{
+		{"1.001", true, []byte{1}, true},
+		{"5.001", 100.0, []byte{0xFF}, false},
+		{"5.010", int64(42), []byte{42}, false},
+		{"6.010", int64(-1), []byte{0xFF}, false},
+		{"7.001", int64(65535), []byte{0xFF, 0xFF}, false},
+		{"8.001", int64(-2), []byte{0xFF, 0xFE}, false},
+		{"9.001", 21.0, []byte{0x0C, 0x1A}, false},
+		{"9.001", -30.0, []byte{0x8A, 0x24}, false},
+		{"9.001", 0.0, []byte{0x00, 0x00}, false},
+		{"12.001", int64(4294967295), []byte{0xFF, 0xFF, 0xFF, 0xFF}, false},
+		{"13.013", int64(-1000), []byte{0xFF, 0xFF, 0xFC, 0x18}, false},
+		{"14.056", 1.5, []byte{0x3F, 0xC0, 0x00, 0x00}, false},
+		{"16.000", "KNX", []byte("KNX\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00"), false},
+		{"17.001", int64(63), []byte{63}, false},
+	}
+	for _, test := range tests {
+		dpt, err := ParseKNXDatapointType(test.dpt)
+		assert.NoError(t, err)
+
+		data, small, err := dpt.Encode(test.value)
+		assert.NoError(t, err, test.dpt)
+		assert.Equal(t, test.data, data, test.dpt)
+		assert.Equal(t, test.small, small, test.dpt)
+
+		value, err := dpt.Decode(data, small)
+		assert.NoError(t, err, test.dpt)
+		assert.Equal(t, test.value, value, test.dpt)
+	}
+
+	// Large values lose precision to the exponent
+	assert.Equal(t, 670760.96, knxFloat16(0x7FFF))
+	raw, err := knxEncodeFloat16(670760.96)
+	assert.NoError(t, err)
+	assert.Equal(t, uint16(0x7FFF), raw)
+	_, err = knxEncodeFloat16(1e6)
+	assert.Error(t, err)
+
+	dpt, _ = ParseKNXDatapointType("5.010")
+	_, _, err = dpt.Encode(256)
+	assert.Error(t, err)
+	_, err = dpt.Decode([]byte{1, 2}, false)
+	assert.Error(t, err)
+	dpt, _ = ParseKNXDatapointType("1.001")
+	_, err = dpt.Decode([]byte{1}, false)
+	assert.Error(t, err)
+}
+
+// knxTestGateway is a KNXnet/IP gateway answering group reads from its
+// values and recording group writes
+type knxTestGateway struct {
+	t    *testing.T
+	conn *net.UDPConn
+
+	mutex   sync.Mutex
+	client  *net.UDPAddr
+	seq     byte
+	values  map[KNXGroupAddress][]byte
+	writes  []*KNXCEMI
+	silence bool // drop tunneling requests without acknowledging them
+}
+
+const knxTestChannel = 7
+
+func newKNXTestGateway(t *testing.T) *knxTestGateway {
+	conn, err := net.ListenUDP("udp4", &net.UDPAddr{IP: net.IPv4(127, 0, 0, 1)})
+	if err != nil {
+		t.Fatalf("failed to listen: %v", err)
+	}
+	gateway := &knxTestGateway{
+		t:    t,
+		conn: conn,
+		values: map[KNXGroupAddress][]byte{
+			0x0A03: {0x0C, 0x1A},
+		},
+	}
+	go gateway.serve()
+	t.Cleanup(func() { conn.Close() })
+	return gateway
+}
+
+func (g *knxTestGateway) address() *net.UDPAddr {
+	return g.conn.LocalAddr().(*net.UDPAddr)
+}
+
+func (g *knxTestGateway) serve() {
+	buffer := make([]byte, knxMaxFrameSize)
+	for {
+		n, from, err := g.conn.ReadFromUDP(buffer)
+		if err != nil {
+			return
+		}
+		service, body, err := parseKNXFrame(buffer[:n])
+		if err != nil {
+			continue
+		}
+
+		switch service {
+		case knxSearchRequest:
+			endpoint, err := parseKNXEndpoint(body)
+			if err != nil {
+				continue
+			}
+			response := appendKNXEndpoint(nil, g.address())
+			dib := make([]byte, 54)
+			dib[0], dib[1], dib[2] = 54, 0x01, 0x02
+			dib[4], dib[5] = 0x11, 0x00
+			copy(dib[8:14], []byte{0x00, 0xC5, 0x01, 0x02, 0x03, 0x04})
+			copy(dib[24:], "Test IP Interface")
+			response = append(response, dib...)
+			_, _ = g.conn.WriteToUDP(appendKNXFrame(nil, knxSearchResponse, response), endpoint)
+		case knxConnectRequest:
+			g.mutex.Lock()
+			g.client, g.seq = from, 0
+			g.mutex.Unlock()
+			response := []byte{knxTestChannel, 0x00}
+			response = appendKNXEndpoint(response, g.address())
+			response = append(response, 0x04, knxTunnelConnection, 0x11, 0xFF)
+			_, _ = g.conn.WriteToUDP(appendKNXFrame(nil, knxConnectResponse, response), from)
+		case knxConnectionStateRequest:
+			_, _ = g.conn.WriteToUDP(appendKNXFrame(nil, knxConnectionStateResponse, []byte{body[0], 0x00}), from)
+		case knxDisconnectRequest:
+			_, _ = g.conn.WriteToUDP(appendKNXFrame(nil, knxDisconnectResponse, []byte{body[0], 0x00}), from)
+		case knxTunnelingRequest:
+			g.tunnelingRequest(body, from)
+		}
+	}
+}
+
+func (g *knxTestGateway) tunnelingRequest(body []byte, from *net.UDPAddr) {
+	g.mutex.Lock()
+	silence := g.silence
+	g.mutex.Unlock()
+	if silence {
+		return
+	}
+
+	_, _ = g.conn.WriteToUDP(appendKNXFrame(nil, knxTunnelingAck, []byte{0x04, body[1], body[2], 0x00}), from)
+	request, err := ParseKNXCEMI(body[4:])
+	if err != nil {
+		return
+	}
+
+	g.mutex.Lock()
+	value, exists := g.values[KNXGroupAddress(request.Destination)]
+	if request.APCI == KNXGroupValueWrite {
+		request.Data = append([]byte(nil), request.Data...)
+		g.writes = append(g.writes, request)
+	}
+	g.mutex.Unlock()
+
+	confirmation := *request
+	confirmation.MessageCode = knxLDataCon
+	confirmation.Source = 0x11FF
+	g.indicate(&confirmation)
+
+	if request.APCI == KNXGroupValueRead && exists {
+		g.indicate(&KNXCEMI{
+			MessageCode: knxLDataInd,
+			Control1:    knxControl1Default,
+			Control2:    knxControl2Group,
+			Source:      0x1105,
+			Destination: request.Destination,
+			APCI:        KNXGroupValueResponse,
+			Data:        value,
+		})
+	}
+}
+
+// indicate tunnels a frame to the client
+func (g *knxTestGateway) indicate(c *KNXCEMI) {
+	g.mutex.Lock()
+	defer g.mutex.Unlock()
+
+	frame, err := AppendKNXCEMI([]byte{0x04, knxTestChannel, g.seq, 0x00}, c)
+	assert.NoError(g.t, err)
+	g.seq++
+	_, _ = g.conn.WriteToUDP(appendKNXFrame(nil, knxTunnelingRequest, frame), g.client)
+}
+
+func TestKNXTunnel(t *testing.T) {
+	gateway := newKNXTestGateway(t)
+	conn, err := net.DialUDP("udp4", nil, gateway.address())
+	assert.NoError(t, err)
+
+	telegrams := make(chan *KNXGroupTelegram, 10)
+	tunnel, err := NewKNXTunnel(conn, time.Second, func(telegram *KNXGroupTelegram) {
+		telegrams <- telegram
+	})
+	assert.NoError(t, err)
+	assert.Equal(t, byte(knxTestChannel), tunnel.Channel())
+	assert.Equal(t, "1.1.255", tunnel.Address().String())
+
+	telegram, err := tunnel.GroupRead(0x0A03)
+	assert.NoError(t, err)
+	assert.Equal(t, uint16(KNXGroupValueResponse), telegram.APCI)
+	assert.Equal(t, []byte{0x0C, 0x1A}, telegram.Data)
+	assert.Equal(t, "1.1.5", telegram.Source.String())
+	assert.Equal(t, telegram.Data, (<-telegrams).Data)
+
+	_, err = tunnel.GroupRead(0x0A04)
+	assert.Error(t, err)
+
+	assert.NoError(t, tunnel.GroupWrite(0x0A05, []byte{1}, true))
+	gateway.mutex.Lock()
+	assert.Len(t, gateway.writes, 1)
+	assert.Equal(t, []byte{1}, gateway.writes[0].Data)
+	gateway.mutex.Unlock()
+
+	// Telegrams from other devices on the bus
+	gateway.indicate(&KNXCEMI{
+		MessageCode: knxLDataInd,
+		Control1:    knxControl1Default,
+		Control2:    knxControl2Group,
+		Source:      0x1106,
+		Destination: 0x0A06,
+		APCI:        KNXGroupValueWrite,
+		Data:        []byte{0x42},
+	})
+	select {
+	case telegram = <-telegrams:
+		assert.Equal(t, KNXGroupAddress(0x0A06), telegram.Destination)
+		assert.Equal(t, []byte{0x42}, telegram.Data)
+	case <-time.After(time.Second):
+		t.Fatal("no telegram")
+	}
+
+	assert.NoError(t, tunnel.Heartbeat())
+
+	gateway.mutex.Lock()
+	gateway.silence = true
+	gateway.mutex.Unlock()
+	err = tunnel.GroupWrite(0x0A05, []byte{0}, true)
+	assert.ErrorContains(t, err, "not acknowledged")
+
+	assert.NoError(t, tunnel.Close())
+	assert.Error(t, tunnel.Err())
+	_, err = tunnel.GroupRead(0x0A03)
+	assert.Error(t, err)
+}
+
+func TestKNXTunnel_ConnectRefused(t *testing.T) {
+	conn, err := net.ListenUDP("udp4", &net.UDPAddr{IP: net.IPv4(127, 0, 0, 1)})
+	assert.NoError(t, err)
+	defer conn.Close()
+	go func() {
+		buffer := make([]byte, knxMaxFrameSize)
+		n, from, err := conn.ReadFromUDP(buffer)
+		if err != nil || n == 0 {
+			return
+		}
+		_, _ = conn.WriteToUDP(appendKNXFrame(nil, knxConnectResponse, []byte{0x00, 0x24}), from)
+	}()
+
+	client, err := net.DialUDP("udp4", nil, conn.LocalAddr().(*net.UDPAddr))
+	assert.NoError(t, err)
+	defer client.Close()
+
+	_, err = NewKNXTunnel(client, time.Second, nil)
+	assert.ErrorContains(t, err, "no more connections")
+}
+
+func TestKNXHandler(t *testing.T) {
+	gateway := newKNXTestGateway(t)
+	handler := NewKNXHandler(zap.NewNop()).(*KNXHandler)
+	handler.searchAddress = gateway.address().String()
+	handler.config.Timeout = 500 * time.Millisecond
+	handler.config.SearchTimeout = 200 * time.Millisecond
+
+	device := &Device{
+		ID:      "knx-test",
+		Address: "127.0.0.1",
+		Port:    gateway.address().Port,
+		Config: map[string]interface{}{
+			"datapoints": map[string]interface{}{
+				"1/2/3": "9.001",
+				"1/2/6": "DPT5.010",
+			},
+		},
+	}
+	assert.NoError(t, handler.Connect(device))
+	defer handler.Disconnect(device)
+	assert.True(t, handler.IsConnected(device))
+
+	value, err := handler.ReadTag(device, &Tag{Address: "1/2/3"})
+	assert.NoError(t, err)
+	assert.Equal(t, 21.0, value)
+
+	// Cached values are not read from the bus again
+	gateway.mutex.Lock()
+	gateway.values[0x0A03] = []byte{0x0C, 0x4C}
+	gateway.mutex.Unlock()
+	value, err = handler.ReadTag(device, &Tag{Address: "1/2/3"})
+	assert.NoError(t, err)
+	assert.Equal(t, 21.0, value)
+	handler.config.CacheMaxAge = 0
+	value, err = handler.ReadTag(device, &Tag{Address: "1/2/3:9.001"})
+	assert.NoError(t, err)
+	assert.Equal(t, 22.0, value)
+
+	_, err = handler.ReadTag(device, &Tag{Address: "1/2/4"})
+	assert.ErrorContains(t, err, "no datapoint type")
+
+	assert.NoError(t, handler.WriteTag(device, &Tag{Address: "1/2/5:1.001"}, true))
+	assert.Error(t, handler.WriteTag(device, &Tag{Address: "1/2/5:5.010"}, 300))
+
+	results, err := handler.ReadMultipleTags(device, []*Tag{
+		{ID: "temperature", Address: "1/2/3"},
+		{ID: "missing", Address: "1/2/4:1.001"},
+	})
+	assert.NoError(t, err)
+	assert.Equal(t, map[string]interface{}{"temperature": 22.0}, results)
+
+	gateway.indicate(&KNXCEMI{
+		MessageCode: knxLDataInd,
+		Control1:    knxControl1Default,
+		Control2:    knxControl2Group,
+		Source:      0x1106,
+		Destination: 0x0A06,
+		APCI:        KNXGroupValueWrite,
+		Data:        []byte{0x42},
+	})
+	var tags []*Tag
+	assert.Eventually(t, func() bool {
+		tags, err = handler.ReadGroupValues(device)
+		return err == nil && len(tags) == 3
+	}, time.Second, 10*time.Millisecond)
+	assert.Equal(t, "1/2/3", tags[0].Address)
+	assert.Equal(t, 22.0, tags[0].Value)
+	assert.Equal(t, "°C", tags[0].Unit)
+	assert.Equal(t, "1/2/5", tags[1].Address)
+	assert.Equal(t, []byte{1}, tags[1].Value)
+	assert.Equal(t, "1/2/6", tags[2].Address)
+	assert.Equal(t, int64(0x42), tags[2].Value)
+
+	assert.NoError(t, handler.Ping(device))
+	diagnostics, err := handler.GetDiagnostics(device)
+	assert.NoError(t, err)
+	assert.True(t, diagnostics.IsHealthy)
+	assert.Equal(t, "1.1.255", diagnostics.ProtocolDiagnostics.(map[string]interface{})["individual_address"])
+
+	info, err := handler.GetDeviceInfo(device)
+	assert.NoError(t, err)
+	assert.Equal(t, "1.1.255", info.CustomInfo["individual_address"])
+
+	devices, err := handler.DiscoverDevices(context.Background(), "127.0.0.0/8")
+	assert.NoError(t, err)
+	if assert.Len(t, devices, 1) {
+		assert.Equal(t, "Test IP Interface", devices[0].Name)
+		assert.Equal(t, "127.0.0.1", devices[0].Address)
+		assert.Equal(t, gateway.address().Port, devices[0].Port)
+		assert.Equal(t, "1.1.0", devices[0].Config["individual_address"])
+		assert.Equal(t, "00C501020304", devices[0].Config["serial_number"])
+	}
+
+	assert.NoError(t, handler.ValidateTagAddress("1/2/3:9.001"))
+	assert.Error(t, handler.ValidateTagAddress("1/2/3:99.001"))
+	assert.Error(t, handler.ValidateTagAddress("1.2.3"))
+
+	assert.NoError(t, handler.Disconnect(device))
+	assert.False(t, handler.IsConnected(device))
+}
+
+func TestKNXHandler_Shrink(t *testing.T) {
+	handler := NewKNXHandler(zap.NewNop()).(*KNXHandler)
+	now := time.Now()
+	conn := &KNXConnection{values: map[KNXGroupAddress]*KNXGroupTelegram{
+		0x0A03: {Destination: 0x0A03, Data: []byte{0x0C, 0x1A}, Timestamp: now.Add(-time.Minute)},
+		0x0A05: {Destination: 0x0A05, Data: []byte{0x01}, Timestamp: now},
+		0x0A06: {Destination: 0x0A06, Data: []byte{0x42}, Timestamp: now.Add(-time.Second)},
+	}}
+	handler.connections.Store("127.0.0.1:3671", conn)
+	assert.Equal(t, int64(3*knxValueSize+4), handler.MemoryUsage())
+
+	// The oldest values are evicted first
+	assert.Equal(t, int64(2*knxValueSize+3), handler.Shrink(knxValueSize+3))
+	assert.Len(t, conn.values, 1)
+	assert.Contains(t, conn.values, KNXGroupAddress(0x0A05))
+	assert.Equal(t, int64(knxValueSize+1), handler.MemoryUsage())
+}
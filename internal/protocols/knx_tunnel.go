@@ -0,0 +1,485 @@
+package protocols
+
+import (
+	"encoding/binary"
+	"errors"
+	"fmt"
+	"net"
+	"sync"
+	"time"
+)
+
+// KNXnet/IP Tunneling
+//
+// KNXnet/IP frames start with a six byte header: header length, protocol
+// version 0x10, service type and total length. A tunnel is opened with a
+// CONNECT_REQUEST to the gateway's control endpoint, which assigns a
+// channel and an individual address. cEMI frames are then exchanged in
+// TUNNELING_REQUESTs, each acknowledged with a TUNNELING_ACK carrying its
+// channel and sequence counter, and the connection is kept alive with a
+// CONNECTIONSTATE_REQUEST at least every 120 seconds. Endpoints are sent
+// as 0.0.0.0:0 so the gateway answers to the address requests come from,
+// which also works through NAT.
+
+// KNXnetIPPort is the default port of KNXnet/IP gateways
+const KNXnetIPPort = 3671
+
+// knxMulticastAddress is the address gateways answer searches on
+const knxMulticastAddress = "224.0.23.12:3671"
+
+// KNXnet/IP service types
+const (
+	knxSearchRequest           = 0x0201
+	knxSearchResponse          = 0x0202
+	knxConnectRequest          = 0x0205
+	knxConnectResponse         = 0x0206
+	knxConnectionStateRequest  = 0x0207
+	knxConnectionStateResponse = 0x0208
+	knxDisconnectRequest       = 0x0209
+	knxDisconnectResponse      = 0x020A
+	knxTunnelingRequest        = 0x0420
+	knxTunnelingAck            = 0x0421
+)
+
+const (
+	knxHeaderSize       = 6
+	knxProtocolVersion  = 0x10
+	knxTunnelConnection = 0x04
+	knxTunnelLinkLayer  = 0x02
+	knxMaxFrameSize     = 512
+)
+
+// knxStatusNames are the names of KNXnet/IP error codes
+var knxStatusNames = map[byte]string{
+	0x01: "host protocol type",
+	0x02: "version not supported",
+	0x04: "sequence number",
+	0x21: "connection ID",
+	0x22: "connection type",
+	0x23: "connection option",
+	0x24: "no more connections",
+	0x26: "data connection",
+	0x27: "KNX connection",
+	0x29: "tunnelling layer",
+}
+
+// KNXStatusError is an error status returned by a gateway
+type KNXStatusError struct {
+	Service uint16
+	Status  byte
+}
+
+func (e *KNXStatusError) Error() string {
+	if name, exists := knxStatusNames[e.Status]; exists {
+		return fmt.Sprintf("KNXnet/IP service 0x%04X failed: %s error", e.Service, name)
+	}
+	return fmt.Sprintf("KNXnet/IP service 0x%04X failed with status 0x%02X", e.Service, e.Status)
+}
+
+// appendKNXFrame appends a KNXnet/IP frame with its header
+func appendKNXFrame(dst []byte, service uint16, body []byte) []byte {
+	dst = append(dst, knxHeaderSize, knxProtocolVersion)
+	dst = binary.BigEndian.AppendUint16(dst, service)
+	dst = binary.BigEndian.AppendUint16(dst, uint16(knxHeaderSize+len(body)))
+	return append(dst, body...)
+}
+
+// parseKNXFrame parses a KNXnet/IP frame into its service type and body
+func parseKNXFrame(data []byte) (uint16, []byte, error) {
+	if len(data) < knxHeaderSize || data[0] != knxHeaderSize || data[1] != knxProtocolVersion {
+		return 0, nil, fmt.Errorf("invalid KNXnet/IP header")
+	}
+	length := int(binary.BigEndian.Uint16(data[4:6]))
+	if length < knxHeaderSize || length > len(data) {
+		return 0, nil, fmt.Errorf("invalid KNXnet/IP frame length %d", length)
+	}
+	return binary.BigEndian.Uint16(data[2:4]), data[knxHeaderSize:length], nil
+}
+
+// appendKNXEndpoint appends a UDP host protocol address information
+// block; nil appends the route back endpoint 0.0.0.0:0
+func appendKNXEndpoint(dst []byte, address *net.UDPAddr) []byte {
+	dst = append(dst, 0x08, 0x01)
+	if address == nil || address.IP.To4() == nil {
+		return append(dst, 0, 0, 0, 0, 0, 0)
+	}
+	dst = append(dst, address.IP.To4()...)
+	return binary.BigEndian.AppendUint16(dst, uint16(address.Port))
+}
+
+// parseKNXEndpoint parses a host protocol address information block
+func parseKNXEndpoint(data []byte) (*net.UDPAddr, error) {
+	if len(data) < 8 || data[0] != 0x08 {
+		return nil, fmt.Errorf("invalid KNXnet/IP endpoint")
+	}
+	return &net.UDPAddr{
+		IP:   net.IPv4(data[2], data[3], data[4], data[5]),
+		Port: int(binary.BigEndian.Uint16(data[6:8])),
+	}, nil
+}
+
+// knxMessage is a frame received on a tunnel; cemi is set for tunnelled
+// frames
+type knxMessage struct {
+	service uint16
+	body    []byte
+	cemi    *KNXCEMI
+}
+
+// knxWaiter receives the first message it matches
+type knxWaiter struct {
+	match  func(*knxMessage) bool
+	result chan *knxMessage
+}
+
+// KNXTunnel is a tunneling connection to a KNXnet/IP gateway. Group
+// telegrams seen on the bus are passed to the tunnel's handler.
+type KNXTunnel struct {
+	Timeout time.Duration
+
+	conn      net.Conn
+	onGroup   func(*KNXGroupTelegram)
+	channel   byte
+	address   KNXIndividualAddress
+	heartbeat time.Duration
+
+	mutex   sync.Mutex // serializes requests and guards sendSeq
+	sendSeq byte
+
+	waitersMutex sync.Mutex
+	waiters      []*knxWaiter
+	err          error
+	done         chan struct{}
+}
+
+// NewKNXTunnel opens a tunnel on a UDP connection to a gateway's control
+// endpoint. onGroup, if not nil, is called for every group telegram and
+// must not block.
+func NewKNXTunnel(conn net.Conn, timeout time.Duration, onGroup func(*KNXGroupTelegram)) (*KNXTunnel, error) {
+	body := appendKNXEndpoint(nil, nil)
+	body = appendKNXEndpoint(body, nil)
+	body = append(body, 0x04, knxTunnelConnection, knxTunnelLinkLayer, 0x00)
+	if _, err := conn.Write(appendKNXFrame(nil, knxConnectRequest, body)); err != nil {
+		return nil, fmt.Errorf("failed to send KNXnet/IP connect request: %w", err)
+	}
+
+	buffer := make([]byte, knxMaxFrameSize)
+	deadline := time.Now().Add(timeout)
+	for {
+		_ = conn.SetReadDeadline(deadline)
+		n, err := conn.Read(buffer)
+		if err != nil {
+			return nil, fmt.Errorf("no KNXnet/IP connect response: %w", err)
+		}
+		service, response, err := parseKNXFrame(buffer[:n])
+		if err != nil || service != knxConnectResponse {
+			continue
+		}
+		if len(response) < 2 {
+			return nil, fmt.Errorf("invalid KNXnet/IP connect response")
+		}
+		if response[1] != 0 {
+			return nil, &KNXStatusError{Service: knxConnectRequest, Status: response[1]}
+		}
+		// Channel, status, data endpoint and connection response data
+		if len(response) < 14 || response[10] != 0x04 || response[11] != knxTunnelConnection {
+			return nil, fmt.Errorf("invalid KNXnet/IP connect response")
+		}
+
+		t := &KNXTunnel{
+			Timeout:   timeout,
+			conn:      conn,
+			onGroup:   onGroup,
+			channel:   response[0],
+			address:   KNXIndividualAddress(binary.BigEndian.Uint16(response[12:14])),
+			heartbeat: 60 * time.Second,
+			done:      make(chan struct{}),
+		}
+		_ = conn.SetReadDeadline(time.Time{})
+		go t.receive()
+		go t.keepAlive()
+		return t, nil
+	}
+}
+
+// Channel returns the channel the gateway assigned to the tunnel
+func (t *KNXTunnel) Channel() byte {
+	return t.channel
+}
+
+// Address returns the individual address the gateway assigned to the
+// tunnel
+func (t *KNXTunnel) Address() KNXIndividualAddress {
+	return t.address
+}
+
+// Err returns the error that closed the tunnel, or nil while it is open
+func (t *KNXTunnel) Err() error {
+	t.waitersMutex.Lock()
+	defer t.waitersMutex.Unlock()
+	return t.err
+}
+
+// GroupRead sends a group value read and waits for the response
+func (t *KNXTunnel) GroupRead(address KNXGroupAddress) (*KNXGroupTelegram, error) {
+	waiter := t.wait(func(m *knxMessage) bool {
+		return m.cemi != nil && m.cemi.MessageCode == knxLDataInd && m.cemi.GroupAddressed() &&
+			m.cemi.Destination == uint16(address) && m.cemi.APCI == KNXGroupValueResponse
+	})
+	defer t.cancel(waiter)
+
+	if err := t.Send(NewKNXGroupCEMI(address, KNXGroupValueRead, nil, false)); err != nil {
+		return nil, err
+	}
+
+	select {
+	case m := <-waiter.result:
+		return knxGroupTelegram(m.cemi), nil
+	case <-time.After(t.Timeout):
+		return nil, fmt.Errorf("no response to KNX group read of %s", address)
+	case <-t.done:
+		return nil, t.Err()
+	}
+}
+
+// GroupWrite sends a group value write; small tells whether data is a
+// value of up to 6 bits
+func (t *KNXTunnel) GroupWrite(address KNXGroupAddress, data []byte, small bool) error {
+	return t.Send(NewKNXGroupCEMI(address, KNXGroupValueWrite, data, small))
+}
+
+// Send tunnels an L_Data.req frame and waits for the gateway to confirm
+// it was sent on the bus. A request the gateway does not acknowledge is
+// sent once more.
+func (t *KNXTunnel) Send(c *KNXCEMI) error {
+	cemi, err := AppendKNXCEMI(nil, c)
+	if err != nil {
+		return err
+	}
+
+	t.mutex.Lock()
+	defer t.mutex.Unlock()
+
+	seq := t.sendSeq
+	body := append([]byte{0x04, t.channel, seq, 0x00}, cemi...)
+	request := appendKNXFrame(nil, knxTunnelingRequest, body)
+
+	confirmation := t.wait(func(m *knxMessage) bool {
+		return m.cemi != nil && m.cemi.MessageCode == knxLDataCon && m.cemi.Destination == c.Destination
+	})
+	defer t.cancel(confirmation)
+
+	acknowledged := false
+	for attempt := 0; attempt < 2 && !acknowledged; attempt++ {
+		ack := t.wait(func(m *knxMessage) bool {
+			return m.service == knxTunnelingAck && len(m.body) >= 4 && m.body[1] == t.channel && m.body[2] == seq
+		})
+		if _, err := t.conn.Write(request); err != nil {
+			t.cancel(ack)
+			return fmt.Errorf("failed to send KNXnet/IP tunneling request: %w", err)
+		}
+
+		select {
+		case m := <-ack.result:
+			if m.body[3] != 0 {
+				return &KNXStatusError{Service: knxTunnelingRequest, Status: m.body[3]}
+			}
+			acknowledged = true
+		case <-time.After(time.Second):
+			t.cancel(ack)
+		case <-t.done:
+			return t.Err()
+		}
+	}
+	if !acknowledged {
+		return fmt.Errorf("KNXnet/IP tunneling request %d not acknowledged", seq)
+	}
+	t.sendSeq++
+
+	select {
+	case m := <-confirmation.result:
+		if m.cemi.Control1&knxControl1Error != 0 {
+			return fmt.Errorf("KNX telegram to %s not confirmed", KNXGroupAddress(c.Destination))
+		}
+		return nil
+	case <-time.After(t.Timeout):
+		return fmt.Errorf("no KNX confirmation for telegram to %s", KNXGroupAddress(c.Destination))
+	case <-t.done:
+		return t.Err()
+	}
+}
+
+// Heartbeat checks the connection with a CONNECTIONSTATE_REQUEST
+func (t *KNXTunnel) Heartbeat() error {
+	return t.control(knxConnectionStateRequest, knxConnectionStateResponse)
+}
+
+// Close disconnects the tunnel and closes the connection
+func (t *KNXTunnel) Close() error {
+	if t.Err() == nil {
+		_ = t.control(knxDisconnectRequest, knxDisconnectResponse)
+	}
+	t.fail(errors.New("KNXnet/IP tunnel closed"))
+	return t.conn.Close()
+}
+
+// control sends a connection state or disconnect request and waits for
+// the response
+func (t *KNXTunnel) control(service, responseService uint16) error {
+	body := appendKNXEndpoint([]byte{t.channel, 0x00}, nil)
+	response := t.wait(func(m *knxMessage) bool {
+		return m.service == responseService && len(m.body) >= 2 && m.body[0] == t.channel
+	})
+	defer t.cancel(response)
+
+	if _, err := t.conn.Write(appendKNXFrame(nil, service, body)); err != nil {
+		return err
+	}
+	select {
+	case m := <-response.result:
+		if m.body[1] != 0 {
+			return &KNXStatusError{Service: service, Status: m.body[1]}
+		}
+		return nil
+	case <-time.After(t.Timeout):
+		return fmt.Errorf("no response to KNXnet/IP service 0x%04X", service)
+	case <-t.done:
+		return t.Err()
+	}
+}
+
+// receive handles frames until the connection is closed
+func (t *KNXTunnel) receive() {
+	buffer := make([]byte, knxMaxFrameSize)
+	var recvSeq byte
+	for {
+		n, err := t.conn.Read(buffer)
+		if err != nil {
+			t.fail(fmt.Errorf("KNXnet/IP connection failed: %w", err))
+			return
+		}
+		service, body, err := parseKNXFrame(buffer[:n])
+		if err != nil {
+			continue
+		}
+		body = append([]byte(nil), body...)
+
+		switch service {
+		case knxTunnelingRequest:
+			if len(body) < 4 || body[1] != t.channel {
+				continue
+			}
+			// Repeated requests are acknowledged again but not handled
+			seq := body[2]
+			if seq != recvSeq && seq != recvSeq-1 {
+				continue
+			}
+			_, _ = t.conn.Write(appendKNXFrame(nil, knxTunnelingAck, []byte{0x04, t.channel, seq, 0x00}))
+			if seq != recvSeq {
+				continue
+			}
+			recvSeq++
+
+			cemi, err := ParseKNXCEMI(body[4:])
+			if err != nil {
+				continue
+			}
+			if cemi.MessageCode == knxLDataInd && cemi.GroupAddressed() && t.onGroup != nil {
+				t.onGroup(knxGroupTelegram(cemi))
+			}
+			t.dispatch(&knxMessage{service: service, body: body, cemi: cemi})
+		case knxDisconnectRequest:
+			if len(body) >= 1 && body[0] == t.channel {
+				_, _ = t.conn.Write(appendKNXFrame(nil, knxDisconnectResponse, []byte{t.channel, 0x00}))
+				t.fail(errors.New("KNXnet/IP gateway closed the tunnel"))
+				return
+			}
+		default:
+			t.dispatch(&knxMessage{service: service, body: body})
+		}
+	}
+}
+
+// keepAlive sends heartbeats while the tunnel is open; the tunnel fails
+// after three heartbeats in a row go unanswered
+func (t *KNXTunnel) keepAlive() {
+	failures := 0
+	ticker := time.NewTicker(t.heartbeat)
+	defer ticker.Stop()
+
+	for {
+		select {
+		case <-ticker.C:
+			if err := t.Heartbeat(); err != nil {
+				failures++
+				if failures == 3 {
+					t.fail(fmt.Errorf("KNXnet/IP gateway stopped answering heartbeats: %w", err))
+					_ = t.conn.Close()
+					return
+				}
+			} else {
+				failures = 0
+			}
+		case <-t.done:
+			return
+		}
+	}
+}
+
+// wait registers a waiter for a message
+func (t *KNXTunnel) wait(match func(*knxMessage) bool) *knxWaiter {
+	waiter := &knxWaiter{match: match, result: make(chan *knxMessage, 1)}
+	t.waitersMutex.Lock()
+	t.waiters = append(t.waiters, waiter)
+	t.waitersMutex.Unlock()
+	return waiter
+}
+
+// cancel removes a waiter
+func (t *KNXTunnel) cancel(waiter *knxWaiter) {
+	t.waitersMutex.Lock()
+	defer t.waitersMutex.Unlock()
+
+	for i, w := range t.waiters {
+		if w == waiter {
+			t.waiters = append(t.waiters[:i], t.waiters[i+1:]...)
+			return
+		}
+	}
+}
+
+// dispatch passes a message to the first waiter it matches
+func (t *KNXTunnel) dispatch(m *knxMessage) {
+	t.waitersMutex.Lock()
+	defer t.waitersMutex.Unlock()
+
+	for i, waiter := range t.waiters {
+		if waiter.match(m) {
+			waiter.result <- m
+			t.waiters = append(t.waiters[:i], t.waiters[i+1:]...)
+			return
+		}
+	}
+}
+
+// fail closes the tunnel with an error, once
+func (t *KNXTunnel) fail(err error) {
+	t.waitersMutex.Lock()
+	defer t.waitersMutex.Unlock()
+
+	if t.err == nil {
+		t.err = err
+		close(t.done)
+	}
+}
+
+func knxGroupTelegram(c *KNXCEMI) *KNXGroupTelegram {
+	return &KNXGroupTelegram{
+		Source:      c.Source,
+		Destination: KNXGroupAddress(c.Destination),
+		APCI:        c.APCI,
+		Data:        c.Data,
+		Small:       c.Small,
+		Timestamp:   time.Now(),
+	}
+}
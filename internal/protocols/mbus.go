@@ -0,0 +1,478 @@
+package protocols
+
+import (
+	"context"
+	"fmt"
+	"io"
+	"strconv"
+	"strings"
+	"sync"
+	"time"
+
+	"github.com/goburrow/serial"
+	"go.uber.org/zap"
+)
+
+// MBusHandler implements ProtocolHandler for meters on a wired M-Bus. The
+// device address names the serial port of the bus level converter, e.g.
+// "/dev/ttyUSB0", and the meter is selected by the config key
+// "primary_address" (0-250) or "secondary_address" (16 hex digits);
+// "baud_rate" sets the bus speed, 2400 by default. Meters on the same
+// port share one connection. Tag addresses are a record's quantity, e.g.
+// "energy" or "flow_temperature", optionally with a storage number for
+// historic values, e.g. "energy@1", or the record's index, e.g. "3".
+type MBusHandler struct {
+	logger      *zap.Logger
+	config      *MBusConfig
+	mutex       sync.Mutex // serializes opening and closing ports
+	connections sync.Map   // map[string]*MBusConnection
+	openPort    func(path string, baudRate int) (io.ReadWriteCloser, error)
+}
+
+// MBusConnection is the master of one bus
+type MBusConnection struct {
+	master    *MBusMaster
+	port      string
+	createdAt time.Time
+	users     int // guarded by the handler's mutex
+
+	mutex    sync.RWMutex
+	lastUsed time.Time
+	requests uint64
+	errors   uint64
+}
+
+// MBusConfig holds M-Bus-specific configuration
+type MBusConfig struct {
+	DefaultBaudRate int           `yaml:"default_baud_rate"`
+	Timeout         time.Duration `yaml:"timeout"`
+	Retries         int           `yaml:"retries"`
+	ScanTimeout     time.Duration `yaml:"scan_timeout"`
+}
+
+// NewMBusHandler creates a new M-Bus protocol handler
+func NewMBusHandler(logger *zap.Logger) ProtocolHandler {
+	handler := &MBusHandler{
+		logger: logger,
+		config: &MBusConfig{
+			DefaultBaudRate: 2400,
+			Timeout:         1 * time.Second,
+			Retries:         2,
+			ScanTimeout:     300 * time.Millisecond,
+		},
+	}
+	handler.openPort = handler.openSerial
+	return handler
+}
+
+// Connect opens the serial port of a meter's bus, unless another meter
+// on it is connected
+func (m *MBusHandler) Connect(device *Device) error {
+	if _, _, err := m.meterAddress(device); err != nil {
+		return err
+	}
+
+	m.mutex.Lock()
+	defer m.mutex.Unlock()
+
+	connectionKey := device.Address
+	if connInterface, exists := m.connections.Load(connectionKey); exists {
+		if device.ConnectionID != connectionKey {
+			connInterface.(*MBusConnection).users++
+		}
+		device.ConnectionID = connectionKey
+		return nil
+	}
+
+	baudRate := m.config.DefaultBaudRate
+	if value, ok := device.Config["baud_rate"].(int); ok && value > 0 {
+		baudRate = value
+	}
+	port, err := m.openPort(device.Address, baudRate)
+	if err != nil {
+		return fmt.Errorf("failed to open M-Bus port %s: %w", device.Address, err)
+	}
+
+	master := NewMBusMaster(port, m.config.Timeout)
+	master.Retries = m.config.Retries
+	connection := &MBusConnection{
+		master:    master,
+		port:      device.Address,
+		createdAt: time.Now(),
+		users:     1,
+		lastUsed:  time.Now(),
+	}
+	m.connections.Store(connectionKey, connection)
+	device.ConnectionID = connectionKey
+
+	m.logger.Info("M-Bus port opened",
+		zap.String("device_id", device.ID),
+		zap.String("port", device.Address),
+		zap.Int("baud_rate", baudRate),
+	)
+
+	return nil
+}
+
+// Disconnect closes the serial port once no meter on it is connected
+func (m *MBusHandler) Disconnect(device *Device) error {
+	if device.ConnectionID == "" {
+		return nil
+	}
+
+	m.mutex.Lock()
+	defer m.mutex.Unlock()
+
+	connInterface, exists := m.connections.Load(device.ConnectionID)
+	if !exists {
+		device.ConnectionID = ""
+		return nil
+	}
+	conn := connInterface.(*MBusConnection)
+
+	device.ConnectionID = ""
+	conn.users--
+	if conn.users > 0 {
+		return nil
+	}
+	m.connections.Delete(conn.port)
+	return conn.master.Close()
+}
+
+// IsConnected checks if the port of a meter's bus is open
+func (m *MBusHandler) IsConnected(device *Device) bool {
+	_, err := m.getConnection(device)
+	return err == nil
+}
+
+// ReadTag reads a meter's data and returns the value of one record
+func (m *MBusHandler) ReadTag(device *Device, tag *Tag) (interface{}, error) {
+	if err := m.ValidateTagAddress(tag.Address); err != nil {
+		return nil, err
+	}
+
+	data, err := m.readData(device)
+	if err != nil {
+		return nil, err
+	}
+
+	record := findMBusRecord(data.Records, tag.Address)
+	if record == nil {
+		return nil, fmt.Errorf("meter has no record %s", tag.Address)
+	}
+	return record.Value, nil
+}
+
+// WriteTag is not supported; the handler only reads meters
+func (m *MBusHandler) WriteTag(device *Device, tag *Tag, value interface{}) error {
+	return fmt.Errorf("M-Bus records are read-only")
+}
+
+// ReadMultipleTags reads a meter's data once and returns the values of
+// the records. Records the meter does not have are left out of the
+// results.
+func (m *MBusHandler) ReadMultipleTags(device *Device, tags []*Tag) (map[string]interface{}, error) {
+	data, err := m.readData(device)
+	if err != nil {
+		return nil, err
+	}
+
+	results := make(map[string]interface{})
+	for _, tag := range tags {
+		if record := findMBusRecord(data.Records, tag.Address); record != nil {
+			results[tag.ID] = record.Value
+		}
+	}
+	return results, nil
+}
+
+// ReadRecords reads a meter's data and returns every record as a tag with
+// its unit. Tags are addressed by quantity where that finds the record
+// and by index otherwise.
+func (m *MBusHandler) ReadRecords(device *Device) ([]*Tag, error) {
+	data, err := m.readData(device)
+	if err != nil {
+		return nil, err
+	}
+
+	now := time.Now()
+	tags := make([]*Tag, 0, len(data.Records))
+	for i := range data.Records {
+		record := &data.Records[i]
+		address := record.Quantity
+		if record.StorageNumber != 0 {
+			address = fmt.Sprintf("%s@%d", record.Quantity, record.StorageNumber)
+		}
+		if findMBusRecord(data.Records, address) != record {
+			address = strconv.Itoa(i)
+		}
+
+		tags = append(tags, &Tag{
+			ID:          address,
+			Name:        address,
+			Address:     address,
+			DataType:    string(record.DataType()),
+			Value:       record.Value,
+			Quality:     QualityGood,
+			Timestamp:   now,
+			Unit:        record.Unit,
+			Description: fmt.Sprintf("%s, storage %d, tariff %d, %s", record.Quantity, record.StorageNumber, record.Tariff, record.Function),
+		})
+	}
+	return tags, nil
+}
+
+// DiscoverDevices scans the primary addresses of the bus on the serial
+// port given as the network range
+func (m *MBusHandler) DiscoverDevices(ctx context.Context, networkRange string) ([]*Device, error) {
+	probe := &Device{
+		ID:       fmt.Sprintf("mbus-scan-%s", networkRange),
+		Address:  networkRange,
+		Config:   map[string]interface{}{"primary_address": 0},
+		Protocol: "mbus",
+	}
+	if err := m.Connect(probe); err != nil {
+		return nil, err
+	}
+	defer m.Disconnect(probe)
+
+	conn, err := m.getConnection(probe)
+	if err != nil {
+		return nil, err
+	}
+	addresses, err := conn.master.ScanPrimary(ctx, m.config.ScanTimeout)
+
+	devices := make([]*Device, 0, len(addresses))
+	for _, address := range addresses {
+		devices = append(devices, &Device{
+			ID:       fmt.Sprintf("mbus-%s-%d", strings.TrimPrefix(networkRange, "/dev/"), address),
+			Name:     fmt.Sprintf("M-Bus meter %d", address),
+			Protocol: "mbus",
+			Address:  networkRange,
+			Config: map[string]interface{}{
+				"primary_address": int(address),
+			},
+			LastSeen: time.Now(),
+		})
+	}
+	return devices, err
+}
+
+// GetDeviceInfo returns information about a meter from its data header
+func (m *MBusHandler) GetDeviceInfo(device *Device) (*DeviceInfo, error) {
+	info := &DeviceInfo{
+		Vendor:         "Unknown",
+		Model:          "M-Bus Meter",
+		Capabilities:   []string{"req-ud2", "secondary-addressing", "multi-telegram"},
+		MaxConnections: 1,
+		SupportedRates: []int{300, 2400, 9600},
+		CustomInfo:     make(map[string]string),
+	}
+
+	data, err := m.readData(device)
+	if err != nil {
+		return info, nil
+	}
+	if data.Manufacturer != "" {
+		info.Vendor = data.Manufacturer
+		info.Model = fmt.Sprintf("%s meter", data.Medium)
+		info.SerialNumber = fmt.Sprintf("%08d", data.ID)
+		info.FirmwareVersion = strconv.Itoa(int(data.Version))
+		info.CustomInfo["secondary_address"] = data.SecondaryAddress()
+	}
+	info.CustomInfo["medium"] = data.Medium.String()
+	info.CustomInfo["status"] = fmt.Sprintf("0x%02X", data.Status)
+	info.CustomInfo["records"] = strconv.Itoa(len(data.Records))
+	return info, nil
+}
+
+// GetSupportedDataTypes returns the data types of record values
+func (m *MBusHandler) GetSupportedDataTypes() []string {
+	return []string{
+		string(DataTypeInt64),
+		string(DataTypeFloat64),
+		string(DataTypeString),
+		string(DataTypeBytes),
+	}
+}
+
+// ValidateTagAddress validates a record address
+func (m *MBusHandler) ValidateTagAddress(address string) error {
+	_, _, _, err := parseMBusAddress(address)
+	return err
+}
+
+// Ping checks that a meter acknowledges a link reset; meters selected by
+// secondary address are selected instead
+func (m *MBusHandler) Ping(device *Device) error {
+	conn, err := m.getConnection(device)
+	if err != nil {
+		return err
+	}
+	primary, secondary, err := m.meterAddress(device)
+	if err != nil {
+		return err
+	}
+
+	if secondary != "" {
+		err = conn.master.SelectSecondary(secondary)
+	} else {
+		err = conn.master.Reset(primary)
+	}
+	conn.record(err)
+	return err
+}
+
+// GetDiagnostics returns diagnostic information for a meter's bus
+func (m *MBusHandler) GetDiagnostics(device *Device) (*Diagnostics, error) {
+	conn, err := m.getConnection(device)
+	if err != nil {
+		return nil, err
+	}
+
+	conn.mutex.RLock()
+	defer conn.mutex.RUnlock()
+
+	diagnostics := &Diagnostics{
+		IsHealthy:         true,
+		LastCommunication: conn.lastUsed,
+		ErrorCount:        conn.errors,
+		ConnectionUptime:  time.Since(conn.createdAt),
+		ProtocolDiagnostics: map[string]interface{}{
+			"port":     conn.port,
+			"requests": conn.requests,
+		},
+	}
+	if conn.requests > 0 {
+		diagnostics.SuccessRate = float64(conn.requests-conn.errors) / float64(conn.requests)
+		diagnostics.IsHealthy = diagnostics.SuccessRate > 0.5
+	}
+	return diagnostics, nil
+}
+
+func (m *MBusHandler) getConnection(device *Device) (*MBusConnection, error) {
+	if device.ConnectionID == "" {
+		return nil, fmt.Errorf("device not connected")
+	}
+
+	connInterface, exists := m.connections.Load(device.ConnectionID)
+	if !exists {
+		return nil, fmt.Errorf("connection not found")
+	}
+	return connInterface.(*MBusConnection), nil
+}
+
+// readData reads the data of a device's meter
+func (m *MBusHandler) readData(device *Device) (*MBusData, error) {
+	conn, err := m.getConnection(device)
+	if err != nil {
+		return nil, err
+	}
+	primary, secondary, err := m.meterAddress(device)
+	if err != nil {
+		return nil, err
+	}
+
+	var data *MBusData
+	if secondary != "" {
+		data, err = conn.master.ReadSecondary(secondary)
+	} else {
+		data, err = conn.master.ReadData(primary)
+	}
+	conn.record(err)
+	return data, err
+}
+
+// meterAddress returns the primary or secondary address of a device's
+// meter
+func (m *MBusHandler) meterAddress(device *Device) (byte, string, error) {
+	if secondary, ok := device.Config["secondary_address"].(string); ok && secondary != "" {
+		if _, err := parseMBusSecondaryAddress(secondary); err != nil {
+			return 0, "", err
+		}
+		return MBusAddressNetwork, secondary, nil
+	}
+
+	switch address := device.Config["primary_address"].(type) {
+	case int:
+		if address < 0 || address > MBusMaxPrimaryAddress {
+			return 0, "", fmt.Errorf("invalid M-Bus primary address %d", address)
+		}
+		return byte(address), "", nil
+	case nil:
+		return 0, "", fmt.Errorf("M-Bus meter requires a primary_address or secondary_address")
+	default:
+		return 0, "", fmt.Errorf("invalid M-Bus primary address %v", address)
+	}
+}
+
+func (m *MBusHandler) openSerial(path string, baudRate int) (io.ReadWriteCloser, error) {
+	return serial.Open(&serial.Config{
+		Address:  path,
+		BaudRate: baudRate,
+		DataBits: 8,
+		StopBits: 1,
+		Parity:   "E",
+		Timeout:  m.config.Timeout,
+	})
+}
+
+func (c *MBusConnection) record(err error) {
+	c.mutex.Lock()
+	defer c.mutex.Unlock()
+
+	c.requests++
+	c.lastUsed = time.Now()
+	if err != nil {
+		c.errors++
+	}
+}
+
+// parseMBusAddress parses a record address into a record index, or a
+// quantity and storage number; the index is -1 for the latter
+func parseMBusAddress(address string) (int, string, uint64, error) {
+	if index, err := strconv.Atoi(address); err == nil {
+		if index < 0 {
+			return 0, "", 0, fmt.Errorf("invalid M-Bus record index %d", index)
+		}
+		return index, "", 0, nil
+	}
+
+	quantity, storageText, hasStorage := strings.Cut(address, "@")
+	if quantity == "" || strings.Trim(quantity, "abcdefghijklmnopqrstuvwxyz_") != "" {
+		return 0, "", 0, fmt.Errorf("invalid M-Bus record address %q", address)
+	}
+	var storage uint64
+	if hasStorage {
+		var err error
+		if storage, err = strconv.ParseUint(storageText, 10, 64); err != nil {
+			return 0, "", 0, fmt.Errorf("invalid M-Bus storage number in %q", address)
+		}
+	}
+	return -1, quantity, storage, nil
+}
+
+// findMBusRecord returns the record at an address. A quantity finds the
+// first instantaneous value of the storage number without tariff or
+// subunit.
+func findMBusRecord(records []MBusRecord, address string) *MBusRecord {
+	index, quantity, storage, err := parseMBusAddress(address)
+	if err != nil {
+		return nil
+	}
+	if index >= 0 {
+		if index < len(records) {
+			return &records[index]
+		}
+		return nil
+	}
+
+	for i := range records {
+		record := &records[i]
+		if record.Quantity == quantity && record.StorageNumber == storage &&
+			record.Function == MBusFunctionInstantaneous && record.Tariff == 0 && record.Subunit == 0 {
+			return record
+		}
+	}
+	return nil
+}
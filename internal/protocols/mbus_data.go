@@ -0,0 +1,640 @@
+package protocols
+
+import (
+	"encoding/binary"
+	"fmt"
+	"math"
+	"time"
+)
+
+// M-Bus Variable Data Structure
+//
+// A RSP_UD response with CI 0x72 carries a 12 byte header (identification
+// number, manufacturer, version, medium, access number, status and
+// signature) followed by data records (EN 13757-3). Each record starts
+// with a data information block, a DIF and up to ten DIFEs giving the
+// coding of the value, its function, storage number, tariff and subunit,
+// and a value information block, a VIF and VIFEs giving the quantity,
+// unit and decimal exponent. Values are returned in the base unit of
+// their quantity, e.g. energy in Wh whether the meter counts Wh or MWh.
+
+// MBusMedium is the medium a meter measures
+type MBusMedium byte
+
+const (
+	MBusMediumOther         MBusMedium = 0x00
+	MBusMediumOil           MBusMedium = 0x01
+	MBusMediumElectricity   MBusMedium = 0x02
+	MBusMediumGas           MBusMedium = 0x03
+	MBusMediumHeatOutlet    MBusMedium = 0x04
+	MBusMediumSteam         MBusMedium = 0x05
+	MBusMediumWarmWater     MBusMedium = 0x06
+	MBusMediumWater         MBusMedium = 0x07
+	MBusMediumHeatCost      MBusMedium = 0x08
+	MBusMediumCompressedAir MBusMedium = 0x09
+	MBusMediumCoolingOutlet MBusMedium = 0x0A
+	MBusMediumCoolingInlet  MBusMedium = 0x0B
+	MBusMediumHeatInlet     MBusMedium = 0x0C
+	MBusMediumHeatCooling   MBusMedium = 0x0D
+	MBusMediumBus           MBusMedium = 0x0E
+	MBusMediumHotWater      MBusMedium = 0x15
+	MBusMediumColdWater     MBusMedium = 0x16
+	MBusMediumDualWater     MBusMedium = 0x17
+	MBusMediumPressure      MBusMedium = 0x18
+	MBusMediumADConverter   MBusMedium = 0x19
+)
+
+var mbusMediumNames = map[MBusMedium]string{
+	MBusMediumOther:         "Other",
+	MBusMediumOil:           "Oil",
+	MBusMediumElectricity:   "Electricity",
+	MBusMediumGas:           "Gas",
+	MBusMediumHeatOutlet:    "Heat (outlet)",
+	MBusMediumSteam:         "Steam",
+	MBusMediumWarmWater:     "Warm water",
+	MBusMediumWater:         "Water",
+	MBusMediumHeatCost:      "Heat cost allocator",
+	MBusMediumCompressedAir: "Compressed air",
+	MBusMediumCoolingOutlet: "Cooling (outlet)",
+	MBusMediumCoolingInlet:  "Cooling (inlet)",
+	MBusMediumHeatInlet:     "Heat (inlet)",
+	MBusMediumHeatCooling:   "Heat / cooling",
+	MBusMediumBus:           "Bus / system",
+	MBusMediumHotWater:      "Hot water",
+	MBusMediumColdWater:     "Cold water",
+	MBusMediumDualWater:     "Dual register water",
+	MBusMediumPressure:      "Pressure",
+	MBusMediumADConverter:   "A/D converter",
+}
+
+func (m MBusMedium) String() string {
+	if name, exists := mbusMediumNames[m]; exists {
+		return name
+	}
+	return fmt.Sprintf("Medium 0x%02X", byte(m))
+}
+
+// MBusFunction is the function of a record's value
+type MBusFunction byte
+
+const (
+	MBusFunctionInstantaneous MBusFunction = 0
+	MBusFunctionMaximum       MBusFunction = 1
+	MBusFunctionMinimum       MBusFunction = 2
+	MBusFunctionError         MBusFunction = 3
+)
+
+func (f MBusFunction) String() string {
+	switch f {
+	case MBusFunctionMaximum:
+		return "maximum"
+	case MBusFunctionMinimum:
+		return "minimum"
+	case MBusFunctionError:
+		return "value during error"
+	}
+	return "instantaneous"
+}
+
+// MBusData is the variable data structure of a response
+type MBusData struct {
+	// Header fields; ID, Manufacturer, Version and Medium are only sent
+	// in the long header
+	ID           uint32
+	Manufacturer string
+	Version      byte
+	Medium       MBusMedium
+	AccessNumber byte
+	Status       byte
+
+	Records []MBusRecord
+
+	// ManufacturerData is the manufacturer specific data after the
+	// records; MoreRecords is set when the meter has records for another
+	// response
+	ManufacturerData []byte
+	MoreRecords      bool
+}
+
+// SecondaryAddress returns the meter's secondary address, the
+// identification number, manufacturer code, version and medium as 16 hex
+// digits
+func (d *MBusData) SecondaryAddress() string {
+	return fmt.Sprintf("%08d%04X%02X%02X", d.ID, mbusManufacturerCode(d.Manufacturer), d.Version, byte(d.Medium))
+}
+
+// MBusRecord is one data record
+type MBusRecord struct {
+	Function      MBusFunction
+	StorageNumber uint64
+	Tariff        uint32
+	Subunit       uint32
+
+	// Quantity names what the value measures, e.g. "energy" or
+	// "flow_temperature"; Unit is empty for counts and identifiers
+	Quantity string
+	Unit     string
+
+	// Value is a float64 for quantities with a unit, an int64 for counts
+	// and identifiers, a time.Time for dates, a string for text and a
+	// []byte for binary data
+	Value interface{}
+
+	DIF  byte
+	DIFE []byte
+	VIF  byte
+	VIFE []byte
+}
+
+// DataType returns the tag data type of the record's value
+func (r *MBusRecord) DataType() DataType {
+	switch r.Value.(type) {
+	case float64:
+		return DataTypeFloat64
+	case int64:
+		return DataTypeInt64
+	case []byte:
+		return DataTypeBytes
+	}
+	return DataTypeString
+}
+
+// ParseMBusData parses the variable data structure of a response with CI
+// field ci
+func ParseMBusData(ci byte, data []byte) (*MBusData, error) {
+	result := &MBusData{}
+	var signature uint16
+
+	switch ci {
+	case MBusCIResponseLong:
+		if len(data) < 12 {
+			return nil, fmt.Errorf("M-Bus long header of %d bytes", len(data))
+		}
+		id, err := mbusBCD(data[0:4])
+		if err != nil {
+			return nil, fmt.Errorf("invalid M-Bus identification number: %w", err)
+		}
+		result.ID = uint32(id)
+		result.Manufacturer = mbusManufacturer(binary.LittleEndian.Uint16(data[4:6]))
+		result.Version = data[6]
+		result.Medium = MBusMedium(data[7])
+		result.AccessNumber, result.Status = data[8], data[9]
+		signature = binary.LittleEndian.Uint16(data[10:12])
+		data = data[12:]
+	case MBusCIResponseShort:
+		if len(data) < 4 {
+			return nil, fmt.Errorf("M-Bus short header of %d bytes", len(data))
+		}
+		result.AccessNumber, result.Status = data[0], data[1]
+		signature = binary.LittleEndian.Uint16(data[2:4])
+		data = data[4:]
+	case MBusCIResponseNone:
+	default:
+		return nil, fmt.Errorf("unsupported M-Bus CI field 0x%02X", ci)
+	}
+
+	// The encryption mode is in bits 8 to 12 of the configuration word
+	if mode := signature >> 8 & 0x1F; mode != 0 {
+		return nil, fmt.Errorf("M-Bus data encrypted with mode %d is not supported", mode)
+	}
+
+	for offset := 0; offset < len(data); {
+		switch data[offset] {
+		case 0x2F:
+			// Idle filler
+			offset++
+			continue
+		case 0x0F, 0x1F:
+			result.MoreRecords = data[offset] == 0x1F
+			result.ManufacturerData = data[offset+1:]
+			return result, nil
+		}
+
+		record, n, err := parseMBusRecord(data[offset:])
+		if err != nil {
+			return nil, fmt.Errorf("M-Bus record %d: %w", len(result.Records), err)
+		}
+		result.Records = append(result.Records, *record)
+		offset += n
+	}
+	return result, nil
+}
+
+// mbusValueKind is how a VIF interprets a record's value
+type mbusValueKind int
+
+const (
+	mbusKindNumber mbusValueKind = iota
+	mbusKindDate
+	mbusKindDateTime
+	mbusKindText
+)
+
+// mbusValueInfo is the meaning of a value information block
+type mbusValueInfo struct {
+	quantity string
+	unit     string
+	exponent int
+	kind     mbusValueKind
+}
+
+// mbusDurationUnits are the units of the two bit duration codes
+var mbusDurationUnits = [4]string{"s", "min", "h", "d"}
+
+// parseMBusRecord parses one data record and returns its length
+func parseMBusRecord(data []byte) (*MBusRecord, int, error) {
+	record := &MBusRecord{DIF: data[0]}
+	record.Function = MBusFunction(data[0] >> 4 & 0x03)
+	record.StorageNumber = uint64(data[0] >> 6 & 0x01)
+
+	offset := 1
+	extended := data[0]&0x80 != 0
+	for extended {
+		if offset >= len(data) {
+			return nil, 0, fmt.Errorf("truncated DIFE")
+		}
+		if len(record.DIFE) == 10 {
+			return nil, 0, fmt.Errorf("more than 10 DIFEs")
+		}
+		dife := data[offset]
+		i := len(record.DIFE)
+		record.StorageNumber |= uint64(dife&0x0F) << (1 + 4*i)
+		record.Tariff |= uint32(dife>>4&0x03) << (2 * i)
+		record.Subunit |= uint32(dife>>6&0x01) << i
+		record.DIFE = append(record.DIFE, dife)
+		extended = dife&0x80 != 0
+		offset++
+	}
+
+	if offset >= len(data) {
+		return nil, 0, fmt.Errorf("missing VIF")
+	}
+	record.VIF = data[offset]
+	offset++
+	extended = record.VIF&0x80 != 0
+	for extended {
+		if offset >= len(data) {
+			return nil, 0, fmt.Errorf("truncated VIFE")
+		}
+		if len(record.VIFE) == 10 {
+			return nil, 0, fmt.Errorf("more than 10 VIFEs")
+		}
+		record.VIFE = append(record.VIFE, data[offset])
+		extended = data[offset]&0x80 != 0
+		offset++
+	}
+
+	info, err := mbusValueInformation(record.VIF, record.VIFE)
+	if err != nil {
+		return nil, 0, err
+	}
+
+	// A plain text VIF is followed by its unit, reversed
+	if record.VIF&0x7F == 0x7C {
+		if offset >= len(data) || offset+1+int(data[offset]) > len(data) {
+			return nil, 0, fmt.Errorf("truncated plain text unit")
+		}
+		info.unit = mbusReversedText(data[offset+1 : offset+1+int(data[offset])])
+		offset += 1 + int(data[offset])
+	}
+	record.Quantity, record.Unit = info.quantity, info.unit
+
+	value, n, err := mbusValue(record.DIF&0x0F, data[offset:], info)
+	if err != nil {
+		return nil, 0, err
+	}
+	record.Value = value
+	return record, offset + n, nil
+}
+
+// mbusValue decodes a value coded by the data field of a DIF
+func mbusValue(field byte, data []byte, info *mbusValueInfo) (interface{}, int, error) {
+	length := [16]int{0, 1, 2, 3, 4, 4, 6, 8, 0, 1, 2, 3, 4, -1, 6, -1}[field]
+	if length < 0 {
+		if field != 0x0D {
+			return nil, 0, fmt.Errorf("unsupported DIF data field 0x%X", field)
+		}
+		return mbusVariableValue(data, info)
+	}
+	if len(data) < length {
+		return nil, 0, fmt.Errorf("truncated value of %d bytes", length)
+	}
+	raw := data[:length]
+
+	var number int64
+	switch {
+	case length == 0:
+		return nil, 0, nil
+	case field == 0x05:
+		return mbusScaled(float64(math.Float32frombits(binary.LittleEndian.Uint32(raw))), info), length, nil
+	case field >= 0x09:
+		bcd, err := mbusBCD(raw)
+		if err != nil {
+			return nil, 0, err
+		}
+		number = bcd
+	default:
+		// Signed, little endian
+		for i := length - 1; i >= 0; i-- {
+			number = number<<8 | int64(raw[i])
+		}
+		shift := 64 - 8*uint(length)
+		number = number << shift >> shift
+	}
+
+	switch {
+	case info.kind == mbusKindDate && length == 2:
+		return mbusDate(raw), length, nil
+	case info.kind == mbusKindDateTime && length == 4:
+		return mbusDateTime(raw), length, nil
+	case info.unit == "":
+		return number, length, nil
+	}
+	return mbusScaled(float64(number), info), length, nil
+}
+
+// mbusVariableValue decodes a variable length value, whose first byte
+// gives its coding and length
+func mbusVariableValue(data []byte, info *mbusValueInfo) (interface{}, int, error) {
+	if len(data) == 0 {
+		return nil, 0, fmt.Errorf("missing variable length")
+	}
+	lvar := int(data[0])
+
+	var length int
+	switch {
+	case lvar <= 0xBF:
+		length = lvar
+	case lvar >= 0xC0 && lvar <= 0xC9, lvar >= 0xD0 && lvar <= 0xD9:
+		length = lvar & 0x0F
+	case lvar >= 0xE0 && lvar <= 0xEF:
+		length = lvar - 0xE0
+	default:
+		return nil, 0, fmt.Errorf("unsupported variable length 0x%02X", lvar)
+	}
+	if len(data) < 1+length {
+		return nil, 0, fmt.Errorf("truncated value of %d bytes", length)
+	}
+	raw := data[1 : 1+length]
+
+	switch {
+	case lvar <= 0xBF:
+		return mbusReversedText(raw), 1 + length, nil
+	case lvar <= 0xD9:
+		number, err := mbusBCD(raw)
+		if err != nil {
+			return nil, 0, err
+		}
+		if lvar >= 0xD0 {
+			number = -number
+		}
+		if info.unit == "" {
+			return number, 1 + length, nil
+		}
+		return mbusScaled(float64(number), info), 1 + length, nil
+	}
+	return append([]byte(nil), raw...), 1 + length, nil
+}
+
+// mbusValueInformation interprets a VIF and its VIFEs
+func mbusValueInformation(vif byte, vife []byte) (*mbusValueInfo, error) {
+	var info *mbusValueInfo
+	combinable := vife
+
+	switch vif & 0x7F {
+	case 0x7C:
+		info = &mbusValueInfo{quantity: "custom"}
+	case 0x7E:
+		info = &mbusValueInfo{quantity: "any"}
+	case 0x7F:
+		info = &mbusValueInfo{quantity: "manufacturer_specific"}
+	case 0x7D:
+		if len(vife) == 0 {
+			return nil, fmt.Errorf("VIF 0xFD without extension")
+		}
+		info = mbusExtendedVIF(vife[0] & 0x7F)
+		combinable = vife[1:]
+	case 0x7B:
+		if len(vife) == 0 {
+			return nil, fmt.Errorf("VIF 0xFB without extension")
+		}
+		info = mbusSecondExtendedVIF(vife[0] & 0x7F)
+		combinable = vife[1:]
+	default:
+		info = mbusPrimaryVIF(vif & 0x7F)
+	}
+	if info == nil {
+		return nil, fmt.Errorf("reserved VIF 0x%02X % X", vif, vife)
+	}
+
+	// Combinable extensions scale the value; other extensions, such as
+	// those qualifying a value as per hour or as an accumulation, are
+	// kept in the record's VIFE
+	for _, extension := range combinable {
+		switch code := extension & 0x7F; {
+		case code >= 0x70 && code <= 0x77:
+			info.exponent += int(code&0x07) - 6
+		case code == 0x7D:
+			info.exponent += 3
+		}
+	}
+	return info, nil
+}
+
+// mbusPrimaryVIF interprets a primary VIF without its extension bit
+func mbusPrimaryVIF(code byte) *mbusValueInfo {
+	n := int(code & 0x07)
+	nn := int(code & 0x03)
+	switch {
+	case code <= 0x07:
+		return &mbusValueInfo{quantity: "energy", unit: "Wh", exponent: n - 3}
+	case code <= 0x0F:
+		return &mbusValueInfo{quantity: "energy", unit: "J", exponent: n}
+	case code <= 0x17:
+		return &mbusValueInfo{quantity: "volume", unit: "m³", exponent: n - 6}
+	case code <= 0x1F:
+		return &mbusValueInfo{quantity: "mass", unit: "kg", exponent: n - 3}
+	case code <= 0x23:
+		return &mbusValueInfo{quantity: "on_time", unit: mbusDurationUnits[nn]}
+	case code <= 0x27:
+		return &mbusValueInfo{quantity: "operating_time", unit: mbusDurationUnits[nn]}
+	case code <= 0x2F:
+		return &mbusValueInfo{quantity: "power", unit: "W", exponent: n - 3}
+	case code <= 0x37:
+		return &mbusValueInfo{quantity: "power", unit: "J/h", exponent: n}
+	case code <= 0x3F:
+		return &mbusValueInfo{quantity: "volume_flow", unit: "m³/h", exponent: n - 6}
+	case code <= 0x47:
+		return &mbusValueInfo{quantity: "volume_flow", unit: "m³/min", exponent: n - 7}
+	case code <= 0x4F:
+		return &mbusValueInfo{quantity: "volume_flow", unit: "m³/s", exponent: n - 9}
+	case code <= 0x57:
+		return &mbusValueInfo{quantity: "mass_flow", unit: "kg/h", exponent: n - 3}
+	case code <= 0x5B:
+		return &mbusValueInfo{quantity: "flow_temperature", unit: "°C", exponent: nn - 3}
+	case code <= 0x5F:
+		return &mbusValueInfo{quantity: "return_temperature", unit: "°C", exponent: nn - 3}
+	case code <= 0x63:
+		return &mbusValueInfo{quantity: "temperature_difference", unit: "K", exponent: nn - 3}
+	case code <= 0x67:
+		return &mbusValueInfo{quantity: "external_temperature", unit: "°C", exponent: nn - 3}
+	case code <= 0x6B:
+		return &mbusValueInfo{quantity: "pressure", unit: "bar", exponent: nn - 3}
+	case code == 0x6C:
+		return &mbusValueInfo{quantity: "date", kind: mbusKindDate}
+	case code == 0x6D:
+		return &mbusValueInfo{quantity: "date_time", kind: mbusKindDateTime}
+	case code == 0x6E:
+		return &mbusValueInfo{quantity: "hca_units"}
+	case code >= 0x70 && code <= 0x73:
+		return &mbusValueInfo{quantity: "averaging_duration", unit: mbusDurationUnits[nn]}
+	case code >= 0x74 && code <= 0x77:
+		return &mbusValueInfo{quantity: "actuality_duration", unit: mbusDurationUnits[nn]}
+	case code == 0x78:
+		return &mbusValueInfo{quantity: "fabrication_number"}
+	case code == 0x79:
+		return &mbusValueInfo{quantity: "identification"}
+	case code == 0x7A:
+		return &mbusValueInfo{quantity: "bus_address"}
+	}
+	return nil
+}
+
+// mbusExtendedVIF interprets the first VIFE after VIF 0xFD
+func mbusExtendedVIF(code byte) *mbusValueInfo {
+	switch {
+	case code >= 0x40 && code <= 0x4F:
+		return &mbusValueInfo{quantity: "voltage", unit: "V", exponent: int(code&0x0F) - 9}
+	case code >= 0x50 && code <= 0x5F:
+		return &mbusValueInfo{quantity: "current", unit: "A", exponent: int(code&0x0F) - 12}
+	}
+
+	quantity, exists := map[byte]string{
+		0x08: "access_number",
+		0x09: "medium",
+		0x0A: "manufacturer",
+		0x0B: "parameter_set",
+		0x0C: "model_version",
+		0x0D: "hardware_version",
+		0x0E: "firmware_version",
+		0x0F: "software_version",
+		0x10: "customer_location",
+		0x11: "customer",
+		0x17: "error_flags",
+		0x1A: "digital_output",
+		0x1B: "digital_input",
+		0x1C: "baud_rate",
+		0x3A: "dimensionless",
+		0x60: "reset_counter",
+		0x61: "cumulation_counter",
+	}[code]
+	if exists {
+		return &mbusValueInfo{quantity: quantity}
+	}
+	if code == 0x74 {
+		return &mbusValueInfo{quantity: "remaining_battery", unit: "d"}
+	}
+	return nil
+}
+
+// mbusSecondExtendedVIF interprets the first VIFE after VIF 0xFB, whose
+// larger units are converted to those of the primary VIFs
+func mbusSecondExtendedVIF(code byte) *mbusValueInfo {
+	n := int(code & 0x01)
+	switch {
+	case code <= 0x01:
+		return &mbusValueInfo{quantity: "energy", unit: "Wh", exponent: n + 5}
+	case code == 0x08 || code == 0x09:
+		return &mbusValueInfo{quantity: "energy", unit: "J", exponent: n + 8}
+	case code == 0x10 || code == 0x11:
+		return &mbusValueInfo{quantity: "volume", unit: "m³", exponent: n + 2}
+	case code == 0x18 || code == 0x19:
+		return &mbusValueInfo{quantity: "mass", unit: "kg", exponent: n + 5}
+	case code == 0x28 || code == 0x29:
+		return &mbusValueInfo{quantity: "power", unit: "W", exponent: n + 5}
+	case code == 0x30 || code == 0x31:
+		return &mbusValueInfo{quantity: "power", unit: "J/h", exponent: n + 8}
+	}
+	return nil
+}
+
+// mbusScaled applies the decimal exponent of a value
+func mbusScaled(value float64, info *mbusValueInfo) float64 {
+	if info.exponent == 0 {
+		return value
+	}
+	return value * math.Pow10(info.exponent)
+}
+
+// mbusBCD decodes a little endian BCD number; a high nibble of 0xF marks
+// a negative number
+func mbusBCD(data []byte) (int64, error) {
+	var value int64
+	negative := false
+	for i := len(data) - 1; i >= 0; i-- {
+		high, low := data[i]>>4, data[i]&0x0F
+		if i == len(data)-1 && high == 0x0F {
+			negative, high = true, 0
+		}
+		if high > 9 || low > 9 {
+			return 0, fmt.Errorf("invalid BCD % X", data)
+		}
+		value = value*100 + int64(high)*10 + int64(low)
+	}
+	if negative {
+		value = -value
+	}
+	return value, nil
+}
+
+// mbusDate decodes a type G date
+func mbusDate(data []byte) time.Time {
+	day := int(data[0] & 0x1F)
+	month := int(data[1] & 0x0F)
+	return time.Date(mbusYear(data[0], data[1]), time.Month(month), day, 0, 0, 0, 0, time.Local)
+}
+
+// mbusDateTime decodes a type F date and time
+func mbusDateTime(data []byte) time.Time {
+	minute := int(data[0] & 0x3F)
+	hour := int(data[1] & 0x1F)
+	day := int(data[2] & 0x1F)
+	month := int(data[3] & 0x0F)
+	return time.Date(mbusYear(data[2], data[3]), time.Month(month), day, hour, minute, 0, 0, time.Local)
+}
+
+// mbusYear decodes the year split over the high bits of a date's day and
+// month bytes; years 81 to 99 are in the 20th century
+func mbusYear(dayByte, monthByte byte) int {
+	year := int(dayByte>>5) | int(monthByte>>4)<<3
+	if year > 80 {
+		return 1900 + year
+	}
+	return 2000 + year
+}
+
+// mbusManufacturer decodes a manufacturer code into its three letters
+func mbusManufacturer(code uint16) string {
+	return string([]byte{byte(code>>10&0x1F) + 64, byte(code>>5&0x1F) + 64, byte(code&0x1F) + 64})
+}
+
+// mbusManufacturerCode encodes a three letter manufacturer
+func mbusManufacturerCode(manufacturer string) uint16 {
+	if len(manufacturer) != 3 {
+		return 0
+	}
+	var code uint16
+	for i := 0; i < 3; i++ {
+		code = code<<5 | uint16(manufacturer[i]-64)&0x1F
+	}
+	return code
+}
+
+// mbusReversedText decodes text sent last character first
+func mbusReversedText(data []byte) string {
+	text := make([]byte, len(data))
+	for i, b := range data {
+		text[len(data)-1-i] = b
+	}
+	return string(text)
+}
@@ -0,0 +1,197 @@
+package protocols
+
+import (
+	"fmt"
+	"io"
+)
+
+// M-Bus Frames
+//
+// Wired M-Bus (EN 13757-2) uses four frame formats: the single character
+// 0xE5 acknowledging a request, the short frame 0x10 C A CS 0x16 for
+// requests without data, and the control and long frames
+// 0x68 L L 0x68 C A CI data CS 0x16, where L counts C, A, CI and the data
+// and CS is the sum of the same bytes modulo 256.
+
+// M-Bus frame delimiters
+const (
+	mbusACK        = 0xE5
+	mbusShortStart = 0x10
+	mbusLongStart  = 0x68
+	mbusStop       = 0x16
+)
+
+// M-Bus control fields. Requests to a meter alternate the frame count bit
+// (FCB) so a meter can tell a repeated request from a new one.
+const (
+	MBusSndNke = 0x40 // link reset
+	MBusSndUD  = 0x53 // send user data
+	MBusReqUD2 = 0x5B // request class 2 data
+	MBusReqUD1 = 0x5A // request class 1 data
+	MBusRspUD  = 0x08 // respond user data
+
+	mbusControlFCB      = 0x20
+	mbusControlResponse = 0x4F // response control field without ACD and DFC
+)
+
+// M-Bus control information fields
+const (
+	MBusCIApplicationReset = 0x50
+	MBusCIDataSend         = 0x51
+	MBusCISelect           = 0x52
+	MBusCIBaudRate300      = 0xB8
+	MBusCIBaudRate2400     = 0xBB
+	MBusCIBaudRate9600     = 0xBD
+	MBusCIResponseLong     = 0x72 // variable data with the long header
+	MBusCIResponseShort    = 0x7A // variable data with the short header
+	MBusCIResponseNone     = 0x78 // variable data without a header
+)
+
+// M-Bus primary addresses
+const (
+	MBusAddressNetwork          = 0xFD // meter selected by secondary address
+	MBusAddressBroadcastReply   = 0xFE
+	MBusAddressBroadcastNoReply = 0xFF
+	MBusMaxPrimaryAddress       = 250
+)
+
+// mbusMaxFrameSize is the size of a long frame of 255 bytes
+const mbusMaxFrameSize = 261
+
+// MBusFrame is an M-Bus frame. Short frames have no CI field and no data;
+// acknowledgements have only ACK set.
+type MBusFrame struct {
+	ACK     bool
+	Control byte
+	Address byte
+	CI      byte
+	Data    []byte
+	short   bool
+}
+
+// NewMBusShortFrame creates a short frame
+func NewMBusShortFrame(control, address byte) *MBusFrame {
+	return &MBusFrame{Control: control, Address: address, short: true}
+}
+
+// NewMBusLongFrame creates a control or long frame
+func NewMBusLongFrame(control, address, ci byte, data []byte) *MBusFrame {
+	return &MBusFrame{Control: control, Address: address, CI: ci, Data: data}
+}
+
+// AppendMBusFrame appends frame to dst
+func AppendMBusFrame(dst []byte, frame *MBusFrame) ([]byte, error) {
+	if frame.ACK {
+		return append(dst, mbusACK), nil
+	}
+	if frame.short {
+		return append(dst, mbusShortStart, frame.Control, frame.Address, frame.Control+frame.Address, mbusStop), nil
+	}
+
+	length := 3 + len(frame.Data)
+	if length > 255 {
+		return dst, fmt.Errorf("M-Bus frame data of %d bytes exceeds maximum", len(frame.Data))
+	}
+	dst = append(dst, mbusLongStart, byte(length), byte(length), mbusLongStart, frame.Control, frame.Address, frame.CI)
+	dst = append(dst, frame.Data...)
+	return append(dst, mbusChecksum(dst[len(dst)-length:]), mbusStop), nil
+}
+
+// ParseMBusFrame parses one frame at the start of data and returns the
+// number of bytes it takes up. The frame's data aliases data.
+func ParseMBusFrame(data []byte) (*MBusFrame, int, error) {
+	if len(data) == 0 {
+		return nil, 0, io.ErrUnexpectedEOF
+	}
+
+	switch data[0] {
+	case mbusACK:
+		return &MBusFrame{ACK: true}, 1, nil
+	case mbusShortStart:
+		if len(data) < 5 {
+			return nil, 0, io.ErrUnexpectedEOF
+		}
+		if data[4] != mbusStop {
+			return nil, 0, fmt.Errorf("M-Bus short frame without stop byte")
+		}
+		if mbusChecksum(data[1:3]) != data[3] {
+			return nil, 0, fmt.Errorf("M-Bus checksum mismatch")
+		}
+		return NewMBusShortFrame(data[1], data[2]), 5, nil
+	case mbusLongStart:
+		if len(data) < 4 {
+			return nil, 0, io.ErrUnexpectedEOF
+		}
+		length := int(data[1])
+		if data[2] != data[1] || data[3] != mbusLongStart || length < 3 {
+			return nil, 0, fmt.Errorf("invalid M-Bus long frame header % X", data[:4])
+		}
+		size := length + 6
+		if len(data) < size {
+			return nil, 0, io.ErrUnexpectedEOF
+		}
+		if data[size-1] != mbusStop {
+			return nil, 0, fmt.Errorf("M-Bus long frame without stop byte")
+		}
+		if mbusChecksum(data[4:4+length]) != data[4+length] {
+			return nil, 0, fmt.Errorf("M-Bus checksum mismatch")
+		}
+		return NewMBusLongFrame(data[4], data[5], data[6], data[7:4+length]), size, nil
+	}
+	return nil, 0, fmt.Errorf("invalid M-Bus frame start 0x%02X", data[0])
+}
+
+// ReadMBusFrame reads one frame from r into buffer, which must hold
+// mbusMaxFrameSize bytes
+func ReadMBusFrame(r io.Reader, buffer []byte) (*MBusFrame, error) {
+	if _, err := io.ReadFull(r, buffer[:1]); err != nil {
+		return nil, err
+	}
+
+	var size int
+	switch buffer[0] {
+	case mbusACK:
+		return &MBusFrame{ACK: true}, nil
+	case mbusShortStart:
+		size = 5
+		if _, err := io.ReadFull(r, buffer[1:size]); err != nil {
+			return nil, err
+		}
+	case mbusLongStart:
+		if _, err := io.ReadFull(r, buffer[1:4]); err != nil {
+			return nil, err
+		}
+		size = int(buffer[1]) + 6
+		if _, err := io.ReadFull(r, buffer[4:size]); err != nil {
+			return nil, err
+		}
+	default:
+		return nil, fmt.Errorf("invalid M-Bus frame start 0x%02X", buffer[0])
+	}
+
+	frame, _, err := ParseMBusFrame(buffer[:size])
+	return frame, err
+}
+
+// IsResponse reports whether the frame is a RSP_UD response
+func (f *MBusFrame) IsResponse() bool {
+	return !f.ACK && !f.short && f.Control&mbusControlResponse == MBusRspUD
+}
+
+func (f *MBusFrame) String() string {
+	switch {
+	case f.ACK:
+		return "ACK"
+	case f.short:
+		return fmt.Sprintf("C=0x%02X A=%d", f.Control, f.Address)
+	}
+	return fmt.Sprintf("C=0x%02X A=%d CI=0x%02X % X", f.Control, f.Address, f.CI, f.Data)
+}
+
+func mbusChecksum(data []byte) byte {
+	var sum byte
+	for _, b := range data {
+		sum += b
+	}
+	return sum
+}
@@ -0,0 +1,211 @@
+package protocols
+
+import (
+	"context"
+	"encoding/hex"
+	"fmt"
+	"io"
+	"strings"
+	"sync"
+	"time"
+)
+
+// MBusMaster is the master of a wired M-Bus. Requests are serialized,
+// since the bus carries one transaction at a time.
+type MBusMaster struct {
+	Timeout time.Duration
+	Retries int
+
+	// MaxTelegrams limits the responses read from a meter that splits its
+	// records over several
+	MaxTelegrams int
+
+	mutex  sync.Mutex // serializes transactions
+	port   io.ReadWriteCloser
+	fcb    map[byte]bool // frame count bit of the next request per address
+	buffer [mbusMaxFrameSize]byte
+}
+
+// NewMBusMaster creates a master on an open serial port, usually at 2400
+// baud 8E1
+func NewMBusMaster(port io.ReadWriteCloser, timeout time.Duration) *MBusMaster {
+	return &MBusMaster{
+		Timeout:      timeout,
+		Retries:      2,
+		MaxTelegrams: 8,
+		port:         port,
+		fcb:          make(map[byte]bool),
+	}
+}
+
+// Close closes the serial port
+func (m *MBusMaster) Close() error {
+	return m.port.Close()
+}
+
+// Reset sends SND_NKE to a meter, which resets its frame count bit, and
+// waits for the acknowledgement
+func (m *MBusMaster) Reset(address byte) error {
+	m.mutex.Lock()
+	defer m.mutex.Unlock()
+
+	return m.reset(address, m.Timeout, m.Retries)
+}
+
+// ReadData requests the class 2 data of a meter. Records a meter splits
+// over several responses are requested until it has none left.
+func (m *MBusMaster) ReadData(address byte) (*MBusData, error) {
+	m.mutex.Lock()
+	defer m.mutex.Unlock()
+
+	return m.readData(address)
+}
+
+// ReadSecondary selects a meter by secondary address and reads its data
+func (m *MBusMaster) ReadSecondary(secondary string) (*MBusData, error) {
+	m.mutex.Lock()
+	defer m.mutex.Unlock()
+
+	if err := m.selectSecondary(secondary); err != nil {
+		return nil, err
+	}
+	return m.readData(MBusAddressNetwork)
+}
+
+// SelectSecondary selects the meter with a secondary address, 16 hex
+// digits of identification number, manufacturer, version and medium, so
+// it answers requests to MBusAddressNetwork. An 'F' digit matches any
+// digit.
+func (m *MBusMaster) SelectSecondary(secondary string) error {
+	m.mutex.Lock()
+	defer m.mutex.Unlock()
+
+	return m.selectSecondary(secondary)
+}
+
+// ScanPrimary returns the primary addresses that acknowledge SND_NKE,
+// waiting timeout for each
+func (m *MBusMaster) ScanPrimary(ctx context.Context, timeout time.Duration) ([]byte, error) {
+	m.mutex.Lock()
+	defer m.mutex.Unlock()
+
+	addresses := make([]byte, 0)
+	for address := 0; address <= MBusMaxPrimaryAddress; address++ {
+		if err := ctx.Err(); err != nil {
+			return addresses, err
+		}
+		if m.reset(byte(address), timeout, 0) == nil {
+			addresses = append(addresses, byte(address))
+		}
+	}
+	return addresses, nil
+}
+
+func (m *MBusMaster) reset(address byte, timeout time.Duration, retries int) error {
+	delete(m.fcb, address)
+	response, err := m.transact(NewMBusShortFrame(MBusSndNke, address), timeout, retries)
+	if err != nil {
+		return err
+	}
+	if !response.ACK {
+		return fmt.Errorf("M-Bus address %d answered SND_NKE with %s", address, response)
+	}
+	return nil
+}
+
+func (m *MBusMaster) selectSecondary(secondary string) error {
+	mask, err := parseMBusSecondaryAddress(secondary)
+	if err != nil {
+		return err
+	}
+
+	delete(m.fcb, MBusAddressNetwork)
+	response, err := m.transact(NewMBusLongFrame(MBusSndUD, MBusAddressNetwork, MBusCISelect, mask), m.Timeout, m.Retries)
+	if err != nil {
+		return fmt.Errorf("failed to select M-Bus meter %s: %w", secondary, err)
+	}
+	if !response.ACK {
+		return fmt.Errorf("M-Bus meter %s answered selection with %s", secondary, response)
+	}
+	return nil
+}
+
+func (m *MBusMaster) readData(address byte) (*MBusData, error) {
+	var result *MBusData
+	for telegram := 0; telegram < m.MaxTelegrams; telegram++ {
+		control := byte(MBusReqUD2)
+		if m.fcb[address] {
+			control |= mbusControlFCB
+		}
+
+		response, err := m.transact(NewMBusShortFrame(control, address), m.Timeout, m.Retries)
+		if err != nil {
+			return nil, err
+		}
+		if !response.IsResponse() {
+			return nil, fmt.Errorf("M-Bus address %d answered REQ_UD2 with %s", address, response)
+		}
+		m.fcb[address] = !m.fcb[address]
+
+		data, err := ParseMBusData(response.CI, response.Data)
+		if err != nil {
+			return nil, err
+		}
+		if result == nil {
+			result = data
+		} else {
+			result.Records = append(result.Records, data.Records...)
+			result.ManufacturerData = append(result.ManufacturerData, data.ManufacturerData...)
+			result.MoreRecords = data.MoreRecords
+		}
+		if !data.MoreRecords {
+			return result, nil
+		}
+	}
+	return result, nil
+}
+
+// transact sends a frame and reads the response, sending the frame again
+// when no valid response arrives. Repeated requests keep their frame
+// count bit.
+func (m *MBusMaster) transact(request *MBusFrame, timeout time.Duration, retries int) (*MBusFrame, error) {
+	frame, err := AppendMBusFrame(nil, request)
+	if err != nil {
+		return nil, err
+	}
+
+	for attempt := 0; ; attempt++ {
+		if _, err := m.port.Write(frame); err != nil {
+			return nil, fmt.Errorf("M-Bus write failed: %w", err)
+		}
+
+		if deadliner, ok := m.port.(interface{ SetReadDeadline(time.Time) error }); ok {
+			deadliner.SetReadDeadline(time.Now().Add(timeout))
+		}
+		response, err := ReadMBusFrame(m.port, m.buffer[:])
+		if err == nil {
+			// The receive buffer is reused by the next transaction
+			response.Data = append([]byte(nil), response.Data...)
+			return response, nil
+		}
+		if attempt >= retries {
+			if isTimeoutError(err) {
+				return nil, fmt.Errorf("M-Bus address %d did not respond", request.Address)
+			}
+			return nil, err
+		}
+	}
+}
+
+// parseMBusSecondaryAddress encodes a secondary address as the data of a
+// selection frame, with the identification number and manufacturer
+// little endian
+func parseMBusSecondaryAddress(secondary string) ([]byte, error) {
+	mask, err := hex.DecodeString(strings.ToUpper(secondary))
+	if err != nil || len(mask) != 8 {
+		return nil, fmt.Errorf("invalid M-Bus secondary address %q", secondary)
+	}
+	mask[0], mask[1], mask[2], mask[3] = mask[3], mask[2], mask[1], mask[0]
+	mask[4], mask[5] = mask[5], mask[4]
+	return mask, nil
+}
@@ -0,0 +1,369 @@
+package protocols
+
+import (
+	"context"
+	"encoding/hex"
+	"io"
+	"net"
+	"sync"
+	"testing"
+	"time"
+
+	"github.com/stretchr/testify/assert"
+	"go.uber.org/zap"
+)
+
+// mbusTestHeader is the long header of meter 12345678 made by KAM, a heat
+// meter of version 0x1B
+const mbusTestHeader = "78563412" + "2D2C" + "1B" + "04" + "2A" + "00" + "0000"
+
+// mbusTestRecords holds one record per line
+const mbusTestRecords = "" +
+	"0406" + "39300000" +   // energy 12345 kWh
+	"0413" + "40E20100" +   // volume 123.456 m³
+	"025B" + "4600" +       // flow temperature 70 °C
+	"025F" + "2800" +       // return temperature 40 °C
+	"0261" + "B80B" +       // temperature difference 30.00 K
+	"042D" + "96000000" +   // power 15 kW
+	"0C78" + "21436587" +   // fabrication number 87654321
+	"4406" + "10270000" +   // energy 10000 kWh in storage 1
+	"426C" + "1F3C" +       // date 2024-12-31 in storage 1
+	"841006" + "F4010000" + // energy 500 kWh in tariff 1
+	"0DFD0E" + "03322E31" + // firmware version "1.2"
+	"046D" + "2D0D0F36"     // date and time 2024-06-15 13:45
+
+func mbusTestBytes(text string) []byte {
+	data, err := hex.DecodeString(text)
+	if err != nil {
+		panic(err)
+	}
+	return data
+}
+
+func TestMBusFrame_RoundTrip(t *testing.T) {
+	frame, err := AppendMBusFrame(nil, NewMBusShortFrame(MBusReqUD2|mbusControlFCB, 5))
+	assert.NoError(t, err)
+	assert.Equal(t, []byte{0x10, 0x7B, 0x05, 0x80, 0x16}, frame)
+
+	frame, err = AppendMBusFrame(nil, NewMBusLongFrame(MBusRspUD, 5, MBusCIResponseShort, []byte{0x2A, 0x00, 0x00, 0x00}))
+	assert.NoError(t, err)
+	assert.Equal(t, []byte{0x68, 0x07, 0x07, 0x68, 0x08, 0x05, 0x7A, 0x2A, 0x00, 0x00, 0x00, 0xB1, 0x16}, frame)
+
+	parsed, n, err := ParseMBusFrame(append(frame, 0xE5))
+	assert.NoError(t, err)
+	assert.Equal(t, len(frame), n)
+	assert.True(t, parsed.IsResponse())
+	assert.Equal(t, byte(MBusCIResponseShort), parsed.CI)
+	assert.Equal(t, []byte{0x2A, 0x00, 0x00, 0x00}, parsed.Data)
+
+	parsed, n, err = ParseMBusFrame([]byte{0xE5})
+	assert.NoError(t, err)
+	assert.Equal(t, 1, n)
+	assert.True(t, parsed.ACK)
+
+	frame[11]++
+	_, _, err = ParseMBusFrame(frame)
+	assert.Error(t, err)
+	_, _, err = ParseMBusFrame(frame[:8])
+	assert.ErrorIs(t, err, io.ErrUnexpectedEOF)
+	_, _, err = ParseMBusFrame([]byte{0x68, 0x07, 0x08, 0x68})
+	assert.Error(t, err)
+}
+
+func TestParseMBusData(t *testing.T) {
+	data, err := ParseMBusData(MBusCIResponseLong, mbusTestBytes(mbusTestHeader+mbusTestRecords+"0F0102"))
+	assert.NoError(t, err)
+
+	assert.Equal(t, uint32(12345678), data.ID)
+	assert.Equal(t, "KAM", data.Manufacturer)
+	assert.Equal(t, byte(0x1B), data.Version)
+	assert.Equal(t, MBusMediumHeatOutlet, data.Medium)
+	assert.Equal(t, "Heat (outlet)", data.Medium.String())
+	assert.Equal(t, byte(0x2A), data.AccessNumber)
+	assert.Equal(t, "123456782D2C1B04", data.SecondaryAddress())
+	assert.Equal(t, []byte{0x01, 0x02}, data.ManufacturerData)
+	assert.False(t, data.MoreRecords)
+
+	if !assert.Len(t, data.Records, 12) {
+		return
+	}
+	expected := []struct {
+		quantity string
+		unit     string
+		value    interface{}
+	}{
+		{"energy", "Wh", 12345000.0},
+		{"volume", "m³", 123.456},
+		{"flow_temperature", "°C", 70.0},
+		{"return_temperature", "°C", 40.0},
+		{"temperature_difference", "K", 30.0},
+		{"power", "W", 15000.0},
+		{"fabrication_number", "", int64(87654321)},
+		{"energy", "Wh", 10000000.0},
+		{"date", "", time.Date(2024, 12, 31, 0, 0, 0, 0, time.Local)},
+		{"energy", "Wh", 500000.0},
+		{"firmware_version", "", "1.2"},
+		{"date_time", "", time.Date(2024, 6, 15, 13, 45, 0, 0, time.Local)},
+	}
+	for i, record := range data.Records {
+		assert.Equal(t, expected[i].quantity, record.Quantity, i)
+		assert.Equal(t, expected[i].unit, record.Unit, i)
+		if value, ok := expected[i].value.(float64); ok {
+			assert.InDelta(t, value, record.Value, 1e-9, i)
+		} else {
+			assert.Equal(t, expected[i].value, record.Value, i)
+		}
+	}
+	assert.Equal(t, uint64(1), data.Records[7].StorageNumber)
+	assert.Equal(t, uint32(1), data.Records[9].Tariff)
+	assert.Equal(t, DataTypeFloat64, data.Records[0].DataType())
+	assert.Equal(t, DataTypeInt64, data.Records[6].DataType())
+
+	assert.Equal(t, data.Records[0].Value, findMBusRecord(data.Records, "energy").Value)
+	assert.Equal(t, data.Records[7].Value, findMBusRecord(data.Records, "energy@1").Value)
+	assert.Equal(t, data.Records[9].Value, findMBusRecord(data.Records, "9").Value)
+	assert.Nil(t, findMBusRecord(data.Records, "mass"))
+	assert.Nil(t, findMBusRecord(data.Records, "12"))
+
+	// Records of a short header response, with fillers and a record
+	// continued in the next response
+	data, err = ParseMBusData(MBusCIResponseShort, mbusTestBytes("2A000000"+"2F2F"+"01FD74"+"B4"+"1F"))
+	assert.NoError(t, err)
+	assert.Equal(t, uint32(0), data.ID)
+	assert.True(t, data.MoreRecords)
+	if assert.Len(t, data.Records, 1) {
+		assert.Equal(t, "remaining_battery", data.Records[0].Quantity)
+		assert.InDelta(t, -76.0, data.Records[0].Value, 0)
+	}
+
+	for _, invalid := range []string{
+		mbusTestHeader[:20],
+		mbusTestHeader[:20] + "0005",    // encrypted
+		mbusTestHeader + "04063930",     // truncated value
+		mbusTestHeader + "0C782143A587", // invalid BCD
+		mbusTestHeader + "04EF00000000", // reserved VIF
+	} {
+		_, err := ParseMBusData(MBusCIResponseLong, mbusTestBytes(invalid))
+		assert.Error(t, err, invalid)
+	}
+	_, err = ParseMBusData(0x51, nil)
+	assert.Error(t, err)
+}
+
+func TestMBusValueInformation(t *testing.T) {
+	value, err := mbusBCD([]byte{0x21, 0xF3})
+	assert.NoError(t, err)
+	assert.Equal(t, int64(-321), value)
+
+	// MWh from the second extension table, and a multiplicative VIFE
+	info, err := mbusValueInformation(0xFB, []byte{0x01})
+	assert.NoError(t, err)
+	assert.Equal(t, mbusValueInfo{quantity: "energy", unit: "Wh", exponent: 6}, *info)
+	info, err = mbusValueInformation(0x93, []byte{0x7D})
+	assert.NoError(t, err)
+	assert.Equal(t, 0, info.exponent)
+
+	info, err = mbusValueInformation(0xFD, []byte{0x48})
+	assert.NoError(t, err)
+	assert.Equal(t, mbusValueInfo{quantity: "voltage", unit: "V", exponent: -1}, *info)
+
+	assert.Equal(t, "KAM", mbusManufacturer(mbusManufacturerCode("KAM")))
+	mask, err := parseMBusSecondaryAddress("12345678FFFFFF07")
+	assert.NoError(t, err)
+	assert.Equal(t, []byte{0x78, 0x56, 0x34, 0x12, 0xFF, 0xFF, 0xFF, 0x07}, mask)
+	_, err = parseMBusSecondaryAddress("1234")
+	assert.Error(t, err)
+}
+
+// mbusTestMeter answers a master on the other end of a pipe
+type mbusTestMeter struct {
+	conn      net.Conn
+	primary   byte
+	secondary []byte
+	telegrams [][]byte
+
+	mutex    sync.Mutex
+	controls []byte
+	drop     int
+	next     int
+	selected bool
+}
+
+func newMBusTestMeter(t *testing.T, primary byte, telegrams ...[]byte) (*mbusTestMeter, net.Conn) {
+	master, meter := net.Pipe()
+	m := &mbusTestMeter{
+		conn:      meter,
+		primary:   primary,
+		secondary: mbusTestBytes("78563412" + "2D2C" + "1B" + "04"),
+		telegrams: telegrams,
+	}
+	go m.serve()
+	t.Cleanup(func() { meter.Close() })
+	return m, master
+}
+
+func (m *mbusTestMeter) serve() {
+	buffer := make([]byte, mbusMaxFrameSize)
+	for {
+		frame, err := ReadMBusFrame(m.conn, buffer)
+		if err != nil {
+			return
+		}
+		if response := m.handle(frame); response != nil {
+			encoded, _ := AppendMBusFrame(nil, response)
+			m.conn.Write(encoded)
+		}
+	}
+}
+
+func (m *mbusTestMeter) handle(frame *MBusFrame) *MBusFrame {
+	m.mutex.Lock()
+	defer m.mutex.Unlock()
+
+	addressed := frame.Address == m.primary || (frame.Address == MBusAddressNetwork && m.selected)
+	switch {
+	case frame.Control == MBusSndNke && addressed:
+		m.next = 0
+		return &MBusFrame{ACK: true}
+	case frame.Control == MBusSndUD && frame.CI == MBusCISelect && frame.Address == MBusAddressNetwork:
+		m.selected = len(frame.Data) == len(m.secondary)
+		for i := 0; m.selected && i < len(frame.Data); i++ {
+			for _, shift := range []uint{0, 4} {
+				nibble := frame.Data[i] >> shift & 0x0F
+				if nibble != 0x0F && nibble != m.secondary[i]>>shift&0x0F {
+					m.selected = false
+				}
+			}
+		}
+		if m.selected {
+			return &MBusFrame{ACK: true}
+		}
+	case frame.Control&^mbusControlFCB == MBusReqUD2 && addressed:
+		m.controls = append(m.controls, frame.Control)
+		if m.drop > 0 {
+			m.drop--
+			return nil
+		}
+		telegram := m.telegrams[m.next%len(m.telegrams)]
+		m.next++
+		return NewMBusLongFrame(MBusRspUD, frame.Address, MBusCIResponseLong, telegram)
+	}
+	return nil
+}
+
+func TestMBusMaster(t *testing.T) {
+	first := mbusTestBytes(mbusTestHeader + "0406" + "39300000" + "1F")
+	second := mbusTestBytes(mbusTestHeader + "0413" + "40E20100")
+	meter, port := newMBusTestMeter(t, 5, first, second)
+	master := NewMBusMaster(port, 100*time.Millisecond)
+	defer master.Close()
+
+	assert.NoError(t, master.Reset(5))
+	assert.Error(t, master.Reset(6))
+
+	// The first request is dropped and repeated with the same frame
+	// count bit; the records of both responses are returned together
+	meter.drop = 1
+	data, err := master.ReadData(5)
+	assert.NoError(t, err)
+	if assert.NotNil(t, data) {
+		assert.Len(t, data.Records, 2)
+		assert.False(t, data.MoreRecords)
+	}
+	assert.Equal(t, []byte{0x5B, 0x5B, 0x7B}, meter.controls)
+
+	_, err = master.ReadData(5)
+	assert.NoError(t, err)
+	assert.Equal(t, byte(0x5B), meter.controls[3])
+
+	data, err = master.ReadSecondary("12345678FFFFFFFF")
+	assert.NoError(t, err)
+	if assert.NotNil(t, data) {
+		assert.Equal(t, "KAM", data.Manufacturer)
+	}
+	assert.Error(t, master.SelectSecondary("87654321FFFFFFFF"))
+
+	addresses, err := master.ScanPrimary(context.Background(), 10*time.Millisecond)
+	assert.NoError(t, err)
+	assert.Equal(t, []byte{5}, addresses)
+
+	ctx, cancel := context.WithCancel(context.Background())
+	cancel()
+	_, err = master.ScanPrimary(ctx, 10*time.Millisecond)
+	assert.ErrorIs(t, err, context.Canceled)
+}
+
+func TestMBusHandler(t *testing.T) {
+	_, port := newMBusTestMeter(t, 5, mbusTestBytes(mbusTestHeader+mbusTestRecords))
+	handler := NewMBusHandler(zap.NewNop()).(*MBusHandler)
+	handler.config.Timeout = 100 * time.Millisecond
+	handler.config.ScanTimeout = 10 * time.Millisecond
+	opened := 0
+	handler.openPort = func(path string, baudRate int) (io.ReadWriteCloser, error) {
+		assert.Equal(t, "/dev/ttyUSB0", path)
+		assert.Equal(t, 2400, baudRate)
+		opened++
+		return port, nil
+	}
+
+	heat := &Device{ID: "heat", Protocol: "mbus", Address: "/dev/ttyUSB0", Config: map[string]interface{}{"primary_address": 5}}
+	secondary := &Device{ID: "secondary", Protocol: "mbus", Address: "/dev/ttyUSB0", Config: map[string]interface{}{"secondary_address": "12345678FFFFFFFF"}}
+	assert.Error(t, handler.Connect(&Device{Address: "/dev/ttyUSB0", Config: map[string]interface{}{}}))
+	assert.NoError(t, handler.Connect(heat))
+	assert.NoError(t, handler.Connect(secondary))
+	assert.Equal(t, 1, opened)
+
+	value, err := handler.ReadTag(heat, &Tag{Address: "flow_temperature"})
+	assert.NoError(t, err)
+	assert.Equal(t, 70.0, value)
+	_, err = handler.ReadTag(heat, &Tag{Address: "mass_flow"})
+	assert.Error(t, err)
+	_, err = handler.ReadTag(heat, &Tag{Address: "Energy!"})
+	assert.Error(t, err)
+
+	results, err := handler.ReadMultipleTags(secondary, []*Tag{
+		{ID: "energy", Address: "energy"},
+		{ID: "billing", Address: "energy@1"},
+		{ID: "serial", Address: "fabrication_number"},
+		{ID: "missing", Address: "mass"},
+	})
+	assert.NoError(t, err)
+	assert.Equal(t, map[string]interface{}{"energy": 12345000.0, "billing": 10000000.0, "serial": int64(87654321)}, results)
+
+	tags, err := handler.ReadRecords(heat)
+	assert.NoError(t, err)
+	if assert.Len(t, tags, 12) {
+		assert.Equal(t, "energy", tags[0].Address)
+		assert.Equal(t, "Wh", tags[0].Unit)
+		assert.Equal(t, "energy@1", tags[7].Address)
+		assert.Equal(t, "9", tags[9].Address)
+		assert.Equal(t, string(DataTypeString), tags[10].DataType)
+	}
+
+	info, err := handler.GetDeviceInfo(heat)
+	assert.NoError(t, err)
+	assert.Equal(t, "KAM", info.Vendor)
+	assert.Equal(t, "12345678", info.SerialNumber)
+	assert.Equal(t, "123456782D2C1B04", info.CustomInfo["secondary_address"])
+
+	assert.NoError(t, handler.Ping(heat))
+	assert.NoError(t, handler.Ping(secondary))
+	assert.Error(t, handler.WriteTag(heat, &Tag{Address: "energy"}, 0))
+
+	devices, err := handler.DiscoverDevices(context.Background(), "/dev/ttyUSB0")
+	assert.NoError(t, err)
+	if assert.Len(t, devices, 1) {
+		assert.Equal(t, 5, devices[0].Config["primary_address"])
+	}
+	assert.Equal(t, 1, opened)
+
+	diagnostics, err := handler.GetDiagnostics(heat)
+	assert.NoError(t, err)
+	assert.Equal(t, uint64(0), diagnostics.ErrorCount)
+
+	// The port stays open until the last meter on it disconnects
+	assert.NoError(t, handler.Disconnect(heat))
+	assert.True(t, handler.IsConnected(secondary))
+	assert.NoError(t, handler.Disconnect(secondary))
+	assert.False(t, handler.IsConnected(secondary))
+}
@@ -12,6 +12,8 @@ import (
 
 	"github.com/goburrow/modbus"
 	"go.uber.org/zap"
+
+	"github.com/bifrost/go-gateway/internal/tlsconfig"
 )
 
 // ModbusHandler implements the ProtocolHandler interface for Modbus TCP/RTU
@@ -24,26 +26,37 @@ type ModbusHandler struct {
 // ModbusConnection represents a Modbus client connection
 type ModbusConnection struct {
 	client      modbus.Client
-	handler     *modbus.TCPClientHandler
+	handler     *ModbusTCPTransport
 	lastUsed    time.Time
 	deviceID    string
 	isConnected bool
 	mutex       sync.RWMutex
 
+	// Capture file of the connection's traffic, when configured
+	capture *CaptureWriter
+
 	// Connection pooling
 	inUse     bool
 	createdAt time.Time
+
+	// Health monitoring, when enabled in the handler config
+	health     *ModbusHealthMonitor
+	stopHealth context.CancelFunc
 }
 
 // ModbusConfig holds Modbus-specific configuration
 type ModbusConfig struct {
-	DefaultTimeout    time.Duration `yaml:"default_timeout"`
-	DefaultUnitID     byte          `yaml:"default_unit_id"`
-	MaxConnections    int           `yaml:"max_connections"`
-	ConnectionTimeout time.Duration `yaml:"connection_timeout"`
-	ReadTimeout       time.Duration `yaml:"read_timeout"`
-	WriteTimeout      time.Duration `yaml:"write_timeout"`
-	EnableKeepAlive   bool          `yaml:"enable_keep_alive"`
+	DefaultTimeout    time.Duration      `yaml:"default_timeout"`
+	DefaultUnitID     byte               `yaml:"default_unit_id"`
+	MaxConnections    int                `yaml:"max_connections"`
+	ConnectionTimeout time.Duration      `yaml:"connection_timeout"`
+	ReadTimeout       time.Duration      `yaml:"read_timeout"`
+	WriteTimeout      time.Duration      `yaml:"write_timeout"`
+	EnableKeepAlive   bool               `yaml:"enable_keep_alive"`
+	MaxInFlight       int                `yaml:"max_in_flight"`
+	DefaultWordOrder  WordOrder          `yaml:"default_word_order"`
+	RetryPolicy       *RetryPolicy       `yaml:"retry_policy"` // nil disables retries
+	HealthCheck       *HealthCheckConfig `yaml:"health_check"` // nil disables health monitoring
 }
 
 // ModbusAddress represents parsed Modbus address information
@@ -69,6 +82,23 @@ const (
 	WriteSingleRegister    ModbusFunctionCode = 6
 	WriteMultipleCoils     ModbusFunctionCode = 15
 	WriteMultipleRegisters ModbusFunctionCode = 16
+
+	// Serial line diagnostic functions
+	ReadExceptionStatus   ModbusFunctionCode = 7
+	SerialLineDiagnostics ModbusFunctionCode = 8
+	GetCommEventCounter   ModbusFunctionCode = 11
+	GetCommEventLog       ModbusFunctionCode = 12
+
+	// File record functions
+	ReadFileRecord  ModbusFunctionCode = 20
+	WriteFileRecord ModbusFunctionCode = 21
+
+	// Combined read/write functions
+	MaskWriteRegister          ModbusFunctionCode = 22
+	ReadWriteMultipleRegisters ModbusFunctionCode = 23
+
+	// Encapsulated interface transport (MEI)
+	EncapsulatedInterfaceTransport ModbusFunctionCode = 43
 )
 
 // NewModbusHandler creates a new Modbus protocol handler
@@ -83,6 +113,8 @@ func NewModbusHandler(logger *zap.Logger) ProtocolHandler {
 			ReadTimeout:       5 * time.Second,
 			WriteTimeout:      5 * time.Second,
 			EnableKeepAlive:   true,
+			MaxInFlight:       defaultMaxInFlight,
+			DefaultWordOrder:  WordOrderABCD,
 		},
 	}
 }
@@ -105,11 +137,41 @@ func (m *ModbusHandler) Connect(device *Device) error {
 	}
 
 	// Create new connection
-	handler := modbus.NewTCPClientHandler(fmt.Sprintf("%s:%d", device.Address, device.Port))
+	handler := NewModbusTCPTransport(fmt.Sprintf("%s:%d", device.Address, device.Port), m.logger)
 	handler.Timeout = m.config.DefaultTimeout
 	handler.SlaveId = m.getUnitID(device)
+	handler.MaxInFlight = m.config.MaxInFlight
+	handler.RetryPolicy = m.config.RetryPolicy
+
+	// Modbus/TCP Security is configured by the "tls" entry of the device
+	// configuration
+	if values, ok := device.Config["tls"].(map[string]interface{}); ok {
+		tlsConfig, err := tlsconfig.FromMap(values)
+		if err != nil {
+			return err
+		}
+		if tlsConfig.Enabled {
+			loader, err := tlsconfig.NewLoader(tlsConfig, m.logger)
+			if err != nil {
+				return err
+			}
+			handler.TLSConfig = loader.ClientConfig()
+		}
+	}
+
+	// Traffic is recorded to the file set by the "capture" entry
+	capture, err := openDeviceCapture(device)
+	if err != nil {
+		return err
+	}
+	if capture != nil {
+		handler.Recorder = capture
+	}
 
 	if err := handler.Connect(); err != nil {
+		if capture != nil {
+			capture.Close()
+		}
 		return fmt.Errorf("failed to connect to Modbus device: %w", err)
 	}
 
@@ -121,9 +183,17 @@ func (m *ModbusHandler) Connect(device *Device) error {
 		lastUsed:    time.Now(),
 		deviceID:    device.ID,
 		isConnected: true,
+		capture:     capture,
 		createdAt:   time.Now(),
 	}
 
+	if m.config.HealthCheck != nil {
+		ctx, cancel := context.WithCancel(context.Background())
+		conn.health = NewModbusHealthMonitor(handler, m.config.HealthCheck, m.logger)
+		conn.stopHealth = cancel
+		go conn.health.Run(ctx)
+	}
+
 	m.connections.Store(connectionKey, conn)
 	device.ConnectionID = connectionKey
 
@@ -151,9 +221,16 @@ func (m *ModbusHandler) Disconnect(device *Device) error {
 	conn.mutex.Lock()
 	defer conn.mutex.Unlock()
 
+	if conn.stopHealth != nil {
+		conn.stopHealth()
+	}
+
 	if conn.handler != nil {
 		conn.handler.Close()
 	}
+	if conn.capture != nil {
+		conn.capture.Close()
+	}
 
 	conn.isConnected = false
 	m.connections.Delete(device.ConnectionID)
@@ -194,6 +271,9 @@ func (m *ModbusHandler) ReadTag(device *Device, tag *Tag) (interface{}, error) {
 	if err != nil {
 		return nil, fmt.Errorf("invalid Modbus address %s: %w", tag.Address, err)
 	}
+	if err := m.applyRegisterCount(addr, tag); err != nil {
+		return nil, err
+	}
 
 	// Update last used time
 	conn.lastUsed = time.Now()
@@ -234,7 +314,7 @@ func (m *ModbusHandler) ReadTag(device *Device, tag *Tag) (interface{}, error) {
 	}
 
 	// Convert binary result to appropriate data type
-	return m.convertFromModbus(result, tag.DataType, addr.FunctionCode)
+	return m.convertTagFromModbus(result, tag, addr.FunctionCode)
 }
 
 // WriteTag writes a value to a Modbus device
@@ -270,19 +350,17 @@ func (m *ModbusHandler) WriteTag(device *Device, tag *Tag, value interface{}) er
 		}
 		_, err = conn.client.WriteSingleCoil(addr.Address, coilValue)
 
-	case WriteSingleRegister:
-		regValue, err := m.convertToModbusRegister(value, tag.DataType)
+	case WriteSingleRegister, WriteMultipleRegisters:
+		var regValues []byte
+		regValues, err = m.convertToModbusRegisters(value, tag)
 		if err != nil {
 			return err
 		}
-		_, err = conn.client.WriteSingleRegister(addr.Address, regValue)
-
-	case WriteMultipleRegisters:
-		regValues, err := m.convertToModbusRegisters(value, tag.DataType, addr.Count)
-		if err != nil {
-			return err
+		if len(regValues) == 2 {
+			_, err = conn.client.WriteSingleRegister(addr.Address, binary.BigEndian.Uint16(regValues))
+		} else {
+			_, err = conn.client.WriteMultipleRegisters(addr.Address, uint16(len(regValues)/2), regValues)
 		}
-		_, err = conn.client.WriteMultipleRegisters(addr.Address, addr.Count, regValues)
 
 	default:
 		return fmt.Errorf("unsupported write function code: %d", addr.FunctionCode)
@@ -303,73 +381,88 @@ func (m *ModbusHandler) ReadMultipleTags(device *Device, tags []*Tag) (map[strin
 	}
 
 	results := make(map[string]interface{})
+	var resultsMutex sync.Mutex
 
 	// Group tags by function code and consecutive addresses for batch reading
 	batches := m.groupTagsForBatchRead(tags)
 
-	conn.mutex.Lock()
-	defer conn.mutex.Unlock()
+	// Batches are issued concurrently; the transport pipelines them over the
+	// single connection and matches responses by transaction ID
+	conn.mutex.RLock()
+	defer conn.mutex.RUnlock()
 
+	var wg sync.WaitGroup
 	for _, batch := range batches {
-		batchResults, err := m.readTagBatch(conn, batch)
-		if err != nil {
-			// If batch read fails, fall back to individual reads
-			for _, tag := range batch {
-				if value, readErr := m.readSingleTag(conn, tag); readErr == nil {
-					results[tag.ID] = value
+		wg.Add(1)
+		go func(batch []*Tag) {
+			defer wg.Done()
+
+			batchResults, err := m.readTagBatch(conn, batch)
+			if err != nil {
+				// If batch read fails, fall back to individual reads
+				batchResults = make(map[string]interface{})
+				for _, tag := range batch {
+					if value, readErr := m.readSingleTag(conn, tag); readErr == nil {
+						batchResults[tag.ID] = value
+					}
 				}
 			}
-		} else {
+
+			resultsMutex.Lock()
 			for tagID, value := range batchResults {
 				results[tagID] = value
 			}
-		}
+			resultsMutex.Unlock()
+		}(batch)
 	}
+	wg.Wait()
 
 	return results, nil
 }
 
 // DiscoverDevices scans a network range for Modbus devices
 func (m *ModbusHandler) DiscoverDevices(ctx context.Context, networkRange string) ([]*Device, error) {
-	devices := make([]*Device, 0)
-
-	// Parse network range (e.g., "192.168.1.0/24")
-	_, network, err := net.ParseCIDR(networkRange)
-	if err != nil {
-		return devices, fmt.Errorf("invalid network range: %w", err)
-	}
-
 	// Common Modbus ports to scan
-	ports := []int{502, 503, 10502}
-
-	// Scan network
-	for ip := network.IP.Mask(network.Mask); network.Contains(ip); m.incrementIP(ip) {
-		for _, port := range ports {
-			select {
-			case <-ctx.Done():
-				return devices, ctx.Err()
-			default:
-				if device := m.probeModbusDevice(ctx, ip.String(), port); device != nil {
-					devices = append(devices, device)
-				}
-			}
-		}
-	}
+	discovered, err := m.ScanNetwork(ctx, networkRange, &DiscoveryOptions{Ports: []int{502, 503, 10502}})
 
-	return devices, nil
+	devices := make([]*Device, 0, len(discovered))
+	for _, d := range discovered {
+		devices = append(devices, d.Device())
+	}
+	return devices, err
 }
 
 // GetDeviceInfo retrieves detailed information about a Modbus device
 func (m *ModbusHandler) GetDeviceInfo(device *Device) (*DeviceInfo, error) {
-	// Modbus doesn't have built-in device identification
-	// This would need to be implemented based on specific device protocols
-	return &DeviceInfo{
+	info := &DeviceInfo{
 		Vendor:         "Unknown",
 		Model:          "Modbus Device",
 		Capabilities:   []string{"modbus-tcp", "modbus-rtu"},
 		MaxConnections: 1, // Most Modbus devices support single connection
 		SupportedRates: []int{9600, 19200, 38400, 57600, 115200},
-	}, nil
+		CustomInfo:     make(map[string]string),
+	}
+
+	// Device identification (0x2B/0x0E) is optional; keep the generic
+	// description for devices that are not connected or don't support it
+	if !m.IsConnected(device) {
+		return info, nil
+	}
+
+	identification, err := m.ReadDeviceIdentification(device, DeviceIDRegular)
+	if err != nil {
+		identification, err = m.ReadDeviceIdentification(device, DeviceIDBasic)
+	}
+	if err != nil {
+		m.logger.Debug("Device identification not available",
+			zap.String("device_id", device.ID),
+			zap.Error(err),
+		)
+		return info, nil
+	}
+
+	identification.applyTo(info)
+	return info, nil
 }
 
 // GetSupportedDataTypes returns the data types supported by Modbus
@@ -381,6 +474,10 @@ func (m *ModbusHandler) GetSupportedDataTypes() []string {
 		string(DataTypeInt32),
 		string(DataTypeUInt32),
 		string(DataTypeFloat32),
+		string(DataTypeInt64),
+		string(DataTypeUInt64),
+		string(DataTypeFloat64),
+		string(DataTypeString),
 	}
 }
 
@@ -420,12 +517,27 @@ func (m *ModbusHandler) GetDiagnostics(device *Device) (*Diagnostics, error) {
 	conn.mutex.RLock()
 	defer conn.mutex.RUnlock()
 
-	return &Diagnostics{
+	diagnostics := &Diagnostics{
 		IsHealthy:         conn.isConnected,
 		LastCommunication: conn.lastUsed,
 		ConnectionUptime:  time.Since(conn.createdAt),
-		// Additional Modbus-specific diagnostics would go here
-	}, nil
+		ProtocolDiagnostics: map[string]interface{}{
+			"in_flight":           conn.handler.InFlight(),
+			"retries":             conn.handler.Retries(),
+			"unmatched_responses": conn.handler.UnmatchedResponses(),
+			"units":               conn.handler.Stats().Units(),
+		},
+	}
+
+	if conn.health != nil {
+		state := conn.health.State()
+		diagnostics.IsHealthy = conn.isConnected && state == ConnectionStateConnected
+		protocolDiagnostics := diagnostics.ProtocolDiagnostics.(map[string]interface{})
+		protocolDiagnostics["connection_state"] = state.String()
+		protocolDiagnostics["reconnects"] = conn.health.Reconnects()
+	}
+
+	return diagnostics, nil
 }
 
 // Helper methods
@@ -540,88 +652,71 @@ func (m *ModbusHandler) parseAddressForWrite(address string) (*ModbusAddress, er
 }
 
 func (m *ModbusHandler) convertFromModbus(data []byte, dataType string, funcCode ModbusFunctionCode) (interface{}, error) {
-	switch DataType(dataType) {
-	case DataTypeBool:
-		if funcCode == ReadCoils || funcCode == ReadDiscreteInputs {
-			return len(data) > 0 && data[0]&0x01 != 0, nil
-		}
-		return binary.BigEndian.Uint16(data) != 0, nil
-
-	case DataTypeInt16:
-		if len(data) < 2 {
-			return nil, fmt.Errorf("insufficient data for int16")
-		}
-		return int16(binary.BigEndian.Uint16(data)), nil
-
-	case DataTypeUInt16:
-		if len(data) < 2 {
-			return nil, fmt.Errorf("insufficient data for uint16")
-		}
-		return binary.BigEndian.Uint16(data), nil
-
-	case DataTypeInt32:
-		if len(data) < 4 {
-			return nil, fmt.Errorf("insufficient data for int32")
-		}
-		return int32(binary.BigEndian.Uint32(data)), nil
+	return m.convertFromModbusOrdered(data, dataType, funcCode, m.config.DefaultWordOrder)
+}
 
-	case DataTypeUInt32:
-		if len(data) < 4 {
-			return nil, fmt.Errorf("insufficient data for uint32")
+func (m *ModbusHandler) convertFromModbusOrdered(data []byte, dataType string, funcCode ModbusFunctionCode, order WordOrder) (interface{}, error) {
+	if funcCode == ReadCoils || funcCode == ReadDiscreteInputs {
+		if DataType(dataType) != DataTypeBool {
+			return nil, fmt.Errorf("unsupported data type for coils: %s", dataType)
 		}
-		return binary.BigEndian.Uint32(data), nil
+		return len(data) > 0 && data[0]&0x01 != 0, nil
+	}
 
-	case DataTypeFloat32:
-		if len(data) < 4 {
-			return nil, fmt.Errorf("insufficient data for float32")
-		}
-		// Convert to IEEE 754 float32
-		bits := binary.BigEndian.Uint32(data)
-		return float32(bits), nil
+	return DecodeRegisterValue(data, DataType(dataType), order)
+}
 
-	default:
-		return nil, fmt.Errorf("unsupported data type: %s", dataType)
+// convertTagFromModbus converts a read result using the tag's word order
+func (m *ModbusHandler) convertTagFromModbus(data []byte, tag *Tag, funcCode ModbusFunctionCode) (interface{}, error) {
+	order, err := m.wordOrder(tag)
+	if err != nil {
+		return nil, err
 	}
+	return m.convertFromModbusOrdered(data, tag.DataType, funcCode, order)
 }
 
-func (m *ModbusHandler) convertToModbusRegister(value interface{}, dataType string) (uint16, error) {
-	switch DataType(dataType) {
-	case DataTypeBool:
-		if boolVal, ok := value.(bool); ok {
-			if boolVal {
-				return 1, nil
-			}
-			return 0, nil
-		}
-		return 0, fmt.Errorf("expected boolean value")
+// convertToModbusRegisters encodes value as register bytes for tag
+func (m *ModbusHandler) convertToModbusRegisters(value interface{}, tag *Tag) ([]byte, error) {
+	order, err := m.wordOrder(tag)
+	if err != nil {
+		return nil, err
+	}
+	return EncodeRegisterValue(value, DataType(tag.DataType), order, tagStringLength(tag))
+}
 
-	case DataTypeUInt16:
-		if intVal, ok := value.(int); ok {
-			return uint16(intVal), nil
-		}
-		if uintVal, ok := value.(uint16); ok {
-			return uintVal, nil
-		}
-		return 0, fmt.Errorf("expected uint16 value")
+// applyRegisterCount sizes register reads to cover the tag's data type
+func (m *ModbusHandler) applyRegisterCount(addr *ModbusAddress, tag *Tag) error {
+	if addr.FunctionCode != ReadHoldingRegisters && addr.FunctionCode != ReadInputRegisters {
+		return nil
+	}
 
-	case DataTypeInt16:
-		if intVal, ok := value.(int); ok {
-			return uint16(int16(intVal)), nil
-		}
-		if int16Val, ok := value.(int16); ok {
-			return uint16(int16Val), nil
-		}
-		return 0, fmt.Errorf("expected int16 value")
+	count, err := RegisterCount(DataType(tag.DataType), tagStringLength(tag))
+	if err != nil {
+		return fmt.Errorf("tag %s: %w", tag.ID, err)
+	}
+	addr.Count = count
+	return nil
+}
 
-	default:
-		return 0, fmt.Errorf("unsupported data type for single register: %s", dataType)
+// wordOrder returns the tag's "word_order" protocol setting, falling back
+// to the handler default
+func (m *ModbusHandler) wordOrder(tag *Tag) (WordOrder, error) {
+	if order, ok := tag.ProtocolConfig["word_order"].(string); ok {
+		return ParseWordOrder(order)
 	}
+	return m.config.DefaultWordOrder, nil
 }
 
-func (m *ModbusHandler) convertToModbusRegisters(value interface{}, dataType string, count uint16) ([]byte, error) {
-	// Implementation for multi-register values (int32, uint32, float32, etc.)
-	// This would handle the conversion of larger data types to byte arrays
-	return nil, fmt.Errorf("multi-register conversion not implemented")
+// tagStringLength returns the tag's "length" protocol setting, used to size
+// string tags
+func tagStringLength(tag *Tag) int {
+	switch length := tag.ProtocolConfig["length"].(type) {
+	case int:
+		return length
+	case float64: // Decoded from JSON
+		return int(length)
+	}
+	return 0
 }
 
 func (m *ModbusHandler) groupTagsForBatchRead(tags []*Tag) [][]*Tag {
@@ -658,11 +753,21 @@ func (m *ModbusHandler) readSingleTag(conn *ModbusConnection, tag *Tag) (interfa
 		return nil, err
 	}
 
+	if err := m.applyRegisterCount(addr, tag); err != nil {
+		return nil, err
+	}
+
 	var result []byte
 
 	switch addr.FunctionCode {
 	case ReadHoldingRegisters:
-		registers, err := conn.client.ReadHoldingRegisters(addr.Address, 1)
+		registers, err := conn.client.ReadHoldingRegisters(addr.Address, addr.Count)
+		if err != nil {
+			return nil, err
+		}
+		result = registers
+	case ReadInputRegisters:
+		registers, err := conn.client.ReadInputRegisters(addr.Address, addr.Count)
 		if err != nil {
 			return nil, err
 		}
@@ -670,28 +775,7 @@ func (m *ModbusHandler) readSingleTag(conn *ModbusConnection, tag *Tag) (interfa
 		// Add other function codes as needed
 	}
 
-	return m.convertFromModbus(result, tag.DataType, addr.FunctionCode)
-}
-
-func (m *ModbusHandler) probeModbusDevice(ctx context.Context, ip string, port int) *Device {
-	// Quick probe to see if a Modbus device responds at this address
-	timeout := 2 * time.Second
-
-	conn, err := net.DialTimeout("tcp", fmt.Sprintf("%s:%d", ip, port), timeout)
-	if err != nil {
-		return nil
-	}
-	conn.Close()
-
-	// Create a minimal device entry for discovered device
-	return &Device{
-		ID:       fmt.Sprintf("modbus-%s-%d", ip, port),
-		Name:     fmt.Sprintf("Modbus Device at %s:%d", ip, port),
-		Protocol: "modbus-tcp",
-		Address:  ip,
-		Port:     port,
-		Config:   make(map[string]interface{}),
-	}
+	return m.convertTagFromModbus(result, tag, addr.FunctionCode)
 }
 
 func (m *ModbusHandler) incrementIP(ip net.IP) {
@@ -0,0 +1,118 @@
+package protocols
+
+import (
+	"fmt"
+	"time"
+
+	"github.com/goburrow/modbus"
+)
+
+// Modbus Broadcast
+//
+// A request addressed to unit 0 is accepted by every slave on a serial line
+// and answered by none. Only writes may be broadcast. After a broadcast the
+// master waits a turnaround delay so slaves can process the request before
+// the bus carries new traffic.
+
+const (
+	// BroadcastUnitID addresses every slave on a serial line
+	BroadcastUnitID byte = 0
+
+	defaultTurnaroundDelay = 100 * time.Millisecond
+)
+
+// ValidateBroadcastPDU checks that pdu is a valid request that may be
+// broadcast
+func ValidateBroadcastPDU(pdu *modbus.ProtocolDataUnit) error {
+	switch ModbusFunctionCode(pdu.FunctionCode) {
+	case WriteSingleCoil, WriteSingleRegister, WriteMultipleCoils, WriteMultipleRegisters,
+		WriteFileRecord, MaskWriteRegister:
+		return ValidateRequestPDU(pdu)
+	}
+
+	return validationError(ModbusErrorBroadcast, pdu,
+		fmt.Sprintf("function code 0x%02X cannot be broadcast", pdu.FunctionCode))
+}
+
+// Broadcast sends pdu to unit 0 without waiting for a response, then waits
+// TurnaroundDelay
+func (t *ModbusTCPTransport) Broadcast(pdu *modbus.ProtocolDataUnit) error {
+	if err := ValidateBroadcastPDU(pdu); err != nil {
+		return err
+	}
+
+	aduRequest, err := t.encode(BroadcastUnitID, pdu)
+	if err != nil {
+		return err
+	}
+
+	t.mutex.Lock()
+	if err := t.connect(); err != nil {
+		t.mutex.Unlock()
+		return err
+	}
+	conn := t.conn
+	t.mutex.Unlock()
+
+	t.writeMu.Lock()
+	conn.SetWriteDeadline(time.Now().Add(t.Timeout))
+	_, err = conn.Write(aduRequest)
+	t.writeMu.Unlock()
+
+	if err != nil {
+		connErr := NewModbusError(ModbusErrorConnection, fmt.Sprintf("write failed: %v", err), "broadcast")
+		t.closeConnection(conn, connErr)
+		return connErr
+	}
+	recordFrame(t.Recorder, CaptureTransportModbusTCP, t.Address, CaptureSent, aduRequest)
+
+	time.Sleep(t.TurnaroundDelay)
+	return nil
+}
+
+// Broadcast sends pdu to every slave on the line without waiting for a
+// response. The bus is held for TurnaroundDelay afterwards.
+func (t *ModbusRTUTransport) Broadcast(pdu *modbus.ProtocolDataUnit) error {
+	if err := ValidateBroadcastPDU(pdu); err != nil {
+		return err
+	}
+
+	t.mutex.Lock()
+	defer t.mutex.Unlock()
+
+	frame, err := appendRTUFrame(t.txBuffer[:0], BroadcastUnitID, pdu)
+	if err != nil {
+		return err
+	}
+
+	if err := t.connect(); err != nil {
+		return err
+	}
+
+	t.waitForIdle()
+	if _, err := t.port.Write(frame); err != nil {
+		t.closePort()
+		return NewModbusError(ModbusErrorConnection, fmt.Sprintf("write failed: %v", err), "broadcast")
+	}
+	recordFrame(t.Recorder, CaptureTransportModbusRTU, t.Address, CaptureSent, frame)
+
+	time.Sleep(t.TurnaroundDelay)
+	t.lastFrame = time.Now()
+	return nil
+}
+
+// Broadcast sends a write request to unit 0 through the device's connection.
+// No response is expected, so success only means the request was sent.
+func (m *ModbusHandler) Broadcast(device *Device, functionCode byte, data []byte) error {
+	conn, err := m.getConnection(device)
+	if err != nil {
+		return err
+	}
+
+	conn.mutex.Lock()
+	defer conn.mutex.Unlock()
+
+	conn.lastUsed = time.Now()
+
+	return conn.handler.Broadcast(&modbus.ProtocolDataUnit{FunctionCode: functionCode, Data: data})
+}
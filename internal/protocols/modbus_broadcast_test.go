@@ -0,0 +1,81 @@
+package protocols
+
+import (
+	"context"
+	"net"
+	"testing"
+	"time"
+
+	"github.com/goburrow/modbus"
+	"github.com/stretchr/testify/assert"
+	"go.uber.org/zap"
+)
+
+func TestValidateBroadcastPDU(t *testing.T) {
+	assert.NoError(t, ValidateBroadcastPDU(&modbus.ProtocolDataUnit{FunctionCode: 0x06, Data: []byte{0x00, 0x01, 0x00, 0x2A}}))
+
+	err := ValidateBroadcastPDU(&modbus.ProtocolDataUnit{FunctionCode: 0x03, Data: []byte{0x00, 0x00, 0x00, 0x01}})
+	assertValidationCategory(t, err, ModbusErrorBroadcast)
+	assert.Equal(t, ExceptionIllegalFunction, ExceptionCodeForError(err))
+
+	err = ValidateBroadcastPDU(&modbus.ProtocolDataUnit{FunctionCode: 0x10, Data: []byte{0x00, 0x01, 0x00, 0x02, 0x02, 0x00, 0x01}})
+	assertValidationCategory(t, err, ModbusErrorByteCountMismatch)
+}
+
+func TestModbusRTUTransport_Broadcast(t *testing.T) {
+	received := make(chan byte, 1)
+	transport := newPipeRTUTransport(t, func(unitID byte, pdu *modbus.ProtocolDataUnit) *modbus.ProtocolDataUnit {
+		received <- unitID
+		return nil
+	})
+	transport.TurnaroundDelay = time.Millisecond
+
+	write := &modbus.ProtocolDataUnit{FunctionCode: 0x06, Data: []byte{0x00, 0x01, 0x00, 0x2A}}
+	assert.NoError(t, transport.Broadcast(write))
+	assert.Equal(t, BroadcastUnitID, <-received)
+
+	_, err := transport.Send(BroadcastUnitID, write)
+	assertValidationCategory(t, err, ModbusErrorBroadcast)
+}
+
+func TestModbusRTUGateway_BroadcastIsNeverAnswered(t *testing.T) {
+	received := make(chan *modbus.ProtocolDataUnit, 2)
+	transport := newPipeRTUTransport(t, func(unitID byte, pdu *modbus.ProtocolDataUnit) *modbus.ProtocolDataUnit {
+		if unitID == BroadcastUnitID {
+			received <- pdu
+		}
+		return nil
+	})
+	transport.TurnaroundDelay = time.Millisecond
+
+	gateway := NewModbusRTUGateway(zap.NewNop())
+	assert.NoError(t, gateway.AddRoute(1, 10, transport))
+
+	listener, err := net.Listen("tcp", "127.0.0.1:0")
+	assert.NoError(t, err)
+
+	ctx, cancel := context.WithCancel(context.Background())
+	defer cancel()
+	go gateway.Serve(ctx, listener)
+
+	conn, err := net.Dial("tcp", listener.Addr().String())
+	assert.NoError(t, err)
+	defer conn.Close()
+
+	// A broadcast read is dropped; the broadcast write is forwarded
+	conn.Write(encodeMBAPFrame(1, BroadcastUnitID, &modbus.ProtocolDataUnit{FunctionCode: 0x03, Data: []byte{0x00, 0x00, 0x00, 0x01}}))
+	conn.Write(encodeMBAPFrame(2, BroadcastUnitID, &modbus.ProtocolDataUnit{FunctionCode: 0x06, Data: []byte{0x00, 0x01, 0x00, 0x2A}}))
+
+	select {
+	case pdu := <-received:
+		assert.Equal(t, byte(0x06), pdu.FunctionCode)
+	case <-time.After(time.Second):
+		t.Fatal("broadcast was not forwarded")
+	}
+
+	conn.SetReadDeadline(time.Now().Add(100 * time.Millisecond))
+	n, err := conn.Read(make([]byte, modbusTCPMaxADUSize))
+	assert.Equal(t, 0, n)
+	assert.True(t, isTimeoutError(err))
+	assert.Len(t, received, 0)
+}
@@ -0,0 +1,90 @@
+package protocols
+
+import (
+	"fmt"
+
+	"github.com/goburrow/modbus"
+)
+
+// User-Defined Modbus Function Codes
+//
+// The spec reserves function codes 65-72 and 100-110 for vendor-specific
+// functions. Their PDUs have no defined structure, so requests are sent as
+// raw data and the response data is returned unparsed.
+
+const (
+	userDefinedFunctionsLow1  = 65
+	userDefinedFunctionsHigh1 = 72
+	userDefinedFunctionsLow2  = 100
+	userDefinedFunctionsHigh2 = 110
+)
+
+// ModbusFunctionHandler serves a request locally and returns the response
+// PDU. Returned errors are answered with the exception code chosen by
+// ExceptionCodeForError.
+type ModbusFunctionHandler func(unitID byte, request *modbus.ProtocolDataUnit) (*modbus.ProtocolDataUnit, error)
+
+// IsUserDefinedFunctionCode reports whether functionCode lies in one of the
+// ranges reserved for user-defined functions
+func IsUserDefinedFunctionCode(functionCode byte) bool {
+	return (functionCode >= userDefinedFunctionsLow1 && functionCode <= userDefinedFunctionsHigh1) ||
+		(functionCode >= userDefinedFunctionsLow2 && functionCode <= userDefinedFunctionsHigh2)
+}
+
+// SendCustomFunction sends a user-defined function code request to the
+// device and returns the response data. Exception responses are returned as
+// *modbus.ModbusError.
+func (m *ModbusHandler) SendCustomFunction(device *Device, functionCode byte, data []byte) ([]byte, error) {
+	if !IsUserDefinedFunctionCode(functionCode) {
+		return nil, fmt.Errorf("function code %d is not user-defined (65-72, 100-110)", functionCode)
+	}
+	if len(data) > maxPDUDataSize {
+		return nil, fmt.Errorf("request data of %d bytes exceeds %d", len(data), maxPDUDataSize)
+	}
+
+	return m.SendPDU(device, functionCode, data, nil)
+}
+
+// HandleFunction answers requests with a user-defined function code locally
+// instead of forwarding them to the serial line
+func (g *ModbusRTUGateway) HandleFunction(functionCode byte, handler ModbusFunctionHandler) error {
+	if !IsUserDefinedFunctionCode(functionCode) {
+		return fmt.Errorf("function code %d is not user-defined (65-72, 100-110)", functionCode)
+	}
+
+	g.mutex.Lock()
+	defer g.mutex.Unlock()
+
+	if handler == nil {
+		delete(g.functions, functionCode)
+		return nil
+	}
+	g.functions[functionCode] = handler
+	return nil
+}
+
+// functionHandler returns the local handler for functionCode, if any
+func (g *ModbusRTUGateway) functionHandler(functionCode byte) (ModbusFunctionHandler, bool) {
+	g.mutex.RLock()
+	defer g.mutex.RUnlock()
+
+	handler, exists := g.functions[functionCode]
+	return handler, exists
+}
+
+// serveFunction runs a local function handler and checks its response
+func serveFunction(handler ModbusFunctionHandler, unitID byte, request *modbus.ProtocolDataUnit) (*modbus.ProtocolDataUnit, error) {
+	response, err := handler(unitID, request)
+	if err != nil {
+		return nil, err
+	}
+
+	if response == nil || (response.FunctionCode != request.FunctionCode && response.FunctionCode != request.FunctionCode|exceptionFunctionFlag) {
+		return nil, NewModbusError(ModbusErrorFrame, "handler returned a response for another function code", "serve")
+	}
+	if len(response.Data) > maxPDUDataSize {
+		return nil, NewModbusError(ModbusErrorFrame,
+			fmt.Sprintf("handler response of %d bytes exceeds %d", len(response.Data), maxPDUDataSize), "serve")
+	}
+	return response, nil
+}
@@ -0,0 +1,76 @@
+package protocols
+
+import (
+	"context"
+	"net"
+	"testing"
+	"time"
+
+	"github.com/goburrow/modbus"
+	"github.com/stretchr/testify/assert"
+	"go.uber.org/zap"
+)
+
+func TestIsUserDefinedFunctionCode(t *testing.T) {
+	for _, code := range []byte{65, 72, 100, 110} {
+		assert.True(t, IsUserDefinedFunctionCode(code), "code %d", code)
+	}
+	for _, code := range []byte{0x03, 64, 73, 99, 111} {
+		assert.False(t, IsUserDefinedFunctionCode(code), "code %d", code)
+	}
+}
+
+func TestModbusHandler_SendCustomFunction(t *testing.T) {
+	device := startModbusTestServer(t, func(unitID byte, pdu []byte) []byte {
+		return append([]byte{pdu[0]}, pdu[2], pdu[1])
+	})
+	handler := connectTestDevice(t, device)
+
+	data, err := handler.SendCustomFunction(device, 0x41, []byte{0x01, 0x02})
+	assert.NoError(t, err)
+	assert.Equal(t, []byte{0x02, 0x01}, data)
+
+	_, err = handler.SendCustomFunction(device, 0x03, []byte{0x00, 0x00, 0x00, 0x01})
+	assert.Error(t, err)
+}
+
+func TestModbusRTUGateway_HandleFunction(t *testing.T) {
+	gateway := NewModbusRTUGateway(zap.NewNop())
+	assert.Error(t, gateway.HandleFunction(0x03, nil))
+	assert.NoError(t, gateway.HandleFunction(100, func(unitID byte, request *modbus.ProtocolDataUnit) (*modbus.ProtocolDataUnit, error) {
+		if len(request.Data) == 0 {
+			return nil, NewModbusError(ModbusErrorInvalidValue, "empty request", "serve")
+		}
+		return &modbus.ProtocolDataUnit{FunctionCode: request.FunctionCode, Data: []byte{unitID, request.Data[0]}}, nil
+	}))
+
+	listener, err := net.Listen("tcp", "127.0.0.1:0")
+	assert.NoError(t, err)
+
+	ctx, cancel := context.WithCancel(context.Background())
+	defer cancel()
+	go gateway.Serve(ctx, listener)
+
+	client := NewModbusTCPTransport(listener.Addr().String(), zap.NewNop())
+	client.Timeout = time.Second
+	client.SlaveId = 9
+	defer client.Close()
+
+	send := func(functionCode byte, data []byte) *modbus.ProtocolDataUnit {
+		request, err := client.Encode(&modbus.ProtocolDataUnit{FunctionCode: functionCode, Data: data})
+		assert.NoError(t, err)
+		response, err := client.Send(request)
+		if !assert.NoError(t, err) {
+			return nil
+		}
+		pdu, err := client.Decode(response)
+		assert.NoError(t, err)
+		return pdu
+	}
+
+	assert.Equal(t, &modbus.ProtocolDataUnit{FunctionCode: 100, Data: []byte{9, 0x2A}}, send(100, []byte{0x2A}))
+	assert.Equal(t, ExceptionResponsePDU(100, ExceptionIllegalDataValue), send(100, nil))
+
+	// Other user-defined codes are forwarded, and unit 9 has no route
+	assert.Equal(t, ExceptionResponsePDU(101, ExceptionGatewayPathUnavailable), send(101, []byte{0x2A}))
+}
@@ -0,0 +1,318 @@
+package protocols
+
+import (
+	"encoding/binary"
+	"fmt"
+	"math"
+	"strings"
+)
+
+// Modbus Register Data Type Conversion
+
+// WordOrder describes how a multi-register value is laid out on the wire.
+// The letters name the bytes of the big-endian value, most significant
+// first, in the order the device transmits them.
+type WordOrder string
+
+const (
+	WordOrderABCD WordOrder = "ABCD" // Big endian (Modbus default)
+	WordOrderDCBA WordOrder = "DCBA" // Little endian
+	WordOrderBADC WordOrder = "BADC" // Big endian words, bytes swapped within each word
+	WordOrderCDAB WordOrder = "CDAB" // Little endian words, big endian bytes (word swap)
+)
+
+// ParseWordOrder parses a word order name, defaulting to ABCD when empty
+func ParseWordOrder(order string) (WordOrder, error) {
+	switch WordOrder(strings.ToUpper(strings.TrimSpace(order))) {
+	case "", WordOrderABCD:
+		return WordOrderABCD, nil
+	case WordOrderDCBA:
+		return WordOrderDCBA, nil
+	case WordOrderBADC:
+		return WordOrderBADC, nil
+	case WordOrderCDAB:
+		return WordOrderCDAB, nil
+	default:
+		return "", fmt.Errorf("unknown word order: %s", order)
+	}
+}
+
+// RegisterCount returns the number of 16-bit registers used by dataType.
+// stringLength is the string length in characters and is only used for
+// DataTypeString.
+func RegisterCount(dataType DataType, stringLength int) (uint16, error) {
+	switch dataType {
+	case DataTypeBool, DataTypeInt16, DataTypeUInt16:
+		return 1, nil
+	case DataTypeInt32, DataTypeUInt32, DataTypeFloat32:
+		return 2, nil
+	case DataTypeInt64, DataTypeUInt64, DataTypeFloat64:
+		return 4, nil
+	case DataTypeString:
+		if stringLength <= 0 || stringLength > 2*MaxReadRegistersQuantity {
+			return 0, fmt.Errorf("invalid string length: %d", stringLength)
+		}
+		return uint16((stringLength + 1) / 2), nil
+	default:
+		return 0, fmt.Errorf("unsupported data type: %s", dataType)
+	}
+}
+
+// DecodeRegisterValue converts raw register bytes, as returned by a register
+// read, into a typed value. Strings are ASCII with trailing NUL and space
+// padding removed; for strings only the byte order within each register is
+// significant.
+func DecodeRegisterValue(data []byte, dataType DataType, order WordOrder) (interface{}, error) {
+	if dataType == DataTypeString {
+		if len(data)%2 != 0 {
+			return nil, fmt.Errorf("register data has odd length %d", len(data))
+		}
+		return strings.TrimRight(string(reorderStringBytes(data, order)), "\x00 "), nil
+	}
+
+	count, err := RegisterCount(dataType, 0)
+	if err != nil {
+		return nil, err
+	}
+	if len(data) < int(count)*2 {
+		return nil, fmt.Errorf("insufficient data for %s", dataType)
+	}
+
+	raw, err := reorderRegisterBytes(data[:count*2], order)
+	if err != nil {
+		return nil, err
+	}
+
+	switch dataType {
+	case DataTypeBool:
+		return binary.BigEndian.Uint16(raw) != 0, nil
+	case DataTypeInt16:
+		return int16(binary.BigEndian.Uint16(raw)), nil
+	case DataTypeUInt16:
+		return binary.BigEndian.Uint16(raw), nil
+	case DataTypeInt32:
+		return int32(binary.BigEndian.Uint32(raw)), nil
+	case DataTypeUInt32:
+		return binary.BigEndian.Uint32(raw), nil
+	case DataTypeFloat32:
+		return math.Float32frombits(binary.BigEndian.Uint32(raw)), nil
+	case DataTypeInt64:
+		return int64(binary.BigEndian.Uint64(raw)), nil
+	case DataTypeUInt64:
+		return binary.BigEndian.Uint64(raw), nil
+	default: // DataTypeFloat64
+		return math.Float64frombits(binary.BigEndian.Uint64(raw)), nil
+	}
+}
+
+// EncodeRegisterValue converts value into register bytes ready for a
+// register write. Strings are padded with NULs to stringLength characters
+// rounded up to a whole register.
+func EncodeRegisterValue(value interface{}, dataType DataType, order WordOrder, stringLength int) ([]byte, error) {
+	count, err := RegisterCount(dataType, stringLength)
+	if err != nil {
+		return nil, err
+	}
+	raw := make([]byte, count*2)
+
+	switch dataType {
+	case DataTypeString:
+		s, ok := value.(string)
+		if !ok {
+			return nil, fmt.Errorf("expected string value, got %T", value)
+		}
+		if len(s) > stringLength {
+			return nil, fmt.Errorf("string of length %d exceeds %d characters", len(s), stringLength)
+		}
+		copy(raw, s)
+		return reorderStringBytes(raw, order), nil
+
+	case DataTypeBool:
+		b, ok := value.(bool)
+		if !ok {
+			return nil, fmt.Errorf("expected boolean value, got %T", value)
+		}
+		if b {
+			binary.BigEndian.PutUint16(raw, 1)
+		}
+
+	case DataTypeInt16:
+		v, err := integerValue(value, math.MinInt16, math.MaxInt16)
+		if err != nil {
+			return nil, err
+		}
+		binary.BigEndian.PutUint16(raw, uint16(v))
+
+	case DataTypeUInt16:
+		v, err := integerValue(value, 0, math.MaxUint16)
+		if err != nil {
+			return nil, err
+		}
+		binary.BigEndian.PutUint16(raw, uint16(v))
+
+	case DataTypeInt32:
+		v, err := integerValue(value, math.MinInt32, math.MaxInt32)
+		if err != nil {
+			return nil, err
+		}
+		binary.BigEndian.PutUint32(raw, uint32(v))
+
+	case DataTypeUInt32:
+		v, err := integerValue(value, 0, math.MaxUint32)
+		if err != nil {
+			return nil, err
+		}
+		binary.BigEndian.PutUint32(raw, uint32(v))
+
+	case DataTypeInt64:
+		v, err := integerValue(value, math.MinInt64, math.MaxInt64)
+		if err != nil {
+			return nil, err
+		}
+		binary.BigEndian.PutUint64(raw, uint64(v))
+
+	case DataTypeUInt64:
+		v, err := unsigned64Value(value)
+		if err != nil {
+			return nil, err
+		}
+		binary.BigEndian.PutUint64(raw, v)
+
+	case DataTypeFloat32:
+		v, err := floatValue(value)
+		if err != nil {
+			return nil, err
+		}
+		binary.BigEndian.PutUint32(raw, math.Float32bits(float32(v)))
+
+	case DataTypeFloat64:
+		v, err := floatValue(value)
+		if err != nil {
+			return nil, err
+		}
+		binary.BigEndian.PutUint64(raw, math.Float64bits(v))
+	}
+
+	return reorderRegisterBytes(raw, order)
+}
+
+// reorderRegisterBytes converts between the wire layout described by order
+// and big-endian byte order. Every supported order is its own inverse, so the
+// same function serves both directions.
+func reorderRegisterBytes(data []byte, order WordOrder) ([]byte, error) {
+	out := make([]byte, len(data))
+
+	switch order {
+	case "", WordOrderABCD:
+		copy(out, data)
+	case WordOrderDCBA:
+		for i := range data {
+			out[i] = data[len(data)-1-i]
+		}
+	case WordOrderBADC:
+		for i := 0; i+1 < len(data); i += 2 {
+			out[i], out[i+1] = data[i+1], data[i]
+		}
+	case WordOrderCDAB:
+		for i := 0; i+1 < len(data); i += 2 {
+			j := len(data) - 2 - i
+			out[i], out[i+1] = data[j], data[j+1]
+		}
+	default:
+		return nil, fmt.Errorf("unknown word order: %s", order)
+	}
+
+	return out, nil
+}
+
+// reorderStringBytes swaps the two characters of every register for the
+// byte-swapped orders (BADC and DCBA)
+func reorderStringBytes(data []byte, order WordOrder) []byte {
+	out := make([]byte, len(data))
+	copy(out, data)
+
+	if order == WordOrderBADC || order == WordOrderDCBA {
+		for i := 0; i+1 < len(out); i += 2 {
+			out[i], out[i+1] = out[i+1], out[i]
+		}
+	}
+
+	return out
+}
+
+// integerValue converts value to int64 and checks it lies within [min, max]
+func integerValue(value interface{}, min, max int64) (int64, error) {
+	var v int64
+
+	switch n := value.(type) {
+	case int:
+		v = int64(n)
+	case int8:
+		v = int64(n)
+	case int16:
+		v = int64(n)
+	case int32:
+		v = int64(n)
+	case int64:
+		v = n
+	case uint8:
+		v = int64(n)
+	case uint16:
+		v = int64(n)
+	case uint32:
+		v = int64(n)
+	case uint:
+		if uint64(n) > math.MaxInt64 {
+			return 0, fmt.Errorf("value %d out of range [%d, %d]", n, min, max)
+		}
+		v = int64(n)
+	case uint64:
+		if n > math.MaxInt64 {
+			return 0, fmt.Errorf("value %d out of range [%d, %d]", n, min, max)
+		}
+		v = int64(n)
+	case float32:
+		return integerValue(float64(n), min, max)
+	case float64:
+		if n != math.Trunc(n) || n < math.MinInt64 || n >= math.MaxInt64 {
+			return 0, fmt.Errorf("value %v is not a representable integer", n)
+		}
+		v = int64(n)
+	default:
+		return 0, fmt.Errorf("expected integer value, got %T", value)
+	}
+
+	if v < min || v > max {
+		return 0, fmt.Errorf("value %d out of range [%d, %d]", v, min, max)
+	}
+	return v, nil
+}
+
+func unsigned64Value(value interface{}) (uint64, error) {
+	switch n := value.(type) {
+	case uint64:
+		return n, nil
+	case uint:
+		return uint64(n), nil
+	case float64:
+		if n != math.Trunc(n) || n < 0 || n >= math.MaxUint64 {
+			return 0, fmt.Errorf("value %v is not a representable integer", n)
+		}
+		return uint64(n), nil
+	default:
+		v, err := integerValue(value, 0, math.MaxInt64)
+		return uint64(v), err
+	}
+}
+
+func floatValue(value interface{}) (float64, error) {
+	switch n := value.(type) {
+	case float32:
+		return float64(n), nil
+	case float64:
+		return n, nil
+	default:
+		v, err := integerValue(value, math.MinInt64, math.MaxInt64)
+		return float64(v), err
+	}
+}
@@ -0,0 +1,136 @@
+package protocols
+
+import (
+	"encoding/binary"
+	"math"
+	"testing"
+
+	"github.com/stretchr/testify/assert"
+)
+
+func TestDecodeRegisterValue_WordOrders(t *testing.T) {
+	// 123.456 as float32 is 0x42F6E979
+	tests := []struct {
+		order WordOrder
+		data  []byte
+	}{
+		{WordOrderABCD, []byte{0x42, 0xF6, 0xE9, 0x79}},
+		{WordOrderDCBA, []byte{0x79, 0xE9, 0xF6, 0x42}},
+		{WordOrderBADC, []byte{0xF6, 0x42, 0x79, 0xE9}},
+		{WordOrderCDAB, []byte{0xE9, 0x79, 0x42, 0xF6}},
+	}
+
+	for _, tt := range tests {
+		t.Run(string(tt.order), func(t *testing.T) {
+			value, err := DecodeRegisterValue(tt.data, DataTypeFloat32, tt.order)
+			assert.NoError(t, err)
+			assert.Equal(t, float32(123.456), value)
+
+			encoded, err := EncodeRegisterValue(float32(123.456), DataTypeFloat32, tt.order, 0)
+			assert.NoError(t, err)
+			assert.Equal(t, tt.data, encoded)
+		})
+	}
+}
+
+func TestRegisterValue_RoundTrip(t *testing.T) {
+	values := []struct {
+		dataType DataType
+		value    interface{}
+	}{
+		{DataTypeInt16, int16(-1234)},
+		{DataTypeUInt16, uint16(65000)},
+		{DataTypeInt32, int32(-123456789)},
+		{DataTypeUInt32, uint32(4000000000)},
+		{DataTypeInt64, int64(-1234567890123)},
+		{DataTypeUInt64, uint64(math.MaxUint64)},
+		{DataTypeFloat32, float32(-0.5)},
+		{DataTypeFloat64, 3.141592653589793},
+	}
+
+	for _, order := range []WordOrder{WordOrderABCD, WordOrderDCBA, WordOrderBADC, WordOrderCDAB} {
+		for _, v := range values {
+			encoded, err := EncodeRegisterValue(v.value, v.dataType, order, 0)
+			assert.NoError(t, err, "%s %s", v.dataType, order)
+
+			decoded, err := DecodeRegisterValue(encoded, v.dataType, order)
+			assert.NoError(t, err, "%s %s", v.dataType, order)
+			assert.Equal(t, v.value, decoded, "%s %s", v.dataType, order)
+		}
+	}
+}
+
+func TestRegisterValue_Float64WordSwap(t *testing.T) {
+	raw := make([]byte, 8)
+	binary.BigEndian.PutUint64(raw, math.Float64bits(1.5))
+
+	// CDAB reverses the register order of a four register value
+	swapped := []byte{raw[6], raw[7], raw[4], raw[5], raw[2], raw[3], raw[0], raw[1]}
+	value, err := DecodeRegisterValue(swapped, DataTypeFloat64, WordOrderCDAB)
+	assert.NoError(t, err)
+	assert.Equal(t, 1.5, value)
+}
+
+func TestRegisterValue_String(t *testing.T) {
+	encoded, err := EncodeRegisterValue("PUMP1", DataTypeString, WordOrderABCD, 8)
+	assert.NoError(t, err)
+	assert.Equal(t, []byte{'P', 'U', 'M', 'P', '1', 0, 0, 0}, encoded)
+
+	value, err := DecodeRegisterValue(encoded, DataTypeString, WordOrderABCD)
+	assert.NoError(t, err)
+	assert.Equal(t, "PUMP1", value)
+
+	value, err = DecodeRegisterValue([]byte{'U', 'P', 'P', 'M', ' ', '1'}, DataTypeString, WordOrderBADC)
+	assert.NoError(t, err)
+	assert.Equal(t, "PUMP1", value)
+
+	_, err = EncodeRegisterValue("TOO LONG", DataTypeString, WordOrderABCD, 4)
+	assert.Error(t, err)
+}
+
+func TestEncodeRegisterValue_Range(t *testing.T) {
+	_, err := EncodeRegisterValue(70000, DataTypeUInt16, WordOrderABCD, 0)
+	assert.Error(t, err)
+
+	_, err = EncodeRegisterValue(-1, DataTypeUInt32, WordOrderABCD, 0)
+	assert.Error(t, err)
+
+	_, err = EncodeRegisterValue(1.5, DataTypeInt32, WordOrderABCD, 0)
+	assert.Error(t, err)
+
+	encoded, err := EncodeRegisterValue(float64(42), DataTypeInt32, WordOrderABCD, 0)
+	assert.NoError(t, err)
+	assert.Equal(t, []byte{0x00, 0x00, 0x00, 0x2A}, encoded)
+}
+
+func TestParseWordOrder(t *testing.T) {
+	order, err := ParseWordOrder("cdab")
+	assert.NoError(t, err)
+	assert.Equal(t, WordOrderCDAB, order)
+
+	order, err = ParseWordOrder("")
+	assert.NoError(t, err)
+	assert.Equal(t, WordOrderABCD, order)
+
+	_, err = ParseWordOrder("ACBD")
+	assert.Error(t, err)
+}
+
+func TestModbusReadTag_Float32WordSwapped(t *testing.T) {
+	device := startModbusTestServer(t, func(unitID byte, pdu []byte) []byte {
+		assert.Equal(t, uint16(2), binary.BigEndian.Uint16(pdu[3:5]))
+		return []byte{0x03, 0x04, 0xE9, 0x79, 0x42, 0xF6}
+	})
+	handler := connectTestDevice(t, device)
+
+	tag := &Tag{
+		ID:             "flow",
+		Address:        "40001",
+		DataType:       string(DataTypeFloat32),
+		ProtocolConfig: map[string]interface{}{"word_order": "CDAB"},
+	}
+
+	value, err := handler.ReadTag(device, tag)
+	assert.NoError(t, err)
+	assert.Equal(t, float32(123.456), value)
+}
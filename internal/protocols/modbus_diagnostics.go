@@ -0,0 +1,215 @@
+package protocols
+
+import (
+	"encoding/binary"
+	"fmt"
+	"time"
+)
+
+// Modbus Serial Line Diagnostics
+//
+// Function codes 0x07, 0x08, 0x0B and 0x0C are defined for serial line
+// devices but are commonly forwarded by TCP gateways as well.
+
+// DiagnosticSubFunction identifies a Diagnostics (0x08) sub-function
+type DiagnosticSubFunction uint16
+
+const (
+	DiagReturnQueryData                DiagnosticSubFunction = 0x00
+	DiagRestartCommunications          DiagnosticSubFunction = 0x01
+	DiagReturnDiagnosticRegister       DiagnosticSubFunction = 0x02
+	DiagChangeASCIIInputDelimiter      DiagnosticSubFunction = 0x03
+	DiagForceListenOnlyMode            DiagnosticSubFunction = 0x04
+	DiagClearCounters                  DiagnosticSubFunction = 0x0A
+	DiagReturnBusMessageCount          DiagnosticSubFunction = 0x0B
+	DiagReturnBusCommErrorCount        DiagnosticSubFunction = 0x0C
+	DiagReturnBusExceptionErrorCount   DiagnosticSubFunction = 0x0D
+	DiagReturnServerMessageCount       DiagnosticSubFunction = 0x0E
+	DiagReturnServerNoResponseCount    DiagnosticSubFunction = 0x0F
+	DiagReturnServerNAKCount           DiagnosticSubFunction = 0x10
+	DiagReturnServerBusyCount          DiagnosticSubFunction = 0x11
+	DiagReturnBusCharacterOverrunCount DiagnosticSubFunction = 0x12
+	DiagClearOverrunCounterAndFlag     DiagnosticSubFunction = 0x14
+)
+
+// maxCommEventLogEvents is the size of the device event log buffer
+const maxCommEventLogEvents = 64
+
+// DiagnosticRequest is a Diagnostics (0x08) request
+type DiagnosticRequest struct {
+	SubFunction DiagnosticSubFunction `json:"sub_function"`
+	Data        []uint16              `json:"data"`
+}
+
+// DiagnosticResponse is a Diagnostics (0x08) response
+type DiagnosticResponse struct {
+	SubFunction DiagnosticSubFunction `json:"sub_function"`
+	Data        []uint16              `json:"data"`
+}
+
+// CommEventCounter is a Get Comm Event Counter (0x0B) response
+type CommEventCounter struct {
+	Busy       bool   `json:"busy"`
+	EventCount uint16 `json:"event_count"`
+}
+
+// CommEventLog is a Get Comm Event Log (0x0C) response. Events are ordered
+// most recent first, as returned by the device.
+type CommEventLog struct {
+	Busy         bool   `json:"busy"`
+	EventCount   uint16 `json:"event_count"`
+	MessageCount uint16 `json:"message_count"`
+	Events       []byte `json:"events"`
+}
+
+// ReadExceptionStatus reads the eight exception status outputs of the device
+// (function code 0x07)
+func (m *ModbusHandler) ReadExceptionStatus(device *Device) (byte, error) {
+	data, err := m.sendDiagnosticPDU(device, ReadExceptionStatus, nil)
+	if err != nil {
+		return 0, err
+	}
+
+	if len(data) != 1 {
+		return 0, fmt.Errorf("exception status response has %d bytes, expected 1", len(data))
+	}
+	return data[0], nil
+}
+
+// Diagnostics executes a Diagnostics (0x08) sub-function
+func (m *ModbusHandler) Diagnostics(device *Device, request *DiagnosticRequest) (*DiagnosticResponse, error) {
+	if request.SubFunction == DiagReturnQueryData && len(request.Data) == 0 {
+		return nil, fmt.Errorf("return query data requires at least one data word")
+	}
+
+	data := make([]byte, 2, 2+len(request.Data)*2)
+	binary.BigEndian.PutUint16(data, uint16(request.SubFunction))
+	data = append(data, registersToBytes(request.Data)...)
+
+	responseData, err := m.sendDiagnosticPDU(device, SerialLineDiagnostics, data)
+	if err != nil {
+		return nil, err
+	}
+
+	response, err := parseDiagnosticResponse(responseData)
+	if err != nil {
+		return nil, err
+	}
+
+	if response.SubFunction != request.SubFunction {
+		return nil, fmt.Errorf("response sub-function 0x%04X does not match request 0x%04X",
+			response.SubFunction, request.SubFunction)
+	}
+
+	if request.SubFunction == DiagReturnQueryData && !equalRegisters(response.Data, request.Data) {
+		return nil, fmt.Errorf("return query data echo does not match request")
+	}
+
+	return response, nil
+}
+
+// ReadDiagnosticCounter reads one of the diagnostic counters (sub-functions
+// 0x0B-0x12) or the diagnostic register (0x02)
+func (m *ModbusHandler) ReadDiagnosticCounter(device *Device, subFunction DiagnosticSubFunction) (uint16, error) {
+	response, err := m.Diagnostics(device, &DiagnosticRequest{
+		SubFunction: subFunction,
+		Data:        []uint16{0x0000},
+	})
+	if err != nil {
+		return 0, err
+	}
+
+	if len(response.Data) != 1 {
+		return 0, fmt.Errorf("counter response has %d data words, expected 1", len(response.Data))
+	}
+	return response.Data[0], nil
+}
+
+// GetCommEventCounter reads the communication event counter (function code 0x0B)
+func (m *ModbusHandler) GetCommEventCounter(device *Device) (*CommEventCounter, error) {
+	data, err := m.sendDiagnosticPDU(device, GetCommEventCounter, nil)
+	if err != nil {
+		return nil, err
+	}
+
+	if len(data) != 4 {
+		return nil, fmt.Errorf("comm event counter response has %d bytes, expected 4", len(data))
+	}
+
+	return &CommEventCounter{
+		Busy:       binary.BigEndian.Uint16(data[0:2]) == 0xFFFF,
+		EventCount: binary.BigEndian.Uint16(data[2:4]),
+	}, nil
+}
+
+// GetCommEventLog reads the communication event log (function code 0x0C)
+func (m *ModbusHandler) GetCommEventLog(device *Device) (*CommEventLog, error) {
+	data, err := m.sendDiagnosticPDU(device, GetCommEventLog, nil)
+	if err != nil {
+		return nil, err
+	}
+
+	return parseCommEventLog(data)
+}
+
+func (m *ModbusHandler) sendDiagnosticPDU(device *Device, functionCode ModbusFunctionCode, data []byte) ([]byte, error) {
+	conn, err := m.getConnection(device)
+	if err != nil {
+		return nil, err
+	}
+
+	conn.mutex.Lock()
+	defer conn.mutex.Unlock()
+
+	conn.lastUsed = time.Now()
+
+	return m.sendPDU(conn, byte(functionCode), data)
+}
+
+// parseDiagnosticResponse decodes a Diagnostics response (without the function code)
+func parseDiagnosticResponse(data []byte) (*DiagnosticResponse, error) {
+	if len(data) < 2 || len(data)%2 != 0 {
+		return nil, fmt.Errorf("invalid diagnostics response length: %d", len(data))
+	}
+
+	return &DiagnosticResponse{
+		SubFunction: DiagnosticSubFunction(binary.BigEndian.Uint16(data[0:2])),
+		Data:        bytesToRegisters(data[2:]),
+	}, nil
+}
+
+// parseCommEventLog decodes a Get Comm Event Log response (without the function code)
+func parseCommEventLog(data []byte) (*CommEventLog, error) {
+	if len(data) < 7 {
+		return nil, fmt.Errorf("comm event log response too short: %d bytes", len(data))
+	}
+
+	byteCount := int(data[0])
+	if byteCount != len(data)-1 {
+		return nil, fmt.Errorf("comm event log byte count %d does not match payload %d", byteCount, len(data)-1)
+	}
+
+	events := data[7:]
+	if len(events) > maxCommEventLogEvents {
+		return nil, fmt.Errorf("comm event log contains %d events, maximum is %d", len(events), maxCommEventLogEvents)
+	}
+
+	return &CommEventLog{
+		Busy:         binary.BigEndian.Uint16(data[1:3]) == 0xFFFF,
+		EventCount:   binary.BigEndian.Uint16(data[3:5]),
+		MessageCount: binary.BigEndian.Uint16(data[5:7]),
+		Events:       append([]byte(nil), events...),
+	}, nil
+}
+
+func equalRegisters(a, b []uint16) bool {
+	if len(a) != len(b) {
+		return false
+	}
+	for i := range a {
+		if a[i] != b[i] {
+			return false
+		}
+	}
+	return true
+}
@@ -0,0 +1,59 @@
+package protocols
+
+import (
+	"testing"
+
+	"github.com/stretchr/testify/assert"
+)
+
+func TestModbusDiagnosticFunctions(t *testing.T) {
+	device := startModbusTestServer(t, func(unitID byte, pdu []byte) []byte {
+		switch pdu[0] {
+		case 0x07:
+			return []byte{0x07, 0x6D}
+		case 0x08:
+			if pdu[2] == byte(DiagReturnBusMessageCount) {
+				return []byte{0x08, 0x00, 0x0B, 0x01, 0x2C}
+			}
+			return pdu // Echo
+		case 0x0B:
+			return []byte{0x0B, 0xFF, 0xFF, 0x01, 0x08}
+		case 0x0C:
+			return []byte{0x0C, 0x08, 0x00, 0x00, 0x01, 0x08, 0x01, 0x21, 0x20, 0x00}
+		}
+		return []byte{pdu[0] | 0x80, 0x01}
+	})
+	handler := connectTestDevice(t, device)
+
+	status, err := handler.ReadExceptionStatus(device)
+	assert.NoError(t, err)
+	assert.Equal(t, byte(0x6D), status)
+
+	response, err := handler.Diagnostics(device, &DiagnosticRequest{
+		SubFunction: DiagReturnQueryData,
+		Data:        []uint16{0xA537},
+	})
+	assert.NoError(t, err)
+	assert.Equal(t, []uint16{0xA537}, response.Data)
+
+	count, err := handler.ReadDiagnosticCounter(device, DiagReturnBusMessageCount)
+	assert.NoError(t, err)
+	assert.Equal(t, uint16(300), count)
+
+	counter, err := handler.GetCommEventCounter(device)
+	assert.NoError(t, err)
+	assert.True(t, counter.Busy)
+	assert.Equal(t, uint16(264), counter.EventCount)
+
+	log, err := handler.GetCommEventLog(device)
+	assert.NoError(t, err)
+	assert.False(t, log.Busy)
+	assert.Equal(t, uint16(264), log.EventCount)
+	assert.Equal(t, uint16(289), log.MessageCount)
+	assert.Equal(t, []byte{0x20, 0x00}, log.Events)
+}
+
+func TestParseCommEventLog_ByteCountMismatch(t *testing.T) {
+	_, err := parseCommEventLog([]byte{0x09, 0x00, 0x00, 0x01, 0x08, 0x01, 0x21, 0x20, 0x00})
+	assert.Error(t, err)
+}
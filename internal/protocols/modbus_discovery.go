@@ -0,0 +1,277 @@
+package protocols
+
+import (
+	"context"
+	"errors"
+	"fmt"
+	"net"
+	"strconv"
+	"sync"
+	"time"
+
+	"github.com/goburrow/modbus"
+	"go.uber.org/zap"
+)
+
+// Modbus Device Discovery
+//
+// Units are probed with Read Device Identification (0x2B / 0x0E, basic
+// category). A unit that answers with an exception is present but does not
+// implement identification; it is reported without one. Only silence means
+// nothing is there.
+
+const (
+	defaultScanTCPTimeout    = 500 * time.Millisecond
+	defaultScanSerialTimeout = 100 * time.Millisecond
+	defaultScanConcurrency   = 32
+	maxScanAddresses         = 1 << 16 // Refuse ranges larger than a /16
+)
+
+// DiscoveryOptions controls a discovery scan. Zero values select defaults.
+type DiscoveryOptions struct {
+	Ports       []int         // TCP ports to probe, default 502
+	UnitIDs     []byte        // Units to probe, default 1 on TCP and 1-247 on serial lines
+	Timeout     time.Duration // Per connection attempt and per probe
+	Concurrency int           // Hosts probed in parallel on TCP
+}
+
+// DiscoveredDevice is one unit that answered a discovery probe
+type DiscoveredDevice struct {
+	Protocol       string                `json:"protocol"` // "modbus-tcp" or "modbus-rtu"
+	Address        string                `json:"address"`  // IP address or serial port
+	Port           int                   `json:"port,omitempty"`
+	UnitID         byte                  `json:"unit_id"`
+	Identification *DeviceIdentification `json:"identification,omitempty"` // nil when not supported
+	ResponseTime   time.Duration         `json:"response_time"`
+}
+
+// Device converts the discovered unit into a Device that can be connected
+func (d *DiscoveredDevice) Device() *Device {
+	device := &Device{
+		Protocol: d.Protocol,
+		Address:  d.Address,
+		Port:     d.Port,
+		Config:   map[string]interface{}{"unit_id": int(d.UnitID)},
+		LastSeen: time.Now(),
+	}
+
+	if d.Protocol == "modbus-tcp" {
+		device.ID = fmt.Sprintf("modbus-%s-%d-%d", d.Address, d.Port, d.UnitID)
+		device.Name = fmt.Sprintf("Modbus Device at %s:%d unit %d", d.Address, d.Port, d.UnitID)
+	} else {
+		device.ID = fmt.Sprintf("modbus-%s-%d", d.Address, d.UnitID)
+		device.Name = fmt.Sprintf("Modbus Device on %s unit %d", d.Address, d.UnitID)
+	}
+
+	if d.Identification != nil {
+		vendor := d.Identification.Objects[DeviceIDObjectVendorName]
+		product := d.Identification.Objects[DeviceIDObjectProductCode]
+		if vendor != "" || product != "" {
+			device.Name = fmt.Sprintf("%s %s (unit %d)", vendor, product, d.UnitID)
+		}
+	}
+
+	return device
+}
+
+// ScanNetwork probes every address of networkRange (e.g. "192.168.1.0/24")
+// for Modbus TCP servers and returns the units that answered, in address
+// order
+func (m *ModbusHandler) ScanNetwork(ctx context.Context, networkRange string, options *DiscoveryOptions) ([]*DiscoveredDevice, error) {
+	_, network, err := net.ParseCIDR(networkRange)
+	if err != nil {
+		return nil, fmt.Errorf("invalid network range: %w", err)
+	}
+
+	ones, bits := network.Mask.Size()
+	if bits-ones > 16 {
+		return nil, fmt.Errorf("network range %s exceeds %d addresses", networkRange, maxScanAddresses)
+	}
+
+	options = scanDefaults(options, []byte{1}, defaultScanTCPTimeout)
+
+	var hosts []string
+	for ip := network.IP.Mask(network.Mask); network.Contains(ip); m.incrementIP(ip) {
+		hosts = append(hosts, ip.String())
+	}
+
+	targets := make(chan int)
+	found := make([][]*DiscoveredDevice, len(hosts)*len(options.Ports))
+
+	var wg sync.WaitGroup
+	for i := 0; i < options.Concurrency; i++ {
+		wg.Add(1)
+		go func() {
+			defer wg.Done()
+			for index := range targets {
+				host, port := hosts[index/len(options.Ports)], options.Ports[index%len(options.Ports)]
+				found[index] = m.probeTCPUnits(ctx, host, port, options)
+			}
+		}()
+	}
+
+dispatch:
+	for index := range found {
+		select {
+		case <-ctx.Done():
+			break dispatch
+		case targets <- index:
+		}
+	}
+	close(targets)
+	wg.Wait()
+
+	devices := make([]*DiscoveredDevice, 0)
+	for _, units := range found {
+		devices = append(devices, units...)
+	}
+
+	m.logger.Info("Modbus network scan finished",
+		zap.String("range", networkRange),
+		zap.Int("devices", len(devices)),
+	)
+	return devices, ctx.Err()
+}
+
+// ScanSerialBus sweeps the unit IDs of a serial line and returns the units
+// that answered. Units are probed one at a time, each request once and
+// with options.Timeout to answer regardless of the transport's retry
+// policy; with the defaults a full sweep of a silent bus takes about 25
+// seconds.
+func (m *ModbusHandler) ScanSerialBus(ctx context.Context, transport *ModbusRTUTransport, options *DiscoveryOptions) ([]*DiscoveredDevice, error) {
+	options = scanDefaults(options, nil, defaultScanSerialTimeout)
+
+	devices := make([]*DiscoveredDevice, 0)
+	for _, unitID := range options.UnitIDs {
+		if ctx.Err() != nil {
+			return devices, ctx.Err()
+		}
+		if unitID == BroadcastUnitID {
+			continue
+		}
+
+		device := probeUnit(func(request []byte) ([]byte, error) {
+			response, err := transport.transact(unitID,
+				&modbus.ProtocolDataUnit{FunctionCode: byte(EncapsulatedInterfaceTransport), Data: request}, options.Timeout)
+			if err != nil {
+				return nil, err
+			}
+			if err := exceptionError(response); err != nil {
+				return nil, err
+			}
+			return response.Data, nil
+		})
+		if device == nil {
+			continue
+		}
+
+		device.Protocol = "modbus-rtu"
+		device.Address = transport.Address
+		device.UnitID = unitID
+		devices = append(devices, device)
+	}
+
+	m.logger.Info("Modbus serial bus scan finished",
+		zap.String("port", transport.Address),
+		zap.Int("devices", len(devices)),
+	)
+	return devices, nil
+}
+
+// probeTCPUnits connects to host:port and probes each configured unit
+func (m *ModbusHandler) probeTCPUnits(ctx context.Context, host string, port int, options *DiscoveryOptions) []*DiscoveredDevice {
+	transport := NewModbusTCPTransport(net.JoinHostPort(host, strconv.Itoa(port)), m.logger)
+	transport.Timeout = options.Timeout
+	transport.MaxInFlight = 1
+	if err := transport.Connect(); err != nil {
+		return nil
+	}
+	defer transport.Close()
+
+	var devices []*DiscoveredDevice
+	for _, unitID := range options.UnitIDs {
+		if ctx.Err() != nil {
+			break
+		}
+
+		device := probeUnit(func(request []byte) ([]byte, error) {
+			aduRequest, err := transport.encode(unitID,
+				&modbus.ProtocolDataUnit{FunctionCode: byte(EncapsulatedInterfaceTransport), Data: request})
+			if err != nil {
+				return nil, err
+			}
+			aduResponse, err := transport.SendWithPolicy(aduRequest, nil)
+			if err != nil {
+				return nil, err
+			}
+			response, err := transport.Decode(aduResponse)
+			if err != nil {
+				return nil, err
+			}
+			if err := exceptionError(response); err != nil {
+				return nil, err
+			}
+			return response.Data, nil
+		})
+		if device == nil {
+			continue
+		}
+
+		device.Protocol = "modbus-tcp"
+		device.Address = host
+		device.Port = port
+		device.UnitID = unitID
+		devices = append(devices, device)
+
+		m.logger.Debug("Discovered Modbus device",
+			zap.String("address", transport.Address),
+			zap.Uint8("unit_id", unitID),
+		)
+	}
+	return devices
+}
+
+// probeUnit reads the basic identification through send. It returns nil
+// when no valid frame came back. Exceptions and malformed identification
+// data still prove that the unit exists.
+func probeUnit(send func(request []byte) ([]byte, error)) *DiscoveredDevice {
+	start := time.Now()
+	identification, err := identifyDevice(send, DeviceIDBasic, DeviceIDObjectVendorName)
+	device := &DiscoveredDevice{ResponseTime: time.Since(start)}
+
+	var transportErr *ModbusError
+	if errors.As(err, &transportErr) {
+		return nil
+	}
+	if err == nil {
+		device.Identification = identification
+	}
+	return device
+}
+
+// scanDefaults returns a copy of options with defaults filled in
+func scanDefaults(options *DiscoveryOptions, unitIDs []byte, timeout time.Duration) *DiscoveryOptions {
+	resolved := DiscoveryOptions{}
+	if options != nil {
+		resolved = *options
+	}
+
+	if len(resolved.Ports) == 0 {
+		resolved.Ports = []int{502}
+	}
+	if len(resolved.UnitIDs) == 0 {
+		resolved.UnitIDs = unitIDs
+		if resolved.UnitIDs == nil {
+			for id := 1; id <= 247; id++ {
+				resolved.UnitIDs = append(resolved.UnitIDs, byte(id))
+			}
+		}
+	}
+	if resolved.Timeout <= 0 {
+		resolved.Timeout = timeout
+	}
+	if resolved.Concurrency <= 0 {
+		resolved.Concurrency = defaultScanConcurrency
+	}
+	return &resolved
+}
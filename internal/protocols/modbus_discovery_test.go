@@ -0,0 +1,133 @@
+package protocols
+
+import (
+	"context"
+	"io"
+	"net"
+	"testing"
+	"time"
+
+	"github.com/goburrow/modbus"
+	"github.com/stretchr/testify/assert"
+	"go.uber.org/zap"
+)
+
+// basicIdentification is a Read Device Identification response carrying
+// vendor name and product code
+var basicIdentification = append(append([]byte{0x0E, 0x01, 0x01, 0x00, 0x00, 0x02, 0x00, 0x04}, "Acme"...),
+	append([]byte{0x01, 0x04}, "PX-1"...)...)
+
+func TestModbusHandler_ScanNetwork(t *testing.T) {
+	server := startModbusTestServer(t, func(unitID byte, pdu []byte) []byte {
+		if unitID == 1 {
+			return append([]byte{0x2B}, basicIdentification...)
+		}
+		return []byte{0x2B | 0x80, byte(ExceptionIllegalFunction)}
+	})
+
+	handler := NewModbusHandler(zap.NewNop()).(*ModbusHandler)
+	devices, err := handler.ScanNetwork(context.Background(), server.Address+"/32", &DiscoveryOptions{
+		Ports:   []int{server.Port},
+		UnitIDs: []byte{1, 2},
+		Timeout: time.Second,
+	})
+	assert.NoError(t, err)
+	if !assert.Len(t, devices, 2) {
+		return
+	}
+
+	assert.Equal(t, "modbus-tcp", devices[0].Protocol)
+	assert.Equal(t, server.Port, devices[0].Port)
+	assert.Equal(t, byte(1), devices[0].UnitID)
+	assert.Equal(t, "Acme", devices[0].Identification.Objects[DeviceIDObjectVendorName])
+
+	// Unit 2 does not support identification but is still there
+	assert.Equal(t, byte(2), devices[1].UnitID)
+	assert.Nil(t, devices[1].Identification)
+
+	device := devices[0].Device()
+	assert.Equal(t, "Acme PX-1 (unit 1)", device.Name)
+	assert.Equal(t, 1, device.Config["unit_id"])
+
+	_, err = handler.ScanNetwork(context.Background(), "10.0.0.0/8", nil)
+	assert.Error(t, err)
+}
+
+func TestModbusHandler_ScanSerialBus(t *testing.T) {
+	master, slave := net.Pipe()
+	t.Cleanup(func() { slave.Close() })
+
+	go func() {
+		request := make([]byte, 7) // Unit, 0x2B, 0x0E, category, object, CRC
+		for {
+			if _, err := io.ReadFull(slave, request); err != nil {
+				return
+			}
+			unitID, pdu, err := decodeRTUFrame(request)
+			if err != nil {
+				continue
+			}
+
+			var reply *modbus.ProtocolDataUnit
+			switch unitID {
+			case 4:
+				reply = &modbus.ProtocolDataUnit{FunctionCode: pdu.FunctionCode, Data: basicIdentification}
+			case 5:
+				reply = ExceptionResponsePDU(pdu.FunctionCode, ExceptionIllegalFunction)
+			default:
+				continue
+			}
+			frame, _ := encodeRTUFrame(unitID, reply)
+			slave.Write(frame)
+		}
+	}()
+
+	transport := NewModbusRTUTransport("/dev/ttyTEST", zap.NewNop())
+	transport.dial = func() (io.ReadWriteCloser, error) { return master, nil }
+	t.Cleanup(func() { transport.Close() })
+
+	handler := NewModbusHandler(zap.NewNop()).(*ModbusHandler)
+	devices, err := handler.ScanSerialBus(context.Background(), transport, &DiscoveryOptions{
+		UnitIDs: []byte{BroadcastUnitID, 3, 4, 5},
+		Timeout: 30 * time.Millisecond,
+	})
+	assert.NoError(t, err)
+	if !assert.Len(t, devices, 2) {
+		return
+	}
+
+	assert.Equal(t, "modbus-rtu", devices[0].Protocol)
+	assert.Equal(t, "/dev/ttyTEST", devices[0].Address)
+	assert.Equal(t, byte(4), devices[0].UnitID)
+	assert.Equal(t, "PX-1", devices[0].Identification.Objects[DeviceIDObjectProductCode])
+	assert.Equal(t, byte(5), devices[1].UnitID)
+	assert.Nil(t, devices[1].Identification)
+	assert.Equal(t, "modbus-/dev/ttyTEST-5", devices[1].Device().ID)
+
+	ctx, cancel := context.WithCancel(context.Background())
+	cancel()
+	_, err = handler.ScanSerialBus(ctx, transport, nil)
+	assert.ErrorIs(t, err, context.Canceled)
+}
+
+func TestModbusHandler_ScanSerialBus_Silent(t *testing.T) {
+	port := newSerialTestPort(5*time.Millisecond, func(request []byte) []serialTestChunk { return nil })
+	transport := NewModbusRTUTransport("/dev/ttyTEST", zap.NewNop())
+	transport.RetryPolicy = DefaultRetryPolicy()
+	transport.dial = func() (io.ReadWriteCloser, error) { return port, nil }
+	t.Cleanup(func() { transport.Close() })
+
+	// Each unit is given the probe timeout once, on a port without read
+	// deadlines and despite the retry policy
+	handler := NewModbusHandler(zap.NewNop()).(*ModbusHandler)
+	start := time.Now()
+	devices, err := handler.ScanSerialBus(context.Background(), transport, &DiscoveryOptions{
+		UnitIDs: []byte{1, 2, 3, 4, 5},
+		Timeout: 20 * time.Millisecond,
+	})
+	assert.NoError(t, err)
+	assert.Empty(t, devices)
+	assert.GreaterOrEqual(t, time.Since(start), 100*time.Millisecond)
+	assert.Less(t, time.Since(start), time.Second)
+	assert.Zero(t, transport.Retries())
+}
@@ -0,0 +1,74 @@
+package protocols
+
+import (
+	"fmt"
+	"strings"
+)
+
+// Modbus Error Handling
+
+// ModbusError represents a Modbus specific error
+type ModbusError struct {
+	*ProtocolError
+	TransactionID uint16
+	UnitID        byte
+	FunctionCode  byte
+	ErrorCategory ModbusErrorCategory
+}
+
+// ModbusErrorCategory categorizes different types of Modbus errors
+type ModbusErrorCategory string
+
+const (
+	ModbusErrorConnection          ModbusErrorCategory = "CONNECTION"
+	ModbusErrorTimeout             ModbusErrorCategory = "TIMEOUT"
+	ModbusErrorTransactionMismatch ModbusErrorCategory = "TRANSACTION_MISMATCH"
+	ModbusErrorFrame               ModbusErrorCategory = "FRAME"
+	ModbusErrorCRC                 ModbusErrorCategory = "CRC"
+	ModbusErrorBroadcast           ModbusErrorCategory = "BROADCAST"
+	ModbusErrorInvalidQuantity     ModbusErrorCategory = "INVALID_QUANTITY"
+	ModbusErrorInvalidAddress      ModbusErrorCategory = "INVALID_ADDRESS"
+	ModbusErrorInvalidValue        ModbusErrorCategory = "INVALID_VALUE"
+	ModbusErrorByteCountMismatch   ModbusErrorCategory = "BYTE_COUNT_MISMATCH"
+	ModbusErrorUnitOffline         ModbusErrorCategory = "UNIT_OFFLINE"
+)
+
+// NewModbusError creates a new Modbus specific error
+func NewModbusError(category ModbusErrorCategory, message string, operation string) *ModbusError {
+	protocolError := NewProtocolError(string(category), message, operation)
+	protocolError.Recoverable = category == ModbusErrorConnection || category == ModbusErrorTimeout ||
+		category == ModbusErrorCRC
+
+	return &ModbusError{
+		ProtocolError: protocolError,
+		ErrorCategory: category,
+	}
+}
+
+// Error returns the error message
+func (e *ModbusError) Error() string {
+	var parts []string
+
+	parts = append(parts, fmt.Sprintf("[%s]", e.ErrorCategory))
+
+	if e.UnitID != 0 {
+		parts = append(parts, fmt.Sprintf("Unit: %d", e.UnitID))
+	}
+
+	if e.FunctionCode != 0 {
+		parts = append(parts, fmt.Sprintf("Function: 0x%02X", e.FunctionCode))
+	}
+
+	if e.TransactionID != 0 {
+		parts = append(parts, fmt.Sprintf("Transaction: %d", e.TransactionID))
+	}
+
+	parts = append(parts, e.ProtocolError.Error())
+
+	return strings.Join(parts, " ")
+}
+
+// IsRecoverable determines if the error is recoverable
+func (e *ModbusError) IsRecoverable() bool {
+	return e.ProtocolError.Recoverable
+}
@@ -0,0 +1,128 @@
+package protocols
+
+import (
+	"encoding/binary"
+	"errors"
+	"fmt"
+
+	"github.com/goburrow/modbus"
+)
+
+// Modbus Exception Responses
+
+// ModbusExceptionCode is the exception code carried in an exception response
+type ModbusExceptionCode byte
+
+const (
+	ExceptionIllegalFunction            ModbusExceptionCode = 0x01
+	ExceptionIllegalDataAddress         ModbusExceptionCode = 0x02
+	ExceptionIllegalDataValue           ModbusExceptionCode = 0x03
+	ExceptionServerDeviceFailure        ModbusExceptionCode = 0x04
+	ExceptionAcknowledge                ModbusExceptionCode = 0x05
+	ExceptionServerDeviceBusy           ModbusExceptionCode = 0x06
+	ExceptionNegativeAcknowledge        ModbusExceptionCode = 0x07
+	ExceptionMemoryParityError          ModbusExceptionCode = 0x08
+	ExceptionGatewayPathUnavailable     ModbusExceptionCode = 0x0A
+	ExceptionGatewayTargetFailedRespond ModbusExceptionCode = 0x0B
+)
+
+// exceptionFunctionFlag is set on the function code of an exception response
+const exceptionFunctionFlag = 0x80
+
+// String returns a human-readable description of the exception code
+func (c ModbusExceptionCode) String() string {
+	switch c {
+	case ExceptionIllegalFunction:
+		return "Illegal function"
+	case ExceptionIllegalDataAddress:
+		return "Illegal data address"
+	case ExceptionIllegalDataValue:
+		return "Illegal data value"
+	case ExceptionServerDeviceFailure:
+		return "Server device failure"
+	case ExceptionAcknowledge:
+		return "Acknowledge"
+	case ExceptionServerDeviceBusy:
+		return "Server device busy"
+	case ExceptionNegativeAcknowledge:
+		return "Negative acknowledge"
+	case ExceptionMemoryParityError:
+		return "Memory parity error"
+	case ExceptionGatewayPathUnavailable:
+		return "Gateway path unavailable"
+	case ExceptionGatewayTargetFailedRespond:
+		return "Gateway target device failed to respond"
+	default:
+		return fmt.Sprintf("Unknown exception (0x%02X)", byte(c))
+	}
+}
+
+// ExceptionResponsePDU builds the exception response PDU for a request with
+// the given function code
+func ExceptionResponsePDU(functionCode byte, code ModbusExceptionCode) *modbus.ProtocolDataUnit {
+	return &modbus.ProtocolDataUnit{
+		FunctionCode: functionCode | exceptionFunctionFlag,
+		Data:         []byte{byte(code)},
+	}
+}
+
+// EncodeModbusException builds a complete Modbus TCP exception response frame
+func EncodeModbusException(transactionID uint16, unitID byte, functionCode byte, code ModbusExceptionCode) []byte {
+	return appendModbusException(nil, transactionID, unitID, functionCode, code)
+}
+
+// appendModbusException appends the frame built by EncodeModbusException
+// to dst
+func appendModbusException(dst []byte, transactionID uint16, unitID byte, functionCode byte, code ModbusExceptionCode) []byte {
+	dst, frame := growFrame(dst, modbusTCPHeaderSize+2)
+	binary.BigEndian.PutUint16(frame[0:2], transactionID)
+	binary.BigEndian.PutUint16(frame[2:4], modbusTCPProtocolID)
+	binary.BigEndian.PutUint16(frame[4:6], 3)
+	frame[6] = unitID
+	frame[7] = functionCode | exceptionFunctionFlag
+	frame[8] = byte(code)
+	return dst
+}
+
+// EncodeExceptionForRequest builds the Modbus TCP exception response that
+// answers the given request frame
+func EncodeExceptionForRequest(aduRequest []byte, code ModbusExceptionCode) ([]byte, error) {
+	if len(aduRequest) < modbusTCPHeaderSize+1 {
+		return nil, NewModbusError(ModbusErrorFrame, "request frame too short", "encode_exception")
+	}
+
+	transactionID := binary.BigEndian.Uint16(aduRequest[0:2])
+	return EncodeModbusException(transactionID, aduRequest[6], aduRequest[7]&^exceptionFunctionFlag, code), nil
+}
+
+// IsExceptionResponse reports whether pdu is an exception response
+func IsExceptionResponse(pdu *modbus.ProtocolDataUnit) bool {
+	return pdu.FunctionCode&exceptionFunctionFlag != 0
+}
+
+// ExceptionCodeForError maps an error raised while serving a request to the
+// exception code that should be returned to the requester
+func ExceptionCodeForError(err error) ModbusExceptionCode {
+	var exception *modbus.ModbusError
+	if errors.As(err, &exception) {
+		return ModbusExceptionCode(exception.ExceptionCode)
+	}
+
+	var modbusErr *ModbusError
+	if errors.As(err, &modbusErr) {
+		switch modbusErr.ErrorCategory {
+		case ModbusErrorBroadcast:
+			return ExceptionIllegalFunction
+		case ModbusErrorInvalidAddress:
+			return ExceptionIllegalDataAddress
+		case ModbusErrorInvalidQuantity, ModbusErrorInvalidValue, ModbusErrorByteCountMismatch:
+			return ExceptionIllegalDataValue
+		case ModbusErrorTimeout, ModbusErrorCRC, ModbusErrorUnitOffline:
+			return ExceptionGatewayTargetFailedRespond
+		case ModbusErrorConnection:
+			return ExceptionGatewayPathUnavailable
+		}
+	}
+
+	return ExceptionServerDeviceFailure
+}
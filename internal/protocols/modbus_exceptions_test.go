@@ -0,0 +1,39 @@
+package protocols
+
+import (
+	"fmt"
+	"testing"
+
+	"github.com/goburrow/modbus"
+	"github.com/stretchr/testify/assert"
+)
+
+func TestEncodeModbusException(t *testing.T) {
+	frame := EncodeModbusException(0x1234, 0x11, 0x03, ExceptionIllegalDataAddress)
+	assert.Equal(t, []byte{0x12, 0x34, 0x00, 0x00, 0x00, 0x03, 0x11, 0x83, 0x02}, frame)
+
+	request := []byte{0x00, 0x07, 0x00, 0x00, 0x00, 0x06, 0x01, 0x10, 0x00, 0x00, 0x00, 0x01}
+	response, err := EncodeExceptionForRequest(request, ExceptionIllegalFunction)
+	assert.NoError(t, err)
+	assert.Equal(t, []byte{0x00, 0x07, 0x00, 0x00, 0x00, 0x03, 0x01, 0x90, 0x01}, response)
+
+	_, err = EncodeExceptionForRequest([]byte{0x00, 0x01}, ExceptionIllegalFunction)
+	assert.Error(t, err)
+}
+
+func TestExceptionResponsePDU(t *testing.T) {
+	pdu := ExceptionResponsePDU(0x06, ExceptionServerDeviceBusy)
+	assert.True(t, IsExceptionResponse(pdu))
+	assert.Equal(t, byte(0x86), pdu.FunctionCode)
+	assert.Equal(t, []byte{0x06}, pdu.Data)
+}
+
+func TestExceptionCodeForError(t *testing.T) {
+	assert.Equal(t, ExceptionIllegalDataValue,
+		ExceptionCodeForError(&modbus.ModbusError{FunctionCode: 0x83, ExceptionCode: 0x03}))
+	assert.Equal(t, ExceptionGatewayTargetFailedRespond,
+		ExceptionCodeForError(NewModbusError(ModbusErrorTimeout, "timeout", "send")))
+	assert.Equal(t, ExceptionGatewayPathUnavailable,
+		ExceptionCodeForError(fmt.Errorf("wrapped: %w", NewModbusError(ModbusErrorConnection, "down", "send"))))
+	assert.Equal(t, ExceptionServerDeviceFailure, ExceptionCodeForError(fmt.Errorf("other")))
+}
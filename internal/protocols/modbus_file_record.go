@@ -0,0 +1,204 @@
+package protocols
+
+import (
+	"bytes"
+	"encoding/binary"
+	"fmt"
+	"time"
+)
+
+// Modbus File Record Access (0x14 / 0x15)
+
+const (
+	fileRecordReferenceType = 0x06
+	maxFileRecordNumber     = 0x270F
+	fileSubRequestHeaderLen = 7
+	maxFileRecordByteCount  = 0xF5
+)
+
+// FileRecordRequest identifies a group of registers to read from a file
+type FileRecordRequest struct {
+	FileNumber   uint16 `json:"file_number"`
+	RecordNumber uint16 `json:"record_number"`
+	RecordLength uint16 `json:"record_length"`
+}
+
+// FileRecord is a group of registers read from or written to a file
+type FileRecord struct {
+	FileNumber   uint16   `json:"file_number"`
+	RecordNumber uint16   `json:"record_number"`
+	Data         []uint16 `json:"data"`
+}
+
+// ReadFileRecords reads one or more record groups in a single Read File
+// Record transaction. The returned slice has one entry per request.
+func (m *ModbusHandler) ReadFileRecords(device *Device, requests []FileRecordRequest) ([][]uint16, error) {
+	data, err := encodeReadFileRecordRequest(requests)
+	if err != nil {
+		return nil, err
+	}
+
+	responseData, err := m.sendFileRecordPDU(device, ReadFileRecord, data)
+	if err != nil {
+		return nil, err
+	}
+
+	return parseReadFileRecordResponse(responseData, requests)
+}
+
+// WriteFileRecords writes one or more record groups in a single Write File
+// Record transaction
+func (m *ModbusHandler) WriteFileRecords(device *Device, records []FileRecord) error {
+	data, err := encodeWriteFileRecordRequest(records)
+	if err != nil {
+		return err
+	}
+
+	responseData, err := m.sendFileRecordPDU(device, WriteFileRecord, data)
+	if err != nil {
+		return err
+	}
+
+	// The normal response is an echo of the request
+	if !bytes.Equal(responseData, data) {
+		return fmt.Errorf("write file record response does not echo the request")
+	}
+	return nil
+}
+
+func (m *ModbusHandler) sendFileRecordPDU(device *Device, functionCode ModbusFunctionCode, data []byte) ([]byte, error) {
+	conn, err := m.getConnection(device)
+	if err != nil {
+		return nil, err
+	}
+
+	conn.mutex.Lock()
+	defer conn.mutex.Unlock()
+
+	conn.lastUsed = time.Now()
+
+	return m.sendPDU(conn, byte(functionCode), data)
+}
+
+func validateFileReference(fileNumber, recordNumber uint16) error {
+	if fileNumber == 0 {
+		return fmt.Errorf("file number must be in range [1, 65535]")
+	}
+	if recordNumber > maxFileRecordNumber {
+		return fmt.Errorf("record number %d out of range [0, %d]", recordNumber, maxFileRecordNumber)
+	}
+	return nil
+}
+
+// encodeReadFileRecordRequest builds the Read File Record request data
+// (without the function code)
+func encodeReadFileRecordRequest(requests []FileRecordRequest) ([]byte, error) {
+	if len(requests) == 0 {
+		return nil, fmt.Errorf("at least one file record request is required")
+	}
+
+	byteCount := len(requests) * fileSubRequestHeaderLen
+	if byteCount > maxFileRecordByteCount {
+		return nil, fmt.Errorf("%d sub-requests exceed the maximum request size", len(requests))
+	}
+
+	// Each response group carries a 2 byte header plus the record data
+	responseSize := 1
+	for i, request := range requests {
+		if err := validateFileReference(request.FileNumber, request.RecordNumber); err != nil {
+			return nil, fmt.Errorf("sub-request %d: %w", i, err)
+		}
+		if request.RecordLength == 0 {
+			return nil, fmt.Errorf("sub-request %d: record length must be at least 1", i)
+		}
+		responseSize += 2 + int(request.RecordLength)*2
+	}
+	if responseSize > maxFileRecordByteCount+1 {
+		return nil, fmt.Errorf("requested records exceed the maximum response size")
+	}
+
+	data := make([]byte, 1, 1+byteCount)
+	data[0] = byte(byteCount)
+	for _, request := range requests {
+		data = append(data, fileRecordReferenceType)
+		data = binary.BigEndian.AppendUint16(data, request.FileNumber)
+		data = binary.BigEndian.AppendUint16(data, request.RecordNumber)
+		data = binary.BigEndian.AppendUint16(data, request.RecordLength)
+	}
+
+	return data, nil
+}
+
+// parseReadFileRecordResponse decodes Read File Record response data and
+// checks each group against the length that was requested
+func parseReadFileRecordResponse(data []byte, requests []FileRecordRequest) ([][]uint16, error) {
+	if len(data) < 1 {
+		return nil, fmt.Errorf("read file record response is empty")
+	}
+	if int(data[0]) != len(data)-1 {
+		return nil, fmt.Errorf("response data length %d does not match payload %d", data[0], len(data)-1)
+	}
+
+	records := make([][]uint16, 0, len(requests))
+	offset := 1
+	for i, request := range requests {
+		if offset+2 > len(data) {
+			return nil, fmt.Errorf("sub-response %d missing", i)
+		}
+
+		groupLength := int(data[offset])
+		if data[offset+1] != fileRecordReferenceType {
+			return nil, fmt.Errorf("sub-response %d has invalid reference type 0x%02X", i, data[offset+1])
+		}
+		if groupLength != 1+int(request.RecordLength)*2 {
+			return nil, fmt.Errorf("sub-response %d length %d does not match requested %d registers",
+				i, groupLength, request.RecordLength)
+		}
+		if offset+1+groupLength > len(data) {
+			return nil, fmt.Errorf("sub-response %d truncated", i)
+		}
+
+		records = append(records, bytesToRegisters(data[offset+2:offset+1+groupLength]))
+		offset += 1 + groupLength
+	}
+
+	if offset != len(data) {
+		return nil, fmt.Errorf("%d unexpected trailing bytes in response", len(data)-offset)
+	}
+
+	return records, nil
+}
+
+// encodeWriteFileRecordRequest builds the Write File Record request data
+// (without the function code)
+func encodeWriteFileRecordRequest(records []FileRecord) ([]byte, error) {
+	if len(records) == 0 {
+		return nil, fmt.Errorf("at least one file record is required")
+	}
+
+	byteCount := 0
+	for i, record := range records {
+		if err := validateFileReference(record.FileNumber, record.RecordNumber); err != nil {
+			return nil, fmt.Errorf("record %d: %w", i, err)
+		}
+		if len(record.Data) == 0 {
+			return nil, fmt.Errorf("record %d: record data must not be empty", i)
+		}
+		byteCount += fileSubRequestHeaderLen + len(record.Data)*2
+	}
+	if byteCount > maxFileRecordByteCount {
+		return nil, fmt.Errorf("records exceed the maximum request size")
+	}
+
+	data := make([]byte, 1, 1+byteCount)
+	data[0] = byte(byteCount)
+	for _, record := range records {
+		data = append(data, fileRecordReferenceType)
+		data = binary.BigEndian.AppendUint16(data, record.FileNumber)
+		data = binary.BigEndian.AppendUint16(data, record.RecordNumber)
+		data = binary.BigEndian.AppendUint16(data, uint16(len(record.Data)))
+		data = append(data, registersToBytes(record.Data)...)
+	}
+
+	return data, nil
+}
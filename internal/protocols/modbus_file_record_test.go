@@ -0,0 +1,66 @@
+package protocols
+
+import (
+	"testing"
+
+	"github.com/stretchr/testify/assert"
+)
+
+func TestModbusReadFileRecords(t *testing.T) {
+	device := startModbusTestServer(t, func(unitID byte, pdu []byte) []byte {
+		// Example from the Modbus specification: two groups from files 4 and 3
+		assert.Equal(t, []byte{
+			0x14, 0x0E,
+			0x06, 0x00, 0x04, 0x00, 0x01, 0x00, 0x02,
+			0x06, 0x00, 0x03, 0x00, 0x09, 0x00, 0x02,
+		}, pdu)
+
+		return []byte{
+			0x14, 0x0C,
+			0x05, 0x06, 0x0D, 0xFE, 0x00, 0x20,
+			0x05, 0x06, 0x33, 0xCD, 0x00, 0x40,
+		}
+	})
+	handler := connectTestDevice(t, device)
+
+	records, err := handler.ReadFileRecords(device, []FileRecordRequest{
+		{FileNumber: 4, RecordNumber: 1, RecordLength: 2},
+		{FileNumber: 3, RecordNumber: 9, RecordLength: 2},
+	})
+	assert.NoError(t, err)
+	assert.Equal(t, [][]uint16{{0x0DFE, 0x0020}, {0x33CD, 0x0040}}, records)
+}
+
+func TestModbusWriteFileRecords(t *testing.T) {
+	device := startModbusTestServer(t, func(unitID byte, pdu []byte) []byte {
+		assert.Equal(t, []byte{
+			0x15, 0x0D,
+			0x06, 0x00, 0x04, 0x00, 0x07, 0x00, 0x03, 0x06, 0xAF, 0x04, 0xBE, 0x10, 0x0D,
+		}, pdu)
+		return pdu
+	})
+	handler := connectTestDevice(t, device)
+
+	err := handler.WriteFileRecords(device, []FileRecord{
+		{FileNumber: 4, RecordNumber: 7, Data: []uint16{0x06AF, 0x04BE, 0x100D}},
+	})
+	assert.NoError(t, err)
+}
+
+func TestFileRecordValidation(t *testing.T) {
+	_, err := encodeReadFileRecordRequest(nil)
+	assert.Error(t, err)
+
+	_, err = encodeReadFileRecordRequest([]FileRecordRequest{{FileNumber: 0, RecordNumber: 1, RecordLength: 1}})
+	assert.Error(t, err)
+
+	_, err = encodeReadFileRecordRequest([]FileRecordRequest{{FileNumber: 1, RecordNumber: 10000, RecordLength: 1}})
+	assert.Error(t, err)
+
+	_, err = encodeWriteFileRecordRequest([]FileRecord{{FileNumber: 1, Data: make([]uint16, 200)}})
+	assert.Error(t, err)
+
+	_, err = parseReadFileRecordResponse([]byte{0x03, 0x05, 0x06, 0x00},
+		[]FileRecordRequest{{FileNumber: 1, RecordLength: 2}})
+	assert.Error(t, err)
+}
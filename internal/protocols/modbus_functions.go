@@ -0,0 +1,363 @@
+package protocols
+
+import (
+	"encoding/binary"
+	"fmt"
+	"time"
+
+	"github.com/goburrow/modbus"
+)
+
+// Extended Modbus Function Codes
+//
+// The methods below expose Modbus functions that do not map onto the generic
+// tag read/write model of ProtocolHandler.
+
+// Modbus spec quantity limits
+const (
+	MaxReadWriteReadQuantity  = 125
+	MaxReadWriteWriteQuantity = 121
+)
+
+// ReadWriteMultipleRegisters writes values starting at writeAddress and then
+// reads readQuantity holding registers starting at readAddress, as a single
+// atomic transaction (function code 0x17)
+func (m *ModbusHandler) ReadWriteMultipleRegisters(device *Device, readAddress, readQuantity, writeAddress uint16, values []uint16) ([]uint16, error) {
+	if readQuantity < 1 || readQuantity > MaxReadWriteReadQuantity {
+		return nil, fmt.Errorf("read quantity %d out of range [1, %d]", readQuantity, MaxReadWriteReadQuantity)
+	}
+	if len(values) < 1 || len(values) > MaxReadWriteWriteQuantity {
+		return nil, fmt.Errorf("write quantity %d out of range [1, %d]", len(values), MaxReadWriteWriteQuantity)
+	}
+
+	conn, err := m.getConnection(device)
+	if err != nil {
+		return nil, err
+	}
+
+	conn.mutex.Lock()
+	defer conn.mutex.Unlock()
+
+	conn.lastUsed = time.Now()
+
+	result, err := conn.client.ReadWriteMultipleRegisters(
+		readAddress, readQuantity, writeAddress, uint16(len(values)), registersToBytes(values))
+	if err != nil {
+		return nil, err
+	}
+
+	if len(result) != int(readQuantity)*2 {
+		return nil, fmt.Errorf("response contains %d bytes, expected %d", len(result), int(readQuantity)*2)
+	}
+
+	return bytesToRegisters(result), nil
+}
+
+// MaskWriteRegister modifies the holding register at address on the device
+// using AND and OR masks (function code 0x16), so individual bits can be
+// changed without a read-modify-write cycle. The device computes
+// (current AND andMask) OR (orMask AND NOT andMask).
+func (m *ModbusHandler) MaskWriteRegister(device *Device, address, andMask, orMask uint16) error {
+	conn, err := m.getConnection(device)
+	if err != nil {
+		return err
+	}
+
+	conn.mutex.Lock()
+	defer conn.mutex.Unlock()
+
+	conn.lastUsed = time.Now()
+
+	// The client verifies that the response echoes address and both masks
+	_, err = conn.client.MaskWriteRegister(address, andMask, orMask)
+	return err
+}
+
+// WriteRegisterBit sets or clears a single bit (0-15) of a holding register
+// using Mask Write Register
+func (m *ModbusHandler) WriteRegisterBit(device *Device, address uint16, bit uint, value bool) error {
+	andMask, orMask, err := RegisterBitMasks(bit, value)
+	if err != nil {
+		return err
+	}
+	return m.MaskWriteRegister(device, address, andMask, orMask)
+}
+
+// RegisterBitMasks returns the AND/OR masks that set or clear one bit
+func RegisterBitMasks(bit uint, value bool) (andMask, orMask uint16, err error) {
+	if bit > 15 {
+		return 0, 0, fmt.Errorf("register bit %d out of range [0, 15]", bit)
+	}
+
+	andMask = ^uint16(1 << bit)
+	if value {
+		orMask = 1 << bit
+	}
+	return andMask, orMask, nil
+}
+
+// ApplyMaskWrite computes the result of a Mask Write Register request against
+// the current register contents, as a server applies it
+func ApplyMaskWrite(current, andMask, orMask uint16) uint16 {
+	return (current & andMask) | (orMask &^ andMask)
+}
+
+// Read Device Identification (0x2B / MEI type 0x0E)
+
+// DeviceIDCategory selects which identification objects are requested
+type DeviceIDCategory byte
+
+const (
+	DeviceIDBasic    DeviceIDCategory = 0x01 // Objects 0x00-0x02
+	DeviceIDRegular  DeviceIDCategory = 0x02 // Objects 0x00-0x7F
+	DeviceIDExtended DeviceIDCategory = 0x03 // Objects 0x00-0xFF
+	DeviceIDSpecific DeviceIDCategory = 0x04 // One specific object
+)
+
+// Standard device identification object IDs
+const (
+	DeviceIDObjectVendorName          byte = 0x00
+	DeviceIDObjectProductCode         byte = 0x01
+	DeviceIDObjectMajorMinorRevision  byte = 0x02
+	DeviceIDObjectVendorURL           byte = 0x03
+	DeviceIDObjectProductName         byte = 0x04
+	DeviceIDObjectModelName           byte = 0x05
+	DeviceIDObjectUserApplicationName byte = 0x06
+)
+
+const (
+	meiTypeReadDeviceID = 0x0E
+
+	// maxDeviceIDRequests bounds stream continuation against devices that
+	// keep reporting "more follows"
+	maxDeviceIDRequests = 32
+)
+
+// DeviceIdentification holds the objects returned by Read Device Identification
+type DeviceIdentification struct {
+	ConformityLevel byte            `json:"conformity_level"`
+	Objects         map[byte]string `json:"objects"`
+}
+
+// deviceIDPage is one response of a possibly multi-part identification stream
+type deviceIDPage struct {
+	conformityLevel byte
+	moreFollows     bool
+	nextObjectID    byte
+	objects         map[byte]string
+}
+
+// ReadDeviceIdentification reads the identification objects of category
+// from the device, following stream continuation until all objects have
+// been received
+func (m *ModbusHandler) ReadDeviceIdentification(device *Device, category DeviceIDCategory) (*DeviceIdentification, error) {
+	return m.readDeviceIdentification(device, category, DeviceIDObjectVendorName)
+}
+
+// ReadDeviceIdentificationObject reads a single identification object
+func (m *ModbusHandler) ReadDeviceIdentificationObject(device *Device, objectID byte) (string, error) {
+	identification, err := m.readDeviceIdentification(device, DeviceIDSpecific, objectID)
+	if err != nil {
+		return "", err
+	}
+
+	value, exists := identification.Objects[objectID]
+	if !exists {
+		return "", fmt.Errorf("object 0x%02X not returned by device", objectID)
+	}
+	return value, nil
+}
+
+func (m *ModbusHandler) readDeviceIdentification(device *Device, category DeviceIDCategory, objectID byte) (*DeviceIdentification, error) {
+	if category < DeviceIDBasic || category > DeviceIDSpecific {
+		return nil, fmt.Errorf("invalid device identification category: %d", category)
+	}
+
+	conn, err := m.getConnection(device)
+	if err != nil {
+		return nil, err
+	}
+
+	conn.mutex.Lock()
+	defer conn.mutex.Unlock()
+
+	conn.lastUsed = time.Now()
+
+	return identifyDevice(func(request []byte) ([]byte, error) {
+		return m.sendPDU(conn, byte(EncapsulatedInterfaceTransport), request)
+	}, category, objectID)
+}
+
+// identifyDevice runs a Read Device Identification stream, calling send with
+// each request's data and expecting the response data (without function
+// codes)
+func identifyDevice(send func(request []byte) ([]byte, error), category DeviceIDCategory, objectID byte) (*DeviceIdentification, error) {
+	identification := &DeviceIdentification{Objects: make(map[byte]string)}
+
+	for i := 0; i < maxDeviceIDRequests; i++ {
+		data, err := send([]byte{meiTypeReadDeviceID, byte(category), objectID})
+		if err != nil {
+			return nil, err
+		}
+
+		page, err := parseDeviceIDResponse(data, category)
+		if err != nil {
+			return nil, err
+		}
+
+		identification.ConformityLevel = page.conformityLevel
+		for id, value := range page.objects {
+			identification.Objects[id] = value
+		}
+
+		if !page.moreFollows || category == DeviceIDSpecific {
+			return identification, nil
+		}
+		objectID = page.nextObjectID
+	}
+
+	return nil, fmt.Errorf("device identification stream did not terminate after %d requests", maxDeviceIDRequests)
+}
+
+// parseDeviceIDResponse decodes a Read Device Identification response PDU
+// (without the function code)
+func parseDeviceIDResponse(data []byte, category DeviceIDCategory) (*deviceIDPage, error) {
+	if len(data) < 6 {
+		return nil, fmt.Errorf("device identification response too short: %d bytes", len(data))
+	}
+	if data[0] != meiTypeReadDeviceID {
+		return nil, fmt.Errorf("unexpected MEI type: 0x%02X", data[0])
+	}
+	if DeviceIDCategory(data[1]) != category {
+		return nil, fmt.Errorf("response category %d does not match request %d", data[1], category)
+	}
+
+	page := &deviceIDPage{
+		conformityLevel: data[2],
+		moreFollows:     data[3] == 0xFF,
+		nextObjectID:    data[4],
+		objects:         make(map[byte]string),
+	}
+
+	count := int(data[5])
+	offset := 6
+	for i := 0; i < count; i++ {
+		if offset+2 > len(data) {
+			return nil, fmt.Errorf("object %d header truncated", i)
+		}
+		id := data[offset]
+		length := int(data[offset+1])
+		offset += 2
+
+		if offset+length > len(data) {
+			return nil, fmt.Errorf("object 0x%02X value truncated", id)
+		}
+		page.objects[id] = string(data[offset : offset+length])
+		offset += length
+	}
+
+	return page, nil
+}
+
+// applyTo copies identification objects into a DeviceInfo
+func (d *DeviceIdentification) applyTo(info *DeviceInfo) {
+	if vendor, ok := d.Objects[DeviceIDObjectVendorName]; ok {
+		info.Vendor = vendor
+	}
+	if model, ok := d.Objects[DeviceIDObjectModelName]; ok {
+		info.Model = model
+	} else if product, ok := d.Objects[DeviceIDObjectProductName]; ok {
+		info.Model = product
+	}
+	if revision, ok := d.Objects[DeviceIDObjectMajorMinorRevision]; ok {
+		info.FirmwareVersion = revision
+	}
+
+	if info.CustomInfo == nil {
+		info.CustomInfo = make(map[string]string)
+	}
+	for id, value := range d.Objects {
+		switch id {
+		case DeviceIDObjectProductCode:
+			info.CustomInfo["product_code"] = value
+		case DeviceIDObjectVendorURL:
+			info.CustomInfo["vendor_url"] = value
+		case DeviceIDObjectProductName:
+			info.CustomInfo["product_name"] = value
+		case DeviceIDObjectUserApplicationName:
+			info.CustomInfo["user_application_name"] = value
+		case DeviceIDObjectVendorName, DeviceIDObjectModelName, DeviceIDObjectMajorMinorRevision:
+		default:
+			info.CustomInfo[fmt.Sprintf("object_0x%02X", id)] = value
+		}
+	}
+}
+
+// sendPDU sends a raw request PDU over the connection and returns the
+// response data (without the function code). Exception responses are
+// returned as *modbus.ModbusError.
+func (m *ModbusHandler) sendPDU(conn *ModbusConnection, functionCode byte, data []byte) ([]byte, error) {
+	return m.sendUnitPDU(conn, conn.handler.SlaveId, functionCode, data)
+}
+
+// sendUnitPDU is sendPDU addressed to a specific unit identifier
+func (m *ModbusHandler) sendUnitPDU(conn *ModbusConnection, unitID byte, functionCode byte, data []byte) ([]byte, error) {
+	return m.sendUnitPDUWithPolicy(conn, unitID, functionCode, data, conn.handler.RetryPolicy)
+}
+
+func (m *ModbusHandler) sendUnitPDUWithPolicy(conn *ModbusConnection, unitID byte, functionCode byte, data []byte, policy *RetryPolicy) ([]byte, error) {
+	request := &modbus.ProtocolDataUnit{FunctionCode: functionCode, Data: data}
+
+	aduRequest, err := conn.handler.encode(unitID, request)
+	if err != nil {
+		return nil, err
+	}
+
+	aduResponse, err := conn.handler.SendWithPolicy(aduRequest, policy)
+	if err != nil {
+		return nil, err
+	}
+
+	if err := conn.handler.Verify(aduRequest, aduResponse); err != nil {
+		return nil, err
+	}
+
+	response, err := conn.handler.Decode(aduResponse)
+	if err != nil {
+		return nil, err
+	}
+
+	if response.FunctionCode == functionCode|0x80 {
+		if len(response.Data) < 1 {
+			return nil, NewModbusError(ModbusErrorFrame, "exception response without exception code", "receive")
+		}
+		return nil, &modbus.ModbusError{FunctionCode: response.FunctionCode, ExceptionCode: response.Data[0]}
+	}
+
+	if response.FunctionCode != functionCode {
+		return nil, NewModbusError(ModbusErrorFrame,
+			fmt.Sprintf("response function code 0x%02X does not match request 0x%02X", response.FunctionCode, functionCode),
+			"receive")
+	}
+
+	return response.Data, nil
+}
+
+// registersToBytes encodes register values big-endian as sent on the wire
+func registersToBytes(registers []uint16) []byte {
+	data := make([]byte, len(registers)*2)
+	for i, register := range registers {
+		binary.BigEndian.PutUint16(data[i*2:], register)
+	}
+	return data
+}
+
+// bytesToRegisters decodes big-endian register values; a trailing odd byte
+// is ignored
+func bytesToRegisters(data []byte) []uint16 {
+	registers := make([]uint16, len(data)/2)
+	for i := range registers {
+		registers[i] = binary.BigEndian.Uint16(data[i*2:])
+	}
+	return registers
+}
@@ -0,0 +1,190 @@
+package protocols
+
+import (
+	"encoding/binary"
+	"io"
+	"net"
+	"strconv"
+	"testing"
+
+	"github.com/stretchr/testify/assert"
+	"go.uber.org/zap"
+)
+
+// startModbusTestServer runs a single-connection Modbus TCP server that
+// answers every request with the PDU returned by respond
+func startModbusTestServer(t *testing.T, respond func(unitID byte, pdu []byte) []byte) *Device {
+	listener, err := net.Listen("tcp", "127.0.0.1:0")
+	if err != nil {
+		t.Fatalf("failed to listen: %v", err)
+	}
+	t.Cleanup(func() { listener.Close() })
+
+	go func() {
+		conn, err := listener.Accept()
+		if err != nil {
+			return
+		}
+		defer conn.Close()
+
+		header := make([]byte, modbusTCPHeaderSize)
+		for {
+			if _, err := io.ReadFull(conn, header); err != nil {
+				return
+			}
+			pdu := make([]byte, int(binary.BigEndian.Uint16(header[4:6]))-1)
+			if _, err := io.ReadFull(conn, pdu); err != nil {
+				return
+			}
+
+			reply := respond(header[6], pdu)
+			response := make([]byte, modbusTCPHeaderSize+len(reply))
+			copy(response[0:4], header[0:4])
+			binary.BigEndian.PutUint16(response[4:6], uint16(1+len(reply)))
+			response[6] = header[6]
+			copy(response[modbusTCPHeaderSize:], reply)
+			conn.Write(response)
+		}
+	}()
+
+	host, portString, _ := net.SplitHostPort(listener.Addr().String())
+	port, _ := strconv.Atoi(portString)
+
+	return &Device{
+		ID:       "test-device",
+		Protocol: "modbus-tcp",
+		Address:  host,
+		Port:     port,
+		Config:   make(map[string]interface{}),
+	}
+}
+
+func connectTestDevice(t *testing.T, device *Device) *ModbusHandler {
+	handler := NewModbusHandler(zap.NewNop()).(*ModbusHandler)
+	if err := handler.Connect(device); err != nil {
+		t.Fatalf("failed to connect: %v", err)
+	}
+	t.Cleanup(func() { handler.Disconnect(device) })
+	return handler
+}
+
+func TestModbusReadWriteMultipleRegisters(t *testing.T) {
+	var written []uint16
+	device := startModbusTestServer(t, func(unitID byte, pdu []byte) []byte {
+		assert.Equal(t, byte(0x17), pdu[0])
+		assert.Equal(t, uint16(0x0010), binary.BigEndian.Uint16(pdu[1:3])) // read address
+		assert.Equal(t, uint16(2), binary.BigEndian.Uint16(pdu[3:5]))      // read quantity
+		assert.Equal(t, uint16(0x0020), binary.BigEndian.Uint16(pdu[5:7])) // write address
+		assert.Equal(t, uint16(1), binary.BigEndian.Uint16(pdu[7:9]))      // write quantity
+		written = bytesToRegisters(pdu[10:])
+
+		return []byte{0x17, 0x04, 0x12, 0x34, 0xAB, 0xCD}
+	})
+	handler := connectTestDevice(t, device)
+
+	registers, err := handler.ReadWriteMultipleRegisters(device, 0x0010, 2, 0x0020, []uint16{0xBEEF})
+	assert.NoError(t, err)
+	assert.Equal(t, []uint16{0x1234, 0xABCD}, registers)
+	assert.Equal(t, []uint16{0xBEEF}, written)
+}
+
+func TestModbusReadWriteMultipleRegisters_QuantityLimits(t *testing.T) {
+	handler := NewModbusHandler(zap.NewNop()).(*ModbusHandler)
+	device := &Device{ID: "test-device"}
+
+	_, err := handler.ReadWriteMultipleRegisters(device, 0, 0, 0, []uint16{1})
+	assert.Error(t, err)
+
+	_, err = handler.ReadWriteMultipleRegisters(device, 0, MaxReadWriteReadQuantity+1, 0, []uint16{1})
+	assert.Error(t, err)
+
+	_, err = handler.ReadWriteMultipleRegisters(device, 0, 1, 0, make([]uint16, MaxReadWriteWriteQuantity+1))
+	assert.Error(t, err)
+}
+
+func TestModbusMaskWriteRegister(t *testing.T) {
+	register := uint16(0x0012)
+	device := startModbusTestServer(t, func(unitID byte, pdu []byte) []byte {
+		assert.Equal(t, byte(0x16), pdu[0])
+		andMask := binary.BigEndian.Uint16(pdu[3:5])
+		orMask := binary.BigEndian.Uint16(pdu[5:7])
+		register = ApplyMaskWrite(register, andMask, orMask)
+
+		// Normal response echoes the request
+		return pdu
+	})
+	handler := connectTestDevice(t, device)
+
+	assert.NoError(t, handler.MaskWriteRegister(device, 4, 0x00F2, 0x0025))
+	assert.Equal(t, uint16(0x0017), register) // Example from the Modbus specification
+
+	assert.NoError(t, handler.WriteRegisterBit(device, 4, 15, true))
+	assert.Equal(t, uint16(0x8017), register)
+
+	assert.NoError(t, handler.WriteRegisterBit(device, 4, 0, false))
+	assert.Equal(t, uint16(0x8016), register)
+}
+
+func TestRegisterBitMasks(t *testing.T) {
+	andMask, orMask, err := RegisterBitMasks(3, true)
+	assert.NoError(t, err)
+	assert.Equal(t, uint16(0xFFF7), andMask)
+	assert.Equal(t, uint16(0x0008), orMask)
+
+	_, _, err = RegisterBitMasks(16, true)
+	assert.Error(t, err)
+}
+
+func TestModbusReadDeviceIdentification_StreamContinuation(t *testing.T) {
+	device := startModbusTestServer(t, func(unitID byte, pdu []byte) []byte {
+		assert.Equal(t, []byte{0x2B, 0x0E, 0x02}, pdu[0:3])
+
+		switch pdu[3] {
+		case 0x00:
+			// First page: vendor and product code, more follows from object 0x02
+			return append([]byte{0x2B, 0x0E, 0x02, 0x82, 0xFF, 0x02, 0x02},
+				append([]byte{0x00, 0x04}, append([]byte("Acme"),
+					append([]byte{0x01, 0x03}, []byte("P42")...)...)...)...)
+		case 0x02:
+			return append([]byte{0x2B, 0x0E, 0x02, 0x82, 0x00, 0x00, 0x01, 0x02, 0x05}, []byte("1.2.3")...)
+		}
+		return []byte{0xAB, 0x02}
+	})
+	handler := connectTestDevice(t, device)
+
+	identification, err := handler.ReadDeviceIdentification(device, DeviceIDRegular)
+	assert.NoError(t, err)
+	assert.Equal(t, byte(0x82), identification.ConformityLevel)
+	assert.Equal(t, "Acme", identification.Objects[DeviceIDObjectVendorName])
+	assert.Equal(t, "P42", identification.Objects[DeviceIDObjectProductCode])
+	assert.Equal(t, "1.2.3", identification.Objects[DeviceIDObjectMajorMinorRevision])
+
+	info, err := handler.GetDeviceInfo(device)
+	assert.NoError(t, err)
+	assert.Equal(t, "Acme", info.Vendor)
+	assert.Equal(t, "1.2.3", info.FirmwareVersion)
+	assert.Equal(t, "P42", info.CustomInfo["product_code"])
+}
+
+func TestModbusReadDeviceIdentification_Exception(t *testing.T) {
+	device := startModbusTestServer(t, func(unitID byte, pdu []byte) []byte {
+		return []byte{0xAB, 0x01} // Illegal function
+	})
+	handler := connectTestDevice(t, device)
+
+	_, err := handler.ReadDeviceIdentification(device, DeviceIDBasic)
+	assert.Error(t, err)
+
+	// GetDeviceInfo falls back to the generic description
+	info, err := handler.GetDeviceInfo(device)
+	assert.NoError(t, err)
+	assert.Equal(t, "Unknown", info.Vendor)
+}
+
+func TestParseDeviceIDResponse_Truncated(t *testing.T) {
+	_, err := parseDeviceIDResponse([]byte{0x0E, 0x01, 0x01, 0x00, 0x00, 0x01, 0x00, 0x05, 'A'}, DeviceIDBasic)
+	assert.Error(t, err)
+
+	_, err = parseDeviceIDResponse([]byte{0x0E, 0x01}, DeviceIDBasic)
+	assert.Error(t, err)
+}
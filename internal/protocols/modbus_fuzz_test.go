@@ -0,0 +1,141 @@
+package protocols
+
+import (
+	"bytes"
+	"testing"
+
+	"github.com/goburrow/modbus"
+)
+
+// Fuzz targets for the frame decoders. They run their seed corpus as part of
+// the normal test suite; explore further with, for example,
+//
+//	go test ./internal/protocols -run '^$' -fuzz FuzzDecodeRTUFrame
+
+func FuzzDecodeRTUFrame(f *testing.F) {
+	for _, pdu := range fuzzSeedResponses() {
+		frame, _ := encodeRTUFrame(1, pdu)
+		f.Add(frame)
+	}
+	f.Add([]byte{0x01, 0x03, 0xFF})
+	f.Add(make([]byte, rtuMaxFrameSize+1))
+
+	f.Fuzz(func(t *testing.T, frame []byte) {
+		unitID, pdu, err := decodeRTUFrame(frame)
+		if err != nil {
+			return
+		}
+		if len(frame) > rtuMaxFrameSize {
+			t.Fatalf("accepted %d byte frame", len(frame))
+		}
+
+		// Anything accepted must survive a round trip unchanged
+		encoded, err := encodeRTUFrame(unitID, pdu)
+		if err != nil {
+			t.Fatalf("re-encoding accepted frame: %v", err)
+		}
+		if !bytes.Equal(encoded, frame) {
+			t.Fatalf("round trip changed frame: % X -> % X", frame, encoded)
+		}
+	})
+}
+
+func FuzzDecodeMBAPFrame(f *testing.F) {
+	for _, pdu := range fuzzSeedResponses() {
+		f.Add(encodeMBAPFrame(1, 1, pdu))
+	}
+	f.Add([]byte{0x00, 0x01, 0x00, 0x00, 0xFF, 0xFF, 0x01, 0x03})
+
+	f.Fuzz(func(t *testing.T, adu []byte) {
+		transactionID, unitID, pdu, err := decodeMBAPFrame(adu)
+		if err != nil {
+			return
+		}
+		if len(adu) > modbusTCPMaxADUSize {
+			t.Fatalf("accepted %d byte frame", len(adu))
+		}
+
+		encoded := encodeMBAPFrame(transactionID, unitID, pdu)
+		if !bytes.Equal(encoded[4:], adu[4:]) || !bytes.Equal(encoded[0:2], adu[0:2]) {
+			t.Fatalf("round trip changed frame: % X -> % X", adu, encoded)
+		}
+	})
+}
+
+func FuzzValidateResponsePDU(f *testing.F) {
+	requests := []*modbus.ProtocolDataUnit{
+		{FunctionCode: 0x01, Data: []byte{0x00, 0x00, 0x00, 0x0A}},
+		{FunctionCode: 0x03, Data: []byte{0x00, 0x00, 0x00, 0x02}},
+		{FunctionCode: 0x07},
+		{FunctionCode: 0x0B},
+		{FunctionCode: 0x0C},
+		{FunctionCode: 0x10, Data: []byte{0x00, 0x01, 0x00, 0x01, 0x02, 0x00, 0x0A}},
+		{FunctionCode: 0x14, Data: []byte{0x07, 0x06, 0x00, 0x04, 0x00, 0x01, 0x00, 0x02}},
+		{FunctionCode: 0x17, Data: []byte{0x00, 0x00, 0x00, 0x01, 0x00, 0x10, 0x00, 0x01, 0x02, 0x12, 0x34}},
+	}
+	for _, request := range requests {
+		for _, response := range fuzzSeedResponses() {
+			f.Add(request.FunctionCode, request.Data, response.FunctionCode, response.Data)
+		}
+	}
+
+	f.Fuzz(func(t *testing.T, requestFunction byte, requestData []byte, responseFunction byte, responseData []byte) {
+		request := &modbus.ProtocolDataUnit{FunctionCode: requestFunction, Data: requestData}
+		if ValidateRequestPDU(request) != nil {
+			return // Only validated requests are ever sent
+		}
+
+		response := &modbus.ProtocolDataUnit{FunctionCode: responseFunction, Data: responseData}
+		if ValidateResponsePDU(request, response) != nil {
+			return
+		}
+		if len(responseData) > maxPDUDataSize {
+			t.Fatalf("accepted %d bytes of response data", len(responseData))
+		}
+
+		// Declared byte counts of accepted responses match the payload
+		switch ModbusFunctionCode(responseFunction) {
+		case ReadCoils, ReadDiscreteInputs, ReadHoldingRegisters, ReadInputRegisters,
+			ReadWriteMultipleRegisters, GetCommEventLog, ReadFileRecord:
+			if int(responseData[0]) != len(responseData)-1 {
+				t.Fatalf("byte count %d accepted for %d byte payload", responseData[0], len(responseData)-1)
+			}
+		}
+	})
+}
+
+func FuzzParseDeviceIDResponse(f *testing.F) {
+	f.Add([]byte{0x0E, 0x01, 0x01, 0x00, 0x00, 0x01, 0x00, 0x04, 'A', 'c', 'm', 'e'})
+	f.Add([]byte{0x0E, 0x01, 0x01, 0xFF, 0x02, 0x02, 0x00, 0xFF})
+
+	f.Fuzz(func(t *testing.T, data []byte) {
+		page, err := parseDeviceIDResponse(data, DeviceIDBasic)
+		if err != nil {
+			return
+		}
+
+		total := 0
+		for _, value := range page.objects {
+			total += len(value)
+		}
+		if total > len(data) {
+			t.Fatalf("decoded %d bytes of objects from %d bytes", total, len(data))
+		}
+	})
+}
+
+// fuzzSeedResponses returns well-formed responses for the common function
+// codes
+func fuzzSeedResponses() []*modbus.ProtocolDataUnit {
+	return []*modbus.ProtocolDataUnit{
+		{FunctionCode: 0x01, Data: []byte{0x02, 0x05, 0x02}},
+		{FunctionCode: 0x03, Data: []byte{0x04, 0x00, 0x01, 0x00, 0x02}},
+		{FunctionCode: 0x07, Data: []byte{0x6D}},
+		{FunctionCode: 0x0B, Data: []byte{0xFF, 0xFF, 0x01, 0x08}},
+		{FunctionCode: 0x0C, Data: []byte{0x08, 0x00, 0x00, 0x01, 0x08, 0x01, 0x21, 0x20, 0x00}},
+		{FunctionCode: 0x10, Data: []byte{0x00, 0x01, 0x00, 0x01}},
+		{FunctionCode: 0x14, Data: []byte{0x06, 0x05, 0x06, 0x0D, 0xFE, 0x00, 0x20}},
+		{FunctionCode: 0x17, Data: []byte{0x02, 0x12, 0x34}},
+		{FunctionCode: 0x83, Data: []byte{0x02}},
+	}
+}
@@ -0,0 +1,309 @@
+package protocols
+
+import (
+	"context"
+	"encoding/binary"
+	"math/rand"
+	"sync"
+	"sync/atomic"
+	"time"
+
+	"github.com/goburrow/modbus"
+	"go.uber.org/zap"
+)
+
+// Modbus Connection Health Monitoring
+//
+// ModbusHealthMonitor periodically checks a TCP transport with a cheap read,
+// reconnects with jittered exponential backoff when the connection is lost
+// and reports every connection state change.
+
+// ConnectionState is the health state of a monitored connection
+type ConnectionState int
+
+const (
+	ConnectionStateDisconnected ConnectionState = iota
+	ConnectionStateConnecting
+	ConnectionStateConnected
+)
+
+// String returns the name of the state
+func (s ConnectionState) String() string {
+	switch s {
+	case ConnectionStateDisconnected:
+		return "disconnected"
+	case ConnectionStateConnecting:
+		return "connecting"
+	case ConnectionStateConnected:
+		return "connected"
+	default:
+		return "unknown"
+	}
+}
+
+// ConnectionStateEvent describes a connection state change
+type ConnectionStateEvent struct {
+	Address   string          `json:"address"`
+	From      ConnectionState `json:"from"`
+	To        ConnectionState `json:"to"`
+	Err       error           `json:"-"` // Cause of a transition to disconnected
+	Timestamp time.Time       `json:"timestamp"`
+}
+
+// HealthCheckConfig controls health checks and reconnection
+type HealthCheckConfig struct {
+	Interval         time.Duration `yaml:"interval"`
+	FailureThreshold int           `yaml:"failure_threshold"` // Failed checks before reconnecting
+	CheckAddress     uint16        `yaml:"check_address"`     // Holding register read by the check
+
+	InitialBackoff time.Duration `yaml:"initial_backoff"`
+	MaxBackoff     time.Duration `yaml:"max_backoff"`
+	Jitter         float64       `yaml:"jitter"` // Fraction of the backoff that is randomized, 0-1
+}
+
+// DefaultHealthCheckConfig checks every 10 seconds and reconnects after
+// three failed checks, backing off from 500ms to 30s
+func DefaultHealthCheckConfig() *HealthCheckConfig {
+	return &HealthCheckConfig{
+		Interval:         10 * time.Second,
+		FailureThreshold: 3,
+		InitialBackoff:   500 * time.Millisecond,
+		MaxBackoff:       30 * time.Second,
+		Jitter:           0.2,
+	}
+}
+
+// ModbusHealthMonitor keeps a ModbusTCPTransport connected
+type ModbusHealthMonitor struct {
+	transport *ModbusTCPTransport
+	config    HealthCheckConfig
+	logger    *zap.Logger
+
+	// OnStateChange is called on every state change, from the monitor's
+	// goroutine
+	OnStateChange func(event ConnectionStateEvent)
+
+	// Check overrides the default health check, a one-register read at
+	// CheckAddress
+	Check func() error
+
+	mutex      sync.Mutex
+	state      ConnectionState
+	failures   int
+	lastError  error
+	reconnects uint64
+}
+
+// NewModbusHealthMonitor creates a monitor for transport
+func NewModbusHealthMonitor(transport *ModbusTCPTransport, config *HealthCheckConfig, logger *zap.Logger) *ModbusHealthMonitor {
+	if config == nil {
+		config = DefaultHealthCheckConfig()
+	}
+
+	return &ModbusHealthMonitor{
+		transport: transport,
+		config:    *config,
+		logger:    logger,
+	}
+}
+
+// State returns the current connection state
+func (h *ModbusHealthMonitor) State() ConnectionState {
+	h.mutex.Lock()
+	defer h.mutex.Unlock()
+
+	return h.state
+}
+
+// LastError returns the error that caused the last failed check or
+// connection attempt
+func (h *ModbusHealthMonitor) LastError() error {
+	h.mutex.Lock()
+	defer h.mutex.Unlock()
+
+	return h.lastError
+}
+
+// Reconnects returns the number of successful reconnections
+func (h *ModbusHealthMonitor) Reconnects() uint64 {
+	return atomic.LoadUint64(&h.reconnects)
+}
+
+// Run monitors the connection until ctx is cancelled
+func (h *ModbusHealthMonitor) Run(ctx context.Context) error {
+	interval := h.config.Interval
+	if interval <= 0 {
+		interval = DefaultHealthCheckConfig().Interval
+	}
+
+	ticker := time.NewTicker(interval)
+	defer ticker.Stop()
+
+	connected := false
+	for {
+		if h.State() == ConnectionStateConnected {
+			h.check()
+		}
+		if h.State() != ConnectionStateConnected {
+			if !h.reconnect(ctx) {
+				return ctx.Err()
+			}
+			if connected {
+				atomic.AddUint64(&h.reconnects, 1)
+			}
+			connected = true
+		}
+
+		select {
+		case <-ctx.Done():
+			return ctx.Err()
+		case <-ticker.C:
+		}
+	}
+}
+
+// check runs one health check and drops the connection once
+// FailureThreshold consecutive checks have failed
+func (h *ModbusHealthMonitor) check() {
+	if !h.transport.IsConnected() {
+		h.disconnected(NewModbusError(ModbusErrorConnection, "connection lost", "health_check"))
+		return
+	}
+
+	err := h.runCheck()
+
+	h.mutex.Lock()
+	if err == nil {
+		h.failures = 0
+		h.mutex.Unlock()
+		return
+	}
+	h.failures++
+	failures := h.failures
+	h.lastError = err
+	h.mutex.Unlock()
+
+	h.logger.Debug("Modbus health check failed",
+		zap.String("address", h.transport.Address),
+		zap.Int("consecutive_failures", failures),
+		zap.Error(err),
+	)
+
+	if failures >= h.config.FailureThreshold {
+		h.disconnected(err)
+	}
+}
+
+func (h *ModbusHealthMonitor) runCheck() error {
+	if h.Check != nil {
+		return h.Check()
+	}
+
+	data := make([]byte, 4)
+	binary.BigEndian.PutUint16(data[0:2], h.config.CheckAddress)
+	binary.BigEndian.PutUint16(data[2:4], 1)
+
+	request, err := h.transport.Encode(&modbus.ProtocolDataUnit{FunctionCode: byte(ReadHoldingRegisters), Data: data})
+	if err != nil {
+		return err
+	}
+
+	// An exception response still proves the device is reachable
+	_, err = h.transport.SendWithPolicy(request, nil)
+	return err
+}
+
+// disconnected closes the transport so the next attempt starts from a fresh
+// connection
+func (h *ModbusHealthMonitor) disconnected(err error) {
+	h.transport.Close()
+
+	h.mutex.Lock()
+	h.lastError = err
+	h.failures = 0
+	h.mutex.Unlock()
+
+	h.setState(ConnectionStateDisconnected, err)
+}
+
+// reconnect connects the transport, backing off between failed attempts.
+// It returns false if ctx was cancelled first.
+func (h *ModbusHealthMonitor) reconnect(ctx context.Context) bool {
+	for attempt := 1; ; attempt++ {
+		h.setState(ConnectionStateConnecting, nil)
+
+		err := h.transport.Connect()
+		if err == nil {
+			if ctx.Err() != nil {
+				// Stopped while connecting; do not leave the connection open
+				h.transport.Close()
+				return false
+			}
+			h.setState(ConnectionStateConnected, nil)
+			return true
+		}
+
+		h.mutex.Lock()
+		h.lastError = err
+		h.mutex.Unlock()
+		h.setState(ConnectionStateDisconnected, err)
+
+		backoff := h.backoff(attempt)
+		h.logger.Warn("Modbus reconnect failed",
+			zap.String("address", h.transport.Address),
+			zap.Int("attempt", attempt),
+			zap.Duration("backoff", backoff),
+			zap.Error(err),
+		)
+
+		select {
+		case <-ctx.Done():
+			return false
+		case <-time.After(backoff):
+		}
+	}
+}
+
+// backoff returns the jittered delay after the given failed attempt
+func (h *ModbusHealthMonitor) backoff(attempt int) time.Duration {
+	backoff := exponentialBackoff(h.config.InitialBackoff, h.config.MaxBackoff, 2, attempt)
+
+	jitter := h.config.Jitter
+	if jitter <= 0 {
+		return backoff
+	}
+	if jitter > 1 {
+		jitter = 1
+	}
+	return time.Duration(float64(backoff) * (1 - jitter + 2*jitter*rand.Float64()))
+}
+
+func (h *ModbusHealthMonitor) setState(state ConnectionState, err error) {
+	h.mutex.Lock()
+	previous := h.state
+	h.state = state
+	h.mutex.Unlock()
+
+	if previous == state {
+		return
+	}
+
+	event := ConnectionStateEvent{
+		Address:   h.transport.Address,
+		From:      previous,
+		To:        state,
+		Err:       err,
+		Timestamp: time.Now(),
+	}
+
+	h.logger.Info("Modbus connection state changed",
+		zap.String("address", event.Address),
+		zap.Stringer("from", event.From),
+		zap.Stringer("to", event.To),
+		zap.Error(err),
+	)
+
+	if h.OnStateChange != nil {
+		h.OnStateChange(event)
+	}
+}
@@ -0,0 +1,108 @@
+package protocols
+
+import (
+	"context"
+	"encoding/binary"
+	"io"
+	"net"
+	"testing"
+	"time"
+
+	"github.com/goburrow/modbus"
+	"github.com/stretchr/testify/assert"
+	"go.uber.org/zap"
+)
+
+func TestHealthMonitor_Backoff(t *testing.T) {
+	config := &HealthCheckConfig{InitialBackoff: 100 * time.Millisecond, MaxBackoff: time.Second, Jitter: 0.5}
+	monitor := NewModbusHealthMonitor(NewModbusTCPTransport("127.0.0.1:0", zap.NewNop()), config, zap.NewNop())
+
+	for i := 0; i < 100; i++ {
+		backoff := monitor.backoff(2)
+		assert.GreaterOrEqual(t, backoff, 100*time.Millisecond)
+		assert.LessOrEqual(t, backoff, 300*time.Millisecond)
+	}
+	assert.LessOrEqual(t, monitor.backoff(10), 1500*time.Millisecond)
+}
+
+func TestHealthMonitor_ReconnectsAfterConnectionLoss(t *testing.T) {
+	listener, err := net.Listen("tcp", "127.0.0.1:0")
+	assert.NoError(t, err)
+	defer listener.Close()
+
+	accepted := make(chan net.Conn, 4)
+	go func() {
+		for {
+			conn, err := listener.Accept()
+			if err != nil {
+				return
+			}
+			accepted <- conn
+			go serveHealthChecks(conn)
+		}
+	}()
+
+	transport := NewModbusTCPTransport(listener.Addr().String(), zap.NewNop())
+	transport.Timeout = 200 * time.Millisecond
+	defer transport.Close()
+
+	monitor := NewModbusHealthMonitor(transport, &HealthCheckConfig{
+		Interval:         10 * time.Millisecond,
+		FailureThreshold: 1,
+		InitialBackoff:   time.Millisecond,
+	}, zap.NewNop())
+
+	events := make(chan ConnectionStateEvent, 16)
+	monitor.OnStateChange = func(event ConnectionStateEvent) { events <- event }
+
+	ctx, cancel := context.WithCancel(context.Background())
+	done := make(chan error, 1)
+	go func() { done <- monitor.Run(ctx) }()
+	defer func() {
+		cancel()
+		assert.Equal(t, context.Canceled, <-done)
+	}()
+
+	waitForState := func(state ConnectionState) {
+		t.Helper()
+		for {
+			select {
+			case event := <-events:
+				if event.To == state {
+					return
+				}
+			case <-time.After(2 * time.Second):
+				t.Fatalf("no transition to %s", state)
+			}
+		}
+	}
+
+	waitForState(ConnectionStateConnected)
+
+	// Simulate a switch reboot by dropping the server side of the connection
+	(<-accepted).Close()
+
+	waitForState(ConnectionStateDisconnected)
+	waitForState(ConnectionStateConnected)
+	assert.Equal(t, uint64(1), monitor.Reconnects())
+	assert.Equal(t, ConnectionStateConnected, monitor.State())
+}
+
+// serveHealthChecks answers every request on conn with a one-register read
+// response
+func serveHealthChecks(conn net.Conn) {
+	defer conn.Close()
+
+	header := make([]byte, modbusTCPHeaderSize)
+	for {
+		if _, err := io.ReadFull(conn, header); err != nil {
+			return
+		}
+		pdu := make([]byte, int(binary.BigEndian.Uint16(header[4:6]))-1)
+		if _, err := io.ReadFull(conn, pdu); err != nil {
+			return
+		}
+		conn.Write(encodeMBAPFrame(binary.BigEndian.Uint16(header[0:2]), header[6],
+			&modbus.ProtocolDataUnit{FunctionCode: 0x03, Data: []byte{0x02, 0x00, 0x00}}))
+	}
+}
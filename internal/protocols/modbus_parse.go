@@ -0,0 +1,176 @@
+package protocols
+
+import (
+	"encoding/binary"
+	"fmt"
+)
+
+// Modbus Frame Inspection
+//
+// ParseModbusFrame decodes a captured frame without knowing which transport
+// it came from or whether it is a request or a response. It is meant for
+// diagnostics and traffic inspection; the transports themselves decode with
+// the request at hand and never guess.
+
+// Frame directions reported by ParseModbusFrame
+const (
+	FrameDirectionRequest  = "request"
+	FrameDirectionResponse = "response"
+	FrameDirectionUnknown  = ""
+)
+
+// ParsedModbusFrame is the decoded content of a captured frame
+type ParsedModbusFrame struct {
+	Transport     string               `json:"transport"`                // "tcp" or "rtu"
+	TransactionID uint16               `json:"transaction_id,omitempty"` // TCP only
+	UnitID        byte                 `json:"unit_id"`
+	FunctionCode  byte                 `json:"function_code"`
+	Function      string               `json:"function"`
+	Direction     string               `json:"direction,omitempty"` // Guessed from the data layout
+	Exception     *ModbusExceptionCode `json:"exception,omitempty"`
+	Address       uint16               `json:"address,omitempty"`
+	Quantity      uint16               `json:"quantity,omitempty"`
+	Registers     []uint16             `json:"registers,omitempty"` // Register values read or written
+	Data          []byte               `json:"data"`                // PDU data after the function code
+	CRCValid      *bool                `json:"crc_valid,omitempty"` // RTU only
+}
+
+// ParseModbusFrame decodes a Modbus TCP or RTU frame. A frame is taken as
+// TCP when its MBAP header is consistent with its length and as RTU
+// otherwise; RTU frames with a bad CRC are still decoded and reported with
+// CRCValid false.
+func ParseModbusFrame(frame []byte) (*ParsedModbusFrame, error) {
+	parsed := &ParsedModbusFrame{}
+
+	var data []byte
+	switch {
+	case len(frame) >= modbusTCPHeaderSize+1 && len(frame) <= modbusTCPMaxADUSize &&
+		binary.BigEndian.Uint16(frame[2:4]) == modbusTCPProtocolID &&
+		int(binary.BigEndian.Uint16(frame[4:6])) == len(frame)-6:
+		parsed.Transport = "tcp"
+		parsed.TransactionID = binary.BigEndian.Uint16(frame[0:2])
+		parsed.UnitID = frame[6]
+		parsed.FunctionCode = frame[7]
+		data = frame[8:]
+
+	case len(frame) >= rtuMinFrameSize && len(frame) <= rtuMaxFrameSize:
+		crcValid := validRTUCRC(frame)
+		parsed.Transport = "rtu"
+		parsed.CRCValid = &crcValid
+		parsed.UnitID = frame[0]
+		parsed.FunctionCode = frame[1]
+		data = frame[2 : len(frame)-2]
+
+	default:
+		return nil, NewModbusError(ModbusErrorFrame, fmt.Sprintf("%d bytes is neither a TCP nor an RTU frame", len(frame)), "parse")
+	}
+
+	parsed.Data = append([]byte(nil), data...)
+	parsed.Function = functionName(parsed.FunctionCode &^ exceptionFunctionFlag)
+
+	if parsed.FunctionCode&exceptionFunctionFlag != 0 && len(data) == 1 {
+		code := ModbusExceptionCode(data[0])
+		parsed.Direction = FrameDirectionResponse
+		parsed.Exception = &code
+		return parsed, nil
+	}
+
+	parsed.decodePayload(data)
+	return parsed, nil
+}
+
+// decodePayload fills in the fields of the well-known function codes
+func (p *ParsedModbusFrame) decodePayload(data []byte) {
+	byteCountMatches := len(data) >= 1 && int(data[0]) == len(data)-1
+
+	switch ModbusFunctionCode(p.FunctionCode) {
+	case ReadCoils, ReadDiscreteInputs:
+		// A 4 byte coil response is indistinguishable from a request; the
+		// request is far more common in captures of polling traffic
+		if len(data) == singleWriteRequestDataLen {
+			p.Direction = FrameDirectionRequest
+			p.Address, p.Quantity = binary.BigEndian.Uint16(data[0:2]), binary.BigEndian.Uint16(data[2:4])
+		} else if byteCountMatches {
+			p.Direction = FrameDirectionResponse
+		}
+
+	case ReadHoldingRegisters, ReadInputRegisters, ReadWriteMultipleRegisters:
+		if byteCountMatches && len(data)%2 == 1 {
+			p.Direction = FrameDirectionResponse
+			p.Registers = bytesToRegisters(data[1:])
+		} else if len(data) >= singleWriteRequestDataLen {
+			p.Direction = FrameDirectionRequest
+			p.Address, p.Quantity = binary.BigEndian.Uint16(data[0:2]), binary.BigEndian.Uint16(data[2:4])
+			if len(data) >= readWriteHeaderDataLen && ModbusFunctionCode(p.FunctionCode) == ReadWriteMultipleRegisters {
+				p.Registers = bytesToRegisters(data[readWriteHeaderDataLen:])
+			}
+		}
+
+	case WriteSingleCoil, WriteSingleRegister:
+		// Requests and responses are identical
+		if len(data) == singleWriteRequestDataLen {
+			p.Address = binary.BigEndian.Uint16(data[0:2])
+			if ModbusFunctionCode(p.FunctionCode) == WriteSingleRegister {
+				p.Registers = bytesToRegisters(data[2:4])
+			}
+		}
+
+	case WriteMultipleCoils, WriteMultipleRegisters:
+		if len(data) < singleWriteRequestDataLen {
+			return
+		}
+		p.Address, p.Quantity = binary.BigEndian.Uint16(data[0:2]), binary.BigEndian.Uint16(data[2:4])
+		if len(data) == singleWriteRequestDataLen {
+			p.Direction = FrameDirectionResponse
+		} else if int(data[4]) == len(data)-multipleWriteHeaderDataLen {
+			p.Direction = FrameDirectionRequest
+			if ModbusFunctionCode(p.FunctionCode) == WriteMultipleRegisters {
+				p.Registers = bytesToRegisters(data[multipleWriteHeaderDataLen:])
+			}
+		}
+	}
+}
+
+// functionName returns the specification name of a function code
+func functionName(functionCode byte) string {
+	switch ModbusFunctionCode(functionCode) {
+	case ReadCoils:
+		return "Read Coils"
+	case ReadDiscreteInputs:
+		return "Read Discrete Inputs"
+	case ReadHoldingRegisters:
+		return "Read Holding Registers"
+	case ReadInputRegisters:
+		return "Read Input Registers"
+	case WriteSingleCoil:
+		return "Write Single Coil"
+	case WriteSingleRegister:
+		return "Write Single Register"
+	case ReadExceptionStatus:
+		return "Read Exception Status"
+	case SerialLineDiagnostics:
+		return "Diagnostics"
+	case GetCommEventCounter:
+		return "Get Comm Event Counter"
+	case GetCommEventLog:
+		return "Get Comm Event Log"
+	case WriteMultipleCoils:
+		return "Write Multiple Coils"
+	case WriteMultipleRegisters:
+		return "Write Multiple Registers"
+	case ReadFileRecord:
+		return "Read File Record"
+	case WriteFileRecord:
+		return "Write File Record"
+	case MaskWriteRegister:
+		return "Mask Write Register"
+	case ReadWriteMultipleRegisters:
+		return "Read/Write Multiple Registers"
+	case EncapsulatedInterfaceTransport:
+		return "Encapsulated Interface Transport"
+	}
+	if IsUserDefinedFunctionCode(functionCode) {
+		return fmt.Sprintf("User-defined (0x%02X)", functionCode)
+	}
+	return fmt.Sprintf("Unknown (0x%02X)", functionCode)
+}
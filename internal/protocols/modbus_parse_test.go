@@ -0,0 +1,61 @@
+package protocols
+
+import (
+	"testing"
+
+	"github.com/goburrow/modbus"
+	"github.com/stretchr/testify/assert"
+)
+
+func TestParseModbusFrame_TCP(t *testing.T) {
+	parsed, err := ParseModbusFrame(encodeMBAPFrame(7, 3, &modbus.ProtocolDataUnit{FunctionCode: 0x03, Data: []byte{0x00, 0x10, 0x00, 0x02}}))
+	assert.NoError(t, err)
+	assert.Equal(t, "tcp", parsed.Transport)
+	assert.Equal(t, uint16(7), parsed.TransactionID)
+	assert.Equal(t, byte(3), parsed.UnitID)
+	assert.Equal(t, "Read Holding Registers", parsed.Function)
+	assert.Equal(t, FrameDirectionRequest, parsed.Direction)
+	assert.Equal(t, uint16(0x10), parsed.Address)
+	assert.Equal(t, uint16(2), parsed.Quantity)
+	assert.Nil(t, parsed.CRCValid)
+
+	parsed, err = ParseModbusFrame(encodeMBAPFrame(7, 3, &modbus.ProtocolDataUnit{FunctionCode: 0x03, Data: []byte{0x04, 0x12, 0x34, 0x56, 0x78}}))
+	assert.NoError(t, err)
+	assert.Equal(t, FrameDirectionResponse, parsed.Direction)
+	assert.Equal(t, []uint16{0x1234, 0x5678}, parsed.Registers)
+
+	parsed, err = ParseModbusFrame(EncodeModbusException(9, 1, 0x10, ExceptionIllegalDataAddress))
+	assert.NoError(t, err)
+	assert.Equal(t, "Write Multiple Registers", parsed.Function)
+	assert.Equal(t, FrameDirectionResponse, parsed.Direction)
+	if assert.NotNil(t, parsed.Exception) {
+		assert.Equal(t, ExceptionIllegalDataAddress, *parsed.Exception)
+	}
+}
+
+func TestParseModbusFrame_RTU(t *testing.T) {
+	frame, _ := encodeRTUFrame(1, &modbus.ProtocolDataUnit{FunctionCode: 0x10, Data: []byte{0x00, 0x01, 0x00, 0x02, 0x04, 0x00, 0x0A, 0x01, 0x02}})
+
+	parsed, err := ParseModbusFrame(frame)
+	assert.NoError(t, err)
+	assert.Equal(t, "rtu", parsed.Transport)
+	assert.Equal(t, byte(1), parsed.UnitID)
+	assert.Equal(t, FrameDirectionRequest, parsed.Direction)
+	assert.Equal(t, []uint16{0x000A, 0x0102}, parsed.Registers)
+	if assert.NotNil(t, parsed.CRCValid) {
+		assert.True(t, *parsed.CRCValid)
+	}
+
+	// A corrupted frame is still decoded, with the CRC flagged
+	frame[len(frame)-1] ^= 0xFF
+	parsed, err = ParseModbusFrame(frame)
+	assert.NoError(t, err)
+	assert.False(t, *parsed.CRCValid)
+
+	parsed, err = ParseModbusFrame([]byte{0x01, 0x41, 0x00, 0x00})
+	assert.NoError(t, err)
+	assert.Equal(t, "User-defined (0x41)", parsed.Function)
+
+	_, err = ParseModbusFrame([]byte{0x01, 0x03})
+	assertValidationCategory(t, err, ModbusErrorFrame)
+}
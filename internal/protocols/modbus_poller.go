@@ -0,0 +1,223 @@
+package protocols
+
+import (
+	"context"
+	"fmt"
+	"math"
+	"sync"
+	"time"
+
+	"go.uber.org/zap"
+)
+
+// Modbus Polling Scheduler
+//
+// ModbusPoller reads the tags of a TagMap at per-tag scan rates, filters
+// unchanged values with a dead-band and hands the results to a SampleSink.
+
+// TagSample is one polled tag value
+type TagSample struct {
+	Name          string            `json:"name"`
+	Value         interface{}       `json:"value"`
+	Quality       Quality           `json:"quality"`
+	Substatus     QualitySubstatus  `json:"substatus,omitempty"`
+	QualitySource QualitySource     `json:"quality_source,omitempty"`
+	Timestamp     time.Time         `json:"timestamp"`
+	ClockSync     ClockSync         `json:"clock_sync,omitempty"`
+	Unit          string            `json:"unit,omitempty"`
+	Labels        map[string]string `json:"labels,omitempty"`
+}
+
+// SampleSink receives polled samples, e.g. a time series store
+type SampleSink interface {
+	WriteSamples(samples []TagSample) error
+}
+
+// PollSpec configures how often a tag is read and how much its value must
+// change before a new sample is emitted. A zero Deadband emits every change.
+type PollSpec struct {
+	Tag      string        `json:"tag" yaml:"tag"`
+	ScanRate time.Duration `json:"scan_rate" yaml:"scan_rate"`
+	Deadband float64       `json:"deadband,omitempty" yaml:"deadband,omitempty"`
+}
+
+// ModbusPoller schedules reads of mapped tags on one device
+type ModbusPoller struct {
+	handler *ModbusHandler
+	device  *Device
+	tags    *TagMap
+	sink    SampleSink
+	logger  *zap.Logger
+
+	mutex  sync.Mutex
+	groups map[time.Duration][]*PollSpec
+	last   map[string]TagSample
+}
+
+// NewModbusPoller creates a poller for device that writes into sink
+func NewModbusPoller(handler *ModbusHandler, device *Device, tags *TagMap, sink SampleSink, logger *zap.Logger) *ModbusPoller {
+	return &ModbusPoller{
+		handler: handler,
+		device:  device,
+		tags:    tags,
+		sink:    sink,
+		logger:  logger,
+		groups:  make(map[time.Duration][]*PollSpec),
+		last:    make(map[string]TagSample),
+	}
+}
+
+// AddTag schedules a tag. Tags sharing a scan rate are read in the same cycle.
+func (p *ModbusPoller) AddTag(spec PollSpec) error {
+	if _, exists := p.tags.Get(spec.Tag); !exists {
+		return fmt.Errorf("unknown tag: %s", spec.Tag)
+	}
+	if spec.ScanRate <= 0 {
+		return fmt.Errorf("tag %s: scan rate must be positive", spec.Tag)
+	}
+	if spec.Deadband < 0 {
+		return fmt.Errorf("tag %s: deadband must not be negative", spec.Tag)
+	}
+
+	p.mutex.Lock()
+	defer p.mutex.Unlock()
+
+	p.groups[spec.ScanRate] = append(p.groups[spec.ScanRate], &spec)
+	return nil
+}
+
+// Run polls until ctx is cancelled. Each scan rate group runs on its own
+// ticker; reads within a cycle are issued concurrently and pipelined by the
+// transport.
+func (p *ModbusPoller) Run(ctx context.Context) error {
+	p.mutex.Lock()
+	groups := make(map[time.Duration][]*PollSpec, len(p.groups))
+	for rate, specs := range p.groups {
+		groups[rate] = append([]*PollSpec(nil), specs...)
+	}
+	p.mutex.Unlock()
+
+	if len(groups) == 0 {
+		return fmt.Errorf("no tags scheduled")
+	}
+
+	var wg sync.WaitGroup
+	for rate, specs := range groups {
+		wg.Add(1)
+		go func(rate time.Duration, specs []*PollSpec) {
+			defer wg.Done()
+			p.runGroup(ctx, rate, specs)
+		}(rate, specs)
+	}
+	wg.Wait()
+
+	return ctx.Err()
+}
+
+func (p *ModbusPoller) runGroup(ctx context.Context, rate time.Duration, specs []*PollSpec) {
+	ticker := time.NewTicker(rate)
+	defer ticker.Stop()
+
+	for {
+		p.PollOnce(specs)
+
+		select {
+		case <-ctx.Done():
+			return
+		case <-ticker.C:
+		}
+	}
+}
+
+// PollOnce reads specs once and writes the samples that pass the dead-band
+func (p *ModbusPoller) PollOnce(specs []*PollSpec) {
+	samples := make([]TagSample, len(specs))
+
+	var wg sync.WaitGroup
+	for i, spec := range specs {
+		wg.Add(1)
+		go func(i int, spec *PollSpec) {
+			defer wg.Done()
+			samples[i] = p.readSample(spec.Tag)
+		}(i, spec)
+	}
+	wg.Wait()
+
+	var changed []TagSample
+
+	p.mutex.Lock()
+	for i, sample := range samples {
+		previous, seen := p.last[sample.Name]
+		if seen && !exceedsDeadband(previous, sample, specs[i].Deadband) {
+			continue
+		}
+		p.last[sample.Name] = sample
+		changed = append(changed, sample)
+	}
+	p.mutex.Unlock()
+
+	if len(changed) == 0 {
+		return
+	}
+
+	if err := p.sink.WriteSamples(changed); err != nil {
+		p.logger.Error("Failed to write polled samples",
+			zap.String("device", p.device.ID),
+			zap.Int("samples", len(changed)),
+			zap.Error(err),
+		)
+	}
+}
+
+func (p *ModbusPoller) readSample(name string) TagSample {
+	sample := TagSample{
+		Name:      name,
+		Quality:   QualityGood,
+		Timestamp: DefaultClock.Now(),
+		Labels:    map[string]string{"device": p.device.ID},
+	}
+
+	if def, exists := p.tags.Get(name); exists {
+		sample.Unit = def.Unit
+		sample.Labels["unit_id"] = fmt.Sprintf("%d", def.UnitID)
+	}
+
+	value, err := p.handler.ReadMappedTag(p.device, p.tags, name)
+	if err != nil {
+		p.logger.Debug("Poll read failed",
+			zap.String("device", p.device.ID),
+			zap.String("tag", name),
+			zap.Error(err),
+		)
+		sample.Quality = QualityBad
+		sample.Substatus = QualitySubstatusCommFailure
+		sample.QualitySource = QualitySourceDriver
+		return sample
+	}
+
+	sample.Value = value
+	return sample
+}
+
+// exceedsDeadband reports whether current differs enough from the last
+// published sample to be published. Quality changes are always published;
+// non-numeric values are published whenever they change.
+func exceedsDeadband(previous, current TagSample, deadband float64) bool {
+	if previous.Quality != current.Quality || previous.Substatus != current.Substatus {
+		return true
+	}
+	if current.Quality != QualityGood {
+		return false
+	}
+
+	prev, prevErr := floatValue(previous.Value)
+	cur, curErr := floatValue(current.Value)
+	if prevErr != nil || curErr != nil {
+		return previous.Value != current.Value
+	}
+
+	if deadband == 0 {
+		return prev != cur
+	}
+	return math.Abs(cur-prev) > deadband
+}
@@ -0,0 +1,109 @@
+package protocols
+
+import (
+	"context"
+	"sync"
+	"testing"
+	"time"
+
+	"github.com/stretchr/testify/assert"
+	"go.uber.org/zap"
+)
+
+type recordingSink struct {
+	mutex   sync.Mutex
+	samples []TagSample
+}
+
+func (s *recordingSink) WriteSamples(samples []TagSample) error {
+	s.mutex.Lock()
+	defer s.mutex.Unlock()
+
+	s.samples = append(s.samples, samples...)
+	return nil
+}
+
+func (s *recordingSink) values(name string) []interface{} {
+	s.mutex.Lock()
+	defer s.mutex.Unlock()
+
+	var values []interface{}
+	for _, sample := range s.samples {
+		if sample.Name == name {
+			values = append(values, sample.Value)
+		}
+	}
+	return values
+}
+
+func TestModbusPoller_Deadband(t *testing.T) {
+	var mutex sync.Mutex
+	readings := []byte{100, 101, 103, 104, 110}
+	device := startModbusTestServer(t, func(unitID byte, pdu []byte) []byte {
+		mutex.Lock()
+		defer mutex.Unlock()
+
+		value := readings[0]
+		if len(readings) > 1 {
+			readings = readings[1:]
+		}
+		return []byte{0x03, 0x02, 0x00, value}
+	})
+	handler := connectTestDevice(t, device)
+
+	tags := NewTagMap()
+	assert.NoError(t, tags.Add(&TagDefinition{
+		Name:     "tank.level",
+		Table:    TableHoldingRegisters,
+		DataType: DataTypeUInt16,
+		Unit:     "cm",
+	}))
+
+	sink := &recordingSink{}
+	poller := NewModbusPoller(handler, device, tags, sink, zap.NewNop())
+	assert.NoError(t, poller.AddTag(PollSpec{Tag: "tank.level", ScanRate: time.Millisecond, Deadband: 2}))
+
+	spec := &PollSpec{Tag: "tank.level", Deadband: 2}
+	for i := 0; i < 5; i++ {
+		poller.PollOnce([]*PollSpec{spec})
+	}
+
+	// 101 is within the dead-band of 100; 103 is not; 104 is within 1 of 103
+	assert.Equal(t, []interface{}{uint16(100), uint16(103), uint16(110)}, sink.values("tank.level"))
+
+	sink.mutex.Lock()
+	first := sink.samples[0]
+	sink.mutex.Unlock()
+	assert.Equal(t, QualityGood, first.Quality)
+	assert.Equal(t, "cm", first.Unit)
+	assert.Equal(t, device.ID, first.Labels["device"])
+}
+
+func TestModbusPoller_BadQualityAndRun(t *testing.T) {
+	device := startModbusTestServer(t, func(unitID byte, pdu []byte) []byte {
+		return []byte{0x83, 0x02}
+	})
+	handler := connectTestDevice(t, device)
+
+	tags := NewTagMap()
+	assert.NoError(t, tags.Add(&TagDefinition{Name: "pump.speed", Table: TableHoldingRegisters, DataType: DataTypeInt16}))
+
+	sink := &recordingSink{}
+	poller := NewModbusPoller(handler, device, tags, sink, zap.NewNop())
+	assert.Error(t, poller.AddTag(PollSpec{Tag: "missing", ScanRate: time.Second}))
+	assert.Error(t, poller.AddTag(PollSpec{Tag: "pump.speed"}))
+	assert.NoError(t, poller.AddTag(PollSpec{Tag: "pump.speed", ScanRate: 5 * time.Millisecond}))
+
+	ctx, cancel := context.WithTimeout(context.Background(), 50*time.Millisecond)
+	defer cancel()
+	assert.Equal(t, context.DeadlineExceeded, poller.Run(ctx))
+
+	// Repeated failures publish a single bad quality sample
+	sink.mutex.Lock()
+	defer sink.mutex.Unlock()
+	if assert.Len(t, sink.samples, 1) {
+		assert.Equal(t, QualityBad, sink.samples[0].Quality)
+		assert.Equal(t, QualitySubstatusCommFailure, sink.samples[0].Substatus)
+		assert.Nil(t, sink.samples[0].Value)
+	}
+}
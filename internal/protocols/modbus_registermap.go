@@ -0,0 +1,432 @@
+package protocols
+
+import (
+	"bytes"
+	"encoding/csv"
+	"encoding/json"
+	"fmt"
+	"go/format"
+	"io"
+	"os"
+	"path/filepath"
+	"strconv"
+	"strings"
+	"unicode"
+)
+
+// Modbus Register Map Import
+//
+// Register maps exported from vendor documentation are loaded from CSV or
+// JSON into tag definitions. Column names are matched case-insensitively and
+// common vendor spellings are accepted ("register" for address, "gain" for
+// scale, ...). When no table column is given, the address is read in
+// Modicon notation, e.g. 40013 for holding register 12.
+//
+// GenerateTagMapSource turns a loaded map into Go source for a typed tag
+// map, so address tables need not be typed by hand.
+
+// registerMapColumns maps accepted column names to TagDefinition fields
+var registerMapColumns = map[string]string{
+	"name":       "name",
+	"tag":        "name",
+	"tag_name":   "name",
+	"unit_id":    "unit_id",
+	"slave":      "unit_id",
+	"slave_id":   "unit_id",
+	"table":      "table",
+	"type":       "data_type",
+	"address":    "address",
+	"register":   "address",
+	"data_type":  "data_type",
+	"datatype":   "data_type",
+	"format":     "data_type",
+	"word_order": "word_order",
+	"byte_order": "word_order",
+	"length":     "length",
+	"size":       "length",
+	"scale":      "scale",
+	"gain":       "scale",
+	"multiplier": "scale",
+	"offset":     "offset",
+	"unit":       "unit",
+	"units":      "unit",
+}
+
+// registerTableAliases maps accepted table spellings to register tables
+var registerTableAliases = map[string]RegisterTable{
+	"coils":             TableCoils,
+	"coil":              TableCoils,
+	"0x":                TableCoils,
+	"discrete_inputs":   TableDiscreteInputs,
+	"discrete_input":    TableDiscreteInputs,
+	"1x":                TableDiscreteInputs,
+	"input_registers":   TableInputRegisters,
+	"input_register":    TableInputRegisters,
+	"input":             TableInputRegisters,
+	"3x":                TableInputRegisters,
+	"holding_registers": TableHoldingRegisters,
+	"holding_register":  TableHoldingRegisters,
+	"holding":           TableHoldingRegisters,
+	"4x":                TableHoldingRegisters,
+}
+
+// LoadRegisterMap reads a register map file, chosen by its .csv or .json
+// extension, into a tag map
+func LoadRegisterMap(path string) (*TagMap, error) {
+	file, err := os.Open(path)
+	if err != nil {
+		return nil, err
+	}
+	defer file.Close()
+
+	var definitions []*TagDefinition
+	switch strings.ToLower(filepath.Ext(path)) {
+	case ".csv":
+		definitions, err = ParseRegisterMapCSV(file)
+	case ".json":
+		definitions, err = ParseRegisterMapJSON(file)
+	default:
+		return nil, fmt.Errorf("unsupported register map format: %s", path)
+	}
+	if err != nil {
+		return nil, fmt.Errorf("%s: %w", path, err)
+	}
+
+	tags := NewTagMap()
+	for _, def := range definitions {
+		if _, exists := tags.Get(def.Name); exists {
+			return nil, fmt.Errorf("%s: duplicate tag %s", path, def.Name)
+		}
+		if err := tags.Add(def); err != nil {
+			return nil, fmt.Errorf("%s: %w", path, err)
+		}
+	}
+	return tags, nil
+}
+
+// ParseRegisterMapCSV parses a register map with a header row
+func ParseRegisterMapCSV(r io.Reader) ([]*TagDefinition, error) {
+	reader := csv.NewReader(r)
+	reader.TrimLeadingSpace = true
+	reader.FieldsPerRecord = -1
+	reader.Comment = '#'
+
+	header, err := reader.Read()
+	if err != nil {
+		return nil, fmt.Errorf("failed to read header: %w", err)
+	}
+
+	columns := make([]string, len(header))
+	for i, name := range header {
+		columns[i] = registerMapColumns[normalizeColumnName(name)]
+	}
+
+	var definitions []*TagDefinition
+	for {
+		record, err := reader.Read()
+		if err == io.EOF {
+			return definitions, nil
+		}
+		if err != nil {
+			return nil, err
+		}
+
+		line, _ := reader.FieldPos(0)
+		fields := make(map[string]string)
+		for i, value := range record {
+			if i < len(columns) && columns[i] != "" {
+				fields[columns[i]] = strings.TrimSpace(value)
+			}
+		}
+		if isBlankRow(fields) {
+			continue
+		}
+
+		def, err := parseRegisterMapRow(fields)
+		if err != nil {
+			return nil, fmt.Errorf("line %d: %w", line, err)
+		}
+		definitions = append(definitions, def)
+	}
+}
+
+// ParseRegisterMapJSON parses a register map given as an array of objects,
+// or an object holding that array under "tags"
+func ParseRegisterMapJSON(r io.Reader) ([]*TagDefinition, error) {
+	decoder := json.NewDecoder(r)
+	decoder.UseNumber()
+
+	var document interface{}
+	if err := decoder.Decode(&document); err != nil {
+		return nil, err
+	}
+
+	if object, ok := document.(map[string]interface{}); ok {
+		document = object["tags"]
+	}
+	entries, ok := document.([]interface{})
+	if !ok {
+		return nil, fmt.Errorf("expected an array of tags")
+	}
+
+	definitions := make([]*TagDefinition, 0, len(entries))
+	for i, entry := range entries {
+		object, ok := entry.(map[string]interface{})
+		if !ok {
+			return nil, fmt.Errorf("tag %d: expected an object", i)
+		}
+
+		fields := make(map[string]string)
+		for key, value := range object {
+			if column := registerMapColumns[normalizeColumnName(key)]; column != "" && value != nil {
+				fields[column] = strings.TrimSpace(fmt.Sprint(value))
+			}
+		}
+
+		def, err := parseRegisterMapRow(fields)
+		if err != nil {
+			return nil, fmt.Errorf("tag %d: %w", i, err)
+		}
+		definitions = append(definitions, def)
+	}
+	return definitions, nil
+}
+
+// parseRegisterMapRow builds a tag definition from normalized fields
+func parseRegisterMapRow(fields map[string]string) (*TagDefinition, error) {
+	def := &TagDefinition{
+		Name:     fields["name"],
+		DataType: DataType(strings.ToLower(fields["data_type"])),
+		Unit:     fields["unit"],
+	}
+	if def.Name == "" {
+		return nil, fmt.Errorf("tag name is required")
+	}
+	if def.DataType == "" {
+		def.DataType = DataTypeUInt16
+	}
+
+	var err error
+	if def.WordOrder, err = ParseWordOrder(fields["word_order"]); err != nil {
+		return nil, fmt.Errorf("tag %s: %w", def.Name, err)
+	}
+
+	if value := fields["unit_id"]; value != "" {
+		unitID, err := strconv.ParseUint(value, 0, 8)
+		if err != nil {
+			return nil, fmt.Errorf("tag %s: invalid unit ID %q", def.Name, value)
+		}
+		def.UnitID = byte(unitID)
+	}
+
+	if value := fields["length"]; value != "" {
+		if def.Length, err = strconv.Atoi(value); err != nil {
+			return nil, fmt.Errorf("tag %s: invalid length %q", def.Name, value)
+		}
+	}
+
+	if value := fields["scale"]; value != "" {
+		if def.Scale, err = strconv.ParseFloat(value, 64); err != nil {
+			return nil, fmt.Errorf("tag %s: invalid scale %q", def.Name, value)
+		}
+	}
+
+	if value := fields["offset"]; value != "" {
+		if def.Offset, err = strconv.ParseFloat(value, 64); err != nil {
+			return nil, fmt.Errorf("tag %s: invalid offset %q", def.Name, value)
+		}
+	}
+
+	if err := parseRegisterMapAddress(def, fields["table"], fields["address"]); err != nil {
+		return nil, fmt.Errorf("tag %s: %w", def.Name, err)
+	}
+
+	// Bit tables only hold booleans, so vendor maps often leave the type out
+	if def.isBitTable() && fields["data_type"] == "" {
+		def.DataType = DataTypeBool
+	}
+	return def, nil
+}
+
+// parseRegisterMapAddress sets the table and zero-based address of def.
+// Without a table the address is read in Modicon notation (5 or 6 digits).
+func parseRegisterMapAddress(def *TagDefinition, table, address string) error {
+	if address == "" {
+		return fmt.Errorf("address is required")
+	}
+
+	if table != "" {
+		resolved, exists := registerTableAliases[normalizeColumnName(table)]
+		if !exists {
+			return fmt.Errorf("unknown register table %q", table)
+		}
+		value, err := strconv.ParseUint(address, 0, 16)
+		if err != nil {
+			return fmt.Errorf("invalid address %q", address)
+		}
+		def.Table = resolved
+		def.Address = uint16(value)
+		return nil
+	}
+
+	value, err := strconv.ParseUint(address, 10, 32)
+	if err != nil || (len(address) != 5 && len(address) != 6) {
+		return fmt.Errorf("address %q is not in Modicon notation and no table is given", address)
+	}
+
+	base := uint64(10000)
+	if len(address) == 6 {
+		base = 100000
+	}
+
+	offset := value % base
+	if offset == 0 || offset > maxModbusAddressSpace {
+		return fmt.Errorf("address %q out of range", address)
+	}
+
+	switch value / base {
+	case 0:
+		def.Table = TableCoils
+	case 1:
+		def.Table = TableDiscreteInputs
+	case 3:
+		def.Table = TableInputRegisters
+	case 4:
+		def.Table = TableHoldingRegisters
+	default:
+		return fmt.Errorf("address %q has no register table prefix", address)
+	}
+	def.Address = uint16(offset - 1)
+	return nil
+}
+
+func normalizeColumnName(name string) string {
+	name = strings.ToLower(strings.TrimSpace(name))
+	return strings.NewReplacer(" ", "_", "-", "_").Replace(name)
+}
+
+func isBlankRow(fields map[string]string) bool {
+	for _, value := range fields {
+		if value != "" {
+			return false
+		}
+	}
+	return true
+}
+
+// Go source generation
+
+const protocolsImportPath = "github.com/bifrost/go-gateway/internal/protocols"
+
+var dataTypeConstants = map[DataType]string{
+	DataTypeBool:    "DataTypeBool",
+	DataTypeInt16:   "DataTypeInt16",
+	DataTypeUInt16:  "DataTypeUInt16",
+	DataTypeInt32:   "DataTypeInt32",
+	DataTypeUInt32:  "DataTypeUInt32",
+	DataTypeInt64:   "DataTypeInt64",
+	DataTypeUInt64:  "DataTypeUInt64",
+	DataTypeFloat32: "DataTypeFloat32",
+	DataTypeFloat64: "DataTypeFloat64",
+	DataTypeString:  "DataTypeString",
+}
+
+var tableConstants = map[RegisterTable]string{
+	TableCoils:            "TableCoils",
+	TableDiscreteInputs:   "TableDiscreteInputs",
+	TableInputRegisters:   "TableInputRegisters",
+	TableHoldingRegisters: "TableHoldingRegisters",
+}
+
+// GenerateTagMapSource generates a Go file in package packageName that
+// declares a constant per tag name and a function funcName returning the
+// tags as a *protocols.TagMap. source names the input in the header.
+func GenerateTagMapSource(tags *TagMap, packageName, funcName, source string) ([]byte, error) {
+	names := tags.Names()
+	identifiers := make(map[string]string, len(names))
+	taken := make(map[string]string, len(names))
+	for _, name := range names {
+		identifier := tagIdentifier(name)
+		if other, exists := taken[identifier]; exists {
+			return nil, fmt.Errorf("tags %s and %s both map to identifier %s", other, name, identifier)
+		}
+		taken[identifier] = name
+		identifiers[name] = identifier
+	}
+
+	var buf bytes.Buffer
+	fmt.Fprintf(&buf, "// Code generated by regmapgen from %s; DO NOT EDIT.\n\n", filepath.Base(source))
+	fmt.Fprintf(&buf, "package %s\n\n", packageName)
+	fmt.Fprintf(&buf, "import \"%s\"\n\n", protocolsImportPath)
+
+	buf.WriteString("// Tag names\nconst (\n")
+	for _, name := range names {
+		fmt.Fprintf(&buf, "%s = %q\n", identifiers[name], name)
+	}
+	buf.WriteString(")\n\n")
+
+	fmt.Fprintf(&buf, "// %s returns the register map as a tag map\n", funcName)
+	fmt.Fprintf(&buf, "func %s() (*protocols.TagMap, error) {\n", funcName)
+	buf.WriteString("tags := protocols.NewTagMap()\nfor _, def := range []*protocols.TagDefinition{\n")
+	for _, name := range names {
+		def, _ := tags.Get(name)
+		buf.WriteString(tagDefinitionLiteral(def, identifiers[name]))
+	}
+	buf.WriteString("} {\nif err := tags.Add(def); err != nil {\nreturn nil, err\n}\n}\nreturn tags, nil\n}\n")
+
+	return format.Source(buf.Bytes())
+}
+
+func tagDefinitionLiteral(def *TagDefinition, identifier string) string {
+	fields := []string{"Name: " + identifier}
+	if def.UnitID != 0 {
+		fields = append(fields, fmt.Sprintf("UnitID: %d", def.UnitID))
+	}
+	fields = append(fields,
+		"Table: protocols."+tableConstants[def.Table],
+		fmt.Sprintf("Address: %d", def.Address))
+
+	if constant, exists := dataTypeConstants[def.DataType]; exists {
+		fields = append(fields, "DataType: protocols."+constant)
+	} else {
+		fields = append(fields, fmt.Sprintf("DataType: protocols.DataType(%q)", def.DataType))
+	}
+	if def.WordOrder != "" && def.WordOrder != WordOrderABCD {
+		fields = append(fields, "WordOrder: protocols.WordOrder"+string(def.WordOrder))
+	}
+	if def.Length != 0 {
+		fields = append(fields, fmt.Sprintf("Length: %d", def.Length))
+	}
+	if def.Scale != 0 && def.Scale != 1 {
+		fields = append(fields, "Scale: "+strconv.FormatFloat(def.Scale, 'g', -1, 64))
+	}
+	if def.Offset != 0 {
+		fields = append(fields, "Offset: "+strconv.FormatFloat(def.Offset, 'g', -1, 64))
+	}
+	if def.Unit != "" {
+		fields = append(fields, fmt.Sprintf("Unit: %q", def.Unit))
+	}
+
+	return "{" + strings.Join(fields, ", ") + "},\n"
+}
+
+// tagIdentifier turns a tag name such as "boiler.flow_rate" into an exported
+// identifier such as TagBoilerFlowRate
+func tagIdentifier(name string) string {
+	var identifier strings.Builder
+	identifier.WriteString("Tag")
+
+	upper := true
+	for _, r := range name {
+		if !unicode.IsLetter(r) && !unicode.IsDigit(r) {
+			upper = true
+			continue
+		}
+		if upper {
+			r = unicode.ToUpper(r)
+			upper = false
+		}
+		identifier.WriteRune(r)
+	}
+	return identifier.String()
+}
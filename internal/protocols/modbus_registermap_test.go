@@ -0,0 +1,104 @@
+package protocols
+
+import (
+	"os"
+	"path/filepath"
+	"strings"
+	"testing"
+
+	"github.com/stretchr/testify/assert"
+)
+
+const boilerRegisterMapCSV = `Tag Name,Slave,Register,Type,Byte Order,Gain,Units
+# Exported from the vendor manual
+boiler.pressure,3,40013,uint16,,0.1,bar
+boiler.flow,3,300101,float32,CDAB,,m3/h
+boiler.running,,00006,,,,
+`
+
+func TestParseRegisterMapCSV(t *testing.T) {
+	definitions, err := ParseRegisterMapCSV(strings.NewReader(boilerRegisterMapCSV))
+	assert.NoError(t, err)
+	assert.Len(t, definitions, 3)
+
+	pressure := definitions[0]
+	assert.Equal(t, "boiler.pressure", pressure.Name)
+	assert.Equal(t, byte(3), pressure.UnitID)
+	assert.Equal(t, TableHoldingRegisters, pressure.Table)
+	assert.Equal(t, uint16(12), pressure.Address)
+	assert.Equal(t, 0.1, pressure.Scale)
+	assert.Equal(t, "bar", pressure.Unit)
+
+	flow := definitions[1]
+	assert.Equal(t, TableInputRegisters, flow.Table)
+	assert.Equal(t, uint16(100), flow.Address)
+	assert.Equal(t, DataTypeFloat32, flow.DataType)
+	assert.Equal(t, WordOrderCDAB, flow.WordOrder)
+
+	running := definitions[2]
+	assert.Equal(t, TableCoils, running.Table)
+	assert.Equal(t, uint16(5), running.Address)
+	assert.Equal(t, DataTypeBool, running.DataType)
+
+	_, err = ParseRegisterMapCSV(strings.NewReader("name,address\nbad,20001\n"))
+	assert.Error(t, err)
+}
+
+func TestParseRegisterMapJSON(t *testing.T) {
+	definitions, err := ParseRegisterMapJSON(strings.NewReader(`{"tags": [
+		{"name": "pump.speed", "table": "holding", "address": "0x1000", "data_type": "int32", "scale": 0.5},
+		{"name": "pump.model", "table": "input_registers", "address": 200, "data_type": "string", "length": 8}
+	]}`))
+	assert.NoError(t, err)
+	assert.Len(t, definitions, 2)
+
+	assert.Equal(t, uint16(0x1000), definitions[0].Address)
+	assert.Equal(t, DataTypeInt32, definitions[0].DataType)
+	assert.Equal(t, 0.5, definitions[0].Scale)
+	assert.Equal(t, 8, definitions[1].Length)
+
+	_, err = ParseRegisterMapJSON(strings.NewReader(`[{"name": "x", "table": "registers", "address": 1}]`))
+	assert.Error(t, err)
+}
+
+func TestLoadRegisterMap(t *testing.T) {
+	path := filepath.Join(t.TempDir(), "boiler.csv")
+	assert.NoError(t, os.WriteFile(path, []byte(boilerRegisterMapCSV), 0644))
+
+	tags, err := LoadRegisterMap(path)
+	assert.NoError(t, err)
+	assert.Equal(t, []string{"boiler.flow", "boiler.pressure", "boiler.running"}, tags.Names())
+
+	value, err := tags.Decode("boiler.pressure", []byte{0x00, 0x64})
+	assert.NoError(t, err)
+	assert.InDelta(t, 10.0, value, 1e-9)
+
+	duplicate := filepath.Join(t.TempDir(), "duplicate.csv")
+	assert.NoError(t, os.WriteFile(duplicate, []byte("name,address\na,40001\na,40002\n"), 0644))
+	_, err = LoadRegisterMap(duplicate)
+	assert.Error(t, err)
+}
+
+func TestGenerateTagMapSource(t *testing.T) {
+	definitions, err := ParseRegisterMapCSV(strings.NewReader(boilerRegisterMapCSV))
+	assert.NoError(t, err)
+
+	tags := NewTagMap()
+	for _, def := range definitions {
+		assert.NoError(t, tags.Add(def))
+	}
+
+	source, err := GenerateTagMapSource(tags, "boiler", "NewBoilerTags", "maps/boiler.csv")
+	assert.NoError(t, err)
+
+	generated := string(source)
+	assert.Contains(t, generated, "// Code generated by regmapgen from boiler.csv; DO NOT EDIT.")
+	assert.Contains(t, generated, "package boiler")
+	assert.Contains(t, generated, `TagBoilerPressure = "boiler.pressure"`)
+	assert.Contains(t, generated, "func NewBoilerTags() (*protocols.TagMap, error) {")
+	assert.Contains(t, generated, "{Name: TagBoilerFlow, UnitID: 3, Table: protocols.TableInputRegisters, Address: 100, "+
+		"DataType: protocols.DataTypeFloat32, WordOrder: protocols.WordOrderCDAB, Unit: \"m3/h\"},")
+	assert.Contains(t, generated, "Scale: 0.1")
+
+	assert.Equal(t, "TagPumpFlowRate2", tagIdentifier("pump.flow_rate-2"))
+}
@@ -0,0 +1,319 @@
+package protocols
+
+import (
+	"encoding/binary"
+	"fmt"
+
+	"github.com/goburrow/modbus"
+)
+
+// Fluent Modbus Request Builder
+//
+// ModbusUnit(3).ReadHolding(0x1000, 10).RTU() encodes a request frame and
+// describes the response it expects in one step:
+//
+//	frame, err := ModbusUnit(3).ReadHolding(0x1000, 10).RTU()
+//	port.Write(frame.ADU)
+//	...
+//	registers, err := frame.Expected.Registers(responseFrame)
+
+// ModbusRequestBuilder builds requests addressed to one unit
+type ModbusRequestBuilder struct {
+	unitID byte
+}
+
+// ModbusRequest is a request PDU addressed to a unit, ready to be framed
+type ModbusRequest struct {
+	UnitID byte
+	PDU    *modbus.ProtocolDataUnit
+}
+
+// ModbusFrame is an encoded request together with the response it expects
+type ModbusFrame struct {
+	ADU      []byte
+	Expected *ModbusExpectedResponse
+}
+
+// ModbusExpectedResponse describes the response to an encoded request and
+// decodes it
+type ModbusExpectedResponse struct {
+	UnitID        byte
+	TransactionID uint16 // Modbus TCP only
+	FunctionCode  byte
+
+	// FrameLength is the length of a normal response frame, or 0 when the
+	// function code has no fixed response layout. Exception responses are
+	// shorter.
+	FrameLength int
+
+	request *modbus.ProtocolDataUnit
+	tcp     bool
+}
+
+// ModbusUnit starts a request to unitID
+func ModbusUnit(unitID byte) *ModbusRequestBuilder {
+	return &ModbusRequestBuilder{unitID: unitID}
+}
+
+// ReadCoils builds a Read Coils (0x01) request
+func (b *ModbusRequestBuilder) ReadCoils(address, quantity uint16) *ModbusRequest {
+	return b.addressQuantity(ReadCoils, address, quantity)
+}
+
+// ReadDiscreteInputs builds a Read Discrete Inputs (0x02) request
+func (b *ModbusRequestBuilder) ReadDiscreteInputs(address, quantity uint16) *ModbusRequest {
+	return b.addressQuantity(ReadDiscreteInputs, address, quantity)
+}
+
+// ReadHolding builds a Read Holding Registers (0x03) request
+func (b *ModbusRequestBuilder) ReadHolding(address, quantity uint16) *ModbusRequest {
+	return b.addressQuantity(ReadHoldingRegisters, address, quantity)
+}
+
+// ReadInput builds a Read Input Registers (0x04) request
+func (b *ModbusRequestBuilder) ReadInput(address, quantity uint16) *ModbusRequest {
+	return b.addressQuantity(ReadInputRegisters, address, quantity)
+}
+
+// WriteCoil builds a Write Single Coil (0x05) request
+func (b *ModbusRequestBuilder) WriteCoil(address uint16, value bool) *ModbusRequest {
+	coil := uint16(coilOffValue)
+	if value {
+		coil = coilOnValue
+	}
+	return b.addressQuantity(WriteSingleCoil, address, coil)
+}
+
+// WriteRegister builds a Write Single Register (0x06) request
+func (b *ModbusRequestBuilder) WriteRegister(address, value uint16) *ModbusRequest {
+	return b.addressQuantity(WriteSingleRegister, address, value)
+}
+
+// WriteCoils builds a Write Multiple Coils (0x0F) request
+func (b *ModbusRequestBuilder) WriteCoils(address uint16, values []bool) *ModbusRequest {
+	packed := make([]byte, (len(values)+7)/8)
+	for i, value := range values {
+		if value {
+			packed[i/8] |= 1 << uint(i%8)
+		}
+	}
+	return b.multipleWrite(WriteMultipleCoils, address, uint16(len(values)), packed)
+}
+
+// WriteRegisters builds a Write Multiple Registers (0x10) request
+func (b *ModbusRequestBuilder) WriteRegisters(address uint16, values []uint16) *ModbusRequest {
+	return b.multipleWrite(WriteMultipleRegisters, address, uint16(len(values)), registersToBytes(values))
+}
+
+// MaskWrite builds a Mask Write Register (0x16) request
+func (b *ModbusRequestBuilder) MaskWrite(address, andMask, orMask uint16) *ModbusRequest {
+	data := make([]byte, maskWriteRequestDataLen)
+	binary.BigEndian.PutUint16(data[0:2], address)
+	binary.BigEndian.PutUint16(data[2:4], andMask)
+	binary.BigEndian.PutUint16(data[4:6], orMask)
+	return b.request(MaskWriteRegister, data)
+}
+
+// ReadWriteRegisters builds a Read/Write Multiple Registers (0x17) request
+func (b *ModbusRequestBuilder) ReadWriteRegisters(readAddress, readQuantity, writeAddress uint16, values []uint16) *ModbusRequest {
+	data := make([]byte, readWriteHeaderDataLen, readWriteHeaderDataLen+len(values)*2)
+	binary.BigEndian.PutUint16(data[0:2], readAddress)
+	binary.BigEndian.PutUint16(data[2:4], readQuantity)
+	binary.BigEndian.PutUint16(data[4:6], writeAddress)
+	binary.BigEndian.PutUint16(data[6:8], uint16(len(values)))
+	data[8] = byte(len(values) * 2)
+	return b.request(ReadWriteMultipleRegisters, append(data, registersToBytes(values)...))
+}
+
+// Raw builds a request with an arbitrary function code and data
+func (b *ModbusRequestBuilder) Raw(functionCode byte, data []byte) *ModbusRequest {
+	return &ModbusRequest{UnitID: b.unitID, PDU: &modbus.ProtocolDataUnit{FunctionCode: functionCode, Data: data}}
+}
+
+func (b *ModbusRequestBuilder) addressQuantity(functionCode ModbusFunctionCode, address, value uint16) *ModbusRequest {
+	data := make([]byte, singleWriteRequestDataLen)
+	binary.BigEndian.PutUint16(data[0:2], address)
+	binary.BigEndian.PutUint16(data[2:4], value)
+	return b.request(functionCode, data)
+}
+
+func (b *ModbusRequestBuilder) multipleWrite(functionCode ModbusFunctionCode, address, quantity uint16, payload []byte) *ModbusRequest {
+	data := make([]byte, multipleWriteHeaderDataLen, multipleWriteHeaderDataLen+len(payload))
+	binary.BigEndian.PutUint16(data[0:2], address)
+	binary.BigEndian.PutUint16(data[2:4], quantity)
+	data[4] = byte(len(payload))
+	return b.request(functionCode, append(data, payload...))
+}
+
+func (b *ModbusRequestBuilder) request(functionCode ModbusFunctionCode, data []byte) *ModbusRequest {
+	return b.Raw(byte(functionCode), data)
+}
+
+// RTU validates the request and encodes it as an RTU frame
+func (r *ModbusRequest) RTU() (*ModbusFrame, error) {
+	if err := r.validate(); err != nil {
+		return nil, err
+	}
+
+	adu, err := encodeRTUFrame(r.UnitID, r.PDU)
+	if err != nil {
+		return nil, err
+	}
+
+	expected := r.expected()
+	if pduLength := expectedResponsePDULength(r.PDU); pduLength > 0 {
+		expected.FrameLength = 1 + pduLength + 2
+	}
+	return &ModbusFrame{ADU: adu, Expected: expected}, nil
+}
+
+// TCP validates the request and encodes it as a Modbus TCP frame with the
+// given transaction identifier
+func (r *ModbusRequest) TCP(transactionID uint16) (*ModbusFrame, error) {
+	if err := r.validate(); err != nil {
+		return nil, err
+	}
+	if modbusTCPHeaderSize+1+len(r.PDU.Data) > modbusTCPMaxADUSize {
+		return nil, NewModbusError(ModbusErrorFrame,
+			fmt.Sprintf("PDU length %d exceeds maximum", 1+len(r.PDU.Data)), "encode")
+	}
+
+	expected := r.expected()
+	expected.TransactionID = transactionID
+	expected.tcp = true
+	if pduLength := expectedResponsePDULength(r.PDU); pduLength > 0 {
+		expected.FrameLength = modbusTCPHeaderSize + pduLength
+	}
+	return &ModbusFrame{ADU: encodeMBAPFrame(transactionID, r.UnitID, r.PDU), Expected: expected}, nil
+}
+
+// AppendRTU validates the request and appends its RTU frame to dst. Polling
+// loops can reuse one buffer for every request instead of allocating a frame
+// each time.
+func (r *ModbusRequest) AppendRTU(dst []byte) ([]byte, error) {
+	if err := r.validate(); err != nil {
+		return dst, err
+	}
+	return appendRTUFrame(dst, r.UnitID, r.PDU)
+}
+
+// AppendTCP validates the request and appends its Modbus TCP frame with the
+// given transaction identifier to dst
+func (r *ModbusRequest) AppendTCP(dst []byte, transactionID uint16) ([]byte, error) {
+	if err := r.validate(); err != nil {
+		return dst, err
+	}
+	if modbusTCPHeaderSize+1+len(r.PDU.Data) > modbusTCPMaxADUSize {
+		return dst, NewModbusError(ModbusErrorFrame,
+			fmt.Sprintf("PDU length %d exceeds maximum", 1+len(r.PDU.Data)), "encode")
+	}
+	return appendMBAPFrame(dst, transactionID, r.UnitID, r.PDU), nil
+}
+
+func (r *ModbusRequest) validate() error {
+	if r.UnitID == BroadcastUnitID {
+		return ValidateBroadcastPDU(r.PDU)
+	}
+	return ValidateRequestPDU(r.PDU)
+}
+
+func (r *ModbusRequest) expected() *ModbusExpectedResponse {
+	return &ModbusExpectedResponse{
+		UnitID:       r.UnitID,
+		FunctionCode: r.PDU.FunctionCode,
+		request:      r.PDU,
+	}
+}
+
+// Decode checks that adu answers the request and returns its PDU. Exception
+// responses are returned as *modbus.ModbusError.
+func (e *ModbusExpectedResponse) Decode(adu []byte) (*modbus.ProtocolDataUnit, error) {
+	if e.UnitID == BroadcastUnitID {
+		return nil, validationError(ModbusErrorBroadcast, e.request, "broadcast requests have no response")
+	}
+
+	var unitID byte
+	var response *modbus.ProtocolDataUnit
+	var err error
+	if e.tcp {
+		var transactionID uint16
+		transactionID, unitID, response, err = decodeMBAPFrame(adu)
+		if err == nil && transactionID != e.TransactionID {
+			modbusErr := NewModbusError(ModbusErrorTransactionMismatch,
+				fmt.Sprintf("response transaction %d does not match request", transactionID), "decode")
+			modbusErr.TransactionID = e.TransactionID
+			return nil, modbusErr
+		}
+	} else {
+		unitID, response, err = decodeRTUFrame(adu)
+	}
+	if err != nil {
+		return nil, err
+	}
+
+	if unitID != e.UnitID {
+		modbusErr := NewModbusError(ModbusErrorTransactionMismatch,
+			fmt.Sprintf("response from unit %d does not match request", unitID), "decode")
+		modbusErr.UnitID = e.UnitID
+		return nil, modbusErr
+	}
+
+	if err := ValidateResponsePDU(e.request, response); err != nil {
+		return nil, err
+	}
+	if err := exceptionError(response); err != nil {
+		return nil, err
+	}
+
+	return response, nil
+}
+
+// Registers decodes a register read response into register values
+func (e *ModbusExpectedResponse) Registers(adu []byte) ([]uint16, error) {
+	response, err := e.Decode(adu)
+	if err != nil {
+		return nil, err
+	}
+
+	switch ModbusFunctionCode(e.FunctionCode) {
+	case ReadHoldingRegisters, ReadInputRegisters, ReadWriteMultipleRegisters:
+		return bytesToRegisters(response.Data[1:]), nil
+	}
+	return nil, fmt.Errorf("function code 0x%02X does not return registers", e.FunctionCode)
+}
+
+// Bits decodes a coil or discrete input read response into one value per
+// requested bit
+func (e *ModbusExpectedResponse) Bits(adu []byte) ([]bool, error) {
+	response, err := e.Decode(adu)
+	if err != nil {
+		return nil, err
+	}
+
+	switch ModbusFunctionCode(e.FunctionCode) {
+	case ReadCoils, ReadDiscreteInputs:
+		quantity := int(binary.BigEndian.Uint16(e.request.Data[2:4]))
+		bits := make([]bool, quantity)
+		for i := range bits {
+			bits[i] = response.Data[1+i/8]&(1<<uint(i%8)) != 0
+		}
+		return bits, nil
+	}
+	return nil, fmt.Errorf("function code 0x%02X does not return bits", e.FunctionCode)
+}
+
+// expectedResponsePDULength returns the length of the normal response PDU
+// (including the function code) to a validated request, or 0 if unknown
+func expectedResponsePDULength(request *modbus.ProtocolDataUnit) int {
+	switch ModbusFunctionCode(request.FunctionCode) {
+	case ReadCoils, ReadDiscreteInputs:
+		return 2 + (int(binary.BigEndian.Uint16(request.Data[2:4]))+7)/8
+	case ReadHoldingRegisters, ReadInputRegisters, ReadWriteMultipleRegisters:
+		return 2 + int(binary.BigEndian.Uint16(request.Data[2:4]))*2
+	case WriteSingleCoil, WriteSingleRegister, WriteMultipleCoils, WriteMultipleRegisters:
+		return 1 + singleWriteRequestDataLen
+	case MaskWriteRegister:
+		return 1 + maskWriteRequestDataLen
+	}
+	return 0
+}
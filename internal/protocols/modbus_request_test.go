@@ -0,0 +1,96 @@
+package protocols
+
+import (
+	"testing"
+
+	"github.com/goburrow/modbus"
+	"github.com/stretchr/testify/assert"
+)
+
+func TestModbusRequestBuilder_RTU(t *testing.T) {
+	frame, err := ModbusUnit(1).ReadHolding(0x0000, 1).RTU()
+	assert.NoError(t, err)
+	assert.Equal(t, []byte{0x01, 0x03, 0x00, 0x00, 0x00, 0x01, 0x84, 0x0A}, frame.ADU)
+	assert.Equal(t, 7, frame.Expected.FrameLength)
+
+	response, _ := encodeRTUFrame(1, &modbus.ProtocolDataUnit{FunctionCode: 0x03, Data: []byte{0x02, 0x12, 0x34}})
+	registers, err := frame.Expected.Registers(response)
+	assert.NoError(t, err)
+	assert.Equal(t, []uint16{0x1234}, registers)
+
+	// A reply from another unit is rejected
+	response, _ = encodeRTUFrame(2, &modbus.ProtocolDataUnit{FunctionCode: 0x03, Data: []byte{0x02, 0x12, 0x34}})
+	_, err = frame.Expected.Decode(response)
+	assertValidationCategory(t, err, ModbusErrorTransactionMismatch)
+
+	// Exception responses surface as modbus errors
+	response, _ = encodeRTUFrame(1, &modbus.ProtocolDataUnit{FunctionCode: 0x83, Data: []byte{0x02}})
+	_, err = frame.Expected.Decode(response)
+	assert.Equal(t, &modbus.ModbusError{FunctionCode: 0x83, ExceptionCode: 0x02}, err)
+}
+
+func TestModbusRequestBuilder_TCP(t *testing.T) {
+	frame, err := ModbusUnit(3).ReadCoils(0x0010, 10).TCP(7)
+	assert.NoError(t, err)
+	assert.Equal(t, []byte{0x00, 0x07, 0x00, 0x00, 0x00, 0x06, 0x03, 0x01, 0x00, 0x10, 0x00, 0x0A}, frame.ADU)
+	assert.Equal(t, 11, frame.Expected.FrameLength)
+
+	bits, err := frame.Expected.Bits(encodeMBAPFrame(7, 3, &modbus.ProtocolDataUnit{FunctionCode: 0x01, Data: []byte{0x02, 0x05, 0x02}}))
+	assert.NoError(t, err)
+	assert.Equal(t, []bool{true, false, true, false, false, false, false, false, false, true}, bits)
+
+	_, err = frame.Expected.Decode(encodeMBAPFrame(8, 3, &modbus.ProtocolDataUnit{FunctionCode: 0x01, Data: []byte{0x02, 0x05, 0x02}}))
+	assertValidationCategory(t, err, ModbusErrorTransactionMismatch)
+}
+
+func TestModbusRequest_Append(t *testing.T) {
+	request := ModbusUnit(3).ReadCoils(0x0010, 10)
+	buffer := make([]byte, 0, modbusTCPMaxADUSize)
+
+	adu, err := request.AppendTCP(buffer, 7)
+	assert.NoError(t, err)
+	assert.Equal(t, []byte{0x00, 0x07, 0x00, 0x00, 0x00, 0x06, 0x03, 0x01, 0x00, 0x10, 0x00, 0x0A}, adu)
+	assert.Equal(t, 0.0, testing.AllocsPerRun(100, func() { request.AppendTCP(buffer[:0], 8) }))
+
+	adu, err = request.AppendRTU(buffer[:0])
+	assert.NoError(t, err)
+	frame, _ := request.RTU()
+	assert.Equal(t, frame.ADU, adu)
+
+	_, err = ModbusUnit(1).ReadHolding(0, 0).AppendTCP(buffer[:0], 1)
+	assertValidationCategory(t, err, ModbusErrorInvalidQuantity)
+}
+
+func TestModbusRequestBuilder_Writes(t *testing.T) {
+	request := ModbusUnit(1).WriteCoils(0x0013, []bool{true, false, true, true, false, false, true, true, true, false})
+	assert.Equal(t, []byte{0x00, 0x13, 0x00, 0x0A, 0x02, 0xCD, 0x01}, request.PDU.Data)
+
+	request = ModbusUnit(1).WriteRegisters(0x0001, []uint16{0x000A, 0x0102})
+	assert.Equal(t, []byte{0x00, 0x01, 0x00, 0x02, 0x04, 0x00, 0x0A, 0x01, 0x02}, request.PDU.Data)
+
+	frame, err := ModbusUnit(1).WriteCoil(0x00AC, true).RTU()
+	assert.NoError(t, err)
+	assert.Equal(t, []byte{0x01, 0x05, 0x00, 0xAC, 0xFF, 0x00}, frame.ADU[:6])
+	assert.Equal(t, 8, frame.Expected.FrameLength)
+
+	frame, err = ModbusUnit(1).MaskWrite(0x0004, 0x00F2, 0x0025).RTU()
+	assert.NoError(t, err)
+	assert.Equal(t, 10, frame.Expected.FrameLength)
+
+	frame, err = ModbusUnit(1).ReadWriteRegisters(0x0003, 6, 0x000E, []uint16{0x00FF, 0x00FF, 0x00FF}).TCP(1)
+	assert.NoError(t, err)
+	assert.Equal(t, modbusTCPHeaderSize+2+12, frame.Expected.FrameLength)
+}
+
+func TestModbusRequestBuilder_Validation(t *testing.T) {
+	_, err := ModbusUnit(1).ReadHolding(0x0000, MaxReadRegistersQuantity+1).RTU()
+	assertValidationCategory(t, err, ModbusErrorInvalidQuantity)
+
+	_, err = ModbusUnit(BroadcastUnitID).ReadHolding(0x0000, 1).TCP(1)
+	assertValidationCategory(t, err, ModbusErrorBroadcast)
+
+	frame, err := ModbusUnit(BroadcastUnitID).WriteRegister(0x0001, 0x002A).RTU()
+	assert.NoError(t, err)
+	_, err = frame.Expected.Decode(frame.ADU)
+	assertValidationCategory(t, err, ModbusErrorBroadcast)
+}
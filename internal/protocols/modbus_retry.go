@@ -0,0 +1,143 @@
+package protocols
+
+import (
+	"errors"
+	"math"
+	"sync/atomic"
+	"time"
+
+	"github.com/goburrow/modbus"
+)
+
+// Modbus Retry Policy
+
+// RetryPolicy controls how the Modbus transports retry failed requests.
+// Only errors in RetryOn and exceptions in RetryOnExceptions are retried;
+// exceptions such as Illegal Data Address are never retried unless listed.
+type RetryPolicy struct {
+	MaxAttempts int `yaml:"max_attempts"` // Including the first attempt
+
+	// Per-attempt timeout by function code; functions not listed use the
+	// transport timeout
+	FunctionTimeouts map[ModbusFunctionCode]time.Duration `yaml:"function_timeouts"`
+
+	InitialBackoff time.Duration `yaml:"initial_backoff"`
+	MaxBackoff     time.Duration `yaml:"max_backoff"`
+	Multiplier     float64       `yaml:"multiplier"`
+
+	RetryOn           []ModbusErrorCategory `yaml:"retry_on"`
+	RetryOnExceptions []ModbusExceptionCode `yaml:"retry_on_exceptions"`
+}
+
+// DefaultRetryPolicy retries timeouts, CRC errors, connection failures and
+// busy slaves up to three attempts with exponential backoff
+func DefaultRetryPolicy() *RetryPolicy {
+	return &RetryPolicy{
+		MaxAttempts:       3,
+		InitialBackoff:    100 * time.Millisecond,
+		MaxBackoff:        2 * time.Second,
+		Multiplier:        2,
+		RetryOn:           []ModbusErrorCategory{ModbusErrorTimeout, ModbusErrorCRC, ModbusErrorConnection},
+		RetryOnExceptions: []ModbusExceptionCode{ExceptionServerDeviceBusy},
+	}
+}
+
+// ShouldRetry reports whether err is retryable under the policy
+func (p *RetryPolicy) ShouldRetry(err error) bool {
+	var exception *modbus.ModbusError
+	if errors.As(err, &exception) {
+		for _, code := range p.RetryOnExceptions {
+			if byte(code) == exception.ExceptionCode {
+				return true
+			}
+		}
+		return false
+	}
+
+	var modbusErr *ModbusError
+	if errors.As(err, &modbusErr) {
+		for _, category := range p.RetryOn {
+			if category == modbusErr.ErrorCategory {
+				return true
+			}
+		}
+	}
+
+	return false
+}
+
+// Backoff returns the delay before the retry that follows attempt
+// (counting from 1)
+func (p *RetryPolicy) Backoff(attempt int) time.Duration {
+	return exponentialBackoff(p.InitialBackoff, p.MaxBackoff, p.Multiplier, attempt)
+}
+
+// exponentialBackoff returns initial * multiplier^(attempt-1), capped at max
+// when max is positive
+func exponentialBackoff(initial, max time.Duration, multiplier float64, attempt int) time.Duration {
+	if multiplier < 1 {
+		multiplier = 1
+	}
+
+	backoff := time.Duration(float64(initial) * math.Pow(multiplier, float64(attempt-1)))
+	if max > 0 && (backoff > max || backoff < 0) {
+		backoff = max
+	}
+	return backoff
+}
+
+// TimeoutFor returns the per-attempt timeout for functionCode
+func (p *RetryPolicy) TimeoutFor(functionCode byte, defaultTimeout time.Duration) time.Duration {
+	if timeout, exists := p.FunctionTimeouts[ModbusFunctionCode(functionCode)]; exists && timeout > 0 {
+		return timeout
+	}
+	return defaultTimeout
+}
+
+// do runs attempt until it succeeds, fails with a non-retryable error or
+// the attempts are exhausted, counting retries in retries. A nil policy
+// makes a single attempt.
+func (p *RetryPolicy) do(functionCode byte, defaultTimeout time.Duration, retries *uint64, attempt func(timeout time.Duration) error) error {
+	if p == nil {
+		return attempt(defaultTimeout)
+	}
+
+	timeout := p.TimeoutFor(functionCode, defaultTimeout)
+	for n := 1; ; n++ {
+		err := attempt(timeout)
+		if err == nil || n >= p.MaxAttempts || !p.ShouldRetry(err) {
+			return err
+		}
+
+		atomic.AddUint64(retries, 1)
+		time.Sleep(p.Backoff(n))
+	}
+}
+
+// exceptionError returns the exception carried by pdu, if any
+func exceptionError(pdu *modbus.ProtocolDataUnit) error {
+	if !IsExceptionResponse(pdu) || len(pdu.Data) < 1 {
+		return nil
+	}
+	return &modbus.ModbusError{FunctionCode: pdu.FunctionCode, ExceptionCode: pdu.Data[0]}
+}
+
+// SendPDU sends a request PDU to the device and returns the response data.
+// policy overrides the connection's retry policy for this request when not
+// nil.
+func (m *ModbusHandler) SendPDU(device *Device, functionCode byte, data []byte, policy *RetryPolicy) ([]byte, error) {
+	conn, err := m.getConnection(device)
+	if err != nil {
+		return nil, err
+	}
+
+	conn.mutex.Lock()
+	defer conn.mutex.Unlock()
+
+	conn.lastUsed = time.Now()
+
+	if policy == nil {
+		policy = conn.handler.RetryPolicy
+	}
+	return m.sendUnitPDUWithPolicy(conn, conn.handler.SlaveId, functionCode, data, policy)
+}
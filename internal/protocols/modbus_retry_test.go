@@ -0,0 +1,83 @@
+package protocols
+
+import (
+	"sync"
+	"testing"
+	"time"
+
+	"github.com/goburrow/modbus"
+	"github.com/stretchr/testify/assert"
+	"go.uber.org/zap"
+)
+
+func TestRetryPolicy_Backoff(t *testing.T) {
+	policy := DefaultRetryPolicy()
+	policy.MaxBackoff = 300 * time.Millisecond
+
+	assert.Equal(t, 100*time.Millisecond, policy.Backoff(1))
+	assert.Equal(t, 200*time.Millisecond, policy.Backoff(2))
+	assert.Equal(t, 300*time.Millisecond, policy.Backoff(3))
+}
+
+func TestRetryPolicy_ShouldRetry(t *testing.T) {
+	policy := DefaultRetryPolicy()
+
+	assert.True(t, policy.ShouldRetry(NewModbusError(ModbusErrorTimeout, "timeout", "send")))
+	assert.True(t, policy.ShouldRetry(NewModbusError(ModbusErrorCRC, "CRC mismatch", "decode")))
+	assert.False(t, policy.ShouldRetry(NewModbusError(ModbusErrorInvalidQuantity, "too many", "validate")))
+	assert.True(t, policy.ShouldRetry(&modbus.ModbusError{FunctionCode: 0x83, ExceptionCode: byte(ExceptionServerDeviceBusy)}))
+	assert.False(t, policy.ShouldRetry(&modbus.ModbusError{FunctionCode: 0x83, ExceptionCode: byte(ExceptionIllegalDataAddress)}))
+}
+
+func TestRetryPolicy_TimeoutFor(t *testing.T) {
+	policy := &RetryPolicy{FunctionTimeouts: map[ModbusFunctionCode]time.Duration{WriteFileRecord: 10 * time.Second}}
+
+	assert.Equal(t, 10*time.Second, policy.TimeoutFor(byte(WriteFileRecord), time.Second))
+	assert.Equal(t, time.Second, policy.TimeoutFor(byte(ReadHoldingRegisters), time.Second))
+}
+
+func TestModbusHandler_RetriesBusySlave(t *testing.T) {
+	var mutex sync.Mutex
+	requests := 0
+	device := startModbusTestServer(t, func(unitID byte, pdu []byte) []byte {
+		mutex.Lock()
+		defer mutex.Unlock()
+
+		requests++
+		switch {
+		case pdu[2] == 0x20:
+			return []byte{0x83, byte(ExceptionIllegalDataAddress)}
+		case pdu[2] == 0x10, requests == 1:
+			return []byte{0x83, byte(ExceptionServerDeviceBusy)}
+		}
+		return []byte{0x03, 0x02, 0x00, 0x2A}
+	})
+
+	handler := NewModbusHandler(zap.NewNop()).(*ModbusHandler)
+	handler.config.RetryPolicy = &RetryPolicy{
+		MaxAttempts:       3,
+		InitialBackoff:    time.Millisecond,
+		RetryOnExceptions: []ModbusExceptionCode{ExceptionServerDeviceBusy},
+	}
+	assert.NoError(t, handler.Connect(device))
+	defer handler.Disconnect(device)
+
+	value, err := handler.ReadTag(device, &Tag{ID: "t", Address: "40001", DataType: string(DataTypeUInt16)})
+	assert.NoError(t, err)
+	assert.Equal(t, uint16(42), value)
+	assert.Equal(t, 2, requests)
+
+	// Illegal data address is not retried
+	_, err = handler.ReadTag(device, &Tag{ID: "t", Address: "40033", DataType: string(DataTypeUInt16)})
+	assert.Equal(t, ExceptionIllegalDataAddress, ExceptionCodeForError(err))
+	assert.Equal(t, 3, requests)
+
+	// A per-request policy overrides the connection policy
+	_, err = handler.SendPDU(device, 0x03, []byte{0x00, 0x10, 0x00, 0x01}, &RetryPolicy{MaxAttempts: 1})
+	assert.Equal(t, ExceptionServerDeviceBusy, ExceptionCodeForError(err))
+	assert.Equal(t, 4, requests)
+
+	diagnostics, err := handler.GetDiagnostics(device)
+	assert.NoError(t, err)
+	assert.Equal(t, uint64(1), diagnostics.ProtocolDiagnostics.(map[string]interface{})["retries"])
+}
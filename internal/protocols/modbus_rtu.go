@@ -0,0 +1,499 @@
+package protocols
+
+import (
+	"encoding/binary"
+	"errors"
+	"fmt"
+	"io"
+	"net"
+	"sync"
+	"sync/atomic"
+	"time"
+
+	"github.com/goburrow/modbus"
+	"github.com/goburrow/serial"
+	"go.uber.org/zap"
+)
+
+// Modbus RTU Transport
+//
+// RTU frames carry the unit identifier, the PDU and a CRC-16 (low byte
+// first). There is no length field, so the expected response length is
+// derived from the function code and byte count.
+
+const (
+	rtuMinFrameSize = 4   // unit identifier + function code + CRC
+	rtuMaxFrameSize = 256 // unit identifier + 253 byte PDU + CRC
+	rtuHeaderSize   = 3   // unit identifier + function code + first data byte
+
+	rtuCharacterBits = 11                      // start bit + 8 data bits + parity + stop bit
+	rtuFrameGapChars = 3.5                     // silent interval that separates frames
+	rtuFixedGapBaud  = 19200                   // baud rate above which the gap is fixed
+	rtuHighSpeedGap  = 1750 * time.Microsecond // recommended gap above rtuFixedGapBaud
+
+	rtuMaxPollInterval = 50 * time.Millisecond // longest single read of a serial port
+)
+
+// RTUFrameGapMode selects how the RTU inter-frame gap is determined
+type RTUFrameGapMode int
+
+const (
+	// RTUFrameGapBaudRate derives the gap from BaudRate using the 3.5
+	// character rule, with the fixed 1.75ms the spec recommends above
+	// 19200 baud
+	RTUFrameGapBaudRate RTUFrameGapMode = iota
+
+	// RTUFrameGapFixed uses FrameGap regardless of baud rate, for USB
+	// adapters that buffer bytes and do not preserve line timing
+	RTUFrameGapFixed
+)
+
+// ModbusRTUTransport sends requests to slaves on one serial line. Requests
+// are serialized, since an RTU bus carries one transaction at a time.
+type ModbusRTUTransport struct {
+	Address  string // Serial device, e.g. /dev/ttyUSB0
+	BaudRate int
+	DataBits int
+	StopBits int
+	Parity   string // "N", "E" or "O"
+	Timeout  time.Duration
+
+	// TurnaroundDelay is how long the bus is kept idle after a broadcast
+	TurnaroundDelay time.Duration
+
+	// RetryPolicy controls retries of failed requests; nil disables retries
+	RetryPolicy *RetryPolicy
+
+	// FrameGapMode selects how the inter-frame gap is derived; FrameGap is
+	// the gap used in RTUFrameGapFixed mode
+	FrameGapMode RTUFrameGapMode
+	FrameGap     time.Duration
+
+	// Recorder receives sent and received frames when set
+	Recorder FrameRecorder
+
+	logger  *zap.Logger
+	retries uint64
+	stats   ModbusStats
+
+	mutex     sync.Mutex // serializes transactions and guards port
+	port      io.ReadWriteCloser
+	dial      func() (io.ReadWriteCloser, error)
+	lastFrame time.Time // end of the last frame sent or received
+
+	// Frame buffers reused by every transaction, guarded by mutex
+	txBuffer [rtuMaxFrameSize]byte
+	rxBuffer [rtuMaxFrameSize]byte
+}
+
+// NewModbusRTUTransport creates an RTU transport for the serial device at
+// address with the usual 19200 8E1 line settings
+func NewModbusRTUTransport(address string, logger *zap.Logger) *ModbusRTUTransport {
+	t := &ModbusRTUTransport{
+		Address:  address,
+		BaudRate: 19200,
+		DataBits: 8,
+		StopBits: 1,
+		Parity:   "E",
+		Timeout:  1 * time.Second,
+		logger:   logger,
+
+		TurnaroundDelay: defaultTurnaroundDelay,
+	}
+	t.dial = t.openSerial
+	return t
+}
+
+// Connect opens the serial port
+func (t *ModbusRTUTransport) Connect() error {
+	t.mutex.Lock()
+	defer t.mutex.Unlock()
+
+	return t.connect()
+}
+
+func (t *ModbusRTUTransport) connect() error {
+	if t.port != nil {
+		return nil
+	}
+
+	port, err := t.dial()
+	if err != nil {
+		return NewModbusError(ModbusErrorConnection, fmt.Sprintf("failed to open %s: %v", t.Address, err), "connect")
+	}
+
+	t.port = port
+	return nil
+}
+
+// Close closes the serial port
+func (t *ModbusRTUTransport) Close() error {
+	t.mutex.Lock()
+	defer t.mutex.Unlock()
+
+	if t.port == nil {
+		return nil
+	}
+
+	err := t.port.Close()
+	t.port = nil
+	return err
+}
+
+// Send transmits pdu to unitID and returns the response PDU, which may be
+// an exception response. Use Broadcast for unit 0.
+func (t *ModbusRTUTransport) Send(unitID byte, pdu *modbus.ProtocolDataUnit) (*modbus.ProtocolDataUnit, error) {
+	return t.SendTimeout(unitID, pdu, t.Timeout)
+}
+
+// SendTimeout is Send with a response timeout other than Timeout. A retry
+// policy's per-function timeouts still take precedence.
+func (t *ModbusRTUTransport) SendTimeout(unitID byte, pdu *modbus.ProtocolDataUnit, timeout time.Duration) (*modbus.ProtocolDataUnit, error) {
+	if unitID == BroadcastUnitID {
+		return nil, validationError(ModbusErrorBroadcast, pdu, "broadcast requests have no response")
+	}
+	if err := ValidateRequestPDU(pdu); err != nil {
+		return nil, err
+	}
+	if len(pdu.Data)+rtuMinFrameSize > rtuMaxFrameSize {
+		return nil, NewModbusError(ModbusErrorFrame,
+			fmt.Sprintf("PDU length %d exceeds maximum", 1+len(pdu.Data)), "encode")
+	}
+
+	var response *modbus.ProtocolDataUnit
+	attempt := 0
+	err := t.RetryPolicy.do(pdu.FunctionCode, timeout, &t.retries, func(timeout time.Duration) error {
+		attempt++
+		start := time.Now()
+
+		var err error
+		response, err = t.transact(unitID, pdu, timeout)
+		if err != nil {
+			t.stats.record(unitID, attempt, 0, nil, err)
+			return err
+		}
+
+		t.stats.record(unitID, attempt, time.Since(start), response, nil)
+		return exceptionError(response)
+	})
+	if response != nil {
+		return response, nil
+	}
+	return nil, err
+}
+
+// Retries returns the number of retried requests
+func (t *ModbusRTUTransport) Retries() uint64 {
+	return atomic.LoadUint64(&t.retries)
+}
+
+// Stats returns the per-unit communication statistics
+func (t *ModbusRTUTransport) Stats() *ModbusStats {
+	return &t.stats
+}
+
+// InterFrameGap returns the silent interval that separates frames on the bus
+func (t *ModbusRTUTransport) InterFrameGap() time.Duration {
+	if t.FrameGapMode == RTUFrameGapFixed {
+		return t.FrameGap
+	}
+	if t.BaudRate <= 0 || t.BaudRate > rtuFixedGapBaud {
+		return rtuHighSpeedGap
+	}
+	return time.Duration(rtuFrameGapChars * rtuCharacterBits * float64(time.Second) / float64(t.BaudRate))
+}
+
+// waitForIdle holds off transmission until the bus has been silent for the
+// inter-frame gap since the last frame
+func (t *ModbusRTUTransport) waitForIdle() {
+	if t.lastFrame.IsZero() {
+		return
+	}
+	if wait := time.Until(t.lastFrame.Add(t.InterFrameGap())); wait > 0 {
+		time.Sleep(wait)
+	}
+}
+
+// transact performs one request/response exchange on the bus. The request
+// is encoded into and the response read from the transport's own buffers;
+// only the response data is copied out.
+func (t *ModbusRTUTransport) transact(unitID byte, pdu *modbus.ProtocolDataUnit, timeout time.Duration) (*modbus.ProtocolDataUnit, error) {
+	t.mutex.Lock()
+	defer t.mutex.Unlock()
+
+	if err := t.connect(); err != nil {
+		return nil, err
+	}
+
+	frame, err := appendRTUFrame(t.txBuffer[:0], unitID, pdu)
+	if err != nil {
+		return nil, err
+	}
+
+	t.waitForIdle()
+	if _, err := t.port.Write(frame); err != nil {
+		t.closePort()
+		return nil, NewModbusError(ModbusErrorConnection, fmt.Sprintf("write failed: %v", err), "send")
+	}
+	t.lastFrame = time.Now()
+	recordFrame(t.Recorder, CaptureTransportModbusRTU, t.Address, CaptureSent, frame)
+
+	responseFrame, err := t.readFrame(timeout)
+	t.lastFrame = time.Now()
+	if err != nil {
+		if isTimeoutError(err) {
+			modbusErr := NewModbusError(ModbusErrorTimeout, "response timeout", "send")
+			modbusErr.UnitID = unitID
+			modbusErr.FunctionCode = pdu.FunctionCode
+			return nil, modbusErr
+		}
+		if _, ok := err.(*ModbusError); ok {
+			return nil, err
+		}
+		t.closePort()
+		return nil, NewModbusError(ModbusErrorConnection, fmt.Sprintf("read failed: %v", err), "receive")
+	}
+	recordFrame(t.Recorder, CaptureTransportModbusRTU, t.Address, CaptureReceived, responseFrame)
+
+	responseUnit, response, err := decodeRTUFrame(responseFrame)
+	if err != nil {
+		return nil, err
+	}
+
+	if responseUnit != unitID {
+		modbusErr := NewModbusError(ModbusErrorTransactionMismatch,
+			fmt.Sprintf("response from unit %d does not match request", responseUnit), "receive")
+		modbusErr.UnitID = unitID
+		return nil, modbusErr
+	}
+
+	if err := ValidateResponsePDU(pdu, response); err != nil {
+		return nil, err
+	}
+
+	// The receive buffer is reused by the next transaction
+	response.Data = append(make([]byte, 0, len(response.Data)), response.Data...)
+	return response, nil
+}
+
+// readFrame reads one response frame from the port into the receive
+// buffer within timeout. The returned frame is only valid until the next
+// read.
+func (t *ModbusRTUTransport) readFrame(timeout time.Duration) ([]byte, error) {
+	deadline := time.Now().Add(timeout)
+
+	frame := t.rxBuffer[:]
+	if err := t.readFull(frame[:rtuHeaderSize], deadline); err != nil {
+		return nil, err
+	}
+
+	if length, known := rtuResponseLength(frame[1], frame[2]); known {
+		if length > rtuMaxFrameSize {
+			return nil, NewModbusError(ModbusErrorFrame, fmt.Sprintf("declared frame length %d exceeds maximum", length), "receive")
+		}
+		if err := t.readFull(frame[rtuHeaderSize:length], deadline); err != nil {
+			return nil, err
+		}
+		return frame[:length], nil
+	}
+
+	// Unknown layout: read until the trailing bytes form a valid CRC or the
+	// line is silent for the inter-frame gap
+	gap := t.InterFrameGap()
+	n := rtuHeaderSize
+	last := time.Now()
+	for {
+		if n >= rtuMinFrameSize && validRTUCRC(frame[:n]) {
+			return frame[:n], nil
+		}
+		if n == rtuMaxFrameSize {
+			return nil, NewModbusError(ModbusErrorFrame, "no valid frame within maximum frame size", "receive")
+		}
+
+		readDeadline := deadline
+		if gap > 0 && last.Add(gap).Before(deadline) {
+			readDeadline = last.Add(gap)
+		}
+		read, err := t.read(frame[n:], readDeadline)
+		if err != nil {
+			if isTimeoutError(err) && gap > 0 && time.Since(last) >= gap {
+				return nil, NewModbusError(ModbusErrorFrame,
+					fmt.Sprintf("frame ended after %d bytes without a valid CRC", n), "receive")
+			}
+			return nil, err
+		}
+		n += read
+		last = time.Now()
+	}
+}
+
+// readFull fills buf from the port unless deadline passes first
+func (t *ModbusRTUTransport) readFull(buf []byte, deadline time.Time) error {
+	for n := 0; n < len(buf); {
+		read, err := t.read(buf[n:], deadline)
+		if err != nil {
+			return err
+		}
+		n += read
+	}
+	return nil
+}
+
+// read reads at least one byte into buf unless deadline passes first.
+// Serial ports have no read deadlines: a read returns a timeout after the
+// port's poll interval without data, and is repeated until deadline, so
+// silences are measured to within the poll interval.
+func (t *ModbusRTUTransport) read(buf []byte, deadline time.Time) (int, error) {
+	deadliner, hasDeadline := t.port.(interface{ SetReadDeadline(time.Time) error })
+	for {
+		if hasDeadline {
+			deadliner.SetReadDeadline(deadline)
+		}
+		n, err := t.port.Read(buf)
+		if n > 0 {
+			return n, nil
+		}
+		if err != nil && !isTimeoutError(err) {
+			return 0, err
+		}
+		if !time.Now().Before(deadline) {
+			return 0, serial.ErrTimeout
+		}
+	}
+}
+
+// pollInterval is the read timeout the serial port is opened with, so
+// readFrame can check the response timeout and the inter-frame gap between
+// reads
+func (t *ModbusRTUTransport) pollInterval() time.Duration {
+	if gap := t.InterFrameGap(); gap > 0 && gap < rtuMaxPollInterval {
+		return gap
+	}
+	return rtuMaxPollInterval
+}
+
+func (t *ModbusRTUTransport) closePort() {
+	if t.port != nil {
+		t.port.Close()
+		t.port = nil
+	}
+}
+
+func (t *ModbusRTUTransport) openSerial() (io.ReadWriteCloser, error) {
+	return serial.Open(&serial.Config{
+		Address:  t.Address,
+		BaudRate: t.BaudRate,
+		DataBits: t.DataBits,
+		StopBits: t.StopBits,
+		Parity:   t.Parity,
+		Timeout:  t.pollInterval(),
+	})
+}
+
+// encodeRTUFrame builds an RTU frame carrying pdu
+func encodeRTUFrame(unitID byte, pdu *modbus.ProtocolDataUnit) ([]byte, error) {
+	return appendRTUFrame(nil, unitID, pdu)
+}
+
+// appendRTUFrame appends an RTU frame carrying pdu to dst. It only
+// allocates when dst lacks the capacity for the frame.
+func appendRTUFrame(dst []byte, unitID byte, pdu *modbus.ProtocolDataUnit) ([]byte, error) {
+	length := len(pdu.Data) + rtuMinFrameSize
+	if length > rtuMaxFrameSize {
+		return dst, NewModbusError(ModbusErrorFrame,
+			fmt.Sprintf("PDU length %d exceeds maximum", 1+len(pdu.Data)), "encode")
+	}
+
+	dst, frame := growFrame(dst, length)
+	frame[0] = unitID
+	frame[1] = pdu.FunctionCode
+	copy(frame[2:], pdu.Data)
+	binary.LittleEndian.PutUint16(frame[length-2:], crc16(frame[:length-2]))
+
+	return dst, nil
+}
+
+// decodeRTUFrame checks the CRC of frame and splits it into unit identifier
+// and PDU. The PDU data is not copied and aliases frame.
+func decodeRTUFrame(frame []byte) (byte, *modbus.ProtocolDataUnit, error) {
+	if len(frame) < rtuMinFrameSize {
+		return 0, nil, NewModbusError(ModbusErrorFrame, fmt.Sprintf("frame too short: %d bytes", len(frame)), "decode")
+	}
+	if len(frame) > rtuMaxFrameSize {
+		return 0, nil, NewModbusError(ModbusErrorFrame, fmt.Sprintf("frame too long: %d bytes", len(frame)), "decode")
+	}
+
+	if !validRTUCRC(frame) {
+		err := NewModbusError(ModbusErrorCRC, "CRC mismatch", "decode")
+		err.UnitID = frame[0]
+		return 0, nil, err
+	}
+
+	return frame[0], &modbus.ProtocolDataUnit{
+		FunctionCode: frame[1],
+		Data:         frame[2 : len(frame)-2],
+	}, nil
+}
+
+func validRTUCRC(frame []byte) bool {
+	n := len(frame)
+	return crc16(frame[:n-2]) == binary.LittleEndian.Uint16(frame[n-2:])
+}
+
+// crc16Table holds the CRC of every byte value for the reflected Modbus
+// polynomial 0xA001
+var crc16Table = func() [256]uint16 {
+	var table [256]uint16
+	for i := range table {
+		crc := uint16(i)
+		for bit := 0; bit < 8; bit++ {
+			if crc&0x0001 != 0 {
+				crc = (crc >> 1) ^ 0xA001
+			} else {
+				crc >>= 1
+			}
+		}
+		table[i] = crc
+	}
+	return table
+}()
+
+// crc16 computes the Modbus CRC-16 (polynomial 0xA001, initial value 0xFFFF)
+func crc16(data []byte) uint16 {
+	crc := uint16(0xFFFF)
+	for _, b := range data {
+		crc = (crc >> 8) ^ crc16Table[byte(crc)^b]
+	}
+	return crc
+}
+
+// rtuResponseLength returns the total length of a response frame from its
+// function code and third byte, if the function code has a known layout
+func rtuResponseLength(functionCode byte, thirdByte byte) (int, bool) {
+	if functionCode&exceptionFunctionFlag != 0 {
+		return 5, true
+	}
+
+	switch ModbusFunctionCode(functionCode) {
+	case ReadCoils, ReadDiscreteInputs, ReadHoldingRegisters, ReadInputRegisters,
+		GetCommEventLog, ReadFileRecord, WriteFileRecord, ReadWriteMultipleRegisters:
+		// unit + function code + byte count + data + CRC
+		return rtuHeaderSize + int(thirdByte) + 2, true
+	case ReadExceptionStatus:
+		return 5, true
+	case WriteSingleCoil, WriteSingleRegister, GetCommEventCounter, WriteMultipleCoils, WriteMultipleRegisters:
+		return 8, true
+	case MaskWriteRegister:
+		return 10, true
+	}
+
+	return 0, false
+}
+
+func isTimeoutError(err error) bool {
+	if errors.Is(err, serial.ErrTimeout) {
+		return true
+	}
+	var netErr net.Error
+	return errors.As(err, &netErr) && netErr.Timeout()
+}
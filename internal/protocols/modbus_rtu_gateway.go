@@ -0,0 +1,254 @@
+package protocols
+
+import (
+	"context"
+	"encoding/binary"
+	"fmt"
+	"io"
+	"net"
+	"sync"
+
+	"github.com/goburrow/modbus"
+	"go.uber.org/zap"
+)
+
+// Modbus TCP to RTU Gateway
+//
+// ModbusRTUGateway accepts Modbus TCP connections and forwards each request
+// to the serial line that serves its unit identifier. Failures on the serial
+// side are reported to the TCP client as gateway exceptions (0x0A when there
+// is no path to the unit, 0x0B when the unit does not answer). Requests for
+// unit 0 are broadcast on every serial line and never answered. Each serial
+// line is fronted by a ModbusUnitRouter, so units that stop answering are
+// taken offline and answered with 0x0B immediately. Handlers registered with
+// HandleFunction answer user-defined function codes locally.
+
+// ModbusRTUGateway routes Modbus TCP requests to RTU slaves by unit ID
+type ModbusRTUGateway struct {
+	logger *zap.Logger
+
+	mutex     sync.RWMutex
+	routes    map[byte]*ModbusUnitRouter
+	routers   map[*ModbusRTUTransport]*ModbusUnitRouter
+	functions map[byte]ModbusFunctionHandler
+}
+
+// NewModbusRTUGateway creates a gateway without routes
+func NewModbusRTUGateway(logger *zap.Logger) *ModbusRTUGateway {
+	return &ModbusRTUGateway{
+		logger:    logger,
+		routes:    make(map[byte]*ModbusUnitRouter),
+		routers:   make(map[*ModbusRTUTransport]*ModbusUnitRouter),
+		functions: make(map[byte]ModbusFunctionHandler),
+	}
+}
+
+// AddRoute forwards requests for unit IDs first through last (inclusive)
+// to transport. Later routes replace earlier ones for overlapping units.
+func (g *ModbusRTUGateway) AddRoute(first, last byte, transport *ModbusRTUTransport) error {
+	if first > last {
+		return fmt.Errorf("invalid unit range %d-%d", first, last)
+	}
+
+	g.mutex.Lock()
+	defer g.mutex.Unlock()
+
+	router, exists := g.routers[transport]
+	if !exists {
+		router = NewModbusUnitRouter(transport, g.logger)
+		g.routers[transport] = router
+	}
+
+	for unit := int(first); unit <= int(last); unit++ {
+		g.routes[byte(unit)] = router
+	}
+	return nil
+}
+
+// Route returns the transport serving unitID
+func (g *ModbusRTUGateway) Route(unitID byte) (*ModbusRTUTransport, bool) {
+	router, exists := g.UnitRouter(unitID)
+	if !exists {
+		return nil, false
+	}
+	return router.Transport(), true
+}
+
+// UnitRouter returns the router of the serial line serving unitID, which
+// tracks the unit's online state
+func (g *ModbusRTUGateway) UnitRouter(unitID byte) (*ModbusUnitRouter, bool) {
+	g.mutex.RLock()
+	defer g.mutex.RUnlock()
+
+	router, exists := g.routes[unitID]
+	return router, exists
+}
+
+// ListenAndServe listens on address and serves until ctx is cancelled
+func (g *ModbusRTUGateway) ListenAndServe(ctx context.Context, address string) error {
+	listener, err := net.Listen("tcp", address)
+	if err != nil {
+		return fmt.Errorf("failed to listen on %s: %w", address, err)
+	}
+	return g.Serve(ctx, listener)
+}
+
+// Serve accepts connections on listener until ctx is cancelled
+func (g *ModbusRTUGateway) Serve(ctx context.Context, listener net.Listener) error {
+	go func() {
+		<-ctx.Done()
+		listener.Close()
+	}()
+
+	g.logger.Info("Modbus RTU gateway started", zap.String("address", listener.Addr().String()))
+
+	var wg sync.WaitGroup
+	defer wg.Wait()
+
+	for {
+		conn, err := listener.Accept()
+		if err != nil {
+			if ctx.Err() != nil {
+				return nil
+			}
+			return fmt.Errorf("accept failed: %w", err)
+		}
+
+		wg.Add(1)
+		go func() {
+			defer wg.Done()
+			g.serveConn(ctx, conn)
+		}()
+	}
+}
+
+// serveConn reads requests from one TCP client. Requests are handled
+// concurrently so units on different serial lines do not wait on each other.
+func (g *ModbusRTUGateway) serveConn(ctx context.Context, conn net.Conn) {
+	defer conn.Close()
+
+	go func() {
+		<-ctx.Done()
+		conn.Close()
+	}()
+
+	var writeMu sync.Mutex
+	var wg sync.WaitGroup
+	defer wg.Wait()
+
+	header := make([]byte, modbusTCPHeaderSize)
+	for {
+		if _, err := io.ReadFull(conn, header); err != nil {
+			return
+		}
+
+		length := int(binary.BigEndian.Uint16(header[4:6]))
+		if length < modbusTCPMinLenField || length > modbusTCPMaxADUSize-6 ||
+			binary.BigEndian.Uint16(header[2:4]) != modbusTCPProtocolID {
+			g.logger.Warn("Closing connection after invalid MBAP header",
+				zap.String("remote", conn.RemoteAddr().String()),
+				zap.Int("length", length),
+			)
+			return
+		}
+
+		// The request is decoded in place from a pooled buffer, which is
+		// released once the response has been written
+		buffer := mbapBufferPool.Get().(*[]byte)
+		adu := (*buffer)[:6+length]
+		copy(adu, header)
+		if _, err := io.ReadFull(conn, adu[modbusTCPHeaderSize:]); err != nil {
+			mbapBufferPool.Put(buffer)
+			return
+		}
+
+		wg.Add(1)
+		go func() {
+			defer wg.Done()
+			defer mbapBufferPool.Put(buffer)
+
+			if adu[6] == BroadcastUnitID {
+				g.handleBroadcast(adu)
+				return
+			}
+
+			responseBuffer := mbapBufferPool.Get().(*[]byte)
+			defer mbapBufferPool.Put(responseBuffer)
+			response := g.handleRequest((*responseBuffer)[:0], adu)
+
+			writeMu.Lock()
+			defer writeMu.Unlock()
+			conn.Write(response)
+		}()
+	}
+}
+
+// handleBroadcast forwards a unit 0 request to every serial line. Broadcasts
+// are never answered, so failures are only logged.
+func (g *ModbusRTUGateway) handleBroadcast(adu []byte) {
+	request := &modbus.ProtocolDataUnit{FunctionCode: adu[7], Data: adu[8:]}
+
+	for _, transport := range g.transports() {
+		if err := transport.Broadcast(request); err != nil {
+			g.logger.Warn("RTU broadcast failed",
+				zap.String("port", transport.Address),
+				zap.Uint8("function_code", request.FunctionCode),
+				zap.Error(err),
+			)
+		}
+	}
+}
+
+// transports returns each routed transport once
+func (g *ModbusRTUGateway) transports() []*ModbusRTUTransport {
+	g.mutex.RLock()
+	defer g.mutex.RUnlock()
+
+	seen := make(map[*ModbusRTUTransport]bool)
+	var transports []*ModbusRTUTransport
+	for _, router := range g.routes {
+		if transport := router.Transport(); !seen[transport] {
+			seen[transport] = true
+			transports = append(transports, transport)
+		}
+	}
+	return transports
+}
+
+// handleRequest forwards one MBAP request frame and appends the MBAP
+// response frame to dst
+func (g *ModbusRTUGateway) handleRequest(dst []byte, adu []byte) []byte {
+	transactionID, unitID, request, err := decodeMBAPFrame(adu)
+	if err != nil {
+		response, _ := EncodeExceptionForRequest(adu, ExceptionServerDeviceFailure)
+		return response
+	}
+
+	if handler, exists := g.functionHandler(request.FunctionCode); exists {
+		response, err := serveFunction(handler, unitID, request)
+		if err != nil {
+			return appendModbusException(dst, transactionID, unitID, request.FunctionCode, ExceptionCodeForError(err))
+		}
+		return appendMBAPFrame(dst, transactionID, unitID, response)
+	}
+
+	router, exists := g.UnitRouter(unitID)
+	if !exists {
+		return appendModbusException(dst, transactionID, unitID, request.FunctionCode, ExceptionGatewayPathUnavailable)
+	}
+
+	response, err := router.Send(unitID, request)
+	if err != nil {
+		code := ExceptionCodeForError(err)
+		g.logger.Debug("RTU request failed",
+			zap.String("port", router.Transport().Address),
+			zap.Uint8("unit_id", unitID),
+			zap.Uint8("function_code", request.FunctionCode),
+			zap.Stringer("exception", code),
+			zap.Error(err),
+		)
+		return appendModbusException(dst, transactionID, unitID, request.FunctionCode, code)
+	}
+
+	return appendMBAPFrame(dst, transactionID, unitID, response)
+}
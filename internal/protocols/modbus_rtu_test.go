@@ -0,0 +1,414 @@
+package protocols
+
+import (
+	"context"
+	"io"
+	"net"
+	"testing"
+	"time"
+
+	"github.com/goburrow/modbus"
+	"github.com/goburrow/serial"
+	"github.com/stretchr/testify/assert"
+	"go.uber.org/zap"
+)
+
+func TestEncodeRTUFrame(t *testing.T) {
+	// Read one holding register at address 0 from unit 1
+	frame, err := encodeRTUFrame(1, &modbus.ProtocolDataUnit{FunctionCode: 0x03, Data: []byte{0x00, 0x00, 0x00, 0x01}})
+	assert.NoError(t, err)
+	assert.Equal(t, []byte{0x01, 0x03, 0x00, 0x00, 0x00, 0x01, 0x84, 0x0A}, frame)
+
+	unitID, pdu, err := decodeRTUFrame(frame)
+	assert.NoError(t, err)
+	assert.Equal(t, byte(1), unitID)
+	assert.Equal(t, byte(0x03), pdu.FunctionCode)
+	assert.Equal(t, []byte{0x00, 0x00, 0x00, 0x01}, pdu.Data)
+
+	frame[6] ^= 0xFF
+	_, _, err = decodeRTUFrame(frame)
+	assertValidationCategory(t, err, ModbusErrorCRC)
+}
+
+func TestAppendRTUFrame(t *testing.T) {
+	pdu := &modbus.ProtocolDataUnit{FunctionCode: 0x03, Data: []byte{0x00, 0x00, 0x00, 0x01}}
+	buffer := make([]byte, 0, rtuMaxFrameSize)
+
+	frame, err := appendRTUFrame(buffer, 1, pdu)
+	assert.NoError(t, err)
+	assert.Equal(t, []byte{0x01, 0x03, 0x00, 0x00, 0x00, 0x01, 0x84, 0x0A}, frame)
+	assert.Same(t, &buffer[:1][0], &frame[0])
+
+	// Frames are appended after existing content
+	frame, err = appendRTUFrame(frame, 1, pdu)
+	assert.NoError(t, err)
+	assert.Len(t, frame, 16)
+
+	allocs := testing.AllocsPerRun(100, func() {
+		appendRTUFrame(buffer[:0], 1, pdu)
+	})
+	assert.Equal(t, 0.0, allocs)
+
+	_, err = appendRTUFrame(buffer[:0], 1, &modbus.ProtocolDataUnit{FunctionCode: 0x10, Data: make([]byte, 253)})
+	assertValidationCategory(t, err, ModbusErrorFrame)
+}
+
+func TestModbusRTUTransport_ResponseOutlivesBuffer(t *testing.T) {
+	transport := newPipeRTUTransport(t, func(unitID byte, pdu *modbus.ProtocolDataUnit) *modbus.ProtocolDataUnit {
+		return &modbus.ProtocolDataUnit{FunctionCode: 0x03, Data: []byte{0x02, 0x00, unitID}}
+	})
+
+	read := &modbus.ProtocolDataUnit{FunctionCode: 0x03, Data: []byte{0x00, 0x00, 0x00, 0x01}}
+	first, err := transport.Send(1, read)
+	assert.NoError(t, err)
+	_, err = transport.Send(2, read)
+	assert.NoError(t, err)
+
+	assert.Equal(t, []byte{0x02, 0x00, 0x01}, first.Data)
+}
+
+func TestCRC16(t *testing.T) {
+	// Bitwise reference implementation
+	reference := func(data []byte) uint16 {
+		crc := uint16(0xFFFF)
+		for _, b := range data {
+			crc ^= uint16(b)
+			for i := 0; i < 8; i++ {
+				if crc&0x0001 != 0 {
+					crc = (crc >> 1) ^ 0xA001
+				} else {
+					crc >>= 1
+				}
+			}
+		}
+		return crc
+	}
+
+	data := make([]byte, rtuMaxFrameSize)
+	for i := range data {
+		data[i] = byte(i * 7)
+	}
+	for n := 0; n <= len(data); n++ {
+		assert.Equal(t, reference(data[:n]), crc16(data[:n]))
+	}
+
+	assert.Equal(t, uint16(0x0A84), crc16([]byte{0x01, 0x03, 0x00, 0x00, 0x00, 0x01}))
+}
+
+func TestRTUResponseLength(t *testing.T) {
+	length, known := rtuResponseLength(0x03, 4)
+	assert.True(t, known)
+	assert.Equal(t, 9, length)
+
+	length, known = rtuResponseLength(0x83, 2)
+	assert.True(t, known)
+	assert.Equal(t, 5, length)
+
+	length, known = rtuResponseLength(0x10, 0)
+	assert.True(t, known)
+	assert.Equal(t, 8, length)
+
+	_, known = rtuResponseLength(0x2B, 0x0E)
+	assert.False(t, known)
+}
+
+// newPipeRTUTransport returns a transport connected to a simulated slave
+// that answers each request frame with the PDU returned by respond. A nil
+// reply leaves the request unanswered.
+func newPipeRTUTransport(t *testing.T, respond func(unitID byte, pdu *modbus.ProtocolDataUnit) *modbus.ProtocolDataUnit) *ModbusRTUTransport {
+	master, slave := net.Pipe()
+	t.Cleanup(func() { slave.Close() })
+
+	go func() {
+		request := make([]byte, 8) // Fixed-size requests only
+		for {
+			if _, err := io.ReadFull(slave, request); err != nil {
+				return
+			}
+			unitID, pdu, err := decodeRTUFrame(request)
+			if err != nil {
+				continue
+			}
+			reply := respond(unitID, pdu)
+			if reply == nil {
+				continue
+			}
+			frame, _ := encodeRTUFrame(unitID, reply)
+			slave.Write(frame)
+		}
+	}()
+
+	transport := NewModbusRTUTransport("pipe", zap.NewNop())
+	transport.Timeout = 200 * time.Millisecond
+	transport.dial = func() (io.ReadWriteCloser, error) { return master, nil }
+	t.Cleanup(func() { transport.Close() })
+	return transport
+}
+
+func TestModbusRTUGateway_ForwardsByUnitID(t *testing.T) {
+	transport := newPipeRTUTransport(t, func(unitID byte, pdu *modbus.ProtocolDataUnit) *modbus.ProtocolDataUnit {
+		switch unitID {
+		case 5:
+			return &modbus.ProtocolDataUnit{FunctionCode: 0x03, Data: []byte{0x02, 0x12, 0x34}}
+		case 6:
+			return ExceptionResponsePDU(pdu.FunctionCode, ExceptionIllegalDataAddress)
+		}
+		return nil // Unit 7 never answers
+	})
+
+	gateway := NewModbusRTUGateway(zap.NewNop())
+	assert.NoError(t, gateway.AddRoute(5, 7, transport))
+	assert.Error(t, gateway.AddRoute(9, 8, transport))
+
+	listener, err := net.Listen("tcp", "127.0.0.1:0")
+	assert.NoError(t, err)
+
+	ctx, cancel := context.WithCancel(context.Background())
+	done := make(chan error, 1)
+	go func() { done <- gateway.Serve(ctx, listener) }()
+	defer func() {
+		cancel()
+		assert.NoError(t, <-done)
+	}()
+
+	client := NewModbusTCPTransport(listener.Addr().String(), zap.NewNop())
+	client.Timeout = 2 * time.Second
+	defer client.Close()
+
+	read := func(unitID byte) ([]byte, error) {
+		client.SlaveId = unitID
+		return modbus.NewClient(client).ReadHoldingRegisters(0x0010, 1)
+	}
+
+	value, err := read(5)
+	assert.NoError(t, err)
+	assert.Equal(t, []byte{0x12, 0x34}, value)
+
+	// Slave exceptions are passed through unchanged
+	_, err = read(6)
+	assert.Equal(t, ExceptionIllegalDataAddress, ExceptionCodeForError(err))
+
+	// A unit that does not answer maps to "target failed to respond"
+	_, err = read(7)
+	assert.Equal(t, ExceptionGatewayTargetFailedRespond, ExceptionCodeForError(err))
+
+	// A unit without a route maps to "path unavailable"
+	_, err = read(8)
+	assert.Equal(t, ExceptionGatewayPathUnavailable, ExceptionCodeForError(err))
+}
+
+// serialTestPort behaves like a serial port opened with a read timeout: it
+// has no read deadlines, and a read without data returns serial.ErrTimeout
+// after the timeout. Each request written is answered with the chunks
+// reply returns, each sent after its delay.
+type serialTestPort struct {
+	timeout time.Duration
+	reply   func(request []byte) []serialTestChunk
+	input   chan []byte
+	pending []byte
+}
+
+type serialTestChunk struct {
+	delay time.Duration
+	data  []byte
+}
+
+func newSerialTestPort(timeout time.Duration, reply func(request []byte) []serialTestChunk) *serialTestPort {
+	return &serialTestPort{timeout: timeout, reply: reply, input: make(chan []byte, 16)}
+}
+
+func (p *serialTestPort) Write(b []byte) (int, error) {
+	chunks := p.reply(b)
+	go func() {
+		for _, chunk := range chunks {
+			time.Sleep(chunk.delay)
+			p.input <- chunk.data
+		}
+	}()
+	return len(b), nil
+}
+
+func (p *serialTestPort) Read(b []byte) (int, error) {
+	if len(p.pending) == 0 {
+		select {
+		case p.pending = <-p.input:
+		case <-time.After(p.timeout):
+			return 0, serial.ErrTimeout
+		}
+	}
+	n := copy(b, p.pending)
+	p.pending = p.pending[n:]
+	return n, nil
+}
+
+func (p *serialTestPort) Close() error {
+	return nil
+}
+
+func TestModbusRTUTransport_TimeoutWithoutReadDeadline(t *testing.T) {
+	response, _ := encodeRTUFrame(1, &modbus.ProtocolDataUnit{FunctionCode: 0x03, Data: []byte{0x02, 0x12, 0x34}})
+	requests := 0
+	port := newSerialTestPort(5*time.Millisecond, func(request []byte) []serialTestChunk {
+		requests++
+		if requests > 1 {
+			return nil
+		}
+		return []serialTestChunk{{delay: 40 * time.Millisecond, data: response}}
+	})
+
+	transport := NewModbusRTUTransport("/dev/ttyTEST", zap.NewNop())
+	transport.Timeout = 2 * time.Second
+	transport.dial = func() (io.ReadWriteCloser, error) { return port, nil }
+	defer transport.Close()
+	request := &modbus.ProtocolDataUnit{FunctionCode: 0x03, Data: []byte{0x00, 0x10, 0x00, 0x01}}
+
+	// Responses slower than the port's read timeout are waited for
+	pdu, err := transport.Send(1, request)
+	assert.NoError(t, err)
+	assert.Equal(t, []byte{0x02, 0x12, 0x34}, pdu.Data)
+
+	// but not beyond the timeout of the request
+	start := time.Now()
+	_, err = transport.SendTimeout(1, request, 10*time.Millisecond)
+	assertValidationCategory(t, err, ModbusErrorTimeout)
+	assert.GreaterOrEqual(t, time.Since(start), 10*time.Millisecond)
+	assert.Less(t, time.Since(start), time.Second)
+
+	// or the timeout of the function
+	transport.RetryPolicy = &RetryPolicy{
+		MaxAttempts:      1,
+		FunctionTimeouts: map[ModbusFunctionCode]time.Duration{ReadHoldingRegisters: 10 * time.Millisecond},
+	}
+	start = time.Now()
+	_, err = transport.Send(1, request)
+	assertValidationCategory(t, err, ModbusErrorTimeout)
+	assert.GreaterOrEqual(t, time.Since(start), 10*time.Millisecond)
+	assert.Less(t, time.Since(start), time.Second)
+}
+
+func TestModbusRTUTransport_InterFrameGap(t *testing.T) {
+	transport := NewModbusRTUTransport("pipe", zap.NewNop())
+
+	transport.BaudRate = 9600
+	assert.Equal(t, 4010416*time.Nanosecond, transport.InterFrameGap())
+
+	transport.BaudRate = 115200
+	assert.Equal(t, 1750*time.Microsecond, transport.InterFrameGap())
+
+	transport.FrameGapMode = RTUFrameGapFixed
+	transport.FrameGap = 20 * time.Millisecond
+	assert.Equal(t, 20*time.Millisecond, transport.InterFrameGap())
+}
+
+func TestModbusRTUTransport_GapEndsUnknownFrame(t *testing.T) {
+	master, slave := net.Pipe()
+	defer slave.Close()
+
+	go func() {
+		request := make([]byte, 7)
+		if _, err := io.ReadFull(slave, request); err != nil {
+			return
+		}
+		// Truncated identification response, then silence
+		slave.Write([]byte{0x01, 0x2B, 0x0E, 0x01})
+	}()
+
+	transport := NewModbusRTUTransport("pipe", zap.NewNop())
+	transport.Timeout = 2 * time.Second
+	transport.FrameGapMode = RTUFrameGapFixed
+	transport.FrameGap = 20 * time.Millisecond
+	transport.dial = func() (io.ReadWriteCloser, error) { return master, nil }
+	defer transport.Close()
+
+	start := time.Now()
+	_, err := transport.Send(1, &modbus.ProtocolDataUnit{FunctionCode: 0x2B, Data: []byte{0x0E, 0x01, 0x00}})
+	assertValidationCategory(t, err, ModbusErrorFrame)
+	assert.Less(t, time.Since(start), time.Second)
+}
+
+func TestModbusRTUTransport_GapWithoutReadDeadline(t *testing.T) {
+	identification := []byte{0x0E, 0x01, 0x01, 0x00, 0x00, 0x01, 0x00, 0x02, 'P', 'X'}
+	response, _ := encodeRTUFrame(1, &modbus.ProtocolDataUnit{FunctionCode: 0x2B, Data: identification})
+	requests := 0
+	port := newSerialTestPort(5*time.Millisecond, func(request []byte) []serialTestChunk {
+		requests++
+		if requests == 1 {
+			// Truncated identification response, then silence
+			return []serialTestChunk{{data: response[:4]}}
+		}
+		// Pauses shorter than the gap do not end the frame
+		return []serialTestChunk{{data: response[:6]}, {delay: 10 * time.Millisecond, data: response[6:]}}
+	})
+
+	transport := NewModbusRTUTransport("/dev/ttyTEST", zap.NewNop())
+	transport.Timeout = 2 * time.Second
+	transport.FrameGapMode = RTUFrameGapFixed
+	transport.FrameGap = 30 * time.Millisecond
+	transport.dial = func() (io.ReadWriteCloser, error) { return port, nil }
+	defer transport.Close()
+	request := &modbus.ProtocolDataUnit{FunctionCode: 0x2B, Data: []byte{0x0E, 0x01, 0x00}}
+
+	start := time.Now()
+	_, err := transport.Send(1, request)
+	assertValidationCategory(t, err, ModbusErrorFrame)
+	assert.Less(t, time.Since(start), time.Second)
+
+	pdu, err := transport.Send(1, request)
+	assert.NoError(t, err)
+	assert.Equal(t, identification, pdu.Data)
+}
+
+func TestDecodeRTUFrame_Borrowed(t *testing.T) {
+	frame, err := encodeRTUFrame(1, &modbus.ProtocolDataUnit{FunctionCode: 0x03, Data: []byte{0x02, 0x12, 0x34}})
+	assert.NoError(t, err)
+
+	_, pdu, err := decodeRTUFrame(frame)
+	assert.NoError(t, err)
+	assert.Same(t, &frame[2], &pdu.Data[0])
+}
+
+func BenchmarkDecodeRTUFrame(b *testing.B) {
+	frame, _ := encodeRTUFrame(1, &modbus.ProtocolDataUnit{FunctionCode: 0x03, Data: make([]byte, 251)})
+
+	b.ReportAllocs()
+	b.ResetTimer()
+	for i := 0; i < b.N; i++ {
+		if _, _, err := decodeRTUFrame(frame); err != nil {
+			b.Fatal(err)
+		}
+	}
+}
+
+func BenchmarkEncodeRTUFrame(b *testing.B) {
+	pdu := &modbus.ProtocolDataUnit{FunctionCode: 0x10, Data: make([]byte, 251)}
+
+	b.ReportAllocs()
+	b.ResetTimer()
+	for i := 0; i < b.N; i++ {
+		if _, err := encodeRTUFrame(1, pdu); err != nil {
+			b.Fatal(err)
+		}
+	}
+}
+
+func BenchmarkAppendRTUFrame(b *testing.B) {
+	pdu := &modbus.ProtocolDataUnit{FunctionCode: 0x10, Data: make([]byte, 251)}
+	buffer := make([]byte, 0, rtuMaxFrameSize)
+
+	b.ReportAllocs()
+	b.ResetTimer()
+	for i := 0; i < b.N; i++ {
+		if _, err := appendRTUFrame(buffer[:0], 1, pdu); err != nil {
+			b.Fatal(err)
+		}
+	}
+}
+
+func BenchmarkCRC16(b *testing.B) {
+	data := make([]byte, rtuMaxFrameSize-2)
+
+	b.SetBytes(int64(len(data)))
+	for i := 0; i < b.N; i++ {
+		crc16(data)
+	}
+}
@@ -0,0 +1,191 @@
+package protocols
+
+import (
+	"errors"
+	"sync"
+	"time"
+
+	"github.com/goburrow/modbus"
+)
+
+// Modbus Communication Statistics
+//
+// Each transport counts the outcome of every request attempt per unit, so
+// failing wiring or a misbehaving slave shows up as rising CRC, timeout or
+// exception counts for that unit.
+
+// DefaultRoundTripBounds are the upper bounds of the round-trip histogram
+// buckets
+var DefaultRoundTripBounds = []time.Duration{
+	5 * time.Millisecond,
+	10 * time.Millisecond,
+	25 * time.Millisecond,
+	50 * time.Millisecond,
+	100 * time.Millisecond,
+	250 * time.Millisecond,
+	500 * time.Millisecond,
+	time.Second,
+}
+
+// ModbusUnitStats is a snapshot of the counters for one unit
+type ModbusUnitStats struct {
+	UnitID     byte                           `json:"unit_id"`
+	Requests   uint64                         `json:"requests"`  // Attempts, including retries
+	Responses  uint64                         `json:"responses"` // Including exception responses
+	Retries    uint64                         `json:"retries"`
+	Errors     map[ModbusErrorCategory]uint64 `json:"errors"`
+	Exceptions map[ModbusExceptionCode]uint64 `json:"exceptions"`
+	RoundTrip  RoundTripHistogram             `json:"round_trip"`
+}
+
+// CRCErrors returns the number of responses that failed the CRC check
+func (s *ModbusUnitStats) CRCErrors() uint64 {
+	return s.Errors[ModbusErrorCRC]
+}
+
+// Timeouts returns the number of requests that were not answered in time
+func (s *ModbusUnitStats) Timeouts() uint64 {
+	return s.Errors[ModbusErrorTimeout]
+}
+
+// RoundTripHistogram counts round-trip times of answered requests. Counts
+// has one bucket per bound plus a final overflow bucket.
+type RoundTripHistogram struct {
+	Bounds []time.Duration `json:"bounds"`
+	Counts []uint64        `json:"counts"`
+	Count  uint64          `json:"count"`
+	Sum    time.Duration   `json:"sum"`
+	Max    time.Duration   `json:"max"`
+}
+
+// Mean returns the average round-trip time
+func (h *RoundTripHistogram) Mean() time.Duration {
+	if h.Count == 0 {
+		return 0
+	}
+	return h.Sum / time.Duration(h.Count)
+}
+
+func (h *RoundTripHistogram) observe(rtt time.Duration) {
+	bucket := len(h.Bounds)
+	for i, bound := range h.Bounds {
+		if rtt <= bound {
+			bucket = i
+			break
+		}
+	}
+	h.Counts[bucket]++
+	h.Count++
+	h.Sum += rtt
+	if rtt > h.Max {
+		h.Max = rtt
+	}
+}
+
+// ModbusStats collects per-unit statistics for one transport. The zero
+// value is ready to use.
+type ModbusStats struct {
+	mutex sync.Mutex
+	units map[byte]*ModbusUnitStats
+}
+
+// Unit returns a snapshot of the statistics for unitID
+func (s *ModbusStats) Unit(unitID byte) ModbusUnitStats {
+	s.mutex.Lock()
+	defer s.mutex.Unlock()
+
+	return s.unit(unitID).clone()
+}
+
+// Units returns a snapshot of the statistics of every unit that has been
+// addressed
+func (s *ModbusStats) Units() map[byte]ModbusUnitStats {
+	s.mutex.Lock()
+	defer s.mutex.Unlock()
+
+	units := make(map[byte]ModbusUnitStats, len(s.units))
+	for unitID, stats := range s.units {
+		units[unitID] = stats.clone()
+	}
+	return units
+}
+
+// Reset clears all counters
+func (s *ModbusStats) Reset() {
+	s.mutex.Lock()
+	defer s.mutex.Unlock()
+
+	s.units = nil
+}
+
+// record counts one request attempt to unitID. attempt counts from 1;
+// response is nil if the attempt failed with err.
+func (s *ModbusStats) record(unitID byte, attempt int, rtt time.Duration, response *modbus.ProtocolDataUnit, err error) {
+	s.mutex.Lock()
+	defer s.mutex.Unlock()
+
+	stats := s.unit(unitID)
+	stats.Requests++
+	if attempt > 1 {
+		stats.Retries++
+	}
+
+	if response == nil {
+		var modbusErr *ModbusError
+		if errors.As(err, &modbusErr) {
+			stats.Errors[modbusErr.ErrorCategory]++
+		}
+		return
+	}
+
+	stats.Responses++
+	stats.RoundTrip.observe(rtt)
+	if IsExceptionResponse(response) && len(response.Data) > 0 {
+		stats.Exceptions[ModbusExceptionCode(response.Data[0])]++
+	}
+}
+
+// unit returns the live counters for unitID; s.mutex must be held
+func (s *ModbusStats) unit(unitID byte) *ModbusUnitStats {
+	if s.units == nil {
+		s.units = make(map[byte]*ModbusUnitStats)
+	}
+
+	stats, exists := s.units[unitID]
+	if !exists {
+		stats = &ModbusUnitStats{
+			UnitID:     unitID,
+			Errors:     make(map[ModbusErrorCategory]uint64),
+			Exceptions: make(map[ModbusExceptionCode]uint64),
+			RoundTrip: RoundTripHistogram{
+				Bounds: DefaultRoundTripBounds,
+				Counts: make([]uint64, len(DefaultRoundTripBounds)+1),
+			},
+		}
+		s.units[unitID] = stats
+	}
+	return stats
+}
+
+func (s *ModbusUnitStats) clone() ModbusUnitStats {
+	snapshot := *s
+	snapshot.Errors = make(map[ModbusErrorCategory]uint64, len(s.Errors))
+	for category, count := range s.Errors {
+		snapshot.Errors[category] = count
+	}
+	snapshot.Exceptions = make(map[ModbusExceptionCode]uint64, len(s.Exceptions))
+	for code, count := range s.Exceptions {
+		snapshot.Exceptions[code] = count
+	}
+	snapshot.RoundTrip.Counts = append([]uint64(nil), s.RoundTrip.Counts...)
+	return snapshot
+}
+
+// Stats returns the per-unit statistics of the device's connection
+func (m *ModbusHandler) Stats(device *Device) (map[byte]ModbusUnitStats, error) {
+	conn, err := m.getConnection(device)
+	if err != nil {
+		return nil, err
+	}
+	return conn.handler.Stats().Units(), nil
+}
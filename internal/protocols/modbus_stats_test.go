@@ -0,0 +1,87 @@
+package protocols
+
+import (
+	"testing"
+	"time"
+
+	"github.com/goburrow/modbus"
+	"github.com/stretchr/testify/assert"
+	"go.uber.org/zap"
+)
+
+func TestRoundTripHistogram_Observe(t *testing.T) {
+	histogram := RoundTripHistogram{
+		Bounds: []time.Duration{10 * time.Millisecond, 100 * time.Millisecond},
+		Counts: make([]uint64, 3),
+	}
+
+	histogram.observe(5 * time.Millisecond)
+	histogram.observe(10 * time.Millisecond)
+	histogram.observe(50 * time.Millisecond)
+	histogram.observe(time.Second)
+
+	assert.Equal(t, []uint64{2, 1, 1}, histogram.Counts)
+	assert.Equal(t, uint64(4), histogram.Count)
+	assert.Equal(t, time.Second, histogram.Max)
+	assert.Equal(t, 266*time.Millisecond+time.Millisecond/4, histogram.Mean())
+}
+
+func TestModbusRTUTransport_Stats(t *testing.T) {
+	transport := newPipeRTUTransport(t, func(unitID byte, pdu *modbus.ProtocolDataUnit) *modbus.ProtocolDataUnit {
+		switch unitID {
+		case 1:
+			return &modbus.ProtocolDataUnit{FunctionCode: 0x03, Data: []byte{0x02, 0x00, 0x2A}}
+		case 2:
+			return ExceptionResponsePDU(pdu.FunctionCode, ExceptionIllegalDataAddress)
+		}
+		return nil
+	})
+	transport.Timeout = 50 * time.Millisecond
+	transport.RetryPolicy = &RetryPolicy{MaxAttempts: 2, RetryOn: []ModbusErrorCategory{ModbusErrorTimeout}}
+
+	read := &modbus.ProtocolDataUnit{FunctionCode: 0x03, Data: []byte{0x00, 0x00, 0x00, 0x01}}
+	_, err := transport.Send(1, read)
+	assert.NoError(t, err)
+	response, err := transport.Send(2, read)
+	assert.NoError(t, err)
+	assert.True(t, IsExceptionResponse(response))
+	_, err = transport.Send(3, read)
+	assertValidationCategory(t, err, ModbusErrorTimeout)
+
+	units := transport.Stats().Units()
+	assert.Len(t, units, 3)
+
+	ok := units[1]
+	assert.Equal(t, uint64(1), ok.Requests)
+	assert.Equal(t, uint64(1), ok.Responses)
+	assert.Equal(t, uint64(1), ok.RoundTrip.Count)
+
+	failing := units[2]
+	assert.Equal(t, uint64(1), failing.Exceptions[ExceptionIllegalDataAddress])
+
+	silent := units[3]
+	assert.Equal(t, uint64(2), silent.Requests)
+	assert.Equal(t, uint64(1), silent.Retries)
+	assert.Equal(t, uint64(2), silent.Timeouts())
+	assert.Equal(t, uint64(0), silent.Responses)
+
+	transport.Stats().Reset()
+	assert.Len(t, transport.Stats().Units(), 0)
+}
+
+func TestModbusHandler_Stats(t *testing.T) {
+	device := startModbusTestServer(t, func(unitID byte, pdu []byte) []byte {
+		return []byte{0x03, 0x02, 0x00, 0x2A}
+	})
+
+	handler := NewModbusHandler(zap.NewNop()).(*ModbusHandler)
+	assert.NoError(t, handler.Connect(device))
+	defer handler.Disconnect(device)
+
+	_, err := handler.ReadTag(device, &Tag{ID: "t", Address: "40001", DataType: string(DataTypeUInt16)})
+	assert.NoError(t, err)
+
+	units, err := handler.Stats(device)
+	assert.NoError(t, err)
+	assert.Equal(t, uint64(1), units[handler.getUnitID(device)].Responses)
+}
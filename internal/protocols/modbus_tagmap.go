@@ -0,0 +1,337 @@
+package protocols
+
+import (
+	"encoding/binary"
+	"fmt"
+	"math"
+	"sort"
+	"sync"
+	"time"
+)
+
+// Modbus Tag Map
+//
+// A TagMap gives names to register locations so application code can read
+// "boiler.pressure" instead of holding register 40013 on unit 3.
+
+// RegisterTable identifies one of the four Modbus data tables
+type RegisterTable string
+
+const (
+	TableCoils            RegisterTable = "coils"
+	TableDiscreteInputs   RegisterTable = "discrete_inputs"
+	TableInputRegisters   RegisterTable = "input_registers"
+	TableHoldingRegisters RegisterTable = "holding_registers"
+)
+
+// TagDefinition describes where a named tag lives and how to interpret it.
+// Raw values are converted to engineering units as raw*Scale + Offset; a
+// zero Scale is treated as 1. A zero UnitID addresses the device's own unit.
+type TagDefinition struct {
+	Name      string        `json:"name" yaml:"name"`
+	UnitID    byte          `json:"unit_id" yaml:"unit_id"`
+	Table     RegisterTable `json:"table" yaml:"table"`
+	Address   uint16        `json:"address" yaml:"address"`
+	DataType  DataType      `json:"data_type" yaml:"data_type"`
+	WordOrder WordOrder     `json:"word_order,omitempty" yaml:"word_order,omitempty"`
+	Length    int           `json:"length,omitempty" yaml:"length,omitempty"` // String length in characters
+	Scale     float64       `json:"scale,omitempty" yaml:"scale,omitempty"`
+	Offset    float64       `json:"offset,omitempty" yaml:"offset,omitempty"`
+	Unit      string        `json:"unit,omitempty" yaml:"unit,omitempty"`
+}
+
+// TagMap is a concurrency-safe set of tag definitions keyed by name
+type TagMap struct {
+	mutex sync.RWMutex
+	tags  map[string]*TagDefinition
+}
+
+// NewTagMap creates an empty tag map
+func NewTagMap() *TagMap {
+	return &TagMap{
+		tags: make(map[string]*TagDefinition),
+	}
+}
+
+// Add validates def and adds it to the map, replacing any tag of the same name
+func (tm *TagMap) Add(def *TagDefinition) error {
+	if err := def.Validate(); err != nil {
+		return err
+	}
+
+	tm.mutex.Lock()
+	defer tm.mutex.Unlock()
+
+	tm.tags[def.Name] = def
+	return nil
+}
+
+// Get returns the definition of the named tag
+func (tm *TagMap) Get(name string) (*TagDefinition, bool) {
+	tm.mutex.RLock()
+	defer tm.mutex.RUnlock()
+
+	def, exists := tm.tags[name]
+	return def, exists
+}
+
+// Remove deletes the named tag
+func (tm *TagMap) Remove(name string) {
+	tm.mutex.Lock()
+	defer tm.mutex.Unlock()
+
+	delete(tm.tags, name)
+}
+
+// Names returns the names of all tags in sorted order
+func (tm *TagMap) Names() []string {
+	tm.mutex.RLock()
+	defer tm.mutex.RUnlock()
+
+	names := make([]string, 0, len(tm.tags))
+	for name := range tm.tags {
+		names = append(names, name)
+	}
+	sort.Strings(names)
+	return names
+}
+
+// Len returns the number of tags in the map
+func (tm *TagMap) Len() int {
+	tm.mutex.RLock()
+	defer tm.mutex.RUnlock()
+
+	return len(tm.tags)
+}
+
+// Decode converts the raw read response data for the named tag into its value
+func (tm *TagMap) Decode(name string, data []byte) (interface{}, error) {
+	def, exists := tm.Get(name)
+	if !exists {
+		return nil, fmt.Errorf("unknown tag: %s", name)
+	}
+	return def.Decode(data)
+}
+
+// Validate checks that the definition describes a readable location
+func (d *TagDefinition) Validate() error {
+	if d.Name == "" {
+		return fmt.Errorf("tag name is required")
+	}
+
+	switch d.Table {
+	case TableCoils, TableDiscreteInputs:
+		if d.DataType != DataTypeBool {
+			return fmt.Errorf("tag %s: %s only hold %s values", d.Name, d.Table, DataTypeBool)
+		}
+	case TableInputRegisters, TableHoldingRegisters:
+		if _, err := ParseWordOrder(string(d.WordOrder)); err != nil {
+			return fmt.Errorf("tag %s: %w", d.Name, err)
+		}
+	default:
+		return fmt.Errorf("tag %s: unknown register table %q", d.Name, d.Table)
+	}
+
+	if d.Scaled() && (d.DataType == DataTypeBool || d.DataType == DataTypeString) {
+		return fmt.Errorf("tag %s: %s values cannot be scaled", d.Name, d.DataType)
+	}
+
+	count, err := d.RegisterCount()
+	if err != nil {
+		return fmt.Errorf("tag %s: %w", d.Name, err)
+	}
+	if int(d.Address)+int(count) > maxModbusAddressSpace {
+		return fmt.Errorf("tag %s: address %d + %d exceeds the address space", d.Name, d.Address, count)
+	}
+
+	return nil
+}
+
+// RegisterCount returns the number of coils or registers the tag spans
+func (d *TagDefinition) RegisterCount() (uint16, error) {
+	if d.isBitTable() {
+		return 1, nil
+	}
+	return RegisterCount(d.DataType, d.Length)
+}
+
+// ReadFunctionCode returns the function code used to read the tag
+func (d *TagDefinition) ReadFunctionCode() ModbusFunctionCode {
+	switch d.Table {
+	case TableCoils:
+		return ReadCoils
+	case TableDiscreteInputs:
+		return ReadDiscreteInputs
+	case TableInputRegisters:
+		return ReadInputRegisters
+	default:
+		return ReadHoldingRegisters
+	}
+}
+
+// Writable reports whether the tag's table can be written
+func (d *TagDefinition) Writable() bool {
+	return d.Table == TableCoils || d.Table == TableHoldingRegisters
+}
+
+// Scaled reports whether the tag applies a scale or offset
+func (d *TagDefinition) Scaled() bool {
+	return (d.Scale != 0 && d.Scale != 1) || d.Offset != 0
+}
+
+// Decode converts coil status or register bytes, without the leading byte
+// count, into the tag's value. Scaled tags are returned as float64.
+func (d *TagDefinition) Decode(data []byte) (interface{}, error) {
+	if d.isBitTable() {
+		if len(data) < 1 {
+			return nil, fmt.Errorf("tag %s: empty response", d.Name)
+		}
+		return data[0]&0x01 != 0, nil
+	}
+
+	value, err := DecodeRegisterValue(data, d.DataType, d.WordOrder)
+	if err != nil {
+		return nil, fmt.Errorf("tag %s: %w", d.Name, err)
+	}
+
+	if !d.Scaled() {
+		return value, nil
+	}
+
+	raw, err := floatValue(value)
+	if err != nil {
+		return nil, fmt.Errorf("tag %s: cannot scale %s value", d.Name, d.DataType)
+	}
+	return raw*d.scale() + d.Offset, nil
+}
+
+// Encode converts an engineering value into register bytes, reversing the
+// tag's scaling. Integer types are rounded to the nearest raw value.
+func (d *TagDefinition) Encode(value interface{}) ([]byte, error) {
+	if d.Scaled() {
+		engineering, err := floatValue(value)
+		if err != nil {
+			return nil, fmt.Errorf("tag %s: %w", d.Name, err)
+		}
+
+		raw := (engineering - d.Offset) / d.scale()
+		if d.DataType != DataTypeFloat32 && d.DataType != DataTypeFloat64 {
+			raw = math.Round(raw)
+		}
+		value = raw
+	}
+
+	data, err := EncodeRegisterValue(value, d.DataType, d.WordOrder, d.Length)
+	if err != nil {
+		return nil, fmt.Errorf("tag %s: %w", d.Name, err)
+	}
+	return data, nil
+}
+
+func (d *TagDefinition) scale() float64 {
+	if d.Scale == 0 {
+		return 1
+	}
+	return d.Scale
+}
+
+func (d *TagDefinition) unitID(conn *ModbusConnection) byte {
+	if d.UnitID == 0 {
+		return conn.handler.SlaveId
+	}
+	return d.UnitID
+}
+
+func (d *TagDefinition) isBitTable() bool {
+	return d.Table == TableCoils || d.Table == TableDiscreteInputs
+}
+
+// ReadMappedTag reads the named tag from device and returns its typed,
+// scaled value
+func (m *ModbusHandler) ReadMappedTag(device *Device, tags *TagMap, name string) (interface{}, error) {
+	def, exists := tags.Get(name)
+	if !exists {
+		return nil, fmt.Errorf("unknown tag: %s", name)
+	}
+
+	count, err := def.RegisterCount()
+	if err != nil {
+		return nil, err
+	}
+
+	conn, err := m.getConnection(device)
+	if err != nil {
+		return nil, err
+	}
+
+	conn.mutex.Lock()
+	defer conn.mutex.Unlock()
+
+	conn.lastUsed = time.Now()
+
+	request := make([]byte, 4)
+	binary.BigEndian.PutUint16(request[0:2], def.Address)
+	binary.BigEndian.PutUint16(request[2:4], count)
+
+	// Byte count is checked against the quantity by response validation
+	response, err := m.sendUnitPDU(conn, def.unitID(conn), byte(def.ReadFunctionCode()), request)
+	if err != nil {
+		return nil, fmt.Errorf("tag %s: %w", name, err)
+	}
+
+	return def.Decode(response[1:])
+}
+
+// WriteMappedTag writes an engineering value to the named tag on device
+func (m *ModbusHandler) WriteMappedTag(device *Device, tags *TagMap, name string, value interface{}) error {
+	def, exists := tags.Get(name)
+	if !exists {
+		return fmt.Errorf("unknown tag: %s", name)
+	}
+	if !def.Writable() {
+		return fmt.Errorf("tag %s: %s are read-only", name, def.Table)
+	}
+
+	var functionCode ModbusFunctionCode
+	var request []byte
+
+	if def.Table == TableCoils {
+		on, ok := value.(bool)
+		if !ok {
+			return fmt.Errorf("tag %s: expected boolean value, got %T", name, value)
+		}
+		functionCode = WriteSingleCoil
+		request = make([]byte, 4)
+		binary.BigEndian.PutUint16(request[0:2], def.Address)
+		if on {
+			binary.BigEndian.PutUint16(request[2:4], coilOnValue)
+		}
+	} else {
+		data, err := def.Encode(value)
+		if err != nil {
+			return err
+		}
+		functionCode = WriteMultipleRegisters
+		request = make([]byte, multipleWriteHeaderDataLen+len(data))
+		binary.BigEndian.PutUint16(request[0:2], def.Address)
+		binary.BigEndian.PutUint16(request[2:4], uint16(len(data)/2))
+		request[4] = byte(len(data))
+		copy(request[multipleWriteHeaderDataLen:], data)
+	}
+
+	conn, err := m.getConnection(device)
+	if err != nil {
+		return err
+	}
+
+	conn.mutex.Lock()
+	defer conn.mutex.Unlock()
+
+	conn.lastUsed = time.Now()
+
+	// Response validation checks the echoed address and quantity
+	if _, err := m.sendUnitPDU(conn, def.unitID(conn), byte(functionCode), request); err != nil {
+		return fmt.Errorf("tag %s: %w", name, err)
+	}
+	return nil
+}
@@ -0,0 +1,126 @@
+package protocols
+
+import (
+	"encoding/binary"
+	"testing"
+
+	"github.com/stretchr/testify/assert"
+)
+
+func newBoilerTagMap(t *testing.T) *TagMap {
+	tags := NewTagMap()
+
+	definitions := []*TagDefinition{
+		{
+			Name:     "boiler.pressure",
+			UnitID:   3,
+			Table:    TableHoldingRegisters,
+			Address:  12,
+			DataType: DataTypeUInt16,
+			Scale:    0.1,
+			Unit:     "bar",
+		},
+		{
+			Name:      "boiler.flow",
+			UnitID:    3,
+			Table:     TableInputRegisters,
+			Address:   100,
+			DataType:  DataTypeFloat32,
+			WordOrder: WordOrderCDAB,
+			Unit:      "m3/h",
+		},
+		{
+			Name:     "boiler.running",
+			Table:    TableCoils,
+			Address:  5,
+			DataType: DataTypeBool,
+		},
+	}
+
+	for _, def := range definitions {
+		assert.NoError(t, tags.Add(def))
+	}
+	return tags
+}
+
+func TestTagMap_Validate(t *testing.T) {
+	tags := NewTagMap()
+
+	assert.Error(t, tags.Add(&TagDefinition{Table: TableHoldingRegisters, DataType: DataTypeUInt16}))
+	assert.Error(t, tags.Add(&TagDefinition{Name: "a", Table: "registers", DataType: DataTypeUInt16}))
+	assert.Error(t, tags.Add(&TagDefinition{Name: "a", Table: TableCoils, DataType: DataTypeInt32}))
+	assert.Error(t, tags.Add(&TagDefinition{Name: "a", Table: TableHoldingRegisters, DataType: DataTypeString}))
+	assert.Error(t, tags.Add(&TagDefinition{Name: "a", Table: TableHoldingRegisters, Address: 0xFFFF, DataType: DataTypeUInt32}))
+	assert.Error(t, tags.Add(&TagDefinition{Name: "a", Table: TableHoldingRegisters, DataType: DataTypeUInt16, WordOrder: "XYZW"}))
+	assert.Equal(t, 0, tags.Len())
+
+	tags = newBoilerTagMap(t)
+	assert.Equal(t, []string{"boiler.flow", "boiler.pressure", "boiler.running"}, tags.Names())
+}
+
+func TestTagDefinition_Scaling(t *testing.T) {
+	def := &TagDefinition{Name: "temp", Table: TableHoldingRegisters, DataType: DataTypeInt16, Scale: 0.5, Offset: -40}
+
+	value, err := def.Decode([]byte{0x00, 0xA0}) // 160 * 0.5 - 40
+	assert.NoError(t, err)
+	assert.Equal(t, 40.0, value)
+
+	data, err := def.Encode(21.5) // (21.5 + 40) / 0.5
+	assert.NoError(t, err)
+	assert.Equal(t, []byte{0x00, 0x7B}, data)
+}
+
+func TestModbusReadMappedTag(t *testing.T) {
+	device := startModbusTestServer(t, func(unitID byte, pdu []byte) []byte {
+		address := binary.BigEndian.Uint16(pdu[1:3])
+		quantity := binary.BigEndian.Uint16(pdu[3:5])
+
+		switch {
+		case pdu[0] == 0x03 && address == 12 && quantity == 1 && unitID == 3:
+			return []byte{0x03, 0x02, 0x00, 0x7B}
+		case pdu[0] == 0x04 && address == 100 && quantity == 2 && unitID == 3:
+			return []byte{0x04, 0x04, 0xE9, 0x79, 0x42, 0xF6}
+		case pdu[0] == 0x01 && address == 5 && unitID == 1:
+			return []byte{0x01, 0x01, 0x01}
+		}
+		return []byte{pdu[0] | 0x80, 0x02}
+	})
+	handler := connectTestDevice(t, device)
+	tags := newBoilerTagMap(t)
+
+	pressure, err := handler.ReadMappedTag(device, tags, "boiler.pressure")
+	assert.NoError(t, err)
+	assert.InDelta(t, 12.3, pressure, 1e-9)
+
+	flow, err := handler.ReadMappedTag(device, tags, "boiler.flow")
+	assert.NoError(t, err)
+	assert.Equal(t, float32(123.456), flow)
+
+	running, err := handler.ReadMappedTag(device, tags, "boiler.running")
+	assert.NoError(t, err)
+	assert.Equal(t, true, running)
+
+	_, err = handler.ReadMappedTag(device, tags, "boiler.missing")
+	assert.Error(t, err)
+}
+
+func TestModbusWriteMappedTag(t *testing.T) {
+	var written []byte
+	device := startModbusTestServer(t, func(unitID byte, pdu []byte) []byte {
+		written = append([]byte(nil), pdu...)
+		if pdu[0] == 0x10 {
+			return pdu[0:5]
+		}
+		return pdu
+	})
+	handler := connectTestDevice(t, device)
+	tags := newBoilerTagMap(t)
+
+	assert.NoError(t, handler.WriteMappedTag(device, tags, "boiler.pressure", 4.2))
+	assert.Equal(t, []byte{0x10, 0x00, 0x0C, 0x00, 0x01, 0x02, 0x00, 0x2A}, written)
+
+	assert.NoError(t, handler.WriteMappedTag(device, tags, "boiler.running", true))
+	assert.Equal(t, []byte{0x05, 0x00, 0x05, 0xFF, 0x00}, written)
+
+	assert.Error(t, handler.WriteMappedTag(device, tags, "boiler.flow", 1.0))
+}
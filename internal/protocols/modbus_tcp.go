@@ -0,0 +1,457 @@
+package protocols
+
+import (
+	"crypto/tls"
+	"encoding/binary"
+	"fmt"
+	"io"
+	"net"
+	"sync"
+	"sync/atomic"
+	"time"
+
+	"github.com/goburrow/modbus"
+	"go.uber.org/zap"
+)
+
+// Modbus TCP Transport with Transaction Matching
+
+const (
+	modbusTCPHeaderSize  = 7   // MBAP header including the unit identifier
+	modbusTCPMaxADUSize  = 260 // MBAP header + 253 byte PDU
+	modbusTCPProtocolID  = 0
+	defaultMaxInFlight   = 8
+	modbusTCPMinLenField = 2 // unit identifier + function code
+)
+
+// ModbusTCPTransport is a Modbus TCP client handler that allows several
+// requests to be outstanding on one connection. Each request is tagged with
+// its own MBAP transaction identifier and responses are matched back to the
+// waiting caller by that identifier, so responses may arrive in any order.
+//
+// It implements modbus.ClientHandler and can back a modbus.Client directly.
+type ModbusTCPTransport struct {
+	Address         string
+	Timeout         time.Duration
+	SlaveId         byte
+	MaxInFlight     int
+	TurnaroundDelay time.Duration // Wait after a broadcast
+	RetryPolicy     *RetryPolicy  // nil disables retries
+	TLSConfig       *tls.Config   // Modbus/TCP Security when set
+	Recorder        FrameRecorder // Receives sent and received frames when set
+
+	logger *zap.Logger
+
+	mutex   sync.Mutex // guards conn and pending
+	writeMu sync.Mutex // serializes frame writes
+	conn    net.Conn
+	pending map[uint16]chan modbusTCPResult
+
+	nextTransactionID  uint32
+	inFlight           chan struct{}
+	unmatchedResponses uint64
+	retries            uint64
+	stats              ModbusStats
+}
+
+type modbusTCPResult struct {
+	adu []byte
+	err error
+}
+
+// NewModbusTCPTransport creates a pipelining Modbus TCP transport
+func NewModbusTCPTransport(address string, logger *zap.Logger) *ModbusTCPTransport {
+	return &ModbusTCPTransport{
+		Address:         address,
+		Timeout:         5 * time.Second,
+		SlaveId:         1,
+		MaxInFlight:     defaultMaxInFlight,
+		TurnaroundDelay: defaultTurnaroundDelay,
+		logger:          logger,
+		pending:         make(map[uint16]chan modbusTCPResult),
+	}
+}
+
+// Connect establishes the TCP connection and starts the response reader
+func (t *ModbusTCPTransport) Connect() error {
+	t.mutex.Lock()
+	defer t.mutex.Unlock()
+
+	return t.connect()
+}
+
+func (t *ModbusTCPTransport) connect() error {
+	if t.conn != nil {
+		return nil
+	}
+
+	if t.inFlight == nil {
+		maxInFlight := t.MaxInFlight
+		if maxInFlight <= 0 {
+			maxInFlight = 1
+		}
+		t.inFlight = make(chan struct{}, maxInFlight)
+	}
+
+	var conn net.Conn
+	var err error
+	dialer := &net.Dialer{Timeout: t.Timeout}
+	if t.TLSConfig != nil {
+		conn, err = tls.DialWithDialer(dialer, "tcp", t.Address, t.TLSConfig)
+	} else {
+		conn, err = dialer.Dial("tcp", t.Address)
+	}
+	if err != nil {
+		return NewModbusError(ModbusErrorConnection, fmt.Sprintf("failed to connect to %s: %v", t.Address, err), "connect")
+	}
+
+	t.conn = conn
+	go t.readLoop(conn)
+
+	return nil
+}
+
+// Close closes the connection and fails all outstanding requests
+func (t *ModbusTCPTransport) Close() error {
+	t.mutex.Lock()
+	conn := t.conn
+	t.mutex.Unlock()
+
+	if conn == nil {
+		return nil
+	}
+
+	t.closeConnection(conn, NewModbusError(ModbusErrorConnection, "connection closed", "close"))
+	return nil
+}
+
+// Encode builds an MBAP frame for pdu with a freshly allocated transaction ID
+func (t *ModbusTCPTransport) Encode(pdu *modbus.ProtocolDataUnit) ([]byte, error) {
+	return t.encode(t.SlaveId, pdu)
+}
+
+func (t *ModbusTCPTransport) encode(unitID byte, pdu *modbus.ProtocolDataUnit) ([]byte, error) {
+	if err := ValidateRequestPDU(pdu); err != nil {
+		return nil, err
+	}
+
+	length := modbusTCPHeaderSize + 1 + len(pdu.Data)
+	if length > modbusTCPMaxADUSize {
+		return nil, NewModbusError(ModbusErrorFrame,
+			fmt.Sprintf("PDU length %d exceeds maximum", 1+len(pdu.Data)), "encode")
+	}
+
+	return encodeMBAPFrame(t.allocateTransactionID(), unitID, pdu), nil
+}
+
+// encodeMBAPFrame builds a Modbus TCP frame carrying pdu
+func encodeMBAPFrame(transactionID uint16, unitID byte, pdu *modbus.ProtocolDataUnit) []byte {
+	return appendMBAPFrame(nil, transactionID, unitID, pdu)
+}
+
+// appendMBAPFrame appends a Modbus TCP frame carrying pdu to dst. It only
+// allocates when dst lacks the capacity for the frame.
+func appendMBAPFrame(dst []byte, transactionID uint16, unitID byte, pdu *modbus.ProtocolDataUnit) []byte {
+	dst, adu := growFrame(dst, modbusTCPHeaderSize+1+len(pdu.Data))
+	binary.BigEndian.PutUint16(adu[0:2], transactionID)
+	binary.BigEndian.PutUint16(adu[2:4], modbusTCPProtocolID)
+	binary.BigEndian.PutUint16(adu[4:6], uint16(2+len(pdu.Data)))
+	adu[6] = unitID
+	adu[7] = pdu.FunctionCode
+	copy(adu[8:], pdu.Data)
+
+	return dst
+}
+
+// growFrame extends dst by n bytes and returns the extended slice together
+// with the n byte window to fill in
+func growFrame(dst []byte, n int) ([]byte, []byte) {
+	length := len(dst)
+	if cap(dst)-length < n {
+		grown := make([]byte, length, length+n)
+		copy(grown, dst)
+		dst = grown
+	}
+	dst = dst[:length+n]
+	return dst, dst[length:]
+}
+
+// Decode extracts the PDU from an MBAP frame
+func (t *ModbusTCPTransport) Decode(adu []byte) (*modbus.ProtocolDataUnit, error) {
+	_, _, pdu, err := decodeMBAPFrame(adu)
+	return pdu, err
+}
+
+// decodeMBAPFrame splits a Modbus TCP frame into its header fields and PDU.
+// The PDU data is not copied and aliases adu.
+func decodeMBAPFrame(adu []byte) (uint16, byte, *modbus.ProtocolDataUnit, error) {
+	if len(adu) < modbusTCPHeaderSize+1 {
+		return 0, 0, nil, NewModbusError(ModbusErrorFrame, fmt.Sprintf("frame too short: %d bytes", len(adu)), "decode")
+	}
+	if len(adu) > modbusTCPMaxADUSize {
+		return 0, 0, nil, NewModbusError(ModbusErrorFrame, fmt.Sprintf("frame too long: %d bytes", len(adu)), "decode")
+	}
+
+	length := int(binary.BigEndian.Uint16(adu[4:6]))
+	if length != len(adu)-6 {
+		return 0, 0, nil, NewModbusError(ModbusErrorFrame,
+			fmt.Sprintf("length field %d does not match frame size %d", length, len(adu)-6), "decode")
+	}
+
+	return binary.BigEndian.Uint16(adu[0:2]), adu[6], &modbus.ProtocolDataUnit{
+		FunctionCode: adu[7],
+		Data:         adu[8:],
+	}, nil
+}
+
+// mbapBufferPool holds maximum-size frame buffers for the server side, which
+// reads thousands of frames per second
+var mbapBufferPool = sync.Pool{
+	New: func() interface{} {
+		buffer := make([]byte, modbusTCPMaxADUSize)
+		return &buffer
+	},
+}
+
+// Verify checks that a response frame belongs to the request frame
+func (t *ModbusTCPTransport) Verify(aduRequest []byte, aduResponse []byte) error {
+	if len(aduResponse) < modbusTCPHeaderSize+1 {
+		return NewModbusError(ModbusErrorFrame, fmt.Sprintf("response too short: %d bytes", len(aduResponse)), "verify")
+	}
+
+	requestID := binary.BigEndian.Uint16(aduRequest[0:2])
+	responseID := binary.BigEndian.Uint16(aduResponse[0:2])
+	if requestID != responseID {
+		err := NewModbusError(ModbusErrorTransactionMismatch,
+			fmt.Sprintf("response transaction %d does not match request", responseID), "verify")
+		err.TransactionID = requestID
+		return err
+	}
+
+	if aduRequest[6] != aduResponse[6] {
+		err := NewModbusError(ModbusErrorTransactionMismatch,
+			fmt.Sprintf("response unit %d does not match request", aduResponse[6]), "verify")
+		err.TransactionID = requestID
+		err.UnitID = aduRequest[6]
+		return err
+	}
+
+	if binary.BigEndian.Uint16(aduResponse[2:4]) != modbusTCPProtocolID {
+		return NewModbusError(ModbusErrorFrame, "invalid protocol identifier", "verify")
+	}
+
+	request := &modbus.ProtocolDataUnit{FunctionCode: aduRequest[7], Data: aduRequest[8:]}
+	response := &modbus.ProtocolDataUnit{FunctionCode: aduResponse[7], Data: aduResponse[8:]}
+	return ValidateResponsePDU(request, response)
+}
+
+// Send writes aduRequest and waits for the response carrying the same
+// transaction ID. It is safe to call Send concurrently; up to MaxInFlight
+// requests are outstanding at any time.
+func (t *ModbusTCPTransport) Send(aduRequest []byte) ([]byte, error) {
+	return t.SendWithPolicy(aduRequest, t.RetryPolicy)
+}
+
+// SendWithPolicy is Send with an explicit retry policy. Retries reuse the
+// request's transaction ID. Exception responses that are not retried, or
+// that persist after the last attempt, are returned as normal responses.
+func (t *ModbusTCPTransport) SendWithPolicy(aduRequest []byte, policy *RetryPolicy) ([]byte, error) {
+	if len(aduRequest) < modbusTCPHeaderSize+1 {
+		return nil, NewModbusError(ModbusErrorFrame, "request frame too short", "send")
+	}
+
+	var aduResponse []byte
+	attempt := 0
+	err := policy.do(aduRequest[7], t.Timeout, &t.retries, func(timeout time.Duration) error {
+		attempt++
+		start := time.Now()
+
+		var err error
+		aduResponse, err = t.send(aduRequest, timeout)
+		if err != nil {
+			t.stats.record(aduRequest[6], attempt, 0, nil, err)
+			return err
+		}
+
+		response := &modbus.ProtocolDataUnit{FunctionCode: aduResponse[7], Data: aduResponse[8:]}
+		t.stats.record(aduRequest[6], attempt, time.Since(start), response, nil)
+		return exceptionError(response)
+	})
+	if aduResponse != nil {
+		return aduResponse, nil
+	}
+	return nil, err
+}
+
+func (t *ModbusTCPTransport) send(aduRequest []byte, timeout time.Duration) ([]byte, error) {
+	transactionID := binary.BigEndian.Uint16(aduRequest[0:2])
+	deadline := time.NewTimer(timeout)
+	defer deadline.Stop()
+
+	t.mutex.Lock()
+	if err := t.connect(); err != nil {
+		t.mutex.Unlock()
+		return nil, err
+	}
+	conn := t.conn
+	inFlight := t.inFlight
+	t.mutex.Unlock()
+
+	select {
+	case inFlight <- struct{}{}:
+		defer func() { <-inFlight }()
+	case <-deadline.C:
+		return nil, t.timeoutError(transactionID, aduRequest, "timed out waiting for a free transaction slot")
+	}
+
+	result := make(chan modbusTCPResult, 1)
+
+	t.mutex.Lock()
+	if _, busy := t.pending[transactionID]; busy {
+		t.mutex.Unlock()
+		err := NewModbusError(ModbusErrorTransactionMismatch, "transaction ID already in flight", "send")
+		err.TransactionID = transactionID
+		return nil, err
+	}
+	t.pending[transactionID] = result
+	t.mutex.Unlock()
+
+	defer func() {
+		t.mutex.Lock()
+		delete(t.pending, transactionID)
+		t.mutex.Unlock()
+	}()
+
+	t.writeMu.Lock()
+	conn.SetWriteDeadline(time.Now().Add(timeout))
+	_, err := conn.Write(aduRequest)
+	t.writeMu.Unlock()
+
+	if err != nil {
+		connErr := NewModbusError(ModbusErrorConnection, fmt.Sprintf("write failed: %v", err), "send")
+		t.closeConnection(conn, connErr)
+		return nil, connErr
+	}
+	recordFrame(t.Recorder, CaptureTransportModbusTCP, t.Address, CaptureSent, aduRequest)
+
+	select {
+	case res := <-result:
+		return res.adu, res.err
+	case <-deadline.C:
+		return nil, t.timeoutError(transactionID, aduRequest, "response timeout")
+	}
+}
+
+// IsConnected reports whether the transport currently holds an open
+// connection
+func (t *ModbusTCPTransport) IsConnected() bool {
+	t.mutex.Lock()
+	defer t.mutex.Unlock()
+
+	return t.conn != nil
+}
+
+// InFlight returns the number of requests currently awaiting a response
+func (t *ModbusTCPTransport) InFlight() int {
+	t.mutex.Lock()
+	defer t.mutex.Unlock()
+
+	return len(t.pending)
+}
+
+// Retries returns the number of retried requests
+func (t *ModbusTCPTransport) Retries() uint64 {
+	return atomic.LoadUint64(&t.retries)
+}
+
+// Stats returns the per-unit communication statistics
+func (t *ModbusTCPTransport) Stats() *ModbusStats {
+	return &t.stats
+}
+
+// UnmatchedResponses returns the number of responses that arrived for a
+// transaction nobody was waiting on, typically replies that came in after
+// the request had already timed out
+func (t *ModbusTCPTransport) UnmatchedResponses() uint64 {
+	return atomic.LoadUint64(&t.unmatchedResponses)
+}
+
+// readLoop dispatches incoming frames to the callers waiting on them
+func (t *ModbusTCPTransport) readLoop(conn net.Conn) {
+	header := make([]byte, modbusTCPHeaderSize)
+
+	for {
+		if _, err := io.ReadFull(conn, header); err != nil {
+			t.closeConnection(conn, NewModbusError(ModbusErrorConnection, fmt.Sprintf("read failed: %v", err), "receive"))
+			return
+		}
+
+		length := int(binary.BigEndian.Uint16(header[4:6]))
+		if length < modbusTCPMinLenField || length > modbusTCPMaxADUSize-6 {
+			// A TCP stream cannot be resynchronized after a bad length field
+			t.closeConnection(conn, NewModbusError(ModbusErrorFrame,
+				fmt.Sprintf("invalid MBAP length field: %d", length), "receive"))
+			return
+		}
+
+		adu := make([]byte, 6+length)
+		copy(adu, header)
+		if _, err := io.ReadFull(conn, adu[modbusTCPHeaderSize:]); err != nil {
+			t.closeConnection(conn, NewModbusError(ModbusErrorConnection, fmt.Sprintf("read failed: %v", err), "receive"))
+			return
+		}
+		recordFrame(t.Recorder, CaptureTransportModbusTCP, t.Address, CaptureReceived, adu)
+
+		transactionID := binary.BigEndian.Uint16(adu[0:2])
+
+		t.mutex.Lock()
+		result, exists := t.pending[transactionID]
+		if exists {
+			delete(t.pending, transactionID)
+		}
+		t.mutex.Unlock()
+
+		if !exists {
+			atomic.AddUint64(&t.unmatchedResponses, 1)
+			t.logger.Debug("Discarding unmatched Modbus response",
+				zap.String("address", t.Address),
+				zap.Uint16("transaction_id", transactionID),
+			)
+			continue
+		}
+
+		result <- modbusTCPResult{adu: adu}
+	}
+}
+
+// closeConnection closes conn (if it is still current) and fails every
+// pending request with err
+func (t *ModbusTCPTransport) closeConnection(conn net.Conn, err error) {
+	t.mutex.Lock()
+	if t.conn != conn {
+		t.mutex.Unlock()
+		return
+	}
+	t.conn = nil
+	pending := t.pending
+	t.pending = make(map[uint16]chan modbusTCPResult)
+	t.mutex.Unlock()
+
+	conn.Close()
+
+	for _, result := range pending {
+		result <- modbusTCPResult{err: err}
+	}
+}
+
+func (t *ModbusTCPTransport) allocateTransactionID() uint16 {
+	return uint16(atomic.AddUint32(&t.nextTransactionID, 1))
+}
+
+func (t *ModbusTCPTransport) timeoutError(transactionID uint16, aduRequest []byte, message string) *ModbusError {
+	err := NewModbusError(ModbusErrorTimeout, message, "send")
+	err.TransactionID = transactionID
+	err.UnitID = aduRequest[6]
+	err.FunctionCode = aduRequest[7]
+	return err
+}
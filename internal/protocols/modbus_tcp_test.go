@@ -0,0 +1,168 @@
+package protocols
+
+import (
+	"encoding/binary"
+	"io"
+	"net"
+	"sync"
+	"testing"
+	"time"
+
+	"github.com/goburrow/modbus"
+	"github.com/stretchr/testify/assert"
+	"go.uber.org/zap"
+)
+
+// startReorderingServer answers each pair of holding register requests in
+// reverse order, replying with the requested start address as the value
+func startReorderingServer(t *testing.T) string {
+	listener, err := net.Listen("tcp", "127.0.0.1:0")
+	if err != nil {
+		t.Fatalf("failed to listen: %v", err)
+	}
+	t.Cleanup(func() { listener.Close() })
+
+	go func() {
+		conn, err := listener.Accept()
+		if err != nil {
+			return
+		}
+		defer conn.Close()
+
+		for {
+			var requests [][]byte
+			for len(requests) < 2 {
+				frame := make([]byte, 12)
+				if _, err := io.ReadFull(conn, frame); err != nil {
+					return
+				}
+				requests = append(requests, frame)
+			}
+
+			for i := len(requests) - 1; i >= 0; i-- {
+				request := requests[i]
+				response := make([]byte, 11)
+				copy(response[0:4], request[0:4])
+				binary.BigEndian.PutUint16(response[4:6], 5)
+				response[6] = request[6]
+				response[7] = request[7]
+				response[8] = 2
+				copy(response[9:11], request[8:10])
+				conn.Write(response)
+			}
+		}
+	}()
+
+	return listener.Addr().String()
+}
+
+func TestModbusTCPTransport_MatchesOutOfOrderResponses(t *testing.T) {
+	address := startReorderingServer(t)
+
+	transport := NewModbusTCPTransport(address, zap.NewNop())
+	transport.Timeout = 2 * time.Second
+	assert.NoError(t, transport.Connect())
+	defer transport.Close()
+
+	client := modbus.NewClient(transport)
+
+	var wg sync.WaitGroup
+	results := make([][]byte, 2)
+	errs := make([]error, 2)
+	for i, start := range []uint16{0x0010, 0x0020} {
+		wg.Add(1)
+		go func(i int, start uint16) {
+			defer wg.Done()
+			results[i], errs[i] = client.ReadHoldingRegisters(start, 1)
+		}(i, start)
+	}
+	wg.Wait()
+
+	assert.NoError(t, errs[0])
+	assert.NoError(t, errs[1])
+	assert.Equal(t, []byte{0x00, 0x10}, results[0])
+	assert.Equal(t, []byte{0x00, 0x20}, results[1])
+	assert.Equal(t, 0, transport.InFlight())
+}
+
+func TestModbusTCPTransport_EncodeAllocatesTransactionIDs(t *testing.T) {
+	transport := NewModbusTCPTransport("127.0.0.1:502", zap.NewNop())
+
+	pdu := &modbus.ProtocolDataUnit{FunctionCode: 0x03, Data: []byte{0x00, 0x00, 0x00, 0x01}}
+	first, err := transport.Encode(pdu)
+	assert.NoError(t, err)
+	second, err := transport.Encode(pdu)
+	assert.NoError(t, err)
+
+	assert.NotEqual(t, binary.BigEndian.Uint16(first[0:2]), binary.BigEndian.Uint16(second[0:2]))
+	assert.Equal(t, uint16(6), binary.BigEndian.Uint16(first[4:6]))
+	assert.Equal(t, byte(1), first[6])
+}
+
+func TestModbusTCPTransport_VerifyMismatch(t *testing.T) {
+	transport := NewModbusTCPTransport("127.0.0.1:502", zap.NewNop())
+
+	request := []byte{0x00, 0x01, 0x00, 0x00, 0x00, 0x06, 0x01, 0x03, 0x00, 0x00, 0x00, 0x01}
+	response := []byte{0x00, 0x02, 0x00, 0x00, 0x00, 0x05, 0x01, 0x03, 0x02, 0x00, 0x01}
+
+	err := transport.Verify(request, response)
+	assert.Error(t, err)
+
+	modbusErr, ok := err.(*ModbusError)
+	assert.True(t, ok)
+	assert.Equal(t, ModbusErrorTransactionMismatch, modbusErr.ErrorCategory)
+	assert.Equal(t, uint16(1), modbusErr.TransactionID)
+}
+
+func TestModbusTCPTransport_Timeout(t *testing.T) {
+	listener, err := net.Listen("tcp", "127.0.0.1:0")
+	assert.NoError(t, err)
+	defer listener.Close()
+
+	go func() {
+		conn, err := listener.Accept()
+		if err == nil {
+			// Never answer
+			io.Copy(io.Discard, conn)
+		}
+	}()
+
+	transport := NewModbusTCPTransport(listener.Addr().String(), zap.NewNop())
+	transport.Timeout = 100 * time.Millisecond
+	defer transport.Close()
+
+	_, err = modbus.NewClient(transport).ReadHoldingRegisters(0, 1)
+	assert.Error(t, err)
+
+	modbusErr, ok := err.(*ModbusError)
+	assert.True(t, ok)
+	assert.Equal(t, ModbusErrorTimeout, modbusErr.ErrorCategory)
+	assert.True(t, modbusErr.IsRecoverable())
+}
+
+func TestDecodeMBAPFrame_Borrowed(t *testing.T) {
+	adu := []byte{0x00, 0x07, 0x00, 0x00, 0x00, 0x06, 0x11, 0x03, 0x00, 0x6B, 0x00, 0x03}
+
+	transactionID, unitID, pdu, err := decodeMBAPFrame(adu)
+	assert.NoError(t, err)
+	assert.Equal(t, uint16(7), transactionID)
+	assert.Equal(t, byte(0x11), unitID)
+	assert.Equal(t, byte(0x03), pdu.FunctionCode)
+	assert.Same(t, &adu[8], &pdu.Data[0])
+
+	_, _, _, err = decodeMBAPFrame(adu[:10])
+	assert.Error(t, err)
+}
+
+func BenchmarkDecodeMBAPFrame(b *testing.B) {
+	adu := encodeMBAPFrame(1, 1, &modbus.ProtocolDataUnit{FunctionCode: 0x03, Data: make([]byte, 251)})
+	adu[8] = 250
+
+	b.ReportAllocs()
+	b.ResetTimer()
+	for i := 0; i < b.N; i++ {
+		if _, _, _, err := decodeMBAPFrame(adu); err != nil {
+			b.Fatal(err)
+		}
+	}
+}
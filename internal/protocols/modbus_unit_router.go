@@ -0,0 +1,243 @@
+package protocols
+
+import (
+	"errors"
+	"fmt"
+	"sync"
+	"time"
+
+	"github.com/goburrow/modbus"
+	"go.uber.org/zap"
+)
+
+// Modbus Multi-Drop Unit Routing
+//
+// ModbusUnitRouter addresses several slaves on one serial line. The
+// transport already serializes requests on the port; the router adds
+// per-unit timeouts and takes units that stop answering offline, so a dead
+// slave fails fast instead of holding the bus for a full timeout on every
+// request. An offline unit is probed with one request every OfflineRetry.
+
+const (
+	defaultOfflineThreshold = 3
+	defaultOfflineRetry     = 30 * time.Second
+)
+
+// ModbusUnitStatus describes the state of one unit on the line
+type ModbusUnitStatus struct {
+	UnitID              byte          `json:"unit_id"`
+	Online              bool          `json:"online"`
+	Timeout             time.Duration `json:"timeout"`
+	ConsecutiveFailures int           `json:"consecutive_failures"`
+	LastError           string        `json:"last_error,omitempty"`
+	LastSuccess         time.Time     `json:"last_success"`
+	OfflineSince        time.Time     `json:"offline_since"`
+}
+
+// ModbusUnitRouter routes requests to the units behind one RTU transport
+type ModbusUnitRouter struct {
+	transport *ModbusRTUTransport
+	logger    *zap.Logger
+
+	// OfflineThreshold is the number of consecutive unanswered requests
+	// after which a unit is taken offline
+	OfflineThreshold int
+
+	// OfflineRetry is how long an offline unit is skipped before the next
+	// request is let through as a probe
+	OfflineRetry time.Duration
+
+	mutex sync.Mutex
+	units map[byte]*unitRoute
+}
+
+type unitRoute struct {
+	status    ModbusUnitStatus
+	nextProbe time.Time
+	probing   bool
+}
+
+// NewModbusUnitRouter creates a router for the units on transport
+func NewModbusUnitRouter(transport *ModbusRTUTransport, logger *zap.Logger) *ModbusUnitRouter {
+	return &ModbusUnitRouter{
+		transport:        transport,
+		logger:           logger,
+		OfflineThreshold: defaultOfflineThreshold,
+		OfflineRetry:     defaultOfflineRetry,
+		units:            make(map[byte]*unitRoute),
+	}
+}
+
+// Transport returns the transport the router sends on
+func (r *ModbusUnitRouter) Transport() *ModbusRTUTransport {
+	return r.transport
+}
+
+// AddUnit registers unitID with its own response timeout; zero uses the
+// transport timeout. Units are also registered implicitly on first use.
+func (r *ModbusUnitRouter) AddUnit(unitID byte, timeout time.Duration) error {
+	if unitID == BroadcastUnitID || unitID > 247 {
+		return fmt.Errorf("invalid unit ID %d", unitID)
+	}
+
+	r.mutex.Lock()
+	defer r.mutex.Unlock()
+
+	r.unit(unitID).status.Timeout = timeout
+	return nil
+}
+
+// Send forwards pdu to unitID. Requests to an offline unit fail with
+// ModbusErrorUnitOffline without touching the bus, except for one probe
+// every OfflineRetry.
+func (r *ModbusUnitRouter) Send(unitID byte, pdu *modbus.ProtocolDataUnit) (*modbus.ProtocolDataUnit, error) {
+	timeout, err := r.admit(unitID)
+	if err != nil {
+		return nil, err
+	}
+
+	response, err := r.transport.SendTimeout(unitID, pdu, timeout)
+	r.complete(unitID, err)
+	return response, err
+}
+
+// Status returns the state of unitID
+func (r *ModbusUnitRouter) Status(unitID byte) (ModbusUnitStatus, bool) {
+	r.mutex.Lock()
+	defer r.mutex.Unlock()
+
+	route, exists := r.units[unitID]
+	if !exists {
+		return ModbusUnitStatus{}, false
+	}
+	return route.status, true
+}
+
+// Units returns the state of every known unit
+func (r *ModbusUnitRouter) Units() []ModbusUnitStatus {
+	r.mutex.Lock()
+	defer r.mutex.Unlock()
+
+	units := make([]ModbusUnitStatus, 0, len(r.units))
+	for unitID := 0; unitID < 256; unitID++ {
+		if route, exists := r.units[byte(unitID)]; exists {
+			units = append(units, route.status)
+		}
+	}
+	return units
+}
+
+// SetOnline brings unitID back online immediately, e.g. after a repair
+func (r *ModbusUnitRouter) SetOnline(unitID byte) {
+	r.mutex.Lock()
+	defer r.mutex.Unlock()
+
+	route := r.unit(unitID)
+	route.status.Online = true
+	route.status.ConsecutiveFailures = 0
+	route.status.OfflineSince = time.Time{}
+	route.probing = false
+}
+
+// admit decides whether a request to unitID may use the bus and returns
+// its timeout
+func (r *ModbusUnitRouter) admit(unitID byte) (time.Duration, error) {
+	r.mutex.Lock()
+	defer r.mutex.Unlock()
+
+	route := r.unit(unitID)
+	timeout := route.status.Timeout
+	if timeout <= 0 {
+		timeout = r.transport.Timeout
+	}
+
+	if route.status.Online {
+		return timeout, nil
+	}
+	if !route.probing && !time.Now().Before(route.nextProbe) {
+		route.probing = true
+		return timeout, nil
+	}
+
+	err := NewModbusError(ModbusErrorUnitOffline,
+		fmt.Sprintf("unit offline since %s", route.status.OfflineSince.Format(time.RFC3339)), "send")
+	err.UnitID = unitID
+	return 0, err
+}
+
+// complete records the outcome of a request to unitID
+func (r *ModbusUnitRouter) complete(unitID byte, err error) {
+	r.mutex.Lock()
+	defer r.mutex.Unlock()
+
+	route := r.unit(unitID)
+	wasProbe := route.probing
+	route.probing = false
+
+	if err != nil && !unitFailure(err) {
+		// Rejected requests and port failures say nothing about the unit
+		return
+	}
+
+	if err == nil {
+		if !route.status.Online {
+			r.logger.Info("Modbus unit back online",
+				zap.String("port", r.transport.Address),
+				zap.Uint8("unit_id", unitID),
+			)
+		}
+		route.status.Online = true
+		route.status.ConsecutiveFailures = 0
+		route.status.OfflineSince = time.Time{}
+		route.status.LastSuccess = time.Now()
+		return
+	}
+
+	route.status.ConsecutiveFailures++
+	route.status.LastError = err.Error()
+
+	threshold := r.OfflineThreshold
+	if threshold <= 0 {
+		threshold = defaultOfflineThreshold
+	}
+
+	if wasProbe || (route.status.Online && route.status.ConsecutiveFailures >= threshold) {
+		if route.status.Online {
+			route.status.OfflineSince = time.Now()
+			r.logger.Warn("Modbus unit offline",
+				zap.String("port", r.transport.Address),
+				zap.Uint8("unit_id", unitID),
+				zap.Int("consecutive_failures", route.status.ConsecutiveFailures),
+				zap.Error(err),
+			)
+		}
+		route.status.Online = false
+		route.nextProbe = time.Now().Add(r.OfflineRetry)
+	}
+}
+
+// unit returns the route for unitID, creating it online; r.mutex must be
+// held
+func (r *ModbusUnitRouter) unit(unitID byte) *unitRoute {
+	route, exists := r.units[unitID]
+	if !exists {
+		route = &unitRoute{status: ModbusUnitStatus{UnitID: unitID, Online: true}}
+		r.units[unitID] = route
+	}
+	return route
+}
+
+// unitFailure reports whether err means the unit itself did not produce a
+// usable answer, as opposed to a rejected request or a port failure
+func unitFailure(err error) bool {
+	var modbusErr *ModbusError
+	if !errors.As(err, &modbusErr) {
+		return false
+	}
+
+	switch modbusErr.ErrorCategory {
+	case ModbusErrorTimeout, ModbusErrorCRC, ModbusErrorFrame:
+		return true
+	}
+	return false
+}
@@ -0,0 +1,93 @@
+package protocols
+
+import (
+	"sync/atomic"
+	"testing"
+	"time"
+
+	"github.com/goburrow/modbus"
+	"github.com/stretchr/testify/assert"
+	"go.uber.org/zap"
+)
+
+func TestModbusUnitRouter_TakesSilentUnitOffline(t *testing.T) {
+	var unit2Answers int32
+	transport := newPipeRTUTransport(t, func(unitID byte, pdu *modbus.ProtocolDataUnit) *modbus.ProtocolDataUnit {
+		if unitID == 2 && atomic.LoadInt32(&unit2Answers) == 0 {
+			return nil
+		}
+		return &modbus.ProtocolDataUnit{FunctionCode: 0x03, Data: []byte{0x02, 0x00, unitID}}
+	})
+
+	router := NewModbusUnitRouter(transport, zap.NewNop())
+	router.OfflineThreshold = 2
+	router.OfflineRetry = 100 * time.Millisecond
+	assert.NoError(t, router.AddUnit(2, 30*time.Millisecond))
+	assert.Error(t, router.AddUnit(BroadcastUnitID, 0))
+
+	read := &modbus.ProtocolDataUnit{FunctionCode: 0x03, Data: []byte{0x00, 0x00, 0x00, 0x01}}
+
+	for i := 0; i < 2; i++ {
+		_, err := router.Send(2, read)
+		assertValidationCategory(t, err, ModbusErrorTimeout)
+	}
+
+	status, exists := router.Status(2)
+	assert.True(t, exists)
+	assert.False(t, status.Online)
+	assert.Equal(t, 2, status.ConsecutiveFailures)
+
+	// The offline unit fails fast and does not hold up the rest of the bus
+	start := time.Now()
+	_, err := router.Send(2, read)
+	assertValidationCategory(t, err, ModbusErrorUnitOffline)
+	assert.Equal(t, ExceptionGatewayTargetFailedRespond, ExceptionCodeForError(err))
+	assert.Less(t, time.Since(start), 10*time.Millisecond)
+
+	response, err := router.Send(1, read)
+	assert.NoError(t, err)
+	assert.Equal(t, []byte{0x02, 0x00, 0x01}, response.Data)
+
+	// Once the retry interval has passed, a probe brings the unit back
+	atomic.StoreInt32(&unit2Answers, 1)
+	time.Sleep(router.OfflineRetry)
+
+	_, err = router.Send(2, read)
+	assert.NoError(t, err)
+
+	status, _ = router.Status(2)
+	assert.True(t, status.Online)
+	assert.Equal(t, 0, status.ConsecutiveFailures)
+	assert.Len(t, router.Units(), 2)
+}
+
+func TestModbusUnitRouter_FailedProbeKeepsUnitOffline(t *testing.T) {
+	transport := newPipeRTUTransport(t, func(unitID byte, pdu *modbus.ProtocolDataUnit) *modbus.ProtocolDataUnit {
+		return nil
+	})
+	transport.Timeout = 20 * time.Millisecond
+
+	router := NewModbusUnitRouter(transport, zap.NewNop())
+	router.OfflineThreshold = 1
+	router.OfflineRetry = 50 * time.Millisecond
+
+	read := &modbus.ProtocolDataUnit{FunctionCode: 0x03, Data: []byte{0x00, 0x00, 0x00, 0x01}}
+	_, err := router.Send(3, read)
+	assertValidationCategory(t, err, ModbusErrorTimeout)
+
+	time.Sleep(router.OfflineRetry)
+	_, err = router.Send(3, read)
+	assertValidationCategory(t, err, ModbusErrorTimeout)
+
+	_, err = router.Send(3, read)
+	assertValidationCategory(t, err, ModbusErrorUnitOffline)
+
+	// Invalid requests do not count against the unit
+	router.SetOnline(3)
+	_, err = router.Send(3, &modbus.ProtocolDataUnit{FunctionCode: 0x03, Data: []byte{0x00, 0x00, 0x00, 0x00}})
+	assertValidationCategory(t, err, ModbusErrorInvalidQuantity)
+
+	status, _ := router.Status(3)
+	assert.True(t, status.Online)
+	assert.Equal(t, 0, status.ConsecutiveFailures)
+}
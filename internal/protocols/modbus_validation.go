@@ -0,0 +1,211 @@
+package protocols
+
+import (
+	"encoding/binary"
+	"fmt"
+
+	"github.com/goburrow/modbus"
+)
+
+// Modbus Request and Response Validation
+//
+// Quantity and byte count limits follow the Modbus Application Protocol
+// Specification V1.1b3.
+
+const (
+	MaxReadCoilsQuantity       = 2000
+	MaxReadRegistersQuantity   = 125
+	MaxWriteCoilsQuantity      = 1968
+	MaxWriteRegistersQuantity  = 123
+	maxModbusAddressSpace      = 0x10000
+	coilOnValue                = 0xFF00
+	coilOffValue               = 0x0000
+	singleWriteRequestDataLen  = 4
+	multipleWriteHeaderDataLen = 5
+	readWriteHeaderDataLen     = 9
+	maskWriteRequestDataLen    = 6
+	maxPDUDataSize             = 252 // 253 byte PDU minus the function code
+)
+
+// ValidateRequestPDU checks a request PDU against the Modbus specification.
+// Function codes without fixed structure are accepted unchanged.
+func ValidateRequestPDU(pdu *modbus.ProtocolDataUnit) error {
+	data := pdu.Data
+	functionCode := ModbusFunctionCode(pdu.FunctionCode)
+
+	if len(data) > maxPDUDataSize {
+		return validationError(ModbusErrorFrame, pdu,
+			fmt.Sprintf("request data of %d bytes exceeds %d", len(data), maxPDUDataSize))
+	}
+
+	switch functionCode {
+	case ReadCoils, ReadDiscreteInputs:
+		if err := validateDataLength(pdu, singleWriteRequestDataLen); err != nil {
+			return err
+		}
+		return validateRange(pdu, binary.BigEndian.Uint16(data[0:2]), binary.BigEndian.Uint16(data[2:4]), MaxReadCoilsQuantity)
+
+	case ReadHoldingRegisters, ReadInputRegisters:
+		if err := validateDataLength(pdu, singleWriteRequestDataLen); err != nil {
+			return err
+		}
+		return validateRange(pdu, binary.BigEndian.Uint16(data[0:2]), binary.BigEndian.Uint16(data[2:4]), MaxReadRegistersQuantity)
+
+	case WriteSingleCoil:
+		if err := validateDataLength(pdu, singleWriteRequestDataLen); err != nil {
+			return err
+		}
+		if value := binary.BigEndian.Uint16(data[2:4]); value != coilOnValue && value != coilOffValue {
+			return validationError(ModbusErrorInvalidValue, pdu,
+				fmt.Sprintf("coil value must be 0x0000 or 0xFF00, got 0x%04X", value))
+		}
+		return nil
+
+	case WriteSingleRegister:
+		return validateDataLength(pdu, singleWriteRequestDataLen)
+
+	case WriteMultipleCoils:
+		if len(data) < multipleWriteHeaderDataLen {
+			return validationError(ModbusErrorFrame, pdu, fmt.Sprintf("request data too short: %d bytes", len(data)))
+		}
+		quantity := binary.BigEndian.Uint16(data[2:4])
+		if err := validateRange(pdu, binary.BigEndian.Uint16(data[0:2]), quantity, MaxWriteCoilsQuantity); err != nil {
+			return err
+		}
+		return validateByteCount(pdu, data[4], (int(quantity)+7)/8, data[multipleWriteHeaderDataLen:])
+
+	case WriteMultipleRegisters:
+		if len(data) < multipleWriteHeaderDataLen {
+			return validationError(ModbusErrorFrame, pdu, fmt.Sprintf("request data too short: %d bytes", len(data)))
+		}
+		quantity := binary.BigEndian.Uint16(data[2:4])
+		if err := validateRange(pdu, binary.BigEndian.Uint16(data[0:2]), quantity, MaxWriteRegistersQuantity); err != nil {
+			return err
+		}
+		return validateByteCount(pdu, data[4], int(quantity)*2, data[multipleWriteHeaderDataLen:])
+
+	case MaskWriteRegister:
+		return validateDataLength(pdu, maskWriteRequestDataLen)
+
+	case ReadWriteMultipleRegisters:
+		if len(data) < readWriteHeaderDataLen {
+			return validationError(ModbusErrorFrame, pdu, fmt.Sprintf("request data too short: %d bytes", len(data)))
+		}
+		if err := validateRange(pdu, binary.BigEndian.Uint16(data[0:2]), binary.BigEndian.Uint16(data[2:4]), MaxReadWriteReadQuantity); err != nil {
+			return err
+		}
+		writeQuantity := binary.BigEndian.Uint16(data[6:8])
+		if err := validateRange(pdu, binary.BigEndian.Uint16(data[4:6]), writeQuantity, MaxReadWriteWriteQuantity); err != nil {
+			return err
+		}
+		return validateByteCount(pdu, data[8], int(writeQuantity)*2, data[readWriteHeaderDataLen:])
+	}
+
+	return nil
+}
+
+// ValidateResponsePDU checks that a response PDU is well formed and
+// consistent with the request it answers. Declared byte counts must match
+// the payload exactly, so callers can index the data without further
+// bounds checks.
+func ValidateResponsePDU(request, response *modbus.ProtocolDataUnit) error {
+	if len(response.Data) > maxPDUDataSize {
+		return validationError(ModbusErrorFrame, response,
+			fmt.Sprintf("response data of %d bytes exceeds %d", len(response.Data), maxPDUDataSize))
+	}
+
+	if response.FunctionCode == request.FunctionCode|exceptionFunctionFlag {
+		return validateDataLength(response, 1)
+	}
+
+	if response.FunctionCode != request.FunctionCode {
+		return validationError(ModbusErrorFrame, response,
+			fmt.Sprintf("response function code does not match request 0x%02X", request.FunctionCode))
+	}
+
+	// Requests are validated before they are sent, so their fields can be
+	// read without further length checks
+	data := response.Data
+	switch ModbusFunctionCode(request.FunctionCode) {
+	case ReadCoils, ReadDiscreteInputs:
+		if len(data) < 1 {
+			return validationError(ModbusErrorFrame, response, "response is missing byte count")
+		}
+		quantity := binary.BigEndian.Uint16(request.Data[2:4])
+		return validateByteCount(response, data[0], (int(quantity)+7)/8, data[1:])
+
+	case ReadHoldingRegisters, ReadInputRegisters, ReadWriteMultipleRegisters:
+		if len(data) < 1 {
+			return validationError(ModbusErrorFrame, response, "response is missing byte count")
+		}
+		quantity := binary.BigEndian.Uint16(request.Data[2:4])
+		return validateByteCount(response, data[0], int(quantity)*2, data[1:])
+
+	case WriteSingleCoil, WriteSingleRegister, MaskWriteRegister:
+		if string(data) != string(request.Data) {
+			return validationError(ModbusErrorFrame, response, "write response does not echo the request")
+		}
+
+	case WriteMultipleCoils, WriteMultipleRegisters:
+		if len(data) != singleWriteRequestDataLen || string(data) != string(request.Data[:singleWriteRequestDataLen]) {
+			return validationError(ModbusErrorFrame, response, "write response does not echo address and quantity")
+		}
+
+	case ReadExceptionStatus:
+		return validateDataLength(response, 1)
+
+	case GetCommEventCounter:
+		return validateDataLength(response, 4)
+
+	case GetCommEventLog, ReadFileRecord:
+		if len(data) < 1 {
+			return validationError(ModbusErrorFrame, response, "response is missing byte count")
+		}
+		return validateByteCount(response, data[0], len(data)-1, data[1:])
+
+	case WriteFileRecord:
+		if string(data) != string(request.Data) {
+			return validationError(ModbusErrorFrame, response, "write response does not echo the request")
+		}
+	}
+
+	return nil
+}
+
+func validateDataLength(pdu *modbus.ProtocolDataUnit, expected int) error {
+	if len(pdu.Data) != expected {
+		return validationError(ModbusErrorFrame, pdu,
+			fmt.Sprintf("data length %d, expected %d", len(pdu.Data), expected))
+	}
+	return nil
+}
+
+func validateRange(pdu *modbus.ProtocolDataUnit, address, quantity uint16, maxQuantity int) error {
+	if quantity < 1 || int(quantity) > maxQuantity {
+		return validationError(ModbusErrorInvalidQuantity, pdu,
+			fmt.Sprintf("quantity %d out of range [1, %d]", quantity, maxQuantity))
+	}
+	if int(address)+int(quantity) > maxModbusAddressSpace {
+		return validationError(ModbusErrorInvalidAddress, pdu,
+			fmt.Sprintf("address %d + quantity %d exceeds the address space", address, quantity))
+	}
+	return nil
+}
+
+func validateByteCount(pdu *modbus.ProtocolDataUnit, byteCount byte, expected int, payload []byte) error {
+	if int(byteCount) != expected {
+		return validationError(ModbusErrorByteCountMismatch, pdu,
+			fmt.Sprintf("byte count %d, expected %d", byteCount, expected))
+	}
+	if len(payload) != expected {
+		return validationError(ModbusErrorByteCountMismatch, pdu,
+			fmt.Sprintf("payload has %d bytes, byte count declares %d", len(payload), expected))
+	}
+	return nil
+}
+
+func validationError(category ModbusErrorCategory, pdu *modbus.ProtocolDataUnit, message string) *ModbusError {
+	err := NewModbusError(category, message, "validate")
+	err.FunctionCode = pdu.FunctionCode
+	return err
+}
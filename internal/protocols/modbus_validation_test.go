@@ -0,0 +1,134 @@
+package protocols
+
+import (
+	"testing"
+
+	"github.com/goburrow/modbus"
+	"github.com/stretchr/testify/assert"
+	"go.uber.org/zap"
+)
+
+func assertValidationCategory(t *testing.T, err error, category ModbusErrorCategory) {
+	t.Helper()
+
+	modbusErr, ok := err.(*ModbusError)
+	if assert.True(t, ok, "expected *ModbusError, got %v", err) {
+		assert.Equal(t, category, modbusErr.ErrorCategory)
+	}
+}
+
+func TestValidateRequestPDU(t *testing.T) {
+	tests := []struct {
+		name     string
+		pdu      *modbus.ProtocolDataUnit
+		category ModbusErrorCategory
+	}{
+		{
+			name: "Valid holding register read",
+			pdu:  &modbus.ProtocolDataUnit{FunctionCode: 0x03, Data: []byte{0x00, 0x00, 0x00, 0x7D}},
+		},
+		{
+			name:     "Too many registers",
+			pdu:      &modbus.ProtocolDataUnit{FunctionCode: 0x03, Data: []byte{0x00, 0x00, 0x00, 0x7E}},
+			category: ModbusErrorInvalidQuantity,
+		},
+		{
+			name:     "Too many coils",
+			pdu:      &modbus.ProtocolDataUnit{FunctionCode: 0x01, Data: []byte{0x00, 0x00, 0x07, 0xD1}},
+			category: ModbusErrorInvalidQuantity,
+		},
+		{
+			name:     "Zero quantity",
+			pdu:      &modbus.ProtocolDataUnit{FunctionCode: 0x04, Data: []byte{0x00, 0x00, 0x00, 0x00}},
+			category: ModbusErrorInvalidQuantity,
+		},
+		{
+			name:     "Range past end of address space",
+			pdu:      &modbus.ProtocolDataUnit{FunctionCode: 0x03, Data: []byte{0xFF, 0xFF, 0x00, 0x02}},
+			category: ModbusErrorInvalidAddress,
+		},
+		{
+			name:     "Invalid coil value",
+			pdu:      &modbus.ProtocolDataUnit{FunctionCode: 0x05, Data: []byte{0x00, 0x01, 0x12, 0x34}},
+			category: ModbusErrorInvalidValue,
+		},
+		{
+			name: "Valid coil write",
+			pdu:  &modbus.ProtocolDataUnit{FunctionCode: 0x0F, Data: []byte{0x00, 0x13, 0x00, 0x0A, 0x02, 0xCD, 0x01}},
+		},
+		{
+			name:     "Coil byte count does not match quantity",
+			pdu:      &modbus.ProtocolDataUnit{FunctionCode: 0x0F, Data: []byte{0x00, 0x13, 0x00, 0x0A, 0x01, 0xCD}},
+			category: ModbusErrorByteCountMismatch,
+		},
+		{
+			name:     "Register payload shorter than byte count",
+			pdu:      &modbus.ProtocolDataUnit{FunctionCode: 0x10, Data: []byte{0x00, 0x01, 0x00, 0x02, 0x04, 0x00, 0x0A}},
+			category: ModbusErrorByteCountMismatch,
+		},
+		{
+			name:     "Truncated request",
+			pdu:      &modbus.ProtocolDataUnit{FunctionCode: 0x06, Data: []byte{0x00, 0x01}},
+			category: ModbusErrorFrame,
+		},
+		{
+			name: "Unknown function code is not checked",
+			pdu:  &modbus.ProtocolDataUnit{FunctionCode: 0x41, Data: []byte{0x01}},
+		},
+	}
+
+	for _, tt := range tests {
+		t.Run(tt.name, func(t *testing.T) {
+			err := ValidateRequestPDU(tt.pdu)
+			if tt.category == "" {
+				assert.NoError(t, err)
+			} else {
+				assertValidationCategory(t, err, tt.category)
+			}
+		})
+	}
+}
+
+func TestValidateResponsePDU(t *testing.T) {
+	request := &modbus.ProtocolDataUnit{FunctionCode: 0x03, Data: []byte{0x00, 0x00, 0x00, 0x02}}
+
+	assert.NoError(t, ValidateResponsePDU(request,
+		&modbus.ProtocolDataUnit{FunctionCode: 0x03, Data: []byte{0x04, 0x00, 0x01, 0x00, 0x02}}))
+
+	assert.NoError(t, ValidateResponsePDU(request,
+		&modbus.ProtocolDataUnit{FunctionCode: 0x83, Data: []byte{0x02}}))
+
+	assertValidationCategory(t, ValidateResponsePDU(request,
+		&modbus.ProtocolDataUnit{FunctionCode: 0x03, Data: []byte{0xFF, 0x00, 0x01}}), ModbusErrorByteCountMismatch)
+
+	assertValidationCategory(t, ValidateResponsePDU(request,
+		&modbus.ProtocolDataUnit{FunctionCode: 0x04, Data: []byte{0x00}}), ModbusErrorFrame)
+
+	write := &modbus.ProtocolDataUnit{FunctionCode: 0x10, Data: []byte{0x00, 0x01, 0x00, 0x01, 0x02, 0x00, 0x0A}}
+	assert.NoError(t, ValidateResponsePDU(write,
+		&modbus.ProtocolDataUnit{FunctionCode: 0x10, Data: []byte{0x00, 0x01, 0x00, 0x01}}))
+	assertValidationCategory(t, ValidateResponsePDU(write,
+		&modbus.ProtocolDataUnit{FunctionCode: 0x10, Data: []byte{0x00, 0x02, 0x00, 0x01}}), ModbusErrorFrame)
+
+	// Byte counts of variable-length responses must match the payload
+	eventLog := &modbus.ProtocolDataUnit{FunctionCode: 0x0C}
+	assert.NoError(t, ValidateResponsePDU(eventLog,
+		&modbus.ProtocolDataUnit{FunctionCode: 0x0C, Data: []byte{0x06, 0x00, 0x00, 0x01, 0x08, 0x01, 0x21}}))
+	assertValidationCategory(t, ValidateResponsePDU(eventLog,
+		&modbus.ProtocolDataUnit{FunctionCode: 0x0C, Data: []byte{0xF0, 0x00, 0x00, 0x01, 0x08, 0x01, 0x21}}), ModbusErrorByteCountMismatch)
+	assertValidationCategory(t, ValidateResponsePDU(&modbus.ProtocolDataUnit{FunctionCode: 0x0B},
+		&modbus.ProtocolDataUnit{FunctionCode: 0x0B, Data: []byte{0xFF}}), ModbusErrorFrame)
+
+	assertValidationCategory(t, ValidateResponsePDU(&modbus.ProtocolDataUnit{FunctionCode: 0x41},
+		&modbus.ProtocolDataUnit{FunctionCode: 0x41, Data: make([]byte, maxPDUDataSize+1)}), ModbusErrorFrame)
+	assertValidationCategory(t, ValidateRequestPDU(
+		&modbus.ProtocolDataUnit{FunctionCode: 0x41, Data: make([]byte, maxPDUDataSize+1)}), ModbusErrorFrame)
+}
+
+func TestModbusTCPTransport_EncodeRejectsIllegalRequest(t *testing.T) {
+	transport := NewModbusTCPTransport("127.0.0.1:502", zap.NewNop())
+
+	_, err := transport.Encode(&modbus.ProtocolDataUnit{FunctionCode: 0x03, Data: []byte{0x00, 0x00, 0x01, 0x00}})
+	assertValidationCategory(t, err, ModbusErrorInvalidQuantity)
+	assert.Equal(t, ExceptionIllegalDataValue, ExceptionCodeForError(err))
+}
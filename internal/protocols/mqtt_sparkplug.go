@@ -0,0 +1,483 @@
+package protocols
+
+import (
+	"context"
+	"errors"
+	"fmt"
+	"strconv"
+	"strings"
+	"sync"
+	"time"
+
+	mqtt "github.com/eclipse/paho.mqtt.golang"
+	"go.uber.org/zap"
+
+	"github.com/bifrost/go-gateway/internal/ratelimit"
+	"github.com/bifrost/go-gateway/internal/tlsconfig"
+)
+
+// SparkplugHandler bridges Sparkplug B edge nodes on an MQTT broker to the
+// gateway. A device is one edge node (or one device behind it), addressed
+// by the broker's host and port; the config keys "group_id",
+// "edge_node_id" and optionally "device_id" select it. Tag addresses are
+// metric names. Reads return the latest value the node published; writes
+// are sent as NCMD or DCMD commands.
+type SparkplugHandler struct {
+	logger      *zap.Logger
+	config      *SparkplugConfig
+	connections sync.Map // map[string]*SparkplugConnection
+}
+
+// SparkplugConnection is one MQTT session following one edge node
+type SparkplugConnection struct {
+	client     mqtt.Client
+	host       *SparkplugHost
+	groupID    string
+	edgeNodeID string
+	createdAt  time.Time
+	capture    *CaptureWriter // Received messages, when configured
+
+	mutex    sync.RWMutex
+	lastUsed time.Time
+	errors   uint64
+}
+
+// SparkplugConfig holds Sparkplug-specific configuration
+type SparkplugConfig struct {
+	ClientIDPrefix    string        `yaml:"client_id_prefix"`
+	ConnectionTimeout time.Duration `yaml:"connection_timeout"`
+	QoS               byte          `yaml:"qos"`
+	MaxAge            time.Duration `yaml:"max_age"` // Older cached values read as stale; 0 disables
+}
+
+// NewSparkplugHandler creates a new Sparkplug B protocol handler
+func NewSparkplugHandler(logger *zap.Logger) ProtocolHandler {
+	return &SparkplugHandler{
+		logger: logger,
+		config: &SparkplugConfig{
+			ClientIDPrefix:    "bifrost",
+			ConnectionTimeout: 10 * time.Second,
+			QoS:               0,
+		},
+	}
+}
+
+// Connect opens an MQTT session and subscribes to the edge node's topics
+func (s *SparkplugHandler) Connect(device *Device) error {
+	groupID, edgeNodeID, _, err := sparkplugDeviceIDs(device)
+	if err != nil {
+		return err
+	}
+
+	// TLS is configured by the "tls" entry of the device configuration
+	scheme, defaultPort := "tcp", 1883
+	var tlsConfig tlsconfig.Config
+	if values, ok := device.Config["tls"].(map[string]interface{}); ok {
+		if tlsConfig, err = tlsconfig.FromMap(values); err != nil {
+			return err
+		}
+		if tlsConfig.Enabled {
+			scheme, defaultPort = "ssl", 8883
+		}
+	}
+
+	port := device.Port
+	if port == 0 {
+		port = defaultPort
+	}
+	connectionKey := fmt.Sprintf("%s:%d/%s/%s", device.Address, port, groupID, edgeNodeID)
+
+	if connInterface, exists := s.connections.Load(connectionKey); exists {
+		if connInterface.(*SparkplugConnection).client.IsConnectionOpen() {
+			device.ConnectionID = connectionKey
+			return nil
+		}
+		s.connections.Delete(connectionKey)
+	}
+
+	conn := &SparkplugConnection{
+		host:       NewSparkplugHost(s.logger),
+		groupID:    groupID,
+		edgeNodeID: edgeNodeID,
+		createdAt:  time.Now(),
+		lastUsed:   time.Now(),
+	}
+
+	// The "rate_limit" entry limits what the edge node may publish
+	if values, ok := device.Config["rate_limit"].(map[string]interface{}); ok {
+		limit, err := ratelimit.LimitFromMap(values)
+		if err != nil {
+			return err
+		}
+		if conn.host.Limiter, err = ratelimit.NewLimiter(ratelimit.Config{Default: limit}); err != nil {
+			return err
+		}
+	}
+
+	subscriptions := map[string]byte{
+		fmt.Sprintf("%s/%s/+/%s", SparkplugNamespace, groupID, edgeNodeID):   s.config.QoS,
+		fmt.Sprintf("%s/%s/+/%s/+", SparkplugNamespace, groupID, edgeNodeID): s.config.QoS,
+	}
+
+	broker := fmt.Sprintf("%s:%d", device.Address, port)
+	options := mqtt.NewClientOptions().
+		AddBroker(scheme + "://" + broker).
+		SetClientID(fmt.Sprintf("%s-%s-%d", s.config.ClientIDPrefix, edgeNodeID, time.Now().UnixNano())).
+		SetConnectTimeout(s.config.ConnectionTimeout).
+		SetAutoReconnect(true).
+		SetOnConnectHandler(func(client mqtt.Client) {
+			// Subscribe on every (re)connect, then ask for a rebirth so the
+			// alias table is current
+			token := client.SubscribeMultiple(subscriptions, func(_ mqtt.Client, message mqtt.Message) {
+				if conn.capture != nil {
+					conn.capture.RecordFrame(CapturedFrame{
+						Time:      DefaultClock.Now(),
+						Transport: CaptureTransportMQTT,
+						Address:   broker,
+						Topic:     message.Topic(),
+						Direction: CaptureReceived,
+						Data:      message.Payload(),
+					})
+				}
+				err := conn.host.HandleMessage(message.Topic(), message.Payload())
+				switch {
+				case errors.Is(err, ratelimit.ErrThrottled):
+					s.logger.Debug("Throttling Sparkplug message", zap.String("topic", message.Topic()), zap.Error(err))
+				case err != nil:
+					s.logger.Debug("Ignoring Sparkplug message", zap.String("topic", message.Topic()), zap.Error(err))
+					conn.recordError()
+				}
+			})
+			if token.WaitTimeout(s.config.ConnectionTimeout) && token.Error() == nil {
+				s.requestRebirth(client, groupID, edgeNodeID)
+			}
+		})
+	if username, ok := device.Config["username"].(string); ok {
+		options.SetUsername(username)
+	}
+	if password, ok := device.Config["password"].(string); ok {
+		options.SetPassword(password)
+	}
+	if tlsConfig.Enabled {
+		loader, err := tlsconfig.NewLoader(tlsConfig, s.logger)
+		if err != nil {
+			return err
+		}
+		options.SetTLSConfig(loader.ClientConfig())
+	}
+
+	// Received messages are recorded to the file set by the "capture" entry
+	if conn.capture, err = openDeviceCapture(device); err != nil {
+		return err
+	}
+
+	conn.client = mqtt.NewClient(options)
+	conn.host.RequestRebirth = func(groupID, edgeNodeID string) {
+		s.requestRebirth(conn.client, groupID, edgeNodeID)
+	}
+
+	token := conn.client.Connect()
+	if !token.WaitTimeout(s.config.ConnectionTimeout) {
+		conn.closeCapture()
+		return fmt.Errorf("connecting to MQTT broker %s:%d: timed out", device.Address, port)
+	}
+	if err := token.Error(); err != nil {
+		conn.closeCapture()
+		return fmt.Errorf("connecting to MQTT broker %s:%d: %w", device.Address, port, err)
+	}
+
+	s.connections.Store(connectionKey, conn)
+	device.ConnectionID = connectionKey
+	device.Connected = true
+	device.LastSeen = time.Now()
+
+	s.logger.Info("Connected to Sparkplug edge node",
+		zap.String("device_id", device.ID),
+		zap.String("group_id", groupID),
+		zap.String("edge_node_id", edgeNodeID),
+	)
+
+	return nil
+}
+
+// Disconnect closes the MQTT session
+func (s *SparkplugHandler) Disconnect(device *Device) error {
+	if device.ConnectionID == "" {
+		return nil
+	}
+
+	connInterface, exists := s.connections.LoadAndDelete(device.ConnectionID)
+	if !exists {
+		return nil
+	}
+
+	conn := connInterface.(*SparkplugConnection)
+	conn.client.Disconnect(250)
+	conn.closeCapture()
+	device.Connected = false
+	device.ConnectionID = ""
+	return nil
+}
+
+// IsConnected checks if the MQTT session is open
+func (s *SparkplugHandler) IsConnected(device *Device) bool {
+	conn, err := s.getConnection(device)
+	return err == nil && conn.client.IsConnectionOpen()
+}
+
+// ReadTag returns the latest value of a metric. The tag's quality and
+// timestamp are updated from the metric; values of a dead node or device
+// are returned with stale quality.
+func (s *SparkplugHandler) ReadTag(device *Device, tag *Tag) (interface{}, error) {
+	conn, err := s.getConnection(device)
+	if err != nil {
+		return nil, err
+	}
+	_, _, deviceID, err := sparkplugDeviceIDs(device)
+	if err != nil {
+		return nil, err
+	}
+
+	metric, exists := conn.host.Tag(conn.groupID, conn.edgeNodeID, deviceID, tag.Address)
+	if !exists {
+		return nil, fmt.Errorf("metric %q has not been published by %s/%s", tag.Address, conn.groupID, conn.edgeNodeID)
+	}
+	if s.config.MaxAge > 0 && conn.host.now().Sub(metric.Timestamp) > s.config.MaxAge {
+		metric.Quality = QualityStale
+	}
+
+	conn.touch()
+	tag.Quality = metric.Quality
+	tag.Timestamp = metric.Timestamp
+	return metric.Value, nil
+}
+
+// WriteTag sends a command setting a metric
+func (s *SparkplugHandler) WriteTag(device *Device, tag *Tag, value interface{}) error {
+	conn, err := s.getConnection(device)
+	if err != nil {
+		return err
+	}
+	_, _, deviceID, err := sparkplugDeviceIDs(device)
+	if err != nil {
+		return err
+	}
+
+	dataType, exists := conn.host.MetricDataType(conn.groupID, conn.edgeNodeID, deviceID, tag.Address)
+	if !exists {
+		dataType = SparkplugDataTypeFor(tag.DataType)
+	}
+
+	payload := &SparkplugPayload{
+		Timestamp: sparkplugNow(),
+		Metrics:   []*SparkplugMetric{{Name: tag.Address, DataType: dataType, Value: value}},
+	}
+	data, err := payload.Marshal()
+	if err != nil {
+		return fmt.Errorf("encoding command for %q: %w", tag.Address, err)
+	}
+
+	topic := &SparkplugTopic{GroupID: conn.groupID, MessageType: SparkplugNCmd, EdgeNodeID: conn.edgeNodeID}
+	if deviceID != "" {
+		topic.MessageType, topic.DeviceID = SparkplugDCmd, deviceID
+	}
+
+	token := conn.client.Publish(topic.String(), s.config.QoS, false, data)
+	if !token.WaitTimeout(s.config.ConnectionTimeout) {
+		conn.recordError()
+		return fmt.Errorf("publishing %s: timed out", topic)
+	}
+	if err := token.Error(); err != nil {
+		conn.recordError()
+		return fmt.Errorf("publishing %s: %w", topic, err)
+	}
+
+	conn.touch()
+	return nil
+}
+
+// ReadMultipleTags returns the latest values of several metrics
+func (s *SparkplugHandler) ReadMultipleTags(device *Device, tags []*Tag) (map[string]interface{}, error) {
+	results := make(map[string]interface{})
+	for _, tag := range tags {
+		value, err := s.ReadTag(device, tag)
+		if err != nil {
+			s.logger.Debug("Sparkplug metric not available", zap.String("metric", tag.Address), zap.Error(err))
+			continue
+		}
+		results[tag.ID] = value
+	}
+	return results, nil
+}
+
+// DiscoverDevices lists the edge nodes and devices seen on the connected
+// brokers. Sparkplug nodes announce themselves, so there is no network
+// range to scan.
+func (s *SparkplugHandler) DiscoverDevices(ctx context.Context, networkRange string) ([]*Device, error) {
+	var devices []*Device
+	s.connections.Range(func(key, value interface{}) bool {
+		broker := strings.SplitN(key.(string), "/", 2)[0]
+		address, port := broker, 1883
+		if index := strings.LastIndex(broker, ":"); index >= 0 {
+			address = broker[:index]
+			if parsed, err := strconv.Atoi(broker[index+1:]); err == nil {
+				port = parsed
+			}
+		}
+
+		for _, node := range value.(*SparkplugConnection).host.Nodes() {
+			ids := append([]string{""}, node.Devices...)
+			for _, deviceID := range ids {
+				id := node.GroupID + "/" + node.EdgeNodeID
+				config := map[string]interface{}{"group_id": node.GroupID, "edge_node_id": node.EdgeNodeID}
+				if deviceID != "" {
+					id += "/" + deviceID
+					config["device_id"] = deviceID
+				}
+				devices = append(devices, &Device{
+					ID:       id,
+					Name:     id,
+					Protocol: "sparkplug-b",
+					Address:  address,
+					Port:     port,
+					Config:   config,
+					LastSeen: node.LastSeen,
+				})
+			}
+		}
+		return ctx.Err() == nil
+	})
+	return devices, ctx.Err()
+}
+
+// GetDeviceInfo returns information about a Sparkplug edge node
+func (s *SparkplugHandler) GetDeviceInfo(device *Device) (*DeviceInfo, error) {
+	info := &DeviceInfo{
+		Vendor:       "Unknown",
+		Model:        "Sparkplug B Edge Node",
+		Capabilities: []string{"sparkplug-b", "mqtt"},
+		CustomInfo:   make(map[string]string),
+	}
+
+	conn, err := s.getConnection(device)
+	if err != nil {
+		return info, nil
+	}
+	for _, node := range conn.host.Nodes() {
+		info.CustomInfo["online"] = fmt.Sprintf("%t", node.Online)
+		info.CustomInfo["bd_seq"] = fmt.Sprintf("%d", node.BdSeq)
+		info.CustomInfo["devices"] = strings.Join(node.Devices, ",")
+	}
+	return info, nil
+}
+
+// GetSupportedDataTypes returns the scalar Sparkplug datatypes
+func (s *SparkplugHandler) GetSupportedDataTypes() []string {
+	return []string{
+		string(DataTypeBool),
+		string(DataTypeInt16),
+		string(DataTypeUInt16),
+		string(DataTypeInt32),
+		string(DataTypeUInt32),
+		string(DataTypeInt64),
+		string(DataTypeUInt64),
+		string(DataTypeFloat32),
+		string(DataTypeFloat64),
+		string(DataTypeString),
+		string(DataTypeBytes),
+	}
+}
+
+// ValidateTagAddress validates a metric name
+func (s *SparkplugHandler) ValidateTagAddress(address string) error {
+	if strings.TrimSpace(address) == "" {
+		return fmt.Errorf("metric name is empty")
+	}
+	return nil
+}
+
+// Ping checks the MQTT session and that the edge node is online
+func (s *SparkplugHandler) Ping(device *Device) error {
+	conn, err := s.getConnection(device)
+	if err != nil {
+		return err
+	}
+	if !conn.client.IsConnectionOpen() {
+		return fmt.Errorf("MQTT connection is closed")
+	}
+	for _, node := range conn.host.Nodes() {
+		if node.Online {
+			return nil
+		}
+	}
+	return fmt.Errorf("edge node %s/%s is offline", conn.groupID, conn.edgeNodeID)
+}
+
+// GetDiagnostics returns diagnostic information for the session
+func (s *SparkplugHandler) GetDiagnostics(device *Device) (*Diagnostics, error) {
+	conn, err := s.getConnection(device)
+	if err != nil {
+		return nil, err
+	}
+
+	conn.mutex.RLock()
+	defer conn.mutex.RUnlock()
+
+	return &Diagnostics{
+		IsHealthy:           conn.client.IsConnectionOpen() && s.Ping(device) == nil,
+		LastCommunication:   conn.lastUsed,
+		ErrorCount:          conn.errors,
+		ConnectionUptime:    time.Since(conn.createdAt),
+		ProtocolDiagnostics: conn.host.Nodes(),
+	}, nil
+}
+
+func (s *SparkplugHandler) getConnection(device *Device) (*SparkplugConnection, error) {
+	if device.ConnectionID == "" {
+		return nil, fmt.Errorf("device not connected")
+	}
+
+	connInterface, exists := s.connections.Load(device.ConnectionID)
+	if !exists {
+		return nil, fmt.Errorf("connection not found")
+	}
+	return connInterface.(*SparkplugConnection), nil
+}
+
+func (s *SparkplugHandler) requestRebirth(client mqtt.Client, groupID, edgeNodeID string) {
+	topic, payload, err := RebirthCommand(groupID, edgeNodeID)
+	if err != nil {
+		return
+	}
+	s.logger.Info("Requesting Sparkplug rebirth", zap.String("group_id", groupID), zap.String("edge_node_id", edgeNodeID))
+	client.Publish(topic, s.config.QoS, false, payload)
+}
+
+func (c *SparkplugConnection) touch() {
+	c.mutex.Lock()
+	c.lastUsed = time.Now()
+	c.mutex.Unlock()
+}
+
+func (c *SparkplugConnection) closeCapture() {
+	if c.capture != nil {
+		c.capture.Close()
+	}
+}
+
+func (c *SparkplugConnection) recordError() {
+	c.mutex.Lock()
+	c.errors++
+	c.mutex.Unlock()
+}
+
+// sparkplugDeviceIDs returns the group, edge node and device IDs of a device
+func sparkplugDeviceIDs(device *Device) (string, string, string, error) {
+	groupID, _ := device.Config["group_id"].(string)
+	edgeNodeID, _ := device.Config["edge_node_id"].(string)
+	deviceID, _ := device.Config["device_id"].(string)
+	if groupID == "" || edgeNodeID == "" {
+		return "", "", "", fmt.Errorf("sparkplug devices require group_id and edge_node_id in their config")
+	}
+	return groupID, edgeNodeID, deviceID, nil
+}
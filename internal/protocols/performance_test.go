@@ -9,6 +9,7 @@ import (
 	"testing"
 	"time"
 
+	"github.com/goburrow/modbus"
 	"github.com/stretchr/testify/assert"
 	"github.com/stretchr/testify/require"
 	"go.uber.org/zap"
@@ -43,6 +44,23 @@ func BenchmarkModbusOperations(b *testing.B) {
 		}
 	})
 
+	b.Run("RTUFrameRoundTrip", func(b *testing.B) {
+		// Maximum-size read response: encode, then CRC check and decode
+		pdu := &modbus.ProtocolDataUnit{FunctionCode: 0x03, Data: make([]byte, 251)}
+		pdu.Data[0] = 250
+		b.ReportAllocs()
+		b.ResetTimer()
+		for i := 0; i < b.N; i++ {
+			frame, err := encodeRTUFrame(1, pdu)
+			if err != nil {
+				b.Fatal(err)
+			}
+			if _, _, err := decodeRTUFrame(frame); err != nil {
+				b.Fatal(err)
+			}
+		}
+	})
+
 	b.Run("ConcurrentReads", func(b *testing.B) {
 		// Simulate concurrent tag reads
 		tags := make([]*Tag, 100)
@@ -61,6 +61,10 @@ type Tag struct {
 	Unit        string      `json:"unit"`
 	Description string      `json:"description"`
 
+	// Quality details, see QualityStatus
+	Substatus     QualitySubstatus `json:"substatus,omitempty"`
+	QualitySource QualitySource    `json:"quality_source,omitempty"`
+
 	// Scaling and conversion
 	ScaleFactor float64 `json:"scale_factor,omitempty"`
 	Offset      float64 `json:"offset,omitempty"`
@@ -0,0 +1,117 @@
+package protocols
+
+import (
+	"fmt"
+)
+
+// Data Quality
+//
+// Every layer reports value quality with the same QualityStatus: the
+// Quality of the value, a substatus saying why it is not good and the
+// source that determined it. Drivers map protocol status flags onto it,
+// pollers add communication failures and aggregations combine statuses
+// with WorstQuality.
+
+// QualitySubstatus tells why a value is bad, uncertain or stale
+type QualitySubstatus string
+
+const (
+	QualitySubstatusNotConnected   QualitySubstatus = "not_connected"
+	QualitySubstatusCommFailure    QualitySubstatus = "comm_failure"
+	QualitySubstatusDeviceFailure  QualitySubstatus = "device_failure"
+	QualitySubstatusSensorFailure  QualitySubstatus = "sensor_failure"
+	QualitySubstatusOutOfService   QualitySubstatus = "out_of_service"
+	QualitySubstatusConfigError    QualitySubstatus = "config_error"
+	QualitySubstatusLastKnownValue QualitySubstatus = "last_known_value"
+	QualitySubstatusOverridden     QualitySubstatus = "overridden"
+	QualitySubstatusOutOfRange     QualitySubstatus = "out_of_range"
+	QualitySubstatusInaccurate     QualitySubstatus = "inaccurate"
+)
+
+// QualitySource is the layer that determined a quality
+type QualitySource string
+
+const (
+	// QualitySourceDevice is quality reported by the device, e.g. from
+	// status flags
+	QualitySourceDevice QualitySource = "device"
+	// QualitySourceDriver is quality determined by the protocol driver,
+	// e.g. after a failed read
+	QualitySourceDriver QualitySource = "driver"
+	// QualitySourceGateway is quality determined after acquisition, e.g.
+	// when a value went stale or was aggregated
+	QualitySourceGateway QualitySource = "gateway"
+	// QualitySourceManual is quality of a manually substituted value
+	QualitySourceManual QualitySource = "manual"
+)
+
+// QualityStatus is the quality of a value with its substatus and source
+type QualityStatus struct {
+	Quality   Quality          `json:"quality"`
+	Substatus QualitySubstatus `json:"substatus,omitempty"`
+	Source    QualitySource    `json:"source,omitempty"`
+}
+
+// GoodQuality is the status of a value read without problems
+var GoodQuality = QualityStatus{Quality: QualityGood}
+
+// NewQualityStatus creates a status
+func NewQualityStatus(quality Quality, substatus QualitySubstatus, source QualitySource) QualityStatus {
+	return QualityStatus{Quality: quality, Substatus: substatus, Source: source}
+}
+
+// IsGood reports whether the value can be used without restriction
+func (s QualityStatus) IsGood() bool {
+	return s.Quality == QualityGood
+}
+
+// String returns the quality followed by its substatus and source
+func (s QualityStatus) String() string {
+	switch {
+	case s.Substatus != "" && s.Source != "":
+		return fmt.Sprintf("%s (%s, %s)", s.Quality, s.Substatus, s.Source)
+	case s.Substatus != "":
+		return fmt.Sprintf("%s (%s)", s.Quality, s.Substatus)
+	case s.Source != "":
+		return fmt.Sprintf("%s (%s)", s.Quality, s.Source)
+	}
+	return string(s.Quality)
+}
+
+// qualitySeverity orders qualities from good to bad; unknown qualities
+// rank as uncertain
+func qualitySeverity(quality Quality) int {
+	switch quality {
+	case QualityGood:
+		return 0
+	case QualityStale:
+		return 2
+	case QualityBad:
+		return 3
+	}
+	return 1
+}
+
+// WorstQuality returns the worst of several statuses, the quality of a
+// value computed from them. It returns GoodQuality for no statuses.
+func WorstQuality(statuses ...QualityStatus) QualityStatus {
+	worst := GoodQuality
+	for _, status := range statuses {
+		if qualitySeverity(status.Quality) > qualitySeverity(worst.Quality) {
+			worst = status
+		}
+	}
+	return worst
+}
+
+// Status returns the quality status of the tag's value
+func (t *Tag) Status() QualityStatus {
+	return QualityStatus{Quality: t.Quality, Substatus: t.Substatus, Source: t.QualitySource}
+}
+
+// SetStatus sets the quality, substatus and quality source of the tag
+func (t *Tag) SetStatus(status QualityStatus) {
+	t.Quality = status.Quality
+	t.Substatus = status.Substatus
+	t.QualitySource = status.Source
+}
@@ -0,0 +1,56 @@
+package protocols
+
+import (
+	"math"
+	"testing"
+
+	"github.com/stretchr/testify/assert"
+)
+
+func TestQualityStatus(t *testing.T) {
+	commFailure := NewQualityStatus(QualityBad, QualitySubstatusCommFailure, QualitySourceDriver)
+	assert.Equal(t, "BAD (comm_failure, driver)", commFailure.String())
+	assert.Equal(t, "GOOD", GoodQuality.String())
+	assert.False(t, commFailure.IsGood())
+
+	tag := &Tag{}
+	tag.SetStatus(commFailure)
+	assert.Equal(t, QualityBad, tag.Quality)
+	assert.Equal(t, QualitySubstatusCommFailure, tag.Substatus)
+	assert.Equal(t, commFailure, tag.Status())
+}
+
+func TestWorstQuality(t *testing.T) {
+	stale := NewQualityStatus(QualityStale, QualitySubstatusLastKnownValue, QualitySourceGateway)
+	uncertain := NewQualityStatus(QualityUncertain, QualitySubstatusOutOfRange, QualitySourceDevice)
+	bad := NewQualityStatus(QualityBad, QualitySubstatusSensorFailure, QualitySourceDevice)
+
+	assert.Equal(t, GoodQuality, WorstQuality())
+	assert.Equal(t, uncertain, WorstQuality(GoodQuality, uncertain))
+	assert.Equal(t, stale, WorstQuality(uncertain, stale, GoodQuality))
+	assert.Equal(t, bad, WorstQuality(stale, bad, uncertain))
+}
+
+func TestProtocolQualityStatus(t *testing.T) {
+	tests := []struct {
+		name   string
+		status QualityStatus
+		want   QualityStatus
+	}{
+		{"dnp3 online", (&DNP3Point{Flags: DNP3FlagOnline}).QualityStatus(), GoodQuality},
+		{"dnp3 offline", (&DNP3Point{}).QualityStatus(), NewQualityStatus(QualityBad, QualitySubstatusOutOfService, QualitySourceDevice)},
+		{"dnp3 comm lost", (&DNP3Point{Flags: DNP3FlagOnline | DNP3FlagCommLost}).QualityStatus(), NewQualityStatus(QualityStale, QualitySubstatusCommFailure, QualitySourceDevice)},
+		{"dnp3 forced", (&DNP3Point{Flags: DNP3FlagOnline | DNP3FlagLocalForced}).QualityStatus(), NewQualityStatus(QualityGood, QualitySubstatusOverridden, QualitySourceDevice)},
+		{"hart good", (&HARTVariable{Status: 0xC0}).QualityStatus(), GoodQuality},
+		{"hart fixed", (&HARTVariable{Status: 0x80}).QualityStatus(), NewQualityStatus(QualityUncertain, QualitySubstatusOverridden, QualitySourceDevice)},
+		{"hart nan", (&HARTVariable{Value: math.NaN(), Status: 0xC0}).QualityStatus(), NewQualityStatus(QualityBad, QualitySubstatusSensorFailure, QualitySourceDevice)},
+		{"bacnet fault", bacnetQuality(BACnetBitString{false, true, false, false}), NewQualityStatus(QualityBad, QualitySubstatusDeviceFailure, QualitySourceDevice)},
+		{"bacnet out of service", bacnetQuality(BACnetBitString{false, false, false, true}), NewQualityStatus(QualityUncertain, QualitySubstatusOutOfService, QualitySourceDevice)},
+	}
+
+	for _, tt := range tests {
+		t.Run(tt.name, func(t *testing.T) {
+			assert.Equal(t, tt.want, tt.status)
+		})
+	}
+}
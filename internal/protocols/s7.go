@@ -0,0 +1,409 @@
+package protocols
+
+import (
+	"context"
+	"fmt"
+	"net"
+	"strconv"
+	"sync"
+	"time"
+
+	"go.uber.org/zap"
+)
+
+// S7Handler implements ProtocolHandler for Siemens S7-300/400/1200/1500
+// CPUs over S7comm. Tag addresses use Step 7 notation, e.g. "DB1.DBD4",
+// "M10.3" or "IW2"; the tag's data type selects how words and double
+// words are read (int16, uint16, int32, uint32 or float32). The config
+// keys "rack" and "slot" locate the CPU, or "remote_tsap" sets its TSAP
+// directly. S7-1200/1500 CPUs must allow PUT/GET access and data blocks
+// must not use optimized block access.
+type S7Handler struct {
+	logger      *zap.Logger
+	config      *S7Config
+	connections sync.Map // map[string]*S7Connection
+}
+
+// S7Connection is a client session with one CPU
+type S7Connection struct {
+	client    *S7Client
+	conn      net.Conn
+	rack      int
+	slot      int
+	createdAt time.Time
+
+	mutex    sync.RWMutex
+	lastUsed time.Time
+	requests uint64
+	errors   uint64
+}
+
+// S7Config holds S7-specific configuration
+type S7Config struct {
+	DefaultTimeout    time.Duration `yaml:"default_timeout"`
+	ConnectionTimeout time.Duration `yaml:"connection_timeout"`
+	DefaultRack       int           `yaml:"default_rack"`
+	DefaultSlot       int           `yaml:"default_slot"`
+	PDUSize           int           `yaml:"pdu_size"`
+	ProbeTimeout      time.Duration `yaml:"probe_timeout"`
+	MaxProbes         int           `yaml:"max_probes"`
+}
+
+// NewS7Handler creates a new S7 protocol handler
+func NewS7Handler(logger *zap.Logger) ProtocolHandler {
+	return &S7Handler{
+		logger: logger,
+		config: &S7Config{
+			DefaultTimeout:    5 * time.Second,
+			ConnectionTimeout: 10 * time.Second,
+			DefaultRack:       0,
+			DefaultSlot:       1,
+			PDUSize:           960,
+			ProbeTimeout:      500 * time.Millisecond,
+			MaxProbes:         32,
+		},
+	}
+}
+
+// Connect opens a session with a CPU
+func (s *S7Handler) Connect(device *Device) error {
+	port := device.Port
+	if port == 0 {
+		port = S7DefaultPort
+	}
+	rack := s.configInt(device, "rack", s.config.DefaultRack)
+	slot := s.configInt(device, "slot", s.config.DefaultSlot)
+	remoteTSAP := uint16(s.configInt(device, "remote_tsap", int(S7RemoteTSAP(rack, slot))))
+	localTSAP := uint16(s.configInt(device, "local_tsap", 0x0100))
+	connectionKey := fmt.Sprintf("%s:%d/%04X", device.Address, port, remoteTSAP)
+
+	if _, exists := s.connections.Load(connectionKey); exists {
+		device.ConnectionID = connectionKey
+		return nil
+	}
+
+	conn, err := net.DialTimeout("tcp", fmt.Sprintf("%s:%d", device.Address, port), s.config.ConnectionTimeout)
+	if err != nil {
+		return fmt.Errorf("failed to connect to S7 CPU: %w", err)
+	}
+
+	client, err := NewS7Client(conn, localTSAP, remoteTSAP, s.config.PDUSize, s.config.DefaultTimeout)
+	if err != nil {
+		conn.Close()
+		return err
+	}
+
+	connection := &S7Connection{
+		client:    client,
+		conn:      conn,
+		rack:      rack,
+		slot:      slot,
+		createdAt: time.Now(),
+		lastUsed:  time.Now(),
+	}
+	s.connections.Store(connectionKey, connection)
+	device.ConnectionID = connectionKey
+
+	s.logger.Info("S7 connection established",
+		zap.String("device_id", device.ID),
+		zap.String("address", device.Address),
+		zap.Int("port", port),
+		zap.Int("rack", rack),
+		zap.Int("slot", slot),
+		zap.Int("pdu_size", client.PDUSize()),
+	)
+
+	return nil
+}
+
+// Disconnect closes the session with a CPU
+func (s *S7Handler) Disconnect(device *Device) error {
+	if device.ConnectionID == "" {
+		return nil
+	}
+
+	connInterface, exists := s.connections.LoadAndDelete(device.ConnectionID)
+	if !exists {
+		return nil
+	}
+
+	device.ConnectionID = ""
+	return connInterface.(*S7Connection).conn.Close()
+}
+
+// IsConnected checks if the device has a session
+func (s *S7Handler) IsConnected(device *Device) bool {
+	_, err := s.getConnection(device)
+	return err == nil
+}
+
+// ReadTag reads one address
+func (s *S7Handler) ReadTag(device *Device, tag *Tag) (interface{}, error) {
+	conn, err := s.getConnection(device)
+	if err != nil {
+		return nil, err
+	}
+
+	address, err := ParseS7Address(tag.Address)
+	if err != nil {
+		return nil, err
+	}
+
+	results, err := conn.client.ReadItems([]*S7Address{address})
+	if err == nil && results[0].Err != nil {
+		err = fmt.Errorf("failed to read %s: %w", address, results[0].Err)
+	}
+	conn.record(err)
+	if err != nil {
+		return nil, err
+	}
+	return decodeS7Value(address, tag.DataType, results[0].Data)
+}
+
+// WriteTag writes one address
+func (s *S7Handler) WriteTag(device *Device, tag *Tag, value interface{}) error {
+	if !tag.Writable {
+		return fmt.Errorf("tag %s is not writable", tag.ID)
+	}
+
+	conn, err := s.getConnection(device)
+	if err != nil {
+		return err
+	}
+
+	address, err := ParseS7Address(tag.Address)
+	if err != nil {
+		return err
+	}
+	data, err := encodeS7Value(address, tag.DataType, value)
+	if err != nil {
+		return err
+	}
+
+	errs, err := conn.client.WriteItems([]*S7Address{address}, [][]byte{data})
+	if err == nil && errs[0] != nil {
+		err = fmt.Errorf("failed to write %s: %w", address, errs[0])
+	}
+	conn.record(err)
+	return err
+}
+
+// ReadMultipleTags reads all tags with optimized multi-item requests.
+// Tags the CPU could not read are left out of the results.
+func (s *S7Handler) ReadMultipleTags(device *Device, tags []*Tag) (map[string]interface{}, error) {
+	conn, err := s.getConnection(device)
+	if err != nil {
+		return nil, err
+	}
+
+	var readTags []*Tag
+	var addresses []*S7Address
+	for _, tag := range tags {
+		address, err := ParseS7Address(tag.Address)
+		if err != nil {
+			continue
+		}
+		readTags = append(readTags, tag)
+		addresses = append(addresses, address)
+	}
+
+	itemResults, err := conn.client.ReadItems(addresses)
+	conn.record(err)
+	if err != nil {
+		return nil, err
+	}
+
+	results := make(map[string]interface{})
+	for i, tag := range readTags {
+		if itemResults[i].Err != nil {
+			continue
+		}
+		if value, err := decodeS7Value(addresses[i], tag.DataType, itemResults[i].Data); err == nil {
+			results[tag.ID] = value
+		}
+	}
+	return results, nil
+}
+
+// DiscoverDevices probes each host of a network range for a CPU at the
+// default rack and slot
+func (s *S7Handler) DiscoverDevices(ctx context.Context, networkRange string) ([]*Device, error) {
+	_, network, err := net.ParseCIDR(networkRange)
+	if err != nil || network.IP.To4() == nil {
+		return nil, fmt.Errorf("invalid network range %q", networkRange)
+	}
+	if ones, bits := network.Mask.Size(); bits-ones > 16 {
+		return nil, fmt.Errorf("network range %s is too large to probe", networkRange)
+	}
+
+	var mutex sync.Mutex
+	var wg sync.WaitGroup
+	devices := make([]*Device, 0)
+	probes := make(chan struct{}, s.config.MaxProbes)
+
+	for ip := network.IP.Mask(network.Mask).To4(); network.Contains(ip) && ctx.Err() == nil; ip = s.nextIP(ip) {
+		probes <- struct{}{}
+		wg.Add(1)
+		go func(address string) {
+			defer wg.Done()
+			defer func() { <-probes }()
+
+			if device := s.probe(ctx, address); device != nil {
+				mutex.Lock()
+				devices = append(devices, device)
+				mutex.Unlock()
+			}
+		}(ip.String())
+	}
+	wg.Wait()
+
+	return devices, ctx.Err()
+}
+
+// GetDeviceInfo returns information about an S7 CPU
+func (s *S7Handler) GetDeviceInfo(device *Device) (*DeviceInfo, error) {
+	info := &DeviceInfo{
+		Vendor:         "Siemens",
+		Model:          "S7 CPU",
+		Capabilities:   []string{"s7comm", "multi-item-read", "multi-item-write"},
+		MaxConnections: 1,
+		CustomInfo:     make(map[string]string),
+	}
+
+	if conn, err := s.getConnection(device); err == nil {
+		info.CustomInfo["rack"] = strconv.Itoa(conn.rack)
+		info.CustomInfo["slot"] = strconv.Itoa(conn.slot)
+		info.CustomInfo["pdu_size"] = strconv.Itoa(conn.client.PDUSize())
+	}
+	return info, nil
+}
+
+// GetSupportedDataTypes returns the data types S7 values are read as
+func (s *S7Handler) GetSupportedDataTypes() []string {
+	return []string{
+		string(DataTypeBool),
+		string(DataTypeInt16),
+		string(DataTypeUInt16),
+		string(DataTypeInt32),
+		string(DataTypeUInt32),
+		string(DataTypeFloat32),
+		string(DataTypeBytes),
+	}
+}
+
+// ValidateTagAddress validates an S7 address
+func (s *S7Handler) ValidateTagAddress(address string) error {
+	_, err := ParseS7Address(address)
+	return err
+}
+
+// Ping checks that the CPU answers requests
+func (s *S7Handler) Ping(device *Device) error {
+	conn, err := s.getConnection(device)
+	if err != nil {
+		return err
+	}
+
+	err = conn.client.Ping()
+	conn.record(err)
+	return err
+}
+
+// GetDiagnostics returns diagnostic information for an S7 session
+func (s *S7Handler) GetDiagnostics(device *Device) (*Diagnostics, error) {
+	conn, err := s.getConnection(device)
+	if err != nil {
+		return nil, err
+	}
+
+	conn.mutex.RLock()
+	defer conn.mutex.RUnlock()
+
+	diagnostics := &Diagnostics{
+		IsHealthy:         true,
+		LastCommunication: conn.lastUsed,
+		ErrorCount:        conn.errors,
+		ConnectionUptime:  time.Since(conn.createdAt),
+		ProtocolDiagnostics: map[string]interface{}{
+			"rack":     conn.rack,
+			"slot":     conn.slot,
+			"pdu_size": conn.client.PDUSize(),
+		},
+	}
+	if conn.requests > 0 {
+		diagnostics.SuccessRate = float64(conn.requests-conn.errors) / float64(conn.requests)
+	}
+	return diagnostics, nil
+}
+
+func (s *S7Handler) getConnection(device *Device) (*S7Connection, error) {
+	if device.ConnectionID == "" {
+		return nil, fmt.Errorf("device not connected")
+	}
+
+	connInterface, exists := s.connections.Load(device.ConnectionID)
+	if !exists {
+		return nil, fmt.Errorf("connection not found")
+	}
+	return connInterface.(*S7Connection), nil
+}
+
+func (s *S7Handler) configInt(device *Device, key string, defaultValue int) int {
+	if value, ok := device.Config[key].(int); ok && value >= 0 {
+		return value
+	}
+	return defaultValue
+}
+
+// probe returns a device if a CPU accepts a session at address
+func (s *S7Handler) probe(ctx context.Context, address string) *Device {
+	dialer := net.Dialer{Timeout: s.config.ProbeTimeout}
+	conn, err := dialer.DialContext(ctx, "tcp", net.JoinHostPort(address, strconv.Itoa(S7DefaultPort)))
+	if err != nil {
+		return nil
+	}
+	defer conn.Close()
+
+	remoteTSAP := S7RemoteTSAP(s.config.DefaultRack, s.config.DefaultSlot)
+	client, err := NewS7Client(conn, 0x0100, remoteTSAP, s.config.PDUSize, s.config.ProbeTimeout)
+	if err != nil {
+		return nil
+	}
+
+	return &Device{
+		ID:       fmt.Sprintf("s7-%s", address),
+		Name:     fmt.Sprintf("S7 CPU %s", address),
+		Protocol: "s7",
+		Address:  address,
+		Port:     S7DefaultPort,
+		Config: map[string]interface{}{
+			"rack":     s.config.DefaultRack,
+			"slot":     s.config.DefaultSlot,
+			"pdu_size": client.PDUSize(),
+		},
+		LastSeen: time.Now(),
+	}
+}
+
+// nextIP returns the address following ip
+func (s *S7Handler) nextIP(ip net.IP) net.IP {
+	next := append(net.IP(nil), ip...)
+	for i := len(next) - 1; i >= 0; i-- {
+		next[i]++
+		if next[i] != 0 {
+			break
+		}
+	}
+	return next
+}
+
+func (c *S7Connection) record(err error) {
+	c.mutex.Lock()
+	defer c.mutex.Unlock()
+
+	c.requests++
+	c.lastUsed = time.Now()
+	if err != nil {
+		c.errors++
+	}
+}
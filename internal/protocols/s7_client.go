@@ -0,0 +1,352 @@
+package protocols
+
+import (
+	"encoding/binary"
+	"fmt"
+	"net"
+	"sort"
+	"sync"
+	"time"
+)
+
+// S7 Client
+//
+// S7Client talks to one CPU over an ISO-on-TCP connection. Reads of many
+// addresses are optimized: addresses in the same area and data block that
+// lie close together are merged into one item, and items are packed into
+// as few Read Var requests as the negotiated PDU size allows.
+
+// s7ReadGap is the largest gap between two addresses that are still read
+// as one item; reading a few unused bytes is cheaper than another item
+const s7ReadGap = 16
+
+// S7 PDU overheads: the response header of 12 bytes and the 2 bytes of
+// function and item count
+const (
+	s7HeaderSize       = 12
+	s7ParametersHeader = 2
+	s7ItemSpecSize     = 12
+)
+
+// S7Client is a client session with one S7 CPU
+type S7Client struct {
+	Timeout time.Duration
+
+	conn net.Conn
+
+	mutex     sync.Mutex // serializes requests
+	pduSize   int
+	reference uint16
+}
+
+// S7RemoteTSAP returns the TSAP of a CPU in a rack and slot for a PG
+// connection. S7-300 CPUs sit in slot 2; S7-1200 and S7-1500 CPUs are
+// reached at slot 0 or 1.
+func S7RemoteTSAP(rack, slot int) uint16 {
+	return 0x0100 | uint16(rack*0x20+slot)
+}
+
+// NewS7Client opens a session on an open connection to port 102: it
+// connects the COTP transport between the TSAPs and negotiates the PDU
+// size, proposing pduSize
+func NewS7Client(conn net.Conn, localTSAP, remoteTSAP uint16, pduSize int, timeout time.Duration) (*S7Client, error) {
+	c := &S7Client{Timeout: timeout, conn: conn, pduSize: pduSize}
+
+	_ = conn.SetDeadline(time.Now().Add(timeout))
+	defer conn.SetDeadline(time.Time{})
+
+	if _, err := conn.Write(appendS7Frame(nil, appendCOTPConnectionRequest(nil, localTSAP, remoteTSAP))); err != nil {
+		return nil, fmt.Errorf("failed to send COTP connection request: %w", err)
+	}
+	tpdu, err := readS7Frame(conn)
+	if err != nil {
+		return nil, fmt.Errorf("failed to read COTP connection confirm: %w", err)
+	}
+	if tpdu[1]&0xF0 != cotpConnectionConfirm {
+		return nil, fmt.Errorf("COTP connection refused (TPDU 0x%02X), check rack and slot", tpdu[1])
+	}
+
+	response, err := c.transact(encodeS7SetupCommunication(pduSize), nil)
+	if err != nil {
+		return nil, fmt.Errorf("S7 communication setup failed: %w", err)
+	}
+	if len(response.Parameters) < 8 {
+		return nil, fmt.Errorf("invalid S7 communication setup response")
+	}
+	c.pduSize = int(binary.BigEndian.Uint16(response.Parameters[6:8]))
+	if c.pduSize < 64 {
+		return nil, fmt.Errorf("S7 CPU negotiated PDU size %d", c.pduSize)
+	}
+	return c, nil
+}
+
+// PDUSize returns the negotiated PDU size
+func (c *S7Client) PDUSize() int {
+	c.mutex.Lock()
+	defer c.mutex.Unlock()
+	return c.pduSize
+}
+
+// ReadArea reads length bytes of an area from start; the read is split
+// into as many requests as the PDU size requires
+func (c *S7Client) ReadArea(area S7Area, db uint16, start uint32, length int) ([]byte, error) {
+	chunk := c.maxItemLength()
+	data := make([]byte, 0, length)
+	for offset := 0; offset < length; offset += chunk {
+		item := s7Item{Area: area, DB: db, Start: start + uint32(offset), Length: length - offset}
+		if item.Length > chunk {
+			item.Length = chunk
+		}
+
+		results, err := c.read([]s7Item{item})
+		if err != nil {
+			return nil, err
+		}
+		if results[0].Err != nil {
+			return nil, results[0].Err
+		}
+		data = append(data, results[0].Data...)
+	}
+	return data, nil
+}
+
+// WriteArea writes data to an area from start
+func (c *S7Client) WriteArea(area S7Area, db uint16, start uint32, data []byte) error {
+	// The item's specification shares the request with its data
+	chunk := c.maxItemLength() - s7ItemSpecSize
+	for offset := 0; offset < len(data); offset += chunk {
+		end := offset + chunk
+		if end > len(data) {
+			end = len(data)
+		}
+
+		item := s7Item{Area: area, DB: db, Start: start + uint32(offset), Length: end - offset}
+		errs, err := c.write([]s7Item{item}, [][]byte{data[offset:end]})
+		if err != nil {
+			return err
+		}
+		if errs[0] != nil {
+			return errs[0]
+		}
+	}
+	return nil
+}
+
+// ReadItems reads addresses with as few requests as possible and returns
+// their bytes in order. Addresses the CPU could not read carry an error.
+func (c *S7Client) ReadItems(addresses []*S7Address) ([]S7Result, error) {
+	maxLength := c.maxItemLength()
+
+	// Sort by area, data block and offset, then merge neighbours
+	order := make([]int, len(addresses))
+	for i := range order {
+		order[i] = i
+	}
+	sort.Slice(order, func(i, j int) bool {
+		a, b := addresses[order[i]], addresses[order[j]]
+		if a.Area != b.Area {
+			return a.Area < b.Area
+		}
+		if a.DB != b.DB {
+			return a.DB < b.DB
+		}
+		return a.Byte < b.Byte
+	})
+
+	var items []s7Item
+	itemOf := make([]int, len(addresses))
+	for _, i := range order {
+		address := addresses[i]
+		end := int(address.Byte) + address.Length()
+
+		if n := len(items); n > 0 {
+			last := &items[n-1]
+			lastEnd := int(last.Start) + last.Length
+			if last.Area == address.Area && last.DB == address.DB &&
+				int(address.Byte) <= lastEnd+s7ReadGap && end-int(last.Start) <= maxLength {
+				if end > lastEnd {
+					last.Length = end - int(last.Start)
+				}
+				itemOf[i] = n - 1
+				continue
+			}
+		}
+
+		items = append(items, s7Item{Area: address.Area, DB: address.DB, Start: address.Byte, Length: address.Length()})
+		itemOf[i] = len(items) - 1
+	}
+
+	itemResults, err := c.read(items)
+	if err != nil {
+		return nil, err
+	}
+
+	results := make([]S7Result, len(addresses))
+	for i, address := range addresses {
+		item, result := items[itemOf[i]], itemResults[itemOf[i]]
+		offset := int(address.Byte - item.Start)
+		switch {
+		case result.Err != nil:
+			results[i].Err = result.Err
+		case offset+address.Length() > len(result.Data):
+			results[i].Err = fmt.Errorf("S7 CPU returned %d bytes for %d", len(result.Data), item.Length)
+		default:
+			results[i].Data = result.Data[offset : offset+address.Length()]
+		}
+	}
+	return results, nil
+}
+
+// WriteItems writes encoded values to addresses, packing as many items
+// into each request as fit, and returns an error per address. Bits are
+// written as bits so neighbouring bits are left alone.
+func (c *S7Client) WriteItems(addresses []*S7Address, values [][]byte) ([]error, error) {
+	items := make([]s7Item, len(addresses))
+	for i, address := range addresses {
+		items[i] = s7Item{Area: address.Area, DB: address.DB, Start: address.Byte, Length: address.Length()}
+		if address.Size == S7SizeBit {
+			items[i].Bit, items[i].IsBit = address.Bit, true
+		}
+	}
+	return c.write(items, values)
+}
+
+// Ping reads one byte of the merker area; an S7 response of any kind,
+// including an item error, shows the CPU is answering
+func (c *S7Client) Ping() error {
+	_, err := c.read([]s7Item{{Area: S7AreaMerker, Length: 1}})
+	return err
+}
+
+// read reads items with as few Read Var requests as the PDU size allows.
+// Items must be no longer than maxItemLength.
+func (c *S7Client) read(items []s7Item) ([]S7Result, error) {
+	pduSize := c.PDUSize()
+
+	results := make([]S7Result, 0, len(items))
+	for start := 0; start < len(items); {
+		// Request and response must both fit the PDU
+		end, requestSize, responseSize := start, s7HeaderSize+s7ParametersHeader, s7HeaderSize+s7ParametersHeader
+		for end < len(items) && end-start < s7MaxItems {
+			itemResponseSize := s7ReadResponseSize(items[end].Length)
+			if end > start && (requestSize+s7ItemSpecSize > pduSize || responseSize+itemResponseSize > pduSize) {
+				break
+			}
+			requestSize += s7ItemSpecSize
+			responseSize += itemResponseSize
+			end++
+		}
+
+		response, err := c.transact(encodeS7ReadRequest(items[start:end]), nil)
+		if err != nil {
+			return nil, err
+		}
+		if len(response.Parameters) < 2 || response.Parameters[0] != s7FunctionReadVar || int(response.Parameters[1]) != end-start {
+			return nil, fmt.Errorf("invalid Read Var response")
+		}
+		batch, err := decodeS7ReadResponse(response.Data, end-start)
+		if err != nil {
+			return nil, err
+		}
+		results = append(results, batch...)
+		start = end
+	}
+	return results, nil
+}
+
+// write writes items with as few Write Var requests as the PDU size
+// allows
+func (c *S7Client) write(items []s7Item, values [][]byte) ([]error, error) {
+	pduSize := c.PDUSize()
+	for i, item := range items {
+		if len(values[i]) != item.Length {
+			return nil, fmt.Errorf("%d bytes for an item of %d", len(values[i]), item.Length)
+		}
+		if s7HeaderSize+s7ParametersHeader+s7ItemSpecSize+s7ReadResponseSize(item.Length) > pduSize {
+			return nil, fmt.Errorf("item of %d bytes exceeds the PDU size of %d", item.Length, pduSize)
+		}
+	}
+
+	errs := make([]error, 0, len(items))
+	for start := 0; start < len(items); {
+		end, size := start, s7HeaderSize+s7ParametersHeader
+		for end < len(items) && end-start < s7MaxItems {
+			itemSize := s7ItemSpecSize + s7ReadResponseSize(items[end].Length)
+			if end > start && size+itemSize > pduSize {
+				break
+			}
+			size += itemSize
+			end++
+		}
+
+		parameters, data := encodeS7WriteRequest(items[start:end], values[start:end])
+		response, err := c.transact(parameters, data)
+		if err != nil {
+			return nil, err
+		}
+		if len(response.Parameters) < 2 || response.Parameters[0] != s7FunctionWriteVar {
+			return nil, fmt.Errorf("invalid Write Var response")
+		}
+		batch, err := decodeS7WriteResponse(response.Data, end-start)
+		if err != nil {
+			return nil, err
+		}
+		errs = append(errs, batch...)
+		start = end
+	}
+	return errs, nil
+}
+
+// maxItemLength is the longest item whose reply fits one PDU
+func (c *S7Client) maxItemLength() int {
+	length := c.PDUSize() - s7HeaderSize - s7ParametersHeader - 4
+	return length &^ 1
+}
+
+// transact sends a job and returns its acknowledgement
+func (c *S7Client) transact(parameters, data []byte) (*s7PDU, error) {
+	c.mutex.Lock()
+	defer c.mutex.Unlock()
+
+	c.reference++
+	request := &s7PDU{Type: s7PDUJob, Reference: c.reference, Parameters: parameters, Data: data}
+	frame := appendS7Frame(nil, appendS7PDU(appendCOTPData(nil), request))
+
+	_ = c.conn.SetDeadline(time.Now().Add(c.Timeout))
+	defer c.conn.SetDeadline(time.Time{})
+
+	if _, err := c.conn.Write(frame); err != nil {
+		return nil, fmt.Errorf("failed to send S7 request: %w", err)
+	}
+
+	// A PDU may span several data TPDUs; the last is marked
+	var payload []byte
+	for {
+		tpdu, err := readS7Frame(c.conn)
+		if err != nil {
+			return nil, fmt.Errorf("failed to read S7 response: %w", err)
+		}
+		if tpdu[1] != cotpData || len(tpdu) < 3 {
+			return nil, fmt.Errorf("unexpected COTP TPDU 0x%02X", tpdu[1])
+		}
+		payload = append(payload, tpdu[3:]...)
+		if tpdu[2]&cotpEndOfTransmission != 0 {
+			break
+		}
+	}
+
+	response, err := parseS7PDU(payload)
+	if err != nil {
+		return nil, err
+	}
+	if response.Type != s7PDUAckData && response.Type != s7PDUAck {
+		return nil, fmt.Errorf("unexpected S7 PDU type %d", response.Type)
+	}
+	if response.Reference != c.reference {
+		return nil, fmt.Errorf("S7 response for request %d to request %d", response.Reference, c.reference)
+	}
+	if response.ErrorClass != 0 || response.ErrorCode != 0 {
+		return nil, &S7Error{Class: response.ErrorClass, Code: response.ErrorCode}
+	}
+	return response, nil
+}
@@ -0,0 +1,539 @@
+package protocols
+
+import (
+	"encoding/binary"
+	"fmt"
+	"io"
+	"math"
+	"strconv"
+	"strings"
+)
+
+// S7comm Codec
+//
+// S7comm PDUs travel over ISO-on-TCP (RFC 1006): every TPKT carries one
+// COTP TPDU, and S7 PDUs are sent in COTP data TPDUs. A PDU has a header,
+// parameters naming the function and its items, and data carrying the
+// values of those items. Numeric values are big-endian.
+
+// S7DefaultPort is the ISO-on-TCP port
+const S7DefaultPort = 102
+
+// TPKT and COTP
+const (
+	s7TPKTVersion         = 0x03
+	cotpConnectionRequest = 0xE0
+	cotpConnectionConfirm = 0xD0
+	cotpData              = 0xF0
+	cotpEndOfTransmission = 0x80
+)
+
+// S7 PDU types and functions
+const (
+	s7ProtocolID = 0x32
+
+	s7PDUJob     = 0x01
+	s7PDUAck     = 0x02
+	s7PDUAckData = 0x03
+
+	s7FunctionReadVar            = 0x04
+	s7FunctionWriteVar           = 0x05
+	s7FunctionSetupCommunication = 0xF0
+)
+
+// Transport sizes of item specifications and of item data
+const (
+	s7TransportBit  = 0x01
+	s7TransportByte = 0x02
+
+	s7DataBit   = 0x03
+	s7DataByte  = 0x04 // Length in bits
+	s7DataOctet = 0x09
+	s7DataReal  = 0x07
+
+	s7ReturnSuccess = 0xFF
+)
+
+// s7MaxItems is the number of items CPUs accept in one Read Var or Write
+// Var request
+const s7MaxItems = 20
+
+// s7MaxByteOffset is the largest byte offset of the 24-bit bit addresses
+const s7MaxByteOffset = 1<<21 - 1
+
+// S7Area is a memory area of an S7 CPU
+type S7Area byte
+
+const (
+	S7AreaInputs  S7Area = 0x81
+	S7AreaOutputs S7Area = 0x82
+	S7AreaMerker  S7Area = 0x83
+	S7AreaDB      S7Area = 0x84
+)
+
+// S7Size is the width of an addressed value in bytes; bits have size 0
+type S7Size int
+
+const (
+	S7SizeBit   S7Size = 0
+	S7SizeByte  S7Size = 1
+	S7SizeWord  S7Size = 2
+	S7SizeDWord S7Size = 4
+)
+
+// S7Address addresses a bit, byte, word or double word of a memory area
+type S7Address struct {
+	Area S7Area
+	DB   uint16 // Data block number; DB area only
+	Byte uint32
+	Bit  uint8 // Bit number; bit addresses only
+	Size S7Size
+}
+
+// Length returns the number of bytes spanned by the address
+func (a *S7Address) Length() int {
+	if a.Size == S7SizeBit {
+		return 1
+	}
+	return int(a.Size)
+}
+
+// String formats the address in English mnemonics
+func (a *S7Address) String() string {
+	widths := map[S7Size]string{S7SizeBit: "X", S7SizeByte: "B", S7SizeWord: "W", S7SizeDWord: "D"}
+
+	var prefix string
+	switch a.Area {
+	case S7AreaInputs:
+		prefix = "I"
+	case S7AreaOutputs:
+		prefix = "Q"
+	case S7AreaMerker:
+		prefix = "M"
+	case S7AreaDB:
+		prefix = fmt.Sprintf("DB%d.DB", a.DB)
+	}
+
+	if a.Size != S7SizeBit {
+		return fmt.Sprintf("%s%s%d", prefix, widths[a.Size], a.Byte)
+	}
+	if a.Area == S7AreaDB {
+		prefix += "X"
+	}
+	return fmt.Sprintf("%s%d.%d", prefix, a.Byte, a.Bit)
+}
+
+// ParseS7Address parses an address in Step 7 notation with English or
+// German mnemonics: "DB1.DBX0.3", "DB1.DBW20", "M10.2", "MD4", "IW2" or
+// "EW2", "Q0.1" or "A0.1"
+func ParseS7Address(address string) (*S7Address, error) {
+	s := strings.ToUpper(strings.ReplaceAll(strings.TrimSpace(address), " ", ""))
+	a := &S7Address{}
+
+	var rest string
+	switch {
+	case strings.HasPrefix(s, "DB"):
+		number, member, ok := strings.Cut(s[2:], ".")
+		db, err := strconv.ParseUint(number, 10, 16)
+		if !ok || err != nil || db == 0 || !strings.HasPrefix(member, "DB") {
+			return nil, fmt.Errorf("invalid S7 data block address %q, expected e.g. DB1.DBW0", address)
+		}
+		a.Area, a.DB, rest = S7AreaDB, uint16(db), member[2:]
+	case strings.HasPrefix(s, "I"), strings.HasPrefix(s, "E"):
+		a.Area, rest = S7AreaInputs, s[1:]
+	case strings.HasPrefix(s, "Q"), strings.HasPrefix(s, "A"):
+		a.Area, rest = S7AreaOutputs, s[1:]
+	case strings.HasPrefix(s, "M"):
+		a.Area, rest = S7AreaMerker, s[1:]
+	default:
+		return nil, fmt.Errorf("invalid S7 address %q, expected an I, Q, M or DB address", address)
+	}
+
+	switch {
+	case strings.HasPrefix(rest, "X"):
+		a.Size, rest = S7SizeBit, rest[1:]
+	case strings.HasPrefix(rest, "B"):
+		a.Size, rest = S7SizeByte, rest[1:]
+	case strings.HasPrefix(rest, "W"):
+		a.Size, rest = S7SizeWord, rest[1:]
+	case strings.HasPrefix(rest, "D"):
+		a.Size, rest = S7SizeDWord, rest[1:]
+	case a.Area == S7AreaDB:
+		return nil, fmt.Errorf("invalid S7 address %q, expected DBX, DBB, DBW or DBD", address)
+	default:
+		a.Size = S7SizeBit
+	}
+
+	if a.Size == S7SizeBit {
+		offset, bitNumber, ok := strings.Cut(rest, ".")
+		bit, err := strconv.ParseUint(bitNumber, 10, 8)
+		if !ok || err != nil || bit > 7 {
+			return nil, fmt.Errorf("invalid S7 bit address %q, expected <byte>.<bit> with bit 0 to 7", address)
+		}
+		a.Bit, rest = uint8(bit), offset
+	}
+
+	offset, err := strconv.ParseUint(rest, 10, 32)
+	if err != nil || offset > s7MaxByteOffset {
+		return nil, fmt.Errorf("invalid S7 byte offset in address %q", address)
+	}
+	a.Byte = uint32(offset)
+	return a, nil
+}
+
+// S7Error is an error reported in the header of an S7 response
+type S7Error struct {
+	Class byte
+	Code  byte
+}
+
+var s7ErrorClasses = map[byte]string{
+	0x81: "application relationship error",
+	0x82: "object definition error",
+	0x83: "no resources available",
+	0x84: "error on service processing",
+	0x85: "error on supplies",
+	0x87: "access error",
+}
+
+func (e *S7Error) Error() string {
+	if class, known := s7ErrorClasses[e.Class]; known {
+		return fmt.Sprintf("S7 %s (class 0x%02X, code 0x%02X)", class, e.Class, e.Code)
+	}
+	return fmt.Sprintf("S7 error class 0x%02X, code 0x%02X", e.Class, e.Code)
+}
+
+// S7ItemError is the return code of an item the CPU could not read or
+// write
+type S7ItemError byte
+
+var s7ItemErrors = map[S7ItemError]string{
+	0x01: "hardware fault",
+	0x03: "access to object not allowed",
+	0x05: "address out of range",
+	0x06: "data type not supported",
+	0x07: "data type inconsistent",
+	0x0A: "object does not exist",
+}
+
+func (e S7ItemError) Error() string {
+	if message, known := s7ItemErrors[e]; known {
+		return fmt.Sprintf("S7 item error: %s", message)
+	}
+	return fmt.Sprintf("S7 item error 0x%02X", byte(e))
+}
+
+// appendS7Frame appends a TPKT carrying a COTP TPDU
+func appendS7Frame(dst []byte, tpdu []byte) []byte {
+	dst = append(dst, s7TPKTVersion, 0)
+	dst = binary.BigEndian.AppendUint16(dst, uint16(4+len(tpdu)))
+	return append(dst, tpdu...)
+}
+
+// readS7Frame reads a TPKT and returns the COTP TPDU it carries
+func readS7Frame(r io.Reader) ([]byte, error) {
+	header := make([]byte, 4)
+	if _, err := io.ReadFull(r, header); err != nil {
+		return nil, err
+	}
+	if header[0] != s7TPKTVersion {
+		return nil, fmt.Errorf("invalid TPKT version %d", header[0])
+	}
+
+	length := int(binary.BigEndian.Uint16(header[2:4]))
+	if length < 7 {
+		return nil, fmt.Errorf("invalid TPKT length %d", length)
+	}
+	tpdu := make([]byte, length-4)
+	if _, err := io.ReadFull(r, tpdu); err != nil {
+		return nil, err
+	}
+	if int(tpdu[0]) >= len(tpdu) {
+		return nil, fmt.Errorf("invalid COTP header length %d", tpdu[0])
+	}
+	return tpdu, nil
+}
+
+// appendCOTPConnectionRequest appends a connection request for a TPDU
+// size of 1024 between two TSAPs
+func appendCOTPConnectionRequest(dst []byte, localTSAP, remoteTSAP uint16) []byte {
+	dst = append(dst, 17, cotpConnectionRequest, 0, 0, 0, 1, 0)
+	dst = append(dst, 0xC0, 1, 0x0A)
+	dst = append(dst, 0xC1, 2, byte(localTSAP>>8), byte(localTSAP))
+	return append(dst, 0xC2, 2, byte(remoteTSAP>>8), byte(remoteTSAP))
+}
+
+// appendCOTPData appends the header of the last data TPDU of a PDU
+func appendCOTPData(dst []byte) []byte {
+	return append(dst, 2, cotpData, cotpEndOfTransmission)
+}
+
+// s7PDU is an S7 PDU. The error fields are only sent in acknowledgements.
+type s7PDU struct {
+	Type       byte
+	Reference  uint16
+	ErrorClass byte
+	ErrorCode  byte
+	Parameters []byte
+	Data       []byte
+}
+
+func appendS7PDU(dst []byte, pdu *s7PDU) []byte {
+	dst = append(dst, s7ProtocolID, pdu.Type, 0, 0)
+	dst = binary.BigEndian.AppendUint16(dst, pdu.Reference)
+	dst = binary.BigEndian.AppendUint16(dst, uint16(len(pdu.Parameters)))
+	dst = binary.BigEndian.AppendUint16(dst, uint16(len(pdu.Data)))
+	if pdu.Type == s7PDUAck || pdu.Type == s7PDUAckData {
+		dst = append(dst, pdu.ErrorClass, pdu.ErrorCode)
+	}
+	dst = append(dst, pdu.Parameters...)
+	return append(dst, pdu.Data...)
+}
+
+func parseS7PDU(data []byte) (*s7PDU, error) {
+	if len(data) < 10 || data[0] != s7ProtocolID {
+		return nil, fmt.Errorf("invalid S7 PDU header")
+	}
+
+	pdu := &s7PDU{
+		Type:      data[1],
+		Reference: binary.BigEndian.Uint16(data[4:6]),
+	}
+	parametersLength := int(binary.BigEndian.Uint16(data[6:8]))
+	dataLength := int(binary.BigEndian.Uint16(data[8:10]))
+
+	offset := 10
+	if pdu.Type == s7PDUAck || pdu.Type == s7PDUAckData {
+		if len(data) < 12 {
+			return nil, fmt.Errorf("invalid S7 PDU header")
+		}
+		pdu.ErrorClass, pdu.ErrorCode = data[10], data[11]
+		offset = 12
+	}
+	if len(data) < offset+parametersLength+dataLength {
+		return nil, fmt.Errorf("truncated S7 PDU")
+	}
+
+	pdu.Parameters = data[offset : offset+parametersLength]
+	pdu.Data = data[offset+parametersLength : offset+parametersLength+dataLength]
+	return pdu, nil
+}
+
+// encodeS7SetupCommunication encodes the parameters proposing one
+// outstanding job in each direction and a PDU size
+func encodeS7SetupCommunication(pduSize int) []byte {
+	parameters := []byte{s7FunctionSetupCommunication, 0, 0, 1, 0, 1}
+	return binary.BigEndian.AppendUint16(parameters, uint16(pduSize))
+}
+
+// s7Item is a range of bytes, or a single bit, transferred as one item
+type s7Item struct {
+	Area   S7Area
+	DB     uint16
+	Start  uint32
+	Bit    uint8
+	IsBit  bool
+	Length int
+}
+
+// appendS7ItemSpec appends the variable specification of an item
+func appendS7ItemSpec(dst []byte, item s7Item) []byte {
+	transport, count := byte(s7TransportByte), uint16(item.Length)
+	if item.IsBit {
+		transport, count = s7TransportBit, 1
+	}
+
+	dst = append(dst, 0x12, 0x0A, 0x10, transport)
+	dst = binary.BigEndian.AppendUint16(dst, count)
+	dst = binary.BigEndian.AppendUint16(dst, item.DB)
+	address := item.Start*8 + uint32(item.Bit)
+	return append(dst, byte(item.Area), byte(address>>16), byte(address>>8), byte(address))
+}
+
+// encodeS7ReadRequest encodes the parameters of a Read Var request
+func encodeS7ReadRequest(items []s7Item) []byte {
+	parameters := []byte{s7FunctionReadVar, byte(len(items))}
+	for _, item := range items {
+		parameters = appendS7ItemSpec(parameters, item)
+	}
+	return parameters
+}
+
+// S7Result is the outcome of one item of a request
+type S7Result struct {
+	Data []byte
+	Err  error
+}
+
+// decodeS7ReadResponse decodes the data of a Read Var response. Items are
+// padded to an even length, except the last.
+func decodeS7ReadResponse(data []byte, count int) ([]S7Result, error) {
+	results := make([]S7Result, 0, count)
+	for i := 0; i < count; i++ {
+		if len(data) < 4 {
+			return nil, fmt.Errorf("truncated Read Var response")
+		}
+
+		code, transport := data[0], data[1]
+		length := int(binary.BigEndian.Uint16(data[2:4]))
+		if transport != s7DataBit && transport != s7DataOctet && transport != s7DataReal {
+			length = (length + 7) / 8
+		}
+		if len(data) < 4+length {
+			return nil, fmt.Errorf("truncated Read Var response")
+		}
+
+		if code != s7ReturnSuccess {
+			results = append(results, S7Result{Err: S7ItemError(code)})
+		} else {
+			results = append(results, S7Result{Data: append([]byte(nil), data[4:4+length]...)})
+		}
+
+		data = data[4+length:]
+		if length%2 != 0 && len(data) > 0 {
+			data = data[1:]
+		}
+	}
+	return results, nil
+}
+
+// encodeS7WriteRequest encodes the parameters and data of a Write Var
+// request. Bit items carry one byte holding 0 or 1.
+func encodeS7WriteRequest(items []s7Item, values [][]byte) (parameters, data []byte) {
+	parameters = []byte{s7FunctionWriteVar, byte(len(items))}
+	for i, item := range items {
+		parameters = appendS7ItemSpec(parameters, item)
+
+		transport, length := byte(s7DataByte), len(values[i])*8
+		if item.IsBit {
+			transport, length = s7DataBit, 1
+		}
+		data = append(data, 0, transport)
+		data = binary.BigEndian.AppendUint16(data, uint16(length))
+		data = append(data, values[i]...)
+		if len(values[i])%2 != 0 && i < len(items)-1 {
+			data = append(data, 0)
+		}
+	}
+	return parameters, data
+}
+
+// decodeS7WriteResponse decodes the return codes of a Write Var response
+func decodeS7WriteResponse(data []byte, count int) ([]error, error) {
+	if len(data) < count {
+		return nil, fmt.Errorf("truncated Write Var response")
+	}
+
+	errs := make([]error, count)
+	for i := range errs {
+		if data[i] != s7ReturnSuccess {
+			errs[i] = S7ItemError(data[i])
+		}
+	}
+	return errs, nil
+}
+
+// s7ReadResponseSize is the size of an item in a Read Var response
+func s7ReadResponseSize(length int) int {
+	return 4 + length + length%2
+}
+
+// decodeS7Value decodes the bytes of an address as a tag data type. The
+// type defaults to BOOL, BYTE, INT or DINT by the address width; words
+// can also be read as uint16, and double words as uint32 or float32.
+func decodeS7Value(address *S7Address, dataType string, data []byte) (interface{}, error) {
+	if len(data) < address.Length() {
+		return nil, fmt.Errorf("insufficient data for %s", address)
+	}
+	if DataType(dataType) == DataTypeBytes {
+		return append([]byte(nil), data[:address.Length()]...), nil
+	}
+
+	switch address.Size {
+	case S7SizeBit:
+		if dataType == "" || DataType(dataType) == DataTypeBool {
+			return data[0]>>address.Bit&1 != 0, nil
+		}
+	case S7SizeByte:
+		if dataType == "" {
+			return data[0], nil
+		}
+	case S7SizeWord:
+		switch DataType(dataType) {
+		case "", DataTypeInt16:
+			return int16(binary.BigEndian.Uint16(data)), nil
+		case DataTypeUInt16:
+			return binary.BigEndian.Uint16(data), nil
+		}
+	case S7SizeDWord:
+		switch DataType(dataType) {
+		case "", DataTypeInt32:
+			return int32(binary.BigEndian.Uint32(data)), nil
+		case DataTypeUInt32:
+			return binary.BigEndian.Uint32(data), nil
+		case DataTypeFloat32:
+			return math.Float32frombits(binary.BigEndian.Uint32(data)), nil
+		}
+	}
+	return nil, fmt.Errorf("data type %q does not fit S7 address %s", dataType, address)
+}
+
+// encodeS7Value encodes a value for an address. Bits are encoded as one
+// byte holding 0 or 1.
+func encodeS7Value(address *S7Address, dataType string, value interface{}) ([]byte, error) {
+	if data, ok := value.([]byte); ok {
+		if len(data) != address.Length() || address.Size == S7SizeBit {
+			return nil, fmt.Errorf("%d bytes do not fit S7 address %s", len(data), address)
+		}
+		return data, nil
+	}
+
+	switch address.Size {
+	case S7SizeBit:
+		on, ok := value.(bool)
+		if !ok {
+			return nil, fmt.Errorf("S7 bit %s requires a bool value, got %T", address, value)
+		}
+		if on {
+			return []byte{1}, nil
+		}
+		return []byte{0}, nil
+
+	case S7SizeByte:
+		v, err := integerValue(value, 0, math.MaxUint8)
+		if err != nil {
+			return nil, err
+		}
+		return []byte{byte(v)}, nil
+
+	case S7SizeWord:
+		low, high := int64(math.MinInt16), int64(math.MaxInt16)
+		if DataType(dataType) == DataTypeUInt16 {
+			low, high = 0, math.MaxUint16
+		}
+		v, err := integerValue(value, low, high)
+		if err != nil {
+			return nil, err
+		}
+		return binary.BigEndian.AppendUint16(nil, uint16(v)), nil
+
+	default:
+		if DataType(dataType) == DataTypeFloat32 {
+			f, err := floatValue(value)
+			if err != nil {
+				return nil, err
+			}
+			return binary.BigEndian.AppendUint32(nil, math.Float32bits(float32(f))), nil
+		}
+
+		low, high := int64(math.MinInt32), int64(math.MaxInt32)
+		if DataType(dataType) == DataTypeUInt32 {
+			low, high = 0, math.MaxUint32
+		}
+		v, err := integerValue(value, low, high)
+		if err != nil {
+			return nil, err
+		}
+		return binary.BigEndian.AppendUint32(nil, uint32(v)), nil
+	}
+}
@@ -0,0 +1,380 @@
+package protocols
+
+import (
+	"encoding/binary"
+	"fmt"
+	"math"
+	"net"
+	"sync"
+	"testing"
+	"time"
+
+	"github.com/stretchr/testify/assert"
+	"go.uber.org/zap"
+)
+
+func TestParseS7Address(t *testing.T) {
+	valid := map[string]S7Address{
+		"DB1.DBX0.3": {Area: S7AreaDB, DB: 1, Byte: 0, Bit: 3, Size: S7SizeBit},
+		"db10.dbb4":  {Area: S7AreaDB, DB: 10, Byte: 4, Size: S7SizeByte},
+		"DB2.DBW20":  {Area: S7AreaDB, DB: 2, Byte: 20, Size: S7SizeWord},
+		"M10.2":      {Area: S7AreaMerker, Byte: 10, Bit: 2, Size: S7SizeBit},
+		"MD4":        {Area: S7AreaMerker, Byte: 4, Size: S7SizeDWord},
+		"IW2":        {Area: S7AreaInputs, Byte: 2, Size: S7SizeWord},
+		"EB0":        {Area: S7AreaInputs, Byte: 0, Size: S7SizeByte},
+		"Q0.1":       {Area: S7AreaOutputs, Byte: 0, Bit: 1, Size: S7SizeBit},
+		"A 1.7":      {Area: S7AreaOutputs, Byte: 1, Bit: 7, Size: S7SizeBit},
+	}
+
+	for text, expected := range valid {
+		address, err := ParseS7Address(text)
+		if assert.NoError(t, err, text) {
+			assert.Equal(t, expected, *address, text)
+		}
+	}
+
+	for _, text := range []string{"", "DB1", "DB0.DBW0", "DB1.W0", "DB1.DBX0", "DB1.DBX0.8", "M10", "MW", "T5", "MW-1", "DB1.DBW99999999"} {
+		_, err := ParseS7Address(text)
+		assert.Error(t, err, text)
+	}
+
+	for _, text := range []string{"DB1.DBX0.3", "DB2.DBW20", "M10.2", "MD4", "IW2", "Q0.1"} {
+		address, err := ParseS7Address(text)
+		assert.NoError(t, err)
+		assert.Equal(t, text, address.String())
+	}
+}
+
+func TestS7Values(t *testing.T) {
+	bit := &S7Address{Area: S7AreaMerker, Bit: 3, Size: S7SizeBit}
+	word := &S7Address{Area: S7AreaDB, DB: 1, Size: S7SizeWord}
+	dword := &S7Address{Area: S7AreaDB, DB: 1, Size: S7SizeDWord}
+
+	value, err := decodeS7Value(bit, "", []byte{0x08})
+	assert.NoError(t, err)
+	assert.Equal(t, true, value)
+
+	value, err = decodeS7Value(word, "", []byte{0xFF, 0xFE})
+	assert.NoError(t, err)
+	assert.Equal(t, int16(-2), value)
+	value, err = decodeS7Value(word, string(DataTypeUInt16), []byte{0xFF, 0xFE})
+	assert.NoError(t, err)
+	assert.Equal(t, uint16(0xFFFE), value)
+
+	data, err := encodeS7Value(dword, string(DataTypeFloat32), 1.5)
+	assert.NoError(t, err)
+	assert.Equal(t, []byte{0x3F, 0xC0, 0x00, 0x00}, data)
+	value, err = decodeS7Value(dword, string(DataTypeFloat32), data)
+	assert.NoError(t, err)
+	assert.Equal(t, float32(1.5), value)
+
+	data, err = encodeS7Value(dword, "", -100000)
+	assert.NoError(t, err)
+	value, err = decodeS7Value(dword, "", data)
+	assert.NoError(t, err)
+	assert.Equal(t, int32(-100000), value)
+
+	_, err = encodeS7Value(word, "", 40000)
+	assert.Error(t, err)
+	_, err = encodeS7Value(word, string(DataTypeUInt16), 40000)
+	assert.NoError(t, err)
+	_, err = encodeS7Value(bit, "", 1)
+	assert.Error(t, err)
+	_, err = decodeS7Value(word, string(DataTypeFloat32), []byte{0, 0})
+	assert.Error(t, err)
+}
+
+func TestS7Client_ReadItemsOptimized(t *testing.T) {
+	plc := newS7TestPLC(t, 240)
+	client := plc.dial(t)
+
+	binary.BigEndian.PutUint16(plc.memory(S7AreaDB, 1)[10:], 1234)
+	binary.BigEndian.PutUint32(plc.memory(S7AreaDB, 1)[14:], math.Float32bits(2.5))
+	plc.memory(S7AreaMerker, 0)[3] = 0x04
+
+	var addresses []*S7Address
+	for _, text := range []string{"DB1.DBW10", "M3.2", "DB1.DBD14", "DB1.DBX10.0"} {
+		address, err := ParseS7Address(text)
+		assert.NoError(t, err)
+		addresses = append(addresses, address)
+	}
+	// Thirty words too far apart to merge, in another data block
+	for i := 0; i < 30; i++ {
+		addresses = append(addresses, &S7Address{Area: S7AreaDB, DB: 2, Byte: uint32(i * 100), Size: S7SizeWord})
+		binary.BigEndian.PutUint16(plc.memory(S7AreaDB, 2)[i*100:], uint16(i))
+	}
+	// A missing data block fails on its own
+	addresses = append(addresses, &S7Address{Area: S7AreaDB, DB: 99, Size: S7SizeByte})
+
+	results, err := client.ReadItems(addresses)
+	assert.NoError(t, err)
+	assert.Len(t, results, len(addresses))
+
+	value, _ := decodeS7Value(addresses[0], "", results[0].Data)
+	assert.Equal(t, int16(1234), value)
+	value, _ = decodeS7Value(addresses[1], "", results[1].Data)
+	assert.Equal(t, true, value)
+	value, _ = decodeS7Value(addresses[2], string(DataTypeFloat32), results[2].Data)
+	assert.Equal(t, float32(2.5), value)
+	value, _ = decodeS7Value(addresses[3], "", results[3].Data)
+	assert.Equal(t, false, value) // 1234 = 0x04D2, bit 0 of 0x04
+	for i := 0; i < 30; i++ {
+		assert.Equal(t, []byte{0, byte(i)}, results[4+i].Data)
+	}
+	assert.Equal(t, S7ItemError(0x0A), results[len(results)-1].Err)
+
+	// DB1 merges into one item; the 33 items need two requests at this
+	// PDU size
+	requests, items := plc.readStats()
+	assert.Equal(t, 2, requests)
+	assert.Equal(t, 33, items)
+}
+
+func TestS7Client_ReadWriteArea(t *testing.T) {
+	plc := newS7TestPLC(t, 240)
+	client := plc.dial(t)
+	assert.Equal(t, 240, client.PDUSize())
+
+	data := make([]byte, 700)
+	for i := range data {
+		data[i] = byte(i * 7)
+	}
+	assert.NoError(t, client.WriteArea(S7AreaDB, 3, 5, data))
+	assert.Equal(t, data, plc.memory(S7AreaDB, 3)[5:705])
+
+	read, err := client.ReadArea(S7AreaDB, 3, 5, len(data))
+	assert.NoError(t, err)
+	assert.Equal(t, data, read)
+
+	_, err = client.ReadArea(S7AreaDB, 99, 0, 10)
+	assert.Equal(t, S7ItemError(0x0A), err)
+}
+
+func TestS7Handler(t *testing.T) {
+	plc := newS7TestPLC(t, 480)
+	handler := NewS7Handler(zap.NewNop()).(*S7Handler)
+	device := plc.device(map[string]interface{}{"rack": 0, "slot": 2})
+
+	assert.NoError(t, handler.Connect(device))
+	assert.True(t, handler.IsConnected(device))
+
+	plc.memory(S7AreaMerker, 0)[0] = 0xF0
+	assert.NoError(t, handler.WriteTag(device, &Tag{ID: "run", Address: "M0.1", Writable: true}, true))
+	assert.NoError(t, handler.WriteTag(device, &Tag{ID: "run", Address: "M0.7", Writable: true}, false))
+	assert.Equal(t, byte(0x72), plc.memory(S7AreaMerker, 0)[0])
+
+	speed := &Tag{ID: "speed", Address: "DB1.DBD0", DataType: string(DataTypeFloat32), Writable: true}
+	count := &Tag{ID: "count", Address: "DB1.DBW4", DataType: string(DataTypeUInt16), Writable: true}
+	assert.NoError(t, handler.WriteTag(device, speed, 12.25))
+	assert.NoError(t, handler.WriteTag(device, count, 65000))
+	assert.Error(t, handler.WriteTag(device, &Tag{ID: "x", Address: "DB99.DBW0", Writable: true}, 1))
+	assert.Error(t, handler.WriteTag(device, &Tag{ID: "x", Address: "DB1.DBW0"}, 1))
+
+	value, err := handler.ReadTag(device, speed)
+	assert.NoError(t, err)
+	assert.Equal(t, float32(12.25), value)
+
+	results, err := handler.ReadMultipleTags(device, []*Tag{
+		speed,
+		count,
+		{ID: "run", Address: "M0.1"},
+		{ID: "missing", Address: "DB99.DBW0"},
+		{ID: "invalid", Address: "T1"},
+	})
+	assert.NoError(t, err)
+	assert.Equal(t, map[string]interface{}{"speed": float32(12.25), "count": uint16(65000), "run": true}, results)
+
+	assert.NoError(t, handler.Ping(device))
+	info, err := handler.GetDeviceInfo(device)
+	assert.NoError(t, err)
+	assert.Equal(t, "480", info.CustomInfo["pdu_size"])
+
+	diagnostics, err := handler.GetDiagnostics(device)
+	assert.NoError(t, err)
+	assert.Equal(t, uint64(1), diagnostics.ErrorCount)
+
+	assert.NoError(t, handler.Disconnect(device))
+	assert.False(t, handler.IsConnected(device))
+
+	// The PLC only accepts its own rack and slot
+	assert.Error(t, handler.Connect(plc.device(map[string]interface{}{"slot": 3})))
+}
+
+// s7TestPLC is a minimal S7 CPU in rack 0, slot 2 with inputs, outputs,
+// merkers and data blocks 1 to 3
+type s7TestPLC struct {
+	t        *testing.T
+	listener net.Listener
+	pduSize  int
+
+	mutex     sync.Mutex
+	areas     map[string][]byte
+	readCount int
+	itemCount int
+}
+
+func newS7TestPLC(t *testing.T, pduSize int) *s7TestPLC {
+	listener, err := net.Listen("tcp", "127.0.0.1:0")
+	assert.NoError(t, err)
+	t.Cleanup(func() { listener.Close() })
+
+	plc := &s7TestPLC{t: t, listener: listener, pduSize: pduSize, areas: make(map[string][]byte)}
+	for _, key := range []string{"81/0", "82/0", "83/0", "84/1", "84/2", "84/3"} {
+		plc.areas[key] = make([]byte, 4096)
+	}
+
+	go func() {
+		for {
+			conn, err := listener.Accept()
+			if err != nil {
+				return
+			}
+			go plc.serve(conn)
+		}
+	}()
+	return plc
+}
+
+func (p *s7TestPLC) memory(area S7Area, db uint16) []byte {
+	return p.areas[fmt.Sprintf("%02X/%d", byte(area), db)]
+}
+
+func (p *s7TestPLC) readStats() (int, int) {
+	p.mutex.Lock()
+	defer p.mutex.Unlock()
+	return p.readCount, p.itemCount
+}
+
+func (p *s7TestPLC) device(config map[string]interface{}) *Device {
+	address := p.listener.Addr().(*net.TCPAddr)
+	return &Device{ID: "cpu", Address: address.IP.String(), Port: address.Port, Config: config}
+}
+
+func (p *s7TestPLC) dial(t *testing.T) *S7Client {
+	conn, err := net.Dial("tcp", p.listener.Addr().String())
+	assert.NoError(t, err)
+	t.Cleanup(func() { conn.Close() })
+
+	client, err := NewS7Client(conn, 0x0100, S7RemoteTSAP(0, 2), 960, time.Second)
+	assert.NoError(t, err)
+	return client
+}
+
+func (p *s7TestPLC) serve(conn net.Conn) {
+	defer conn.Close()
+
+	for {
+		tpdu, err := readS7Frame(conn)
+		if err != nil {
+			return
+		}
+
+		var reply []byte
+		switch tpdu[1] {
+		case cotpConnectionRequest:
+			// Only the remote TSAP of rack 0, slot 2 is accepted
+			if tpdu[len(tpdu)-1] != 0x02 {
+				return
+			}
+			reply = append([]byte{tpdu[0], cotpConnectionConfirm}, tpdu[2:]...)
+		case cotpData:
+			request, err := parseS7PDU(tpdu[3:])
+			if err != nil {
+				p.t.Errorf("invalid S7 request: %v", err)
+				return
+			}
+			reply = appendS7PDU(appendCOTPData(nil), p.handle(request))
+		default:
+			p.t.Errorf("unexpected COTP TPDU 0x%02X", tpdu[1])
+			return
+		}
+
+		if _, err := conn.Write(appendS7Frame(nil, reply)); err != nil {
+			return
+		}
+	}
+}
+
+func (p *s7TestPLC) handle(request *s7PDU) *s7PDU {
+	response := &s7PDU{Type: s7PDUAckData, Reference: request.Reference}
+	if len(appendS7PDU(nil, request)) > p.pduSize {
+		p.t.Errorf("request of %d bytes exceeds the PDU size", len(appendS7PDU(nil, request)))
+	}
+
+	p.mutex.Lock()
+	defer p.mutex.Unlock()
+
+	parameters := request.Parameters
+	switch parameters[0] {
+	case s7FunctionSetupCommunication:
+		response.Parameters = binary.BigEndian.AppendUint16(append([]byte(nil), parameters[:6]...), uint16(p.pduSize))
+
+	case s7FunctionReadVar:
+		p.readCount++
+		p.itemCount += int(parameters[1])
+		response.Parameters = parameters[:2]
+		for i := 0; i < int(parameters[1]); i++ {
+			memory, offset, length := p.item(parameters[2+12*i:])
+			if memory == nil {
+				response.Data = append(response.Data, 0x0A, 0, 0, 0)
+				continue
+			}
+			response.Data = append(response.Data, s7ReturnSuccess, s7DataByte)
+			response.Data = binary.BigEndian.AppendUint16(response.Data, uint16(length*8))
+			response.Data = append(response.Data, memory[offset:offset+length]...)
+			if length%2 != 0 && i < int(parameters[1])-1 {
+				response.Data = append(response.Data, 0)
+			}
+		}
+
+	case s7FunctionWriteVar:
+		response.Parameters = parameters[:2]
+		data := request.Data
+		for i := 0; i < int(parameters[1]); i++ {
+			spec := parameters[2+12*i:]
+			memory, offset, length := p.item(spec)
+			if spec[3] == s7TransportBit {
+				length = 1
+			}
+			value := data[4 : 4+length]
+			data = data[4+length:]
+			if length%2 != 0 && len(data) > 0 {
+				data = data[1:]
+			}
+
+			switch {
+			case memory == nil:
+				response.Data = append(response.Data, 0x0A)
+			case spec[3] == s7TransportBit:
+				bit := spec[11] & 0x07
+				memory[offset] = memory[offset]&^(1<<bit) | value[0]<<bit
+				response.Data = append(response.Data, s7ReturnSuccess)
+			default:
+				copy(memory[offset:], value)
+				response.Data = append(response.Data, s7ReturnSuccess)
+			}
+		}
+
+	default:
+		response.ErrorClass, response.ErrorCode = 0x84, 0x01
+	}
+
+	if len(appendS7PDU(nil, response)) > p.pduSize {
+		p.t.Errorf("response of %d bytes exceeds the PDU size", len(appendS7PDU(nil, response)))
+	}
+	return response
+}
+
+// item returns the memory, byte offset and length addressed by an item
+// specification
+func (p *s7TestPLC) item(spec []byte) ([]byte, int, int) {
+	length := int(binary.BigEndian.Uint16(spec[4:6]))
+	db := binary.BigEndian.Uint16(spec[6:8])
+	address := int(spec[9])<<16 | int(spec[10])<<8 | int(spec[11])
+
+	memory := p.memory(S7Area(spec[8]), db)
+	if S7Area(spec[8]) != S7AreaDB {
+		memory = p.memory(S7Area(spec[8]), 0)
+	}
+	return memory, address / 8, length
+}
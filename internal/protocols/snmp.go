@@ -0,0 +1,507 @@
+package protocols
+
+import (
+	"context"
+	"encoding/hex"
+	"fmt"
+	"net"
+	"strconv"
+	"sync"
+	"time"
+
+	"go.uber.org/zap"
+)
+
+// System group objects read for device information and discovery
+const (
+	snmpSysDescr    = "1.3.6.1.2.1.1.1.0"
+	snmpSysObjectID = "1.3.6.1.2.1.1.2.0"
+	snmpSysUpTime   = "1.3.6.1.2.1.1.3.0"
+	snmpSysName     = "1.3.6.1.2.1.1.5.0"
+)
+
+// SNMPHandler implements ProtocolHandler for SNMP agents such as
+// switches, routers and UPSs. Tag addresses are numeric OIDs, e.g.
+// "1.3.6.1.2.1.1.3.0"; Walk reads whole subtrees such as interface
+// tables. The config key "version" selects "2c" (the default) or "3".
+// SNMPv2c agents are read with the config key "community", SNMPv3 agents
+// with "user", "auth_protocol" (MD5, SHA or SHA256), "auth_password",
+// "priv_protocol" (DES or AES) and "priv_password".
+type SNMPHandler struct {
+	logger      *zap.Logger
+	config      *SNMPConfig
+	connections sync.Map // map[string]*SNMPConnection
+}
+
+// SNMPConnection is a client of one agent
+type SNMPConnection struct {
+	client    *SNMPClient
+	conn      net.Conn
+	version   string
+	createdAt time.Time
+
+	mutex    sync.RWMutex
+	lastUsed time.Time
+	requests uint64
+	errors   uint64
+}
+
+// SNMPConfig holds SNMP-specific configuration
+type SNMPConfig struct {
+	DefaultTimeout    time.Duration `yaml:"default_timeout"`
+	Retries           int           `yaml:"retries"`
+	MaxRepetitions    int           `yaml:"max_repetitions"`
+	MaxOIDsPerRequest int           `yaml:"max_oids_per_request"`
+	DefaultCommunity  string        `yaml:"default_community"`
+	ProbeTimeout      time.Duration `yaml:"probe_timeout"`
+	MaxProbes         int           `yaml:"max_probes"`
+}
+
+// NewSNMPHandler creates a new SNMP protocol handler
+func NewSNMPHandler(logger *zap.Logger) ProtocolHandler {
+	return &SNMPHandler{
+		logger: logger,
+		config: &SNMPConfig{
+			DefaultTimeout:    3 * time.Second,
+			Retries:           1,
+			MaxRepetitions:    20,
+			MaxOIDsPerRequest: 32,
+			DefaultCommunity:  "public",
+			ProbeTimeout:      500 * time.Millisecond,
+			MaxProbes:         32,
+		},
+	}
+}
+
+// Connect creates a client of an agent; SNMPv3 clients discover the
+// agent's engine
+func (s *SNMPHandler) Connect(device *Device) error {
+	port := device.Port
+	if port == 0 {
+		port = SNMPDefaultPort
+	}
+	version := s.configString(device, "version", "2c")
+	connectionKey := fmt.Sprintf("%s:%d/v%s", device.Address, port, version)
+
+	if _, exists := s.connections.Load(connectionKey); exists {
+		device.ConnectionID = connectionKey
+		return nil
+	}
+
+	conn, err := net.Dial("udp", net.JoinHostPort(device.Address, strconv.Itoa(port)))
+	if err != nil {
+		return fmt.Errorf("failed to open SNMP socket: %w", err)
+	}
+
+	var client *SNMPClient
+	switch version {
+	case "2c", "2":
+		client = NewSNMPClient(conn, s.configString(device, "community", s.config.DefaultCommunity), s.config.DefaultTimeout)
+	case "3":
+		var security *SNMPSecurity
+		security, err = s.security(device)
+		if err == nil {
+			client, err = NewSNMPv3Client(conn, security, s.config.DefaultTimeout)
+		}
+	default:
+		err = fmt.Errorf("unsupported SNMP version %q", version)
+	}
+	if err != nil {
+		conn.Close()
+		return err
+	}
+	client.Retries = s.config.Retries
+	client.MaxRepetitions = s.config.MaxRepetitions
+
+	connection := &SNMPConnection{
+		client:    client,
+		conn:      conn,
+		version:   version,
+		createdAt: time.Now(),
+		lastUsed:  time.Now(),
+	}
+	s.connections.Store(connectionKey, connection)
+	device.ConnectionID = connectionKey
+
+	s.logger.Info("SNMP agent connected",
+		zap.String("device_id", device.ID),
+		zap.String("address", device.Address),
+		zap.Int("port", port),
+		zap.String("version", version),
+	)
+
+	return nil
+}
+
+// Disconnect closes the client of an agent
+func (s *SNMPHandler) Disconnect(device *Device) error {
+	if device.ConnectionID == "" {
+		return nil
+	}
+
+	connInterface, exists := s.connections.LoadAndDelete(device.ConnectionID)
+	if !exists {
+		return nil
+	}
+
+	device.ConnectionID = ""
+	return connInterface.(*SNMPConnection).conn.Close()
+}
+
+// IsConnected checks if the device has a client
+func (s *SNMPHandler) IsConnected(device *Device) bool {
+	_, err := s.getConnection(device)
+	return err == nil
+}
+
+// ReadTag reads one variable
+func (s *SNMPHandler) ReadTag(device *Device, tag *Tag) (interface{}, error) {
+	conn, err := s.getConnection(device)
+	if err != nil {
+		return nil, err
+	}
+	if _, err := ParseOID(tag.Address); err != nil {
+		return nil, err
+	}
+
+	variables, err := conn.client.Get(tag.Address)
+	if err == nil {
+		err = variables[0].Err()
+	}
+	conn.record(err)
+	if err != nil {
+		return nil, err
+	}
+	return snmpTagValue(&variables[0], tag.DataType), nil
+}
+
+// WriteTag is not supported; the handler only polls agents
+func (s *SNMPHandler) WriteTag(device *Device, tag *Tag, value interface{}) error {
+	return fmt.Errorf("SNMP variables are read-only")
+}
+
+// ReadMultipleTags reads variables with as few Get requests as the
+// maximum number of OIDs per request allows. Variables the agent does
+// not have are left out of the results.
+func (s *SNMPHandler) ReadMultipleTags(device *Device, tags []*Tag) (map[string]interface{}, error) {
+	conn, err := s.getConnection(device)
+	if err != nil {
+		return nil, err
+	}
+
+	var readTags []*Tag
+	for _, tag := range tags {
+		if _, err := ParseOID(tag.Address); err == nil {
+			readTags = append(readTags, tag)
+		}
+	}
+
+	results := make(map[string]interface{})
+	for start := 0; start < len(readTags); start += s.config.MaxOIDsPerRequest {
+		end := start + s.config.MaxOIDsPerRequest
+		if end > len(readTags) {
+			end = len(readTags)
+		}
+
+		oids := make([]string, 0, end-start)
+		for _, tag := range readTags[start:end] {
+			oids = append(oids, tag.Address)
+		}
+		variables, err := conn.client.Get(oids...)
+		conn.record(err)
+		if err != nil {
+			return nil, err
+		}
+
+		for i, tag := range readTags[start:end] {
+			if variables[i].Err() == nil {
+				results[tag.ID] = snmpTagValue(&variables[i], tag.DataType)
+			}
+		}
+	}
+	return results, nil
+}
+
+// Walk reads all variables in the subtree of an OID, e.g. the interface
+// table "1.3.6.1.2.1.2.2", and returns them as tags addressed by OID
+func (s *SNMPHandler) Walk(device *Device, root string) ([]*Tag, error) {
+	conn, err := s.getConnection(device)
+	if err != nil {
+		return nil, err
+	}
+
+	variables, err := conn.client.Walk(root)
+	conn.record(err)
+	if err != nil {
+		return nil, err
+	}
+
+	now := time.Now()
+	tags := make([]*Tag, 0, len(variables))
+	for i := range variables {
+		dataType := snmpDataType(variables[i].Type)
+		tags = append(tags, &Tag{
+			ID:        variables[i].OID,
+			Name:      variables[i].OID,
+			Address:   variables[i].OID,
+			DataType:  string(dataType),
+			Value:     snmpTagValue(&variables[i], string(dataType)),
+			Quality:   QualityGood,
+			Timestamp: now,
+		})
+	}
+	return tags, nil
+}
+
+// DiscoverDevices probes each host of a network range for an SNMPv2c
+// agent answering to the default community
+func (s *SNMPHandler) DiscoverDevices(ctx context.Context, networkRange string) ([]*Device, error) {
+	_, network, err := net.ParseCIDR(networkRange)
+	if err != nil || network.IP.To4() == nil {
+		return nil, fmt.Errorf("invalid network range %q", networkRange)
+	}
+	if ones, bits := network.Mask.Size(); bits-ones > 16 {
+		return nil, fmt.Errorf("network range %s is too large to probe", networkRange)
+	}
+
+	var mutex sync.Mutex
+	var wg sync.WaitGroup
+	devices := make([]*Device, 0)
+	probes := make(chan struct{}, s.config.MaxProbes)
+
+	for ip := network.IP.Mask(network.Mask).To4(); network.Contains(ip) && ctx.Err() == nil; ip = s.nextIP(ip) {
+		probes <- struct{}{}
+		wg.Add(1)
+		go func(address string) {
+			defer wg.Done()
+			defer func() { <-probes }()
+
+			if device := s.probe(address); device != nil {
+				mutex.Lock()
+				devices = append(devices, device)
+				mutex.Unlock()
+			}
+		}(ip.String())
+	}
+	wg.Wait()
+
+	return devices, ctx.Err()
+}
+
+// GetDeviceInfo returns information about an agent from its system group
+func (s *SNMPHandler) GetDeviceInfo(device *Device) (*DeviceInfo, error) {
+	info := &DeviceInfo{
+		Vendor:         "Unknown",
+		Model:          "SNMP Agent",
+		Capabilities:   []string{"snmp-get", "snmp-walk", "get-bulk"},
+		MaxConnections: 0, // Connectionless
+		CustomInfo:     make(map[string]string),
+	}
+
+	conn, err := s.getConnection(device)
+	if err != nil {
+		return info, nil
+	}
+	info.CustomInfo["version"] = conn.version
+	if engineID := conn.client.EngineID(); len(engineID) > 0 {
+		info.CustomInfo["engine_id"] = hex.EncodeToString(engineID)
+	}
+
+	variables, err := conn.client.Get(snmpSysDescr, snmpSysObjectID, snmpSysName)
+	conn.record(err)
+	if err != nil {
+		s.logger.Debug("SNMP system group not available",
+			zap.String("device_id", device.ID),
+			zap.Error(err),
+		)
+		return info, nil
+	}
+	for i, key := range []string{"sys_descr", "sys_object_id", "sys_name"} {
+		if variables[i].Err() == nil {
+			info.CustomInfo[key] = fmt.Sprint(snmpTagValue(&variables[i], string(DataTypeString)))
+		}
+	}
+	if descr, exists := info.CustomInfo["sys_descr"]; exists {
+		info.Model = descr
+	}
+	return info, nil
+}
+
+// GetSupportedDataTypes returns the data types of SNMP values
+func (s *SNMPHandler) GetSupportedDataTypes() []string {
+	return []string{
+		string(DataTypeInt64),
+		string(DataTypeUInt32),
+		string(DataTypeUInt64),
+		string(DataTypeString),
+		string(DataTypeBytes),
+	}
+}
+
+// ValidateTagAddress validates an OID
+func (s *SNMPHandler) ValidateTagAddress(address string) error {
+	_, err := ParseOID(address)
+	return err
+}
+
+// Ping reads the agent's uptime
+func (s *SNMPHandler) Ping(device *Device) error {
+	conn, err := s.getConnection(device)
+	if err != nil {
+		return err
+	}
+
+	_, err = conn.client.Get(snmpSysUpTime)
+	conn.record(err)
+	return err
+}
+
+// GetDiagnostics returns diagnostic information for an agent
+func (s *SNMPHandler) GetDiagnostics(device *Device) (*Diagnostics, error) {
+	conn, err := s.getConnection(device)
+	if err != nil {
+		return nil, err
+	}
+
+	conn.mutex.RLock()
+	defer conn.mutex.RUnlock()
+
+	diagnostics := &Diagnostics{
+		IsHealthy:         true,
+		LastCommunication: conn.lastUsed,
+		ErrorCount:        conn.errors,
+		ConnectionUptime:  time.Since(conn.createdAt),
+		ProtocolDiagnostics: map[string]interface{}{
+			"version": conn.version,
+		},
+	}
+	if conn.requests > 0 {
+		diagnostics.SuccessRate = float64(conn.requests-conn.errors) / float64(conn.requests)
+		diagnostics.IsHealthy = diagnostics.SuccessRate > 0.5
+	}
+	return diagnostics, nil
+}
+
+func (s *SNMPHandler) getConnection(device *Device) (*SNMPConnection, error) {
+	if device.ConnectionID == "" {
+		return nil, fmt.Errorf("device not connected")
+	}
+
+	connInterface, exists := s.connections.Load(device.ConnectionID)
+	if !exists {
+		return nil, fmt.Errorf("connection not found")
+	}
+	return connInterface.(*SNMPConnection), nil
+}
+
+func (s *SNMPHandler) configString(device *Device, key, defaultValue string) string {
+	switch value := device.Config[key].(type) {
+	case string:
+		if value != "" {
+			return value
+		}
+	case int:
+		return strconv.Itoa(value)
+	}
+	return defaultValue
+}
+
+// security returns the SNMPv3 user of a device's config
+func (s *SNMPHandler) security(device *Device) (*SNMPSecurity, error) {
+	authProtocol, err := ParseSNMPAuthProtocol(s.configString(device, "auth_protocol", ""))
+	if err != nil {
+		return nil, err
+	}
+	privProtocol, err := ParseSNMPPrivProtocol(s.configString(device, "priv_protocol", ""))
+	if err != nil {
+		return nil, err
+	}
+
+	return &SNMPSecurity{
+		UserName:     s.configString(device, "user", ""),
+		AuthProtocol: authProtocol,
+		AuthPassword: s.configString(device, "auth_password", ""),
+		PrivProtocol: privProtocol,
+		PrivPassword: s.configString(device, "priv_password", ""),
+	}, nil
+}
+
+// probe returns a device if an agent answers at address
+func (s *SNMPHandler) probe(address string) *Device {
+	conn, err := net.Dial("udp", net.JoinHostPort(address, strconv.Itoa(SNMPDefaultPort)))
+	if err != nil {
+		return nil
+	}
+	defer conn.Close()
+
+	client := NewSNMPClient(conn, s.config.DefaultCommunity, s.config.ProbeTimeout)
+	client.Retries = 0
+	variables, err := client.Get(snmpSysDescr, snmpSysName)
+	if err != nil {
+		return nil
+	}
+
+	name := fmt.Sprintf("SNMP agent %s", address)
+	if sysName, ok := variables[1].Value.([]byte); ok && len(sysName) > 0 {
+		name = string(sysName)
+	}
+	return &Device{
+		ID:       fmt.Sprintf("snmp-%s", address),
+		Name:     name,
+		Protocol: "snmp",
+		Address:  address,
+		Port:     SNMPDefaultPort,
+		Config: map[string]interface{}{
+			"version":   "2c",
+			"community": s.config.DefaultCommunity,
+		},
+		LastSeen: time.Now(),
+	}
+}
+
+// nextIP returns the address following ip
+func (s *SNMPHandler) nextIP(ip net.IP) net.IP {
+	next := append(net.IP(nil), ip...)
+	for i := len(next) - 1; i >= 0; i-- {
+		next[i]++
+		if next[i] != 0 {
+			break
+		}
+	}
+	return next
+}
+
+func (c *SNMPConnection) record(err error) {
+	c.mutex.Lock()
+	defer c.mutex.Unlock()
+
+	c.requests++
+	c.lastUsed = time.Now()
+	if err != nil {
+		c.errors++
+	}
+}
+
+// snmpDataType returns the tag data type of an SNMP type
+func snmpDataType(snmpType SNMPType) DataType {
+	switch snmpType {
+	case SNMPInteger:
+		return DataTypeInt64
+	case SNMPCounter32, SNMPGauge32, SNMPTimeTicks:
+		return DataTypeUInt32
+	case SNMPCounter64:
+		return DataTypeUInt64
+	case SNMPOpaque:
+		return DataTypeBytes
+	}
+	return DataTypeString
+}
+
+// snmpTagValue returns a variable's value for a tag; octet strings are
+// returned as strings unless the tag reads bytes
+func snmpTagValue(variable *SNMPVariable, dataType string) interface{} {
+	if value, ok := variable.Value.([]byte); ok && variable.Type == SNMPOctetString && dataType != string(DataTypeBytes) {
+		return string(value)
+	}
+	return variable.Value
+}
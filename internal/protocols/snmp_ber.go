@@ -0,0 +1,450 @@
+package protocols
+
+import (
+	"encoding/binary"
+	"fmt"
+	"net"
+	"strconv"
+	"strings"
+)
+
+// SNMP Encoding
+//
+// SNMP messages are ASN.1 BER: every field is a tag, a length and the
+// content, and sequences nest fields in their content. The reader keeps
+// offsets into the whole message so SNMPv3 authentication parameters can
+// be located and zeroed to check a message's digest.
+
+// SNMPType is the BER tag of a variable's value
+type SNMPType byte
+
+const (
+	SNMPInteger          SNMPType = 0x02
+	SNMPOctetString      SNMPType = 0x04
+	SNMPNull             SNMPType = 0x05
+	SNMPObjectIdentifier SNMPType = 0x06
+	SNMPIPAddress        SNMPType = 0x40
+	SNMPCounter32        SNMPType = 0x41
+	SNMPGauge32          SNMPType = 0x42
+	SNMPTimeTicks        SNMPType = 0x43
+	SNMPOpaque           SNMPType = 0x44
+	SNMPCounter64        SNMPType = 0x46
+	SNMPNoSuchObject     SNMPType = 0x80
+	SNMPNoSuchInstance   SNMPType = 0x81
+	SNMPEndOfMibView     SNMPType = 0x82
+)
+
+// BER tags of structures and PDUs
+const (
+	berSequence = 0x30
+
+	snmpGetRequest     = 0xA0
+	snmpGetNextRequest = 0xA1
+	snmpResponse       = 0xA2
+	snmpSetRequest     = 0xA3
+	snmpGetBulkRequest = 0xA5
+	snmpReport         = 0xA8
+)
+
+// SNMPVariable is a variable binding: an object identifier and its value.
+// Values are int64 for integers, uint32 for counters, gauges and time
+// ticks, uint64 for 64-bit counters, []byte for octet strings and opaque
+// values and strings for object identifiers and IP addresses. The
+// exception types carry no value.
+type SNMPVariable struct {
+	OID   string
+	Type  SNMPType
+	Value interface{}
+}
+
+// Err returns an error if the agent had no value for the variable
+func (v *SNMPVariable) Err() error {
+	switch v.Type {
+	case SNMPNoSuchObject:
+		return fmt.Errorf("no such object %s", v.OID)
+	case SNMPNoSuchInstance:
+		return fmt.Errorf("no such instance %s", v.OID)
+	case SNMPEndOfMibView:
+		return fmt.Errorf("end of MIB view at %s", v.OID)
+	}
+	return nil
+}
+
+// ParseOID parses a dotted object identifier; a leading dot is allowed
+func ParseOID(text string) ([]uint32, error) {
+	parts := strings.Split(strings.TrimPrefix(strings.TrimSpace(text), "."), ".")
+	if len(parts) < 2 {
+		return nil, fmt.Errorf("invalid OID %q", text)
+	}
+
+	oid := make([]uint32, len(parts))
+	for i, part := range parts {
+		arc, err := strconv.ParseUint(part, 10, 32)
+		if err != nil {
+			return nil, fmt.Errorf("invalid OID %q", text)
+		}
+		oid[i] = uint32(arc)
+	}
+	if oid[0] > 2 || (oid[0] < 2 && oid[1] >= 40) {
+		return nil, fmt.Errorf("invalid OID %q", text)
+	}
+	return oid, nil
+}
+
+// formatOID returns an object identifier in dotted form
+func formatOID(oid []uint32) string {
+	parts := make([]string, len(oid))
+	for i, arc := range oid {
+		parts[i] = strconv.FormatUint(uint64(arc), 10)
+	}
+	return strings.Join(parts, ".")
+}
+
+// compareOIDs orders object identifiers lexicographically by arc
+func compareOIDs(a, b []uint32) int {
+	for i := 0; i < len(a) && i < len(b); i++ {
+		if a[i] != b[i] {
+			if a[i] < b[i] {
+				return -1
+			}
+			return 1
+		}
+	}
+	return len(a) - len(b)
+}
+
+// hasOIDPrefix reports whether oid lies in the subtree of root
+func hasOIDPrefix(oid, root []uint32) bool {
+	if len(oid) < len(root) {
+		return false
+	}
+	return compareOIDs(oid[:len(root)], root) == 0
+}
+
+// appendBERLength appends a definite length
+func appendBERLength(dst []byte, length int) []byte {
+	if length < 0x80 {
+		return append(dst, byte(length))
+	}
+
+	var buffer [4]byte
+	binary.BigEndian.PutUint32(buffer[:], uint32(length))
+	n := 4
+	for n > 1 && buffer[4-n] == 0 {
+		n--
+	}
+	dst = append(dst, 0x80|byte(n))
+	return append(dst, buffer[4-n:]...)
+}
+
+// appendBER appends a field
+func appendBER(dst []byte, tag byte, content []byte) []byte {
+	dst = append(dst, tag)
+	dst = appendBERLength(dst, len(content))
+	return append(dst, content...)
+}
+
+// appendBERInteger appends a two's complement integer in as few bytes as
+// keep its sign
+func appendBERInteger(dst []byte, tag byte, value int64) []byte {
+	var buffer [8]byte
+	binary.BigEndian.PutUint64(buffer[:], uint64(value))
+	start := 0
+	for start < 7 {
+		next := buffer[start+1] & 0x80
+		if !(buffer[start] == 0x00 && next == 0) && !(buffer[start] == 0xFF && next != 0) {
+			break
+		}
+		start++
+	}
+	return appendBER(dst, tag, buffer[start:])
+}
+
+// appendBERUnsigned appends an unsigned integer, with a leading zero byte
+// when its top bit is set
+func appendBERUnsigned(dst []byte, tag byte, value uint64) []byte {
+	var buffer [9]byte
+	binary.BigEndian.PutUint64(buffer[1:], value)
+	start := 0
+	for start < 8 && buffer[start] == 0 && buffer[start+1]&0x80 == 0 {
+		start++
+	}
+	return appendBER(dst, tag, buffer[start:])
+}
+
+// appendBEROID appends an object identifier
+func appendBEROID(dst []byte, oid []uint32) []byte {
+	content := make([]byte, 0, len(oid)+4)
+	content = appendBase128(content, oid[0]*40+oid[1])
+	for _, arc := range oid[2:] {
+		content = appendBase128(content, arc)
+	}
+	return appendBER(dst, byte(SNMPObjectIdentifier), content)
+}
+
+func appendBase128(dst []byte, value uint32) []byte {
+	var buffer [5]byte
+	n := len(buffer) - 1
+	buffer[n] = byte(value & 0x7F)
+	for value >>= 7; value > 0; value >>= 7 {
+		n--
+		buffer[n] = byte(value&0x7F) | 0x80
+	}
+	return append(dst, buffer[n:]...)
+}
+
+// appendSNMPVariable appends a variable binding
+func appendSNMPVariable(dst []byte, variable *SNMPVariable) ([]byte, error) {
+	oid, err := ParseOID(variable.OID)
+	if err != nil {
+		return nil, err
+	}
+
+	content := appendBEROID(nil, oid)
+	tag := byte(variable.Type)
+	switch variable.Type {
+	case SNMPNull, SNMPNoSuchObject, SNMPNoSuchInstance, SNMPEndOfMibView:
+		content = appendBER(content, tag, nil)
+	case SNMPInteger:
+		value, err := integerValue(variable.Value, -1<<31, 1<<31-1)
+		if err != nil {
+			return nil, err
+		}
+		content = appendBERInteger(content, tag, value)
+	case SNMPCounter32, SNMPGauge32, SNMPTimeTicks:
+		value, err := integerValue(variable.Value, 0, 1<<32-1)
+		if err != nil {
+			return nil, err
+		}
+		content = appendBERUnsigned(content, tag, uint64(value))
+	case SNMPCounter64:
+		value, err := unsigned64Value(variable.Value)
+		if err != nil {
+			return nil, err
+		}
+		content = appendBERUnsigned(content, tag, value)
+	case SNMPOctetString, SNMPOpaque:
+		switch value := variable.Value.(type) {
+		case []byte:
+			content = appendBER(content, tag, value)
+		case string:
+			content = appendBER(content, tag, []byte(value))
+		default:
+			return nil, fmt.Errorf("cannot encode %T as an octet string", variable.Value)
+		}
+	case SNMPObjectIdentifier:
+		text, _ := variable.Value.(string)
+		value, err := ParseOID(text)
+		if err != nil {
+			return nil, err
+		}
+		content = appendBEROID(content, value)
+	case SNMPIPAddress:
+		text, _ := variable.Value.(string)
+		ip := net.ParseIP(text).To4()
+		if ip == nil {
+			return nil, fmt.Errorf("invalid IP address %q", text)
+		}
+		content = appendBER(content, tag, ip)
+	default:
+		return nil, fmt.Errorf("unsupported SNMP type 0x%02X", tag)
+	}
+	return appendBER(dst, berSequence, content), nil
+}
+
+// berReader reads consecutive fields of data[offset:end]
+type berReader struct {
+	data   []byte
+	offset int
+	end    int
+}
+
+func newBERReader(data []byte) *berReader {
+	return &berReader{data: data, end: len(data)}
+}
+
+// more reports whether fields remain
+func (r *berReader) more() bool {
+	return r.offset < r.end
+}
+
+// next reads a field and returns its tag and the bounds of its content
+func (r *berReader) next() (byte, int, int, error) {
+	if r.end-r.offset < 2 {
+		return 0, 0, 0, fmt.Errorf("truncated BER field")
+	}
+	tag := r.data[r.offset]
+	length := int(r.data[r.offset+1])
+	start := r.offset + 2
+
+	if length&0x80 != 0 {
+		n := length & 0x7F
+		if n == 0 || n > 4 || start+n > r.end {
+			return 0, 0, 0, fmt.Errorf("invalid BER length")
+		}
+		length = 0
+		for _, b := range r.data[start : start+n] {
+			length = length<<8 | int(b)
+		}
+		start += n
+	}
+	if length < 0 || length > r.end-start {
+		return 0, 0, 0, fmt.Errorf("truncated BER field")
+	}
+
+	r.offset = start + length
+	return tag, start, start + length, nil
+}
+
+// expect reads a field with a tag and returns its content bounds
+func (r *berReader) expect(tag byte) (int, int, error) {
+	actual, start, end, err := r.next()
+	if err != nil {
+		return 0, 0, err
+	}
+	if actual != tag {
+		return 0, 0, fmt.Errorf("unexpected BER tag 0x%02X, expected 0x%02X", actual, tag)
+	}
+	return start, end, nil
+}
+
+// sequence reads a constructed field and returns a reader of its content
+func (r *berReader) sequence(tag byte) (*berReader, error) {
+	start, end, err := r.expect(tag)
+	if err != nil {
+		return nil, err
+	}
+	return &berReader{data: r.data, offset: start, end: end}, nil
+}
+
+// integer reads an INTEGER
+func (r *berReader) integer() (int64, error) {
+	start, end, err := r.expect(byte(SNMPInteger))
+	if err != nil {
+		return 0, err
+	}
+	return berInteger(r.data[start:end])
+}
+
+// octetString reads an OCTET STRING
+func (r *berReader) octetString() ([]byte, error) {
+	start, end, err := r.expect(byte(SNMPOctetString))
+	if err != nil {
+		return nil, err
+	}
+	return r.data[start:end], nil
+}
+
+// berInteger decodes a two's complement integer
+func berInteger(content []byte) (int64, error) {
+	if len(content) == 0 || len(content) > 8 {
+		return 0, fmt.Errorf("invalid BER integer of %d bytes", len(content))
+	}
+	value := int64(int8(content[0]))
+	for _, b := range content[1:] {
+		value = value<<8 | int64(b)
+	}
+	return value, nil
+}
+
+// berUnsigned decodes an unsigned integer of up to 64 bits
+func berUnsigned(content []byte) (uint64, error) {
+	if len(content) > 0 && content[0] == 0 {
+		content = content[1:]
+	}
+	if len(content) == 0 || len(content) > 8 {
+		return 0, fmt.Errorf("invalid BER unsigned integer")
+	}
+	var value uint64
+	for _, b := range content {
+		value = value<<8 | uint64(b)
+	}
+	return value, nil
+}
+
+// berOID decodes an object identifier
+func berOID(content []byte) ([]uint32, error) {
+	var oid []uint32
+	var value uint64
+	for i, b := range content {
+		value = value<<7 | uint64(b&0x7F)
+		if value > 0xFFFFFFFF {
+			return nil, fmt.Errorf("invalid OID arc")
+		}
+		if b&0x80 != 0 {
+			if i == len(content)-1 {
+				return nil, fmt.Errorf("truncated OID")
+			}
+			continue
+		}
+		if oid == nil {
+			first := value / 40
+			if first > 2 {
+				first = 2
+			}
+			oid = append(oid, uint32(first), uint32(value-first*40))
+		} else {
+			oid = append(oid, uint32(value))
+		}
+		value = 0
+	}
+	if len(oid) < 2 {
+		return nil, fmt.Errorf("empty OID")
+	}
+	return oid, nil
+}
+
+// readSNMPVariable reads a variable binding
+func (r *berReader) readSNMPVariable() (SNMPVariable, error) {
+	binding, err := r.sequence(berSequence)
+	if err != nil {
+		return SNMPVariable{}, err
+	}
+	start, end, err := binding.expect(byte(SNMPObjectIdentifier))
+	if err != nil {
+		return SNMPVariable{}, err
+	}
+	oid, err := berOID(r.data[start:end])
+	if err != nil {
+		return SNMPVariable{}, err
+	}
+
+	tag, start, end, err := binding.next()
+	if err != nil {
+		return SNMPVariable{}, err
+	}
+	variable := SNMPVariable{OID: formatOID(oid), Type: SNMPType(tag)}
+	content := r.data[start:end]
+
+	switch variable.Type {
+	case SNMPNull, SNMPNoSuchObject, SNMPNoSuchInstance, SNMPEndOfMibView:
+	case SNMPInteger:
+		variable.Value, err = berInteger(content)
+	case SNMPCounter32, SNMPGauge32, SNMPTimeTicks:
+		var value uint64
+		value, err = berUnsigned(content)
+		if err == nil && value > 0xFFFFFFFF {
+			err = fmt.Errorf("32-bit value out of range")
+		}
+		variable.Value = uint32(value)
+	case SNMPCounter64:
+		variable.Value, err = berUnsigned(content)
+	case SNMPOctetString, SNMPOpaque:
+		variable.Value = append([]byte(nil), content...)
+	case SNMPObjectIdentifier:
+		var value []uint32
+		value, err = berOID(content)
+		variable.Value = formatOID(value)
+	case SNMPIPAddress:
+		if len(content) != 4 {
+			err = fmt.Errorf("invalid IP address of %d bytes", len(content))
+		}
+		variable.Value = net.IP(content).String()
+	default:
+		err = fmt.Errorf("unsupported SNMP type 0x%02X", tag)
+	}
+	if err != nil {
+		return SNMPVariable{}, fmt.Errorf("variable %s: %w", variable.OID, err)
+	}
+	return variable, nil
+}
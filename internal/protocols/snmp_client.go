@@ -0,0 +1,398 @@
+package protocols
+
+import (
+	"bytes"
+	"errors"
+	"fmt"
+	"math/rand"
+	"net"
+	"sync"
+	"time"
+)
+
+// SNMP Client
+//
+// SNMPClient polls one agent over UDP with SNMPv2c communities or SNMPv3
+// users. An SNMPv3 client first discovers the agent's engine ID, boots and
+// time with an unauthenticated request the agent answers with a report;
+// a report that the time is out of the window resynchronizes the clock
+// and the request is sent again once.
+
+// SNMPDefaultPort is the UDP port of SNMP agents
+const SNMPDefaultPort = 161
+
+// SNMP error status names, indexed by status
+var snmpErrorNames = []string{
+	"noError", "tooBig", "noSuchName", "badValue", "readOnly", "genErr",
+	"noAccess", "wrongType", "wrongLength", "wrongEncoding", "wrongValue",
+	"noCreation", "inconsistentValue", "resourceUnavailable", "commitFailed",
+	"undoFailed", "authorizationError", "notWritable", "inconsistentName",
+}
+
+// USM statistics reported by SNMPv3 agents
+var snmpUSMReports = map[string]string{
+	"1.3.6.1.6.3.15.1.1.1.0": "unsupported security level",
+	"1.3.6.1.6.3.15.1.1.2.0": "not in time window",
+	"1.3.6.1.6.3.15.1.1.3.0": "unknown user name",
+	"1.3.6.1.6.3.15.1.1.4.0": "unknown engine ID",
+	"1.3.6.1.6.3.15.1.1.5.0": "wrong digest",
+	"1.3.6.1.6.3.15.1.1.6.0": "decryption error",
+}
+
+// SNMPError is an error status returned by an agent
+type SNMPError struct {
+	Status int
+	Index  int
+}
+
+func (e *SNMPError) Error() string {
+	name := fmt.Sprintf("status %d", e.Status)
+	if e.Status >= 0 && e.Status < len(snmpErrorNames) {
+		name = snmpErrorNames[e.Status]
+	}
+	return fmt.Sprintf("SNMP error %s at variable %d", name, e.Index)
+}
+
+// SNMPReportError is an SNMPv3 report from an agent that refused a
+// request
+type SNMPReportError struct {
+	OID string
+}
+
+func (e *SNMPReportError) Error() string {
+	if reason, exists := snmpUSMReports[e.OID]; exists {
+		return fmt.Sprintf("SNMPv3 report: %s", reason)
+	}
+	return fmt.Sprintf("SNMPv3 report %s", e.OID)
+}
+
+// SNMPClient is a client of one SNMP agent
+type SNMPClient struct {
+	Timeout        time.Duration
+	Retries        int
+	MaxRepetitions int
+
+	conn      net.Conn
+	community string
+	security  *SNMPSecurity // nil for SNMPv2c
+
+	mutex     sync.Mutex // serializes requests
+	requestID int32
+	salt      uint64
+
+	// SNMPv3 engine of the agent
+	engineID    []byte
+	engineBoots int32
+	engineTime  int32
+	timeAt      time.Time
+	authKey     []byte
+	privKey     []byte
+}
+
+// NewSNMPClient creates an SNMPv2c client on a UDP connection to an agent
+func NewSNMPClient(conn net.Conn, community string, timeout time.Duration) *SNMPClient {
+	return &SNMPClient{
+		Timeout:        timeout,
+		Retries:        1,
+		MaxRepetitions: 20,
+		conn:           conn,
+		community:      community,
+		requestID:      rand.Int31n(1 << 30),
+	}
+}
+
+// NewSNMPv3Client creates an SNMPv3 client on a UDP connection to an
+// agent and discovers the agent's engine
+func NewSNMPv3Client(conn net.Conn, security *SNMPSecurity, timeout time.Duration) (*SNMPClient, error) {
+	if err := security.Validate(); err != nil {
+		return nil, err
+	}
+
+	c := NewSNMPClient(conn, "", timeout)
+	c.security = security
+	c.salt = rand.Uint64()
+
+	c.mutex.Lock()
+	defer c.mutex.Unlock()
+
+	c.requestID++
+	_, err := c.exchange(&snmpPDU{Type: snmpGetRequest, RequestID: c.requestID})
+	var report *SNMPReportError
+	if err != nil && !errors.As(err, &report) {
+		return nil, fmt.Errorf("SNMPv3 engine discovery failed: %w", err)
+	}
+	if len(c.engineID) == 0 {
+		return nil, fmt.Errorf("SNMPv3 engine discovery failed: no engine ID reported")
+	}
+	return c, nil
+}
+
+// EngineID returns the SNMPv3 engine ID of the agent
+func (c *SNMPClient) EngineID() []byte {
+	c.mutex.Lock()
+	defer c.mutex.Unlock()
+	return append([]byte(nil), c.engineID...)
+}
+
+// Get reads variables
+func (c *SNMPClient) Get(oids ...string) ([]SNMPVariable, error) {
+	return c.request(snmpGetRequest, 0, 0, oids)
+}
+
+// GetNext reads the variables following each of oids
+func (c *SNMPClient) GetNext(oids ...string) ([]SNMPVariable, error) {
+	return c.request(snmpGetNextRequest, 0, 0, oids)
+}
+
+// GetBulk reads the variable following each of the first nonRepeaters
+// oids and up to maxRepetitions variables following each of the others
+func (c *SNMPClient) GetBulk(nonRepeaters, maxRepetitions int, oids ...string) ([]SNMPVariable, error) {
+	return c.request(snmpGetBulkRequest, nonRepeaters, maxRepetitions, oids)
+}
+
+// Walk reads all variables in the subtree of root with GetBulk requests.
+// A root without a subtree is read as a single variable.
+func (c *SNMPClient) Walk(root string) ([]SNMPVariable, error) {
+	rootOID, err := ParseOID(root)
+	if err != nil {
+		return nil, err
+	}
+
+	var variables []SNMPVariable
+	last := rootOID
+	for {
+		batch, err := c.GetBulk(0, c.MaxRepetitions, formatOID(last))
+		if err != nil {
+			return nil, err
+		}
+
+		done := len(batch) == 0
+		for _, variable := range batch {
+			oid, err := ParseOID(variable.OID)
+			if err != nil {
+				return nil, err
+			}
+			if variable.Type == SNMPEndOfMibView || !hasOIDPrefix(oid, rootOID) {
+				done = true
+				break
+			}
+			if compareOIDs(oid, last) <= 0 {
+				return nil, fmt.Errorf("SNMP agent returned %s after %s", variable.OID, formatOID(last))
+			}
+			variables = append(variables, variable)
+			last = oid
+		}
+		if done {
+			break
+		}
+	}
+
+	if len(variables) == 0 {
+		instance, err := c.Get(root)
+		if err != nil {
+			return nil, err
+		}
+		if instance[0].Err() == nil {
+			variables = instance
+		}
+	}
+	return variables, nil
+}
+
+// request sends a PDU with a variable for each OID and returns the
+// response's variables
+func (c *SNMPClient) request(pduType byte, errorStatus, errorIndex int, oids []string) ([]SNMPVariable, error) {
+	pdu := &snmpPDU{Type: pduType, ErrorStatus: errorStatus, ErrorIndex: errorIndex}
+	for _, oid := range oids {
+		if _, err := ParseOID(oid); err != nil {
+			return nil, err
+		}
+		pdu.Variables = append(pdu.Variables, SNMPVariable{OID: oid, Type: SNMPNull})
+	}
+
+	response, err := c.transact(pdu)
+	if err != nil {
+		return nil, err
+	}
+	if response.ErrorStatus != 0 {
+		return nil, &SNMPError{Status: response.ErrorStatus, Index: response.ErrorIndex}
+	}
+	if pduType == snmpGetRequest && len(response.Variables) != len(oids) {
+		return nil, fmt.Errorf("SNMP agent returned %d variables for %d", len(response.Variables), len(oids))
+	}
+	return response.Variables, nil
+}
+
+// transact sends a request and returns its response. SNMPv3 requests the
+// agent reports as out of its time window or for another engine are sent
+// again once with the agent's engine state.
+func (c *SNMPClient) transact(pdu *snmpPDU) (*snmpPDU, error) {
+	c.mutex.Lock()
+	defer c.mutex.Unlock()
+
+	for attempt := 0; ; attempt++ {
+		c.requestID++
+		pdu.RequestID = c.requestID
+
+		response, err := c.exchange(pdu)
+		var report *SNMPReportError
+		if attempt == 0 && errors.As(err, &report) &&
+			(report.OID == "1.3.6.1.6.3.15.1.1.2.0" || report.OID == "1.3.6.1.6.3.15.1.1.4.0") {
+			continue
+		}
+		return response, err
+	}
+}
+
+// exchange sends a request, retrying on timeouts, and returns the response
+// with the request's ID
+func (c *SNMPClient) exchange(pdu *snmpPDU) (*snmpPDU, error) {
+	var request []byte
+	var err error
+	if c.security == nil {
+		request, err = encodeSNMPMessage(&snmpMessage{Version: snmpVersion2c, Community: c.community, PDU: pdu})
+	} else {
+		request, err = c.encodeV3(pdu)
+	}
+	if err != nil {
+		return nil, err
+	}
+
+	buffer := make([]byte, snmpMaxMessageSize)
+	for attempt := 0; ; attempt++ {
+		_ = c.conn.SetDeadline(time.Now().Add(c.Timeout))
+		if _, err := c.conn.Write(request); err != nil {
+			return nil, fmt.Errorf("failed to send SNMP request: %w", err)
+		}
+
+		for {
+			n, err := c.conn.Read(buffer)
+			if err != nil {
+				var netErr net.Error
+				if errors.As(err, &netErr) && netErr.Timeout() && attempt < c.Retries {
+					break
+				}
+				return nil, fmt.Errorf("failed to read SNMP response: %w", err)
+			}
+
+			// Late responses to earlier requests are dropped
+			response, matched, err := c.decode(buffer[:n], pdu.RequestID)
+			if err != nil || matched {
+				return response, err
+			}
+		}
+	}
+}
+
+// decode decodes a response and reports whether it answers the request
+// with an ID
+func (c *SNMPClient) decode(data []byte, requestID int32) (*snmpPDU, bool, error) {
+	m, err := decodeSNMPMessage(data)
+	if err != nil {
+		return nil, false, nil
+	}
+
+	if c.security == nil {
+		if m.PDU.RequestID != requestID {
+			return nil, false, nil
+		}
+		return m.PDU, true, nil
+	}
+
+	if m.MessageID != requestID {
+		return nil, false, nil
+	}
+	authenticated := m.Flags&snmpFlagAuth != 0
+	if authenticated {
+		if !bytes.Equal(m.EngineID, c.engineID) || !snmpVerify(c.security.AuthProtocol, c.authKey, data, m.authOffset) {
+			return nil, true, fmt.Errorf("SNMPv3 response failed authentication")
+		}
+	}
+	if m.Flags&snmpFlagPriv != 0 {
+		if !authenticated {
+			return nil, true, fmt.Errorf("SNMPv3 response encrypted without authentication")
+		}
+		plaintext, err := snmpDecrypt(c.security.PrivProtocol, c.privKey, m.EngineBoots, m.EngineTime, m.PrivParameters, m.EncryptedPDU)
+		if err == nil {
+			err = m.readScopedPDU(newBERReader(plaintext))
+		}
+		if err != nil {
+			return nil, true, fmt.Errorf("failed to decrypt SNMPv3 response: %w", err)
+		}
+	}
+
+	// Reports carry the agent's engine state; other responses only
+	// update it when authenticated
+	if m.PDU.Type == snmpReport || authenticated {
+		c.setEngine(m.EngineID, m.EngineBoots, m.EngineTime)
+	}
+	if m.PDU.Type == snmpReport {
+		if len(m.PDU.Variables) == 0 {
+			return nil, true, &SNMPReportError{}
+		}
+		return nil, true, &SNMPReportError{OID: m.PDU.Variables[0].OID}
+	}
+	return m.PDU, true, nil
+}
+
+// encodeV3 encodes an SNMPv3 request. Until the engine is discovered
+// requests are sent without security.
+func (c *SNMPClient) encodeV3(pdu *snmpPDU) ([]byte, error) {
+	m := &snmpMessage{
+		Version:         snmpVersion3,
+		MessageID:       pdu.RequestID,
+		Flags:           snmpFlagReportable,
+		EngineID:        c.engineID,
+		ContextEngineID: c.engineID,
+		PDU:             pdu,
+	}
+	if len(c.engineID) == 0 {
+		return encodeSNMPMessage(m)
+	}
+
+	m.Flags |= c.security.flags()
+	m.EngineBoots = c.engineBoots
+	m.EngineTime = c.engineTime + int32(time.Since(c.timeAt)/time.Second)
+	m.UserName = c.security.UserName
+	if c.security.AuthProtocol != SNMPAuthNone {
+		m.AuthParameters = make([]byte, c.security.AuthProtocol.macLength())
+	}
+	if c.security.PrivProtocol != SNMPPrivNone {
+		scoped, err := appendScopedPDU(nil, m)
+		if err != nil {
+			return nil, err
+		}
+		c.salt++
+		m.EncryptedPDU, m.PrivParameters, err = snmpEncrypt(c.security.PrivProtocol, c.privKey, m.EngineBoots, m.EngineTime, c.salt, scoped)
+		if err != nil {
+			return nil, err
+		}
+	}
+
+	request, err := encodeSNMPMessage(m)
+	if err != nil {
+		return nil, err
+	}
+	if c.security.AuthProtocol != SNMPAuthNone {
+		snmpAuthenticate(c.security.AuthProtocol, c.authKey, request, m.authOffset)
+	}
+	return request, nil
+}
+
+// setEngine records the agent's engine state, localizing the keys to a
+// newly discovered engine
+func (c *SNMPClient) setEngine(engineID []byte, boots, engineTime int32) {
+	if len(engineID) == 0 {
+		return
+	}
+	if !bytes.Equal(engineID, c.engineID) {
+		c.engineID = append([]byte(nil), engineID...)
+		if c.security.AuthProtocol != SNMPAuthNone {
+			c.authKey = snmpLocalizedKey(c.security.AuthProtocol, c.security.AuthPassword, c.engineID)
+		}
+		if c.security.PrivProtocol != SNMPPrivNone {
+			c.privKey = snmpLocalizedKey(c.security.AuthProtocol, c.security.PrivPassword, c.engineID)
+		}
+	}
+	c.engineBoots, c.engineTime, c.timeAt = boots, engineTime, time.Now()
+}
@@ -0,0 +1,284 @@
+package protocols
+
+import (
+	"fmt"
+)
+
+// SNMP Messages
+//
+// An SNMPv2c message is the version, the community and a PDU. An SNMPv3
+// message is the version, a header with the message ID and flags, the
+// USM security parameters as an encoded octet string and a scoped PDU,
+// which is encrypted into an octet string when the privacy flag is set.
+
+// SNMP message versions
+const (
+	snmpVersion2c = 1
+	snmpVersion3  = 3
+)
+
+// snmpMaxMessageSize is the largest message accepted in responses
+const snmpMaxMessageSize = 65507
+
+// snmpPDU is a request or response PDU. GetBulk requests carry their
+// non-repeaters and max-repetitions in place of the error status and
+// error index.
+type snmpPDU struct {
+	Type        byte
+	RequestID   int32
+	ErrorStatus int
+	ErrorIndex  int
+	Variables   []SNMPVariable
+}
+
+// snmpMessage is an SNMP message; the SNMPv3 fields are unused in SNMPv2c
+// messages and the community in SNMPv3 ones
+type snmpMessage struct {
+	Version   int
+	Community string
+
+	MessageID       int32
+	Flags           byte
+	EngineID        []byte
+	EngineBoots     int32
+	EngineTime      int32
+	UserName        string
+	AuthParameters  []byte
+	PrivParameters  []byte
+	ContextEngineID []byte
+	ContextName     string
+	EncryptedPDU    []byte
+
+	PDU *snmpPDU
+
+	// authOffset locates the authentication parameters in the encoded
+	// message
+	authOffset int
+}
+
+// appendSNMPPDU appends a PDU
+func appendSNMPPDU(dst []byte, pdu *snmpPDU) ([]byte, error) {
+	var bindings []byte
+	for i := range pdu.Variables {
+		var err error
+		bindings, err = appendSNMPVariable(bindings, &pdu.Variables[i])
+		if err != nil {
+			return nil, err
+		}
+	}
+
+	content := appendBERInteger(nil, byte(SNMPInteger), int64(pdu.RequestID))
+	content = appendBERInteger(content, byte(SNMPInteger), int64(pdu.ErrorStatus))
+	content = appendBERInteger(content, byte(SNMPInteger), int64(pdu.ErrorIndex))
+	content = appendBER(content, berSequence, bindings)
+	return appendBER(dst, pdu.Type, content), nil
+}
+
+// readSNMPPDU reads a PDU of any type
+func (r *berReader) readSNMPPDU() (*snmpPDU, error) {
+	tag, start, end, err := r.next()
+	if err != nil {
+		return nil, err
+	}
+	if tag < snmpGetRequest || tag > snmpReport {
+		return nil, fmt.Errorf("unexpected SNMP PDU type 0x%02X", tag)
+	}
+
+	fields := &berReader{data: r.data, offset: start, end: end}
+	pdu := &snmpPDU{Type: tag}
+	requestID, err := fields.integer()
+	if err != nil {
+		return nil, err
+	}
+	errorStatus, err := fields.integer()
+	if err != nil {
+		return nil, err
+	}
+	errorIndex, err := fields.integer()
+	if err != nil {
+		return nil, err
+	}
+	pdu.RequestID, pdu.ErrorStatus, pdu.ErrorIndex = int32(requestID), int(errorStatus), int(errorIndex)
+
+	bindings, err := fields.sequence(berSequence)
+	if err != nil {
+		return nil, err
+	}
+	for bindings.more() {
+		variable, err := bindings.readSNMPVariable()
+		if err != nil {
+			return nil, err
+		}
+		pdu.Variables = append(pdu.Variables, variable)
+	}
+	return pdu, nil
+}
+
+// appendScopedPDU appends the scoped PDU of an SNMPv3 message
+func appendScopedPDU(dst []byte, m *snmpMessage) ([]byte, error) {
+	content := appendBER(nil, byte(SNMPOctetString), m.ContextEngineID)
+	content = appendBER(content, byte(SNMPOctetString), []byte(m.ContextName))
+	content, err := appendSNMPPDU(content, m.PDU)
+	if err != nil {
+		return nil, err
+	}
+	return appendBER(dst, berSequence, content), nil
+}
+
+// readScopedPDU reads the scoped PDU of an SNMPv3 message. Fields after
+// it, such as the padding of a DES decrypted PDU, are ignored.
+func (m *snmpMessage) readScopedPDU(r *berReader) error {
+	scoped, err := r.sequence(berSequence)
+	if err != nil {
+		return err
+	}
+	if m.ContextEngineID, err = scoped.octetString(); err != nil {
+		return err
+	}
+	contextName, err := scoped.octetString()
+	if err != nil {
+		return err
+	}
+	m.ContextName = string(contextName)
+	m.PDU, err = scoped.readSNMPPDU()
+	return err
+}
+
+// encodeSNMPMessage encodes a message and sets its authOffset. SNMPv3
+// messages carry EncryptedPDU in place of the scoped PDU when set.
+func encodeSNMPMessage(m *snmpMessage) ([]byte, error) {
+	if m.Version != snmpVersion3 {
+		content := appendBERInteger(nil, byte(SNMPInteger), int64(m.Version))
+		content = appendBER(content, byte(SNMPOctetString), []byte(m.Community))
+		content, err := appendSNMPPDU(content, m.PDU)
+		if err != nil {
+			return nil, err
+		}
+		return appendBER(nil, berSequence, content), nil
+	}
+
+	var data []byte
+	if m.EncryptedPDU != nil {
+		data = appendBER(nil, byte(SNMPOctetString), m.EncryptedPDU)
+	} else {
+		var err error
+		if data, err = appendScopedPDU(nil, m); err != nil {
+			return nil, err
+		}
+	}
+
+	header := appendBERInteger(nil, byte(SNMPInteger), int64(m.MessageID))
+	header = appendBERInteger(header, byte(SNMPInteger), snmpMaxMessageSize)
+	header = appendBER(header, byte(SNMPOctetString), []byte{m.Flags})
+	header = appendBERInteger(header, byte(SNMPInteger), snmpUSMModel)
+
+	usm := appendBER(nil, byte(SNMPOctetString), m.EngineID)
+	usm = appendBERInteger(usm, byte(SNMPInteger), int64(m.EngineBoots))
+	usm = appendBERInteger(usm, byte(SNMPInteger), int64(m.EngineTime))
+	usm = appendBER(usm, byte(SNMPOctetString), []byte(m.UserName))
+	authOffset := len(usm) + 1 + len(appendBERLength(nil, len(m.AuthParameters)))
+	usm = appendBER(usm, byte(SNMPOctetString), m.AuthParameters)
+	usm = appendBER(usm, byte(SNMPOctetString), m.PrivParameters)
+
+	// Track the offset of the authentication parameters as each layer
+	// adds its tag and length
+	security := appendBER(nil, berSequence, usm)
+	authOffset += len(security) - len(usm)
+	securityString := appendBER(nil, byte(SNMPOctetString), security)
+	authOffset += len(securityString) - len(security)
+
+	content := appendBERInteger(nil, byte(SNMPInteger), snmpVersion3)
+	content = appendBER(content, berSequence, header)
+	authOffset += len(content)
+	content = append(content, securityString...)
+	content = append(content, data...)
+
+	message := appendBER(nil, berSequence, content)
+	m.authOffset = authOffset + len(message) - len(content)
+	return message, nil
+}
+
+// decodeSNMPMessage decodes a message. The PDU of an encrypted SNMPv3
+// message is left in EncryptedPDU.
+func decodeSNMPMessage(data []byte) (*snmpMessage, error) {
+	message, err := newBERReader(data).sequence(berSequence)
+	if err != nil {
+		return nil, err
+	}
+	version, err := message.integer()
+	if err != nil {
+		return nil, err
+	}
+
+	m := &snmpMessage{Version: int(version)}
+	if version != snmpVersion3 {
+		community, err := message.octetString()
+		if err != nil {
+			return nil, err
+		}
+		m.Community = string(community)
+		m.PDU, err = message.readSNMPPDU()
+		return m, err
+	}
+
+	header, err := message.sequence(berSequence)
+	if err != nil {
+		return nil, err
+	}
+	messageID, err := header.integer()
+	if err != nil {
+		return nil, err
+	}
+	if _, err := header.integer(); err != nil {
+		return nil, err
+	}
+	flags, err := header.octetString()
+	if err != nil || len(flags) != 1 {
+		return nil, fmt.Errorf("invalid SNMPv3 message flags")
+	}
+	model, err := header.integer()
+	if err != nil || model != snmpUSMModel {
+		return nil, fmt.Errorf("unsupported SNMPv3 security model")
+	}
+	m.MessageID, m.Flags = int32(messageID), flags[0]
+
+	start, end, err := message.expect(byte(SNMPOctetString))
+	if err != nil {
+		return nil, err
+	}
+	usm, err := (&berReader{data: data, offset: start, end: end}).sequence(berSequence)
+	if err != nil {
+		return nil, err
+	}
+	if m.EngineID, err = usm.octetString(); err != nil {
+		return nil, err
+	}
+	boots, err := usm.integer()
+	if err != nil {
+		return nil, err
+	}
+	engineTime, err := usm.integer()
+	if err != nil {
+		return nil, err
+	}
+	m.EngineBoots, m.EngineTime = int32(boots), int32(engineTime)
+	userName, err := usm.octetString()
+	if err != nil {
+		return nil, err
+	}
+	m.UserName = string(userName)
+	start, end, err = usm.expect(byte(SNMPOctetString))
+	if err != nil {
+		return nil, err
+	}
+	m.AuthParameters, m.authOffset = data[start:end], start
+	if m.PrivParameters, err = usm.octetString(); err != nil {
+		return nil, err
+	}
+
+	if m.Flags&snmpFlagPriv != 0 {
+		m.EncryptedPDU, err = message.octetString()
+		return m, err
+	}
+	return m, m.readScopedPDU(message)
+}
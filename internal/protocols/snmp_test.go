@@ -0,0 +1,491 @@
+package protocols
+
+import (
+	"bytes"
+	"encoding/hex"
+	"net"
+	"sort"
+	"testing"
+	"time"
+
+	"github.com/stretchr/testify/assert"
+	"go.uber.org/zap"
+)
+
+func TestParseOID(t *testing.T) {
+	oid, err := ParseOID(".1.3.6.1.2.1.1.3.0")
+	assert.NoError(t, err)
+	assert.Equal(t, []uint32{1, 3, 6, 1, 2, 1, 1, 3, 0}, oid)
+	assert.Equal(t, "1.3.6.1.2.1.1.3.0", formatOID(oid))
+
+	for _, text := range []string{"", "1", "1.3.x", "3.1", "1.40", "1..3", "1.3.4294967296"} {
+		_, err := ParseOID(text)
+		assert.Error(t, err, text)
+	}
+
+	root, _ := ParseOID("1.3.6.1.2.1.2.2")
+	column, _ := ParseOID("1.3.6.1.2.1.2.2.1.10.1")
+	next, _ := ParseOID("1.3.6.1.2.1.3")
+	assert.True(t, hasOIDPrefix(column, root))
+	assert.False(t, hasOIDPrefix(next, root))
+	assert.Negative(t, compareOIDs(root, column))
+	assert.Positive(t, compareOIDs(next, column))
+}
+
+func TestSNMPVariables_RoundTrip(t *testing.T) {
+	assert.Equal(t, []byte{0x02, 0x01, 0x7F}, appendBERInteger(nil, 0x02, 127))
+	assert.Equal(t, []byte{0x02, 0x02, 0x00, 0x80}, appendBERInteger(nil, 0x02, 128))
+	assert.Equal(t, []byte{0x02, 0x02, 0xFF, 0x7F}, appendBERInteger(nil, 0x02, -129))
+	assert.Equal(t, []byte{0x41, 0x05, 0x00, 0xFF, 0xFF, 0xFF, 0xFF}, appendBERUnsigned(nil, 0x41, 0xFFFFFFFF))
+	assert.Equal(t, []byte{0x04, 0x81, 0xC8}, appendBER(nil, 0x04, make([]byte, 200))[:3])
+
+	variables := []SNMPVariable{
+		{OID: "1.3.6.1.2.1.1.1.0", Type: SNMPOctetString, Value: []byte("Smart-UPS 3000")},
+		{OID: "1.3.6.1.2.1.1.2.0", Type: SNMPObjectIdentifier, Value: "1.3.6.1.4.1.318.1.3.27"},
+		{OID: "1.3.6.1.2.1.1.3.0", Type: SNMPTimeTicks, Value: uint32(4000000000)},
+		{OID: "1.3.6.1.2.1.4.20.1.1.10.0.0.1", Type: SNMPIPAddress, Value: "10.0.0.1"},
+		{OID: "1.3.6.1.2.1.2.2.1.10.1", Type: SNMPCounter32, Value: uint32(123456)},
+		{OID: "1.3.6.1.2.1.31.1.1.1.6.1", Type: SNMPCounter64, Value: uint64(1) << 63},
+		{OID: "1.3.6.1.2.1.33.1.2.5.0", Type: SNMPInteger, Value: int64(-40)},
+		{OID: "1.3.6.1.2.1.33.1.2.6.0", Type: SNMPNoSuchInstance},
+	}
+
+	pdu := &snmpPDU{Type: snmpResponse, RequestID: 1234567, Variables: variables}
+	encoded, err := appendSNMPPDU(nil, pdu)
+	assert.NoError(t, err)
+
+	decoded, err := newBERReader(encoded).readSNMPPDU()
+	assert.NoError(t, err)
+	assert.Equal(t, pdu, decoded)
+	assert.Error(t, decoded.Variables[7].Err())
+
+	_, err = appendSNMPVariable(nil, &SNMPVariable{OID: "1.3.6.1", Type: SNMPInteger, Value: "x"})
+	assert.Error(t, err)
+	_, err = newBERReader(encoded[:len(encoded)-1]).readSNMPPDU()
+	assert.Error(t, err)
+}
+
+func TestSNMPLocalizedKey(t *testing.T) {
+	// RFC 3414 appendix A.3
+	engineID, _ := hex.DecodeString("000000000000000000000002")
+	assert.Equal(t, "526f5eed9fcce26f8964c2930787d82b",
+		hex.EncodeToString(snmpLocalizedKey(SNMPAuthMD5, "maplesyrup", engineID)))
+	assert.Equal(t, "6695febc9288e36282235fc7151f128497b38f3f",
+		hex.EncodeToString(snmpLocalizedKey(SNMPAuthSHA, "maplesyrup", engineID)))
+}
+
+func TestSNMPv3Message_Security(t *testing.T) {
+	engineID := []byte{0x80, 0x00, 0x1F, 0x88, 0x04, 't', 'e', 's', 't'}
+	authKey := snmpLocalizedKey(SNMPAuthSHA, "authpassword", engineID)
+	pdu := &snmpPDU{Type: snmpGetRequest, RequestID: 7, Variables: []SNMPVariable{{OID: snmpSysDescr, Type: SNMPNull}}}
+
+	for _, protocol := range []SNMPPrivProtocol{SNMPPrivDES, SNMPPrivAES} {
+		privKey := snmpLocalizedKey(SNMPAuthSHA, "privpassword", engineID)
+		m := &snmpMessage{
+			Version:         snmpVersion3,
+			MessageID:       7,
+			Flags:           snmpFlagAuth | snmpFlagPriv | snmpFlagReportable,
+			EngineID:        engineID,
+			EngineBoots:     3,
+			EngineTime:      1000,
+			UserName:        "gateway",
+			AuthParameters:  make([]byte, 12),
+			ContextEngineID: engineID,
+			PDU:             pdu,
+		}
+		scoped, err := appendScopedPDU(nil, m)
+		assert.NoError(t, err)
+		m.EncryptedPDU, m.PrivParameters, err = snmpEncrypt(protocol, privKey, 3, 1000, 42, scoped)
+		assert.NoError(t, err)
+
+		data, err := encodeSNMPMessage(m)
+		assert.NoError(t, err)
+		snmpAuthenticate(SNMPAuthSHA, authKey, data, m.authOffset)
+
+		decoded, err := decodeSNMPMessage(data)
+		assert.NoError(t, err)
+		assert.Equal(t, m.authOffset, decoded.authOffset)
+		assert.Equal(t, "gateway", decoded.UserName)
+		assert.True(t, snmpVerify(SNMPAuthSHA, authKey, data, decoded.authOffset))
+
+		plaintext, err := snmpDecrypt(protocol, privKey, 3, 1000, decoded.PrivParameters, decoded.EncryptedPDU)
+		assert.NoError(t, err)
+		assert.NoError(t, decoded.readScopedPDU(newBERReader(plaintext)))
+		assert.Equal(t, pdu, decoded.PDU)
+
+		// Any change breaks the digest
+		data[len(data)-1] ^= 0x01
+		assert.False(t, snmpVerify(SNMPAuthSHA, authKey, data, decoded.authOffset), protocol)
+	}
+}
+
+// snmpTestAgent answers SNMPv2c and SNMPv3 requests for a fixed MIB
+type snmpTestAgent struct {
+	conn      net.PacketConn
+	community string
+	user      *SNMPSecurity
+	engineID  []byte
+	boots     int32
+	authKey   []byte
+	privKey   []byte
+	variables map[string]SNMPVariable
+	oids      [][]uint32
+}
+
+func newSNMPTestAgent(t *testing.T, user *SNMPSecurity) *snmpTestAgent {
+	conn, err := net.ListenPacket("udp", "127.0.0.1:0")
+	if err != nil {
+		t.Fatal(err)
+	}
+
+	agent := &snmpTestAgent{
+		conn:      conn,
+		community: "plant",
+		user:      user,
+		engineID:  []byte{0x80, 0x00, 0x1F, 0x88, 0x04, 'u', 'p', 's'},
+		boots:     1,
+		variables: make(map[string]SNMPVariable),
+	}
+	if user != nil {
+		agent.authKey = snmpLocalizedKey(user.AuthProtocol, user.AuthPassword, agent.engineID)
+	}
+	if user != nil && user.PrivProtocol != SNMPPrivNone {
+		agent.privKey = snmpLocalizedKey(user.AuthProtocol, user.PrivPassword, agent.engineID)
+	}
+
+	for _, variable := range []SNMPVariable{
+		{OID: snmpSysDescr, Type: SNMPOctetString, Value: []byte("Smart-UPS 3000")},
+		{OID: snmpSysObjectID, Type: SNMPObjectIdentifier, Value: "1.3.6.1.4.1.318.1.3.27"},
+		{OID: snmpSysUpTime, Type: SNMPTimeTicks, Value: uint32(360000)},
+		{OID: snmpSysName, Type: SNMPOctetString, Value: []byte("ups-1")},
+		{OID: "1.3.6.1.2.1.2.2.1.10.1", Type: SNMPCounter32, Value: uint32(1000)},
+		{OID: "1.3.6.1.2.1.2.2.1.10.2", Type: SNMPCounter32, Value: uint32(2000)},
+		{OID: "1.3.6.1.2.1.2.2.1.16.1", Type: SNMPCounter32, Value: uint32(3000)},
+		{OID: "1.3.6.1.2.1.2.2.1.16.2", Type: SNMPCounter32, Value: uint32(4000)},
+		{OID: "1.3.6.1.2.1.31.1.1.1.6.1", Type: SNMPCounter64, Value: uint64(1) << 40},
+		{OID: "1.3.6.1.2.1.33.1.2.4.0", Type: SNMPInteger, Value: int64(95)},
+	} {
+		oid, _ := ParseOID(variable.OID)
+		agent.variables[variable.OID] = variable
+		agent.oids = append(agent.oids, oid)
+	}
+	sort.Slice(agent.oids, func(i, j int) bool { return compareOIDs(agent.oids[i], agent.oids[j]) < 0 })
+
+	go agent.serve()
+	t.Cleanup(func() { conn.Close() })
+	return agent
+}
+
+func (a *snmpTestAgent) port() int {
+	return a.conn.LocalAddr().(*net.UDPAddr).Port
+}
+
+func (a *snmpTestAgent) dial(t *testing.T) net.Conn {
+	conn, err := net.Dial("udp", a.conn.LocalAddr().String())
+	if err != nil {
+		t.Fatal(err)
+	}
+	t.Cleanup(func() { conn.Close() })
+	return conn
+}
+
+func (a *snmpTestAgent) serve() {
+	buffer := make([]byte, snmpMaxMessageSize)
+	for {
+		n, address, err := a.conn.ReadFrom(buffer)
+		if err != nil {
+			return
+		}
+		if response := a.handle(buffer[:n]); response != nil {
+			a.conn.WriteTo(response, address)
+		}
+	}
+}
+
+func (a *snmpTestAgent) handle(data []byte) []byte {
+	m, err := decodeSNMPMessage(data)
+	if err != nil {
+		return nil
+	}
+
+	if m.Version == snmpVersion2c {
+		if m.Community != a.community {
+			return nil
+		}
+		m.PDU = a.process(m.PDU)
+		response, _ := encodeSNMPMessage(m)
+		return response
+	}
+
+	if a.user == nil {
+		return nil
+	}
+	if !bytes.Equal(m.EngineID, a.engineID) {
+		return a.report(m, "1.3.6.1.6.3.15.1.1.4.0", 0)
+	}
+	if m.UserName != a.user.UserName {
+		return a.report(m, "1.3.6.1.6.3.15.1.1.3.0", 0)
+	}
+	if !snmpVerify(a.user.AuthProtocol, a.authKey, data, m.authOffset) {
+		return a.report(m, "1.3.6.1.6.3.15.1.1.5.0", 0)
+	}
+	if m.EngineBoots != a.boots {
+		return a.report(m, "1.3.6.1.6.3.15.1.1.2.0", snmpFlagAuth)
+	}
+	if m.Flags&snmpFlagPriv != 0 {
+		plaintext, err := snmpDecrypt(a.user.PrivProtocol, a.privKey, m.EngineBoots, m.EngineTime, m.PrivParameters, m.EncryptedPDU)
+		if err != nil || m.readScopedPDU(newBERReader(plaintext)) != nil {
+			return a.report(m, "1.3.6.1.6.3.15.1.1.6.0", 0)
+		}
+	}
+	return a.respond(m, a.process(m.PDU), m.Flags&^snmpFlagReportable)
+}
+
+// report answers with a USM report
+func (a *snmpTestAgent) report(m *snmpMessage, oid string, flags byte) []byte {
+	pdu := &snmpPDU{
+		Type:      snmpReport,
+		RequestID: m.MessageID,
+		Variables: []SNMPVariable{{OID: oid, Type: SNMPCounter32, Value: uint32(1)}},
+	}
+	return a.respond(m, pdu, flags)
+}
+
+func (a *snmpTestAgent) respond(request *snmpMessage, pdu *snmpPDU, flags byte) []byte {
+	m := &snmpMessage{
+		Version:         snmpVersion3,
+		MessageID:       request.MessageID,
+		Flags:           flags,
+		EngineID:        a.engineID,
+		EngineBoots:     a.boots,
+		EngineTime:      100,
+		UserName:        request.UserName,
+		ContextEngineID: a.engineID,
+		PDU:             pdu,
+	}
+	if flags&snmpFlagAuth != 0 {
+		m.AuthParameters = make([]byte, a.user.AuthProtocol.macLength())
+	}
+	if flags&snmpFlagPriv != 0 {
+		scoped, _ := appendScopedPDU(nil, m)
+		m.EncryptedPDU, m.PrivParameters, _ = snmpEncrypt(a.user.PrivProtocol, a.privKey, m.EngineBoots, m.EngineTime, 99, scoped)
+	}
+
+	response, _ := encodeSNMPMessage(m)
+	if flags&snmpFlagAuth != 0 {
+		snmpAuthenticate(a.user.AuthProtocol, a.authKey, response, m.authOffset)
+	}
+	return response
+}
+
+// process answers Get, GetNext and GetBulk requests
+func (a *snmpTestAgent) process(request *snmpPDU) *snmpPDU {
+	response := &snmpPDU{Type: snmpResponse, RequestID: request.RequestID}
+	for i, variable := range request.Variables {
+		switch request.Type {
+		case snmpGetRequest:
+			value, exists := a.variables[variable.OID]
+			if !exists {
+				value = SNMPVariable{OID: variable.OID, Type: SNMPNoSuchObject}
+			}
+			response.Variables = append(response.Variables, value)
+		case snmpGetNextRequest:
+			response.Variables = append(response.Variables, a.next(variable.OID))
+		case snmpGetBulkRequest:
+			repetitions := request.ErrorIndex
+			if i < request.ErrorStatus {
+				repetitions = 1
+			}
+			oid := variable.OID
+			for r := 0; r < repetitions; r++ {
+				next := a.next(oid)
+				response.Variables = append(response.Variables, next)
+				if next.Type == SNMPEndOfMibView {
+					break
+				}
+				oid = next.OID
+			}
+		}
+	}
+	return response
+}
+
+func (a *snmpTestAgent) next(text string) SNMPVariable {
+	oid, _ := ParseOID(text)
+	for _, candidate := range a.oids {
+		if compareOIDs(candidate, oid) > 0 {
+			return a.variables[formatOID(candidate)]
+		}
+	}
+	return SNMPVariable{OID: text, Type: SNMPEndOfMibView}
+}
+
+func TestSNMPClient_v2c(t *testing.T) {
+	agent := newSNMPTestAgent(t, nil)
+	client := NewSNMPClient(agent.dial(t), "plant", time.Second)
+
+	variables, err := client.Get(snmpSysDescr, snmpSysUpTime, "1.3.6.1.2.1.99.0")
+	assert.NoError(t, err)
+	if assert.Len(t, variables, 3) {
+		assert.Equal(t, []byte("Smart-UPS 3000"), variables[0].Value)
+		assert.Equal(t, uint32(360000), variables[1].Value)
+		assert.Equal(t, SNMPNoSuchObject, variables[2].Type)
+		assert.Error(t, variables[2].Err())
+	}
+
+	variables, err = client.GetNext("1.3.6.1.2.1.2.2")
+	assert.NoError(t, err)
+	assert.Equal(t, "1.3.6.1.2.1.2.2.1.10.1", variables[0].OID)
+
+	// Walks take several GetBulk requests with small repetitions
+	client.MaxRepetitions = 3
+	variables, err = client.Walk("1.3.6.1.2.1.2.2")
+	assert.NoError(t, err)
+	oids := make([]string, len(variables))
+	for i, variable := range variables {
+		oids[i] = variable.OID
+	}
+	assert.Equal(t, []string{
+		"1.3.6.1.2.1.2.2.1.10.1",
+		"1.3.6.1.2.1.2.2.1.10.2",
+		"1.3.6.1.2.1.2.2.1.16.1",
+		"1.3.6.1.2.1.2.2.1.16.2",
+	}, oids)
+
+	// A scalar has no subtree and is read itself
+	variables, err = client.Walk("1.3.6.1.2.1.33.1.2.4.0")
+	assert.NoError(t, err)
+	if assert.Len(t, variables, 1) {
+		assert.Equal(t, int64(95), variables[0].Value)
+	}
+
+	// The end of the MIB ends a walk
+	variables, err = client.Walk("1.3.6.1.2.1.33")
+	assert.NoError(t, err)
+	assert.Len(t, variables, 1)
+
+	// Agents ignore unknown communities
+	client = NewSNMPClient(agent.dial(t), "public", 100*time.Millisecond)
+	_, err = client.Get(snmpSysDescr)
+	assert.Error(t, err)
+}
+
+func TestSNMPClient_v3(t *testing.T) {
+	users := []*SNMPSecurity{
+		{UserName: "gateway", AuthProtocol: SNMPAuthSHA, AuthPassword: "authpassword", PrivProtocol: SNMPPrivAES, PrivPassword: "privpassword"},
+		{UserName: "gateway", AuthProtocol: SNMPAuthMD5, AuthPassword: "authpassword", PrivProtocol: SNMPPrivDES, PrivPassword: "privpassword"},
+		{UserName: "gateway", AuthProtocol: SNMPAuthSHA256, AuthPassword: "authpassword"},
+	}
+
+	for _, user := range users {
+		agent := newSNMPTestAgent(t, user)
+		client, err := NewSNMPv3Client(agent.dial(t), user, time.Second)
+		if !assert.NoError(t, err, user.AuthProtocol) {
+			continue
+		}
+		assert.Equal(t, agent.engineID, client.EngineID())
+
+		variables, err := client.Get(snmpSysName, "1.3.6.1.2.1.31.1.1.1.6.1")
+		assert.NoError(t, err, user.AuthProtocol)
+		if assert.Len(t, variables, 2) {
+			assert.Equal(t, []byte("ups-1"), variables[0].Value)
+			assert.Equal(t, uint64(1)<<40, variables[1].Value)
+		}
+
+		variables, err = client.Walk("1.3.6.1.2.1.2.2.1.16")
+		assert.NoError(t, err)
+		assert.Len(t, variables, 2)
+
+		// After the agent restarts the client resynchronizes its clock
+		agent.boots++
+		_, err = client.Get(snmpSysUpTime)
+		assert.NoError(t, err, user.AuthProtocol)
+	}
+
+	agent := newSNMPTestAgent(t, users[0])
+	wrongPassword := *users[0]
+	wrongPassword.AuthPassword = "wrongpassword"
+	client, err := NewSNMPv3Client(agent.dial(t), &wrongPassword, time.Second)
+	assert.NoError(t, err)
+	_, err = client.Get(snmpSysName)
+	assert.EqualError(t, err, "SNMPv3 report: wrong digest")
+
+	_, err = NewSNMPv3Client(agent.dial(t), &SNMPSecurity{UserName: "gateway", AuthProtocol: SNMPAuthSHA, AuthPassword: "short"}, time.Second)
+	assert.Error(t, err)
+}
+
+func TestSNMPHandler(t *testing.T) {
+	user := &SNMPSecurity{UserName: "gateway", AuthProtocol: SNMPAuthSHA, AuthPassword: "authpassword", PrivProtocol: SNMPPrivAES, PrivPassword: "privpassword"}
+	agent := newSNMPTestAgent(t, user)
+	handler := NewSNMPHandler(zap.NewNop()).(*SNMPHandler)
+
+	device := &Device{
+		ID:       "ups-1",
+		Protocol: "snmp",
+		Address:  "127.0.0.1",
+		Port:     agent.port(),
+		Config: map[string]interface{}{
+			"version":       3,
+			"user":          "gateway",
+			"auth_protocol": "sha",
+			"auth_password": "authpassword",
+			"priv_protocol": "aes-128",
+			"priv_password": "privpassword",
+		},
+	}
+	assert.NoError(t, handler.Connect(device))
+	defer handler.Disconnect(device)
+	assert.True(t, handler.IsConnected(device))
+
+	value, err := handler.ReadTag(device, &Tag{Address: "1.3.6.1.2.1.33.1.2.4.0"})
+	assert.NoError(t, err)
+	assert.Equal(t, int64(95), value)
+
+	value, err = handler.ReadTag(device, &Tag{Address: snmpSysDescr})
+	assert.NoError(t, err)
+	assert.Equal(t, "Smart-UPS 3000", value)
+
+	_, err = handler.ReadTag(device, &Tag{Address: "1.3.6.1.2.1.99.0"})
+	assert.Error(t, err)
+
+	handler.config.MaxOIDsPerRequest = 2
+	results, err := handler.ReadMultipleTags(device, []*Tag{
+		{ID: "name", Address: snmpSysName},
+		{ID: "uptime", Address: snmpSysUpTime},
+		{ID: "in", Address: "1.3.6.1.2.1.2.2.1.10.1"},
+		{ID: "missing", Address: "1.3.6.1.2.1.99.0"},
+		{ID: "invalid", Address: "sysName"},
+	})
+	assert.NoError(t, err)
+	assert.Equal(t, map[string]interface{}{"name": "ups-1", "uptime": uint32(360000), "in": uint32(1000)}, results)
+
+	tags, err := handler.Walk(device, "1.3.6.1.2.1.2.2.1.10")
+	assert.NoError(t, err)
+	if assert.Len(t, tags, 2) {
+		assert.Equal(t, "1.3.6.1.2.1.2.2.1.10.2", tags[1].Address)
+		assert.Equal(t, uint32(2000), tags[1].Value)
+		assert.Equal(t, string(DataTypeUInt32), tags[1].DataType)
+	}
+
+	info, err := handler.GetDeviceInfo(device)
+	assert.NoError(t, err)
+	assert.Equal(t, "Smart-UPS 3000", info.Model)
+	assert.Equal(t, "1.3.6.1.4.1.318.1.3.27", info.CustomInfo["sys_object_id"])
+	assert.Equal(t, hex.EncodeToString(agent.engineID), info.CustomInfo["engine_id"])
+
+	assert.NoError(t, handler.Ping(device))
+	assert.Error(t, handler.WriteTag(device, &Tag{Address: snmpSysName, Writable: true}, "ups-2"))
+	assert.NoError(t, handler.ValidateTagAddress("1.3.6.1.2.1.1.5.0"))
+	assert.Error(t, handler.ValidateTagAddress("sysName.0"))
+
+	diagnostics, err := handler.GetDiagnostics(device)
+	assert.NoError(t, err)
+	assert.Equal(t, uint64(1), diagnostics.ErrorCount)
+	assert.Equal(t, "3", diagnostics.ProtocolDiagnostics.(map[string]interface{})["version"])
+
+	// SNMPv3 requires an authentication password for the protocol
+	delete(device.Config, "auth_password")
+	device.ConnectionID = ""
+	device.Port++
+	assert.Error(t, handler.Connect(device))
+}
@@ -0,0 +1,295 @@
+package protocols
+
+import (
+	"crypto/aes"
+	"crypto/cipher"
+	"crypto/des"
+	"crypto/hmac"
+	"crypto/md5"
+	"crypto/sha1"
+	"crypto/sha256"
+	"encoding/binary"
+	"fmt"
+	"hash"
+	"strings"
+)
+
+// SNMPv3 User-based Security Model
+//
+// Keys are derived from passwords (RFC 3414): the password is repeated to
+// 1 MB and hashed, and the result localized to an agent by hashing it
+// between two copies of the agent's engine ID. Authenticated messages
+// carry a truncated HMAC of the whole message, computed with the
+// authentication parameters zeroed. Encrypted messages carry the scoped
+// PDU encrypted with DES-CBC (RFC 3414) or AES-128-CFB (RFC 3826) under
+// the localized privacy key, the salt going in the privacy parameters.
+
+// SNMPAuthProtocol is an SNMPv3 authentication protocol
+type SNMPAuthProtocol string
+
+const (
+	SNMPAuthNone   SNMPAuthProtocol = ""
+	SNMPAuthMD5    SNMPAuthProtocol = "MD5"
+	SNMPAuthSHA    SNMPAuthProtocol = "SHA"
+	SNMPAuthSHA256 SNMPAuthProtocol = "SHA256"
+)
+
+// SNMPPrivProtocol is an SNMPv3 privacy protocol
+type SNMPPrivProtocol string
+
+const (
+	SNMPPrivNone SNMPPrivProtocol = ""
+	SNMPPrivDES  SNMPPrivProtocol = "DES"
+	SNMPPrivAES  SNMPPrivProtocol = "AES"
+)
+
+// SNMPv3 message flags
+const (
+	snmpFlagAuth       = 0x01
+	snmpFlagPriv       = 0x02
+	snmpFlagReportable = 0x04
+)
+
+// snmpUSMModel is the security model number of the USM
+const snmpUSMModel = 3
+
+// snmpPasswordLength is the length passwords are expanded to
+const snmpPasswordLength = 1048576
+
+// SNMPSecurity holds the USM settings of an SNMPv3 user
+type SNMPSecurity struct {
+	UserName     string
+	AuthProtocol SNMPAuthProtocol
+	AuthPassword string
+	PrivProtocol SNMPPrivProtocol
+	PrivPassword string
+}
+
+// Validate checks the protocols and that passwords are long enough
+func (s *SNMPSecurity) Validate() error {
+	if s.UserName == "" {
+		return fmt.Errorf("SNMPv3 requires a user name")
+	}
+	switch s.AuthProtocol {
+	case SNMPAuthNone:
+		if s.PrivProtocol != SNMPPrivNone {
+			return fmt.Errorf("SNMPv3 privacy requires authentication")
+		}
+		return nil
+	case SNMPAuthMD5, SNMPAuthSHA, SNMPAuthSHA256:
+	default:
+		return fmt.Errorf("unsupported SNMPv3 authentication protocol %q", s.AuthProtocol)
+	}
+	if len(s.AuthPassword) < 8 {
+		return fmt.Errorf("SNMPv3 authentication password must have at least 8 characters")
+	}
+
+	switch s.PrivProtocol {
+	case SNMPPrivNone:
+		return nil
+	case SNMPPrivDES, SNMPPrivAES:
+	default:
+		return fmt.Errorf("unsupported SNMPv3 privacy protocol %q", s.PrivProtocol)
+	}
+	if len(s.PrivPassword) < 8 {
+		return fmt.Errorf("SNMPv3 privacy password must have at least 8 characters")
+	}
+	return nil
+}
+
+// flags returns the message flags of the user's security level
+func (s *SNMPSecurity) flags() byte {
+	var flags byte
+	if s.AuthProtocol != SNMPAuthNone {
+		flags |= snmpFlagAuth
+	}
+	if s.PrivProtocol != SNMPPrivNone {
+		flags |= snmpFlagPriv
+	}
+	return flags
+}
+
+// ParseSNMPAuthProtocol parses an authentication protocol name
+func ParseSNMPAuthProtocol(name string) (SNMPAuthProtocol, error) {
+	switch protocol := SNMPAuthProtocol(strings.ToUpper(strings.ReplaceAll(name, "-", ""))); protocol {
+	case SNMPAuthNone, SNMPAuthMD5, SNMPAuthSHA, SNMPAuthSHA256:
+		return protocol, nil
+	case "SHA1":
+		return SNMPAuthSHA, nil
+	}
+	return "", fmt.Errorf("unsupported SNMPv3 authentication protocol %q", name)
+}
+
+// ParseSNMPPrivProtocol parses a privacy protocol name
+func ParseSNMPPrivProtocol(name string) (SNMPPrivProtocol, error) {
+	switch protocol := SNMPPrivProtocol(strings.ToUpper(strings.ReplaceAll(name, "-", ""))); protocol {
+	case SNMPPrivNone, SNMPPrivDES, SNMPPrivAES:
+		return protocol, nil
+	case "AES128":
+		return SNMPPrivAES, nil
+	}
+	return "", fmt.Errorf("unsupported SNMPv3 privacy protocol %q", name)
+}
+
+func (p SNMPAuthProtocol) hash() hash.Hash {
+	switch p {
+	case SNMPAuthMD5:
+		return md5.New()
+	case SNMPAuthSHA256:
+		return sha256.New()
+	}
+	return sha1.New()
+}
+
+// macLength returns the length of the truncated HMAC
+func (p SNMPAuthProtocol) macLength() int {
+	if p == SNMPAuthSHA256 {
+		return 24
+	}
+	return 12
+}
+
+// snmpLocalizedKey derives a password's key localized to an engine
+func snmpLocalizedKey(protocol SNMPAuthProtocol, password string, engineID []byte) []byte {
+	h := protocol.hash()
+	buffer := make([]byte, 64)
+	for written := 0; written < snmpPasswordLength; written += len(buffer) {
+		for i := range buffer {
+			buffer[i] = password[(written+i)%len(password)]
+		}
+		h.Write(buffer)
+	}
+	key := h.Sum(nil)
+
+	h.Reset()
+	h.Write(key)
+	h.Write(engineID)
+	h.Write(key)
+	return h.Sum(nil)
+}
+
+// snmpAuthenticate writes the HMAC of message into its authentication
+// parameters at offset, which must hold zeros
+func snmpAuthenticate(protocol SNMPAuthProtocol, key, message []byte, offset int) {
+	mac := hmac.New(protocol.hash, key)
+	mac.Write(message)
+	copy(message[offset:offset+protocol.macLength()], mac.Sum(nil))
+}
+
+// snmpVerify checks the HMAC in the authentication parameters at offset
+func snmpVerify(protocol SNMPAuthProtocol, key, message []byte, offset int) bool {
+	length := protocol.macLength()
+	if offset < 0 || offset+length > len(message) {
+		return false
+	}
+	received := append([]byte(nil), message[offset:offset+length]...)
+
+	zeroed := append([]byte(nil), message...)
+	for i := offset; i < offset+length; i++ {
+		zeroed[i] = 0
+	}
+	mac := hmac.New(protocol.hash, key)
+	mac.Write(zeroed)
+	return hmac.Equal(received, mac.Sum(nil)[:length])
+}
+
+// snmpEncrypt encrypts a scoped PDU and returns it with the privacy
+// parameters
+func snmpEncrypt(protocol SNMPPrivProtocol, key []byte, boots, engineTime int32, salt uint64, plaintext []byte) ([]byte, []byte, error) {
+	switch protocol {
+	case SNMPPrivDES:
+		if len(key) < 16 {
+			return nil, nil, fmt.Errorf("DES privacy key too short")
+		}
+		block, err := des.NewCipher(key[:8])
+		if err != nil {
+			return nil, nil, err
+		}
+		parameters := binary.BigEndian.AppendUint32(nil, uint32(boots))
+		parameters = binary.BigEndian.AppendUint32(parameters, uint32(salt))
+		iv := make([]byte, des.BlockSize)
+		for i := range iv {
+			iv[i] = key[8+i] ^ parameters[i]
+		}
+
+		padded := append([]byte(nil), plaintext...)
+		if rest := len(padded) % des.BlockSize; rest != 0 {
+			padded = append(padded, make([]byte, des.BlockSize-rest)...)
+		}
+		cipher.NewCBCEncrypter(block, iv).CryptBlocks(padded, padded)
+		return padded, parameters, nil
+	case SNMPPrivAES:
+		block, err := aes.NewCipher(key[:16])
+		if err != nil {
+			return nil, nil, err
+		}
+		parameters := binary.BigEndian.AppendUint64(nil, salt)
+		iv := binary.BigEndian.AppendUint32(nil, uint32(boots))
+		iv = binary.BigEndian.AppendUint32(iv, uint32(engineTime))
+		iv = append(iv, parameters...)
+
+		return snmpCFB(block, iv, plaintext, false), parameters, nil
+	}
+	return nil, nil, fmt.Errorf("unsupported SNMPv3 privacy protocol %q", protocol)
+}
+
+// snmpDecrypt decrypts a scoped PDU. DES plaintexts keep their padding,
+// which follows the scoped PDU's BER field.
+func snmpDecrypt(protocol SNMPPrivProtocol, key []byte, boots, engineTime int32, parameters, ciphertext []byte) ([]byte, error) {
+	if len(parameters) != 8 {
+		return nil, fmt.Errorf("invalid privacy parameters of %d bytes", len(parameters))
+	}
+
+	switch protocol {
+	case SNMPPrivDES:
+		if len(key) < 16 || len(ciphertext)%des.BlockSize != 0 {
+			return nil, fmt.Errorf("invalid DES encrypted PDU")
+		}
+		block, err := des.NewCipher(key[:8])
+		if err != nil {
+			return nil, err
+		}
+		iv := make([]byte, des.BlockSize)
+		for i := range iv {
+			iv[i] = key[8+i] ^ parameters[i]
+		}
+		plaintext := make([]byte, len(ciphertext))
+		cipher.NewCBCDecrypter(block, iv).CryptBlocks(plaintext, ciphertext)
+		return plaintext, nil
+	case SNMPPrivAES:
+		block, err := aes.NewCipher(key[:16])
+		if err != nil {
+			return nil, err
+		}
+		iv := binary.BigEndian.AppendUint32(nil, uint32(boots))
+		iv = binary.BigEndian.AppendUint32(iv, uint32(engineTime))
+		iv = append(iv, parameters...)
+
+		return snmpCFB(block, iv, ciphertext, true), nil
+	}
+	return nil, fmt.Errorf("unsupported SNMPv3 privacy protocol %q", protocol)
+}
+
+// snmpCFB runs AES in 128-bit cipher feedback mode; each block's key
+// stream is the encryption of the previous ciphertext block
+func snmpCFB(block cipher.Block, iv, input []byte, decrypt bool) []byte {
+	output := make([]byte, len(input))
+	feedback := append([]byte(nil), iv...)
+	stream := make([]byte, block.BlockSize())
+	for start := 0; start < len(input); start += len(stream) {
+		block.Encrypt(stream, feedback)
+		end := start + len(stream)
+		if end > len(input) {
+			end = len(input)
+		}
+		for i := start; i < end; i++ {
+			output[i] = input[i] ^ stream[i-start]
+		}
+		if decrypt {
+			copy(feedback, input[start:end])
+		} else {
+			copy(feedback, output[start:end])
+		}
+	}
+	return output
+}
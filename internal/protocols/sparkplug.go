@@ -0,0 +1,529 @@
+package protocols
+
+import (
+	"encoding/binary"
+	"fmt"
+	"math"
+	"strings"
+)
+
+// Sparkplug B Payload and Topic Codec
+//
+// Sparkplug B defines an MQTT topic namespace and a protobuf payload for
+// industrial telemetry. The payload is encoded by hand rather than through
+// generated code. Scalar metric values are supported; DataSet, Template and
+// PropertySet fields are skipped when decoding and never produced.
+
+// SparkplugNamespace is the first topic level of every Sparkplug B topic
+const SparkplugNamespace = "spBv1.0"
+
+// SparkplugMessageType is the message type topic level
+type SparkplugMessageType string
+
+const (
+	SparkplugNBirth SparkplugMessageType = "NBIRTH"
+	SparkplugNDeath SparkplugMessageType = "NDEATH"
+	SparkplugDBirth SparkplugMessageType = "DBIRTH"
+	SparkplugDDeath SparkplugMessageType = "DDEATH"
+	SparkplugNData  SparkplugMessageType = "NDATA"
+	SparkplugDData  SparkplugMessageType = "DDATA"
+	SparkplugNCmd   SparkplugMessageType = "NCMD"
+	SparkplugDCmd   SparkplugMessageType = "DCMD"
+)
+
+// SparkplugDataType is the metric datatype from the Sparkplug B protobuf
+type SparkplugDataType uint32
+
+const (
+	SparkplugInt8     SparkplugDataType = 1
+	SparkplugInt16    SparkplugDataType = 2
+	SparkplugInt32    SparkplugDataType = 3
+	SparkplugInt64    SparkplugDataType = 4
+	SparkplugUInt8    SparkplugDataType = 5
+	SparkplugUInt16   SparkplugDataType = 6
+	SparkplugUInt32   SparkplugDataType = 7
+	SparkplugUInt64   SparkplugDataType = 8
+	SparkplugFloat    SparkplugDataType = 9
+	SparkplugDouble   SparkplugDataType = 10
+	SparkplugBoolean  SparkplugDataType = 11
+	SparkplugString   SparkplugDataType = 12
+	SparkplugDateTime SparkplugDataType = 13 // Milliseconds since the epoch, as uint64
+	SparkplugText     SparkplugDataType = 14
+	SparkplugUUID     SparkplugDataType = 15
+	SparkplugBytes    SparkplugDataType = 17
+)
+
+// Well-known metric names
+const (
+	SparkplugBdSeqMetric   = "bdSeq"
+	SparkplugRebirthMetric = "Node Control/Rebirth"
+)
+
+// Protobuf field numbers of the Payload and Metric messages
+const (
+	sparkplugPayloadTimestamp = 1
+	sparkplugPayloadMetrics   = 2
+	sparkplugPayloadSeq       = 3
+	sparkplugPayloadUUID      = 4
+	sparkplugPayloadBody      = 5
+
+	sparkplugMetricName         = 1
+	sparkplugMetricAlias        = 2
+	sparkplugMetricTimestamp    = 3
+	sparkplugMetricDataType     = 4
+	sparkplugMetricIsHistorical = 5
+	sparkplugMetricIsTransient  = 6
+	sparkplugMetricIsNull       = 7
+	sparkplugMetricIntValue     = 10
+	sparkplugMetricLongValue    = 11
+	sparkplugMetricFloatValue   = 12
+	sparkplugMetricDoubleValue  = 13
+	sparkplugMetricBoolValue    = 14
+	sparkplugMetricStringValue  = 15
+	sparkplugMetricBytesValue   = 16
+)
+
+// Protobuf wire types
+const (
+	wireVarint  = 0
+	wireFixed64 = 1
+	wireBytes   = 2
+	wireFixed32 = 5
+)
+
+// SparkplugTopic is a parsed Sparkplug B topic
+type SparkplugTopic struct {
+	GroupID     string
+	MessageType SparkplugMessageType
+	EdgeNodeID  string
+	DeviceID    string // Empty for node-level messages
+}
+
+// ParseSparkplugTopic parses spBv1.0/<group>/<type>/<edge node>[/<device>]
+func ParseSparkplugTopic(topic string) (*SparkplugTopic, error) {
+	parts := strings.Split(topic, "/")
+	if len(parts) < 4 || len(parts) > 5 || parts[0] != SparkplugNamespace {
+		return nil, fmt.Errorf("not a Sparkplug B topic: %s", topic)
+	}
+
+	parsed := &SparkplugTopic{
+		GroupID:     parts[1],
+		MessageType: SparkplugMessageType(parts[2]),
+		EdgeNodeID:  parts[3],
+	}
+	if len(parts) == 5 {
+		parsed.DeviceID = parts[4]
+	}
+
+	deviceMessage := strings.HasPrefix(string(parsed.MessageType), "D")
+	if deviceMessage != (parsed.DeviceID != "") {
+		return nil, fmt.Errorf("message type %s does not match topic %s", parsed.MessageType, topic)
+	}
+	return parsed, nil
+}
+
+// String formats the topic
+func (t *SparkplugTopic) String() string {
+	topic := fmt.Sprintf("%s/%s/%s/%s", SparkplugNamespace, t.GroupID, t.MessageType, t.EdgeNodeID)
+	if t.DeviceID != "" {
+		topic += "/" + t.DeviceID
+	}
+	return topic
+}
+
+// SparkplugMetric is one metric of a payload. Value holds int8 through
+// uint64, float32, float64, bool, string or []byte according to DataType,
+// and is nil when IsNull is set.
+type SparkplugMetric struct {
+	Name         string
+	Alias        uint64
+	HasAlias     bool
+	Timestamp    uint64 // Milliseconds since the epoch; 0 if not set
+	DataType     SparkplugDataType
+	IsHistorical bool
+	IsTransient  bool
+	IsNull       bool
+	Value        interface{}
+}
+
+// SparkplugPayload is a Sparkplug B payload
+type SparkplugPayload struct {
+	Timestamp uint64 // Milliseconds since the epoch
+	Metrics   []*SparkplugMetric
+	Seq       uint64
+	HasSeq    bool // Seq is present; required on everything but NDEATH
+	UUID      string
+	Body      []byte
+}
+
+// Marshal encodes the payload in protobuf wire format
+func (p *SparkplugPayload) Marshal() ([]byte, error) {
+	var buf []byte
+	if p.Timestamp != 0 {
+		buf = appendProtoVarint(buf, sparkplugPayloadTimestamp, p.Timestamp)
+	}
+
+	for _, metric := range p.Metrics {
+		encoded, err := metric.marshal()
+		if err != nil {
+			return nil, fmt.Errorf("metric %q: %w", metric.Name, err)
+		}
+		buf = appendProtoBytes(buf, sparkplugPayloadMetrics, encoded)
+	}
+
+	if p.HasSeq {
+		buf = appendProtoVarint(buf, sparkplugPayloadSeq, p.Seq)
+	}
+	if p.UUID != "" {
+		buf = appendProtoBytes(buf, sparkplugPayloadUUID, []byte(p.UUID))
+	}
+	if p.Body != nil {
+		buf = appendProtoBytes(buf, sparkplugPayloadBody, p.Body)
+	}
+	return buf, nil
+}
+
+func (m *SparkplugMetric) marshal() ([]byte, error) {
+	var buf []byte
+	if m.Name != "" {
+		buf = appendProtoBytes(buf, sparkplugMetricName, []byte(m.Name))
+	}
+	if m.HasAlias {
+		buf = appendProtoVarint(buf, sparkplugMetricAlias, m.Alias)
+	}
+	if m.Timestamp != 0 {
+		buf = appendProtoVarint(buf, sparkplugMetricTimestamp, m.Timestamp)
+	}
+	if m.DataType != 0 {
+		buf = appendProtoVarint(buf, sparkplugMetricDataType, uint64(m.DataType))
+	}
+	if m.IsHistorical {
+		buf = appendProtoVarint(buf, sparkplugMetricIsHistorical, 1)
+	}
+	if m.IsTransient {
+		buf = appendProtoVarint(buf, sparkplugMetricIsTransient, 1)
+	}
+	if m.IsNull || m.Value == nil {
+		return appendProtoVarint(buf, sparkplugMetricIsNull, 1), nil
+	}
+
+	switch m.DataType {
+	case SparkplugInt8, SparkplugInt16, SparkplugInt32:
+		bits := m.DataType.bits()
+		v, err := integerValue(m.Value, -1<<(bits-1), 1<<(bits-1)-1)
+		if err != nil {
+			return nil, err
+		}
+		// Signed values travel as their two's complement bit pattern
+		return appendProtoVarint(buf, sparkplugMetricIntValue, uint64(uint32(int32(v)))), nil
+
+	case SparkplugUInt8, SparkplugUInt16, SparkplugUInt32:
+		v, err := integerValue(m.Value, 0, 1<<m.DataType.bits()-1)
+		if err != nil {
+			return nil, err
+		}
+		return appendProtoVarint(buf, sparkplugMetricIntValue, uint64(v)), nil
+
+	case SparkplugInt64:
+		v, err := integerValue(m.Value, math.MinInt64, math.MaxInt64)
+		if err != nil {
+			return nil, err
+		}
+		return appendProtoVarint(buf, sparkplugMetricLongValue, uint64(v)), nil
+
+	case SparkplugUInt64, SparkplugDateTime:
+		v, err := unsigned64Value(m.Value)
+		if err != nil {
+			return nil, err
+		}
+		return appendProtoVarint(buf, sparkplugMetricLongValue, v), nil
+
+	case SparkplugFloat:
+		v, err := floatValue(m.Value)
+		if err != nil {
+			return nil, err
+		}
+		buf = appendProtoKey(buf, sparkplugMetricFloatValue, wireFixed32)
+		return binary.LittleEndian.AppendUint32(buf, math.Float32bits(float32(v))), nil
+
+	case SparkplugDouble:
+		v, err := floatValue(m.Value)
+		if err != nil {
+			return nil, err
+		}
+		buf = appendProtoKey(buf, sparkplugMetricDoubleValue, wireFixed64)
+		return binary.LittleEndian.AppendUint64(buf, math.Float64bits(v)), nil
+
+	case SparkplugBoolean:
+		v, ok := m.Value.(bool)
+		if !ok {
+			return nil, fmt.Errorf("expected bool value, got %T", m.Value)
+		}
+		var b uint64
+		if v {
+			b = 1
+		}
+		return appendProtoVarint(buf, sparkplugMetricBoolValue, b), nil
+
+	case SparkplugString, SparkplugText, SparkplugUUID:
+		v, ok := m.Value.(string)
+		if !ok {
+			return nil, fmt.Errorf("expected string value, got %T", m.Value)
+		}
+		return appendProtoBytes(buf, sparkplugMetricStringValue, []byte(v)), nil
+
+	case SparkplugBytes:
+		v, ok := m.Value.([]byte)
+		if !ok {
+			return nil, fmt.Errorf("expected []byte value, got %T", m.Value)
+		}
+		return appendProtoBytes(buf, sparkplugMetricBytesValue, v), nil
+	}
+
+	return nil, fmt.Errorf("unsupported datatype %d", m.DataType)
+}
+
+// UnmarshalSparkplugPayload decodes a protobuf Sparkplug B payload. Metrics
+// sent by alias only carry no DataType; resolve them against the birth
+// certificate before use.
+func UnmarshalSparkplugPayload(data []byte) (*SparkplugPayload, error) {
+	payload := &SparkplugPayload{}
+
+	err := walkProtoFields(data, func(field int, wireType int, value uint64, raw []byte) error {
+		switch field {
+		case sparkplugPayloadTimestamp:
+			payload.Timestamp = value
+		case sparkplugPayloadMetrics:
+			metric, err := unmarshalSparkplugMetric(raw)
+			if err != nil {
+				return fmt.Errorf("metric %d: %w", len(payload.Metrics), err)
+			}
+			payload.Metrics = append(payload.Metrics, metric)
+		case sparkplugPayloadSeq:
+			payload.Seq = value
+			payload.HasSeq = true
+		case sparkplugPayloadUUID:
+			payload.UUID = string(raw)
+		case sparkplugPayloadBody:
+			payload.Body = append([]byte(nil), raw...)
+		}
+		return nil
+	})
+	if err != nil {
+		return nil, err
+	}
+	return payload, nil
+}
+
+func unmarshalSparkplugMetric(data []byte) (*SparkplugMetric, error) {
+	metric := &SparkplugMetric{}
+
+	var intValue, longValue uint64
+	var floatBits uint32
+	var doubleBits uint64
+	var stringValue string
+	var bytesValue []byte
+	var boolValue bool
+	valueField := 0
+
+	err := walkProtoFields(data, func(field int, wireType int, value uint64, raw []byte) error {
+		switch field {
+		case sparkplugMetricName:
+			metric.Name = string(raw)
+		case sparkplugMetricAlias:
+			metric.Alias = value
+			metric.HasAlias = true
+		case sparkplugMetricTimestamp:
+			metric.Timestamp = value
+		case sparkplugMetricDataType:
+			metric.DataType = SparkplugDataType(value)
+		case sparkplugMetricIsHistorical:
+			metric.IsHistorical = value != 0
+		case sparkplugMetricIsTransient:
+			metric.IsTransient = value != 0
+		case sparkplugMetricIsNull:
+			metric.IsNull = value != 0
+		case sparkplugMetricIntValue:
+			intValue, valueField = value, field
+		case sparkplugMetricLongValue:
+			longValue, valueField = value, field
+		case sparkplugMetricFloatValue:
+			floatBits, valueField = uint32(value), field
+		case sparkplugMetricDoubleValue:
+			doubleBits, valueField = value, field
+		case sparkplugMetricBoolValue:
+			boolValue, valueField = value != 0, field
+		case sparkplugMetricStringValue:
+			stringValue, valueField = string(raw), field
+		case sparkplugMetricBytesValue:
+			bytesValue, valueField = append([]byte(nil), raw...), field
+		}
+		return nil
+	})
+	if err != nil {
+		return nil, err
+	}
+
+	if metric.IsNull {
+		return metric, nil
+	}
+
+	switch valueField {
+	case sparkplugMetricIntValue:
+		metric.Value = intValue
+	case sparkplugMetricLongValue:
+		metric.Value = longValue
+	case sparkplugMetricFloatValue:
+		metric.Value = math.Float32frombits(floatBits)
+	case sparkplugMetricDoubleValue:
+		metric.Value = math.Float64frombits(doubleBits)
+	case sparkplugMetricBoolValue:
+		metric.Value = boolValue
+	case sparkplugMetricStringValue:
+		metric.Value = stringValue
+	case sparkplugMetricBytesValue:
+		metric.Value = bytesValue
+	}
+
+	if metric.DataType != 0 {
+		metric.Value = metric.DataType.convert(metric.Value)
+	}
+	return metric, nil
+}
+
+// convert narrows a decoded raw value to the Go type of the datatype.
+// Integer values arrive as uint64 and signed types are sign-extended from
+// their two's complement pattern.
+func (d SparkplugDataType) convert(value interface{}) interface{} {
+	raw, ok := value.(uint64)
+	if !ok {
+		return value
+	}
+
+	switch d {
+	case SparkplugInt8:
+		return int8(raw)
+	case SparkplugInt16:
+		return int16(raw)
+	case SparkplugInt32:
+		return int32(raw)
+	case SparkplugInt64:
+		return int64(raw)
+	case SparkplugUInt8:
+		return uint8(raw)
+	case SparkplugUInt16:
+		return uint16(raw)
+	case SparkplugUInt32:
+		return uint32(raw)
+	}
+	return raw
+}
+
+// bits returns the width of the integer datatypes carried in int_value
+func (d SparkplugDataType) bits() uint {
+	switch d {
+	case SparkplugInt8, SparkplugUInt8:
+		return 8
+	case SparkplugInt16, SparkplugUInt16:
+		return 16
+	}
+	return 32
+}
+
+// SparkplugDataTypeFor returns the Sparkplug datatype matching a tag data
+// type, defaulting to Double
+func SparkplugDataTypeFor(dataType string) SparkplugDataType {
+	switch DataType(dataType) {
+	case DataTypeBool:
+		return SparkplugBoolean
+	case DataTypeInt16:
+		return SparkplugInt16
+	case DataTypeUInt16:
+		return SparkplugUInt16
+	case DataTypeInt32:
+		return SparkplugInt32
+	case DataTypeUInt32:
+		return SparkplugUInt32
+	case DataTypeInt64:
+		return SparkplugInt64
+	case DataTypeUInt64:
+		return SparkplugUInt64
+	case DataTypeFloat32:
+		return SparkplugFloat
+	case DataTypeString:
+		return SparkplugString
+	case DataTypeBytes:
+		return SparkplugBytes
+	}
+	return SparkplugDouble
+}
+
+func appendProtoKey(buf []byte, field int, wireType int) []byte {
+	return binary.AppendUvarint(buf, uint64(field)<<3|uint64(wireType))
+}
+
+func appendProtoVarint(buf []byte, field int, value uint64) []byte {
+	buf = appendProtoKey(buf, field, wireVarint)
+	return binary.AppendUvarint(buf, value)
+}
+
+func appendProtoBytes(buf []byte, field int, value []byte) []byte {
+	buf = appendProtoKey(buf, field, wireBytes)
+	buf = binary.AppendUvarint(buf, uint64(len(value)))
+	return append(buf, value...)
+}
+
+// walkProtoFields calls visit for every field of a protobuf message. Scalar
+// fields are passed in value, length-delimited fields in raw (aliasing
+// data). Groups are not supported.
+func walkProtoFields(data []byte, visit func(field int, wireType int, value uint64, raw []byte) error) error {
+	for len(data) > 0 {
+		key, n := binary.Uvarint(data)
+		if n <= 0 {
+			return fmt.Errorf("malformed field key")
+		}
+		data = data[n:]
+
+		field, wireType := int(key>>3), int(key&0x07)
+		if field == 0 {
+			return fmt.Errorf("invalid field number 0")
+		}
+
+		var value uint64
+		var raw []byte
+		switch wireType {
+		case wireVarint:
+			value, n = binary.Uvarint(data)
+			if n <= 0 {
+				return fmt.Errorf("field %d: malformed varint", field)
+			}
+			data = data[n:]
+		case wireFixed64:
+			if len(data) < 8 {
+				return fmt.Errorf("field %d: truncated fixed64", field)
+			}
+			value = binary.LittleEndian.Uint64(data)
+			data = data[8:]
+		case wireFixed32:
+			if len(data) < 4 {
+				return fmt.Errorf("field %d: truncated fixed32", field)
+			}
+			value = uint64(binary.LittleEndian.Uint32(data))
+			data = data[4:]
+		case wireBytes:
+			length, n := binary.Uvarint(data)
+			if n <= 0 || length > uint64(len(data)-n) {
+				return fmt.Errorf("field %d: truncated length-delimited value", field)
+			}
+			raw = data[n : n+int(length)]
+			data = data[n+int(length):]
+		default:
+			return fmt.Errorf("field %d: unsupported wire type %d", field, wireType)
+		}
+
+		if err := visit(field, wireType, value, raw); err != nil {
+			return err
+		}
+	}
+	return nil
+}
@@ -0,0 +1,427 @@
+package protocols
+
+import (
+	"fmt"
+	"sort"
+	"sync"
+	"time"
+
+	"go.uber.org/zap"
+
+	"github.com/bifrost/go-gateway/internal/ratelimit"
+)
+
+// Sparkplug B Host Application
+//
+// SparkplugHost consumes the messages of Sparkplug B edge nodes and keeps
+// the latest value of every metric as a Tag. Birth certificates define the
+// metric names, datatypes and aliases; data messages are resolved against
+// them. Sequence numbers are checked on every message, and a gap or an
+// unknown alias means the host's view of the node can no longer be trusted,
+// so a rebirth is requested.
+
+// SparkplugUpdate is a metric value resolved by the host
+type SparkplugUpdate struct {
+	GroupID    string
+	EdgeNodeID string
+	DeviceID   string // Empty for node metrics
+	Tag        *Tag
+}
+
+// SparkplugNodeStatus describes an edge node as seen by the host
+type SparkplugNodeStatus struct {
+	GroupID    string    `json:"group_id"`
+	EdgeNodeID string    `json:"edge_node_id"`
+	Online     bool      `json:"online"`
+	BdSeq      uint64    `json:"bd_seq"`
+	Devices    []string  `json:"devices"`
+	LastSeen   time.Time `json:"last_seen"`
+	Rebirths   uint64    `json:"rebirths_requested"`
+}
+
+// SparkplugHost tracks Sparkplug B edge nodes and their metric values
+type SparkplugHost struct {
+	// OnUpdate is called for every metric value received
+	OnUpdate func(update *SparkplugUpdate)
+
+	// RequestRebirth is called when a node must republish its births
+	RequestRebirth func(groupID, edgeNodeID string)
+
+	// Limiter limits the metrics and bytes each edge node may publish,
+	// keyed by "group/edge node"; nil for no limit. A dropped message
+	// leaves a sequence gap, so the node is asked for a rebirth once it is
+	// back within its limit.
+	Limiter *ratelimit.Limiter
+
+	// Clock stamps node activity and metrics received without a
+	// timestamp; DefaultClock if nil
+	Clock Clock
+
+	logger *zap.Logger
+
+	mutex sync.RWMutex
+	nodes map[string]*sparkplugNodeState
+}
+
+type sparkplugNodeState struct {
+	groupID    string
+	edgeNodeID string
+	online     bool
+	bdSeq      uint64
+	seq        uint64
+	lastSeen   time.Time
+	rebirths   uint64
+
+	aliases map[uint64]sparkplugMetricDefinition
+	types   map[string]SparkplugDataType // By device + "/" + metric name
+	devices map[string]bool              // Device ID -> online
+	tags    map[string]map[string]*Tag   // Device ID ("" for the node) -> metric name -> tag
+}
+
+type sparkplugMetricDefinition struct {
+	deviceID string
+	name     string
+	dataType SparkplugDataType
+}
+
+// NewSparkplugHost creates a host application state tracker
+func NewSparkplugHost(logger *zap.Logger) *SparkplugHost {
+	return &SparkplugHost{
+		logger: logger,
+		nodes:  make(map[string]*sparkplugNodeState),
+	}
+}
+
+// now returns the time of the host clock
+func (h *SparkplugHost) now() time.Time {
+	if h.Clock == nil {
+		return DefaultClock.Now()
+	}
+	return h.Clock.Now()
+}
+
+// HandleMessage processes one MQTT message received on a Sparkplug topic
+func (h *SparkplugHost) HandleMessage(topicName string, data []byte) error {
+	topic, err := ParseSparkplugTopic(topicName)
+	if err != nil {
+		return err
+	}
+
+	payload, err := UnmarshalSparkplugPayload(data)
+	if err != nil {
+		return fmt.Errorf("%s: %w", topicName, err)
+	}
+	if err := h.Limiter.Allow(topic.GroupID+"/"+topic.EdgeNodeID, len(payload.Metrics), len(data)); err != nil {
+		return err
+	}
+
+	h.mutex.Lock()
+	updates, rebirth, err := h.apply(topic, payload)
+	h.mutex.Unlock()
+
+	if rebirth && h.RequestRebirth != nil {
+		h.RequestRebirth(topic.GroupID, topic.EdgeNodeID)
+	}
+	if h.OnUpdate != nil {
+		for _, update := range updates {
+			h.OnUpdate(update)
+		}
+	}
+	return err
+}
+
+// Tag returns the latest value of a metric
+func (h *SparkplugHost) Tag(groupID, edgeNodeID, deviceID, metric string) (*Tag, bool) {
+	h.mutex.RLock()
+	defer h.mutex.RUnlock()
+
+	node, exists := h.nodes[groupID+"/"+edgeNodeID]
+	if !exists {
+		return nil, false
+	}
+	tag, exists := node.tags[deviceID][metric]
+	if !exists {
+		return nil, false
+	}
+	copied := *tag
+	return &copied, true
+}
+
+// MetricDataType returns the datatype a node declared for a metric in its
+// birth certificate
+func (h *SparkplugHost) MetricDataType(groupID, edgeNodeID, deviceID, metric string) (SparkplugDataType, bool) {
+	h.mutex.RLock()
+	defer h.mutex.RUnlock()
+
+	node, exists := h.nodes[groupID+"/"+edgeNodeID]
+	if !exists {
+		return 0, false
+	}
+	dataType, exists := node.types[deviceID+"/"+metric]
+	return dataType, exists
+}
+
+// Nodes returns the status of every edge node seen, ordered by group and
+// node ID
+func (h *SparkplugHost) Nodes() []*SparkplugNodeStatus {
+	h.mutex.RLock()
+	defer h.mutex.RUnlock()
+
+	statuses := make([]*SparkplugNodeStatus, 0, len(h.nodes))
+	for _, node := range h.nodes {
+		status := &SparkplugNodeStatus{
+			GroupID:    node.groupID,
+			EdgeNodeID: node.edgeNodeID,
+			Online:     node.online,
+			BdSeq:      node.bdSeq,
+			Devices:    make([]string, 0, len(node.devices)),
+			LastSeen:   node.lastSeen,
+			Rebirths:   node.rebirths,
+		}
+		for deviceID := range node.devices {
+			status.Devices = append(status.Devices, deviceID)
+		}
+		sort.Strings(status.Devices)
+		statuses = append(statuses, status)
+	}
+
+	sort.Slice(statuses, func(i, j int) bool {
+		if statuses[i].GroupID != statuses[j].GroupID {
+			return statuses[i].GroupID < statuses[j].GroupID
+		}
+		return statuses[i].EdgeNodeID < statuses[j].EdgeNodeID
+	})
+	return statuses
+}
+
+// apply updates the node state for one message. It returns the resulting
+// metric updates and whether a rebirth should be requested.
+func (h *SparkplugHost) apply(topic *SparkplugTopic, payload *SparkplugPayload) ([]*SparkplugUpdate, bool, error) {
+	key := topic.GroupID + "/" + topic.EdgeNodeID
+	node, exists := h.nodes[key]
+	if !exists {
+		node = &sparkplugNodeState{groupID: topic.GroupID, edgeNodeID: topic.EdgeNodeID}
+		node.reset()
+		h.nodes[key] = node
+	}
+
+	switch topic.MessageType {
+	case SparkplugNBirth:
+		node.reset()
+		node.online = true
+		node.seq = payload.Seq
+		node.lastSeen = h.now()
+		for _, metric := range payload.Metrics {
+			if metric.Name == SparkplugBdSeqMetric {
+				node.bdSeq, _ = unsigned64Value(metric.Value)
+			}
+		}
+		return node.birth("", payload), false, nil
+
+	case SparkplugNDeath:
+		// A death certificate from an older session is stale
+		bdSeq, found := uint64(0), false
+		for _, metric := range payload.Metrics {
+			if metric.Name == SparkplugBdSeqMetric {
+				bdSeq, _ = unsigned64Value(metric.Value)
+				found = true
+			}
+		}
+		if found && bdSeq != node.bdSeq {
+			return nil, false, nil
+		}
+		node.online = false
+		node.lastSeen = h.now()
+		var updates []*SparkplugUpdate
+		for deviceID := range node.tags {
+			updates = append(updates, node.markStale(deviceID)...)
+			if deviceID != "" {
+				node.devices[deviceID] = false
+			}
+		}
+		return updates, false, nil
+
+	case SparkplugNCmd, SparkplugDCmd:
+		return nil, false, nil
+	}
+
+	// Everything else requires a live node and the next sequence number
+	if !node.online {
+		return nil, h.rebirth(node), fmt.Errorf("%s received for edge node %s without a birth certificate", topic.MessageType, key)
+	}
+	node.lastSeen = h.now()
+
+	rebirth := false
+	if !payload.HasSeq || payload.Seq != (node.seq+1)%256 {
+		h.logger.Warn("Sparkplug sequence gap",
+			zap.String("edge_node", key),
+			zap.Uint64("expected", (node.seq+1)%256),
+			zap.Uint64("received", payload.Seq),
+		)
+		rebirth = true
+	}
+	node.seq = payload.Seq
+
+	var updates []*SparkplugUpdate
+	switch topic.MessageType {
+	case SparkplugDBirth:
+		node.devices[topic.DeviceID] = true
+		node.tags[topic.DeviceID] = make(map[string]*Tag)
+		updates = node.birth(topic.DeviceID, payload)
+
+	case SparkplugDDeath:
+		node.devices[topic.DeviceID] = false
+		updates = node.markStale(topic.DeviceID)
+
+	case SparkplugNData, SparkplugDData:
+		if topic.DeviceID != "" && !node.devices[topic.DeviceID] {
+			rebirth = true
+			break
+		}
+		var unknown bool
+		updates, unknown = node.data(topic.DeviceID, payload)
+		rebirth = rebirth || unknown
+
+	default:
+		return nil, false, fmt.Errorf("unsupported Sparkplug message type %s", topic.MessageType)
+	}
+
+	if rebirth {
+		return updates, h.rebirth(node), nil
+	}
+	return updates, false, nil
+}
+
+// rebirth counts a rebirth request for node and reports whether one should
+// be sent
+func (h *SparkplugHost) rebirth(node *sparkplugNodeState) bool {
+	node.rebirths++
+	return h.RequestRebirth != nil
+}
+
+func (n *sparkplugNodeState) reset() {
+	n.aliases = make(map[uint64]sparkplugMetricDefinition)
+	n.types = make(map[string]SparkplugDataType)
+	n.devices = make(map[string]bool)
+	n.tags = map[string]map[string]*Tag{"": make(map[string]*Tag)}
+}
+
+// birth records the metric definitions of a birth certificate and their
+// initial values
+func (n *sparkplugNodeState) birth(deviceID string, payload *SparkplugPayload) []*SparkplugUpdate {
+	updates := make([]*SparkplugUpdate, 0, len(payload.Metrics))
+	for _, metric := range payload.Metrics {
+		if metric.Name == "" {
+			continue
+		}
+		n.types[deviceID+"/"+metric.Name] = metric.DataType
+		if metric.HasAlias {
+			n.aliases[metric.Alias] = sparkplugMetricDefinition{deviceID: deviceID, name: metric.Name, dataType: metric.DataType}
+		}
+		updates = append(updates, n.update(deviceID, metric.Name, metric, payload.Timestamp))
+	}
+	return updates
+}
+
+// data applies a data message. It reports whether a metric could not be
+// resolved against the birth certificate.
+func (n *sparkplugNodeState) data(deviceID string, payload *SparkplugPayload) ([]*SparkplugUpdate, bool) {
+	var updates []*SparkplugUpdate
+	unknown := false
+
+	for _, metric := range payload.Metrics {
+		name := metric.Name
+		if name == "" {
+			definition, exists := n.aliases[metric.Alias]
+			if !metric.HasAlias || !exists || definition.deviceID != deviceID {
+				unknown = true
+				continue
+			}
+			name = definition.name
+		}
+
+		dataType, exists := n.types[deviceID+"/"+name]
+		if !exists {
+			unknown = true
+			continue
+		}
+		if metric.DataType == 0 {
+			metric.DataType = dataType
+			metric.Value = dataType.convert(metric.Value)
+		}
+		updates = append(updates, n.update(deviceID, name, metric, payload.Timestamp))
+	}
+	return updates, unknown
+}
+
+// update stores a metric value as a tag
+func (n *sparkplugNodeState) update(deviceID, name string, metric *SparkplugMetric, payloadTimestamp uint64) *SparkplugUpdate {
+	timestamp := metric.Timestamp
+	if timestamp == 0 {
+		timestamp = payloadTimestamp
+	}
+
+	tag := &Tag{
+		ID:        name,
+		Name:      name,
+		Address:   name,
+		DataType:  metric.DataType.tagDataType(),
+		Value:     metric.Value,
+		Quality:   QualityGood,
+		Timestamp: n.lastSeen,
+	}
+	if timestamp != 0 {
+		tag.Timestamp = time.UnixMilli(int64(timestamp))
+	}
+	if metric.IsNull {
+		tag.SetStatus(NewQualityStatus(QualityBad, "", QualitySourceDevice))
+	}
+
+	if n.tags[deviceID] == nil {
+		n.tags[deviceID] = make(map[string]*Tag)
+	}
+	n.tags[deviceID][name] = tag
+
+	copied := *tag
+	return &SparkplugUpdate{GroupID: n.groupID, EdgeNodeID: n.edgeNodeID, DeviceID: deviceID, Tag: &copied}
+}
+
+// markStale flags every metric of a device (or the node, for "") as stale
+func (n *sparkplugNodeState) markStale(deviceID string) []*SparkplugUpdate {
+	updates := make([]*SparkplugUpdate, 0, len(n.tags[deviceID]))
+	for _, tag := range n.tags[deviceID] {
+		tag.SetStatus(NewQualityStatus(QualityStale, QualitySubstatusLastKnownValue, QualitySourceGateway))
+		copied := *tag
+		updates = append(updates, &SparkplugUpdate{GroupID: n.groupID, EdgeNodeID: n.edgeNodeID, DeviceID: deviceID, Tag: &copied})
+	}
+	return updates
+}
+
+// tagDataType maps a Sparkplug datatype onto the tag data types
+func (d SparkplugDataType) tagDataType() string {
+	switch d {
+	case SparkplugBoolean:
+		return string(DataTypeBool)
+	case SparkplugInt8, SparkplugInt16:
+		return string(DataTypeInt16)
+	case SparkplugUInt8, SparkplugUInt16:
+		return string(DataTypeUInt16)
+	case SparkplugInt32:
+		return string(DataTypeInt32)
+	case SparkplugUInt32:
+		return string(DataTypeUInt32)
+	case SparkplugInt64:
+		return string(DataTypeInt64)
+	case SparkplugUInt64, SparkplugDateTime:
+		return string(DataTypeUInt64)
+	case SparkplugFloat:
+		return string(DataTypeFloat32)
+	case SparkplugDouble:
+		return string(DataTypeFloat64)
+	case SparkplugString, SparkplugText, SparkplugUUID:
+		return string(DataTypeString)
+	case SparkplugBytes:
+		return string(DataTypeBytes)
+	}
+	return ""
+}
@@ -0,0 +1,212 @@
+package protocols
+
+import (
+	"fmt"
+	"sync"
+	"time"
+)
+
+// Sparkplug B Edge Node
+//
+// SparkplugEdgeNode produces the message sequence of a Sparkplug B edge
+// node: the NDEATH will for a session, the NBIRTH and DBIRTH certificates
+// that declare metrics and assign their aliases, and the data and death
+// messages that follow. It does not talk to a broker itself; messages are
+// handed to the publish function in the order they must be sent.
+
+// SparkplugPublishFunc publishes one Sparkplug message
+type SparkplugPublishFunc func(topic string, payload []byte) error
+
+// SparkplugEdgeNode publishes on behalf of one edge node and its devices
+type SparkplugEdgeNode struct {
+	GroupID    string
+	EdgeNodeID string
+
+	publish SparkplugPublishFunc
+
+	mutex     sync.Mutex
+	bdSeq     uint64
+	seq       uint64
+	born      bool
+	aliases   map[string]uint64 // By device + "/" + metric name
+	types     map[string]SparkplugDataType
+	nextAlias uint64
+}
+
+// NewSparkplugEdgeNode creates an edge node publishing through publish
+func NewSparkplugEdgeNode(groupID, edgeNodeID string, publish SparkplugPublishFunc) *SparkplugEdgeNode {
+	return &SparkplugEdgeNode{
+		GroupID:    groupID,
+		EdgeNodeID: edgeNodeID,
+		publish:    publish,
+		aliases:    make(map[string]uint64),
+		types:      make(map[string]SparkplugDataType),
+	}
+}
+
+// NewSession starts a new MQTT session and returns the NDEATH certificate
+// to register as the connection's will. The birth/death sequence number is
+// incremented for every session after the first.
+func (n *SparkplugEdgeNode) NewSession() (string, []byte, error) {
+	n.mutex.Lock()
+	defer n.mutex.Unlock()
+
+	if n.born {
+		n.bdSeq = (n.bdSeq + 1) % 256
+	}
+	n.born = false
+
+	payload := &SparkplugPayload{
+		Timestamp: sparkplugNow(),
+		Metrics:   []*SparkplugMetric{n.bdSeqMetric()},
+	}
+	data, err := payload.Marshal()
+	if err != nil {
+		return "", nil, err
+	}
+	return n.topic(SparkplugNDeath, ""), data, nil
+}
+
+// Birth publishes the NBIRTH certificate with the node metrics. The
+// sequence number restarts at 0 and all aliases are reassigned, so device
+// births must be published again afterwards.
+func (n *SparkplugEdgeNode) Birth(metrics []*SparkplugMetric) error {
+	n.mutex.Lock()
+	defer n.mutex.Unlock()
+
+	n.aliases = make(map[string]uint64)
+	n.types = make(map[string]SparkplugDataType)
+	n.nextAlias = 0
+	n.seq = 0
+	n.born = true
+
+	birth := []*SparkplugMetric{n.bdSeqMetric(), {Name: SparkplugRebirthMetric, DataType: SparkplugBoolean, Value: false}}
+	birth = append(birth, n.define("", metrics)...)
+	return n.send(SparkplugNBirth, "", &SparkplugPayload{Timestamp: sparkplugNow(), Metrics: birth, Seq: 0, HasSeq: true})
+}
+
+// DeviceBirth publishes the DBIRTH certificate of a device
+func (n *SparkplugEdgeNode) DeviceBirth(deviceID string, metrics []*SparkplugMetric) error {
+	n.mutex.Lock()
+	defer n.mutex.Unlock()
+
+	if !n.born {
+		return fmt.Errorf("device birth for %s before node birth", deviceID)
+	}
+	return n.send(SparkplugDBirth, deviceID, n.nextPayload(n.define(deviceID, metrics)))
+}
+
+// Data publishes metric values, by alias, as NDATA or, with a device ID,
+// as DDATA. Metrics must have been declared in the matching birth.
+func (n *SparkplugEdgeNode) Data(deviceID string, metrics []*SparkplugMetric) error {
+	n.mutex.Lock()
+	defer n.mutex.Unlock()
+
+	if !n.born {
+		return fmt.Errorf("data published before node birth")
+	}
+
+	data := make([]*SparkplugMetric, 0, len(metrics))
+	for _, metric := range metrics {
+		key := deviceID + "/" + metric.Name
+		alias, exists := n.aliases[key]
+		if !exists {
+			return fmt.Errorf("metric %q was not declared in the birth certificate", metric.Name)
+		}
+		data = append(data, &SparkplugMetric{
+			Alias:     alias,
+			HasAlias:  true,
+			Timestamp: metric.Timestamp,
+			DataType:  n.types[key],
+			IsNull:    metric.IsNull,
+			Value:     metric.Value,
+		})
+	}
+
+	messageType := SparkplugNData
+	if deviceID != "" {
+		messageType = SparkplugDData
+	}
+	return n.send(messageType, deviceID, n.nextPayload(data))
+}
+
+// DeviceDeath publishes the DDEATH certificate of a device
+func (n *SparkplugEdgeNode) DeviceDeath(deviceID string) error {
+	n.mutex.Lock()
+	defer n.mutex.Unlock()
+
+	if !n.born {
+		return fmt.Errorf("device death for %s before node birth", deviceID)
+	}
+	return n.send(SparkplugDDeath, deviceID, n.nextPayload(nil))
+}
+
+// IsRebirthRequest reports whether a command payload asks the node to
+// republish its birth certificates
+func IsRebirthRequest(payload *SparkplugPayload) bool {
+	for _, metric := range payload.Metrics {
+		if metric.Name == SparkplugRebirthMetric && metric.Value == true {
+			return true
+		}
+	}
+	return false
+}
+
+// RebirthCommand builds the NCMD a host sends to request a rebirth
+func RebirthCommand(groupID, edgeNodeID string) (string, []byte, error) {
+	payload := &SparkplugPayload{
+		Timestamp: sparkplugNow(),
+		Metrics:   []*SparkplugMetric{{Name: SparkplugRebirthMetric, DataType: SparkplugBoolean, Value: true}},
+	}
+	data, err := payload.Marshal()
+	if err != nil {
+		return "", nil, err
+	}
+	topic := &SparkplugTopic{GroupID: groupID, MessageType: SparkplugNCmd, EdgeNodeID: edgeNodeID}
+	return topic.String(), data, nil
+}
+
+// define assigns aliases to the metrics of a birth certificate
+func (n *SparkplugEdgeNode) define(deviceID string, metrics []*SparkplugMetric) []*SparkplugMetric {
+	defined := make([]*SparkplugMetric, 0, len(metrics))
+	for _, metric := range metrics {
+		copied := *metric
+		key := deviceID + "/" + metric.Name
+		alias, exists := n.aliases[key]
+		if !exists {
+			alias = n.nextAlias
+			n.nextAlias++
+			n.aliases[key] = alias
+		}
+		n.types[key] = metric.DataType
+		copied.Alias, copied.HasAlias = alias, true
+		defined = append(defined, &copied)
+	}
+	return defined
+}
+
+func (n *SparkplugEdgeNode) nextPayload(metrics []*SparkplugMetric) *SparkplugPayload {
+	n.seq = (n.seq + 1) % 256
+	return &SparkplugPayload{Timestamp: sparkplugNow(), Metrics: metrics, Seq: n.seq, HasSeq: true}
+}
+
+func (n *SparkplugEdgeNode) bdSeqMetric() *SparkplugMetric {
+	return &SparkplugMetric{Name: SparkplugBdSeqMetric, DataType: SparkplugUInt64, Value: n.bdSeq}
+}
+
+func (n *SparkplugEdgeNode) topic(messageType SparkplugMessageType, deviceID string) string {
+	topic := &SparkplugTopic{GroupID: n.GroupID, MessageType: messageType, EdgeNodeID: n.EdgeNodeID, DeviceID: deviceID}
+	return topic.String()
+}
+
+func (n *SparkplugEdgeNode) send(messageType SparkplugMessageType, deviceID string, payload *SparkplugPayload) error {
+	data, err := payload.Marshal()
+	if err != nil {
+		return err
+	}
+	return n.publish(n.topic(messageType, deviceID), data)
+}
+
+func sparkplugNow() uint64 {
+	return uint64(time.Now().UnixMilli())
+}
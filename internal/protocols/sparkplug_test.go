@@ -0,0 +1,188 @@
+package protocols
+
+import (
+	"testing"
+
+	"github.com/stretchr/testify/assert"
+	"go.uber.org/zap"
+
+	"github.com/bifrost/go-gateway/internal/ratelimit"
+)
+
+func TestSparkplugPayload_RoundTrip(t *testing.T) {
+	payload := &SparkplugPayload{
+		Timestamp: 1700000000000,
+		Seq:       42,
+		HasSeq:    true,
+		Metrics: []*SparkplugMetric{
+			{Name: "int8", Alias: 1, HasAlias: true, DataType: SparkplugInt8, Value: int8(-5)},
+			{Name: "int32", DataType: SparkplugInt32, Value: int32(-100000)},
+			{Name: "int64", DataType: SparkplugInt64, Value: int64(-1)},
+			{Name: "uint16", DataType: SparkplugUInt16, Value: uint16(65535)},
+			{Name: "float", DataType: SparkplugFloat, Value: float32(1.5)},
+			{Name: "double", DataType: SparkplugDouble, Value: 2.25, Timestamp: 1700000000001},
+			{Name: "bool", DataType: SparkplugBoolean, Value: true},
+			{Name: "string", DataType: SparkplugString, Value: "hello"},
+			{Name: "bytes", DataType: SparkplugBytes, Value: []byte{0x01, 0x02}},
+			{Name: "null", DataType: SparkplugDouble, IsNull: true},
+		},
+	}
+
+	data, err := payload.Marshal()
+	assert.NoError(t, err)
+
+	decoded, err := UnmarshalSparkplugPayload(data)
+	assert.NoError(t, err)
+	assert.Equal(t, payload, decoded)
+}
+
+func TestSparkplugPayload_Errors(t *testing.T) {
+	_, err := (&SparkplugPayload{Metrics: []*SparkplugMetric{{Name: "x", DataType: SparkplugInt8, Value: 200}}}).Marshal()
+	assert.Error(t, err)
+
+	_, err = (&SparkplugPayload{Metrics: []*SparkplugMetric{{Name: "x", DataType: SparkplugBoolean, Value: "yes"}}}).Marshal()
+	assert.Error(t, err)
+
+	// Truncated metric
+	_, err = UnmarshalSparkplugPayload([]byte{0x12, 0x05, 0x0A, 0x01})
+	assert.Error(t, err)
+}
+
+func TestParseSparkplugTopic(t *testing.T) {
+	topic, err := ParseSparkplugTopic("spBv1.0/plant/DDATA/edge1/pump")
+	assert.NoError(t, err)
+	assert.Equal(t, &SparkplugTopic{GroupID: "plant", MessageType: SparkplugDData, EdgeNodeID: "edge1", DeviceID: "pump"}, topic)
+	assert.Equal(t, "spBv1.0/plant/DDATA/edge1/pump", topic.String())
+
+	topic, err = ParseSparkplugTopic("spBv1.0/plant/NBIRTH/edge1")
+	assert.NoError(t, err)
+	assert.Equal(t, "", topic.DeviceID)
+
+	for _, invalid := range []string{"spAv1.0/plant/NDATA/edge1", "spBv1.0/plant/NDATA", "spBv1.0/plant/DDATA/edge1", "spBv1.0/plant/NDATA/edge1/pump"} {
+		_, err = ParseSparkplugTopic(invalid)
+		assert.Error(t, err, invalid)
+	}
+}
+
+// sparkplugLink connects an edge node to a host without a broker
+func sparkplugLink(t *testing.T) (*SparkplugEdgeNode, *SparkplugHost, map[string]*Tag, *int) {
+	host := NewSparkplugHost(zap.NewNop())
+	tags := make(map[string]*Tag)
+	rebirths := 0
+	host.OnUpdate = func(update *SparkplugUpdate) {
+		tags[update.DeviceID+"/"+update.Tag.Name] = update.Tag
+	}
+	host.RequestRebirth = func(groupID, edgeNodeID string) {
+		assert.Equal(t, "plant", groupID)
+		assert.Equal(t, "edge1", edgeNodeID)
+		rebirths++
+	}
+
+	node := NewSparkplugEdgeNode("plant", "edge1", func(topic string, payload []byte) error {
+		return host.HandleMessage(topic, payload)
+	})
+	return node, host, tags, &rebirths
+}
+
+func TestSparkplug_BirthDataDeath(t *testing.T) {
+	node, host, tags, rebirths := sparkplugLink(t)
+
+	willTopic, will, err := node.NewSession()
+	assert.NoError(t, err)
+	assert.Equal(t, "spBv1.0/plant/NDEATH/edge1", willTopic)
+
+	assert.NoError(t, node.Birth([]*SparkplugMetric{{Name: "Uptime", DataType: SparkplugUInt64, Value: uint64(10)}}))
+	assert.NoError(t, node.DeviceBirth("pump", []*SparkplugMetric{
+		{Name: "Speed", DataType: SparkplugInt16, Value: int16(0)},
+		{Name: "Running", DataType: SparkplugBoolean, Value: false},
+	}))
+	assert.Equal(t, QualityGood, tags["pump/Speed"].Quality)
+
+	// Data travels by alias and is resolved against the birth
+	assert.NoError(t, node.Data("pump", []*SparkplugMetric{{Name: "Speed", Value: int16(-120)}}))
+	tag, exists := host.Tag("plant", "edge1", "pump", "Speed")
+	assert.True(t, exists)
+	assert.Equal(t, int16(-120), tag.Value)
+	assert.Equal(t, string(DataTypeInt16), tag.DataType)
+
+	assert.Error(t, node.Data("pump", []*SparkplugMetric{{Name: "Unknown", Value: 1}}))
+
+	assert.NoError(t, node.DeviceDeath("pump"))
+	assert.Equal(t, QualityStale, tags["pump/Speed"].Quality)
+	assert.Equal(t, QualityGood, tags["/Uptime"].Quality)
+
+	// The will published by the broker when the session drops
+	assert.NoError(t, host.HandleMessage(willTopic, will))
+	assert.Equal(t, QualityStale, tags["/Uptime"].Quality)
+	assert.False(t, host.Nodes()[0].Online)
+	assert.Equal(t, 0, *rebirths)
+}
+
+func TestSparkplug_SequenceGapRequestsRebirth(t *testing.T) {
+	node, host, _, rebirths := sparkplugLink(t)
+
+	_, _, err := node.NewSession()
+	assert.NoError(t, err)
+	assert.NoError(t, node.Birth([]*SparkplugMetric{{Name: "Level", DataType: SparkplugDouble, Value: 1.0}}))
+
+	node.seq += 2 // Lose two messages
+	assert.NoError(t, node.Data("", []*SparkplugMetric{{Name: "Level", Value: 2.0}}))
+	assert.Equal(t, 1, *rebirths)
+
+	// The value is still applied; the rebirth resynchronises the node
+	tag, _ := host.Tag("plant", "edge1", "", "Level")
+	assert.Equal(t, 2.0, tag.Value)
+	assert.Equal(t, uint64(1), host.Nodes()[0].Rebirths)
+}
+
+func TestSparkplug_RateLimit(t *testing.T) {
+	node, host, _, _ := sparkplugLink(t)
+
+	_, _, err := node.NewSession()
+	assert.NoError(t, err)
+	assert.NoError(t, node.Birth([]*SparkplugMetric{{Name: "Level", DataType: SparkplugDouble, Value: 1.0}}))
+
+	host.Limiter, err = ratelimit.NewLimiter(ratelimit.Config{Default: ratelimit.Limit{PointsPerSecond: 1}})
+	assert.NoError(t, err)
+	assert.NoError(t, node.Data("", []*SparkplugMetric{{Name: "Level", Value: 2.0}}))
+	assert.ErrorIs(t, node.Data("", []*SparkplugMetric{{Name: "Level", Value: 3.0}}), ratelimit.ErrThrottled)
+
+	tag, _ := host.Tag("plant", "edge1", "", "Level")
+	assert.Equal(t, 2.0, tag.Value)
+	assert.Equal(t, uint64(1), host.Limiter.Stats()["plant/edge1"].Throttled)
+}
+
+func TestSparkplug_StaleDeathIgnored(t *testing.T) {
+	node, host, _, _ := sparkplugLink(t)
+
+	_, oldWill, _ := node.NewSession()
+	assert.NoError(t, node.Birth(nil))
+
+	// A new session is born before the broker delivers the old will
+	_, _, _ = node.NewSession()
+	assert.NoError(t, node.Birth(nil))
+	assert.Equal(t, uint64(1), host.Nodes()[0].BdSeq)
+
+	assert.NoError(t, host.HandleMessage("spBv1.0/plant/NDEATH/edge1", oldWill))
+	assert.True(t, host.Nodes()[0].Online)
+}
+
+func TestSparkplug_DataWithoutBirth(t *testing.T) {
+	host := NewSparkplugHost(zap.NewNop())
+	requested := false
+	host.RequestRebirth = func(string, string) { requested = true }
+
+	data, _ := (&SparkplugPayload{Seq: 3, HasSeq: true, Metrics: []*SparkplugMetric{{Alias: 0, HasAlias: true, DataType: SparkplugUInt64, Value: uint64(1)}}}).Marshal()
+	assert.Error(t, host.HandleMessage("spBv1.0/plant/NDATA/edge1", data))
+	assert.True(t, requested)
+}
+
+func TestIsRebirthRequest(t *testing.T) {
+	topic, data, err := RebirthCommand("plant", "edge1")
+	assert.NoError(t, err)
+	assert.Equal(t, "spBv1.0/plant/NCMD/edge1", topic)
+
+	payload, err := UnmarshalSparkplugPayload(data)
+	assert.NoError(t, err)
+	assert.True(t, IsRebirthRequest(payload))
+}
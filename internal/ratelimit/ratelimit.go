@@ -0,0 +1,284 @@
+// Package ratelimit limits the rate at which sources may ingest data
+package ratelimit
+
+import (
+	"errors"
+	"fmt"
+	"math"
+	"sync"
+	"time"
+)
+
+// Rate Limiting
+//
+// Every source, e.g. a device, a Sparkplug edge node or an API client,
+// has a token bucket for points and one for bytes that refill at the
+// configured rate and hold up to rate × burst. A write consumes from both
+// buckets or, if either holds too little, is rejected as a whole with a
+// ThrottledError, so a misbehaving publisher is slowed down without
+// starving the others. A write larger than a bucket passes when the
+// bucket is full.
+
+const (
+	defaultBurst = time.Second
+
+	// Buckets that refilled completely are forgotten after this long
+	sweepInterval = time.Minute
+)
+
+// ErrThrottled matches every ThrottledError
+var ErrThrottled = errors.New("rate limit exceeded")
+
+// ThrottledError is returned for a write exceeding a rate limit
+type ThrottledError struct {
+	Source     string
+	Limit      string        // "points" or "bytes"
+	RetryAfter time.Duration // When the write would pass
+}
+
+func (e *ThrottledError) Error() string {
+	return fmt.Sprintf("%s: %s rate limit exceeded, retry after %v", e.Source, e.Limit, e.RetryAfter)
+}
+
+// Is makes errors.Is(err, ErrThrottled) match
+func (e *ThrottledError) Is(target error) bool {
+	return target == ErrThrottled
+}
+
+// Limit is the rate limit of a source
+type Limit struct {
+	PointsPerSecond float64       `yaml:"points_per_second"` // 0 for unlimited
+	BytesPerSecond  float64       `yaml:"bytes_per_second"`  // 0 for unlimited
+	Burst           time.Duration `yaml:"burst"`             // Bucket size in time at the rate, default 1s
+}
+
+// LimitFromMap reads a Limit from a device configuration entry, e.g.
+// device.Config["rate_limit"]. The burst is a duration string such as
+// "5s".
+func LimitFromMap(values map[string]interface{}) (Limit, error) {
+	var limit Limit
+	rates := map[string]*float64{
+		"points_per_second": &limit.PointsPerSecond,
+		"bytes_per_second":  &limit.BytesPerSecond,
+	}
+	for key, field := range rates {
+		switch value := values[key].(type) {
+		case nil:
+		case int:
+			*field = float64(value)
+		case float64:
+			*field = value
+		default:
+			return limit, fmt.Errorf("rate_limit %s must be a number", key)
+		}
+	}
+
+	if value, exists := values["burst"]; exists {
+		str, ok := value.(string)
+		if !ok {
+			return limit, fmt.Errorf("rate_limit burst must be a duration string")
+		}
+		burst, err := time.ParseDuration(str)
+		if err != nil {
+			return limit, fmt.Errorf("rate_limit burst: %w", err)
+		}
+		limit.Burst = burst
+	}
+	return limit, nil
+}
+
+// Config configures the limits of all sources
+type Config struct {
+	Default Limit            `yaml:"default"`
+	Sources map[string]Limit `yaml:"sources"` // Overrides by source name
+}
+
+// Stats are the counters of a source
+type Stats struct {
+	Allowed   uint64 `json:"allowed"`
+	Throttled uint64 `json:"throttled"`
+}
+
+// bucket is a token bucket; a zero rate never limits
+type bucket struct {
+	rate     float64
+	capacity float64
+	tokens   float64
+}
+
+func newBucket(rate float64, burst time.Duration) bucket {
+	capacity := rate * burst.Seconds()
+	return bucket{rate: rate, capacity: capacity, tokens: capacity}
+}
+
+func (b *bucket) refill(elapsed time.Duration) {
+	b.tokens = math.Min(b.capacity, b.tokens+b.rate*elapsed.Seconds())
+}
+
+// wait returns how long until n tokens are available, 0 if they are
+func (b *bucket) wait(n float64) time.Duration {
+	if b.rate == 0 || b.tokens >= n || b.tokens >= b.capacity {
+		return 0
+	}
+	need := math.Min(n, b.capacity) - b.tokens
+	return time.Duration(need / b.rate * float64(time.Second))
+}
+
+func (b *bucket) take(n float64) {
+	if b.rate != 0 {
+		b.tokens -= n
+	}
+}
+
+func (b *bucket) full() bool {
+	return b.tokens >= b.capacity
+}
+
+// source is the state of one source
+type source struct {
+	points  bucket
+	bytes   bucket
+	updated time.Time
+	stats   Stats
+}
+
+// Limiter enforces rate limits per source
+type Limiter struct {
+	config Config
+	now    func() time.Time
+
+	mutex   sync.Mutex
+	sources map[string]*source
+	swept   time.Time
+}
+
+// NewLimiter validates the configuration
+func NewLimiter(config Config) (*Limiter, error) {
+	limits := map[string]Limit{"default": config.Default}
+	for name, limit := range config.Sources {
+		limits[name] = limit
+	}
+	for name, limit := range limits {
+		if limit.PointsPerSecond < 0 || limit.BytesPerSecond < 0 || limit.Burst < 0 {
+			return nil, fmt.Errorf("rate limit %s: rates and burst must not be negative", name)
+		}
+	}
+
+	return &Limiter{
+		config:  config,
+		now:     time.Now,
+		sources: make(map[string]*source),
+	}, nil
+}
+
+// Allow records a write of points and bytes by a source, or returns a
+// ThrottledError if it exceeds the source's limit. A nil limiter allows
+// everything.
+func (l *Limiter) Allow(name string, points, bytes int) error {
+	if l == nil {
+		return nil
+	}
+
+	l.mutex.Lock()
+	defer l.mutex.Unlock()
+
+	now := l.now()
+	l.sweep(now)
+	s := l.source(name, now)
+	s.points.refill(now.Sub(s.updated))
+	s.bytes.refill(now.Sub(s.updated))
+	s.updated = now
+
+	if wait := s.points.wait(float64(points)); wait > 0 {
+		s.stats.Throttled++
+		return &ThrottledError{Source: name, Limit: "points", RetryAfter: wait}
+	}
+	if wait := s.bytes.wait(float64(bytes)); wait > 0 {
+		s.stats.Throttled++
+		return &ThrottledError{Source: name, Limit: "bytes", RetryAfter: wait}
+	}
+	s.points.take(float64(points))
+	s.bytes.take(float64(bytes))
+	s.stats.Allowed++
+	return nil
+}
+
+// Charge records points and bytes a source has already written, e.g. data
+// whose size is only known once it has been read. It never rejects: a
+// source over its limit is left in debt, which throttles its next writes
+// until the buckets have refilled.
+func (l *Limiter) Charge(name string, points, bytes int) {
+	if l == nil {
+		return
+	}
+
+	l.mutex.Lock()
+	defer l.mutex.Unlock()
+
+	now := l.now()
+	l.sweep(now)
+	s := l.source(name, now)
+	s.points.refill(now.Sub(s.updated))
+	s.bytes.refill(now.Sub(s.updated))
+	s.updated = now
+	s.points.take(float64(points))
+	s.bytes.take(float64(bytes))
+}
+
+// Stats returns the counters of the sources active within the last
+// minute or so
+func (l *Limiter) Stats() map[string]Stats {
+	if l == nil {
+		return nil
+	}
+
+	l.mutex.Lock()
+	defer l.mutex.Unlock()
+
+	stats := make(map[string]Stats, len(l.sources))
+	for name, s := range l.sources {
+		stats[name] = s.stats
+	}
+	return stats
+}
+
+func (l *Limiter) source(name string, now time.Time) *source {
+	if s, exists := l.sources[name]; exists {
+		return s
+	}
+
+	limit, exists := l.config.Sources[name]
+	if !exists {
+		limit = l.config.Default
+	}
+	burst := limit.Burst
+	if burst == 0 {
+		burst = defaultBurst
+	}
+	s := &source{
+		points:  newBucket(limit.PointsPerSecond, burst),
+		bytes:   newBucket(limit.BytesPerSecond, burst),
+		updated: now,
+	}
+	l.sources[name] = s
+	return s
+}
+
+// sweep forgets sources whose buckets refilled, as a new bucket is full
+// too, so sources such as API clients do not accumulate
+func (l *Limiter) sweep(now time.Time) {
+	if now.Sub(l.swept) < sweepInterval {
+		return
+	}
+	l.swept = now
+
+	for name, s := range l.sources {
+		elapsed := now.Sub(s.updated)
+		s.points.refill(elapsed)
+		s.bytes.refill(elapsed)
+		s.updated = now
+		if s.points.full() && s.bytes.full() {
+			delete(l.sources, name)
+		}
+	}
+}
@@ -0,0 +1,101 @@
+package ratelimit
+
+import (
+	"errors"
+	"testing"
+	"time"
+
+	"github.com/stretchr/testify/assert"
+)
+
+// testLimiter returns a limiter with a manually advanced clock
+func testLimiter(t *testing.T, config Config) (*Limiter, *time.Time) {
+	limiter, err := NewLimiter(config)
+	assert.NoError(t, err)
+	now := time.Unix(1700000000, 0)
+	limiter.now = func() time.Time { return now }
+	return limiter, &now
+}
+
+func TestLimiter_Points(t *testing.T) {
+	limiter, now := testLimiter(t, Config{Default: Limit{PointsPerSecond: 100}})
+
+	// The bucket holds one second of points
+	assert.NoError(t, limiter.Allow("site/pump", 60, 0))
+	assert.NoError(t, limiter.Allow("site/pump", 40, 0))
+	err := limiter.Allow("site/pump", 10, 0)
+	assert.True(t, errors.Is(err, ErrThrottled))
+	var throttled *ThrottledError
+	assert.True(t, errors.As(err, &throttled))
+	assert.Equal(t, &ThrottledError{Source: "site/pump", Limit: "points", RetryAfter: 100 * time.Millisecond}, throttled)
+
+	// Other sources have their own bucket
+	assert.NoError(t, limiter.Allow("site/valve", 100, 0))
+
+	*now = now.Add(100 * time.Millisecond)
+	assert.NoError(t, limiter.Allow("site/pump", 10, 0))
+	assert.Error(t, limiter.Allow("site/pump", 1, 0))
+
+	assert.Equal(t, Stats{Allowed: 3, Throttled: 2}, limiter.Stats()["site/pump"])
+}
+
+func TestLimiter_Bytes(t *testing.T) {
+	limiter, now := testLimiter(t, Config{
+		Default: Limit{BytesPerSecond: 1000, Burst: 2 * time.Second},
+		Sources: map[string]Limit{"plant/edge-01": {}},
+	})
+
+	assert.NoError(t, limiter.Allow("plant/edge-02", 1, 2000))
+	err := limiter.Allow("plant/edge-02", 1, 500)
+	assert.Error(t, err)
+	assert.Contains(t, err.Error(), "bytes rate limit exceeded")
+
+	// A write larger than the bucket passes when it is full and the debt
+	// is paid off before the next write passes
+	*now = now.Add(2 * time.Second)
+	assert.NoError(t, limiter.Allow("plant/edge-02", 1, 5000))
+	*now = now.Add(2 * time.Second)
+	assert.Error(t, limiter.Allow("plant/edge-02", 1, 1))
+	*now = now.Add(3 * time.Second)
+	assert.NoError(t, limiter.Allow("plant/edge-02", 1, 1))
+
+	// Sources without limits are never throttled
+	for i := 0; i < 100; i++ {
+		assert.NoError(t, limiter.Allow("plant/edge-01", 1000, 1000000))
+	}
+}
+
+func TestLimiter_Charge(t *testing.T) {
+	limiter, now := testLimiter(t, Config{Default: Limit{BytesPerSecond: 1000}})
+
+	// Data read before its size is known leaves the source in debt
+	assert.NoError(t, limiter.Allow("site/pump", 10, 0))
+	limiter.Charge("site/pump", 0, 3000)
+	err := limiter.Allow("site/pump", 10, 0)
+	var throttled *ThrottledError
+	assert.True(t, errors.As(err, &throttled))
+	assert.Equal(t, "bytes", throttled.Limit)
+	assert.Equal(t, 2*time.Second, throttled.RetryAfter)
+
+	*now = now.Add(2 * time.Second)
+	assert.NoError(t, limiter.Allow("site/pump", 10, 0))
+
+	var unlimited *Limiter
+	unlimited.Charge("site/pump", 10, 3000)
+}
+
+func TestLimiter_Sweep(t *testing.T) {
+	limiter, now := testLimiter(t, Config{Default: Limit{PointsPerSecond: 10}})
+	assert.NoError(t, limiter.Allow("10.0.0.1", 10, 0))
+	assert.Error(t, limiter.Allow("10.0.0.1", 10, 0))
+
+	*now = now.Add(2 * sweepInterval)
+	assert.NoError(t, limiter.Allow("10.0.0.2", 1, 0))
+	assert.Len(t, limiter.Stats(), 1)
+
+	var nilLimiter *Limiter
+	assert.NoError(t, nilLimiter.Allow("10.0.0.1", 1, 1))
+
+	_, err := NewLimiter(Config{Sources: map[string]Limit{"a": {PointsPerSecond: -1}}})
+	assert.Error(t, err)
+}
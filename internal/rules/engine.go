@@ -0,0 +1,464 @@
+package rules
+
+import (
+	"context"
+	"encoding/json"
+	"fmt"
+	"reflect"
+	"sort"
+	"sync"
+	"time"
+
+	"go.uber.org/zap"
+
+	"github.com/bifrost/go-gateway/internal/protocols"
+)
+
+// Edge Rule Engine
+//
+// An Engine evaluates rules against live tag values. It is a
+// protocols.SampleSink: it keeps the latest sample of every tag and, when
+// a sample arrives for a tag a rule references, re-evaluates the rule's
+// condition. A rule fires when its condition becomes true and fires again
+// only after the condition has been false, so a high level raises one
+// alarm rather than one per sample. Rules can be replaced at runtime.
+//
+// Actions write a tag value, raise an event, publish a message or run a
+// script through the Actuators the engine was created with. They run in
+// order on the goroutine that delivered the samples, each with a timeout.
+
+// ActionType selects what an action does
+type ActionType string
+
+const (
+	ActionWrite   ActionType = "write"
+	ActionEvent   ActionType = "event"
+	ActionPublish ActionType = "publish"
+	ActionScript  ActionType = "script"
+)
+
+// Action is done when a rule fires. Value is an expression evaluated when
+// the rule fires, e.g. "Setpoint - 5" or "true".
+type Action struct {
+	Type     ActionType `json:"type" yaml:"type"`
+	Device   string     `json:"device,omitempty" yaml:"device,omitempty"`     // write
+	Tag      string     `json:"tag,omitempty" yaml:"tag,omitempty"`           // write
+	Value    string     `json:"value,omitempty" yaml:"value,omitempty"`       // write
+	Event    string     `json:"event,omitempty" yaml:"event,omitempty"`       // event
+	Severity string     `json:"severity,omitempty" yaml:"severity,omitempty"` // event
+	Message  string     `json:"message,omitempty" yaml:"message,omitempty"`   // event
+	Topic    string     `json:"topic,omitempty" yaml:"topic,omitempty"`       // publish
+	Script   string     `json:"script,omitempty" yaml:"script,omitempty"`     // script
+}
+
+// Rule is a condition and the actions done when it becomes true
+type Rule struct {
+	ID          string   `json:"id" yaml:"id"`
+	Description string   `json:"description,omitempty" yaml:"description,omitempty"`
+	Condition   string   `json:"condition" yaml:"condition"`
+	Actions     []Action `json:"actions" yaml:"actions"`
+	Disabled    bool     `json:"disabled,omitempty" yaml:"disabled,omitempty"`
+}
+
+// Firing describes a rule whose condition became true
+type Firing struct {
+	Rule   string                 `json:"rule"`
+	Time   time.Time              `json:"time"`
+	Values map[string]interface{} `json:"values"` // Tags referenced by the condition
+}
+
+// Event is raised by an event action
+type Event struct {
+	Name     string `json:"name"`
+	Severity string `json:"severity,omitempty"`
+	Message  string `json:"message,omitempty"`
+	Firing
+}
+
+// TagWriter writes a value to a device tag
+type TagWriter interface {
+	WriteTag(ctx context.Context, device, tag string, value interface{}) error
+}
+
+// EventSink receives events raised by rules
+type EventSink interface {
+	RaiseEvent(event Event) error
+}
+
+// Publisher publishes a message, e.g. to an MQTT broker
+type Publisher interface {
+	Publish(ctx context.Context, topic string, payload []byte) error
+}
+
+// ScriptRunner runs a named script for a firing rule
+type ScriptRunner interface {
+	RunScript(ctx context.Context, name string, firing Firing) error
+}
+
+// Actuators carry out actions. Rules with actions whose actuator is nil are
+// rejected.
+type Actuators struct {
+	Writer    TagWriter
+	Events    EventSink
+	Publisher Publisher
+	Scripts   ScriptRunner
+}
+
+// RuleStatus is a rule and its evaluation state
+type RuleStatus struct {
+	Rule
+	Active    bool      `json:"active"`
+	Fired     uint64    `json:"fired"`
+	LastFired time.Time `json:"last_fired,omitempty"`
+	LastError string    `json:"last_error,omitempty"`
+}
+
+type compiledRule struct {
+	status    RuleStatus
+	condition *Expression
+	values    []*Expression // Per action, nil without a value
+}
+
+// firing is a rule that fired with its action values
+type firing struct {
+	rule   *compiledRule
+	firing Firing
+	values []interface{}
+}
+
+// sampleEnv resolves tags from the latest samples
+type sampleEnv map[string]protocols.TagSample
+
+func (e sampleEnv) Sample(tag string) (protocols.TagSample, bool) {
+	sample, exists := e[tag]
+	return sample, exists
+}
+
+// Engine evaluates rules against tag samples
+type Engine struct {
+	actuators Actuators
+	timeout   time.Duration
+	logger    *zap.Logger
+	clock     protocols.Clock
+
+	mutex   sync.Mutex
+	rules   map[string]*compiledRule
+	samples sampleEnv
+}
+
+// NewEngine creates an engine without rules. Each action may take up to
+// timeout; 0 means 5 seconds.
+func NewEngine(actuators Actuators, timeout time.Duration, logger *zap.Logger) *Engine {
+	if timeout <= 0 {
+		timeout = 5 * time.Second
+	}
+	return &Engine{
+		actuators: actuators,
+		timeout:   timeout,
+		logger:    logger,
+		clock:     protocols.DefaultClock,
+		rules:     make(map[string]*compiledRule),
+		samples:   make(sampleEnv),
+	}
+}
+
+// SetClock sets the clock stamping firings. It must be called before
+// samples are evaluated.
+func (e *Engine) SetClock(clock protocols.Clock) {
+	e.clock = clock
+}
+
+// Put adds a rule or replaces the rule with the same ID. A replaced rule
+// starts inactive and is evaluated with the next sample of its tags.
+func (e *Engine) Put(rule Rule) error {
+	compiled, err := e.compile(rule)
+	if err != nil {
+		return err
+	}
+
+	e.mutex.Lock()
+	defer e.mutex.Unlock()
+	e.rules[rule.ID] = compiled
+	return nil
+}
+
+// Set is a compiled set of rules, loaded with Replace
+type Set struct {
+	rules map[string]*compiledRule
+}
+
+// Load replaces all rules. Rules that did not change keep their state, so
+// an active rule does not fire again. Nothing changes if any rule is
+// invalid.
+func (e *Engine) Load(rules []Rule) error {
+	set, err := e.Compile(rules)
+	if err != nil {
+		return err
+	}
+	e.Replace(set)
+	return nil
+}
+
+// Compile compiles rules without changing the engine, so they can replace
+// its rules together with other changes
+func (e *Engine) Compile(rules []Rule) (*Set, error) {
+	compiled, err := e.compileAll(rules)
+	if err != nil {
+		return nil, err
+	}
+	return &Set{rules: compiled}, nil
+}
+
+// Replace replaces all rules with a compiled set like Load. A set is
+// replaced once.
+func (e *Engine) Replace(set *Set) {
+	e.mutex.Lock()
+	defer e.mutex.Unlock()
+	for id, c := range set.rules {
+		if current, exists := e.rules[id]; exists && reflect.DeepEqual(current.status.Rule, c.status.Rule) {
+			set.rules[id] = current
+		}
+	}
+	e.rules = set.rules
+}
+
+// Validate returns the error Load would return for rules, without
+// changing the rules
+func (e *Engine) Validate(rules []Rule) error {
+	_, err := e.compileAll(rules)
+	return err
+}
+
+func (e *Engine) compileAll(rules []Rule) (map[string]*compiledRule, error) {
+	compiled := make(map[string]*compiledRule, len(rules))
+	for _, rule := range rules {
+		if _, exists := compiled[rule.ID]; exists {
+			return nil, fmt.Errorf("duplicate rule: %s", rule.ID)
+		}
+		c, err := e.compile(rule)
+		if err != nil {
+			return nil, err
+		}
+		compiled[rule.ID] = c
+	}
+	return compiled, nil
+}
+
+// Delete removes a rule and reports whether it existed
+func (e *Engine) Delete(id string) bool {
+	e.mutex.Lock()
+	defer e.mutex.Unlock()
+
+	_, exists := e.rules[id]
+	delete(e.rules, id)
+	return exists
+}
+
+// Rules returns the rules and their state, sorted by ID
+func (e *Engine) Rules() []RuleStatus {
+	e.mutex.Lock()
+	defer e.mutex.Unlock()
+
+	statuses := make([]RuleStatus, 0, len(e.rules))
+	for _, rule := range e.rules {
+		statuses = append(statuses, rule.status)
+	}
+	sort.Slice(statuses, func(i, j int) bool { return statuses[i].ID < statuses[j].ID })
+	return statuses
+}
+
+// WriteSamples updates tag values, evaluates the rules referencing them
+// and runs the actions of rules that fire
+func (e *Engine) WriteSamples(samples []protocols.TagSample) error {
+	if len(samples) == 0 {
+		return nil
+	}
+
+	e.mutex.Lock()
+	changed := make(map[string]bool, len(samples))
+	for _, sample := range samples {
+		e.samples[sample.Name] = sample
+		changed[sample.Name] = true
+	}
+
+	var firings []firing
+	for _, rule := range e.rules {
+		if rule.status.Disabled || !references(rule.condition, changed) {
+			continue
+		}
+		if f, fired := e.evaluate(rule); fired {
+			firings = append(firings, f)
+		}
+	}
+	e.mutex.Unlock()
+
+	sort.Slice(firings, func(i, j int) bool { return firings[i].firing.Rule < firings[j].firing.Rule })
+	for _, f := range firings {
+		e.execute(f)
+	}
+	return nil
+}
+
+// evaluate updates the state of a rule and returns a firing when its
+// condition became true
+func (e *Engine) evaluate(rule *compiledRule) (firing, bool) {
+	active, err := rule.condition.EvalBool(e.samples)
+	if err != nil {
+		rule.status.LastError = err.Error()
+		rule.status.Active = false
+		return firing{}, false
+	}
+	wasActive := rule.status.Active
+	rule.status.Active = active
+	rule.status.LastError = ""
+	if !active || wasActive {
+		return firing{}, false
+	}
+
+	f := firing{
+		rule: rule,
+		firing: Firing{
+			Rule:   rule.status.ID,
+			Time:   e.clock.Now(),
+			Values: make(map[string]interface{}),
+		},
+		values: make([]interface{}, len(rule.values)),
+	}
+	for _, tag := range rule.condition.Tags() {
+		f.firing.Values[tag] = e.samples[tag].Value
+	}
+	for i, expression := range rule.values {
+		if expression == nil {
+			continue
+		}
+		value, err := expression.Eval(e.samples)
+		if err != nil {
+			rule.status.LastError = fmt.Sprintf("action %d: %v", i, err)
+			return firing{}, false
+		}
+		f.values[i] = value
+	}
+
+	rule.status.Fired++
+	rule.status.LastFired = f.firing.Time
+	return f, true
+}
+
+// execute runs the actions of a firing rule in order
+func (e *Engine) execute(f firing) {
+	for i, action := range f.rule.status.Actions {
+		ctx, cancel := context.WithTimeout(context.Background(), e.timeout)
+		err := e.run(ctx, action, f.values[i], f.firing)
+		cancel()
+		if err == nil {
+			continue
+		}
+
+		e.logger.Warn("Rule action failed",
+			zap.String("rule", f.firing.Rule),
+			zap.String("action", string(action.Type)),
+			zap.Error(err),
+		)
+		e.mutex.Lock()
+		f.rule.status.LastError = fmt.Sprintf("action %d: %v", i, err)
+		e.mutex.Unlock()
+	}
+}
+
+func (e *Engine) run(ctx context.Context, action Action, value interface{}, f Firing) error {
+	switch action.Type {
+	case ActionWrite:
+		return e.actuators.Writer.WriteTag(ctx, action.Device, action.Tag, value)
+	case ActionEvent:
+		return e.actuators.Events.RaiseEvent(Event{
+			Name:     action.Event,
+			Severity: action.Severity,
+			Message:  action.Message,
+			Firing:   f,
+		})
+	case ActionPublish:
+		payload, err := json.Marshal(f)
+		if err != nil {
+			return err
+		}
+		return e.actuators.Publisher.Publish(ctx, action.Topic, payload)
+	case ActionScript:
+		return e.actuators.Scripts.RunScript(ctx, action.Script, f)
+	}
+	return fmt.Errorf("unknown action type: %s", action.Type)
+}
+
+// compile validates a rule and compiles its expressions
+func (e *Engine) compile(rule Rule) (*compiledRule, error) {
+	if rule.ID == "" {
+		return nil, fmt.Errorf("rule ID is required")
+	}
+	condition, err := Compile(rule.Condition)
+	if err != nil {
+		return nil, fmt.Errorf("rule %s: condition: %w", rule.ID, err)
+	}
+	if len(condition.Tags()) == 0 {
+		return nil, fmt.Errorf("rule %s: condition references no tags", rule.ID)
+	}
+
+	compiled := &compiledRule{
+		status:    RuleStatus{Rule: rule},
+		condition: condition,
+		values:    make([]*Expression, len(rule.Actions)),
+	}
+	for i, action := range rule.Actions {
+		if err := e.validateAction(action); err != nil {
+			return nil, fmt.Errorf("rule %s: action %d: %w", rule.ID, i, err)
+		}
+		if action.Type != ActionWrite {
+			continue
+		}
+		compiled.values[i], err = Compile(action.Value)
+		if err != nil {
+			return nil, fmt.Errorf("rule %s: action %d: value: %w", rule.ID, i, err)
+		}
+	}
+	return compiled, nil
+}
+
+func (e *Engine) validateAction(action Action) error {
+	var configured bool
+	switch action.Type {
+	case ActionWrite:
+		if action.Device == "" || action.Tag == "" || action.Value == "" {
+			return fmt.Errorf("write needs a device, tag and value")
+		}
+		configured = e.actuators.Writer != nil
+	case ActionEvent:
+		if action.Event == "" {
+			return fmt.Errorf("event needs a name")
+		}
+		configured = e.actuators.Events != nil
+	case ActionPublish:
+		if action.Topic == "" {
+			return fmt.Errorf("publish needs a topic")
+		}
+		configured = e.actuators.Publisher != nil
+	case ActionScript:
+		if action.Script == "" {
+			return fmt.Errorf("script needs a name")
+		}
+		configured = e.actuators.Scripts != nil
+	default:
+		return fmt.Errorf("unknown action type: %s", action.Type)
+	}
+	if !configured {
+		return fmt.Errorf("%s actions are not available", action.Type)
+	}
+	return nil
+}
+
+// references reports whether an expression references a changed tag
+func references(expression *Expression, changed map[string]bool) bool {
+	for _, tag := range expression.Tags() {
+		if changed[tag] {
+			return true
+		}
+	}
+	return false
+}
@@ -0,0 +1,202 @@
+package rules
+
+import (
+	"context"
+	"encoding/json"
+	"errors"
+	"sync"
+	"testing"
+	"time"
+
+	"github.com/stretchr/testify/assert"
+	"go.uber.org/zap"
+
+	"github.com/bifrost/go-gateway/internal/protocols"
+)
+
+type testWrite struct {
+	device, tag string
+	value       interface{}
+}
+
+// testActuators records the actions done by rules
+type testActuators struct {
+	mutex    sync.Mutex
+	writes   []testWrite
+	events   []Event
+	messages map[string][]byte
+	scripts  []string
+	err      error
+}
+
+func (a *testActuators) WriteTag(ctx context.Context, device, tag string, value interface{}) error {
+	a.mutex.Lock()
+	defer a.mutex.Unlock()
+	a.writes = append(a.writes, testWrite{device: device, tag: tag, value: value})
+	return a.err
+}
+
+func (a *testActuators) RaiseEvent(event Event) error {
+	a.mutex.Lock()
+	defer a.mutex.Unlock()
+	a.events = append(a.events, event)
+	return nil
+}
+
+func (a *testActuators) Publish(ctx context.Context, topic string, payload []byte) error {
+	a.mutex.Lock()
+	defer a.mutex.Unlock()
+	if a.messages == nil {
+		a.messages = make(map[string][]byte)
+	}
+	a.messages[topic] = payload
+	return nil
+}
+
+func (a *testActuators) RunScript(ctx context.Context, name string, firing Firing) error {
+	a.mutex.Lock()
+	defer a.mutex.Unlock()
+	a.scripts = append(a.scripts, name)
+	return nil
+}
+
+func newTestEngine(actuators *testActuators) *Engine {
+	return NewEngine(Actuators{
+		Writer:    actuators,
+		Events:    actuators,
+		Publisher: actuators,
+		Scripts:   actuators,
+	}, 0, zap.NewNop())
+}
+
+func level(value float64) []protocols.TagSample {
+	return []protocols.TagSample{{Name: "Level", Value: value, Quality: protocols.QualityGood}}
+}
+
+func TestEngine_FiresOnRisingEdge(t *testing.T) {
+	actuators := &testActuators{}
+	engine := newTestEngine(actuators)
+	clock := protocols.NewManualClock(time.Date(2024, 1, 1, 0, 0, 0, 0, time.UTC))
+	engine.SetClock(clock)
+	assert.NoError(t, engine.Put(Rule{
+		ID:        "high-level",
+		Condition: "Level > 90",
+		Actions: []Action{
+			{Type: ActionWrite, Device: "site/tank", Tag: "InletValve", Value: "false"},
+			{Type: ActionEvent, Event: "HighLevel", Severity: "warning", Message: "Tank level high"},
+			{Type: ActionPublish, Topic: "alarms/tank"},
+			{Type: ActionScript, Script: "notify"},
+		},
+	}))
+
+	for _, value := range []float64{50, 95, 97, 80, 92} {
+		clock.Advance(time.Second)
+		assert.NoError(t, engine.WriteSamples(level(value)))
+	}
+
+	assert.Equal(t, []testWrite{
+		{device: "site/tank", tag: "InletValve", value: false},
+		{device: "site/tank", tag: "InletValve", value: false},
+	}, actuators.writes)
+	assert.Len(t, actuators.events, 2)
+	assert.Equal(t, "HighLevel", actuators.events[0].Name)
+	assert.Equal(t, "high-level", actuators.events[0].Rule)
+	assert.Equal(t, 95.0, actuators.events[0].Values["Level"])
+	assert.Equal(t, []string{"notify", "notify"}, actuators.scripts)
+
+	var published Firing
+	assert.NoError(t, json.Unmarshal(actuators.messages["alarms/tank"], &published))
+	assert.Equal(t, 92.0, published.Values["Level"])
+
+	statuses := engine.Rules()
+	assert.Len(t, statuses, 1)
+	assert.True(t, statuses[0].Active)
+	assert.Equal(t, uint64(2), statuses[0].Fired)
+	assert.Equal(t, clock.Now(), statuses[0].LastFired)
+	assert.WithinDuration(t, clock.Now(), published.Time, 0)
+}
+
+func TestEngine_ValueExpressionsAndErrors(t *testing.T) {
+	actuators := &testActuators{}
+	engine := newTestEngine(actuators)
+	assert.NoError(t, engine.Put(Rule{
+		ID:        "trim",
+		Condition: "Level > Setpoint",
+		Actions:   []Action{{Type: ActionWrite, Device: "pump", Tag: "Speed", Value: "Setpoint - 5"}},
+	}))
+
+	// Setpoint is not known yet
+	assert.NoError(t, engine.WriteSamples(level(80)))
+	assert.Contains(t, engine.Rules()[0].LastError, "unknown tag: Setpoint")
+
+	assert.NoError(t, engine.WriteSamples([]protocols.TagSample{{Name: "Setpoint", Value: int32(70)}}))
+	assert.Equal(t, []testWrite{{device: "pump", tag: "Speed", value: 65.0}}, actuators.writes)
+
+	// Failed actions are reported in the rule state
+	actuators.err = errors.New("device not connected")
+	assert.NoError(t, engine.WriteSamples(level(60)))
+	assert.NoError(t, engine.WriteSamples(level(75)))
+	assert.Contains(t, engine.Rules()[0].LastError, "device not connected")
+}
+
+func TestEngine_RuntimeChanges(t *testing.T) {
+	actuators := &testActuators{}
+	engine := newTestEngine(actuators)
+	rule := Rule{ID: "high", Condition: "Level > 90", Actions: []Action{{Type: ActionScript, Script: "a"}}}
+
+	assert.NoError(t, engine.Put(rule))
+	rule.Disabled = true
+	assert.NoError(t, engine.Put(rule))
+	assert.NoError(t, engine.WriteSamples(level(95)))
+	assert.Empty(t, actuators.scripts)
+
+	assert.NoError(t, engine.Load([]Rule{
+		{ID: "b", Condition: "Level > 90", Actions: []Action{{Type: ActionScript, Script: "b"}}},
+		{ID: "a", Condition: "Level > 50", Actions: []Action{{Type: ActionScript, Script: "a"}}},
+	}))
+	assert.NoError(t, engine.WriteSamples(level(96)))
+	assert.Equal(t, []string{"a", "b"}, actuators.scripts)
+
+	assert.True(t, engine.Delete("a"))
+	assert.False(t, engine.Delete("a"))
+	assert.Len(t, engine.Rules(), 1)
+
+	// An invalid rule set leaves the rules unchanged
+	assert.Error(t, engine.Load([]Rule{
+		{ID: "c", Condition: "Level > 90"},
+		{ID: "c", Condition: "Level > 90"},
+	}))
+	assert.Equal(t, "b", engine.Rules()[0].ID)
+	assert.Error(t, engine.Validate([]Rule{{ID: "c", Condition: "Level >"}}))
+
+	// Reloading an unchanged rule keeps it active, so it does not fire again
+	assert.NoError(t, engine.Load([]Rule{
+		{ID: "b", Condition: "Level > 90", Actions: []Action{{Type: ActionScript, Script: "b"}}},
+	}))
+	assert.NoError(t, engine.WriteSamples(level(97)))
+	assert.Equal(t, []string{"a", "b"}, actuators.scripts)
+	assert.True(t, engine.Rules()[0].Active)
+
+	// A compiled set changes nothing until it replaces the rules
+	set, err := engine.Compile([]Rule{{ID: "c", Condition: "Level > 90"}})
+	assert.NoError(t, err)
+	assert.Equal(t, "b", engine.Rules()[0].ID)
+	engine.Replace(set)
+	assert.Equal(t, "c", engine.Rules()[0].ID)
+}
+
+func TestEngine_InvalidRules(t *testing.T) {
+	engine := NewEngine(Actuators{Events: &testActuators{}}, 0, zap.NewNop())
+
+	for _, rule := range []Rule{
+		{Condition: "Level > 1"},
+		{ID: "a", Condition: "Level >"},
+		{ID: "a", Condition: "1 > 0"},
+		{ID: "a", Condition: "Level > 1", Actions: []Action{{Type: "email"}}},
+		{ID: "a", Condition: "Level > 1", Actions: []Action{{Type: ActionEvent}}},
+		{ID: "a", Condition: "Level > 1", Actions: []Action{{Type: ActionPublish, Topic: "alarms"}}},
+	} {
+		assert.Error(t, engine.Put(rule))
+	}
+	assert.Empty(t, engine.Rules())
+}
@@ -0,0 +1,637 @@
+// Package rules evaluates declarative rules against live tag values
+package rules
+
+import (
+	"fmt"
+	"math"
+	"reflect"
+	"sort"
+	"strconv"
+	"strings"
+
+	"github.com/bifrost/go-gateway/internal/protocols"
+)
+
+// Expressions
+//
+// Rule conditions and action values are expressions over tag values, e.g.
+//
+//	Flow > 10 && (Pressure < 3 || quality(Pressure) != "GOOD")
+//
+// Tags are referenced by name. Names that are not identifiers, such as the
+// gateway's "site/line/pump/Flow", are quoted in backticks. Expressions
+// have numbers, booleans and double-quoted strings, the arithmetic,
+// comparison and logical operators of Go, and the functions abs, min, max
+// and quality. Integer tag values are compared as float64.
+
+// Env resolves tag references
+type Env interface {
+	Sample(tag string) (protocols.TagSample, bool)
+}
+
+// Expression is a compiled expression
+type Expression struct {
+	source string
+	root   node
+	tags   []string
+}
+
+// Compile parses an expression
+func Compile(source string) (*Expression, error) {
+	tokens, err := tokenize(source)
+	if err != nil {
+		return nil, err
+	}
+
+	p := &parser{tokens: tokens, tags: make(map[string]bool)}
+	root, err := p.parseOr()
+	if err != nil {
+		return nil, err
+	}
+	if t := p.peek(); t.kind != tokenEOF {
+		return nil, fmt.Errorf("position %d: unexpected %q", t.pos, t.text)
+	}
+
+	tags := make([]string, 0, len(p.tags))
+	for tag := range p.tags {
+		tags = append(tags, tag)
+	}
+	sort.Strings(tags)
+	return &Expression{source: source, root: root, tags: tags}, nil
+}
+
+// String returns the source of the expression
+func (e *Expression) String() string {
+	return e.source
+}
+
+// Tags returns the tags the expression references, sorted
+func (e *Expression) Tags() []string {
+	return e.tags
+}
+
+// Eval evaluates the expression to a float64, bool or string
+func (e *Expression) Eval(env Env) (interface{}, error) {
+	return e.root.eval(env)
+}
+
+// EvalBool evaluates an expression that must be boolean
+func (e *Expression) EvalBool(env Env) (bool, error) {
+	value, err := e.root.eval(env)
+	if err != nil {
+		return false, err
+	}
+	b, ok := value.(bool)
+	if !ok {
+		return false, fmt.Errorf("expression is %T, not bool", value)
+	}
+	return b, nil
+}
+
+type tokenKind int
+
+const (
+	tokenEOF tokenKind = iota
+	tokenNumber
+	tokenString
+	tokenIdent
+	tokenTag
+	tokenOperator
+)
+
+type token struct {
+	kind  tokenKind
+	text  string
+	value interface{} // Number or unquoted string
+	pos   int
+}
+
+var operators = []string{"&&", "||", "==", "!=", "<=", ">=", "(", ")", ",", "+", "-", "*", "/", "%", "!", "<", ">"}
+
+func tokenize(source string) ([]token, error) {
+	var tokens []token
+	i := 0
+	for i < len(source) {
+		c := source[i]
+		start := i
+		switch {
+		case c == ' ' || c == '\t' || c == '\n' || c == '\r':
+			i++
+			continue
+		case isDigit(c) || c == '.' && i+1 < len(source) && isDigit(source[i+1]):
+			i = scanNumber(source, i)
+			number, err := strconv.ParseFloat(source[start:i], 64)
+			if err != nil {
+				return nil, fmt.Errorf("position %d: invalid number %q", start, source[start:i])
+			}
+			tokens = append(tokens, token{kind: tokenNumber, text: source[start:i], value: number, pos: start})
+			continue
+		case isIdentStart(c):
+			for i < len(source) && (isIdentStart(source[i]) || isDigit(source[i]) || source[i] == '.') {
+				i++
+			}
+			tokens = append(tokens, token{kind: tokenIdent, text: source[start:i], pos: start})
+			continue
+		case c == '`':
+			end := strings.IndexByte(source[i+1:], '`')
+			if end < 0 {
+				return nil, fmt.Errorf("position %d: unterminated tag name", start)
+			}
+			i += end + 2
+			name := source[start+1 : i-1]
+			tokens = append(tokens, token{kind: tokenTag, text: source[start:i], value: name, pos: start})
+			continue
+		case c == '"':
+			i++
+			for i < len(source) && source[i] != '"' {
+				if source[i] == '\\' {
+					i++
+				}
+				i++
+			}
+			if i >= len(source) {
+				return nil, fmt.Errorf("position %d: unterminated string", start)
+			}
+			i++
+			str, err := strconv.Unquote(source[start:i])
+			if err != nil {
+				return nil, fmt.Errorf("position %d: invalid string %s", start, source[start:i])
+			}
+			tokens = append(tokens, token{kind: tokenString, text: source[start:i], value: str, pos: start})
+			continue
+		}
+
+		matched := false
+		for _, op := range operators {
+			if strings.HasPrefix(source[i:], op) {
+				tokens = append(tokens, token{kind: tokenOperator, text: op, pos: start})
+				i += len(op)
+				matched = true
+				break
+			}
+		}
+		if !matched {
+			return nil, fmt.Errorf("position %d: unexpected %q", start, c)
+		}
+	}
+	return append(tokens, token{kind: tokenEOF, pos: len(source)}), nil
+}
+
+// scanNumber returns the end of the number at i, with an optional exponent
+func scanNumber(source string, i int) int {
+	for i < len(source) && (isDigit(source[i]) || source[i] == '.') {
+		i++
+	}
+	if i < len(source) && (source[i] == 'e' || source[i] == 'E') {
+		j := i + 1
+		if j < len(source) && (source[j] == '+' || source[j] == '-') {
+			j++
+		}
+		if j < len(source) && isDigit(source[j]) {
+			i = j
+			for i < len(source) && isDigit(source[i]) {
+				i++
+			}
+		}
+	}
+	return i
+}
+
+func isDigit(c byte) bool {
+	return c >= '0' && c <= '9'
+}
+
+func isIdentStart(c byte) bool {
+	return c >= 'a' && c <= 'z' || c >= 'A' && c <= 'Z' || c == '_'
+}
+
+// functions maps function names to their number of arguments
+var functions = map[string]int{
+	"abs":     1,
+	"min":     2,
+	"max":     2,
+	"quality": 1,
+}
+
+type parser struct {
+	tokens []token
+	pos    int
+	tags   map[string]bool
+}
+
+func (p *parser) peek() token {
+	return p.tokens[p.pos]
+}
+
+func (p *parser) next() token {
+	t := p.tokens[p.pos]
+	if t.kind != tokenEOF {
+		p.pos++
+	}
+	return t
+}
+
+// accept consumes the next token if it is the operator op
+func (p *parser) accept(op string) bool {
+	if t := p.peek(); t.kind == tokenOperator && t.text == op {
+		p.pos++
+		return true
+	}
+	return false
+}
+
+func (p *parser) parseOr() (node, error) {
+	left, err := p.parseAnd()
+	if err != nil {
+		return nil, err
+	}
+	for p.accept("||") {
+		right, err := p.parseAnd()
+		if err != nil {
+			return nil, err
+		}
+		left = &binaryNode{op: "||", left: left, right: right}
+	}
+	return left, nil
+}
+
+func (p *parser) parseAnd() (node, error) {
+	left, err := p.parseComparison()
+	if err != nil {
+		return nil, err
+	}
+	for p.accept("&&") {
+		right, err := p.parseComparison()
+		if err != nil {
+			return nil, err
+		}
+		left = &binaryNode{op: "&&", left: left, right: right}
+	}
+	return left, nil
+}
+
+func (p *parser) parseComparison() (node, error) {
+	left, err := p.parseSum()
+	if err != nil {
+		return nil, err
+	}
+	for _, op := range []string{"==", "!=", "<=", ">=", "<", ">"} {
+		if p.accept(op) {
+			right, err := p.parseSum()
+			if err != nil {
+				return nil, err
+			}
+			return &binaryNode{op: op, left: left, right: right}, nil
+		}
+	}
+	return left, nil
+}
+
+func (p *parser) parseSum() (node, error) {
+	left, err := p.parseProduct()
+	if err != nil {
+		return nil, err
+	}
+	for {
+		op := p.peek().text
+		if !p.accept("+") && !p.accept("-") {
+			return left, nil
+		}
+		right, err := p.parseProduct()
+		if err != nil {
+			return nil, err
+		}
+		left = &binaryNode{op: op, left: left, right: right}
+	}
+}
+
+func (p *parser) parseProduct() (node, error) {
+	left, err := p.parseUnary()
+	if err != nil {
+		return nil, err
+	}
+	for {
+		op := p.peek().text
+		if !p.accept("*") && !p.accept("/") && !p.accept("%") {
+			return left, nil
+		}
+		right, err := p.parseUnary()
+		if err != nil {
+			return nil, err
+		}
+		left = &binaryNode{op: op, left: left, right: right}
+	}
+}
+
+func (p *parser) parseUnary() (node, error) {
+	for _, op := range []string{"!", "-"} {
+		if p.accept(op) {
+			operand, err := p.parseUnary()
+			if err != nil {
+				return nil, err
+			}
+			return &unaryNode{op: op, operand: operand}, nil
+		}
+	}
+	return p.parsePrimary()
+}
+
+func (p *parser) parsePrimary() (node, error) {
+	t := p.next()
+	switch t.kind {
+	case tokenNumber, tokenString:
+		return &literalNode{value: t.value}, nil
+	case tokenTag:
+		return p.tag(t.value.(string)), nil
+	case tokenIdent:
+		switch t.text {
+		case "true":
+			return &literalNode{value: true}, nil
+		case "false":
+			return &literalNode{value: false}, nil
+		}
+		if p.accept("(") {
+			return p.parseCall(t)
+		}
+		return p.tag(t.text), nil
+	case tokenOperator:
+		if t.text == "(" {
+			inner, err := p.parseOr()
+			if err != nil {
+				return nil, err
+			}
+			if !p.accept(")") {
+				return nil, fmt.Errorf("position %d: expected )", p.peek().pos)
+			}
+			return inner, nil
+		}
+	case tokenEOF:
+		return nil, fmt.Errorf("position %d: unexpected end of expression", t.pos)
+	}
+	return nil, fmt.Errorf("position %d: unexpected %q", t.pos, t.text)
+}
+
+func (p *parser) parseCall(name token) (node, error) {
+	arity, exists := functions[name.text]
+	if !exists {
+		return nil, fmt.Errorf("position %d: unknown function %s", name.pos, name.text)
+	}
+
+	var args []node
+	if !p.accept(")") {
+		for {
+			arg, err := p.parseOr()
+			if err != nil {
+				return nil, err
+			}
+			args = append(args, arg)
+			if p.accept(")") {
+				break
+			}
+			if !p.accept(",") {
+				return nil, fmt.Errorf("position %d: expected , or )", p.peek().pos)
+			}
+		}
+	}
+	if len(args) != arity {
+		return nil, fmt.Errorf("position %d: %s takes %d arguments, got %d", name.pos, name.text, arity, len(args))
+	}
+	if name.text == "quality" {
+		if _, ok := args[0].(*tagNode); !ok {
+			return nil, fmt.Errorf("position %d: quality takes a tag", name.pos)
+		}
+	}
+	return &callNode{name: name.text, args: args}, nil
+}
+
+func (p *parser) tag(name string) node {
+	p.tags[name] = true
+	return &tagNode{name: name}
+}
+
+type node interface {
+	eval(env Env) (interface{}, error)
+}
+
+type literalNode struct {
+	value interface{}
+}
+
+func (n *literalNode) eval(env Env) (interface{}, error) {
+	return n.value, nil
+}
+
+type tagNode struct {
+	name string
+}
+
+func (n *tagNode) sample(env Env) (protocols.TagSample, error) {
+	sample, exists := env.Sample(n.name)
+	if !exists {
+		return sample, fmt.Errorf("unknown tag: %s", n.name)
+	}
+	return sample, nil
+}
+
+func (n *tagNode) eval(env Env) (interface{}, error) {
+	sample, err := n.sample(env)
+	if err != nil {
+		return nil, err
+	}
+	if number, ok := toNumber(sample.Value); ok {
+		return number, nil
+	}
+	switch value := sample.Value.(type) {
+	case bool, string:
+		return value, nil
+	}
+	return nil, fmt.Errorf("tag %s has unsupported value type %T", n.name, sample.Value)
+}
+
+type unaryNode struct {
+	op      string
+	operand node
+}
+
+func (n *unaryNode) eval(env Env) (interface{}, error) {
+	value, err := n.operand.eval(env)
+	if err != nil {
+		return nil, err
+	}
+	switch v := value.(type) {
+	case bool:
+		if n.op == "!" {
+			return !v, nil
+		}
+	case float64:
+		if n.op == "-" {
+			return -v, nil
+		}
+	}
+	return nil, fmt.Errorf("operator %s does not apply to %T", n.op, value)
+}
+
+type binaryNode struct {
+	op          string
+	left, right node
+}
+
+func (n *binaryNode) eval(env Env) (interface{}, error) {
+	left, err := n.left.eval(env)
+	if err != nil {
+		return nil, err
+	}
+
+	if n.op == "&&" || n.op == "||" {
+		l, ok := left.(bool)
+		if !ok {
+			return nil, fmt.Errorf("operator %s needs booleans, got %T", n.op, left)
+		}
+		if l == (n.op == "||") {
+			return l, nil
+		}
+		right, err := n.right.eval(env)
+		if err != nil {
+			return nil, err
+		}
+		r, ok := right.(bool)
+		if !ok {
+			return nil, fmt.Errorf("operator %s needs booleans, got %T", n.op, right)
+		}
+		return r, nil
+	}
+
+	right, err := n.right.eval(env)
+	if err != nil {
+		return nil, err
+	}
+
+	switch n.op {
+	case "==", "!=":
+		if reflect.TypeOf(left) != reflect.TypeOf(right) {
+			return nil, fmt.Errorf("cannot compare %T and %T", left, right)
+		}
+		return (left == right) == (n.op == "=="), nil
+	case "+":
+		if l, ok := left.(string); ok {
+			if r, ok := right.(string); ok {
+				return l + r, nil
+			}
+		}
+	}
+
+	if l, ok := left.(string); ok {
+		if r, ok := right.(string); ok {
+			switch n.op {
+			case "<":
+				return l < r, nil
+			case "<=":
+				return l <= r, nil
+			case ">":
+				return l > r, nil
+			case ">=":
+				return l >= r, nil
+			}
+		}
+	}
+
+	l, lok := left.(float64)
+	r, rok := right.(float64)
+	if !lok || !rok {
+		return nil, fmt.Errorf("operator %s does not apply to %T and %T", n.op, left, right)
+	}
+	switch n.op {
+	case "<":
+		return l < r, nil
+	case "<=":
+		return l <= r, nil
+	case ">":
+		return l > r, nil
+	case ">=":
+		return l >= r, nil
+	case "+":
+		return l + r, nil
+	case "-":
+		return l - r, nil
+	case "*":
+		return l * r, nil
+	case "/", "%":
+		if r == 0 {
+			return nil, fmt.Errorf("division by zero")
+		}
+		if n.op == "%" {
+			return math.Mod(l, r), nil
+		}
+		return l / r, nil
+	}
+	return nil, fmt.Errorf("unknown operator %s", n.op)
+}
+
+type callNode struct {
+	name string
+	args []node
+}
+
+func (n *callNode) eval(env Env) (interface{}, error) {
+	if n.name == "quality" {
+		sample, err := n.args[0].(*tagNode).sample(env)
+		if err != nil {
+			return nil, err
+		}
+		return string(sample.Quality), nil
+	}
+
+	args := make([]float64, len(n.args))
+	for i, arg := range n.args {
+		value, err := arg.eval(env)
+		if err != nil {
+			return nil, err
+		}
+		number, ok := value.(float64)
+		if !ok {
+			return nil, fmt.Errorf("%s needs numbers, got %T", n.name, value)
+		}
+		args[i] = number
+	}
+
+	switch n.name {
+	case "abs":
+		return math.Abs(args[0]), nil
+	case "min":
+		return math.Min(args[0], args[1]), nil
+	case "max":
+		return math.Max(args[0], args[1]), nil
+	}
+	return nil, fmt.Errorf("unknown function %s", n.name)
+}
+
+// toNumber converts numeric tag values to float64
+func toNumber(value interface{}) (float64, bool) {
+	switch v := value.(type) {
+	case float64:
+		return v, true
+	case float32:
+		return float64(v), true
+	case int:
+		return float64(v), true
+	case int8:
+		return float64(v), true
+	case int16:
+		return float64(v), true
+	case int32:
+		return float64(v), true
+	case int64:
+		return float64(v), true
+	case uint:
+		return float64(v), true
+	case uint8:
+		return float64(v), true
+	case uint16:
+		return float64(v), true
+	case uint32:
+		return float64(v), true
+	case uint64:
+		return float64(v), true
+	}
+	return 0, false
+}
@@ -0,0 +1,95 @@
+package rules
+
+import (
+	"testing"
+
+	"github.com/stretchr/testify/assert"
+
+	"github.com/bifrost/go-gateway/internal/protocols"
+)
+
+func testEnv() sampleEnv {
+	return sampleEnv{
+		"Flow":                {Name: "Flow", Value: 12.5, Quality: protocols.QualityGood},
+		"Pressure":            {Name: "Pressure", Value: int16(3), Quality: protocols.QualityUncertain},
+		"Running":             {Name: "Running", Value: true, Quality: protocols.QualityGood},
+		"Mode":                {Name: "Mode", Value: "AUTO", Quality: protocols.QualityGood},
+		"site/line/pump/Flow": {Name: "site/line/pump/Flow", Value: uint32(40), Quality: protocols.QualityGood},
+	}
+}
+
+func TestExpression_Eval(t *testing.T) {
+	tests := []struct {
+		source   string
+		expected interface{}
+	}{
+		{"Flow > 10", true},
+		{"Flow * 2 + 1", 26.0},
+		{"-Flow + 2 * (Pressure - 1)", -8.5},
+		{"Pressure == 3 && Running", true},
+		{"!Running || Flow < 0", false},
+		{"Mode == \"AUTO\"", true},
+		{"Mode + \"-1\"", "AUTO-1"},
+		{"quality(Pressure) != \"GOOD\"", true},
+		{"abs(-Flow) == 12.5 && min(Flow, Pressure) == 3 && max(Flow, 20) == 20", true},
+		{"`site/line/pump/Flow` / 4 % 3", 1.0},
+		{"1.5e1 >= Flow", true},
+		{"Running && Unknown > 1 || true", nil}, // Unknown is evaluated
+		{"!Running && Unknown > 1", false},      // Short-circuited
+	}
+
+	for _, test := range tests {
+		t.Run(test.source, func(t *testing.T) {
+			expression, err := Compile(test.source)
+			assert.NoError(t, err)
+			value, err := expression.Eval(testEnv())
+			if test.expected == nil {
+				assert.Error(t, err)
+				return
+			}
+			assert.NoError(t, err)
+			assert.Equal(t, test.expected, value)
+		})
+	}
+}
+
+func TestExpression_Tags(t *testing.T) {
+	expression, err := Compile("Pressure > 3 && quality(Flow) == \"GOOD\" || `a/b` > Pressure")
+	assert.NoError(t, err)
+	assert.Equal(t, []string{"Flow", "Pressure", "a/b"}, expression.Tags())
+}
+
+func TestExpression_Errors(t *testing.T) {
+	for _, source := range []string{
+		"",
+		"Flow >",
+		"(Flow > 1",
+		"Flow = 1",
+		"Flow > 1 2",
+		"sqrt(Flow)",
+		"min(Flow)",
+		"quality(1)",
+		"\"open",
+		"`open",
+	} {
+		_, err := Compile(source)
+		assert.Error(t, err, source)
+	}
+
+	for _, source := range []string{
+		"Flow > \"10\"",
+		"Mode && true",
+		"Flow / 0",
+		"-Mode",
+	} {
+		expression, err := Compile(source)
+		assert.NoError(t, err, source)
+		_, err = expression.Eval(testEnv())
+		assert.Error(t, err, source)
+	}
+
+	expression, err := Compile("Flow + 1")
+	assert.NoError(t, err)
+	_, err = expression.EvalBool(testEnv())
+	assert.Error(t, err)
+}
@@ -0,0 +1,197 @@
+package scripting
+
+import (
+	"context"
+	"fmt"
+	"os"
+	"sort"
+	"sync"
+
+	"go.uber.org/zap"
+
+	"github.com/bifrost/go-gateway/internal/protocols"
+	"github.com/bifrost/go-gateway/internal/rules"
+)
+
+// Config is a script of the gateway configuration, inline or from a file
+type Config struct {
+	Name     string `yaml:"name"`
+	File     string `yaml:"file,omitempty"`
+	Source   string `yaml:"source,omitempty"`
+	MaxSteps uint64 `yaml:"max_steps,omitempty"`
+}
+
+// Registry holds named scripts. It runs scripts for rule actions and
+// applies chains of transform scripts.
+type Registry struct {
+	writer rules.TagWriter
+	logger *zap.Logger
+
+	mutex   sync.RWMutex
+	scripts map[string]*Script
+}
+
+// NewRegistry creates an empty registry. writer is used by write_tag and
+// may be nil.
+func NewRegistry(writer rules.TagWriter, logger *zap.Logger) *Registry {
+	return &Registry{
+		writer:  writer,
+		logger:  logger,
+		scripts: make(map[string]*Script),
+	}
+}
+
+// Set is a compiled set of scripts, loaded with Replace
+type Set struct {
+	scripts map[string]*Script
+}
+
+// Load replaces all scripts. Nothing changes if any script fails to load.
+func (r *Registry) Load(configs []Config) error {
+	set, err := r.Compile(configs)
+	if err != nil {
+		return err
+	}
+	r.Replace(set)
+	return nil
+}
+
+// Compile compiles the scripts of configs without changing the registry,
+// so they can replace its scripts together with other changes
+func (r *Registry) Compile(configs []Config) (*Set, error) {
+	scripts, err := r.compileAll(configs)
+	if err != nil {
+		return nil, err
+	}
+	return &Set{scripts: scripts}, nil
+}
+
+// Replace replaces all scripts with a compiled set
+func (r *Registry) Replace(set *Set) {
+	r.mutex.Lock()
+	defer r.mutex.Unlock()
+	r.scripts = set.scripts
+}
+
+// Validate returns the error Load would return for configs, without
+// changing the scripts
+func (r *Registry) Validate(configs []Config) error {
+	_, err := r.compileAll(configs)
+	return err
+}
+
+func (r *Registry) compileAll(configs []Config) (map[string]*Script, error) {
+	scripts := make(map[string]*Script, len(configs))
+	for _, config := range configs {
+		if config.Name == "" {
+			return nil, fmt.Errorf("script name is required")
+		}
+		if _, exists := scripts[config.Name]; exists {
+			return nil, fmt.Errorf("duplicate script: %s", config.Name)
+		}
+
+		source := config.Source
+		if config.File != "" {
+			data, err := os.ReadFile(config.File)
+			if err != nil {
+				return nil, fmt.Errorf("script %s: %w", config.Name, err)
+			}
+			source = string(data)
+		}
+		script, err := Compile(config.Name, source, config.MaxSteps, r.writer, r.logger)
+		if err != nil {
+			return nil, err
+		}
+		scripts[config.Name] = script
+	}
+	return scripts, nil
+}
+
+// Put compiles a script and adds it, replacing a script of the same name
+func (r *Registry) Put(name, source string, maxSteps uint64) error {
+	script, err := Compile(name, source, maxSteps, r.writer, r.logger)
+	if err != nil {
+		return err
+	}
+
+	r.mutex.Lock()
+	defer r.mutex.Unlock()
+	r.scripts[name] = script
+	return nil
+}
+
+// Script returns a script by name
+func (r *Registry) Script(name string) (*Script, bool) {
+	r.mutex.RLock()
+	defer r.mutex.RUnlock()
+
+	script, exists := r.scripts[name]
+	return script, exists
+}
+
+// Names returns the names of the scripts, sorted
+func (r *Registry) Names() []string {
+	r.mutex.RLock()
+	defer r.mutex.RUnlock()
+
+	names := make([]string, 0, len(r.scripts))
+	for name := range r.scripts {
+		names = append(names, name)
+	}
+	sort.Strings(names)
+	return names
+}
+
+// RunScript implements rules.ScriptRunner
+func (r *Registry) RunScript(ctx context.Context, name string, firing rules.Firing) error {
+	script, exists := r.Script(name)
+	if !exists {
+		return fmt.Errorf("unknown script: %s", name)
+	}
+	return script.Run(ctx, firing)
+}
+
+// Transform passes samples through the transform scripts names in order
+func (r *Registry) Transform(names []string, samples []protocols.TagSample) ([]protocols.TagSample, error) {
+	for _, name := range names {
+		script, exists := r.Script(name)
+		if !exists {
+			return nil, fmt.Errorf("unknown script: %s", name)
+		}
+
+		var err error
+		samples, err = script.Transform(samples)
+		if err != nil {
+			return nil, err
+		}
+		if len(samples) == 0 {
+			break
+		}
+	}
+	return samples, nil
+}
+
+// TransformSink is a protocols.SampleSink that transforms samples before
+// writing them to another sink, e.g. at an egress point
+type TransformSink struct {
+	registry *Registry
+	names    []string
+	next     protocols.SampleSink
+}
+
+// NewTransformSink creates a sink applying the transform scripts names
+func NewTransformSink(registry *Registry, names []string, next protocols.SampleSink) *TransformSink {
+	return &TransformSink{registry: registry, names: names, next: next}
+}
+
+// WriteSamples transforms the samples and writes the result
+func (s *TransformSink) WriteSamples(samples []protocols.TagSample) error {
+	samples, err := s.registry.Transform(s.names, samples)
+	if err != nil {
+		return err
+	}
+	if len(samples) == 0 {
+		return nil
+	}
+	return s.next.WriteSamples(samples)
+}
@@ -0,0 +1,78 @@
+package scripting
+
+import (
+	"context"
+	"os"
+	"path/filepath"
+	"testing"
+
+	"github.com/stretchr/testify/assert"
+	"go.uber.org/zap"
+
+	"github.com/bifrost/go-gateway/internal/protocols"
+	"github.com/bifrost/go-gateway/internal/rules"
+)
+
+type registryTestSink struct {
+	samples []protocols.TagSample
+}
+
+func (s *registryTestSink) WriteSamples(samples []protocols.TagSample) error {
+	s.samples = append(s.samples, samples...)
+	return nil
+}
+
+func TestRegistry_Load(t *testing.T) {
+	file := filepath.Join(t.TempDir(), "power.star")
+	assert.NoError(t, os.WriteFile(file, []byte(power), 0o644))
+
+	registry := NewRegistry(nil, zap.NewNop())
+	assert.NoError(t, registry.Load([]Config{
+		{Name: "linearize", Source: linearize},
+		{Name: "power", File: file},
+	}))
+	assert.Equal(t, []string{"linearize", "power"}, registry.Names())
+
+	// A failing configuration keeps the loaded scripts
+	assert.Error(t, registry.Load([]Config{{Name: "linearize", Source: linearize}, {Name: "broken", Source: "def"}}))
+	assert.Error(t, registry.Load([]Config{{Name: "a", Source: linearize}, {Name: "a", Source: linearize}}))
+	assert.Error(t, registry.Load([]Config{{Name: "missing", File: file + ".missing"}}))
+	assert.Error(t, registry.Validate([]Config{{Source: linearize}}))
+	assert.NoError(t, registry.Validate([]Config{{Name: "a", Source: linearize}}))
+	assert.Equal(t, []string{"linearize", "power"}, registry.Names())
+
+	// A compiled set changes nothing until it replaces the scripts
+	set, err := registry.Compile([]Config{{Name: "linearize", Source: linearize}})
+	assert.NoError(t, err)
+	assert.Equal(t, []string{"linearize", "power"}, registry.Names())
+	registry.Replace(set)
+	assert.Equal(t, []string{"linearize"}, registry.Names())
+
+	assert.NoError(t, registry.Put("run", "def run(firing):\n    print(firing['rule'])", 0))
+	assert.NoError(t, registry.RunScript(context.Background(), "run", rules.Firing{Rule: "high"}))
+	assert.Error(t, registry.RunScript(context.Background(), "unknown", rules.Firing{Rule: "high"}))
+}
+
+func TestTransformSink(t *testing.T) {
+	registry := NewRegistry(nil, zap.NewNop())
+	assert.NoError(t, registry.Load([]Config{
+		{Name: "linearize", Source: linearize},
+		{Name: "power", Source: power},
+	}))
+
+	sink := &registryTestSink{}
+	transform := NewTransformSink(registry, []string{"linearize", "power"}, sink)
+	assert.NoError(t, transform.WriteSamples([]protocols.TagSample{
+		{Name: "Level", Value: 20.0, Unit: "mA", Quality: protocols.QualityGood},
+		{Name: "Current", Value: 2.0, Quality: protocols.QualityGood},
+		{Name: "Flow", Value: 1.0, Quality: protocols.QualityBad},
+	}))
+
+	assert.Len(t, sink.samples, 3)
+	assert.Equal(t, 100.0, sink.samples[0].Value)
+	assert.Equal(t, "Current", sink.samples[1].Name)
+	assert.Equal(t, int64(460), sink.samples[2].Value)
+
+	unknown := NewTransformSink(registry, []string{"unknown"}, sink)
+	assert.Error(t, unknown.WriteSamples([]protocols.TagSample{{Name: "Level"}}))
+}
@@ -0,0 +1,354 @@
+// Package scripting runs sandboxed user scripts that transform samples
+package scripting
+
+import (
+	"context"
+	"fmt"
+	"math"
+	"time"
+
+	starlarkmath "go.starlark.net/lib/math"
+	"go.starlark.net/starlark"
+	"go.starlark.net/syntax"
+	"go.uber.org/zap"
+
+	"github.com/bifrost/go-gateway/internal/protocols"
+	"github.com/bifrost/go-gateway/internal/rules"
+)
+
+// Scripting Hooks
+//
+// Scripts are Starlark, a Python dialect designed for embedding: scripts
+// have no access to files, the network or the clock, and every call is
+// limited in execution steps. A script attached at an ingest or egress
+// point defines
+//
+//	def transform(sample):
+//	    sample["value"] = sample["value"] * 0.25 - 12.5
+//	    return sample
+//
+// which is called with each sample as a dict with the keys name, value,
+// quality, substatus, source, unit, timestamp (Unix seconds) and labels.
+// It returns the sample, a list of samples, e.g. to add a value computed
+// from several tags, or None to drop the sample. A script run by a rule
+// action defines run(firing), called with a dict of rule, time and values,
+// and may call write_tag(device, tag, value). The math module is
+// predeclared. Module-level values are frozen once the top level has run,
+// so calls cannot keep state in them and may run concurrently.
+
+const defaultMaxSteps = 100000
+
+// contextKey is the thread local holding the context of a run call
+const contextKey = "context"
+
+// Script is a compiled script
+type Script struct {
+	name     string
+	globals  starlark.StringDict
+	maxSteps uint64
+	logger   *zap.Logger
+}
+
+// Compile executes the top level of a script. maxSteps limits each call;
+// 0 means 100000 steps. writer is used by write_tag and may be nil.
+func Compile(name, source string, maxSteps uint64, writer rules.TagWriter, logger *zap.Logger) (*Script, error) {
+	if maxSteps == 0 {
+		maxSteps = defaultMaxSteps
+	}
+	s := &Script{name: name, maxSteps: maxSteps, logger: logger}
+
+	predeclared := starlark.StringDict{
+		"math":      starlarkmath.Module,
+		"write_tag": starlark.NewBuiltin("write_tag", writeTag(writer)),
+	}
+	globals, err := starlark.ExecFileOptions(&syntax.FileOptions{}, s.thread(), name, source, predeclared)
+	if err != nil {
+		return nil, fmt.Errorf("script %s: %w", name, err)
+	}
+	// Calls run concurrently, e.g. one per polled device
+	globals.Freeze()
+	s.globals = globals
+
+	if s.function("transform") == nil && s.function("run") == nil {
+		return nil, fmt.Errorf("script %s defines neither transform nor run", name)
+	}
+	return s, nil
+}
+
+// Name returns the name of the script
+func (s *Script) Name() string {
+	return s.name
+}
+
+// CanTransform reports whether the script defines transform
+func (s *Script) CanTransform() bool {
+	return s.function("transform") != nil
+}
+
+// Transform calls transform for each sample and returns the samples it
+// produced
+func (s *Script) Transform(samples []protocols.TagSample) ([]protocols.TagSample, error) {
+	transform := s.function("transform")
+	if transform == nil {
+		return nil, fmt.Errorf("script %s does not define transform", s.name)
+	}
+
+	result := make([]protocols.TagSample, 0, len(samples))
+	for _, sample := range samples {
+		value, err := starlark.Call(s.thread(), transform, starlark.Tuple{sampleToStarlark(sample)}, nil)
+		if err != nil {
+			return nil, fmt.Errorf("script %s: %w", s.name, err)
+		}
+
+		switch v := value.(type) {
+		case starlark.NoneType:
+		case *starlark.List:
+			for i := 0; i < v.Len(); i++ {
+				produced, err := sampleFromStarlark(v.Index(i))
+				if err != nil {
+					return nil, fmt.Errorf("script %s: %w", s.name, err)
+				}
+				result = append(result, produced)
+			}
+		default:
+			produced, err := sampleFromStarlark(value)
+			if err != nil {
+				return nil, fmt.Errorf("script %s: %w", s.name, err)
+			}
+			result = append(result, produced)
+		}
+	}
+	return result, nil
+}
+
+// Run calls run with a firing rule. The call is cancelled with ctx.
+func (s *Script) Run(ctx context.Context, firing rules.Firing) error {
+	run := s.function("run")
+	if run == nil {
+		return fmt.Errorf("script %s does not define run", s.name)
+	}
+
+	values := starlark.NewDict(len(firing.Values))
+	for tag, value := range firing.Values {
+		values.SetKey(starlark.String(tag), toStarlark(value))
+	}
+	arg := starlark.NewDict(3)
+	arg.SetKey(starlark.String("rule"), starlark.String(firing.Rule))
+	arg.SetKey(starlark.String("time"), starlark.Float(unixSeconds(firing.Time)))
+	arg.SetKey(starlark.String("values"), values)
+
+	thread := s.thread()
+	thread.SetLocal(contextKey, ctx)
+	done := make(chan struct{})
+	defer close(done)
+	go func() {
+		select {
+		case <-ctx.Done():
+			thread.Cancel(ctx.Err().Error())
+		case <-done:
+		}
+	}()
+
+	if _, err := starlark.Call(thread, run, starlark.Tuple{arg}, nil); err != nil {
+		return fmt.Errorf("script %s: %w", s.name, err)
+	}
+	return nil
+}
+
+// thread returns a thread for one call
+func (s *Script) thread() *starlark.Thread {
+	thread := &starlark.Thread{
+		Name: s.name,
+		Print: func(_ *starlark.Thread, msg string) {
+			s.logger.Info("Script output", zap.String("script", s.name), zap.String("message", msg))
+		},
+	}
+	thread.SetMaxExecutionSteps(s.maxSteps)
+	return thread
+}
+
+func (s *Script) function(name string) starlark.Callable {
+	if s.globals == nil {
+		return nil
+	}
+	function, _ := s.globals[name].(starlark.Callable)
+	return function
+}
+
+// writeTag returns the write_tag builtin, available in run calls only
+func writeTag(writer rules.TagWriter) func(*starlark.Thread, *starlark.Builtin, starlark.Tuple, []starlark.Tuple) (starlark.Value, error) {
+	return func(thread *starlark.Thread, fn *starlark.Builtin, args starlark.Tuple, kwargs []starlark.Tuple) (starlark.Value, error) {
+		var device, tag string
+		var value starlark.Value
+		if err := starlark.UnpackArgs(fn.Name(), args, kwargs, "device", &device, "tag", &tag, "value", &value); err != nil {
+			return nil, err
+		}
+
+		ctx, ok := thread.Local(contextKey).(context.Context)
+		if !ok {
+			return nil, fmt.Errorf("%s: only available in run", fn.Name())
+		}
+		if writer == nil {
+			return nil, fmt.Errorf("%s: tag writes are not available", fn.Name())
+		}
+		goValue, err := fromStarlark(value)
+		if err != nil {
+			return nil, fmt.Errorf("%s: %w", fn.Name(), err)
+		}
+		if err := writer.WriteTag(ctx, device, tag, goValue); err != nil {
+			return nil, fmt.Errorf("%s: %w", fn.Name(), err)
+		}
+		return starlark.None, nil
+	}
+}
+
+// sampleToStarlark returns a sample as a dict
+func sampleToStarlark(sample protocols.TagSample) *starlark.Dict {
+	labels := starlark.NewDict(len(sample.Labels))
+	for key, value := range sample.Labels {
+		labels.SetKey(starlark.String(key), starlark.String(value))
+	}
+
+	dict := starlark.NewDict(8)
+	dict.SetKey(starlark.String("name"), starlark.String(sample.Name))
+	dict.SetKey(starlark.String("value"), toStarlark(sample.Value))
+	dict.SetKey(starlark.String("quality"), starlark.String(sample.Quality))
+	dict.SetKey(starlark.String("substatus"), starlark.String(sample.Substatus))
+	dict.SetKey(starlark.String("source"), starlark.String(sample.QualitySource))
+	dict.SetKey(starlark.String("unit"), starlark.String(sample.Unit))
+	dict.SetKey(starlark.String("timestamp"), starlark.Float(unixSeconds(sample.Timestamp)))
+	dict.SetKey(starlark.String("labels"), labels)
+	return dict
+}
+
+// sampleFromStarlark converts a dict returned by transform to a sample
+func sampleFromStarlark(value starlark.Value) (protocols.TagSample, error) {
+	var sample protocols.TagSample
+	dict, ok := value.(*starlark.Dict)
+	if !ok {
+		return sample, fmt.Errorf("transform returned %s, not a sample dict", value.Type())
+	}
+
+	var err error
+	fields := map[string]*string{
+		"name":      &sample.Name,
+		"quality":   (*string)(&sample.Quality),
+		"substatus": (*string)(&sample.Substatus),
+		"source":    (*string)(&sample.QualitySource),
+		"unit":      &sample.Unit,
+	}
+	for key, field := range fields {
+		if v, found, _ := dict.Get(starlark.String(key)); found {
+			str, ok := starlark.AsString(v)
+			if !ok {
+				return sample, fmt.Errorf("sample %s must be a string", key)
+			}
+			*field = str
+		}
+	}
+	if sample.Name == "" {
+		return sample, fmt.Errorf("sample has no name")
+	}
+
+	if v, found, _ := dict.Get(starlark.String("value")); found {
+		if sample.Value, err = fromStarlark(v); err != nil {
+			return sample, err
+		}
+	}
+	if v, found, _ := dict.Get(starlark.String("timestamp")); found {
+		seconds, ok := starlark.AsFloat(v)
+		if !ok {
+			return sample, fmt.Errorf("sample timestamp must be a number")
+		}
+		if seconds != 0 {
+			whole, fraction := math.Modf(seconds)
+			sample.Timestamp = time.Unix(int64(whole), int64(fraction*1e9))
+		}
+	}
+	if v, found, _ := dict.Get(starlark.String("labels")); found {
+		labels, ok := v.(*starlark.Dict)
+		if !ok {
+			return sample, fmt.Errorf("sample labels must be a dict")
+		}
+		sample.Labels = make(map[string]string, labels.Len())
+		for _, item := range labels.Items() {
+			key, keyOK := starlark.AsString(item[0])
+			value, valueOK := starlark.AsString(item[1])
+			if !keyOK || !valueOK {
+				return sample, fmt.Errorf("sample labels must be strings")
+			}
+			sample.Labels[key] = value
+		}
+		if len(sample.Labels) == 0 {
+			sample.Labels = nil
+		}
+	}
+	return sample, nil
+}
+
+// toStarlark converts a tag value; unsupported types become strings
+func toStarlark(value interface{}) starlark.Value {
+	switch v := value.(type) {
+	case nil:
+		return starlark.None
+	case bool:
+		return starlark.Bool(v)
+	case string:
+		return starlark.String(v)
+	case float32:
+		return starlark.Float(v)
+	case float64:
+		return starlark.Float(v)
+	case int:
+		return starlark.MakeInt(v)
+	case int8:
+		return starlark.MakeInt64(int64(v))
+	case int16:
+		return starlark.MakeInt64(int64(v))
+	case int32:
+		return starlark.MakeInt64(int64(v))
+	case int64:
+		return starlark.MakeInt64(v)
+	case uint:
+		return starlark.MakeUint(v)
+	case uint8:
+		return starlark.MakeUint64(uint64(v))
+	case uint16:
+		return starlark.MakeUint64(uint64(v))
+	case uint32:
+		return starlark.MakeUint64(uint64(v))
+	case uint64:
+		return starlark.MakeUint64(v)
+	}
+	return starlark.String(fmt.Sprint(value))
+}
+
+// fromStarlark converts a script value to a tag value
+func fromStarlark(value starlark.Value) (interface{}, error) {
+	switch v := value.(type) {
+	case starlark.NoneType:
+		return nil, nil
+	case starlark.Bool:
+		return bool(v), nil
+	case starlark.String:
+		return string(v), nil
+	case starlark.Float:
+		return float64(v), nil
+	case starlark.Int:
+		if i, ok := v.Int64(); ok {
+			return i, nil
+		}
+		if u, ok := v.Uint64(); ok {
+			return u, nil
+		}
+		return float64(v.Float()), nil
+	}
+	return nil, fmt.Errorf("unsupported value type %s", value.Type())
+}
+
+func unixSeconds(t time.Time) float64 {
+	if t.IsZero() {
+		return 0
+	}
+	return float64(t.UnixNano()) / 1e9
+}
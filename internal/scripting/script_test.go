@@ -0,0 +1,169 @@
+package scripting
+
+import (
+	"context"
+	"sync"
+	"testing"
+	"time"
+
+	"github.com/stretchr/testify/assert"
+	"go.uber.org/zap"
+
+	"github.com/bifrost/go-gateway/internal/protocols"
+	"github.com/bifrost/go-gateway/internal/rules"
+)
+
+const linearize = `
+def transform(sample):
+    if sample["unit"] == "mA":
+        # 4-20 mA to 0-100 %
+        sample["value"] = (sample["value"] - 4) * 6.25
+        sample["unit"] = "%"
+    return sample
+`
+
+const power = `
+def transform(sample):
+    if sample["quality"] == "BAD":
+        return None
+    if sample["name"] != "Current":
+        return sample
+    power = dict(sample)
+    power["name"] = "Power"
+    power["value"] = int(sample["value"] * 230)
+    power["unit"] = "W"
+    return [sample, power]
+`
+
+type scriptTestWriter struct {
+	device, tag string
+	value       interface{}
+}
+
+func (w *scriptTestWriter) WriteTag(ctx context.Context, device, tag string, value interface{}) error {
+	w.device, w.tag, w.value = device, tag, value
+	return nil
+}
+
+func TestScript_Transform(t *testing.T) {
+	script, err := Compile("linearize", linearize, 0, nil, zap.NewNop())
+	assert.NoError(t, err)
+	assert.True(t, script.CanTransform())
+
+	timestamp := time.Unix(1700000000, 0)
+	samples, err := script.Transform([]protocols.TagSample{
+		{Name: "Level", Value: uint16(12), Unit: "mA", Quality: protocols.QualityGood, Timestamp: timestamp},
+		{Name: "Running", Value: true, Quality: protocols.QualityGood, Labels: map[string]string{"line": "2"}},
+	})
+	assert.NoError(t, err)
+	assert.Len(t, samples, 2)
+	assert.Equal(t, "Level", samples[0].Name)
+	assert.Equal(t, 50.0, samples[0].Value)
+	assert.Equal(t, "%", samples[0].Unit)
+	assert.Equal(t, protocols.QualityGood, samples[0].Quality)
+	assert.True(t, timestamp.Equal(samples[0].Timestamp))
+	assert.Equal(t, true, samples[1].Value)
+	assert.Equal(t, map[string]string{"line": "2"}, samples[1].Labels)
+	assert.True(t, samples[1].Timestamp.IsZero())
+}
+
+func TestScript_TransformAddsAndDrops(t *testing.T) {
+	script, err := Compile("power", power, 0, nil, zap.NewNop())
+	assert.NoError(t, err)
+
+	samples, err := script.Transform([]protocols.TagSample{
+		{Name: "Current", Value: 1.5, Quality: protocols.QualityGood},
+		{Name: "Voltage", Value: 230.0, Quality: protocols.QualityBad},
+	})
+	assert.NoError(t, err)
+	assert.Len(t, samples, 2)
+	assert.Equal(t, "Current", samples[0].Name)
+	assert.Equal(t, "Power", samples[1].Name)
+	assert.Equal(t, int64(345), samples[1].Value)
+	assert.Equal(t, "W", samples[1].Unit)
+}
+
+func TestScript_TransformConcurrently(t *testing.T) {
+	script, err := Compile("offsets", `
+offsets = {"Level": 1.5}
+
+def transform(sample):
+    sample["value"] = sample["value"] + offsets.get(sample["name"], 0)
+    return sample
+`, 0, nil, zap.NewNop())
+	assert.NoError(t, err)
+
+	var wg sync.WaitGroup
+	for i := 0; i < 8; i++ {
+		wg.Add(1)
+		go func() {
+			defer wg.Done()
+			for j := 0; j < 50; j++ {
+				samples, err := script.Transform([]protocols.TagSample{{Name: "Level", Value: 10.0}})
+				assert.NoError(t, err)
+				assert.Equal(t, 11.5, samples[0].Value)
+			}
+		}()
+	}
+	wg.Wait()
+
+	// Module-level values cannot be changed by calls
+	script, err = Compile("counter", `
+seen = []
+
+def transform(sample):
+    seen.append(sample["name"])
+    return sample
+`, 0, nil, zap.NewNop())
+	assert.NoError(t, err)
+	_, err = script.Transform([]protocols.TagSample{{Name: "Level", Value: 10.0}})
+	assert.Error(t, err)
+}
+
+func TestScript_Run(t *testing.T) {
+	writer := &scriptTestWriter{}
+	script, err := Compile("shutdown", `
+def run(firing):
+    if firing["values"]["Level"] > 90:
+        write_tag("site/tank", "InletValve", False)
+`, 0, writer, zap.NewNop())
+	assert.NoError(t, err)
+	assert.False(t, script.CanTransform())
+
+	err = script.Run(context.Background(), rules.Firing{Rule: "high", Values: map[string]interface{}{"Level": 95.0}})
+	assert.NoError(t, err)
+	assert.Equal(t, &scriptTestWriter{device: "site/tank", tag: "InletValve", value: false}, writer)
+
+	_, err = script.Transform([]protocols.TagSample{{Name: "Level"}})
+	assert.Error(t, err)
+}
+
+func TestScript_Sandbox(t *testing.T) {
+	// Scripts must define a hook and cannot reach the host
+	for _, source := range []string{
+		"x = 1",
+		"def transform(sample):\n    return open('/etc/passwd')",
+		"def transform(sample)",
+	} {
+		_, err := Compile("bad", source, 0, nil, zap.NewNop())
+		assert.Error(t, err, source)
+	}
+
+	// Every call is limited in steps
+	script, err := Compile("loop", "def transform(sample):\n    for i in range(10000000):\n        pass\n    return sample", 1000, nil, zap.NewNop())
+	assert.NoError(t, err)
+	_, err = script.Transform([]protocols.TagSample{{Name: "Level", Value: 1.0}})
+	assert.Error(t, err)
+
+	// write_tag is not available in transforms
+	script, err = Compile("write", "def transform(sample):\n    write_tag('a', 'b', 1)\n    return sample", 0, &scriptTestWriter{}, zap.NewNop())
+	assert.NoError(t, err)
+	_, err = script.Transform([]protocols.TagSample{{Name: "Level", Value: 1.0}})
+	assert.Error(t, err)
+
+	// Transforms must return samples
+	script, err = Compile("invalid", "def transform(sample):\n    return 1", 0, nil, zap.NewNop())
+	assert.NoError(t, err)
+	_, err = script.Transform([]protocols.TagSample{{Name: "Level", Value: 1.0}})
+	assert.Error(t, err)
+}
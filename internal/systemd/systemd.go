@@ -0,0 +1,112 @@
+// Package systemd notifies systemd of the gateway's state and feeds its
+// watchdog
+package systemd
+
+import (
+	"context"
+	"fmt"
+	"net"
+	"os"
+	"strconv"
+	"time"
+
+	"go.uber.org/zap"
+)
+
+// systemd Integration
+//
+// With Type=notify in the unit file, systemd considers the gateway started
+// when it sends READY=1 over the socket in NOTIFY_SOCKET. With WatchdogSec
+// set, systemd also expects WATCHDOG=1 at least that often and otherwise
+// kills and restarts the service. Watchdog sends it at half the interval,
+// but only while the gateway's liveness checks pass, so a gateway whose
+// collection loop hangs is restarted even though the process still runs.
+// Without systemd, NOTIFY_SOCKET is unset and all of this does nothing.
+
+// Notification states
+const (
+	Ready     = "READY=1"
+	Reloading = "RELOADING=1"
+	Stopping  = "STOPPING=1"
+	Watchdog  = "WATCHDOG=1"
+)
+
+// Notify sends state, e.g. Ready or "STATUS=...", to systemd. It reports
+// false without an error if the service does not run under systemd.
+func Notify(state string) (bool, error) {
+	socket := os.Getenv("NOTIFY_SOCKET")
+	if socket == "" {
+		return false, nil
+	}
+	if socket[0] == '@' {
+		// Abstract namespace socket
+		socket = "\x00" + socket[1:]
+	}
+
+	conn, err := net.DialUnix("unixgram", nil, &net.UnixAddr{Name: socket, Net: "unixgram"})
+	if err != nil {
+		return false, err
+	}
+	defer conn.Close()
+	if _, err := conn.Write([]byte(state)); err != nil {
+		return false, err
+	}
+	return true, nil
+}
+
+// WatchdogInterval returns the watchdog interval systemd expects, or 0 if
+// the watchdog is not enabled for this process
+func WatchdogInterval() (time.Duration, error) {
+	usec := os.Getenv("WATCHDOG_USEC")
+	if usec == "" {
+		return 0, nil
+	}
+	if pid := os.Getenv("WATCHDOG_PID"); pid != "" && pid != strconv.Itoa(os.Getpid()) {
+		// Meant for another process, e.g. the parent of a wrapper script
+		return 0, nil
+	}
+
+	n, err := strconv.ParseInt(usec, 10, 64)
+	if err != nil || n <= 0 {
+		return 0, fmt.Errorf("invalid WATCHDOG_USEC %q", usec)
+	}
+	return time.Duration(n) * time.Microsecond, nil
+}
+
+// RunWatchdog feeds the systemd watchdog at half its interval while alive
+// returns true, until ctx is done. It returns immediately if the watchdog
+// is not enabled.
+func RunWatchdog(ctx context.Context, alive func(ctx context.Context) bool, logger *zap.Logger) error {
+	interval, err := WatchdogInterval()
+	if err != nil || interval == 0 {
+		return err
+	}
+
+	logger.Info("systemd watchdog enabled", zap.Duration("interval", interval))
+	ticker := time.NewTicker(interval / 2)
+	defer ticker.Stop()
+
+	healthy := true
+	for {
+		select {
+		case <-ctx.Done():
+			return nil
+		case <-ticker.C:
+		}
+
+		if !alive(ctx) {
+			if healthy {
+				logger.Error("Liveness checks failing, no longer feeding the systemd watchdog")
+			}
+			healthy = false
+			continue
+		}
+		if !healthy {
+			logger.Info("Liveness checks passing again, feeding the systemd watchdog")
+		}
+		healthy = true
+		if _, err := Notify(Watchdog); err != nil {
+			logger.Warn("Failed to notify systemd watchdog", zap.Error(err))
+		}
+	}
+}
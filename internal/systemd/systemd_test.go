@@ -0,0 +1,103 @@
+package systemd
+
+import (
+	"context"
+	"net"
+	"os"
+	"path/filepath"
+	"strconv"
+	"sync/atomic"
+	"testing"
+	"time"
+
+	"github.com/stretchr/testify/assert"
+	"go.uber.org/zap"
+)
+
+// notifySocket listens on a notify socket set in NOTIFY_SOCKET and
+// returns the received states
+func notifySocket(t *testing.T) <-chan string {
+	socket := filepath.Join(t.TempDir(), "notify.sock")
+	conn, err := net.ListenUnixgram("unixgram", &net.UnixAddr{Name: socket, Net: "unixgram"})
+	assert.NoError(t, err)
+	t.Cleanup(func() { conn.Close() })
+	t.Setenv("NOTIFY_SOCKET", socket)
+
+	states := make(chan string, 16)
+	go func() {
+		buf := make([]byte, 4096)
+		for {
+			n, err := conn.Read(buf)
+			if err != nil {
+				return
+			}
+			states <- string(buf[:n])
+		}
+	}()
+	return states
+}
+
+func TestNotify(t *testing.T) {
+	t.Setenv("NOTIFY_SOCKET", "")
+	sent, err := Notify(Ready)
+	assert.NoError(t, err)
+	assert.False(t, sent)
+
+	states := notifySocket(t)
+	sent, err = Notify(Ready)
+	assert.NoError(t, err)
+	assert.True(t, sent)
+	assert.Equal(t, Ready, <-states)
+}
+
+func TestWatchdogInterval(t *testing.T) {
+	t.Setenv("WATCHDOG_USEC", "")
+	interval, err := WatchdogInterval()
+	assert.NoError(t, err)
+	assert.Zero(t, interval)
+
+	t.Setenv("WATCHDOG_USEC", "30000000")
+	t.Setenv("WATCHDOG_PID", strconv.Itoa(os.Getpid()))
+	interval, err = WatchdogInterval()
+	assert.NoError(t, err)
+	assert.Equal(t, 30*time.Second, interval)
+
+	t.Setenv("WATCHDOG_PID", "1")
+	interval, err = WatchdogInterval()
+	assert.NoError(t, err)
+	assert.Zero(t, interval)
+
+	t.Setenv("WATCHDOG_PID", "")
+	t.Setenv("WATCHDOG_USEC", "soon")
+	_, err = WatchdogInterval()
+	assert.Error(t, err)
+}
+
+func TestRunWatchdog(t *testing.T) {
+	states := notifySocket(t)
+	t.Setenv("WATCHDOG_USEC", "20000")
+	t.Setenv("WATCHDOG_PID", "")
+
+	var alive int32 = 1
+	ctx, cancel := context.WithCancel(context.Background())
+	done := make(chan error)
+	go func() {
+		done <- RunWatchdog(ctx, func(ctx context.Context) bool { return atomic.LoadInt32(&alive) == 1 }, zap.NewNop())
+	}()
+	assert.Equal(t, Watchdog, <-states)
+
+	// A failing liveness check stops the pings
+	atomic.StoreInt32(&alive, 0)
+	time.Sleep(30 * time.Millisecond)
+	for len(states) > 0 {
+		<-states
+	}
+	select {
+	case state := <-states:
+		t.Fatalf("unexpected %s while not alive", state)
+	case <-time.After(50 * time.Millisecond):
+	}
+
+	cancel()
+	assert.NoError(t, <-done)
+}
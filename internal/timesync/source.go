@@ -0,0 +1,128 @@
+package timesync
+
+import (
+	"bufio"
+	"bytes"
+	"context"
+	"fmt"
+	"math"
+	"os/exec"
+	"strconv"
+	"strings"
+	"time"
+)
+
+// Reading is the synchronization state reported by a time source
+type Reading struct {
+	Synchronized bool
+	Reference    string // Server or grandmaster the clock follows
+	Stratum      int
+	Offset       time.Duration // Of the system clock from the reference
+	LastSync     time.Time     // Last update from the reference
+}
+
+// Source queries the synchronization state
+type Source interface {
+	Query(ctx context.Context) (Reading, error)
+}
+
+// runner runs a command and returns its standard output
+type runner func(ctx context.Context, name string, args ...string) ([]byte, error)
+
+func runCommand(ctx context.Context, name string, args ...string) ([]byte, error) {
+	return exec.CommandContext(ctx, name, args...).Output()
+}
+
+// chrony reads the tracking report of chronyd
+type chrony struct {
+	command string
+	run     runner
+}
+
+// Query runs "chronyc -c tracking"
+func (c *chrony) Query(ctx context.Context) (Reading, error) {
+	output, err := c.run(ctx, c.command, "-c", "tracking")
+	if err != nil {
+		return Reading{}, fmt.Errorf("chronyc tracking: %w", err)
+	}
+	return parseChronyTracking(output)
+}
+
+// parseChronyTracking parses the CSV tracking report: reference ID,
+// reference name, stratum, reference time, system time offset, then
+// statistics and the leap status as the 14th field
+func parseChronyTracking(output []byte) (Reading, error) {
+	fields := strings.Split(strings.TrimSpace(string(output)), ",")
+	if len(fields) < 14 {
+		return Reading{}, fmt.Errorf("unexpected chronyc tracking output %q", output)
+	}
+	stratum, err := strconv.Atoi(fields[2])
+	if err != nil {
+		return Reading{}, fmt.Errorf("invalid stratum %q", fields[2])
+	}
+	refTime, err := strconv.ParseFloat(fields[3], 64)
+	if err != nil {
+		return Reading{}, fmt.Errorf("invalid reference time %q", fields[3])
+	}
+	offset, err := strconv.ParseFloat(fields[4], 64)
+	if err != nil {
+		return Reading{}, fmt.Errorf("invalid system time offset %q", fields[4])
+	}
+
+	reading := Reading{
+		Synchronized: fields[13] != "Not synchronised" && fields[0] != "00000000",
+		Reference:    fields[1],
+		Stratum:      stratum,
+		Offset:       time.Duration(offset * float64(time.Second)),
+	}
+	if refTime > 0 {
+		seconds, fraction := math.Modf(refTime)
+		reading.LastSync = time.Unix(int64(seconds), int64(fraction*1e9))
+	}
+	return reading, nil
+}
+
+// ptp reads the state of the linuxptp daemon ptp4l through its management
+// client
+type ptp struct {
+	command string
+	run     runner
+}
+
+// Query runs "pmc -u -b 0 'GET TIME_STATUS_NP'"
+func (p *ptp) Query(ctx context.Context) (Reading, error) {
+	output, err := p.run(ctx, p.command, "-u", "-b", "0", "GET TIME_STATUS_NP")
+	if err != nil {
+		return Reading{}, fmt.Errorf("pmc: %w", err)
+	}
+	return parsePTPTimeStatus(output)
+}
+
+// parsePTPTimeStatus parses the TIME_STATUS_NP management response. The
+// master offset and ingress time are in nanoseconds.
+func parsePTPTimeStatus(output []byte) (Reading, error) {
+	values := make(map[string]string)
+	scanner := bufio.NewScanner(bytes.NewReader(output))
+	for scanner.Scan() {
+		if fields := strings.Fields(scanner.Text()); len(fields) == 2 {
+			values[fields[0]] = fields[1]
+		}
+	}
+	if _, ok := values["master_offset"]; !ok {
+		return Reading{}, fmt.Errorf("no TIME_STATUS_NP response from ptp4l")
+	}
+
+	offset, err := strconv.ParseInt(values["master_offset"], 10, 64)
+	if err != nil {
+		return Reading{}, fmt.Errorf("invalid master_offset %q", values["master_offset"])
+	}
+	reading := Reading{
+		Synchronized: values["gmPresent"] == "true",
+		Reference:    values["gmIdentity"],
+		Offset:       time.Duration(offset),
+	}
+	if ingress, err := strconv.ParseInt(values["ingress_time"], 10, 64); err == nil && ingress > 0 {
+		reading.LastSync = time.Unix(0, ingress)
+	}
+	return reading, nil
+}
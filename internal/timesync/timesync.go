@@ -0,0 +1,405 @@
+// Package timesync tracks how well the system clock is synchronized and
+// corrects timestamps taken while it was wrong
+package timesync
+
+import (
+	"context"
+	"fmt"
+	"sync"
+	"time"
+
+	"go.uber.org/zap"
+
+	"github.com/bifrost/go-gateway/internal/protocols"
+)
+
+// Clock Synchronization
+//
+// A Monitor queries chronyd or the linuxptp daemon for the offset of the
+// system clock and when it last heard from its reference, and rates the
+// synchronization as a protocols.ClockSync that is attached to collected
+// samples. Periods in which the clock was not synchronized, and wall clock
+// steps reported by the HybridClock, are recorded as windows; a step ending
+// a window tells by how much the timestamps in it were off.
+//
+// Data already written while the clock was wrong can be corrected after
+// the fact: a Correction re-stamps samples of a period by an offset or
+// flags them as unsynchronized. Corrections apply to samples passing
+// through Apply, e.g. samples still queued for forwarding.
+
+// Sources
+const (
+	SourceNone   = "none"
+	SourceChrony = "chrony"
+	SourcePTP    = "ptp"
+)
+
+// Correction actions
+const (
+	ActionRestamp = "restamp"
+	ActionFlag    = "flag"
+)
+
+const (
+	defaultInterval  = 16 * time.Second
+	defaultMaxOffset = 100 * time.Millisecond
+	defaultMaxAge    = time.Hour
+
+	// maxWindows and maxCorrections bound the history kept in memory
+	maxWindows     = 100
+	maxCorrections = 100
+)
+
+// Config configures the monitor
+type Config struct {
+	Source    string        `yaml:"source"`     // chrony, ptp or none (default)
+	Command   string        `yaml:"command"`    // chronyc or pmc binary, default from PATH
+	Interval  time.Duration `yaml:"interval"`   // Between queries, default 16s
+	MaxOffset time.Duration `yaml:"max_offset"` // Beyond which sync is degraded, default 100ms
+	MaxAge    time.Duration `yaml:"max_age"`    // Without updates before the clock counts as unsynchronized, default 1h
+}
+
+// Status is the synchronization of the system clock
+type Status struct {
+	Source    string              `json:"source"`
+	Sync      protocols.ClockSync `json:"sync,omitempty"`
+	Reference string              `json:"reference,omitempty"`
+	Stratum   int                 `json:"stratum,omitempty"`
+	Offset    time.Duration       `json:"offset"`
+	LastSync  time.Time           `json:"last_sync,omitempty"`
+	LastQuery time.Time           `json:"last_query,omitempty"`
+	Error     string              `json:"error,omitempty"`
+	Steps     int                 `json:"steps"`
+	LastStep  time.Time           `json:"last_step,omitempty"`
+}
+
+// Window is a period in which the clock was known to be wrong
+type Window struct {
+	From   time.Time           `json:"from"`
+	To     time.Time           `json:"to,omitempty"` // Zero while the period lasts
+	Sync   protocols.ClockSync `json:"sync"`
+	Offset time.Duration       `json:"offset,omitempty"` // Step that ended the period, to be added to its timestamps
+	Reason string              `json:"reason"`
+}
+
+// Correction re-stamps or flags samples with timestamps in [From, To)
+type Correction struct {
+	From   time.Time     `json:"from"`
+	To     time.Time     `json:"to"`
+	Action string        `json:"action"`           // restamp or flag
+	Offset time.Duration `json:"offset,omitempty"` // Added to timestamps when re-stamping
+}
+
+// Validate checks the period and action
+func (c Correction) Validate() error {
+	if c.From.IsZero() || !c.To.After(c.From) {
+		return fmt.Errorf("correction requires from before to")
+	}
+	switch c.Action {
+	case ActionRestamp:
+		if c.Offset == 0 {
+			return fmt.Errorf("restamp requires an offset")
+		}
+	case ActionFlag:
+	default:
+		return fmt.Errorf("unknown correction action %q", c.Action)
+	}
+	return nil
+}
+
+// Monitor tracks the synchronization of the system clock
+type Monitor struct {
+	config Config
+	source Source
+	logger *zap.Logger
+	now    func() time.Time
+
+	mutex       sync.RWMutex
+	status      Status
+	lastGood    time.Time // Last query that found the clock synchronized
+	windows     []Window
+	corrections []Correction
+}
+
+// NewMonitor creates a monitor for the configured source
+func NewMonitor(config Config, logger *zap.Logger) (*Monitor, error) {
+	if config.Interval <= 0 {
+		config.Interval = defaultInterval
+	}
+	if config.MaxOffset <= 0 {
+		config.MaxOffset = defaultMaxOffset
+	}
+	if config.MaxAge <= 0 {
+		config.MaxAge = defaultMaxAge
+	}
+
+	var source Source
+	switch config.Source {
+	case "", SourceNone:
+		config.Source = SourceNone
+	case SourceChrony:
+		if config.Command == "" {
+			config.Command = "chronyc"
+		}
+		source = &chrony{command: config.Command, run: runCommand}
+	case SourcePTP:
+		if config.Command == "" {
+			config.Command = "pmc"
+		}
+		source = &ptp{command: config.Command, run: runCommand}
+	default:
+		return nil, fmt.Errorf("unknown time sync source %q", config.Source)
+	}
+	return newMonitor(config, source, logger), nil
+}
+
+func newMonitor(config Config, source Source, logger *zap.Logger) *Monitor {
+	return &Monitor{
+		config: config,
+		source: source,
+		logger: logger,
+		now:    time.Now,
+		status: Status{Source: config.Source},
+	}
+}
+
+// Run queries the source every interval until ctx is done. It returns
+// immediately without a source.
+func (m *Monitor) Run(ctx context.Context) {
+	if m.source == nil {
+		return
+	}
+
+	m.Poll(ctx)
+	ticker := time.NewTicker(m.config.Interval)
+	defer ticker.Stop()
+	for {
+		select {
+		case <-ctx.Done():
+			return
+		case <-ticker.C:
+			m.Poll(ctx)
+		}
+	}
+}
+
+// Poll queries the source once and returns the updated status
+func (m *Monitor) Poll(ctx context.Context) Status {
+	if m.source == nil {
+		return m.Status()
+	}
+
+	ctx, cancel := context.WithTimeout(ctx, m.config.Interval)
+	defer cancel()
+	reading, err := m.source.Query(ctx)
+	now := m.now()
+
+	m.mutex.Lock()
+	defer m.mutex.Unlock()
+
+	previous := m.status.Sync
+	m.status.LastQuery = now
+	m.status.Error = ""
+	if err != nil {
+		m.status.Error = err.Error()
+		m.status.Sync = protocols.ClockSyncUnsynchronized
+	} else {
+		m.status.Reference = reading.Reference
+		m.status.Stratum = reading.Stratum
+		m.status.Offset = reading.Offset
+		m.status.LastSync = reading.LastSync
+		m.status.Sync = m.rate(reading, now)
+	}
+
+	if m.status.Sync == protocols.ClockSyncSynchronized {
+		m.lastGood = now
+	}
+	if m.status.Sync != previous {
+		m.logger.Info("Clock synchronization changed",
+			zap.String("source", m.config.Source),
+			zap.String("sync", string(m.status.Sync)),
+			zap.Duration("offset", m.status.Offset),
+			zap.String("error", m.status.Error),
+		)
+		m.transition(now, reason(err, m.status.Sync))
+	}
+	return m.status
+}
+
+// rate turns a reading into a ClockSync
+func (m *Monitor) rate(reading Reading, now time.Time) protocols.ClockSync {
+	if !reading.Synchronized || (!reading.LastSync.IsZero() && now.Sub(reading.LastSync) > m.config.MaxAge) {
+		return protocols.ClockSyncUnsynchronized
+	}
+	if reading.Offset > m.config.MaxOffset || reading.Offset < -m.config.MaxOffset {
+		return protocols.ClockSyncDegraded
+	}
+	return protocols.ClockSyncSynchronized
+}
+
+func reason(err error, sync protocols.ClockSync) string {
+	if err != nil {
+		return "source unavailable"
+	}
+	if sync == protocols.ClockSyncDegraded {
+		return "offset beyond tolerance"
+	}
+	return "not synchronized"
+}
+
+// transition closes the open window when the clock is synchronized again
+// and opens one when it no longer is
+func (m *Monitor) transition(now time.Time, reason string) {
+	if open := m.openWindow(); open != nil {
+		if open.Sync == m.status.Sync {
+			return
+		}
+		open.To = now
+	}
+	if m.status.Sync != protocols.ClockSyncSynchronized {
+		m.addWindow(Window{From: now, Sync: m.status.Sync, Reason: reason})
+	}
+}
+
+// OnClockEvent records wall clock steps, typically reported by
+// protocols.HybridClock. A step ends the current window with its offset,
+// or records the period since the clock was last found synchronized.
+func (m *Monitor) OnClockEvent(event protocols.ClockEvent) {
+	if event.Type != protocols.ClockEventStep {
+		return
+	}
+
+	m.mutex.Lock()
+	defer m.mutex.Unlock()
+
+	m.status.Steps++
+	m.status.LastStep = event.Time
+	// The step is measured on the new clock; timestamps before it were off
+	// by its size
+	end := event.Time.Add(-event.Offset)
+	if open := m.openWindow(); open != nil {
+		open.To = end
+		open.Offset = event.Offset
+		open.Reason += ", stepped"
+		if m.status.Sync != protocols.ClockSyncSynchronized {
+			m.addWindow(Window{From: event.Time, Sync: m.status.Sync, Reason: "not synchronized after step"})
+		}
+		return
+	}
+
+	from := m.lastGood
+	if from.IsZero() || !from.Before(end) {
+		from = end
+	}
+	m.addWindow(Window{From: from, To: end, Sync: protocols.ClockSyncUnsynchronized, Offset: event.Offset, Reason: "stepped"})
+}
+
+func (m *Monitor) openWindow() *Window {
+	if n := len(m.windows); n > 0 && m.windows[n-1].To.IsZero() {
+		return &m.windows[n-1]
+	}
+	return nil
+}
+
+func (m *Monitor) addWindow(window Window) {
+	if len(m.windows) == maxWindows {
+		m.windows = append(m.windows[:0], m.windows[1:]...)
+	}
+	m.windows = append(m.windows, window)
+}
+
+// Status returns the result of the last query
+func (m *Monitor) Status() Status {
+	m.mutex.RLock()
+	defer m.mutex.RUnlock()
+	return m.status
+}
+
+// Sync returns the current synchronization, empty without a source
+func (m *Monitor) Sync() protocols.ClockSync {
+	m.mutex.RLock()
+	defer m.mutex.RUnlock()
+	return m.status.Sync
+}
+
+// Windows returns the periods in which the clock was wrong, oldest first
+func (m *Monitor) Windows() []Window {
+	m.mutex.RLock()
+	defer m.mutex.RUnlock()
+	return append([]Window(nil), m.windows...)
+}
+
+// Check fails while the clock is not synchronized. It passes without a
+// source.
+func (m *Monitor) Check(ctx context.Context) error {
+	status := m.Status()
+	switch status.Sync {
+	case "", protocols.ClockSyncSynchronized:
+		return nil
+	case protocols.ClockSyncDegraded:
+		return fmt.Errorf("clock offset %s beyond %s", status.Offset, m.config.MaxOffset)
+	}
+	if status.Error != "" {
+		return fmt.Errorf("clock not synchronized: %s", status.Error)
+	}
+	return fmt.Errorf("clock not synchronized")
+}
+
+// Correct adds a correction applied to samples from now on
+func (m *Monitor) Correct(correction Correction) error {
+	if err := correction.Validate(); err != nil {
+		return err
+	}
+
+	m.mutex.Lock()
+	defer m.mutex.Unlock()
+	if len(m.corrections) == maxCorrections {
+		m.corrections = append(m.corrections[:0], m.corrections[1:]...)
+	}
+	m.corrections = append(m.corrections, correction)
+	m.logger.Info("Timestamp correction added",
+		zap.String("action", correction.Action),
+		zap.Time("from", correction.From),
+		zap.Time("to", correction.To),
+		zap.Duration("offset", correction.Offset),
+	)
+	return nil
+}
+
+// Corrections returns the corrections in the order they were added
+func (m *Monitor) Corrections() []Correction {
+	m.mutex.RLock()
+	defer m.mutex.RUnlock()
+	return append([]Correction(nil), m.corrections...)
+}
+
+// Apply corrects the samples in place. Of overlapping corrections the
+// most recent one applies, so a period is never re-stamped twice.
+func (m *Monitor) Apply(samples []protocols.TagSample) []protocols.TagSample {
+	m.mutex.RLock()
+	defer m.mutex.RUnlock()
+	if len(m.corrections) == 0 {
+		return samples
+	}
+
+	for i := range samples {
+		sample := &samples[i]
+		if sample.ClockSync == protocols.ClockSyncCorrected {
+			continue
+		}
+		for j := len(m.corrections) - 1; j >= 0; j-- {
+			correction := m.corrections[j]
+			if sample.Timestamp.Before(correction.From) || !sample.Timestamp.Before(correction.To) {
+				continue
+			}
+			if correction.Action == ActionRestamp {
+				sample.Timestamp = sample.Timestamp.Add(correction.Offset)
+				sample.ClockSync = protocols.ClockSyncCorrected
+			} else {
+				sample.ClockSync = protocols.ClockSyncUnsynchronized
+			}
+			break
+		}
+	}
+	return samples
+}
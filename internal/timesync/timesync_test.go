@@ -0,0 +1,137 @@
+package timesync
+
+import (
+	"context"
+	"errors"
+	"testing"
+	"time"
+
+	"github.com/stretchr/testify/assert"
+	"go.uber.org/zap"
+
+	"github.com/bifrost/go-gateway/internal/protocols"
+)
+
+const chronyTracking = "A29FC87B,162.159.200.123,3,1700000000.500000000,-0.000012000,0.000001,0.00002,-12.3,0.001,0.05,0.012,0.0009,64.5,Normal\n"
+
+const pmcTimeStatus = `sending: GET TIME_STATUS_NP
+	90e2ba.fffe.0b7f4c-0 seq 0 RESPONSE MANAGEMENT TIME_STATUS_NP
+		master_offset              -250
+		ingress_time               1700000000000000000
+		cumulativeScaledRateOffset +0.000000000
+		gmPresent                  true
+		gmIdentity                 001122.fffe.334455
+`
+
+// fakeSource returns a reading or an error
+type fakeSource struct {
+	reading Reading
+	err     error
+}
+
+func (s *fakeSource) Query(ctx context.Context) (Reading, error) {
+	return s.reading, s.err
+}
+
+func TestParseChronyTracking(t *testing.T) {
+	reading, err := parseChronyTracking([]byte(chronyTracking))
+	assert.NoError(t, err)
+	assert.True(t, reading.Synchronized)
+	assert.Equal(t, "162.159.200.123", reading.Reference)
+	assert.Equal(t, 3, reading.Stratum)
+	assert.Equal(t, -12*time.Microsecond, reading.Offset)
+	assert.Equal(t, time.Unix(1700000000, 500000000), reading.LastSync)
+
+	reading, err = parseChronyTracking([]byte("00000000,,0,0.000000000,0.000000000,0,0,0,0,0,0,0,0,Not synchronised\n"))
+	assert.NoError(t, err)
+	assert.False(t, reading.Synchronized)
+	assert.True(t, reading.LastSync.IsZero())
+
+	_, err = parseChronyTracking([]byte("506 Cannot talk to daemon"))
+	assert.Error(t, err)
+}
+
+func TestParsePTPTimeStatus(t *testing.T) {
+	reading, err := parsePTPTimeStatus([]byte(pmcTimeStatus))
+	assert.NoError(t, err)
+	assert.True(t, reading.Synchronized)
+	assert.Equal(t, "001122.fffe.334455", reading.Reference)
+	assert.Equal(t, -250*time.Nanosecond, reading.Offset)
+	assert.Equal(t, time.Unix(1700000000, 0), reading.LastSync)
+
+	_, err = parsePTPTimeStatus([]byte("sending: GET TIME_STATUS_NP\n"))
+	assert.Error(t, err)
+}
+
+func TestMonitor_Windows(t *testing.T) {
+	now := time.Unix(1700000000, 0)
+	source := &fakeSource{reading: Reading{Synchronized: true, Offset: time.Millisecond, LastSync: now}}
+	monitor := newMonitor(Config{Source: SourceChrony, MaxOffset: 100 * time.Millisecond, MaxAge: time.Hour}, source, zap.NewNop())
+	monitor.now = func() time.Time { return now }
+
+	assert.Equal(t, protocols.ClockSyncSynchronized, monitor.Poll(context.Background()).Sync)
+	assert.NoError(t, monitor.Check(context.Background()))
+	assert.Empty(t, monitor.Windows())
+
+	// Losing the source opens a window
+	now = now.Add(time.Minute)
+	source.err = errors.New("506 Cannot talk to daemon")
+	assert.Equal(t, protocols.ClockSyncUnsynchronized, monitor.Poll(context.Background()).Sync)
+	assert.Error(t, monitor.Check(context.Background()))
+
+	// A step closes it with the offset of its timestamps
+	monitor.OnClockEvent(protocols.ClockEvent{Type: protocols.ClockEventStep, Time: now.Add(time.Minute + 5*time.Second), Offset: 5 * time.Second})
+	source.err = nil
+	source.reading.LastSync = now.Add(time.Minute)
+	now = now.Add(2 * time.Minute)
+	monitor.Poll(context.Background())
+
+	windows := monitor.Windows()
+	if assert.Len(t, windows, 2) {
+		assert.Equal(t, Window{
+			From:   time.Unix(1700000060, 0),
+			To:     time.Unix(1700000120, 0),
+			Sync:   protocols.ClockSyncUnsynchronized,
+			Offset: 5 * time.Second,
+			Reason: "source unavailable, stepped",
+		}, windows[0])
+		assert.Equal(t, time.Unix(1700000125, 0), windows[1].From)
+		assert.Equal(t, now, windows[1].To)
+	}
+	assert.Equal(t, 1, monitor.Status().Steps)
+
+	// A large offset degrades, an old last sync unsynchronizes
+	source.reading.Offset = time.Second
+	assert.Equal(t, protocols.ClockSyncDegraded, monitor.Poll(context.Background()).Sync)
+	source.reading.LastSync = now.Add(-2 * time.Hour)
+	assert.Equal(t, protocols.ClockSyncUnsynchronized, monitor.Poll(context.Background()).Sync)
+}
+
+func TestMonitor_Apply(t *testing.T) {
+	monitor := newMonitor(Config{Source: SourceNone}, nil, zap.NewNop())
+	base := time.Unix(1700000000, 0)
+
+	assert.Error(t, monitor.Correct(Correction{From: base, To: base, Action: ActionFlag}))
+	assert.Error(t, monitor.Correct(Correction{From: base, To: base.Add(time.Minute), Action: ActionRestamp}))
+	assert.Error(t, monitor.Correct(Correction{From: base, To: base.Add(time.Minute), Action: "delete"}))
+
+	assert.NoError(t, monitor.Correct(Correction{From: base, To: base.Add(time.Hour), Action: ActionFlag}))
+	assert.NoError(t, monitor.Correct(Correction{From: base, To: base.Add(time.Minute), Action: ActionRestamp, Offset: 5 * time.Second}))
+
+	samples := monitor.Apply([]protocols.TagSample{
+		{Name: "before", Timestamp: base.Add(-time.Second)},
+		{Name: "restamped", Timestamp: base.Add(time.Second)},
+		{Name: "flagged", Timestamp: base.Add(2 * time.Minute)},
+	})
+	assert.Equal(t, base.Add(-time.Second), samples[0].Timestamp)
+	assert.Empty(t, samples[0].ClockSync)
+	assert.Equal(t, base.Add(6*time.Second), samples[1].Timestamp)
+	assert.Equal(t, protocols.ClockSyncCorrected, samples[1].ClockSync)
+	assert.Equal(t, protocols.ClockSyncUnsynchronized, samples[2].ClockSync)
+
+	// Corrected samples are not re-stamped again
+	monitor.Apply(samples)
+	assert.Equal(t, base.Add(6*time.Second), samples[1].Timestamp)
+	assert.Len(t, monitor.Corrections(), 2)
+	assert.NoError(t, monitor.Check(context.Background()))
+}
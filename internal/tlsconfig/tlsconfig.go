@@ -0,0 +1,304 @@
+// Package tlsconfig loads the TLS settings shared by the gateway's servers
+// and protocol clients
+package tlsconfig
+
+import (
+	"crypto/tls"
+	"crypto/x509"
+	"fmt"
+	"os"
+	"sync"
+	"time"
+
+	"go.uber.org/zap"
+)
+
+// TLS Configuration
+//
+// Every TLS endpoint of the gateway, the HTTP API as well as broker and
+// device connections, is configured with the same Config instead of its
+// own set of options. A Loader reads the certificate, key and CA bundle
+// and checks the files for changes at most once per reload interval when a
+// handshake needs them, so renewed certificates are picked up without a
+// restart. If a changed file cannot be loaded, e.g. because it is halfway
+// written, the previous certificates stay in use.
+
+const defaultReloadInterval = 30 * time.Second
+
+// Client authentication modes of a server
+const (
+	ClientAuthNone             = "none"
+	ClientAuthRequest          = "request"
+	ClientAuthRequire          = "require"
+	ClientAuthVerify           = "verify" // Verify a client certificate if one is sent
+	ClientAuthRequireAndVerify = "require_and_verify"
+)
+
+// Config configures TLS for a server or client
+type Config struct {
+	Enabled            bool          `yaml:"enabled"`
+	CertFile           string        `yaml:"cert_file"`   // PEM certificate chain, required for servers
+	KeyFile            string        `yaml:"key_file"`    // PEM private key of the certificate
+	CAFile             string        `yaml:"ca_file"`     // PEM bundle verifying peers, system roots if empty
+	ClientAuth         string        `yaml:"client_auth"` // Servers only, default none
+	ServerName         string        `yaml:"server_name"` // Clients only, overrides the dialed host
+	InsecureSkipVerify bool          `yaml:"insecure_skip_verify"`
+	MinVersion         string        `yaml:"min_version"`     // "1.2" (default) or "1.3"
+	ReloadInterval     time.Duration `yaml:"reload_interval"` // Default 30s
+}
+
+// FromMap reads a Config from a device configuration entry, e.g.
+// device.Config["tls"]. Durations are strings such as "1m".
+func FromMap(values map[string]interface{}) (Config, error) {
+	var config Config
+	stringFields := map[string]*string{
+		"cert_file":   &config.CertFile,
+		"key_file":    &config.KeyFile,
+		"ca_file":     &config.CAFile,
+		"client_auth": &config.ClientAuth,
+		"server_name": &config.ServerName,
+		"min_version": &config.MinVersion,
+	}
+	for key, field := range stringFields {
+		if value, exists := values[key]; exists {
+			str, ok := value.(string)
+			if !ok {
+				return config, fmt.Errorf("tls %s must be a string", key)
+			}
+			*field = str
+		}
+	}
+
+	boolFields := map[string]*bool{
+		"enabled":              &config.Enabled,
+		"insecure_skip_verify": &config.InsecureSkipVerify,
+	}
+	for key, field := range boolFields {
+		if value, exists := values[key]; exists {
+			b, ok := value.(bool)
+			if !ok {
+				return config, fmt.Errorf("tls %s must be a boolean", key)
+			}
+			*field = b
+		}
+	}
+
+	if value, exists := values["reload_interval"]; exists {
+		str, ok := value.(string)
+		if !ok {
+			return config, fmt.Errorf("tls reload_interval must be a duration string")
+		}
+		interval, err := time.ParseDuration(str)
+		if err != nil {
+			return config, fmt.Errorf("tls reload_interval: %w", err)
+		}
+		config.ReloadInterval = interval
+	}
+	return config, nil
+}
+
+// Loader provides TLS configurations whose certificates are reloaded when
+// the files change
+type Loader struct {
+	config     Config
+	clientAuth tls.ClientAuthType
+	minVersion uint16
+	logger     *zap.Logger
+
+	mutex   sync.Mutex
+	current *material
+	checked time.Time
+}
+
+// material is the content of the configured files
+type material struct {
+	certificate *tls.Certificate // nil without a certificate
+	roots       *x509.CertPool   // nil for the system roots
+	server      *tls.Config
+	stamps      map[string]fileStamp
+}
+
+// fileStamp identifies a version of a file
+type fileStamp struct {
+	modTime time.Time
+	size    int64
+}
+
+// NewLoader validates the configuration and loads the files
+func NewLoader(config Config, logger *zap.Logger) (*Loader, error) {
+	if config.ReloadInterval <= 0 {
+		config.ReloadInterval = defaultReloadInterval
+	}
+	if (config.CertFile == "") != (config.KeyFile == "") {
+		return nil, fmt.Errorf("tls cert_file and key_file must be set together")
+	}
+
+	l := &Loader{config: config, logger: logger}
+	switch config.MinVersion {
+	case "", "1.2":
+		l.minVersion = tls.VersionTLS12
+	case "1.3":
+		l.minVersion = tls.VersionTLS13
+	default:
+		return nil, fmt.Errorf("unsupported tls min_version %q", config.MinVersion)
+	}
+	switch config.ClientAuth {
+	case "", ClientAuthNone:
+		l.clientAuth = tls.NoClientCert
+	case ClientAuthRequest:
+		l.clientAuth = tls.RequestClientCert
+	case ClientAuthRequire:
+		l.clientAuth = tls.RequireAnyClientCert
+	case ClientAuthVerify:
+		l.clientAuth = tls.VerifyClientCertIfGiven
+	case ClientAuthRequireAndVerify:
+		l.clientAuth = tls.RequireAndVerifyClientCert
+	default:
+		return nil, fmt.Errorf("unsupported tls client_auth %q", config.ClientAuth)
+	}
+	if (l.clientAuth == tls.VerifyClientCertIfGiven || l.clientAuth == tls.RequireAndVerifyClientCert) && config.CAFile == "" {
+		return nil, fmt.Errorf("tls client_auth %s requires a ca_file", config.ClientAuth)
+	}
+
+	if err := l.Reload(); err != nil {
+		return nil, err
+	}
+	return l, nil
+}
+
+// Reload reads the files now. On error the previous files stay in use.
+func (l *Loader) Reload() error {
+	loaded, err := l.load()
+	if err != nil {
+		return err
+	}
+
+	l.mutex.Lock()
+	defer l.mutex.Unlock()
+	l.current = loaded
+	l.checked = time.Now()
+	return nil
+}
+
+// ServerConfig returns a configuration for a TLS listener. It fails
+// without a certificate.
+func (l *Loader) ServerConfig() (*tls.Config, error) {
+	if l.config.CertFile == "" {
+		return nil, fmt.Errorf("tls server requires cert_file and key_file")
+	}
+	return &tls.Config{
+		MinVersion: l.minVersion,
+		GetConfigForClient: func(*tls.ClientHelloInfo) (*tls.Config, error) {
+			return l.files().server, nil
+		},
+	}, nil
+}
+
+// ClientConfig returns a configuration for a TLS connection. The client
+// certificate is reloaded when it changes, the CA bundle is the one
+// current when ClientConfig is called.
+func (l *Loader) ClientConfig() *tls.Config {
+	return &tls.Config{
+		MinVersion:         l.minVersion,
+		RootCAs:            l.files().roots,
+		ServerName:         l.config.ServerName,
+		InsecureSkipVerify: l.config.InsecureSkipVerify,
+		GetClientCertificate: func(*tls.CertificateRequestInfo) (*tls.Certificate, error) {
+			if certificate := l.files().certificate; certificate != nil {
+				return certificate, nil
+			}
+			// No certificate is sent; the server decides whether that is
+			// acceptable
+			return &tls.Certificate{}, nil
+		},
+	}
+}
+
+// files returns the loaded files, reloading them if they changed since
+// the last check
+func (l *Loader) files() *material {
+	l.mutex.Lock()
+	defer l.mutex.Unlock()
+
+	if time.Since(l.checked) < l.config.ReloadInterval {
+		return l.current
+	}
+	l.checked = time.Now()
+	if !l.changed() {
+		return l.current
+	}
+
+	loaded, err := l.load()
+	if err != nil {
+		l.logger.Warn("Failed to reload TLS files, keeping the previous ones", zap.Error(err))
+		return l.current
+	}
+	l.logger.Info("Reloaded TLS files",
+		zap.String("cert_file", l.config.CertFile),
+		zap.String("ca_file", l.config.CAFile),
+	)
+	l.current = loaded
+	return l.current
+}
+
+// changed reports whether a file differs from the loaded version
+func (l *Loader) changed() bool {
+	for file, loaded := range l.current.stamps {
+		stamp, err := stat(file)
+		if err != nil || stamp != loaded {
+			return true
+		}
+	}
+	return false
+}
+
+func (l *Loader) load() (*material, error) {
+	loaded := &material{stamps: make(map[string]fileStamp)}
+	for _, file := range []string{l.config.CertFile, l.config.KeyFile, l.config.CAFile} {
+		if file == "" {
+			continue
+		}
+		stamp, err := stat(file)
+		if err != nil {
+			return nil, fmt.Errorf("tls: %w", err)
+		}
+		loaded.stamps[file] = stamp
+	}
+
+	if l.config.CertFile != "" {
+		certificate, err := tls.LoadX509KeyPair(l.config.CertFile, l.config.KeyFile)
+		if err != nil {
+			return nil, fmt.Errorf("tls: loading %s: %w", l.config.CertFile, err)
+		}
+		loaded.certificate = &certificate
+	}
+
+	if l.config.CAFile != "" {
+		pem, err := os.ReadFile(l.config.CAFile)
+		if err != nil {
+			return nil, fmt.Errorf("tls: %w", err)
+		}
+		loaded.roots = x509.NewCertPool()
+		if !loaded.roots.AppendCertsFromPEM(pem) {
+			return nil, fmt.Errorf("tls: no certificates in %s", l.config.CAFile)
+		}
+	}
+
+	loaded.server = &tls.Config{
+		MinVersion: l.minVersion,
+		ClientAuth: l.clientAuth,
+		ClientCAs:  loaded.roots,
+	}
+	if loaded.certificate != nil {
+		loaded.server.Certificates = []tls.Certificate{*loaded.certificate}
+	}
+	return loaded, nil
+}
+
+func stat(file string) (fileStamp, error) {
+	info, err := os.Stat(file)
+	if err != nil {
+		return fileStamp{}, err
+	}
+	return fileStamp{modTime: info.ModTime(), size: info.Size()}, nil
+}
@@ -0,0 +1,188 @@
+package tlsconfig
+
+import (
+	"crypto/ecdsa"
+	"crypto/elliptic"
+	"crypto/rand"
+	"crypto/tls"
+	"crypto/x509"
+	"crypto/x509/pkix"
+	"encoding/pem"
+	"math/big"
+	"os"
+	"path/filepath"
+	"testing"
+	"time"
+
+	"github.com/stretchr/testify/assert"
+	"go.uber.org/zap"
+)
+
+// writeCertificate writes a self-signed certificate for localhost, which
+// also serves as its own CA bundle, and returns the file names
+func writeCertificate(t *testing.T, dir string, serial int64) (certFile, keyFile string) {
+	key, err := ecdsa.GenerateKey(elliptic.P256(), rand.Reader)
+	assert.NoError(t, err)
+	template := &x509.Certificate{
+		SerialNumber:          big.NewInt(serial),
+		Subject:               pkix.Name{CommonName: "localhost"},
+		DNSNames:              []string{"localhost"},
+		NotBefore:             time.Now().Add(-time.Hour),
+		NotAfter:              time.Now().Add(time.Hour),
+		KeyUsage:              x509.KeyUsageDigitalSignature | x509.KeyUsageCertSign,
+		ExtKeyUsage:           []x509.ExtKeyUsage{x509.ExtKeyUsageServerAuth, x509.ExtKeyUsageClientAuth},
+		IsCA:                  true,
+		BasicConstraintsValid: true,
+	}
+	der, err := x509.CreateCertificate(rand.Reader, template, template, &key.PublicKey, key)
+	assert.NoError(t, err)
+	keyDER, err := x509.MarshalECPrivateKey(key)
+	assert.NoError(t, err)
+
+	certFile, keyFile = filepath.Join(dir, "cert.pem"), filepath.Join(dir, "key.pem")
+	assert.NoError(t, os.WriteFile(certFile, pem.EncodeToMemory(&pem.Block{Type: "CERTIFICATE", Bytes: der}), 0o644))
+	assert.NoError(t, os.WriteFile(keyFile, pem.EncodeToMemory(&pem.Block{Type: "EC PRIVATE KEY", Bytes: keyDER}), 0o600))
+
+	// Make the change visible to the modification time check
+	modTime := time.Now().Add(time.Duration(serial) * time.Second)
+	assert.NoError(t, os.Chtimes(certFile, modTime, modTime))
+	assert.NoError(t, os.Chtimes(keyFile, modTime, modTime))
+	return certFile, keyFile
+}
+
+// handshake connects client to server and returns the serial number of
+// the server certificate
+func handshake(t *testing.T, server, client *tls.Config) (int64, error) {
+	listener, err := tls.Listen("tcp", "127.0.0.1:0", server)
+	assert.NoError(t, err)
+	defer listener.Close()
+
+	serverErr := make(chan error, 1)
+	go func() {
+		conn, err := listener.Accept()
+		if err != nil {
+			serverErr <- err
+			return
+		}
+		defer conn.Close()
+		serverErr <- conn.(*tls.Conn).Handshake()
+	}()
+
+	conn, err := tls.Dial("tcp", listener.Addr().String(), client)
+	if err != nil {
+		return 0, err
+	}
+	defer conn.Close()
+	if err := <-serverErr; err != nil {
+		return 0, err
+	}
+	return conn.ConnectionState().PeerCertificates[0].SerialNumber.Int64(), nil
+}
+
+func TestLoader_Reload(t *testing.T) {
+	dir := t.TempDir()
+	certFile, keyFile := writeCertificate(t, dir, 1)
+
+	loader, err := NewLoader(Config{CertFile: certFile, KeyFile: keyFile, ReloadInterval: time.Nanosecond}, zap.NewNop())
+	assert.NoError(t, err)
+	server, err := loader.ServerConfig()
+	assert.NoError(t, err)
+
+	serial, err := handshake(t, server, &tls.Config{InsecureSkipVerify: true})
+	assert.NoError(t, err)
+	assert.Equal(t, int64(1), serial)
+
+	// A renewed certificate is used for the next handshake
+	writeCertificate(t, dir, 2)
+	serial, err = handshake(t, server, &tls.Config{InsecureSkipVerify: true})
+	assert.NoError(t, err)
+	assert.Equal(t, int64(2), serial)
+
+	// A broken file keeps the previous certificate
+	assert.NoError(t, os.WriteFile(certFile, []byte("partial"), 0o644))
+	serial, err = handshake(t, server, &tls.Config{InsecureSkipVerify: true})
+	assert.NoError(t, err)
+	assert.Equal(t, int64(2), serial)
+}
+
+func TestLoader_ClientAuth(t *testing.T) {
+	certFile, keyFile := writeCertificate(t, t.TempDir(), 1)
+	loader, err := NewLoader(Config{
+		CertFile:   certFile,
+		KeyFile:    keyFile,
+		CAFile:     certFile,
+		ClientAuth: ClientAuthRequireAndVerify,
+	}, zap.NewNop())
+	assert.NoError(t, err)
+	server, err := loader.ServerConfig()
+	assert.NoError(t, err)
+
+	// The client verifies the server against the CA bundle and presents
+	// its certificate
+	client, err := NewLoader(Config{Enabled: true, CertFile: certFile, KeyFile: keyFile, CAFile: certFile, ServerName: "localhost"}, zap.NewNop())
+	assert.NoError(t, err)
+	_, err = handshake(t, server, client.ClientConfig())
+	assert.NoError(t, err)
+
+	// A client without a certificate is rejected
+	anonymous, err := NewLoader(Config{Enabled: true, CAFile: certFile, ServerName: "localhost"}, zap.NewNop())
+	assert.NoError(t, err)
+	_, err = handshake(t, server, anonymous.ClientConfig())
+	assert.Error(t, err)
+
+	// A server not signed by the CA bundle is rejected
+	unknown, err := NewLoader(Config{Enabled: true, CAFile: certFile, ServerName: "localhost"}, zap.NewNop())
+	assert.NoError(t, err)
+	otherCert, otherKey := writeCertificate(t, t.TempDir(), 3)
+	other, err := NewLoader(Config{CertFile: otherCert, KeyFile: otherKey}, zap.NewNop())
+	assert.NoError(t, err)
+	otherServer, err := other.ServerConfig()
+	assert.NoError(t, err)
+	_, err = handshake(t, otherServer, unknown.ClientConfig())
+	assert.Error(t, err)
+}
+
+func TestNewLoader_Invalid(t *testing.T) {
+	certFile, keyFile := writeCertificate(t, t.TempDir(), 1)
+	for _, config := range []Config{
+		{CertFile: certFile},
+		{CertFile: certFile, KeyFile: keyFile, MinVersion: "1.0"},
+		{CertFile: certFile, KeyFile: keyFile, ClientAuth: "always"},
+		{CertFile: certFile, KeyFile: keyFile, ClientAuth: ClientAuthVerify},
+		{CertFile: certFile, KeyFile: certFile},
+		{CAFile: keyFile},
+		{CAFile: certFile + ".missing"},
+	} {
+		_, err := NewLoader(config, zap.NewNop())
+		assert.Error(t, err, config)
+	}
+
+	// A client without a certificate cannot serve
+	loader, err := NewLoader(Config{}, zap.NewNop())
+	assert.NoError(t, err)
+	_, err = loader.ServerConfig()
+	assert.Error(t, err)
+}
+
+func TestFromMap(t *testing.T) {
+	config, err := FromMap(map[string]interface{}{
+		"enabled":         true,
+		"ca_file":         "/etc/bifrost/ca.pem",
+		"server_name":     "broker.plant.local",
+		"reload_interval": "1m",
+	})
+	assert.NoError(t, err)
+	assert.Equal(t, Config{
+		Enabled:        true,
+		CAFile:         "/etc/bifrost/ca.pem",
+		ServerName:     "broker.plant.local",
+		ReloadInterval: time.Minute,
+	}, config)
+
+	_, err = FromMap(map[string]interface{}{"enabled": "yes"})
+	assert.Error(t, err)
+	_, err = FromMap(map[string]interface{}{"cert_file": 1})
+	assert.Error(t, err)
+	_, err = FromMap(map[string]interface{}{"reload_interval": "often"})
+	assert.Error(t, err)
+}